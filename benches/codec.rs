@@ -0,0 +1,314 @@
+//! Encode and decode throughput across bitrates, complexities, frame sizes,
+//! channel counts and sample formats, e.g. to compare `fast-math`,
+//! `presume-avx2` or `lto` builds against plain ones, or a vendored Opus
+//! update against the last one
+//!
+//! ```text
+//! cargo bench --bench codec
+//! cargo bench --bench codec -- stereo/64k
+//! RUSTFLAGS="-Clinker-plugin-lto -Clinker=clang -Clink-arg=-fuse-ld=lld" \
+//!     cargo bench --bench codec --features lto
+//! ```
+//!
+//! An argument runs only the cases whose name contains it. Every case codes
+//! the same seeded noise at 48 kHz; times are the best of several rounds,
+//! reported as multiples of realtime (audio duration over CPU time). With
+//! `dred` and the weights in `target/model`, a DRED encode case runs too.
+
+use opus_head_sys::*;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+#[path = "../tests/util/mod.rs"]
+mod util;
+
+const SAMPLE_RATE: i32 = 48000;
+const SECONDS: usize = 2;
+const ROUNDS: usize = 3;
+const MAX_PACKET: usize = 1275 * 3;
+
+const BITRATES: [i32; 4] = [16000, 32000, 64000, 128000];
+const COMPLEXITIES: [i32; 3] = [0, 5, 10];
+const FRAME_MS: [usize; 3] = [10, 20, 60];
+const CHANNELS: [usize; 2] = [1, 2];
+
+/// Input and output sample format
+#[derive(Clone, Copy)]
+enum Format {
+    I16,
+    #[cfg(not(feature = "no-float-api"))]
+    F32,
+}
+
+const FORMATS: &[Format] = &[
+    Format::I16,
+    #[cfg(not(feature = "no-float-api"))]
+    Format::F32,
+];
+
+impl Format {
+    fn name(self) -> &'static str {
+        match self {
+            Format::I16 => "i16",
+            #[cfg(not(feature = "no-float-api"))]
+            Format::F32 => "f32",
+        }
+    }
+}
+
+struct Case {
+    format: Format,
+    channels: usize,
+    bitrate: i32,
+    complexity: i32,
+    frame_ms: usize,
+}
+
+impl Case {
+    fn name(&self) -> String {
+        format!(
+            "{}/{}/{}k/c{}/{}ms",
+            self.format.name(),
+            if self.channels == 1 { "mono" } else { "stereo" },
+            self.bitrate / 1000,
+            self.complexity,
+            self.frame_ms
+        )
+    }
+
+    fn frame_size(&self) -> usize {
+        SAMPLE_RATE as usize * self.frame_ms / 1000
+    }
+
+    fn frames(&self) -> usize {
+        SECONDS * 1000 / self.frame_ms
+    }
+}
+
+/// Encoded packets of one case
+struct Packets {
+    data: Vec<[u8; MAX_PACKET]>,
+    lengths: Vec<i32>,
+}
+
+fn best_of(mut round: impl FnMut() -> Duration) -> Duration {
+    (0..ROUNDS).map(|_| round()).min().unwrap_or_default()
+}
+
+/// How many times faster than realtime `elapsed` for [`SECONDS`] of audio is
+fn realtime(elapsed: Duration) -> f64 {
+    SECONDS as f64 / elapsed.as_secs_f64()
+}
+
+/// Input samples in the case's format
+enum Pcm {
+    I16(Vec<i16>),
+    #[cfg(not(feature = "no-float-api"))]
+    F32(Vec<f32>),
+}
+
+impl Pcm {
+    /// [`SECONDS`] of seeded noise
+    fn noise(case: &Case) -> Self {
+        let samples = SECONDS * SAMPLE_RATE as usize * case.channels;
+        match case.format {
+            Format::I16 => Pcm::I16(test_util::noise(1, samples)),
+            #[cfg(not(feature = "no-float-api"))]
+            Format::F32 => Pcm::F32(test_util::noise(1, samples)),
+        }
+    }
+
+    /// One frame of silence, to decode into
+    fn frame(case: &Case) -> Self {
+        let samples = case.frame_size() * case.channels;
+        match case.format {
+            Format::I16 => Pcm::I16(vec![0; samples]),
+            #[cfg(not(feature = "no-float-api"))]
+            Format::F32 => Pcm::F32(vec![0.0; samples]),
+        }
+    }
+}
+
+/// Encode all frames of `pcm`, resetting the encoder first
+unsafe fn encode(
+    encoder: *mut OpusEncoder,
+    case: &Case,
+    pcm: &Pcm,
+    packets: &mut Packets,
+) -> Duration {
+    opus_encoder_ctl(encoder, OPUS_RESET_STATE as i32);
+    let samples = case.frame_size() * case.channels;
+    let start = Instant::now();
+    for (frame, (packet, len)) in packets
+        .data
+        .iter_mut()
+        .zip(&mut packets.lengths)
+        .enumerate()
+    {
+        *len = match pcm {
+            Pcm::I16(pcm) => opus_encode(
+                encoder,
+                black_box(pcm[frame * samples..].as_ptr()),
+                case.frame_size() as i32,
+                packet.as_mut_ptr(),
+                MAX_PACKET as i32,
+            ),
+            #[cfg(not(feature = "no-float-api"))]
+            Pcm::F32(pcm) => opus_encode_float(
+                encoder,
+                black_box(pcm[frame * samples..].as_ptr()),
+                case.frame_size() as i32,
+                packet.as_mut_ptr(),
+                MAX_PACKET as i32,
+            ),
+        };
+        assert!(*len > 0, "Encoding {} failed: {}", case.name(), len);
+    }
+    start.elapsed()
+}
+
+/// Decode all of `packets`, resetting the decoder first
+unsafe fn decode(decoder: *mut OpusDecoder, case: &Case, packets: &Packets) -> Duration {
+    let mut output = Pcm::frame(case);
+    opus_decoder_ctl(decoder, OPUS_RESET_STATE as i32);
+    let start = Instant::now();
+    for (packet, &len) in packets.data.iter().zip(&packets.lengths) {
+        let decoded = match &mut output {
+            Pcm::I16(output) => opus_decode(
+                decoder,
+                black_box(packet.as_ptr()),
+                len,
+                output.as_mut_ptr(),
+                case.frame_size() as i32,
+                0,
+            ),
+            #[cfg(not(feature = "no-float-api"))]
+            Pcm::F32(output) => opus_decode_float(
+                decoder,
+                black_box(packet.as_ptr()),
+                len,
+                output.as_mut_ptr(),
+                case.frame_size() as i32,
+                0,
+            ),
+        };
+        assert_eq!(decoded, case.frame_size() as i32);
+        black_box(&output);
+    }
+    start.elapsed()
+}
+
+unsafe fn run(case: &Case) {
+    let pcm = Pcm::noise(case);
+
+    let mut error: i32 = 0;
+    let encoder = opus_encoder_create(
+        SAMPLE_RATE,
+        case.channels as i32,
+        OPUS_APPLICATION_AUDIO as i32,
+        &mut error,
+    );
+    assert_eq!(error, OPUS_OK as i32, "Failed to create encoder");
+    opus_encoder_ctl(encoder, OPUS_SET_BITRATE_REQUEST as i32, case.bitrate);
+    opus_encoder_ctl(encoder, OPUS_SET_COMPLEXITY_REQUEST as i32, case.complexity);
+    let decoder = opus_decoder_create(SAMPLE_RATE, case.channels as i32, &mut error);
+    assert_eq!(error, OPUS_OK as i32, "Failed to create decoder");
+
+    let mut packets = Packets {
+        data: vec![[0u8; MAX_PACKET]; case.frames()],
+        lengths: vec![0; case.frames()],
+    };
+    let encoded = best_of(|| encode(encoder, case, &pcm, &mut packets));
+    let decoded = best_of(|| decode(decoder, case, &packets));
+    println!(
+        "{:<28} encode {:>7.1}x  decode {:>7.1}x realtime",
+        case.name(),
+        realtime(encoded),
+        realtime(decoded)
+    );
+
+    opus_encoder_destroy(encoder);
+    opus_decoder_destroy(decoder);
+}
+
+/// Mono 20 ms speech-rate encode with 100 ms of DRED, the DNN inference
+/// `presume-dotprod` and `fast-math` are about
+#[cfg(feature = "dred")]
+unsafe fn run_dred(filter: &str) {
+    let case = Case {
+        format: Format::I16,
+        channels: 1,
+        bitrate: 32000,
+        complexity: 10,
+        frame_ms: 20,
+    };
+    let name = format!("dred/{}", case.name());
+    if !name.contains(filter) {
+        return;
+    }
+    let Some(weights) = util::load_dnn_weights().filter(|_| build_info::DRED_ENABLED) else {
+        println!("{:<28} skipped: no DNN weights in target/model", name);
+        return;
+    };
+
+    let pcm = Pcm::noise(&case);
+    let mut error: i32 = 0;
+    let encoder = opus_encoder_create(SAMPLE_RATE, 1, OPUS_APPLICATION_VOIP as i32, &mut error);
+    assert_eq!(error, OPUS_OK as i32, "Failed to create encoder");
+    opus_encoder_ctl(encoder, OPUS_SET_BITRATE_REQUEST as i32, case.bitrate);
+    opus_encoder_ctl(encoder, OPUS_SET_COMPLEXITY_REQUEST as i32, case.complexity);
+    opus_encoder_ctl(encoder, OPUS_SET_PACKET_LOSS_PERC_REQUEST as i32, 25);
+    let ret = opus_encoder_ctl(
+        encoder,
+        OPUS_SET_DNN_BLOB_REQUEST as i32,
+        weights.as_ptr() as *const std::ffi::c_void,
+        weights.len() as i32,
+    );
+    assert_eq!(ret, OPUS_OK as i32, "Failed to load DNN weights");
+    let ret = opus_encoder_ctl(encoder, OPUS_SET_DRED_DURATION_REQUEST as i32, 100);
+    assert_eq!(ret, OPUS_OK as i32, "Failed to enable DRED");
+
+    let mut packets = Packets {
+        data: vec![[0u8; MAX_PACKET]; case.frames()],
+        lengths: vec![0; case.frames()],
+    };
+    let encoded = best_of(|| encode(encoder, &case, &pcm, &mut packets));
+    println!("{:<28} encode {:>7.1}x realtime", name, realtime(encoded));
+
+    opus_encoder_destroy(encoder);
+}
+
+fn main() {
+    // `cargo bench` passes `--bench`
+    let filter = std::env::args()
+        .skip(1)
+        .find(|arg| !arg.starts_with('-'))
+        .unwrap_or_default();
+    println!("build: {}", build_info());
+
+    for &format in FORMATS {
+        for channels in CHANNELS {
+            for bitrate in BITRATES {
+                for complexity in COMPLEXITIES {
+                    for frame_ms in FRAME_MS {
+                        let case = Case {
+                            format,
+                            channels,
+                            bitrate,
+                            complexity,
+                            frame_ms,
+                        };
+                        if case.name().contains(&filter) {
+                            unsafe { run(&case) };
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[cfg(feature = "dred")]
+    unsafe {
+        run_dred(&filter)
+    };
+}
@@ -2,8 +2,52 @@
 //!
 //! Compiles the vendored Opus library using CMake with cross-platform support.
 
+#[path = "build/android.rs"]
+mod android;
+#[path = "build/apple.rs"]
+mod apple;
+#[path = "build/archive.rs"]
+mod archive;
+#[path = "build/cross.rs"]
+mod cross;
+#[path = "build/crt.rs"]
+mod crt;
+#[path = "build/env_vars.rs"]
+mod env_vars;
+#[path = "build/generator.rs"]
+mod generator;
+#[path = "build/install.rs"]
+mod install;
+#[path = "build/jobs.rs"]
+mod jobs;
+#[path = "build/lto.rs"]
+mod lto;
+#[path = "build/patch.rs"]
+mod patch;
+#[path = "build/platform.rs"]
+mod platform;
+#[path = "build/prefix.rs"]
+mod prefix;
+#[path = "build/preflight.rs"]
+mod preflight;
+#[path = "build/profile.rs"]
+mod profile;
+#[path = "build/rtcd.rs"]
+mod rtcd;
+#[path = "build/sanitizer.rs"]
+mod sanitizer;
+#[path = "build/source.rs"]
+mod source;
+#[path = "build/vendor.rs"]
+mod vendor;
+#[path = "build/version.rs"]
+mod version;
+
 use cmake::Config;
-use std::{env, path::PathBuf};
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
 
 macro_rules! warn {
     ($($arg:tt)*) => {
@@ -11,16 +55,48 @@ macro_rules! warn {
     };
 }
 
+/// Every environment variable the build script reads; see `env_vars`
+static ENV_VARS: env_vars::EnvVars = env_vars::EnvVars::new();
+
 fn main() {
-    if let Err(e) = build_opus() {
+    let result = build_opus();
+
+    // Declared on every path, including the WASM early return and failures
+    for name in ENV_VARS.rerun_vars() {
+        println!("cargo:rerun-if-env-changed={}", name);
+    }
+
+    if let Err(e) = result {
         panic!("Failed to build Opus: {}", e);
     }
 }
 
+/// Read an environment variable, watching it for changes
+fn var(name: &str) -> Option<String> {
+    ENV_VARS.var(name)
+}
+
+/// Read a variable Cargo always sets for build scripts
+fn cargo_var(name: &str) -> Result<String, String> {
+    var(name).ok_or_else(|| format!("{} is not set", name))
+}
+
 fn build_opus() -> Result<(), Box<dyn std::error::Error>> {
-    let target_os = env::var("CARGO_CFG_TARGET_OS").unwrap_or_default();
-    let target_arch = env::var("CARGO_CFG_TARGET_ARCH").unwrap_or_default();
-    let target_triple = env::var("TARGET")?;
+    // Declared even when nothing is built, so cfg checks see them
+    for name in EXPORTED_CFGS {
+        println!("cargo:rustc-check-cfg=cfg({})", name);
+    }
+
+    let target_os = var("CARGO_CFG_TARGET_OS").unwrap_or_default();
+    let target_arch = var("CARGO_CFG_TARGET_ARCH").unwrap_or_default();
+    let target_env = var("CARGO_CFG_TARGET_ENV").unwrap_or_default();
+    let target_triple = cargo_var("TARGET")?;
+
+    // lib.rs includes these on every target, WASM too. With
+    // generate-bindings they are replaced once the headers are installed.
+    if prefix_symbols_enabled() || generate_bindings_enabled() {
+        copy_bindings()?;
+    }
 
     // Skip build for WASM targets
     if target_arch.starts_with("wasm") {
@@ -31,33 +107,89 @@ fn build_opus() -> Result<(), Box<dyn std::error::Error>> {
         return Ok(());
     }
 
-    warn!("Building Opus for {} ({})", target_triple, target_arch);
+    // A library built elsewhere, e.g. with the firmware's own toolchain for
+    // a bare-metal target
+    if let Some(lib_dir) = var("OPUS_LIB_DIR") {
+        if prefix_symbols_enabled() {
+            return Err(
+                "prefix-symbols renames the library built here, it can't be used with OPUS_LIB_DIR"
+                    .into(),
+            );
+        }
+        warn!("Linking the prebuilt Opus in {}", lib_dir);
+        println!("cargo:rustc-link-search=native={}", lib_dir);
+        println!("cargo:rustc-link-lib=static=opus");
+        println!("cargo:lib={}", lib_dir);
+        println!("cargo:static=1");
+        describe_prebuilt(Path::new(&lib_dir), &target_env, &target_triple);
+        return Ok(());
+    }
+    if target_os == "none" {
+        warn!(
+            "No Opus build for bare-metal {}, set OPUS_LIB_DIR to a libopus.a built for it",
+            target_triple
+        );
+        return Ok(());
+    }
 
-    let manifest_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR")?);
-    let opus_dir = manifest_dir.join("vendored").join("opus");
+    warn!("Building Opus for {} ({})", target_triple, target_arch);
+    preflight(&target_triple, &target_os, &target_env)?;
 
-    if !opus_dir.is_dir() {
-        return Err(format!(
-            "Missing Opus source directory: {}. Run 'python vendor_opus.py' to download.",
-            opus_dir.display()
-        )
-        .into());
-    }
+    let manifest_dir = PathBuf::from(cargo_var("CARGO_MANIFEST_DIR")?);
+    let opus_dir = match source::source_dir(
+        var(source::SOURCE_DIR_ENV).as_deref(),
+        &manifest_dir.join("vendored").join("opus"),
+        Path::is_file,
+    )? {
+        source::Source::Vendored(dir) if dir.is_dir() => {
+            println!("cargo:rerun-if-changed=vendored/opus");
+            println!("cargo:rerun-if-changed=vendored/OPUS_VERSION");
+            let read = |path: PathBuf| std::fs::read_to_string(path).ok();
+            vendor::verify_vendored(
+                &vendor::PINNED,
+                read(manifest_dir.join("vendored").join("OPUS_VERSION")).as_deref(),
+                read(dir.join(version::PACKAGE_VERSION_FILE)).as_deref(),
+            )?;
+            dir
+        }
+        source::Source::Vendored(dir) if var("CARGO_FEATURE_AUTO_VENDOR").is_some() => {
+            warn!("{} is missing, downloading Opus", dir.display());
+            println!("cargo:rerun-if-changed=patches");
+            auto_vendor(&manifest_dir)?
+        }
+        source::Source::Vendored(dir) => {
+            return Err(format!(
+                "Missing Opus source directory: {}. Run 'cargo vendor-opus' to download it, or enable the auto-vendor feature.",
+                dir.display()
+            )
+            .into());
+        }
+        source::Source::External(dir) => {
+            warn!("Using Opus sources from {}", dir.display());
+            println!("cargo:rerun-if-changed={}", dir.display());
+            dir
+        }
+    };
 
-    println!("cargo:rerun-if-changed=vendored/opus");
     println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=build");
     println!("cargo:rerun-if-changed=Cargo.toml");
 
-    let mut config = Config::new(&opus_dir);
+    println!(
+        "cargo:rustc-env=OPUS_VENDORED_VERSION={}",
+        opus_version(&opus_dir)
+    );
 
-    // Use RelWithDebInfo for debug builds (gives debug symbols but links release CRT)
-    // On Windows, pure Debug builds use MSVCRTD which conflicts with Rust's CRT
-    let profile = if env::var("PROFILE").unwrap_or_default() == "release" {
-        "Release"
+    let source_dir = if windows_arm64_rtcd_patch(&target_os, &target_arch) {
+        patched_windows_arm64_source(&manifest_dir, &opus_dir)?
     } else {
-        "RelWithDebInfo"
+        opus_dir
     };
 
+    let mut config = Config::new(&source_dir);
+
+    let profile = cmake_profile(&target_os, &target_env)?;
+
     config
         .profile(profile)
         .define("OPUS_BUILD_SHARED_LIBRARY", "OFF")
@@ -66,76 +198,936 @@ fn build_opus() -> Result<(), Box<dyn std::error::Error>> {
         .define("OPUS_INSTALL_PKG_CONFIG_MODULE", "OFF")
         .define("OPUS_INSTALL_CMAKE_CONFIG_MODULE", "OFF");
 
+    // Toolchain from CMAKE_TOOLCHAIN_FILE, TARGET_CC and friends
+    let toolchain_file = configure_toolchain(&mut config, &target_triple, &target_env)?;
+
+    // User flags go first: flags added below are ones the build relies on
+    // (SDK sysroot, NEON/DOTPROD and WINAPI_FAMILY defines) and must win
+    configure_env_flags(&mut config, &target_triple)?;
+    let asan = configure_sanitizers(&mut config, &target_os, &target_env)?;
+    if lto_enabled() {
+        configure_lto(&mut config);
+    }
+
     // Platform-specific configuration
-    configure_for_platform(&mut config, &target_os, &target_arch, &target_triple);
+    configure_for_platform(
+        &mut config,
+        &target_os,
+        &target_arch,
+        &target_triple,
+        toolchain_file,
+    )?;
+
+    configure_page_size(&mut config, &target_os, &target_arch);
+
+    let platform = platform::platform(&target_os);
+    for (name, value) in &platform.defines {
+        config.define(name, value);
+    }
+
+    if target_env == "musl" {
+        configure_musl(&mut config);
+    }
+
+    if target_os == "windows" && target_env == "msvc" {
+        configure_msvc_crt(&mut config)?;
+    }
+
+    // CPU feature detection: either presume the target's ISA outright or,
+    // for x86_64 and ARM, raise the RTCD baseline from the enabled target
+    // features
+    let mut presumed = Vec::new();
+    if rtcd_disabled() {
+        presumed = configure_no_rtcd(&mut config, &target_arch);
+    } else if target_arch == "x86_64" {
+        presumed = configure_x86_features(&mut config);
+    }
 
-    // CPU feature detection for x86_64
-    if target_arch == "x86_64" {
-        configure_x86_features(&mut config);
+    if target_arch == "arm" || target_arch == "aarch64" {
+        for name in configure_arm_features(&mut config) {
+            if !presumed.contains(&name) {
+                presumed.push(name);
+            }
+        }
     }
 
     // Configure Cargo feature flags
-    configure_features(&mut config, &target_os, &target_arch);
+    let dnn = configure_features(&mut config, &target_os, &target_arch);
+
+    configure_generator(&mut config, &target_env)?;
+    configure_parallel();
+    configure_jobs()?;
+    configure_launcher(&mut config);
 
-    // Windows-specific: enable Control Flow Guard
-    let dst = if target_os == "windows" {
+    // MSVC-specific: enable Control Flow Guard unless opted out, e.g. when a
+    // CI injects its own security flags. MinGW GCC has no equivalent
+    // (-mguard=cf is clang-only), so windows-gnu goes without.
+    let guard_cf =
+        target_os == "windows" && target_env == "msvc" && var("OPUS_WINDOWS_NO_GUARD_CF").is_none();
+    let dst = if guard_cf {
         config.cflag("/guard:cf").build()
     } else {
         config.build()
     };
 
-    // Tell cargo where to find the library
-    println!("cargo:rustc-link-search=native={}/lib", dst.display());
-    println!("cargo:rustc-link-search=native={}/lib64", dst.display());
+    // Tell cargo where to find the library; where it lands depends on
+    // GNUInstallDirs (lib, lib64, multiarch) and the generator
+    let mut lib = install::find_static_lib(&dst)?;
+    if var("CARGO_FEATURE_CUSTOM_MODES").is_some() {
+        verify_custom_modes(&dst, &lib)?;
+    }
+    let rtcd = has_rtcd(&lib)?;
+    #[cfg(feature = "generate-bindings")]
+    generate_bindings(&dst.join("include").join("opus"))?;
+    if prefix_symbols_enabled() {
+        lib = prefix_symbols(&lib, &dst, &target_os, &target_arch, &target_env)?;
+    }
+
+    let lib_dir = lib.parent().unwrap_or(&dst);
+    println!("cargo:rustc-link-search=native={}", lib_dir.display());
     println!("cargo:rustc-link-lib=static=opus");
+    for lib in &platform.link_libs {
+        println!("cargo:rustc-link-lib={}", lib);
+    }
+
+    export_build_info(dnn, asan, rtcd, false, &presumed, profile, &target_triple);
+    export_metadata(&dst, lib_dir);
 
     warn!("Opus build complete");
     Ok(())
 }
 
+/// `opus_*` cfgs set for the crate, read back by `src/build_info.rs`
+const EXPORTED_CFGS: [&str; 11] = [
+    "opus_dnn",
+    "opus_dred",
+    "opus_osce",
+    "opus_fast_math",
+    "opus_fixed_point",
+    "opus_custom_modes",
+    "opus_no_intrinsics",
+    "opus_sanitize_address",
+    "opus_rtcd",
+    "opus_assertions",
+    "opus_prebuilt",
+];
+
+/// Tell the crate what the C build ended up with. Cargo features alone
+/// don't say, e.g. DRED and OSCE are switched off for armv7 Android and fixed-point
+/// builds, and SIMD levels can come from `-C target-feature`.
+fn export_build_info(
+    (dred, osce): (bool, bool),
+    asan: bool,
+    rtcd: bool,
+    prebuilt: bool,
+    presumed: &[&str],
+    profile: &str,
+    target: &str,
+) {
+    let enabled = [
+        dred || osce,
+        dred,
+        osce,
+        var("CARGO_FEATURE_FAST_MATH").is_some(),
+        var("CARGO_FEATURE_NO_FLOAT_API").is_some(),
+        var("CARGO_FEATURE_CUSTOM_MODES").is_some(),
+        intrinsics_disabled(),
+        asan,
+        rtcd,
+        assertions_enabled(),
+        prebuilt,
+    ];
+    for (name, on) in EXPORTED_CFGS.iter().zip(enabled) {
+        if on {
+            println!("cargo:rustc-cfg={}", name);
+        }
+    }
+
+    println!(
+        "cargo:rustc-env=OPUS_SYS_PRESUMED_ISA={}",
+        presumed.join(",")
+    );
+    println!("cargo:rustc-env=OPUS_SYS_CMAKE_BUILD_TYPE={}", profile);
+    println!("cargo:rustc-env=OPUS_SYS_TARGET={}", target);
+}
+
+/// [`export_build_info`] for an `OPUS_LIB_DIR` library, which wasn't
+/// configured here. DRED, OSCE and RTCD are read from its symbol index and
+/// the version from its version string; the other options are taken from
+/// the Cargo features, which must match how it was built. Without a
+/// readable library only the features are exported.
+fn describe_prebuilt(lib_dir: &Path, target_env: &str, target: &str) {
+    let name = if target_env == "msvc" {
+        "opus.lib"
+    } else {
+        "libopus.a"
+    };
+    let lib = lib_dir.join(name);
+    let data = std::fs::read(&lib).unwrap_or_default();
+    let (dnn, rtcd) = match archive::symbols(&data) {
+        Some(symbols) => (
+            (
+                archive::defines(&symbols, "dred_encoder_init"),
+                archive::defines(&symbols, "osce_enhance_frame"),
+            ),
+            archive::defines(&symbols, "opus_select_arch"),
+        ),
+        None => {
+            warn!(
+                "Can't read the symbol index of {}, build_info() reports the Cargo features",
+                lib.display()
+            );
+            let float = var("CARGO_FEATURE_NO_FLOAT_API").is_none();
+            (
+                (
+                    float && var("CARGO_FEATURE_DRED").is_some(),
+                    float && var("CARGO_FEATURE_OSCE").is_some(),
+                ),
+                !rtcd_disabled(),
+            )
+        }
+    };
+    export_build_info(dnn, false, rtcd, true, &[], "", target);
+
+    if let Some(version) = version::from_library(&data) {
+        println!("cargo:rustc-env=OPUS_VENDORED_VERSION={}", version);
+    }
+}
+
+/// `links = "opus"` metadata for the build scripts of crates that depend on
+/// this one directly, e.g. a -sys crate compiling opusfile or libopusenc
+/// against the same library. They read it as `DEP_OPUS_INCLUDE` (the
+/// directory with `opus.h`, as pkg-config's `-I`), `DEP_OPUS_LIB`,
+/// `DEP_OPUS_ROOT` and `DEP_OPUS_STATIC`. With `prefix-symbols`,
+/// `DEP_OPUS_SYMBOL_PREFIX` gives the prefix of the renamed symbols, which
+/// C code compiled against the plain headers won't find.
+fn export_metadata(dst: &Path, lib_dir: &Path) {
+    println!("cargo:root={}", dst.display());
+    println!(
+        "cargo:include={}",
+        dst.join("include").join("opus").display()
+    );
+    println!("cargo:lib={}", lib_dir.display());
+    println!("cargo:static=1");
+    if prefix_symbols_enabled() {
+        println!("cargo:symbol_prefix={}", prefix::PREFIX);
+    }
+}
+
+/// The version Opus' CMake build compiles into `opus_get_version_string()`
+fn opus_version(opus_dir: &Path) -> String {
+    if opus_dir.join(".git").exists() {
+        let describe = std::process::Command::new("git")
+            .arg(format!("--git-dir={}", opus_dir.join(".git").display()))
+            .args(["describe", "--tags", "--match", "v*"])
+            .output();
+        if let Some(version) = describe
+            .ok()
+            .and_then(|output| String::from_utf8(output.stdout).ok())
+            .and_then(|stdout| version::from_git_describe(&stdout))
+        {
+            return version;
+        }
+    } else if let Some(version) =
+        std::fs::read_to_string(opus_dir.join(version::PACKAGE_VERSION_FILE))
+            .ok()
+            .and_then(|text| version::from_package_version(&text))
+    {
+        return version;
+    }
+    version::UNKNOWN_VERSION.to_string()
+}
+
+/// Download the pinned Opus sources into OUT_DIR for the `auto-vendor`
+/// feature, reusing a previous download of the same commit
+fn auto_vendor(manifest_dir: &Path) -> Result<PathBuf, String> {
+    let dest = PathBuf::from(cargo_var("OUT_DIR")?).join("vendored");
+    let opus_dir = dest.join("opus");
+    let version = std::fs::read_to_string(dest.join("OPUS_VERSION")).unwrap_or_default();
+
+    if vendor::vendored_commit(&version) == Some(vendor::PINNED.commit) && opus_dir.is_dir() {
+        warn!("Reusing Opus sources in {}", opus_dir.display());
+    } else {
+        vendor::vendor(
+            &vendor::PINNED,
+            &dest,
+            &manifest_dir.join("patches"),
+            vendor::download,
+        )?;
+        warn!(
+            "Downloaded Opus {} to {}",
+            vendor::PINNED.commit,
+            opus_dir.display()
+        );
+    }
+    Ok(opus_dir)
+}
+
+/// CMake build type from the Cargo profile, see `profile::cmake_profile`
+fn cmake_profile(target_os: &str, target_env: &str) -> Result<&'static str, String> {
+    let requested = profile::OVERRIDE_ENV
+        .iter()
+        .find_map(|name| var(name).map(|value| (*name, value)));
+    let msvc = target_os == "windows" && target_env == "msvc";
+    let profile = profile::cmake_profile(
+        requested.as_ref().map(|(_, value)| value.as_str()),
+        var("OPT_LEVEL").as_deref(),
+        var("PROFILE").as_deref(),
+        msvc,
+    )
+    .map_err(|e| match &requested {
+        Some((name, _)) => format!("{}: {}", name, e),
+        None => e,
+    })?;
+
+    if let Some((name, _)) = &requested {
+        warn!("Using CMake build type {} from {}", profile, name);
+    }
+    Ok(profile)
+}
+
+/// Whether the library selects its SIMD level at runtime. Opus only
+/// defines `opus_select_arch` (read back by `src/cpu.rs`) when some level
+/// is left to runtime detection; otherwise it is an inline stub.
+fn has_rtcd(lib: &Path) -> Result<bool, Box<dyn std::error::Error>> {
+    let symbols = archive::symbols(&std::fs::read(lib)?).ok_or_else(|| {
+        format!(
+            "{} has no symbol index; run ranlib on it or set AR to a working archiver",
+            lib.display()
+        )
+    })?;
+    Ok(archive::defines(&symbols, "opus_select_arch"))
+}
+
+/// Copy the committed bindings to OUT_DIR, where `src/lib.rs` includes
+/// them from with `prefix-symbols` or `generate-bindings`
+fn copy_bindings() -> Result<(), Box<dyn std::error::Error>> {
+    let manifest_dir = PathBuf::from(cargo_var("CARGO_MANIFEST_DIR")?);
+    for name in ["bindings.rs", "custom_bindings.rs"] {
+        let source = manifest_dir.join("src").join(name);
+        println!("cargo:rerun-if-changed=src/{}", name);
+        let bindings = std::fs::read_to_string(&source)
+            .map_err(|e| format!("Failed to read {}: {}", source.display(), e))?;
+        write_bindings(name, &bindings)?;
+    }
+    Ok(())
+}
+
+/// Run bindgen on the installed headers with the rules `vendor_opus.py`
+/// uses for the committed bindings, and write the result to OUT_DIR. The
+/// `*_float` functions are left out with `no-float-api`, as the committed
+/// file does with `cfg`s.
+#[cfg(feature = "generate-bindings")]
+fn generate_bindings(include_dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let builder = || {
+        bindgen::Builder::default()
+            .use_core()
+            .ctypes_prefix("::core::ffi")
+            .clang_arg(format!("-I{}", include_dir.display()))
+    };
+
+    let mut bindings = builder().header_contents(
+        "opus_all.h",
+        "#include \"opus.h\"\n#include \"opus_multistream.h\"\n#include \"opus_projection.h\"\n",
+    );
+    if var("CARGO_FEATURE_NO_FLOAT_API").is_some() {
+        bindings = bindings.blocklist_function(".*_float|opus_pcm_soft_clip");
+    }
+    let bindings = bindings
+        .generate()
+        .map_err(|e| format!("bindgen failed for {}: {}", include_dir.display(), e))?;
+    write_bindings("bindings.rs", &bindings.to_string())?;
+
+    // Only installed with custom modes; the types it shares with opus.h
+    // come from the main bindings
+    if var("CARGO_FEATURE_CUSTOM_MODES").is_some() {
+        let custom = builder()
+            .header(include_dir.join("opus_custom.h").display().to_string())
+            .allowlist_item("opus_custom_.*|OpusCustom.*")
+            .allowlist_recursively(false)
+            .raw_line("use super::bindings::*;")
+            .generate()
+            .map_err(|e| format!("bindgen failed for opus_custom.h: {}", e))?;
+        write_bindings("custom_bindings.rs", &custom.to_string())?;
+    }
+    warn!("Generated bindings from {}", include_dir.display());
+    Ok(())
+}
+
+/// Write bindgen output to OUT_DIR for `include!`, with `#[link_name]`s
+/// for the renamed symbols with `prefix-symbols`. Inner attributes can't be
+/// included and are dropped; the including module carries them instead.
+fn write_bindings(name: &str, bindings: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let out_dir = PathBuf::from(cargo_var("OUT_DIR")?);
+    let bindings = if prefix_symbols_enabled() {
+        prefix::prefix_bindings(bindings)
+    } else {
+        bindings
+            .lines()
+            .filter(|line| !line.starts_with("#!["))
+            .map(|line| format!("{}\n", line))
+            .collect()
+    };
+    std::fs::write(out_dir.join(name), bindings)?;
+    Ok(())
+}
+
+/// Copy the library to `{dst}/prefixed` with every global symbol renamed,
+/// see `build/prefix.rs`
+fn prefix_symbols(
+    lib: &Path,
+    dst: &Path,
+    target_os: &str,
+    target_arch: &str,
+    target_env: &str,
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let target_vendor = var("CARGO_CFG_TARGET_VENDOR").unwrap_or_default();
+    let candidates = prefix::objcopy_candidates(&target_vendor, target_env);
+    let objcopy = var("OBJCOPY")
+        .or_else(|| {
+            candidates
+                .iter()
+                .find(|tool| find_program(tool))
+                .map(|tool| tool.to_string())
+        })
+        .ok_or_else(|| {
+            format!(
+                "The prefix-symbols feature needs {} on PATH, or OBJCOPY set",
+                candidates.join(" or ")
+            )
+        })?;
+
+    let symbols = archive::symbols(&std::fs::read(lib)?)
+        .ok_or_else(|| format!("{} has no symbol index", lib.display()))?;
+    // C names carry a leading underscore on Apple targets and 32-bit Windows
+    let underscore = target_vendor == "apple" || (target_os == "windows" && target_arch == "x86");
+    let redefine = dst.join("prefix-symbols.txt");
+    std::fs::write(&redefine, prefix::redefine_syms(&symbols, underscore))?;
+
+    let out_dir = dst.join("prefixed");
+    std::fs::create_dir_all(&out_dir)?;
+    let out = out_dir.join(lib.file_name().ok_or("library path has no file name")?);
+    let status = std::process::Command::new(&objcopy)
+        .arg(format!("--redefine-syms={}", redefine.display()))
+        .arg(lib)
+        .arg(&out)
+        .status()
+        .map_err(|e| format!("Failed to run {}: {}", objcopy, e))?;
+    if !status.success() {
+        return Err(format!(
+            "{} failed to rename the symbols of {}",
+            objcopy,
+            lib.display()
+        )
+        .into());
+    }
+
+    warn!(
+        "Renamed the symbols of {} to {}* with {}",
+        lib.display(),
+        prefix::PREFIX,
+        objcopy
+    );
+    Ok(out)
+}
+
+/// Check that the Opus Custom API made it into the installed headers and
+/// library, so a misconfigured build fails here instead of as an undefined
+/// symbol in the application link.
+fn verify_custom_modes(dst: &Path, lib: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let header = dst.join("include").join("opus").join("opus_custom.h");
+    if !header.is_file() {
+        return Err(format!(
+            "custom-modes enabled but opus_custom.h was not installed to {}",
+            header.display()
+        )
+        .into());
+    }
+
+    // The archive symbol table stores names verbatim for both ar and MSVC lib
+    let archive = std::fs::read(lib)?;
+    let symbol = b"opus_custom_mode_create";
+    if !archive.windows(symbol.len()).any(|w| w == symbol) {
+        return Err(format!(
+            "custom-modes enabled but {} does not export opus_custom_mode_create",
+            lib.display()
+        )
+        .into());
+    }
+
+    warn!("Opus Custom API verified in {}", lib.display());
+    Ok(())
+}
+
+/// Fail early, with one message, if cmake, the C compiler, xcrun or the
+/// Android NDK is missing
+fn preflight(target_triple: &str, target_os: &str, target_env: &str) -> Result<(), String> {
+    let host_triple = cargo_var("HOST")?;
+    let cmake = cross::env_names("CMAKE", target_triple, &host_triple)
+        .iter()
+        .find_map(|name| var(name))
+        .filter(|cmake| !cmake.trim().is_empty());
+    let cross = cross::cross_config(var, target_triple, &host_triple);
+    let c_compiler = cross
+        .defines
+        .iter()
+        .find(|(name, _)| *name == "CMAKE_C_COMPILER")
+        .map(|(_, compiler)| compiler.as_str());
+
+    let toolchain = preflight::Toolchain {
+        host: &host_triple,
+        target: target_triple,
+        target_os,
+        target_env,
+        cmake: cmake.as_deref(),
+        c_compiler,
+        toolchain_file: cross.toolchain_file,
+        sdkroot: var("SDKROOT").is_some_and(|sdkroot| {
+            apple::sdk(target_os, target_triple)
+                .is_some_and(|sdk| apple::sdkroot_matches(&sdkroot, sdk.sdk))
+        }),
+        android_ndk: android::NDK_ENV.iter().any(|name| var(name).is_some()),
+        compiler_checked_elsewhere: target_env == "musl" || lto_enabled(),
+    };
+    let missing = preflight::missing(&toolchain, find_program);
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(preflight::message(target_triple, &missing))
+    }
+}
+
+/// Forward the cross-compilation toolchain from the environment.
+///
+/// Returns whether a CMake toolchain file was given, in which case it owns
+/// the system name, sysroot and compilers.
+fn configure_toolchain(
+    config: &mut Config,
+    target_triple: &str,
+    target_env: &str,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let host_triple = cargo_var("HOST")?;
+
+    let mut cross = cross::cross_config(var, target_triple, &host_triple);
+
+    // Without an explicit compiler CMake finds the system (glibc) gcc, whose
+    // objects don't link against the musl Rust std
+    let has_compiler = cross.defines.iter().any(|(n, _)| *n == "CMAKE_C_COMPILER");
+    if target_env == "musl" && !cross.toolchain_file && !has_compiler {
+        match cross::musl_compiler(target_triple, &host_triple, find_program) {
+            Some(cc) => cross.defines.push(("CMAKE_C_COMPILER", cc)),
+            None => {
+                warn!(
+                    "No musl C compiler found for {}; set TARGET_CC or install musl-gcc",
+                    target_triple
+                );
+            }
+        }
+    }
+
+    if lto_enabled() {
+        configure_lto_compiler(&mut cross.defines, cross.toolchain_file, target_env)?;
+    }
+
+    for (name, value) in &cross.defines {
+        warn!("{}={}", name, value);
+        config.define(name, value);
+    }
+    if !cross.ignored.is_empty() {
+        warn!(
+            "CMAKE_TOOLCHAIN_FILE is set, ignoring {}",
+            cross.ignored.join(", ")
+        );
+    }
+
+    Ok(cross.toolchain_file)
+}
+
+/// Forward CFLAGS and CXXFLAGS (and their target-specific variants), then
+/// OPUS_EXTRA_CFLAGS, to CMake. Each flag is quoted so arguments containing
+/// spaces survive. OPUS_EXTRA_CFLAGS only reaches the Opus build, e.g. for
+/// `-march=native` or sanitizers without affecting other C dependencies.
+///
+/// The cmake crate builds `CMAKE_<LANG>_FLAGS` from the cc crate, which
+/// splits these variables on whitespace (or, with `CC_SHELL_ESCAPED_FLAGS`,
+/// drops the quoting). They are removed from this build script's
+/// environment once read so cc doesn't add a second, mangled copy.
+fn configure_env_flags(config: &mut Config, target_triple: &str) -> Result<(), String> {
+    let host_triple = cargo_var("HOST")?;
+
+    for base in cross::FLAG_VARS {
+        let flags = cross::env_flags(var, base, target_triple, &host_triple)?;
+        for name in cross::env_names(base, target_triple, &host_triple) {
+            env::remove_var(name);
+        }
+        if flags.is_empty() {
+            continue;
+        }
+        warn!("{}: {}", base, flags.join(" "));
+
+        for flag in &flags {
+            let flag = cross::quote_flag(flag);
+            if base == "CFLAGS" {
+                config.cflag(flag);
+            } else {
+                config.cxxflag(flag);
+            }
+        }
+    }
+
+    if let Some(extra) = var("OPUS_EXTRA_CFLAGS") {
+        let flags = cross::split_flags(&extra).map_err(|e| format!("OPUS_EXTRA_CFLAGS: {}", e))?;
+        if !flags.is_empty() {
+            warn!("OPUS_EXTRA_CFLAGS: {}", flags.join(" "));
+        }
+        for flag in &flags {
+            config.cflag(cross::quote_flag(flag));
+        }
+    }
+    Ok(())
+}
+
+/// Make sure the C compiler for the `lto` feature is a clang matching
+/// rustc's LLVM, defaulting to `clang` when none is configured. A
+/// toolchain file picks its own compiler, which can't be checked here.
+fn configure_lto_compiler(
+    defines: &mut Vec<(&'static str, String)>,
+    toolchain_file: bool,
+    target_env: &str,
+) -> Result<(), String> {
+    if target_env == "msvc" {
+        return Err("The lto feature is not supported for MSVC targets".to_string());
+    }
+    if toolchain_file {
+        warn!("lto: the C compiler comes from CMAKE_TOOLCHAIN_FILE and must be a clang matching rustc's LLVM");
+        return Ok(());
+    }
+
+    let compiler = match defines.iter().find(|(n, _)| *n == "CMAKE_C_COMPILER") {
+        Some((_, compiler)) => compiler.clone(),
+        None => {
+            defines.push(("CMAKE_C_COMPILER", "clang".to_string()));
+            "clang".to_string()
+        }
+    };
+
+    let output = |program: &str, arg: &str| {
+        std::process::Command::new(program)
+            .arg(arg)
+            .output()
+            .map(|o| String::from_utf8_lossy(&o.stdout).into_owned())
+            .map_err(|e| format!("Failed to run {} {}: {}", program, arg, e))
+    };
+    let rustc_version = output(&cargo_var("RUSTC")?, "-vV")?;
+    let rustc_llvm = lto::rustc_llvm_major(&rustc_version)
+        .ok_or("The lto feature needs rustc's LLVM version, which `rustc -vV` did not report")?;
+    let compiler_version = output(&compiler, "--version").map_err(|e| {
+        format!(
+            "The lto feature needs clang {} as the C compiler: {}",
+            rustc_llvm, e
+        )
+    })?;
+    lto::check_compiler(&compiler, &compiler_version, rustc_llvm)
+}
+
+/// Compile Opus to LLVM bitcode for the `lto` feature
+fn configure_lto(config: &mut Config) {
+    // CMake's IPO adds the same flag, and archives with llvm-ar so the
+    // bitcode objects get a symbol index
+    config
+        .cflag("-flto=thin")
+        .define("CMAKE_INTERPROCEDURAL_OPTIMIZATION", "ON");
+
+    // libopus.a then holds bitcode, which only a linker running LLVM's LTO
+    // can read; without it the link fails with undefined opus_* symbols
+    let rustflags = var("CARGO_ENCODED_RUSTFLAGS").unwrap_or_default();
+    if !lto::has_linker_plugin_lto(&rustflags) {
+        warn!(
+            "lto: RUSTFLAGS lacks -Clinker-plugin-lto, which is needed to link the bitcode in libopus.a (e.g. RUSTFLAGS=\"-Clinker-plugin-lto -Clinker=clang -Clink-arg=-fuse-ld=lld\")"
+        );
+    }
+}
+
+/// Instrument the C build for the sanitizers rustc runs with, or those in
+/// OPUS_SYS_SANITIZER. Returns whether AddressSanitizer is on.
+fn configure_sanitizers(
+    config: &mut Config,
+    target_os: &str,
+    target_env: &str,
+) -> Result<bool, String> {
+    let cfg_sanitize = var("CARGO_CFG_SANITIZE");
+    let sanitizers = sanitizer::sanitizers(
+        var(sanitizer::SANITIZER_ENV).as_deref(),
+        cfg_sanitize.as_deref(),
+    )?;
+    if sanitizers.is_empty() {
+        return Ok(false);
+    }
+
+    for missing in sanitizer::missing_runtimes(&sanitizers, cfg_sanitize.as_deref()) {
+        warn!(
+            "{} sanitizer requested without RUSTFLAGS=-Zsanitizer={}, linking will lack its runtime",
+            missing.name(),
+            missing.name()
+        );
+    }
+
+    let msvc = target_os == "windows" && target_env == "msvc";
+    let mut flags = Vec::new();
+    for sanitizer in &sanitizers {
+        flags.extend_from_slice(sanitizer.cflags(msvc)?);
+    }
+    warn!("Sanitizer flags: {}", flags.join(" "));
+    for flag in flags {
+        config.cflag(flag);
+    }
+
+    // Fortified libc calls are checked by glibc, not the sanitizer
+    // interceptors, and GCC warns about combining them
+    config.define("OPUS_FORTIFY_SOURCE", "OFF");
+
+    Ok(sanitizers.contains(&sanitizer::Sanitizer::Address))
+}
+
+/// Apply OPUS_CMAKE_GENERATOR, falling back to the default generator if
+/// its build tool isn't installed, and drop a CMake cache configured with
+/// another generator.
+fn configure_generator(config: &mut Config, target_env: &str) -> Result<(), String> {
+    let requested = var(generator::GENERATOR_ENV);
+    let wanted = generator::generator(requested.as_deref(), find_program);
+    match &wanted {
+        generator::Generator::Default => {}
+        generator::Generator::Use(name) => {
+            warn!("Using CMake generator {}", name);
+        }
+        generator::Generator::Missing { name, program } => {
+            warn!(
+                "CMake generator {} needs {}, which was not found; using the default generator",
+                name, program
+            );
+        }
+    }
+    if let Some(name) = wanted.name() {
+        config.generator(name);
+    }
+
+    let build_dir = PathBuf::from(cargo_var("OUT_DIR")?).join("build");
+    let cache = build_dir.join("CMakeCache.txt");
+    if let Ok(contents) = std::fs::read_to_string(&cache) {
+        if let Some(cached) = generator::cached_generator(&contents) {
+            if generator::cache_is_stale(cached, &wanted, target_env == "msvc") {
+                warn!("CMake generator changed from {}, reconfiguring", cached);
+                let _ = std::fs::remove_file(&cache);
+                let _ = std::fs::remove_dir_all(build_dir.join("CMakeFiles"));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Pass Cargo's NUM_JOBS on as `cmake --build --parallel`, see
+/// `generator::parallel_jobs`. The cmake crate reads it from the
+/// environment, so an invalid value is dropped rather than failing the
+/// build step.
+fn configure_parallel() {
+    match generator::parallel_jobs(var("NUM_JOBS").as_deref()) {
+        Some(jobs) => env::set_var("NUM_JOBS", jobs.to_string()),
+        None => env::remove_var("NUM_JOBS"),
+    }
+}
+
+/// Apply the OPUS_SYS_BUILD_JOBS override.
+///
+/// The cmake crate passes NUM_JOBS to `cmake --build --parallel`, except
+/// for Makefile generators with a Cargo jobserver, where make takes job
+/// slots from Cargo instead. An override replaces NUM_JOBS and hides the
+/// jobserver from the cmake crate, so the count applies to every generator.
+fn configure_jobs() -> Result<(), String> {
+    if let Some(jobs) = jobs::build_jobs(var(jobs::JOBS_ENV).as_deref())? {
+        warn!("Building Opus with {} jobs from {}", jobs, jobs::JOBS_ENV);
+        env::set_var("NUM_JOBS", jobs.to_string());
+        env::remove_var("CARGO_MAKEFLAGS");
+    }
+    Ok(())
+}
+
+/// Wrap the C compiler in sccache or ccache, see `cross::compiler_launcher`.
+///
+/// CMake keeps the launcher in its cache like any other -D option, so it is
+/// removed again when unset or not installed. Otherwise a build directory
+/// configured with it would keep calling it, e.g. after the CI cache of
+/// OUT_DIR is restored on a machine without the tool. Only the Makefile and
+/// Ninja generators support launchers.
+fn configure_launcher(config: &mut Config) {
+    let launcher = cross::compiler_launcher(
+        var(cross::LAUNCHER_ENV).as_deref(),
+        var("RUSTC_WRAPPER").as_deref(),
+    );
+    match launcher {
+        Some(launcher) if find_program(&launcher) => {
+            warn!("Using C compiler launcher {}", launcher);
+            config.define("CMAKE_C_COMPILER_LAUNCHER", launcher);
+        }
+        Some(launcher) => {
+            warn!(
+                "C compiler launcher {} not found, building without it",
+                launcher
+            );
+            config.configure_arg("-UCMAKE_C_COMPILER_LAUNCHER");
+        }
+        None => {
+            config.configure_arg("-UCMAKE_C_COMPILER_LAUNCHER");
+        }
+    }
+}
+
+/// Whether `program` is an executable file, given as a path or found on
+/// PATH (with the host's executable suffix, if any)
+fn find_program(program: &str) -> bool {
+    let path = Path::new(program);
+    if path.components().count() > 1 {
+        return path.is_file();
+    }
+
+    let with_suffix = format!("{}{}", program, env::consts::EXE_SUFFIX);
+    ENV_VARS
+        .var_os("PATH")
+        .map(|paths| {
+            env::split_paths(&paths)
+                .any(|dir| dir.join(program).is_file() || dir.join(&with_suffix).is_file())
+        })
+        .unwrap_or(false)
+}
+
+/// Static musl builds: objects must be PIC for static-pie executables, and
+/// _FORTIFY_SOURCE would reference glibc-only __*_chk symbols
+fn configure_musl(config: &mut Config) {
+    config
+        .define("CMAKE_POSITION_INDEPENDENT_CODE", "ON")
+        .define("OPUS_FORTIFY_SOURCE", "OFF");
+}
+
+/// Build against the C runtime Rust links, see `crt::static_crt`
+fn configure_msvc_crt(config: &mut Config) -> Result<(), String> {
+    let static_crt = crt::static_crt(
+        var(crt::STATIC_CRT_ENV).as_deref(),
+        &var("CARGO_CFG_TARGET_FEATURE").unwrap_or_default(),
+    )?;
+    if static_crt {
+        warn!("Building Opus with the static CRT (/MT)");
+    }
+    // The cmake crate passes cc's runtime flag in CMAKE_C_FLAGS too; keep
+    // it in agreement to avoid /MD vs /MT override warnings
+    config
+        .define("OPUS_STATIC_RUNTIME", if static_crt { "ON" } else { "OFF" })
+        .static_crt(static_crt);
+    Ok(())
+}
+
+/// Point CMake at the NDK, deriving ANDROID_ABI from the target architecture
+fn configure_android(
+    config: &mut Config,
+    target_arch: &str,
+    has_toolchain_file: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let features = target_features();
+    let features: Vec<&str> = features.iter().map(String::as_str).collect();
+
+    let defines = android::android_defines(
+        var,
+        target_arch,
+        &features,
+        has_toolchain_file,
+        Path::is_file,
+    )?;
+    for (name, value) in &defines {
+        warn!("Android: {}={}", name, value);
+        config.define(name, value);
+    }
+
+    Ok(())
+}
+
+/// Link with 16 KB page alignment, as Google Play requires for 64-bit
+/// Android. The link args only reach this crate's own binaries and tests;
+/// a cdylib that links Opus needs the same flags in its own RUSTFLAGS.
+fn configure_page_size(config: &mut Config, target_os: &str, target_arch: &str) {
+    let setting = var("OPUS_16KB_PAGE_SIZE");
+    if !android::wants_16kb_pages(setting.as_deref(), target_os, target_arch) {
+        // Drop flags cached by an earlier build that had them enabled
+        config.configure_arg("-UCMAKE_SHARED_LINKER_FLAGS");
+        return;
+    }
+
+    let flags = android::PAGE_SIZE_LINK_ARGS.join(" ");
+    warn!("Linking for 16 KB pages: {}", flags);
+    config.define("CMAKE_SHARED_LINKER_FLAGS", &flags);
+    if target_os == "android" {
+        // Honoured by NDK r27+ toolchain files, ignored by older ones
+        config.define("ANDROID_SUPPORT_FLEXIBLE_PAGE_SIZES", "ON");
+    }
+
+    for arg in android::PAGE_SIZE_LINK_ARGS {
+        println!("cargo:rustc-link-arg={}", arg);
+    }
+}
+
 fn configure_for_platform(
     config: &mut Config,
     target_os: &str,
     target_arch: &str,
     target_triple: &str,
-) {
-    // Pass ANDROID_ABI if set in environment (for Android cross-compilation)
-    if let Ok(abi) = env::var("ANDROID_ABI") {
-        config.define("ANDROID_ABI", abi);
+    toolchain_file: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Android always builds through a toolchain file, the NDK's by default
+    if target_os == "android" {
+        return configure_android(config, target_arch, toolchain_file);
+    }
+
+    if toolchain_file {
+        warn!("Using CMake toolchain file, skipping platform detection");
+        return Ok(());
     }
 
-    let host_arch = env::var("CARGO_CFG_TARGET_ARCH")
+    let host_arch = var("CARGO_CFG_TARGET_ARCH")
         .map(|_| env::consts::ARCH)
         .unwrap_or(env::consts::ARCH);
+    let target_vendor = var("CARGO_CFG_TARGET_VENDOR").unwrap_or_default();
 
     match target_os {
-        "ios" => configure_ios(config, target_arch, target_triple),
+        os if apple::platform(os).is_some() => configure_apple_embedded(config, os, target_triple),
         "macos" => configure_macos(config, target_arch),
+        "windows" if target_vendor == "uwp" => configure_uwp(config, target_arch),
         // Only apply cross-compilation settings when host is x86/x64 and target is ARM64
         "windows" if target_arch == "aarch64" => configure_windows_arm64(config, host_arch),
         _ => {}
     }
+
+    Ok(())
 }
 
-fn configure_ios(config: &mut Config, target_arch: &str, target_triple: &str) {
+/// iOS, tvOS, watchOS and visionOS, devices and simulators, and Mac Catalyst
+fn configure_apple_embedded(config: &mut Config, target_os: &str, target_triple: &str) {
+    let Some(sdk) = apple::sdk(target_os, target_triple) else {
+        warn!("Unsupported {} target: {}", target_os, target_triple);
+        return;
+    };
+
     let deployment_target =
-        env::var("IPHONEOS_DEPLOYMENT_TARGET").unwrap_or_else(|_| "14.0".to_string());
+        var(sdk.deployment_env).unwrap_or_else(|| sdk.default_deployment_target.to_string());
 
-    let is_simulator = target_triple.contains("sim");
-    let (sdk_name, arch) = match (target_arch, is_simulator) {
-        ("aarch64", true) => ("iphonesimulator", "arm64"),
-        ("aarch64", false) => ("iphoneos", "arm64"),
-        ("x86_64", _) => ("iphonesimulator", "x86_64"),
-        _ => {
-            warn!("Unsupported iOS architecture: {}", target_arch);
-            return;
+    // Get SDK path - prefer SDKROOT env var if it is for this SDK, fall
+    // back to xcrun
+    let sdkroot = var("SDKROOT").filter(|path| {
+        let matches = apple::sdkroot_matches(path, sdk.sdk);
+        if !matches {
+            warn!("Ignoring SDKROOT={}, which is not a {} SDK", path, sdk.sdk);
         }
-    };
-
-    // Get SDK path - prefer SDKROOT env var, fall back to xcrun
-    let sdk_path = env::var("SDKROOT").unwrap_or_else(|_| {
+        matches
+    });
+    let sdk_path = sdkroot.unwrap_or_else(|| {
         std::process::Command::new("xcrun")
-            .args(["--sdk", sdk_name, "--show-sdk-path"])
+            .args(["--sdk", sdk.sdk, "--show-sdk-path"])
             .output()
             .ok()
             .and_then(|o| String::from_utf8(o.stdout).ok())
@@ -143,20 +1135,29 @@ fn configure_ios(config: &mut Config, target_arch: &str, target_triple: &str) {
             .unwrap_or_default()
     });
 
-    let cflags =
-        env::var("CFLAGS").unwrap_or_else(|_| format!("-isysroot {} -arch {}", sdk_path, arch));
+    // Catalyst selects the iOS-on-macOS ABI through the clang target triple
+    let arch_flags = match sdk.catalyst_target(&deployment_target) {
+        Some(target) => format!("-target {}", target),
+        None => format!("-arch {}", sdk.arch),
+    };
+    let cflags = format!("-isysroot {} {}", cross::quote_flag(&sdk_path), arch_flags);
 
-    warn!("iOS SDK: {}, CFLAGS: {}", sdk_path, cflags);
+    warn!("{} SDK: {}, flags: {}", sdk.system_name, sdk_path, cflags);
 
     config
-        .define("CMAKE_SYSTEM_NAME", "iOS")
+        .define("CMAKE_SYSTEM_NAME", sdk.system_name)
         .define("CMAKE_OSX_SYSROOT", &sdk_path)
-        .define("CMAKE_OSX_ARCHITECTURES", arch)
-        .define("CMAKE_OSX_DEPLOYMENT_TARGET", &deployment_target);
+        .define("CMAKE_OSX_ARCHITECTURES", sdk.arch);
 
-    for flag in cflags.split_whitespace() {
-        config.cflag(flag).cxxflag(flag);
+    // For Catalyst this would add a conflicting -mmacosx-version-min; the
+    // iOS version is already part of the -target triple
+    if !sdk.catalyst {
+        config.define("CMAKE_OSX_DEPLOYMENT_TARGET", &deployment_target);
     }
+
+    // Passed as one string: CMake pastes the flags into the command line
+    // as is. They come after CFLAGS from the environment, so they win.
+    config.cflag(&cflags).cxxflag(&cflags);
 }
 
 fn configure_macos(config: &mut Config, target_arch: &str) {
@@ -174,6 +1175,44 @@ fn configure_macos(config: &mut Config, target_arch: &str) {
         .define("CMAKE_SYSTEM_PROCESSOR", cmake_processor);
 }
 
+/// CMAKE_SYSTEM_VERSION for WindowsStore; UWP only exists on Windows 10+
+const UWP_SYSTEM_VERSION: &str = "10.0";
+
+/// UWP / Windows Store (`*-uwp-windows-msvc`, tier 3, needs `-Z build-std`)
+///
+/// Compiles against the app container API partition. Linking with
+/// /APPCONTAINER and the umbrella libraries is left to rustc's target spec.
+/// Everything Opus needs at runtime is allowed there: x86 CPU detection is
+/// the cpuid instruction, ARM64 detection (patched sources) calls
+/// IsProcessorFeaturePresent, and DNN weights are passed in memory through
+/// OPUS_SET_DNN_BLOB rather than loaded from disk. So RTCD, DRED and OSCE
+/// and Control Flow Guard all stay enabled.
+fn configure_uwp(config: &mut Config, target_arch: &str) {
+    let processor = match target_arch {
+        "x86_64" => "AMD64",
+        "x86" => "X86",
+        "aarch64" => "ARM64",
+        _ => {
+            warn!("Unsupported UWP architecture: {}", target_arch);
+            return;
+        }
+    };
+
+    warn!(
+        "UWP: building for WindowsStore {} ({})",
+        UWP_SYSTEM_VERSION, processor
+    );
+    config
+        .define("CMAKE_SYSTEM_NAME", "WindowsStore")
+        .define("CMAKE_SYSTEM_VERSION", UWP_SYSTEM_VERSION)
+        .define("CMAKE_SYSTEM_PROCESSOR", processor)
+        .cflag("/DWINAPI_FAMILY=WINAPI_FAMILY_APP");
+
+    if target_arch == "aarch64" {
+        configure_windows_arm64_neon(config);
+    }
+}
+
 fn configure_windows_arm64(config: &mut Config, host_arch: &str) {
     // Always set ARM64 processor to ensure Opus CMake properly detects ARM architecture
     config.define("CMAKE_SYSTEM_PROCESSOR", "ARM64");
@@ -186,69 +1225,315 @@ fn configure_windows_arm64(config: &mut Config, host_arch: &str) {
         warn!("Windows ARM64: native ARM64 build");
     }
 
-    // TODO(xnorpx): Revisit this and make pr for Opus
-    // MSVC doesn't define __ARM_NEON like GCC/Clang, but Opus's NEON source
-    // files check for it. Define it manually since NEON is always available on ARM64.
-    config.cflag("/D__ARM_NEON=1");
+    configure_windows_arm64_neon(config);
+}
+
+/// MSVC ARM64 NEON setup, shared by desktop and UWP builds
+fn configure_windows_arm64_neon(config: &mut Config) {
+    // Nothing below applies when intrinsics are off: no NEON sources are built
+    if intrinsics_disabled() {
+        return;
+    }
+
+    // __ARM_NEON, which MSVC doesn't define but Opus's NEON sources check
+    // for, comes from configure_arm_features: neon is a baseline feature of
+    // every ARM64 Windows target
 
-    // There's a bug in Opus where dnn_arm.h declares DNN_COMPUTE_LINEAR_IMPL extern
+    // The patched sources fix RTCD, so keep it and let the DOTPROD DNN
+    // kernels be picked at runtime on capable machines
+    if windows_arm64_rtcd_patch("windows", "aarch64") {
+        warn!("Windows ARM64: building patched sources with RTCD");
+        if dnn_requested() {
+            config.define("OPUS_MAY_HAVE_DOTPROD", "ON");
+        }
+        return;
+    }
+
+    // Unpatched sources (OPUS_WINDOWS_ARM64_NO_RTCD=1): there's a bug in Opus where dnn_arm.h declares DNN_COMPUTE_LINEAR_IMPL extern
     // when OPUS_HAVE_RTCD && OPUS_ARM_MAY_HAVE_NEON, but arm_dnn_map.c only defines it
     // when OPUS_ARM_MAY_HAVE_DOTPROD. This causes unresolved symbol errors.
     //
-    // Workaround: Disable RTCD and force the direct NEON function call path by:
-    // 1. Disabling MAY_HAVE_NEON (prevents RTCD dispatch table declaration)
-    // 2. Manually defining the macros needed for dnn_arm.h to use direct calls
-    config
-        .define("OPUS_MAY_HAVE_NEON", "OFF")
-        .cflag("/DOPUS_ARM_MAY_HAVE_NEON_INTR=1")
-        .cflag("/DOPUS_ARM_PRESUME_NEON_INTR=1");
+    // Workaround: always build without RTCD, using the direct NEON call path
+    apply_presumed_isa(config, &rtcd::presumed_isa("aarch64", &["neon"]));
 }
 
-fn configure_x86_features(config: &mut Config) {
-    if let Ok(target_features) = env::var("CARGO_CFG_TARGET_FEATURE") {
-        let features: Vec<&str> = target_features.split(',').map(|s| s.trim()).collect();
+/// Whether Windows ARM64 is built from a copy of the sources patched with
+/// build/windows-arm64-rtcd.patch, which keeps runtime CPU detection working
+/// under MSVC. OPUS_WINDOWS_ARM64_NO_RTCD=1 restores the unpatched workaround,
+/// which is also used for an OPUS_SOURCE_DIR tree: the patch is made against
+/// the vendored sources and may not apply to local changes.
+fn windows_arm64_rtcd_patch(target_os: &str, target_arch: &str) -> bool {
+    target_os == "windows"
+        && target_arch == "aarch64"
+        && var("CARGO_CFG_TARGET_ENV").unwrap_or_default() == "msvc"
+        && var("OPUS_WINDOWS_ARM64_NO_RTCD").is_none()
+        && var(source::SOURCE_DIR_ENV).is_none_or(|dir| dir.trim().is_empty())
+        && !rtcd_disabled()
+        && !intrinsics_disabled()
+}
 
-        let has_sse41 = features.contains(&"sse4.1");
-        let has_avx2 = features.contains(&"avx2");
-        let has_fma = features.contains(&"fma");
+fn patched_windows_arm64_source(
+    manifest_dir: &Path,
+    opus_dir: &Path,
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let patch_file = manifest_dir.join("build").join("windows-arm64-rtcd.patch");
+    let patch_text = std::fs::read_to_string(&patch_file)
+        .map_err(|e| format!("Failed to read {}: {}", patch_file.display(), e))?;
+    // Re-copy the tree whenever the vendored Opus version changes;
+    // OPUS_VERSION sits next to the tree, also for auto-vendor downloads
+    let version = opus_dir
+        .parent()
+        .map(|dir| std::fs::read(dir.join("OPUS_VERSION")).unwrap_or_default())
+        .unwrap_or_default();
 
-        if has_sse41 {
-            warn!("SSE4.1 detected, enabling OPUS_X86_PRESUME_SSE4_1");
-            config.define("OPUS_X86_PRESUME_SSE4_1", "ON");
+    let dest = PathBuf::from(cargo_var("OUT_DIR")?).join("opus-patched");
+    if patch::prepare_patched_tree(opus_dir, &dest, &patch_text, &version)? {
+        warn!("Applied {} to {}", patch_file.display(), dest.display());
+    } else {
+        warn!("Reusing patched Opus sources in {}", dest.display());
+    }
+    Ok(dest)
+}
+
+/// Returns the presumed ISA options, as for the other `configure_*_features`
+fn configure_no_rtcd(config: &mut Config, target_arch: &str) -> Vec<&'static str> {
+    if intrinsics_disabled() {
+        warn!("Intrinsics disabled, no-rtcd has no effect");
+        return Vec::new();
+    }
+
+    let features = target_features();
+    let features: Vec<&str> = features.iter().map(String::as_str).collect();
+    let isa = rtcd::presumed_isa(target_arch, &features);
+
+    let presumed: Vec<&'static str> = isa
+        .cmake_defines
+        .iter()
+        .filter(|(name, value)| name.contains("PRESUME") && *value == "ON")
+        .map(|(name, _)| *name)
+        .collect();
+    warn!(
+        "RTCD disabled, presuming: {}",
+        if presumed.is_empty() {
+            "baseline only".to_string()
+        } else {
+            presumed.join(", ")
         }
+    );
+
+    apply_presumed_isa(config, &isa);
+    presumed
+}
+
+fn apply_presumed_isa(config: &mut Config, isa: &rtcd::PresumedIsa) {
+    let msvc = var("CARGO_CFG_TARGET_ENV").unwrap_or_default() == "msvc";
+    for (name, value) in &isa.cmake_defines {
+        config.define(name, value);
+    }
+    for name in &isa.c_defines {
+        config.cflag(format!("{}D{}=1", if msvc { "/" } else { "-" }, name));
+    }
+}
+
+/// Returns the `OPUS_X86_PRESUME_*` options turned on
+fn configure_x86_features(config: &mut Config) -> Vec<&'static str> {
+    let mut presumed = Vec::new();
+    if intrinsics_disabled() {
+        warn!("Intrinsics disabled, ignoring x86 target features");
+        return presumed;
+    }
+
+    let features = target_features();
+    let has = |feature: &str| features.iter().any(|f| f == feature);
+
+    let has_sse41 = has("sse4.1");
+    let has_avx2 = has("avx2");
+    let has_fma = has("fma");
+
+    if has_sse41 {
+        warn!("SSE4.1 enabled, enabling OPUS_X86_PRESUME_SSE4_1");
+        config.define("OPUS_X86_PRESUME_SSE4_1", "ON");
+        presumed.push("OPUS_X86_PRESUME_SSE4_1");
+    }
+
+    if has_avx2 && has_fma {
+        warn!("AVX2+FMA enabled, enabling OPUS_X86_PRESUME_AVX2");
+        config.define("OPUS_X86_PRESUME_AVX2", "ON");
+        presumed.push("OPUS_X86_PRESUME_AVX2");
+    } else if has_avx2 {
+        // Opus's AVX2 kernels also use FMA instructions
+        warn!("AVX2 enabled without FMA, not presuming AVX2 (add -C target-feature=+fma)");
+    }
+    presumed
+}
+
+/// Returns the `OPUS_PRESUME_*` options turned on
+fn configure_arm_features(config: &mut Config) -> Vec<&'static str> {
+    if intrinsics_disabled() {
+        warn!("Intrinsics disabled, ignoring ARM target features");
+        return Vec::new();
+    }
 
-        if has_avx2 && has_fma {
-            warn!("AVX2+FMA detected, enabling OPUS_X86_PRESUME_AVX2");
-            config.define("OPUS_X86_PRESUME_AVX2", "ON");
+    let features = target_features();
+    let features: Vec<&str> = features.iter().map(String::as_str).collect();
+    let dnn = dnn_requested();
+    if features.contains(&"dotprod") && !dnn {
+        warn!("DOTPROD enabled but the dred and osce features are off, ignoring");
+    }
+    for feature in rtcd::ARM_UNUSED_FEATURES {
+        if features.contains(&feature) {
+            warn!("{} enabled, but Opus has no kernels using it", feature);
         }
     }
+
+    let msvc = var("CARGO_CFG_TARGET_ENV").unwrap_or_default() == "msvc";
+    let isa = rtcd::arm_features(&features, msvc, dnn);
+    let presumed: Vec<&'static str> = isa.cmake_defines.iter().map(|(name, _)| *name).collect();
+    if !presumed.is_empty() {
+        warn!("ARM target features, enabling {}", presumed.join(", "));
+    }
+    apply_presumed_isa(config, &isa);
+    presumed
 }
 
-fn configure_features(config: &mut Config, target_os: &str, target_arch: &str) {
-    // Check Cargo feature flags
-    let dnn_enabled = env::var("CARGO_FEATURE_DNN").is_ok();
-    let fast_math_enabled = env::var("CARGO_FEATURE_FAST_MATH").is_ok();
+/// Target features from CARGO_CFG_TARGET_FEATURE plus those requested
+/// through the presume-* cargo features, which work without RUSTFLAGS.
+fn target_features() -> Vec<String> {
+    let mut features: Vec<String> = var("CARGO_CFG_TARGET_FEATURE")
+        .unwrap_or_default()
+        .split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect();
+
+    let mut presumed = Vec::new();
+    if var("CARGO_FEATURE_PRESUME_SSE4.1").is_some() {
+        presumed.push("sse4.1");
+    }
+    if var("CARGO_FEATURE_PRESUME_AVX2").is_some() {
+        presumed.extend(["avx", "avx2", "fma"]);
+    }
+    if var("CARGO_FEATURE_PRESUME_DOTPROD").is_some() {
+        presumed.push("dotprod");
+    }
+
+    for feature in presumed {
+        if host_lacks_feature(feature) {
+            warn!(
+                "presume-* feature requests {} but this host lacks it; tests run here will crash",
+                feature
+            );
+        }
+        if !features.iter().any(|f| f == feature) {
+            features.push(feature.to_string());
+        }
+    }
+
+    features
+}
+
+/// Only meaningful for native builds; a cross build's target CPU is unknown here.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+fn host_lacks_feature(feature: &str) -> bool {
+    if var("HOST") != var("TARGET") {
+        return false;
+    }
+
+    match feature {
+        "sse4.1" => !std::arch::is_x86_feature_detected!("sse4.1"),
+        "avx" => !std::arch::is_x86_feature_detected!("avx"),
+        "avx2" => !std::arch::is_x86_feature_detected!("avx2"),
+        "fma" => !std::arch::is_x86_feature_detected!("fma"),
+        _ => false,
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+fn host_lacks_feature(feature: &str) -> bool {
+    if var("HOST") != var("TARGET") {
+        return false;
+    }
+
+    match feature {
+        "dotprod" => !std::arch::is_aarch64_feature_detected!("dotprod"),
+        _ => false,
+    }
+}
+
+#[cfg(not(any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64")))]
+fn host_lacks_feature(_feature: &str) -> bool {
+    false
+}
+
+fn intrinsics_disabled() -> bool {
+    var("CARGO_FEATURE_NO_INTRINSICS").is_some()
+}
+
+/// The `assertions` feature, or `OPUS_SYS_ASSERTIONS=1` to switch them on
+/// without touching Cargo.toml, e.g. for a dependency
+fn assertions_enabled() -> bool {
+    var("CARGO_FEATURE_ASSERTIONS").is_some() || var("OPUS_SYS_ASSERTIONS").as_deref() == Some("1")
+}
+
+fn prefix_symbols_enabled() -> bool {
+    var("CARGO_FEATURE_PREFIX_SYMBOLS").is_some()
+}
+
+fn generate_bindings_enabled() -> bool {
+    var("CARGO_FEATURE_GENERATE_BINDINGS").is_some()
+}
+
+fn lto_enabled() -> bool {
+    var("CARGO_FEATURE_LTO").is_some()
+}
+
+/// The `dred` or `osce` feature is on, so the DNN kernels are compiled
+fn dnn_requested() -> bool {
+    var("CARGO_FEATURE_DRED").is_some() || var("CARGO_FEATURE_OSCE").is_some()
+}
+
+fn rtcd_disabled() -> bool {
+    var("CARGO_FEATURE_NO_RTCD").is_some()
+}
+
+/// Returns whether DRED and OSCE are built
+fn configure_features(config: &mut Config, target_os: &str, target_arch: &str) -> (bool, bool) {
+    // Check Cargo feature flags; `dnn` enables both
+    let dred_enabled = var("CARGO_FEATURE_DRED").is_some();
+    let osce_enabled = var("CARGO_FEATURE_OSCE").is_some();
+    let fast_math_enabled = var("CARGO_FEATURE_FAST_MATH").is_some();
+    let no_float_api = var("CARGO_FEATURE_NO_FLOAT_API").is_some();
 
     // DRED/OSCE don't work on some platforms
-    let is_android_armv7 = target_os == "android" && target_arch == "arm";
+    let dnn_unsupported = target_os == "android" && !android::dnn_supported(target_arch);
 
-    if is_android_armv7 {
+    let (dred, osce) = if dnn_unsupported {
         warn!(
             "AI features (DRED/OSCE) not supported on {}-{}",
             target_os, target_arch
         );
-        config.define("OPUS_DRED", "OFF").define("OPUS_OSCE", "OFF");
-    } else if dnn_enabled {
-        warn!("DNN features enabled (DRED + OSCE)");
-        config.define("OPUS_DRED", "ON");
-        config.define("OPUS_OSCE", "ON");
+        (false, false)
+    } else if (dred_enabled || osce_enabled) && no_float_api {
+        // Opus does not support DRED/OSCE in fixed-point builds
+        warn!("AI features (DRED/OSCE) not supported with no-float-api (fixed-point)");
+        (false, false)
+    } else {
+        (dred_enabled, osce_enabled)
+    };
+    let on = |enabled: bool| if enabled { "ON" } else { "OFF" };
+    config
+        .define("OPUS_DRED", on(dred))
+        .define("OPUS_OSCE", on(osce));
+    if dred || osce {
+        warn!(
+            "DNN features enabled (DRED={}, OSCE={})",
+            on(dred),
+            on(osce)
+        );
         // Use runtime weight loading - weight data files are too large to embed (~83MB)
         // Users must call OPUS_SET_DNN_BLOB() to provide weights at runtime
         warn!("DNN weights will be loaded at runtime via OPUS_SET_DNN_BLOB()");
         config.define("OPUS_RUNTIME_WEIGHTS", "ON");
-    } else {
-        config.define("OPUS_DRED", "OFF");
-        config.define("OPUS_OSCE", "OFF");
     }
 
     // Performance optimizations
@@ -258,4 +1543,33 @@ fn configure_features(config: &mut Config, target_os: &str, target_arch: &str) {
             .define("OPUS_FLOAT_APPROX", "ON")
             .define("OPUS_FAST_MATH", "ON");
     }
+
+    if intrinsics_disabled() {
+        warn!("SIMD intrinsics disabled (OPUS_DISABLE_INTRINSICS)");
+        config.define("OPUS_DISABLE_INTRINSICS", "ON");
+    }
+
+    if assertions_enabled() {
+        warn!("Opus assertions enabled (OPUS_ASSERTIONS), not for production builds");
+        config.define("OPUS_ASSERTIONS", "ON");
+        // Opus' cheap production checks (ENABLE_HARDENING) are on by
+        // default; spell it out so the paranoid build never lacks them
+        config.define("OPUS_HARDENING", "ON");
+    }
+
+    if var("CARGO_FEATURE_CUSTOM_MODES").is_some() {
+        warn!("Opus Custom modes enabled (OPUS_CUSTOM_MODES)");
+        config.define("OPUS_CUSTOM_MODES", "ON");
+    }
+
+    // Opus only supports DISABLE_FLOAT_API on fixed-point builds; the float
+    // build references float helpers that are compiled out along with the API.
+    if no_float_api {
+        warn!("Float API disabled (OPUS_ENABLE_FLOAT_API=OFF, OPUS_FIXED_POINT=ON)");
+        config
+            .define("OPUS_ENABLE_FLOAT_API", "OFF")
+            .define("OPUS_FIXED_POINT", "ON");
+    }
+
+    (dred, osce)
 }
@@ -0,0 +1,339 @@
+//! Android NDK configuration
+//!
+//! Kept free of the cmake crate and cargo directives so it can be shared
+//! between build.rs and the tests in `tests/build_android.rs`.
+
+use std::path::{Path, PathBuf};
+
+/// API level used when ANDROID_PLATFORM is not set, the oldest one the
+/// Rust Android targets support
+pub const DEFAULT_PLATFORM: &str = "android-21";
+
+/// Environment variables naming the NDK, in priority order
+pub const NDK_ENV: [&str; 2] = ["ANDROID_NDK_HOME", "ANDROID_NDK_ROOT"];
+
+/// Linker flags that align ELF segments for 16 KB pages, which Google Play
+/// requires of 64-bit native libraries
+pub const PAGE_SIZE_LINK_ARGS: [&str; 2] = [
+    "-Wl,-z,max-page-size=16384",
+    "-Wl,-z,common-page-size=16384",
+];
+
+/// Whether to link for 16 KB pages. `setting` is OPUS_16KB_PAGE_SIZE:
+/// `0` opts out, any other value forces the flags on (e.g. to test them
+/// on the host), and by default they apply to 64-bit Android targets.
+pub fn wants_16kb_pages(setting: Option<&str>, target_os: &str, target_arch: &str) -> bool {
+    match setting.map(str::trim) {
+        Some("0") => false,
+        Some(s) if !s.is_empty() => true,
+        _ => target_os == "android" && abi(target_arch).is_some_and(|abi| abi.page_size_16k),
+    }
+}
+
+/// Settings that differ between the Android ABIs
+struct Abi {
+    /// Rust `target_arch`
+    arch: &'static str,
+    /// NDK `ANDROID_ABI` name
+    name: &'static str,
+    /// Whether DRED/OSCE build and run on this ABI
+    dnn: bool,
+    /// Whether Google Play requires 16 KB page alignment (64-bit ABIs)
+    page_size_16k: bool,
+}
+
+const ABIS: [Abi; 4] = [
+    Abi {
+        arch: "aarch64",
+        name: "arm64-v8a",
+        dnn: true,
+        page_size_16k: true,
+    },
+    Abi {
+        arch: "arm",
+        name: "armeabi-v7a",
+        dnn: false,
+        page_size_16k: false,
+    },
+    Abi {
+        arch: "x86_64",
+        name: "x86_64",
+        dnn: true,
+        page_size_16k: true,
+    },
+    Abi {
+        arch: "x86",
+        name: "x86",
+        dnn: true,
+        page_size_16k: false,
+    },
+];
+
+fn abi(target_arch: &str) -> Option<&'static Abi> {
+    ABIS.iter().find(|abi| abi.arch == target_arch)
+}
+
+/// The NDK ABI name for a Rust target architecture
+pub fn abi_for_arch(target_arch: &str) -> Option<&'static str> {
+    abi(target_arch).map(|abi| abi.name)
+}
+
+/// Whether DRED/OSCE can be built for this Android architecture
+pub fn dnn_supported(target_arch: &str) -> bool {
+    abi(target_arch).is_none_or(|abi| abi.dnn)
+}
+
+/// Path of the CMake toolchain file inside an NDK
+pub fn toolchain_file(ndk: &Path) -> PathBuf {
+    ndk.join("build")
+        .join("cmake")
+        .join("android.toolchain.cmake")
+}
+
+/// Compute the CMake defines for an Android build.
+///
+/// ANDROID_ABI is derived from the target architecture unless set in the
+/// environment. For armeabi-v7a the instruction set and NEON follow the
+/// Rust target's `thumb-mode` and `neon` features, so the C code matches
+/// e.g. armv7-linux-androideabi (no NEON) or thumbv7neon. Unless the user already supplied a toolchain file, the NDK
+/// is located through [`NDK_ENV`] and its toolchain file is used; failing
+/// to find it is an error rather than a confusing host-compiler build.
+pub fn android_defines(
+    getenv: impl Fn(&str) -> Option<String>,
+    target_arch: &str,
+    target_features: &[&str],
+    has_toolchain_file: bool,
+    is_file: impl Fn(&Path) -> bool,
+) -> Result<Vec<(&'static str, String)>, String> {
+    let getenv = |name: &str| getenv(name).filter(|v| !v.trim().is_empty());
+
+    let abi = match getenv("ANDROID_ABI") {
+        Some(abi) => abi,
+        None => abi_for_arch(target_arch)
+            .ok_or_else(|| format!("Unsupported Android architecture: {}", target_arch))?
+            .to_string(),
+    };
+
+    // The NDK accepts "android-21" and also a bare "21"
+    let platform = match getenv("ANDROID_PLATFORM") {
+        Some(p) if p.chars().all(|c| c.is_ascii_digit()) => format!("android-{}", p),
+        Some(p) => p,
+        None => DEFAULT_PLATFORM.to_string(),
+    };
+
+    let has = |feature: &str| target_features.contains(&feature);
+    let arm_settings = [
+        (
+            "ANDROID_ARM_MODE",
+            if has("thumb-mode") { "thumb" } else { "arm" },
+        ),
+        ("ANDROID_ARM_NEON", if has("neon") { "ON" } else { "OFF" }),
+    ];
+    let is_armv7 = abi == "armeabi-v7a";
+
+    let mut defines = vec![("ANDROID_ABI", abi), ("ANDROID_PLATFORM", platform)];
+    if is_armv7 {
+        defines.extend(arm_settings.map(|(name, value)| (name, value.to_string())));
+    }
+
+    // Both are read, even with a toolchain file, so the build script
+    // watches them for changes
+    let ndk = NDK_ENV.map(getenv);
+    if !has_toolchain_file {
+        let ndk = ndk
+            .into_iter()
+            .flatten()
+            .next()
+            .ok_or("Android NDK not found. Set ANDROID_NDK_HOME (or ANDROID_NDK_ROOT) to the NDK directory, or CMAKE_TOOLCHAIN_FILE to a toolchain file.")?;
+
+        let toolchain = toolchain_file(Path::new(&ndk));
+        if !is_file(&toolchain) {
+            return Err(format!(
+                "Android NDK toolchain file not found: {}",
+                toolchain.display()
+            ));
+        }
+        defines.push(("CMAKE_TOOLCHAIN_FILE", toolchain.display().to_string()));
+    }
+
+    Ok(defines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+
+    const NDK: &str = "/opt/android-ndk";
+
+    fn resolve(
+        vars: &[(&str, &str)],
+        target_arch: &str,
+        has_toolchain_file: bool,
+    ) -> Result<Vec<(&'static str, String)>, String> {
+        resolve_with_features(vars, target_arch, &[], has_toolchain_file)
+    }
+
+    fn resolve_with_features(
+        vars: &[(&str, &str)],
+        target_arch: &str,
+        target_features: &[&str],
+        has_toolchain_file: bool,
+    ) -> Result<Vec<(&'static str, String)>, String> {
+        let env: HashMap<&str, &str> = vars.iter().copied().collect();
+        android_defines(
+            |name| env.get(name).map(|v| v.to_string()),
+            target_arch,
+            target_features,
+            has_toolchain_file,
+            |path| path == toolchain_file(Path::new(NDK)),
+        )
+    }
+
+    fn define<'a>(defines: &'a [(&'static str, String)], name: &str) -> Option<&'a str> {
+        defines
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    #[test]
+    fn test_abi_from_arch() {
+        for (arch, abi) in [
+            ("aarch64", "arm64-v8a"),
+            ("arm", "armeabi-v7a"),
+            ("x86_64", "x86_64"),
+            ("x86", "x86"),
+        ] {
+            let defines = resolve(&[("ANDROID_NDK_HOME", NDK)], arch, false).unwrap();
+            assert_eq!(define(&defines, "ANDROID_ABI"), Some(abi));
+        }
+        assert!(resolve(&[("ANDROID_NDK_HOME", NDK)], "riscv64", false).is_err());
+    }
+
+    #[test]
+    fn test_armv7_follows_target_features() {
+        let vars = [("ANDROID_NDK_HOME", NDK)];
+
+        // armv7-linux-androideabi: Thumb-2 without NEON
+        let features = ["v7", "thumb-mode", "thumb2", "vfp3"];
+        let defines = resolve_with_features(&vars, "arm", &features, false).unwrap();
+        assert_eq!(define(&defines, "ANDROID_ARM_MODE"), Some("thumb"));
+        assert_eq!(define(&defines, "ANDROID_ARM_NEON"), Some("OFF"));
+
+        // thumbv7neon-linux-androideabi
+        let features = ["v7", "thumb-mode", "thumb2", "neon"];
+        let defines = resolve_with_features(&vars, "arm", &features, false).unwrap();
+        assert_eq!(define(&defines, "ANDROID_ARM_NEON"), Some("ON"));
+
+        let defines = resolve_with_features(&vars, "aarch64", &["neon"], false).unwrap();
+        assert_eq!(define(&defines, "ANDROID_ARM_MODE"), None);
+        assert_eq!(define(&defines, "ANDROID_ARM_NEON"), None);
+    }
+
+    #[test]
+    fn test_dnn_disabled_only_on_armv7() {
+        assert!(!dnn_supported("arm"));
+        for arch in ["aarch64", "x86_64", "x86"] {
+            assert!(dnn_supported(arch), "{}", arch);
+        }
+    }
+
+    #[test]
+    fn test_explicit_abi_overrides() {
+        let vars = [("ANDROID_NDK_HOME", NDK), ("ANDROID_ABI", "armeabi-v7a")];
+        let defines = resolve(&vars, "aarch64", false).unwrap();
+        assert_eq!(define(&defines, "ANDROID_ABI"), Some("armeabi-v7a"));
+    }
+
+    #[test]
+    fn test_ndk_toolchain_and_platform() {
+        let defines = resolve(&[("ANDROID_NDK_ROOT", NDK)], "aarch64", false).unwrap();
+        assert_eq!(define(&defines, "ANDROID_PLATFORM"), Some(DEFAULT_PLATFORM));
+        assert_eq!(
+            define(&defines, "CMAKE_TOOLCHAIN_FILE"),
+            Some(
+                toolchain_file(Path::new(NDK))
+                    .display()
+                    .to_string()
+                    .as_str()
+            )
+        );
+
+        for platform in ["28", "android-28"] {
+            let vars = [("ANDROID_NDK_HOME", NDK), ("ANDROID_PLATFORM", platform)];
+            let defines = resolve(&vars, "aarch64", false).unwrap();
+            assert_eq!(define(&defines, "ANDROID_PLATFORM"), Some("android-28"));
+        }
+    }
+
+    #[test]
+    fn test_ndk_home_takes_priority() {
+        let vars = [("ANDROID_NDK_HOME", NDK), ("ANDROID_NDK_ROOT", "/missing")];
+        assert!(resolve(&vars, "aarch64", false).is_ok());
+
+        let vars = [("ANDROID_NDK_HOME", "/missing"), ("ANDROID_NDK_ROOT", NDK)];
+        assert!(resolve(&vars, "aarch64", false).is_err());
+    }
+
+    #[test]
+    fn test_missing_ndk_fails_fast() {
+        let err = resolve(&[], "aarch64", false).unwrap_err();
+        assert!(err.contains("ANDROID_NDK_HOME"), "{}", err);
+    }
+
+    #[test]
+    fn test_user_toolchain_file_skips_ndk_lookup() {
+        let defines = resolve(&[], "x86_64", true).unwrap();
+        assert_eq!(define(&defines, "ANDROID_ABI"), Some("x86_64"));
+        assert_eq!(define(&defines, "CMAKE_TOOLCHAIN_FILE"), None);
+    }
+
+    #[test]
+    fn test_reads_every_name() {
+        for has_toolchain_file in [false, true] {
+            let read = RefCell::new(Vec::new());
+            let _ = android_defines(
+                |name| {
+                    read.borrow_mut().push(name.to_string());
+                    (name == "ANDROID_NDK_HOME").then(|| NDK.to_string())
+                },
+                "aarch64",
+                &[],
+                has_toolchain_file,
+                |_| true,
+            );
+            let read = read.into_inner();
+            for name in NDK_ENV.iter().chain(&["ANDROID_ABI", "ANDROID_PLATFORM"]) {
+                assert!(read.iter().any(|r| r == name), "{} not read", name);
+            }
+        }
+    }
+
+    #[test]
+    fn test_16kb_pages_default_to_64_bit_android() {
+        assert!(wants_16kb_pages(None, "android", "aarch64"));
+        assert!(wants_16kb_pages(None, "android", "x86_64"));
+        assert!(!wants_16kb_pages(None, "android", "arm"));
+        assert!(!wants_16kb_pages(None, "linux", "aarch64"));
+        assert!(!wants_16kb_pages(Some(""), "linux", "aarch64"));
+    }
+
+    #[test]
+    fn test_16kb_pages_override() {
+        assert!(!wants_16kb_pages(Some("0"), "android", "aarch64"));
+        assert!(wants_16kb_pages(Some("1"), "linux", "x86_64"));
+    }
+
+    #[test]
+    fn test_page_size_link_args() {
+        for arg in PAGE_SIZE_LINK_ARGS {
+            assert!(
+                arg.starts_with("-Wl,-z,") && arg.ends_with("=16384"),
+                "{}",
+                arg
+            );
+        }
+    }
+}
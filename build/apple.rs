@@ -0,0 +1,274 @@
+//! Apple embedded platform (iOS, tvOS, watchOS, visionOS, Mac Catalyst)
+//! configuration
+//!
+//! Kept free of the cmake crate and cargo directives so it can be shared
+//! between build.rs and the tests in `tests/build_apple.rs`.
+
+/// Per-platform CMake and SDK names
+pub struct Platform {
+    /// Rust `target_os`
+    pub os: &'static str,
+    /// `CMAKE_SYSTEM_NAME`
+    pub system_name: &'static str,
+    /// xcrun SDK for devices
+    pub device_sdk: &'static str,
+    /// xcrun SDK for the simulator
+    pub simulator_sdk: &'static str,
+    /// Deployment target variable, the same one rustc reads
+    pub deployment_env: &'static str,
+    /// Deployment target when the variable is unset
+    pub default_deployment_target: &'static str,
+}
+
+pub const PLATFORMS: [Platform; 4] = [
+    Platform {
+        os: "ios",
+        system_name: "iOS",
+        device_sdk: "iphoneos",
+        simulator_sdk: "iphonesimulator",
+        deployment_env: "IPHONEOS_DEPLOYMENT_TARGET",
+        default_deployment_target: "14.0",
+    },
+    Platform {
+        os: "tvos",
+        system_name: "tvOS",
+        device_sdk: "appletvos",
+        simulator_sdk: "appletvsimulator",
+        deployment_env: "TVOS_DEPLOYMENT_TARGET",
+        default_deployment_target: "14.0",
+    },
+    Platform {
+        os: "watchos",
+        system_name: "watchOS",
+        device_sdk: "watchos",
+        simulator_sdk: "watchsimulator",
+        deployment_env: "WATCHOS_DEPLOYMENT_TARGET",
+        default_deployment_target: "7.0",
+    },
+    Platform {
+        os: "visionos",
+        system_name: "visionOS",
+        device_sdk: "xros",
+        simulator_sdk: "xrsimulator",
+        deployment_env: "XROS_DEPLOYMENT_TARGET",
+        default_deployment_target: "1.0",
+    },
+];
+
+/// SDK selection for one target triple
+#[derive(Debug, PartialEq, Eq)]
+pub struct Sdk {
+    pub system_name: &'static str,
+    /// xcrun `--sdk` name
+    pub sdk: &'static str,
+    /// `-arch` / `CMAKE_OSX_ARCHITECTURES` value
+    pub arch: &'static str,
+    pub deployment_env: &'static str,
+    pub default_deployment_target: &'static str,
+    /// Mac Catalyst: iOS code built against the macOS SDK
+    pub catalyst: bool,
+}
+
+impl Sdk {
+    /// Clang `-target` triple for Mac Catalyst, e.g.
+    /// `arm64-apple-ios14.0-macabi`; `None` for other platforms
+    pub fn catalyst_target(&self, deployment_target: &str) -> Option<String> {
+        self.catalyst
+            .then(|| format!("{}-apple-ios{}-macabi", self.arch, deployment_target))
+    }
+}
+
+/// The platform entry for a Rust `target_os`, if it is an embedded Apple OS
+pub fn platform(target_os: &str) -> Option<&'static Platform> {
+    PLATFORMS.iter().find(|p| p.os == target_os)
+}
+
+/// Pick the SDK and architecture for an Apple embedded target.
+///
+/// The architecture comes from the triple rather than `target_arch`, which
+/// can't tell arm64_32 (ILP32 watchOS) from arm64. Intel targets only exist
+/// for the simulator and Mac Catalyst (`-macabi`), which builds iOS code
+/// with the macOS SDK and takes the iOS deployment target.
+pub fn sdk(target_os: &str, target_triple: &str) -> Option<Sdk> {
+    let platform = platform(target_os)?;
+
+    let arch = match target_triple.split('-').next()? {
+        "aarch64" => "arm64",
+        "arm64_32" => "arm64_32",
+        "armv7k" => "armv7k",
+        "x86_64" => "x86_64",
+        _ => return None,
+    };
+    let catalyst = target_os == "ios" && target_triple.ends_with("-macabi");
+    let simulator = !catalyst && (target_triple.ends_with("-sim") || arch == "x86_64");
+
+    let (system_name, sdk) = if catalyst {
+        ("Darwin", "macosx")
+    } else if simulator {
+        (platform.system_name, platform.simulator_sdk)
+    } else {
+        (platform.system_name, platform.device_sdk)
+    };
+
+    Some(Sdk {
+        system_name,
+        sdk,
+        arch,
+        deployment_env: platform.deployment_env,
+        default_deployment_target: platform.default_deployment_target,
+        catalyst,
+    })
+}
+
+/// Whether `sdkroot` is a path to the `sdk` SDK. Xcode names them after the
+/// platform, e.g. `.../iPhoneSimulator17.2.sdk` for `iphonesimulator` or
+/// `.../MacOSX.sdk` for `macosx`. An `SDKROOT` left over from another
+/// platform (Xcode exports the macOS one to build scripts) must not win.
+pub fn sdkroot_matches(sdkroot: &str, sdk: &str) -> bool {
+    let name = sdkroot
+        .trim_end_matches(['/', '\\'])
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    name.strip_prefix(sdk).is_some_and(|rest| {
+        rest.ends_with(".sdk")
+            && rest[..rest.len() - 4]
+                .chars()
+                .all(|c| c.is_ascii_digit() || c == '.')
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sdk_and_arch(target_os: &str, target_triple: &str) -> Option<(&'static str, &'static str)> {
+        sdk(target_os, target_triple).map(|s| (s.sdk, s.arch))
+    }
+
+    #[test]
+    fn test_ios() {
+        assert_eq!(
+            sdk_and_arch("ios", "aarch64-apple-ios"),
+            Some(("iphoneos", "arm64"))
+        );
+        assert_eq!(
+            sdk_and_arch("ios", "aarch64-apple-ios-sim"),
+            Some(("iphonesimulator", "arm64"))
+        );
+        assert_eq!(
+            sdk_and_arch("ios", "x86_64-apple-ios"),
+            Some(("iphonesimulator", "x86_64"))
+        );
+        assert_eq!(sdk_and_arch("ios", "armv7s-apple-ios"), None);
+    }
+
+    #[test]
+    fn test_mac_catalyst() {
+        for (triple, arch) in [
+            ("aarch64-apple-ios-macabi", "arm64"),
+            ("x86_64-apple-ios-macabi", "x86_64"),
+        ] {
+            let sdk = sdk("ios", triple).unwrap();
+            assert_eq!((sdk.sdk, sdk.arch), ("macosx", arch), "{}", triple);
+            assert_eq!(sdk.system_name, "Darwin");
+            assert_eq!(sdk.deployment_env, "IPHONEOS_DEPLOYMENT_TARGET");
+            assert_eq!(
+                sdk.catalyst_target("14.0"),
+                Some(format!("{}-apple-ios14.0-macabi", arch))
+            );
+        }
+
+        let device = sdk("ios", "aarch64-apple-ios").unwrap();
+        assert_eq!(device.catalyst_target("14.0"), None);
+    }
+
+    #[test]
+    fn test_tvos_and_visionos() {
+        assert_eq!(
+            sdk_and_arch("tvos", "aarch64-apple-tvos"),
+            Some(("appletvos", "arm64"))
+        );
+        assert_eq!(
+            sdk_and_arch("tvos", "aarch64-apple-tvos-sim"),
+            Some(("appletvsimulator", "arm64"))
+        );
+        assert_eq!(
+            sdk_and_arch("visionos", "aarch64-apple-visionos"),
+            Some(("xros", "arm64"))
+        );
+        assert_eq!(
+            sdk_and_arch("visionos", "aarch64-apple-visionos-sim"),
+            Some(("xrsimulator", "arm64"))
+        );
+    }
+
+    #[test]
+    fn test_watchos_architectures() {
+        assert_eq!(
+            sdk_and_arch("watchos", "arm64_32-apple-watchos"),
+            Some(("watchos", "arm64_32"))
+        );
+        assert_eq!(
+            sdk_and_arch("watchos", "armv7k-apple-watchos"),
+            Some(("watchos", "armv7k"))
+        );
+        assert_eq!(
+            sdk_and_arch("watchos", "aarch64-apple-watchos-sim"),
+            Some(("watchsimulator", "arm64"))
+        );
+    }
+
+    #[test]
+    fn test_platform_settings() {
+        let watch = sdk("watchos", "aarch64-apple-watchos").unwrap();
+        assert_eq!(watch.system_name, "watchOS");
+        assert_eq!(watch.deployment_env, "WATCHOS_DEPLOYMENT_TARGET");
+
+        let vision = sdk("visionos", "aarch64-apple-visionos").unwrap();
+        assert_eq!(vision.system_name, "visionOS");
+        assert_eq!(vision.deployment_env, "XROS_DEPLOYMENT_TARGET");
+
+        assert!(sdk("macos", "aarch64-apple-darwin").is_none());
+    }
+
+    #[test]
+    fn test_sdkroot_matches() {
+        let platforms = "/Applications/Xcode.app/Contents/Developer/Platforms";
+        let tvos = format!(
+            "{}/AppleTVOS.platform/Developer/SDKs/AppleTVOS17.2.sdk",
+            platforms
+        );
+        assert!(sdkroot_matches(&tvos, "appletvos"));
+        assert!(sdkroot_matches(&format!("{}/", tvos), "appletvos"));
+        assert!(!sdkroot_matches(&tvos, "appletvsimulator"));
+
+        let sim = format!(
+            "{}/iPhoneSimulator.platform/Developer/SDKs/iPhoneSimulator.sdk",
+            platforms
+        );
+        assert!(sdkroot_matches(&sim, "iphonesimulator"));
+        assert!(!sdkroot_matches(&sim, "iphoneos"));
+
+        let xros = format!("{}/XROS.platform/Developer/SDKs/XROS1.0.sdk", platforms);
+        assert!(sdkroot_matches(&xros, "xros"));
+        assert!(!sdkroot_matches(&xros, "xrsimulator"));
+
+        let macos = "/Library/Developer/CommandLineTools/SDKs/MacOSX14.sdk";
+        assert!(sdkroot_matches(macos, "macosx"));
+        assert!(!sdkroot_matches(macos, "iphoneos"));
+        assert!(!sdkroot_matches("", "macosx"));
+    }
+
+    #[test]
+    fn test_sdk_names_are_unique() {
+        let mut names: Vec<&str> = PLATFORMS
+            .iter()
+            .flat_map(|p| [p.device_sdk, p.simulator_sdk])
+            .collect();
+        names.sort_unstable();
+        names.dedup();
+        assert_eq!(names.len(), PLATFORMS.len() * 2);
+    }
+}
@@ -0,0 +1,189 @@
+//! Reading the symbol index of a static library
+//!
+//! Whether an optional piece of Opus made it into the library is easiest
+//! to tell from the global symbols it defines, e.g. `opus_select_arch` is
+//! only a real function when runtime CPU detection is compiled in. The
+//! index lists exactly those; searching the whole archive would also find
+//! local symbols and strings.
+//!
+//! Kept free of the cmake crate and cargo directives so it can be shared
+//! between build.rs and the tests in `tests/build_archive.rs`.
+
+const MAGIC: &[u8] = b"!<arch>\n";
+const HEADER_LEN: usize = 60;
+
+/// Global symbol names in the index of an ar archive: the `/` member of
+/// GNU and MSVC archives (also `/SYM64/`) or the `__.SYMDEF` member of BSD
+/// archives, whose names carry Apple's leading underscore. `None` if the
+/// data is not an archive or has no index.
+pub fn symbols(archive: &[u8]) -> Option<Vec<String>> {
+    let data = archive.strip_prefix(MAGIC)?;
+    let header = data.get(..HEADER_LEN)?;
+    let name = std::str::from_utf8(&header[..16]).ok()?.trim_end();
+    let size: usize = std::str::from_utf8(&header[48..58])
+        .ok()?
+        .trim()
+        .parse()
+        .ok()?;
+    let member = data.get(HEADER_LEN..HEADER_LEN + size)?;
+
+    match name {
+        "/" => gnu_index(member, 4),
+        "/SYM64/" => gnu_index(member, 8),
+        "__.SYMDEF" | "__.SYMDEF SORTED" => bsd_index(member),
+        _ => {
+            // BSD long names: "#1/<len>", with the name leading the data
+            let len: usize = name.strip_prefix("#1/")?.parse().ok()?;
+            let long_name = member.get(..len)?;
+            let long_name = std::str::from_utf8(long_name).ok()?.trim_end_matches('\0');
+            if long_name.starts_with("__.SYMDEF") {
+                bsd_index(&member[len..])
+            } else {
+                None
+            }
+        }
+    }
+}
+
+/// Whether `symbols` has `name`, with or without Apple's leading underscore
+pub fn defines(symbols: &[String], name: &str) -> bool {
+    symbols
+        .iter()
+        .any(|s| s.strip_prefix('_').unwrap_or(s) == name)
+}
+
+/// Big-endian count, that many offsets of `width` bytes, then the names
+fn gnu_index(member: &[u8], width: usize) -> Option<Vec<String>> {
+    let count = be(member.get(..width)?) as usize;
+    let names = member.get(width + count.checked_mul(width)?..)?;
+    let names: Vec<String> = names
+        .split(|&b| b == 0)
+        .take(count)
+        .map(|name| String::from_utf8_lossy(name).into_owned())
+        .collect();
+    (names.len() == count).then_some(names)
+}
+
+/// Little-endian byte length of the (string offset, member offset) pairs,
+/// the pairs, the byte length of the string table, then the strings
+fn bsd_index(member: &[u8]) -> Option<Vec<String>> {
+    let ranlib_len = le(member.get(..4)?) as usize;
+    let ranlib = member.get(4..4 + ranlib_len)?;
+    let strings_at = 4 + ranlib_len;
+    let strings_len = le(member.get(strings_at..strings_at + 4)?) as usize;
+    let strings = member.get(strings_at + 4..strings_at + 4 + strings_len)?;
+
+    ranlib
+        .chunks_exact(8)
+        .map(|entry| {
+            let name = strings.get(le(&entry[..4]) as usize..)?;
+            let end = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+            Some(String::from_utf8_lossy(&name[..end]).into_owned())
+        })
+        .collect()
+}
+
+fn be(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0, |n, &b| (n << 8) | u64::from(b))
+}
+
+fn le(bytes: &[u8]) -> u32 {
+    bytes.iter().rev().fold(0, |n, &b| (n << 8) | u32::from(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(name: &str, size: usize) -> Vec<u8> {
+        let header = format!(
+            "{:<16}{:<12}{:<6}{:<6}{:<8}{:<10}`\n",
+            name, 0, 0, 0, 644, size
+        );
+        assert_eq!(header.len(), HEADER_LEN);
+        header.into_bytes()
+    }
+
+    fn archive(name: &str, member: &[u8]) -> Vec<u8> {
+        let mut archive = MAGIC.to_vec();
+        archive.extend(header(name, member.len()));
+        archive.extend(member);
+        // An object member follows; its contents don't matter
+        archive.extend(header("celt.o/", 4));
+        archive.extend(b"\x7fELF");
+        archive
+    }
+
+    fn gnu_member(names: &[&str]) -> Vec<u8> {
+        let mut member = (names.len() as u32).to_be_bytes().to_vec();
+        for _ in names {
+            member.extend(100u32.to_be_bytes());
+        }
+        for name in names {
+            member.extend(name.as_bytes());
+            member.push(0);
+        }
+        member
+    }
+
+    fn bsd_member(names: &[&str]) -> Vec<u8> {
+        let mut strings = Vec::new();
+        let mut ranlib = Vec::new();
+        for name in names {
+            ranlib.extend((strings.len() as u32).to_le_bytes());
+            ranlib.extend(100u32.to_le_bytes());
+            strings.extend(name.as_bytes());
+            strings.push(0);
+        }
+        let mut member = (ranlib.len() as u32).to_le_bytes().to_vec();
+        member.extend(ranlib);
+        member.extend((strings.len() as u32).to_le_bytes());
+        member.extend(strings);
+        member
+    }
+
+    #[test]
+    fn test_gnu() {
+        let names = ["opus_encode", "opus_select_arch", "celt_encoder_init"];
+        assert_eq!(
+            symbols(&archive("/", &gnu_member(&names))),
+            Some(names.map(String::from).to_vec())
+        );
+
+        let mut sym64 = (1u64).to_be_bytes().to_vec();
+        sym64.extend(100u64.to_be_bytes());
+        sym64.extend(b"opus_decode\0");
+        assert_eq!(
+            symbols(&archive("/SYM64/", &sym64)),
+            Some(vec!["opus_decode".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_bsd() {
+        let names = ["_opus_encode", "_opus_select_arch"];
+        let expected = Some(names.map(String::from).to_vec());
+        assert!(defines(expected.as_ref().unwrap(), "opus_select_arch"));
+        assert!(!defines(expected.as_ref().unwrap(), "opus_dred_parse"));
+        assert_eq!(
+            symbols(&archive("__.SYMDEF SORTED", &bsd_member(&names))),
+            expected
+        );
+
+        // llvm-ar writes the member name after the header
+        let mut member = b"__.SYMDEF SORTED\0\0\0\0".to_vec();
+        member.extend(bsd_member(&names));
+        assert_eq!(symbols(&archive("#1/20", &member)), expected);
+    }
+
+    #[test]
+    fn test_not_an_index() {
+        assert_eq!(symbols(b""), None);
+        assert_eq!(symbols(b"\x7fELF"), None);
+        // No index: the first member is an object
+        assert_eq!(symbols(&archive("opus.o/", b"\x7fELF")), None);
+        // Truncated
+        let full = archive("/", &gnu_member(&["opus_encode"]));
+        assert_eq!(symbols(&full[..MAGIC.len() + HEADER_LEN + 6]), None);
+    }
+}
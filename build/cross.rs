@@ -0,0 +1,527 @@
+//! Cross-compilation toolchain selection
+//!
+//! Kept free of the cmake crate and cargo directives so it can be shared
+//! between build.rs and the tests in `tests/build_cross.rs`.
+
+/// Toolchain settings resolved from the environment
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct CrossConfig {
+    /// CMake cache entries, passed as `-D<name>=<value>`
+    pub defines: Vec<(&'static str, String)>,
+    /// A toolchain file is in charge, so the platform-specific guessing in
+    /// build.rs must not override it
+    pub toolchain_file: bool,
+    /// Variables that were set but had no effect, for a build warning
+    pub ignored: Vec<String>,
+}
+
+/// Tools and the CMake variable each one sets
+const TOOLS: [(&str, &str); 3] = [
+    ("CC", "CMAKE_C_COMPILER"),
+    ("CXX", "CMAKE_CXX_COMPILER"),
+    ("AR", "CMAKE_AR"),
+];
+
+/// Tool names appended to a CROSS_COMPILE prefix such as `aarch64-poky-linux-`
+const CROSS_COMPILE_TOOLS: [&str; 3] = ["gcc", "g++", "ar"];
+
+/// Compiler flag variables, each passed to the compiler of the same name
+pub const FLAG_VARS: [&str; 2] = ["CFLAGS", "CXXFLAGS"];
+
+/// Names looked up for `base`, highest priority first. This is the order
+/// used by the cc and cmake crates: `<base>_<target>`,
+/// `<base>_<target_with_underscores>`, `TARGET_<base>` (or `HOST_<base>`
+/// for native builds), then `<base>`.
+pub fn env_names(base: &str, target: &str, host: &str) -> [String; 4] {
+    let kind = if target == host { "HOST" } else { "TARGET" };
+    [
+        format!("{}_{}", base, target),
+        format!("{}_{}", base, target.replace(['-', '.'], "_")),
+        format!("{}_{}", kind, base),
+        base.to_string(),
+    ]
+}
+
+/// Decide the toolchain defines from the environment.
+///
+/// A CMake toolchain file takes precedence over everything else: it is
+/// expected to name the compilers itself. Otherwise explicitly configured
+/// tools are used, falling back to `<CROSS_COMPILE>gcc` and friends.
+pub fn cross_config(
+    getenv: impl Fn(&str) -> Option<String>,
+    target: &str,
+    host: &str,
+) -> CrossConfig {
+    // Every name is read, not just those up to the first one set, so the
+    // build script watches all of them for changes
+    let lookup = |base: &str| {
+        let values: Vec<_> = env_names(base, target, host)
+            .into_iter()
+            .map(|name| (getenv(&name), name))
+            .collect();
+        values
+            .into_iter()
+            .find_map(|(value, name)| value.filter(|v| !v.trim().is_empty()).map(|v| (name, v)))
+    };
+    let mut config = CrossConfig::default();
+
+    if let Some((_, toolchain)) = lookup("CMAKE_TOOLCHAIN_FILE") {
+        config.defines.push(("CMAKE_TOOLCHAIN_FILE", toolchain));
+        config.toolchain_file = true;
+
+        for (tool, _) in TOOLS {
+            if let Some((name, _)) = lookup(tool) {
+                config.ignored.push(name);
+            }
+        }
+        if getenv("CROSS_COMPILE").is_some() {
+            config.ignored.push("CROSS_COMPILE".to_string());
+        }
+        return config;
+    }
+
+    let prefix = getenv("CROSS_COMPILE").filter(|p| !p.trim().is_empty());
+    for ((tool, cmake_var), suffix) in TOOLS.into_iter().zip(CROSS_COMPILE_TOOLS) {
+        if let Some((_, value)) = lookup(tool) {
+            config.defines.push((cmake_var, value));
+        } else if let Some(prefix) = &prefix {
+            config
+                .defines
+                .push((cmake_var, format!("{}{}", prefix.trim(), suffix)));
+        }
+    }
+
+    config
+}
+
+/// Collect the flags from every variant of `base` (e.g. `CFLAGS`).
+///
+/// Like the cc crate, all variants apply, from `<base>` up to
+/// `<base>_<target>`, so the more specific ones come last and win for
+/// flags where the last occurrence counts. Each value is split with
+/// [`split_flags`].
+pub fn env_flags(
+    getenv: impl Fn(&str) -> Option<String>,
+    base: &str,
+    target: &str,
+    host: &str,
+) -> Result<Vec<String>, String> {
+    let mut flags = Vec::new();
+    for name in env_names(base, target, host).iter().rev() {
+        if let Some(value) = getenv(name) {
+            flags.extend(split_flags(&value).map_err(|e| format!("{}: {}", name, e))?);
+        }
+    }
+    Ok(flags)
+}
+
+/// Split a flags variable the way a POSIX shell splits words, so
+/// `-DNAME="a b"` stays one argument. Single quotes are literal, and a
+/// backslash escapes the next character outside quotes and `"`, `\`, `$`
+/// and `` ` `` inside double quotes.
+pub fn split_flags(value: &str) -> Result<Vec<String>, String> {
+    let mut flags = Vec::new();
+    let mut current: Option<String> = None;
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => flags.extend(current.take()),
+            '\'' => {
+                let word = current.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err(format!("unterminated single quote in {:?}", value)),
+                    }
+                }
+            }
+            '"' => {
+                let word = current.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$' | '`')) => word.push(c),
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => {
+                                return Err(format!("unterminated double quote in {:?}", value))
+                            }
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err(format!("unterminated double quote in {:?}", value)),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                Some(c) => current.get_or_insert_with(String::new).push(c),
+                None => return Err(format!("trailing backslash in {:?}", value)),
+            },
+            c => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+    flags.extend(current);
+    Ok(flags)
+}
+
+/// Quote one flag for `CMAKE_<LANG>_FLAGS`, which CMake pastes into the
+/// compiler command line as is. Plain flags are returned unchanged.
+pub fn quote_flag(flag: &str) -> String {
+    let plain = !flag.is_empty()
+        && !flag
+            .chars()
+            .any(|c| c.is_whitespace() || matches!(c, '"' | '\'' | '\\' | '$' | '`'));
+    if plain {
+        return flag.to_string();
+    }
+
+    let mut quoted = String::from("\"");
+    for c in flag.chars() {
+        if matches!(c, '"' | '\\' | '$' | '`') {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Explicit compiler launcher, e.g. `sccache` or `ccache`
+pub const LAUNCHER_ENV: &str = "OPUS_SYS_COMPILER_LAUNCHER";
+
+/// Pick a `CMAKE_C_COMPILER_LAUNCHER`.
+///
+/// `OPUS_SYS_COMPILER_LAUNCHER` wins; set to an empty string it turns the
+/// launcher off. Otherwise a `RUSTC_WRAPPER` that is sccache is mirrored,
+/// so the C objects land in the same cache as the Rust ones. Other
+/// wrappers are left alone, as they are not necessarily C compiler
+/// launchers.
+pub fn compiler_launcher(requested: Option<&str>, rustc_wrapper: Option<&str>) -> Option<String> {
+    if let Some(requested) = requested {
+        let requested = requested.trim();
+        return (!requested.is_empty()).then(|| requested.to_string());
+    }
+
+    let wrapper = rustc_wrapper?.trim();
+    let program = wrapper.rsplit(['/', '\\']).next()?;
+    let stem = program.strip_suffix(".exe").unwrap_or(program);
+    (stem == "sccache").then(|| wrapper.to_string())
+}
+
+/// Pick a C compiler for a musl target when none is configured, so CMake
+/// doesn't fall back to the glibc gcc. Tries the musl-cross-make name
+/// (`x86_64-linux-musl-gcc`), then the `musl-gcc` wrapper, which only
+/// targets the host architecture.
+pub fn musl_compiler(
+    target: &str,
+    host: &str,
+    find_program: impl Fn(&str) -> bool,
+) -> Option<String> {
+    let parts: Vec<&str> = target.split('-').collect();
+    let cross_gcc = match parts.as_slice() {
+        [arch, _vendor, os, env] => format!("{}-{}-{}-gcc", arch, os, env),
+        _ => format!("{}-gcc", target),
+    };
+
+    let native = target.split('-').next() == host.split('-').next();
+    let candidates = [Some(cross_gcc), native.then(|| "musl-gcc".to_string())];
+    candidates
+        .into_iter()
+        .flatten()
+        .find(|program| find_program(program))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+
+    const TARGET: &str = "aarch64-poky-linux";
+    const HOST: &str = "x86_64-unknown-linux-gnu";
+
+    fn config(vars: &[(&str, &str)]) -> CrossConfig {
+        let env: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        cross_config(|name| env.get(name).cloned(), TARGET, HOST)
+    }
+
+    fn define<'a>(config: &'a CrossConfig, name: &str) -> Option<&'a str> {
+        config
+            .defines
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    #[test]
+    fn test_empty_env_defines_nothing() {
+        assert_eq!(config(&[]), CrossConfig::default());
+    }
+
+    #[test]
+    fn test_toolchain_file_wins() {
+        let config = config(&[
+            ("CMAKE_TOOLCHAIN_FILE", "/sdk/toolchain.cmake"),
+            ("TARGET_CC", "clang"),
+            ("CROSS_COMPILE", "aarch64-poky-linux-"),
+        ]);
+
+        assert!(config.toolchain_file);
+        assert_eq!(
+            config.defines,
+            [("CMAKE_TOOLCHAIN_FILE", "/sdk/toolchain.cmake".to_string())]
+        );
+        assert_eq!(config.ignored, ["TARGET_CC", "CROSS_COMPILE"]);
+    }
+
+    #[test]
+    fn test_target_specific_names_take_priority() {
+        let mut vars = vec![("CC", "cc")];
+        vars.push(("TARGET_CC", "target-cc"));
+        assert_eq!(
+            define(&config(&vars), "CMAKE_C_COMPILER"),
+            Some("target-cc")
+        );
+        vars.push(("CC_aarch64_poky_linux", "underscore-cc"));
+        assert_eq!(
+            define(&config(&vars), "CMAKE_C_COMPILER"),
+            Some("underscore-cc")
+        );
+        vars.push(("CC_aarch64-poky-linux", "exact-cc"));
+        assert_eq!(define(&config(&vars), "CMAKE_C_COMPILER"), Some("exact-cc"));
+    }
+
+    #[test]
+    fn test_empty_values_are_skipped() {
+        let config = config(&[
+            ("CMAKE_TOOLCHAIN_FILE_aarch64_poky_linux", ""),
+            ("CMAKE_TOOLCHAIN_FILE", "/sdk/toolchain.cmake"),
+        ]);
+        assert_eq!(
+            define(&config, "CMAKE_TOOLCHAIN_FILE"),
+            Some("/sdk/toolchain.cmake")
+        );
+    }
+
+    #[test]
+    fn test_native_build_uses_host_prefix() {
+        let env: HashMap<&str, &str> = [("HOST_CC", "host-cc"), ("TARGET_CC", "target-cc")].into();
+        let config = cross_config(|name| env.get(name).map(|v| v.to_string()), HOST, HOST);
+        assert_eq!(define(&config, "CMAKE_C_COMPILER"), Some("host-cc"));
+    }
+
+    #[test]
+    fn test_target_tools() {
+        let config = config(&[
+            ("TARGET_CC", "aarch64-poky-linux-gcc"),
+            ("TARGET_CXX", "aarch64-poky-linux-g++"),
+            ("TARGET_AR", "aarch64-poky-linux-ar"),
+        ]);
+
+        assert!(!config.toolchain_file);
+        assert_eq!(
+            define(&config, "CMAKE_C_COMPILER"),
+            Some("aarch64-poky-linux-gcc")
+        );
+        assert_eq!(
+            define(&config, "CMAKE_CXX_COMPILER"),
+            Some("aarch64-poky-linux-g++")
+        );
+        assert_eq!(define(&config, "CMAKE_AR"), Some("aarch64-poky-linux-ar"));
+    }
+
+    #[test]
+    fn test_cross_compile_prefix_fills_gaps() {
+        let config = config(&[
+            ("CROSS_COMPILE", "aarch64-poky-linux-"),
+            ("TARGET_CC", "clang"),
+        ]);
+
+        assert_eq!(define(&config, "CMAKE_C_COMPILER"), Some("clang"));
+        assert_eq!(
+            define(&config, "CMAKE_CXX_COMPILER"),
+            Some("aarch64-poky-linux-g++")
+        );
+        assert_eq!(define(&config, "CMAKE_AR"), Some("aarch64-poky-linux-ar"));
+    }
+
+    #[test]
+    fn test_reads_every_name() {
+        // Set names must not hide the others from `rerun-if-env-changed`
+        for vars in [
+            vec![],
+            vec![("CC_aarch64-poky-linux", "cc"), ("CROSS_COMPILE", "x-")],
+            vec![("CMAKE_TOOLCHAIN_FILE_aarch64-poky-linux", "/t.cmake")],
+        ] {
+            let env: HashMap<&str, &str> = vars.into_iter().collect();
+            let read = RefCell::new(Vec::new());
+            let getenv = |name: &str| {
+                read.borrow_mut().push(name.to_string());
+                env.get(name).map(|v| v.to_string())
+            };
+            cross_config(getenv, TARGET, HOST);
+            for base in FLAG_VARS {
+                env_flags(getenv, base, TARGET, HOST).unwrap();
+            }
+
+            let read = read.into_inner();
+            for base in [
+                "CMAKE_TOOLCHAIN_FILE",
+                "CC",
+                "CXX",
+                "AR",
+                "CFLAGS",
+                "CXXFLAGS",
+            ] {
+                for name in env_names(base, TARGET, HOST) {
+                    assert!(read.contains(&name), "{} not read", name);
+                }
+            }
+            assert!(read.iter().any(|name| name == "CROSS_COMPILE"));
+        }
+    }
+
+    #[test]
+    fn test_split_flags() {
+        assert_eq!(
+            split_flags("  -O2\t-fstack-protector-strong\n-D_FORTIFY_SOURCE=3 ").unwrap(),
+            ["-O2", "-fstack-protector-strong", "-D_FORTIFY_SOURCE=3"]
+        );
+        assert_eq!(
+            split_flags(r#"-DMSG="hello world" '-I/opt/my sdk/include' -DQ=\"x\""#).unwrap(),
+            ["-DMSG=hello world", "-I/opt/my sdk/include", "-DQ=\"x\""]
+        );
+        assert_eq!(
+            split_flags(r#"-DA="a\"b" -DB='c\d' e\ f """#).unwrap(),
+            ["-DA=a\"b", "-DB=c\\d", "e f", ""]
+        );
+        assert_eq!(split_flags("").unwrap(), Vec::<String>::new());
+
+        assert!(split_flags("-DMSG=\"open").is_err());
+        assert!(split_flags("-DMSG='open").is_err());
+        assert!(split_flags("-O2 \\").is_err());
+    }
+
+    #[test]
+    fn test_quote_flag_roundtrips() {
+        assert_eq!(
+            quote_flag("-fstack-protector-strong"),
+            "-fstack-protector-strong"
+        );
+        assert_eq!(quote_flag("-DMSG=hello world"), "\"-DMSG=hello world\"");
+
+        for flag in ["-DQ=\"x\"", "-I/opt/my sdk", "-DP=$HOME", "a\\b", ""] {
+            assert_eq!(split_flags(&quote_flag(flag)).unwrap(), [flag], "{}", flag);
+        }
+    }
+
+    #[test]
+    fn test_env_flags_accumulate_variants() {
+        let env: HashMap<&str, &str> = [
+            ("CFLAGS", "-O2 -D_FORTIFY_SOURCE=2"),
+            ("TARGET_CFLAGS", "-D_FORTIFY_SOURCE=3"),
+            ("CFLAGS_aarch64-poky-linux", "-DNAME='a b'"),
+            ("CXXFLAGS", "-fno-rtti"),
+        ]
+        .into();
+        let getenv = |name: &str| env.get(name).map(|v| v.to_string());
+
+        assert_eq!(
+            env_flags(getenv, "CFLAGS", TARGET, HOST).unwrap(),
+            [
+                "-O2",
+                "-D_FORTIFY_SOURCE=2",
+                "-D_FORTIFY_SOURCE=3",
+                "-DNAME=a b"
+            ]
+        );
+        assert_eq!(
+            env_flags(getenv, "CXXFLAGS", TARGET, HOST).unwrap(),
+            ["-fno-rtti"]
+        );
+        assert_eq!(
+            env_flags(|_| None, "CFLAGS", TARGET, HOST).unwrap(),
+            Vec::<String>::new()
+        );
+
+        let bad = |name: &str| (name == "TARGET_CFLAGS").then(|| "\"".to_string());
+        let err = env_flags(bad, "CFLAGS", TARGET, HOST).unwrap_err();
+        assert!(err.starts_with("TARGET_CFLAGS:"), "{}", err);
+    }
+
+    #[test]
+    fn test_env_names_replace_dots() {
+        let names = env_names("CFLAGS", "thumbv8m.main-none-eabi", HOST);
+        assert_eq!(names[1], "CFLAGS_thumbv8m_main_none_eabi");
+        assert_eq!(FLAG_VARS.len(), 2);
+    }
+
+    #[test]
+    fn test_compiler_launcher() {
+        assert_eq!(compiler_launcher(None, None), None);
+        assert_eq!(
+            compiler_launcher(Some(" ccache "), None).as_deref(),
+            Some("ccache")
+        );
+        // Explicit setting wins over the wrapper, and empty turns it off
+        assert_eq!(
+            compiler_launcher(Some("ccache"), Some("sccache")).as_deref(),
+            Some("ccache")
+        );
+        assert_eq!(compiler_launcher(Some(""), Some("sccache")), None);
+    }
+
+    #[test]
+    fn test_compiler_launcher_mirrors_sccache() {
+        for wrapper in [
+            "sccache",
+            "/home/ci/.cargo/bin/sccache",
+            r"C:\tools\sccache.exe",
+        ] {
+            assert_eq!(
+                compiler_launcher(None, Some(wrapper)).as_deref(),
+                Some(wrapper)
+            );
+        }
+        assert_eq!(compiler_launcher(None, Some("/usr/bin/cachepot")), None);
+        assert_eq!(compiler_launcher(None, Some("")), None);
+        assert_eq!(LAUNCHER_ENV, "OPUS_SYS_COMPILER_LAUNCHER");
+    }
+
+    #[test]
+    fn test_musl_compiler_prefers_cross_gcc() {
+        let target = "x86_64-unknown-linux-musl";
+        let all = |_: &str| true;
+        assert_eq!(
+            musl_compiler(target, HOST, all).as_deref(),
+            Some("x86_64-linux-musl-gcc")
+        );
+
+        let only_wrapper = |p: &str| p == "musl-gcc";
+        assert_eq!(
+            musl_compiler(target, HOST, only_wrapper).as_deref(),
+            Some("musl-gcc")
+        );
+        assert_eq!(musl_compiler(target, HOST, |_| false), None);
+    }
+
+    #[test]
+    fn test_musl_gcc_wrapper_is_host_only() {
+        let only_wrapper = |p: &str| p == "musl-gcc";
+        assert_eq!(
+            musl_compiler("aarch64-unknown-linux-musl", HOST, only_wrapper),
+            None
+        );
+        assert_eq!(
+            musl_compiler("armv7-unknown-linux-musleabihf", HOST, |_| true).as_deref(),
+            Some("armv7-linux-musleabihf-gcc")
+        );
+    }
+}
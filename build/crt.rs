@@ -0,0 +1,69 @@
+//! MSVC C runtime (CRT) selection
+//!
+//! Rust links the DLL runtime (`/MD`, msvcrt) by default and the static one
+//! (`/MT`, libcmt) with `-C target-feature=+crt-static`. Objects built for
+//! the other runtime fail to link (LNK2038 `RuntimeLibrary` mismatches,
+//! LNK4098 default library conflicts), so Opus has to follow. Its
+//! CMakeLists sets `CMAKE_MSVC_RUNTIME_LIBRARY` itself from
+//! `OPUS_STATIC_RUNTIME`, overriding a `-DCMAKE_MSVC_RUNTIME_LIBRARY`, so
+//! that option is what picks `MultiThreaded` over `MultiThreadedDLL`.
+//!
+//! Kept free of the cmake crate and cargo directives so it can be shared
+//! between build.rs and the tests in `tests/build_crt.rs`.
+
+/// Override for the runtime Opus links on windows-msvc
+pub const STATIC_CRT_ENV: &str = "OPUS_SYS_STATIC_CRT";
+
+/// Whether to build Opus against the static CRT.
+///
+/// `requested` is `OPUS_SYS_STATIC_CRT`: `1` forces the static runtime,
+/// `0` the DLL one, e.g. for a Rust build that links the CRT by hand. Unset
+/// or blank, the choice follows `crt-static` in `target_features`
+/// (`CARGO_CFG_TARGET_FEATURE`), which is what rustc links.
+pub fn static_crt(requested: Option<&str>, target_features: &str) -> Result<bool, String> {
+    match requested.map(str::trim) {
+        None | Some("") => Ok(target_features
+            .split(',')
+            .any(|feature| feature.trim() == "crt-static")),
+        Some("1") => Ok(true),
+        Some("0") => Ok(false),
+        Some(other) => Err(format!(
+            "Invalid {} '{}', expected 1 (static CRT) or 0 (DLL CRT)",
+            STATIC_CRT_ENV, other
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_follows_crt_static() {
+        assert_eq!(static_crt(None, ""), Ok(false));
+        assert_eq!(static_crt(None, "fxsr,sse,sse2"), Ok(false));
+        assert_eq!(static_crt(None, "crt-static,fxsr,sse"), Ok(true));
+        assert_eq!(static_crt(Some(" "), "fxsr, crt-static"), Ok(true));
+        // Only the exact feature
+        assert_eq!(static_crt(None, "crt-static-extra"), Ok(false));
+    }
+
+    #[test]
+    fn test_override() {
+        assert_eq!(static_crt(Some("1"), ""), Ok(true));
+        assert_eq!(static_crt(Some("0"), "crt-static"), Ok(false));
+        assert_eq!(static_crt(Some(" 1 "), ""), Ok(true));
+    }
+
+    #[test]
+    fn test_invalid_override() {
+        for value in ["yes", "2", "static"] {
+            let err = static_crt(Some(value), "").unwrap_err();
+            assert!(
+                err.contains(STATIC_CRT_ENV) && err.contains(value),
+                "{}",
+                err
+            );
+        }
+    }
+}
@@ -0,0 +1,173 @@
+//! Environment variables read by the build script
+//!
+//! Every read goes through [`EnvVars`], which remembers the name so the
+//! build script can declare `cargo:rerun-if-env-changed` for all of them
+//! at once; a variable read without it (SDKROOT, ANDROID_ABI, ...) would
+//! otherwise keep a stale library when it changes.
+//!
+//! Kept free of the cmake crate and cargo directives so it can be shared
+//! between build.rs and the tests in `tests/build_env_vars.rs`.
+
+use std::collections::BTreeSet;
+use std::ffi::OsString;
+use std::sync::Mutex;
+
+/// Records the names of the environment variables read through it
+pub struct EnvVars {
+    consulted: Mutex<BTreeSet<String>>,
+}
+
+impl EnvVars {
+    pub const fn new() -> Self {
+        EnvVars {
+            consulted: Mutex::new(BTreeSet::new()),
+        }
+    }
+
+    /// `std::env::var`, as an `Option`; non-Unicode values read as unset
+    pub fn var(&self, name: &str) -> Option<String> {
+        self.record(name);
+        std::env::var(name).ok()
+    }
+
+    /// `std::env::var_os`, for values that are paths
+    pub fn var_os(&self, name: &str) -> Option<OsString> {
+        self.record(name);
+        std::env::var_os(name)
+    }
+
+    fn record(&self, name: &str) {
+        self.consulted
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(name.to_string());
+    }
+
+    /// Every variable read so far, sorted
+    pub fn consulted(&self) -> Vec<String> {
+        self.consulted
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    /// The consulted variables that need `cargo:rerun-if-env-changed`: all
+    /// but those Cargo sets for the build script itself
+    pub fn rerun_vars(&self) -> Vec<String> {
+        self.consulted()
+            .into_iter()
+            .filter(|name| !set_by_cargo(name))
+            .collect()
+    }
+}
+
+/// Whether Cargo sets `name` for build scripts. Cargo reruns the script
+/// whenever these change (features, target, profile), and they are not in
+/// Cargo's own environment, so watching them would be meaningless.
+pub fn set_by_cargo(name: &str) -> bool {
+    const EXACT: &[&str] = &[
+        "TARGET",
+        "HOST",
+        "OUT_DIR",
+        "OPT_LEVEL",
+        "PROFILE",
+        "DEBUG",
+        "NUM_JOBS",
+        "RUSTC",
+        "RUSTDOC",
+        "CARGO_ENCODED_RUSTFLAGS",
+    ];
+    const PREFIXES: &[&str] = &[
+        "CARGO_FEATURE_",
+        "CARGO_CFG_",
+        "CARGO_MANIFEST_",
+        "CARGO_PKG_",
+    ];
+
+    EXACT.contains(&name) || PREFIXES.iter().any(|prefix| name.starts_with(prefix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_records_set_and_unset() {
+        let vars = EnvVars::new();
+        assert!(vars.consulted().is_empty());
+
+        // Set by `cargo test`, for the test binary itself
+        assert!(vars.var("CARGO_MANIFEST_DIR").is_some());
+        assert!(vars.var_os("CARGO_MANIFEST_DIR").is_some());
+        assert_eq!(vars.var("OPUS_SYS_ENV_VARS_TEST_UNSET"), None);
+
+        assert_eq!(
+            vars.consulted(),
+            ["CARGO_MANIFEST_DIR", "OPUS_SYS_ENV_VARS_TEST_UNSET"]
+        );
+    }
+
+    #[test]
+    fn test_rerun_vars_match_consulted() {
+        let vars = EnvVars::new();
+        for name in [
+            "ANDROID_ABI",
+            "IPHONEOS_DEPLOYMENT_TARGET",
+            "SDKROOT",
+            "CFLAGS",
+            "CFLAGS_aarch64_apple_ios",
+            "OPUS_SYS_CMAKE_PROFILE",
+            "PATH",
+            "CARGO_FEATURE_DRED",
+            "CARGO_CFG_TARGET_OS",
+            "OPT_LEVEL",
+            "PROFILE",
+            "TARGET",
+        ] {
+            vars.var(name);
+        }
+
+        let rerun = vars.rerun_vars();
+        let consulted = vars.consulted();
+        // Everything consulted is watched, unless Cargo already tracks it
+        for name in &consulted {
+            assert_eq!(rerun.contains(name), !set_by_cargo(name), "{}", name);
+        }
+        assert!(rerun.iter().all(|name| consulted.contains(name)));
+        assert_eq!(
+            rerun,
+            [
+                "ANDROID_ABI",
+                "CFLAGS",
+                "CFLAGS_aarch64_apple_ios",
+                "IPHONEOS_DEPLOYMENT_TARGET",
+                "OPUS_SYS_CMAKE_PROFILE",
+                "PATH",
+                "SDKROOT",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_set_by_cargo() {
+        for name in [
+            "TARGET",
+            "PROFILE",
+            "CARGO_FEATURE_NO_RTCD",
+            "CARGO_CFG_TARGET_ENV",
+        ] {
+            assert!(set_by_cargo(name), "{}", name);
+        }
+        for name in [
+            "CARGO_MAKEFLAGS",
+            "RUSTC_WRAPPER",
+            "TARGET_CC",
+            "CC",
+            "HOST_CFLAGS",
+        ] {
+            assert!(!set_by_cargo(name), "{}", name);
+        }
+    }
+}
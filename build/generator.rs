@@ -0,0 +1,179 @@
+//! CMake generator selection
+//!
+//! `OPUS_CMAKE_GENERATOR` picks the generator, e.g. `Ninja` on CI runners
+//! where make is slow or missing. A generator whose build tool isn't
+//! installed falls back to the cmake crate's default instead of failing in
+//! the configure step. CMake refuses to configure a build directory with a
+//! different generator than the one it was created with, so the cache is
+//! dropped when the generator changes.
+//!
+//! Cargo's `NUM_JOBS` becomes the `--parallel` value of the build step,
+//! which the cmake crate passes on unless make shares Cargo's jobserver.
+//!
+//! Kept free of the cmake crate and cargo directives so it can be shared
+//! between build.rs and the tests in `tests/build_generator.rs`.
+
+/// Generator for the CMake build, passed as `-G`
+pub const GENERATOR_ENV: &str = "OPUS_CMAKE_GENERATOR";
+
+/// What to configure the build with
+#[derive(Debug, PartialEq, Eq)]
+pub enum Generator {
+    /// The cmake crate's choice: `CMAKE_GENERATOR` if set, Visual Studio
+    /// for MSVC, otherwise CMake's default
+    Default,
+    /// The requested generator
+    Use(String),
+    /// The requested generator's build tool isn't installed
+    Missing { name: String, program: &'static str },
+}
+
+impl Generator {
+    /// The generator to pass as `-G`; `None` for the cmake crate's default,
+    /// which a missing build tool falls back to
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            Generator::Use(name) => Some(name),
+            Generator::Default | Generator::Missing { .. } => None,
+        }
+    }
+}
+
+/// The build tool a generator runs, `None` for generators that bring their
+/// own or that can't be checked (Visual Studio, unknown ones)
+pub fn build_program(generator: &str) -> Option<&'static str> {
+    match generator {
+        name if name.starts_with("Ninja") => Some("ninja"),
+        "Unix Makefiles" | "MSYS Makefiles" => Some("make"),
+        "MinGW Makefiles" => Some("mingw32-make"),
+        "NMake Makefiles" => Some("nmake"),
+        "Xcode" => Some("xcodebuild"),
+        _ => None,
+    }
+}
+
+/// Parse `OPUS_CMAKE_GENERATOR`; `installed` tells whether a program is on
+/// PATH
+pub fn generator(requested: Option<&str>, installed: impl Fn(&str) -> bool) -> Generator {
+    let Some(name) = requested.map(str::trim).filter(|s| !s.is_empty()) else {
+        return Generator::Default;
+    };
+    match build_program(name) {
+        Some(program) if !installed(program) => Generator::Missing {
+            name: name.to_string(),
+            program,
+        },
+        _ => Generator::Use(name.to_string()),
+    }
+}
+
+/// The job count for `cmake --build --parallel`, from Cargo's `NUM_JOBS`.
+/// `None` if it is unset, zero or not a number, which would fail the build
+/// step; the build tool's own default applies then.
+pub fn parallel_jobs(num_jobs: Option<&str>) -> Option<usize> {
+    num_jobs?.trim().parse().ok().filter(|&jobs| jobs > 0)
+}
+
+/// The generator recorded in a `CMakeCache.txt`
+pub fn cached_generator(cache: &str) -> Option<&str> {
+    cache
+        .lines()
+        .find_map(|line| line.strip_prefix("CMAKE_GENERATOR:INTERNAL="))
+}
+
+/// Whether a build directory configured with `cached` must be configured
+/// from scratch for `wanted`. With the default, CMake keeps the cached
+/// generator, except for MSVC, where the cmake crate always asks for
+/// Visual Studio.
+pub fn cache_is_stale(cached: &str, wanted: &Generator, msvc: bool) -> bool {
+    match wanted {
+        Generator::Use(name) => cached != name,
+        Generator::Default | Generator::Missing { .. } => {
+            msvc && !cached.starts_with("Visual Studio")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generator() {
+        let all = |_: &str| true;
+        assert_eq!(generator(None, all), Generator::Default);
+        assert_eq!(generator(Some(" "), all), Generator::Default);
+        assert_eq!(
+            generator(Some("Ninja"), all),
+            Generator::Use("Ninja".to_string())
+        );
+        assert_eq!(
+            generator(Some("Ninja Multi-Config"), |program| program == "ninja"),
+            Generator::Use("Ninja Multi-Config".to_string())
+        );
+        // Nothing to look for
+        assert_eq!(
+            generator(Some("Visual Studio 17 2022"), |_| false),
+            Generator::Use("Visual Studio 17 2022".to_string())
+        );
+        assert_eq!(GENERATOR_ENV, "OPUS_CMAKE_GENERATOR");
+    }
+
+    #[test]
+    fn test_missing_program() {
+        assert_eq!(
+            generator(Some("Ninja"), |program| program == "make"),
+            Generator::Missing {
+                name: "Ninja".to_string(),
+                program: "ninja"
+            }
+        );
+        assert_eq!(
+            generator(Some("MinGW Makefiles"), |_| false),
+            Generator::Missing {
+                name: "MinGW Makefiles".to_string(),
+                program: "mingw32-make"
+            }
+        );
+    }
+
+    #[test]
+    fn test_fallback_to_default() {
+        // Ninja requested on a runner with only make
+        let wanted = generator(Some("Ninja"), |program| program == "make");
+        assert_eq!(wanted.name(), None);
+        assert_eq!(wanted.name(), Generator::Default.name());
+        let wanted = generator(Some("Ninja"), |program| program == "ninja");
+        assert_eq!(wanted.name(), Some("Ninja"));
+    }
+
+    #[test]
+    fn test_parallel_jobs() {
+        assert_eq!(parallel_jobs(Some("8")), Some(8));
+        assert_eq!(parallel_jobs(Some(" 16\n")), Some(16));
+        for num_jobs in [None, Some(""), Some("0"), Some("-2"), Some("auto")] {
+            assert_eq!(parallel_jobs(num_jobs), None, "{:?}", num_jobs);
+        }
+    }
+
+    #[test]
+    fn test_stale_cache() {
+        let cache = "CMAKE_EXTRA_GENERATOR:INTERNAL=\n\
+                     CMAKE_GENERATOR:INTERNAL=Unix Makefiles\n\
+                     CMAKE_GENERATOR_PLATFORM:INTERNAL=\n";
+        let cached = cached_generator(cache).unwrap();
+        assert_eq!(cached, "Unix Makefiles");
+        assert_eq!(cached_generator("CMAKE_HOME_DIRECTORY:INTERNAL=/src"), None);
+
+        let ninja = Generator::Use("Ninja".to_string());
+        assert!(cache_is_stale(cached, &ninja, false));
+        assert!(!cache_is_stale("Ninja", &ninja, false));
+        assert!(!cache_is_stale(cached, &Generator::Default, false));
+        assert!(cache_is_stale("Ninja", &Generator::Default, true));
+        assert!(!cache_is_stale(
+            "Visual Studio 17 2022",
+            &Generator::Default,
+            true
+        ));
+    }
+}
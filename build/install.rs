@@ -0,0 +1,241 @@
+//! Locating the installed Opus library
+//!
+//! Where `cmake --install` puts the archive depends on the platform and
+//! generator: `lib` or `lib64` from GNUInstallDirs, `lib/x86_64-linux-gnu`
+//! with a Debian multiarch CMake, `lib/Release` with some multi-config
+//! generators. CMake records every installed file in `install_manifest.txt`,
+//! so that is read first, with a scan of the `lib*` directories as the
+//! fallback.
+//!
+//! Kept free of the cmake crate and cargo directives so it can be shared
+//! between build.rs and the tests in `tests/build_install.rs`.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// File names of the static library: ar archives and MSVC `.lib`
+const LIB_NAMES: [&str; 2] = ["libopus.a", "opus.lib"];
+
+/// The manifest CMake writes into the build directory, which the cmake
+/// crate places at `{dst}/build`
+pub fn manifest_path(dst: &Path) -> PathBuf {
+    dst.join("build").join("install_manifest.txt")
+}
+
+/// The static library listed in the contents of an install manifest (one
+/// absolute path per line)
+pub fn from_manifest(manifest: &str) -> Option<PathBuf> {
+    manifest
+        .lines()
+        .map(str::trim)
+        .map(PathBuf::from)
+        .find(|path| is_static_lib(path))
+}
+
+/// The installed static library under the install prefix `dst`, or an
+/// error listing every place that was searched
+pub fn find_static_lib(dst: &Path) -> Result<PathBuf, String> {
+    let manifest = manifest_path(dst);
+    if let Some(lib) = fs::read_to_string(&manifest)
+        .ok()
+        .and_then(|contents| from_manifest(&contents))
+        .filter(|lib| lib.is_file())
+    {
+        return Ok(lib);
+    }
+
+    let mut searched = Vec::new();
+    for dir in lib_dirs(dst) {
+        let found = LIB_NAMES
+            .iter()
+            .map(|name| dir.join(name))
+            .find(|path| path.is_file());
+        searched.push(dir);
+        if let Some(lib) = found {
+            return Ok(lib);
+        }
+    }
+
+    let mut message = format!(
+        "No Opus static library ({}) found; searched:\n  {}",
+        LIB_NAMES.join(" or "),
+        manifest.display()
+    );
+    for dir in &searched {
+        message.push_str(&format!("\n  {}", dir.display()));
+    }
+    Err(message)
+}
+
+fn is_static_lib(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| LIB_NAMES.contains(&name))
+}
+
+/// `lib`, `lib64` and other `lib*` directories of the prefix, each
+/// followed by its subdirectories (multiarch triples, configurations), in
+/// a stable order
+fn lib_dirs(dst: &Path) -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    for dir in sorted_subdirs(dst) {
+        if !dir
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with("lib"))
+        {
+            continue;
+        }
+        let nested = sorted_subdirs(&dir);
+        dirs.push(dir);
+        dirs.extend(nested);
+    }
+    dirs
+}
+
+fn sorted_subdirs(dir: &Path) -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .collect();
+    dirs.sort();
+    dirs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// An install prefix holding `files`, relative to it
+    fn prefix(name: &str, files: &[&str]) -> PathBuf {
+        let dst = temp_dir(name);
+        for file in files {
+            let path = dst.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, b"!<arch>\n").unwrap();
+        }
+        dst
+    }
+
+    fn write_manifest(dst: &Path, files: &[&str]) {
+        let lines: Vec<String> = files
+            .iter()
+            .map(|file| dst.join(file).display().to_string())
+            .collect();
+        fs::create_dir_all(dst.join("build")).unwrap();
+        fs::write(manifest_path(dst), lines.join("\n")).unwrap();
+    }
+
+    #[test]
+    fn test_from_manifest() {
+        let manifest = "/out/include/opus/opus.h\n\
+            /out/lib/x86_64-linux-gnu/libopus.a\n\
+            /out/lib/x86_64-linux-gnu/pkgconfig/opus.pc\n";
+        assert_eq!(
+            from_manifest(manifest),
+            Some(PathBuf::from("/out/lib/x86_64-linux-gnu/libopus.a"))
+        );
+        assert_eq!(
+            from_manifest("C:/out/include/opus/opus.h\r\nC:/out/lib/opus.lib\r\n"),
+            Some(PathBuf::from("C:/out/lib/opus.lib"))
+        );
+        assert_eq!(from_manifest("/out/lib/libopusfile.a\n"), None);
+        assert_eq!(from_manifest(""), None);
+    }
+
+    #[test]
+    fn test_linux_multiarch() {
+        let files = [
+            "include/opus/opus.h",
+            "lib/x86_64-linux-gnu/libopus.a",
+            "lib/x86_64-linux-gnu/pkgconfig/opus.pc",
+        ];
+        let dst = prefix("install_linux_multiarch", &files);
+        let expected = dst.join("lib/x86_64-linux-gnu/libopus.a");
+        assert_eq!(find_static_lib(&dst), Ok(expected.clone()));
+
+        write_manifest(&dst, &files);
+        assert_eq!(find_static_lib(&dst), Ok(expected));
+
+        let dst = prefix("install_linux_lib64", &["lib64/libopus.a"]);
+        assert_eq!(find_static_lib(&dst), Ok(dst.join("lib64/libopus.a")));
+    }
+
+    #[test]
+    fn test_illumos() {
+        // GNUInstallDirs on illumos and Solaris uses the ISA directory
+        let files = ["include/opus/opus.h", "lib/amd64/libopus.a"];
+        let dst = prefix("install_illumos", &files);
+        let expected = dst.join("lib/amd64/libopus.a");
+        assert_eq!(find_static_lib(&dst), Ok(expected.clone()));
+
+        write_manifest(&dst, &files);
+        assert_eq!(find_static_lib(&dst), Ok(expected));
+    }
+
+    #[test]
+    fn test_macos() {
+        let files = [
+            "include/opus/opus.h",
+            "lib/libopus.a",
+            "lib/pkgconfig/opus.pc",
+        ];
+        let dst = prefix("install_macos", &files);
+        write_manifest(&dst, &files);
+        assert_eq!(find_static_lib(&dst), Ok(dst.join("lib/libopus.a")));
+    }
+
+    #[test]
+    fn test_msvc() {
+        let dst = prefix("install_msvc_config", &["lib/Release/opus.lib"]);
+        assert_eq!(find_static_lib(&dst), Ok(dst.join("lib/Release/opus.lib")));
+
+        let files = ["include/opus/opus.h", "lib/opus.lib"];
+        let dst = prefix("install_msvc", &files);
+        write_manifest(&dst, &files);
+        assert_eq!(find_static_lib(&dst), Ok(dst.join("lib/opus.lib")));
+    }
+
+    #[test]
+    fn test_stale_manifest() {
+        // The manifest names a library that is gone; the scan still finds
+        // the real one
+        let dst = prefix("install_stale_manifest", &["lib64/libopus.a"]);
+        write_manifest(&dst, &["lib/libopus.a"]);
+        assert_eq!(find_static_lib(&dst), Ok(dst.join("lib64/libopus.a")));
+    }
+
+    #[test]
+    fn test_not_found() {
+        let dst = prefix(
+            "install_not_found",
+            &["include/opus/opus.h", "lib/pkgconfig/opus.pc"],
+        );
+        let err = find_static_lib(&dst).unwrap_err();
+        assert!(err.contains("libopus.a or opus.lib"), "{}", err);
+        for searched in [
+            manifest_path(&dst),
+            dst.join("lib"),
+            dst.join("lib/pkgconfig"),
+        ] {
+            assert!(
+                err.contains(&searched.display().to_string()),
+                "{} not in {}",
+                searched.display(),
+                err
+            );
+        }
+        // Not a library directory
+        assert!(!err.contains(&dst.join("include").display().to_string()));
+    }
+}
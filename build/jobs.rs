@@ -0,0 +1,53 @@
+//! Parallelism of the CMake build step
+//!
+//! Kept free of the cmake crate and cargo directives so it can be shared
+//! between build.rs and the tests in `tests/build_jobs.rs`.
+
+/// Override for the number of parallel compile jobs
+pub const JOBS_ENV: &str = "OPUS_SYS_BUILD_JOBS";
+
+/// Parse the `OPUS_SYS_BUILD_JOBS` override.
+///
+/// `None` (unset or blank) leaves parallelism to the cmake crate, which
+/// shares Cargo's jobserver with Makefile generators and otherwise passes
+/// Cargo's `NUM_JOBS` as `--parallel`. A positive number forces that many
+/// jobs for every generator.
+pub fn build_jobs(requested: Option<&str>) -> Result<Option<usize>, String> {
+    let Some(requested) = requested.map(str::trim).filter(|s| !s.is_empty()) else {
+        return Ok(None);
+    };
+
+    match requested.parse::<usize>() {
+        Ok(jobs) if jobs > 0 => Ok(Some(jobs)),
+        _ => Err(format!(
+            "Invalid {} '{}', expected a positive number of jobs",
+            JOBS_ENV, requested
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unset_defers_to_cargo() {
+        assert_eq!(build_jobs(None), Ok(None));
+        assert_eq!(build_jobs(Some("")), Ok(None));
+        assert_eq!(build_jobs(Some("  ")), Ok(None));
+    }
+
+    #[test]
+    fn test_job_count() {
+        assert_eq!(build_jobs(Some("1")), Ok(Some(1)));
+        assert_eq!(build_jobs(Some(" 16 ")), Ok(Some(16)));
+    }
+
+    #[test]
+    fn test_invalid_job_count() {
+        for value in ["0", "-4", "many", "2.5"] {
+            let err = build_jobs(Some(value)).unwrap_err();
+            assert!(err.contains(JOBS_ENV) && err.contains(value), "{}", err);
+        }
+    }
+}
@@ -0,0 +1,158 @@
+//! Cross-language LTO (the `lto` feature)
+//!
+//! The C objects are compiled to LLVM bitcode with clang, which the linker
+//! then optimizes together with rustc's bitcode. That only works when
+//! clang's LLVM matches rustc's and the Rust side passes
+//! `-C linker-plugin-lto`.
+//!
+//! Kept free of the cmake crate and cargo directives so it can be shared
+//! between build.rs and the tests in `tests/build_lto.rs`.
+
+/// The LLVM major version in `rustc -vV` output
+pub fn rustc_llvm_major(verbose_version: &str) -> Option<u32> {
+    verbose_version
+        .lines()
+        .find_map(|line| line.strip_prefix("LLVM version:"))
+        .and_then(major)
+}
+
+/// The clang major version in `<compiler> --version` output, e.g. `19`
+/// for `Ubuntu clang version 19.1.1 (1ubuntu1)`. Apple clang is versioned
+/// separately from LLVM, so it is rejected along with gcc.
+pub fn clang_major(version_output: &str) -> Result<u32, String> {
+    let first = version_output.lines().next().unwrap_or_default().trim();
+    if first.starts_with("Apple clang") {
+        return Err(format!(
+            "{} does not say which LLVM it is based on; install clang from LLVM",
+            first
+        ));
+    }
+    first
+        .split_once("clang version ")
+        .and_then(|(_, version)| major(version))
+        .ok_or_else(|| format!("{} is not clang", first))
+}
+
+/// Check that `compiler` (with `--version` output `version_output`) can
+/// produce bitcode for rustc's LLVM `rustc_llvm`
+pub fn check_compiler(compiler: &str, version_output: &str, rustc_llvm: u32) -> Result<(), String> {
+    let clang = clang_major(version_output).map_err(|e| {
+        format!(
+            "The lto feature needs clang as the C compiler ({}): {}",
+            compiler, e
+        )
+    })?;
+    if clang != rustc_llvm {
+        return Err(format!(
+            "The lto feature needs clang {} to match rustc's LLVM, but {} is clang {}; set CC to clang-{}",
+            rustc_llvm, compiler, clang, rustc_llvm
+        ));
+    }
+    Ok(())
+}
+
+/// Whether `CARGO_ENCODED_RUSTFLAGS` enables `-C linker-plugin-lto`. The
+/// flags are separated by `\x1f`, so `-C` and its value may be apart.
+pub fn has_linker_plugin_lto(encoded_rustflags: &str) -> bool {
+    let flags: Vec<&str> = encoded_rustflags.split('\x1f').collect();
+    let mut codegen = Vec::new();
+    for (i, flag) in flags.iter().enumerate() {
+        if let Some(option) = flag.strip_prefix("-C") {
+            codegen.push(if option.is_empty() {
+                flags.get(i + 1).copied().unwrap_or_default()
+            } else {
+                option
+            });
+        } else if let Some(option) = flag.strip_prefix("--codegen=") {
+            codegen.push(option);
+        }
+    }
+    // The last occurrence wins, as in rustc
+    codegen
+        .iter()
+        .rev()
+        .find_map(|option| {
+            let value = option.strip_prefix("linker-plugin-lto")?;
+            match value.strip_prefix('=') {
+                None if value.is_empty() => Some(true),
+                Some("n" | "no" | "off" | "false") => Some(false),
+                Some(_) => Some(true),
+                None => None,
+            }
+        })
+        .unwrap_or(false)
+}
+
+fn major(version: &str) -> Option<u32> {
+    version.trim().split(['.', ' ', '-']).next()?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rustc_llvm_major() {
+        let verbose = "rustc 1.85.0 (4d91de4e4 2025-02-17)\n\
+            binary: rustc\n\
+            host: x86_64-unknown-linux-gnu\n\
+            release: 1.85.0\n\
+            LLVM version: 19.1.7\n";
+        assert_eq!(rustc_llvm_major(verbose), Some(19));
+        assert_eq!(rustc_llvm_major("rustc 1.85.0\n"), None);
+    }
+
+    #[test]
+    fn test_clang_major() {
+        assert_eq!(clang_major("clang version 19.1.7\nTarget: x86_64"), Ok(19));
+        assert_eq!(
+            clang_major("Ubuntu clang version 18.1.3 (1ubuntu1)\nTarget: x86_64"),
+            Ok(18)
+        );
+        assert_eq!(
+            clang_major("Homebrew clang version 20.1.0\nTarget: arm64-apple-darwin24"),
+            Ok(20)
+        );
+
+        let err = clang_major("Apple clang version 16.0.0 (clang-1600.0.26.6)").unwrap_err();
+        assert!(err.contains("Apple clang"), "{}", err);
+        let err = clang_major("cc (Debian 12.2.0-14) 12.2.0\n").unwrap_err();
+        assert!(err.contains("not clang"), "{}", err);
+    }
+
+    #[test]
+    fn test_check_compiler() {
+        assert_eq!(check_compiler("clang", "clang version 19.1.7", 19), Ok(()));
+
+        let err = check_compiler("clang", "clang version 18.1.8", 19).unwrap_err();
+        assert!(
+            err.contains("clang-19") && err.contains("clang 18"),
+            "{}",
+            err
+        );
+        let err = check_compiler("gcc", "gcc (GCC) 14.2.1", 19).unwrap_err();
+        assert!(err.contains("gcc") && err.contains("lto"), "{}", err);
+    }
+
+    #[test]
+    fn test_has_linker_plugin_lto() {
+        for flags in [
+            "-Clinker-plugin-lto",
+            "-C\x1flinker-plugin-lto",
+            "-Copt-level=3\x1f-Clinker-plugin-lto=yes",
+            "--codegen=linker-plugin-lto",
+            "-Clinker-plugin-lto=/usr/lib/LLVMgold.so",
+        ] {
+            assert!(has_linker_plugin_lto(flags), "{:?}", flags);
+        }
+        for flags in [
+            "",
+            "-Clto",
+            "-Clinker-plugin-lto\x1f-Clinker-plugin-lto=no",
+            "-Clinker-plugin-ltox",
+            "-Ctarget-cpu=native",
+        ] {
+            assert!(!has_linker_plugin_lto(flags), "{:?}", flags);
+        }
+    }
+}
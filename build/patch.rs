@@ -0,0 +1,368 @@
+//! Build-time source patching
+//!
+//! Applies a unified diff shipped with the crate to a copy of the vendored
+//! Opus tree, without relying on `git` or `patch` being installed. Kept free
+//! of the cmake crate and cargo directives so it can be shared between
+//! build.rs and the tests in `tests/build_patch.rs`.
+
+use std::fs;
+use std::path::Path;
+
+/// Name of the stamp file written into a finished patched tree
+pub const STAMP_FILE: &str = ".opus-head-sys-patch";
+
+/// Paths (relative to the Opus root) and extensions not needed to build,
+/// mirroring the `exclude` list in Cargo.toml
+const SKIP_DIRS: [&str; 6] = ["doc", "tests", "training", "scripts", "meson", "dnn/torch"];
+const SKIP_EXTENSIONS: [&str; 5] = ["pth", "ipynb", "tar", "py", "zip"];
+
+/// The changes a patch makes to one file
+#[derive(Debug, PartialEq, Eq)]
+pub struct FilePatch {
+    pub path: String,
+    hunks: Vec<Hunk>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Hunk {
+    old: Vec<String>,
+    new: Vec<String>,
+}
+
+/// 64-bit FNV-1a, used to fingerprint patch inputs and verify outputs
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| {
+        (hash ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Parse a git-style unified diff. Text before the first `diff --git` line
+/// (the patch description) is ignored.
+pub fn parse(diff: &str) -> Result<Vec<FilePatch>, String> {
+    let mut files: Vec<FilePatch> = Vec::new();
+    // Lines still expected in the current hunk (old side, new side)
+    let mut remaining = (0usize, 0usize);
+
+    for line in diff.lines().map(|l| l.trim_end_matches('\r')) {
+        if remaining != (0, 0) {
+            let hunk = files
+                .last_mut()
+                .and_then(|f| f.hunks.last_mut())
+                .ok_or("hunk content without a hunk")?;
+            // Some editors strip the single space of empty context lines
+            let (kind, text) = match line.chars().next() {
+                Some(c @ (' ' | '-' | '+')) => (c, &line[1..]),
+                None => (' ', ""),
+                Some('\\') => continue, // "\ No newline at end of file"
+                Some(_) => return Err(format!("unexpected line in hunk: {}", line)),
+            };
+            if kind != '+' {
+                hunk.old.push(text.to_string());
+                remaining.0 = remaining.0.checked_sub(1).ok_or("hunk too long")?;
+            }
+            if kind != '-' {
+                hunk.new.push(text.to_string());
+                remaining.1 = remaining.1.checked_sub(1).ok_or("hunk too long")?;
+            }
+        } else if let Some(path) = line.strip_prefix("+++ b/") {
+            files.push(FilePatch {
+                path: path.to_string(),
+                hunks: Vec::new(),
+            });
+        } else if let Some(header) = line.strip_prefix("@@ -") {
+            let file = files
+                .last_mut()
+                .ok_or_else(|| format!("hunk before any file header: {}", line))?;
+            remaining = parse_hunk_header(header)
+                .ok_or_else(|| format!("malformed hunk header: {}", line))?;
+            file.hunks.push(Hunk {
+                old: Vec::new(),
+                new: Vec::new(),
+            });
+        }
+    }
+
+    if remaining != (0, 0) {
+        return Err("patch ends in the middle of a hunk".into());
+    }
+    if files.is_empty() {
+        return Err("patch contains no files".into());
+    }
+    Ok(files)
+}
+
+/// Line counts from `-start[,count] +start[,count] @@`
+fn parse_hunk_header(header: &str) -> Option<(usize, usize)> {
+    let mut ranges = header.split(' ');
+    let count = |range: &str| match range.split_once(',') {
+        Some((_, count)) => count.parse().ok(),
+        None => Some(1),
+    };
+    let old = count(ranges.next()?)?;
+    let new = count(ranges.next()?.strip_prefix('+')?)?;
+    Some((old, new))
+}
+
+impl FilePatch {
+    /// Apply all hunks in order. Context must match exactly (ignoring CRLF
+    /// line endings, which are preserved); there is no fuzz.
+    pub fn apply(&self, original: &str) -> Result<String, String> {
+        let crlf = original.contains("\r\n");
+        let mut lines: Vec<String> = original
+            .split('\n')
+            .map(|l| l.trim_end_matches('\r').to_string())
+            .collect();
+        let mut cursor = 0;
+
+        for (i, hunk) in self.hunks.iter().enumerate() {
+            let start = lines
+                .len()
+                .checked_sub(hunk.old.len())
+                .and_then(|end| {
+                    (cursor..=end).find(|&at| lines[at..at + hunk.old.len()] == hunk.old[..])
+                })
+                .ok_or_else(|| format!("{}: hunk {} does not apply", self.path, i + 1))?;
+
+            lines.splice(start..start + hunk.old.len(), hunk.new.iter().cloned());
+            cursor = start + hunk.new.len();
+        }
+
+        Ok(lines.join(if crlf { "\r\n" } else { "\n" }))
+    }
+}
+
+/// Create `dest` as a copy of `src` with `patch` applied, or reuse it if a
+/// previous run already produced it from the same inputs.
+///
+/// All hunks are applied in memory before anything is written, the copy is
+/// assembled in a staging directory, every patched file is re-read and
+/// checked against the expected hash, and only then is the staging directory
+/// renamed into place. An interrupted run therefore never leaves a
+/// half-patched `dest` behind. `extra` is mixed into the stamp so callers can
+/// invalidate the copy when other inputs (e.g. the vendored version) change.
+///
+/// Returns `true` if the tree was (re)created, `false` if it was reused.
+pub fn prepare_patched_tree(
+    src: &Path,
+    dest: &Path,
+    patch: &str,
+    extra: &[u8],
+) -> Result<bool, String> {
+    let files = parse(patch)?;
+
+    let mut patched = Vec::new();
+    let mut fingerprint = patch.replace('\r', "").into_bytes();
+    fingerprint.extend_from_slice(extra);
+    for file in &files {
+        let path = src.join(&file.path);
+        let original = fs::read_to_string(&path)
+            .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+        let result = file.apply(&original)?;
+        fingerprint.extend_from_slice(original.as_bytes());
+        patched.push((file.path.as_str(), fnv1a(result.as_bytes()), result));
+    }
+    let stamp = format!("{:016x}", fnv1a(&fingerprint));
+
+    let verify = |root: &Path| -> Result<(), String> {
+        for (path, hash, _) in &patched {
+            let bytes = fs::read(root.join(path))
+                .map_err(|e| format!("failed to read back {}: {}", path, e))?;
+            if fnv1a(&bytes) != *hash {
+                return Err(format!("{} does not match the patched hash", path));
+            }
+        }
+        Ok(())
+    };
+
+    let existing = fs::read_to_string(dest.join(STAMP_FILE)).unwrap_or_default();
+    if existing.trim() == stamp && verify(dest).is_ok() {
+        return Ok(false);
+    }
+
+    let staging = dest.with_extension("staging");
+    remove_dir_if_exists(&staging)?;
+    copy_tree(src, &staging, Path::new(""))?;
+    for (path, _, contents) in &patched {
+        fs::write(staging.join(path), contents)
+            .map_err(|e| format!("failed to write {}: {}", path, e))?;
+    }
+    verify(&staging)?;
+    fs::write(staging.join(STAMP_FILE), &stamp).map_err(|e| e.to_string())?;
+
+    remove_dir_if_exists(dest)?;
+    fs::rename(&staging, dest).map_err(|e| {
+        format!(
+            "failed to move {} to {}: {}",
+            staging.display(),
+            dest.display(),
+            e
+        )
+    })?;
+    Ok(true)
+}
+
+fn remove_dir_if_exists(dir: &Path) -> Result<(), String> {
+    if dir.exists() {
+        fs::remove_dir_all(dir)
+            .map_err(|e| format!("failed to remove {}: {}", dir.display(), e))?;
+    }
+    Ok(())
+}
+
+fn copy_tree(src: &Path, dest: &Path, rel: &Path) -> Result<(), String> {
+    fs::create_dir_all(dest.join(rel)).map_err(|e| e.to_string())?;
+
+    let entries = fs::read_dir(src.join(rel))
+        .map_err(|e| format!("failed to read {}: {}", rel.display(), e))?;
+    for entry in entries {
+        let entry = entry.map_err(|e| e.to_string())?;
+        let name = entry.file_name();
+        let rel_path = rel.join(&name);
+        let rel_str = rel_path.to_string_lossy().replace('\\', "/");
+
+        if name.to_string_lossy().starts_with(".git") || SKIP_DIRS.contains(&rel_str.as_str()) {
+            continue;
+        }
+
+        let file_type = entry.file_type().map_err(|e| e.to_string())?;
+        if file_type.is_dir() {
+            copy_tree(src, dest, &rel_path)?;
+        } else {
+            let skipped = rel_path
+                .extension()
+                .is_some_and(|ext| SKIP_EXTENSIONS.contains(&ext.to_string_lossy().as_ref()));
+            if !skipped {
+                fs::copy(entry.path(), dest.join(&rel_path))
+                    .map_err(|e| format!("failed to copy {}: {}", rel_str, e))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    const SAMPLE_PATCH: &str = "\
+Subject: sample
+
+diff --git a/a.txt b/a.txt
+--- a/a.txt
++++ b/a.txt
+@@ -1,3 +1,3 @@
+ one
+-two
++TWO
+ three
+@@ -5,2 +5,3 @@
+ five
++five and a half
+ six
+";
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_apply_hunks() {
+        let files = parse(SAMPLE_PATCH).unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].path, "a.txt");
+
+        let result = files[0]
+            .apply("one\ntwo\nthree\nfour\nfive\nsix\n")
+            .unwrap();
+        assert_eq!(
+            result,
+            "one\nTWO\nthree\nfour\nfive\nfive and a half\nsix\n"
+        );
+    }
+
+    #[test]
+    fn test_apply_preserves_crlf() {
+        let files = parse(&SAMPLE_PATCH.replace('\n', "\r\n")).unwrap();
+        let result = files[0]
+            .apply("one\r\ntwo\r\nthree\r\nfour\r\nfive\r\nsix\r\n")
+            .unwrap();
+        assert_eq!(
+            result,
+            "one\r\nTWO\r\nthree\r\nfour\r\nfive\r\nfive and a half\r\nsix\r\n"
+        );
+    }
+
+    #[test]
+    fn test_apply_rejects_mismatched_context() {
+        let files = parse(SAMPLE_PATCH).unwrap();
+        let err = files[0].apply("one\nzwei\nthree\n").unwrap_err();
+        assert!(err.contains("hunk 1"), "{}", err);
+
+        // Already patched input must not be patched a second time
+        let patched = "one\nTWO\nthree\nfour\nfive\nfive and a half\nsix\n";
+        assert!(files[0].apply(patched).is_err());
+    }
+
+    #[test]
+    fn test_prepare_is_idempotent_and_skips_excluded() {
+        let root = temp_dir("patch_idempotent");
+        let src = root.join("src");
+        fs::create_dir_all(src.join("doc")).unwrap();
+        fs::write(src.join("a.txt"), "one\ntwo\nthree\nfour\nfive\nsix\n").unwrap();
+        fs::write(src.join("doc").join("big.html"), "skip me").unwrap();
+        fs::write(src.join("model.pth"), "skip me").unwrap();
+        let dest = root.join("patched");
+
+        assert!(prepare_patched_tree(&src, &dest, SAMPLE_PATCH, b"v1").unwrap());
+        assert!(fs::read_to_string(dest.join("a.txt"))
+            .unwrap()
+            .contains("TWO"));
+        assert!(!dest.join("doc").exists());
+        assert!(!dest.join("model.pth").exists());
+        assert!(!dest.with_extension("staging").exists());
+
+        // Same inputs: reused as is
+        assert!(!prepare_patched_tree(&src, &dest, SAMPLE_PATCH, b"v1").unwrap());
+
+        // Tampered output fails hash verification and is rebuilt
+        fs::write(dest.join("a.txt"), "one\ntwo\n").unwrap();
+        assert!(prepare_patched_tree(&src, &dest, SAMPLE_PATCH, b"v1").unwrap());
+        assert!(fs::read_to_string(dest.join("a.txt"))
+            .unwrap()
+            .contains("TWO"));
+
+        // Changed extra input invalidates the stamp
+        assert!(prepare_patched_tree(&src, &dest, SAMPLE_PATCH, b"v2").unwrap());
+    }
+
+    #[test]
+    fn test_failed_patch_leaves_dest_untouched() {
+        let root = temp_dir("patch_failure");
+        let src = root.join("src");
+        fs::create_dir_all(&src).unwrap();
+        fs::write(src.join("a.txt"), "unrelated\n").unwrap();
+        let dest = root.join("patched");
+
+        assert!(prepare_patched_tree(&src, &dest, SAMPLE_PATCH, b"").is_err());
+        assert!(!dest.exists());
+        assert!(!dest.with_extension("staging").exists());
+    }
+
+    #[test]
+    fn test_windows_arm64_patch_applies_to_vendored_opus() {
+        let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let patch =
+            fs::read_to_string(manifest_dir.join("build/windows-arm64-rtcd.patch")).unwrap();
+        let opus_dir = manifest_dir.join("vendored").join("opus");
+
+        for file in parse(&patch).unwrap() {
+            let original = fs::read_to_string(opus_dir.join(&file.path)).unwrap();
+            let result = file.apply(&original).unwrap();
+            assert_ne!(result, original, "{} unchanged", file.path);
+        }
+    }
+}
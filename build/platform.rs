@@ -0,0 +1,76 @@
+//! Settings for Unix targets outside Opus' usual platforms
+//!
+//! Linux, Android, the BSDs and Apple build with Opus' CMake defaults.
+//! illumos, Solaris and Haiku need a few changes, mapped here from
+//! `target_os`; the library itself installs wherever GNUInstallDirs puts
+//! it (`lib/amd64` and the like), which `install::find_static_lib` reads
+//! from CMake's manifest.
+//!
+//! Kept free of the cmake crate and cargo directives so it can be shared
+//! between build.rs and the tests in `tests/build_platform.rs`.
+
+/// What a target needs beyond the defaults
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Platform {
+    /// CMake cache entries, passed as `-D<name>=<value>`
+    pub defines: Vec<(&'static str, &'static str)>,
+    /// System libraries `libopus.a` calls into, linked after it
+    pub link_libs: Vec<&'static str>,
+}
+
+/// The settings for `target_os`, empty for targets that need none
+pub fn platform(target_os: &str) -> Platform {
+    match target_os {
+        // libm is a library of its own there; link it after libopus.a
+        // instead of relying on where std puts it on the link line
+        "illumos" | "solaris" => Platform {
+            link_libs: vec!["m"],
+            ..Platform::default()
+        },
+        // The math functions are in libroot, which everything links. GCC's
+        // -fstack-protector-strong needs libssp, which rustc doesn't link,
+        // and the system headers have no _FORTIFY_SOURCE support.
+        "haiku" => Platform {
+            defines: vec![
+                ("OPUS_STACK_PROTECTOR", "OFF"),
+                ("OPUS_FORTIFY_SOURCE", "OFF"),
+            ],
+            ..Platform::default()
+        },
+        _ => Platform::default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_illumos_and_solaris_link_libm() {
+        for os in ["illumos", "solaris"] {
+            let platform = platform(os);
+            assert_eq!(platform.link_libs, ["m"], "{}", os);
+            assert!(platform.defines.is_empty(), "{}", os);
+        }
+    }
+
+    #[test]
+    fn test_haiku_disables_hardening() {
+        let platform = platform("haiku");
+        assert_eq!(
+            platform.defines,
+            [
+                ("OPUS_STACK_PROTECTOR", "OFF"),
+                ("OPUS_FORTIFY_SOURCE", "OFF")
+            ]
+        );
+        assert!(platform.link_libs.is_empty());
+    }
+
+    #[test]
+    fn test_other_targets_use_defaults() {
+        for os in ["linux", "macos", "windows", "freebsd", "android", ""] {
+            assert_eq!(platform(os), Platform::default(), "{}", os);
+        }
+    }
+}
@@ -0,0 +1,182 @@
+//! Symbol prefixing (the `prefix-symbols` feature)
+//!
+//! Another libopus in the same process, e.g. one bundled by a browser
+//! engine, defines the same global symbols. Linking both fails with
+//! duplicate symbols or, with shared libraries, silently binds calls to
+//! whichever copy the loader finds first. With the feature, every global
+//! symbol the archive defines is renamed with objcopy (`opus_encode` to
+//! `opus_head_encode`, `celt_fir_c` to `opus_head_celt_fir_c`) and the
+//! bindings are compiled with matching `#[link_name]`s.
+//!
+//! Kept free of the cmake crate and cargo directives so it can be shared
+//! between build.rs and the tests in `tests/build_prefix.rs`.
+
+/// Prefix of every renamed symbol
+pub const PREFIX: &str = "opus_head_";
+
+/// The renamed symbol: `opus_` is replaced by [`PREFIX`], anything else
+/// gets it prepended
+pub fn prefixed(symbol: &str) -> String {
+    match symbol.strip_prefix("opus_") {
+        Some(rest) => format!("{}{}", PREFIX, rest),
+        None => format!("{}{}", PREFIX, symbol),
+    }
+}
+
+/// An objcopy `--redefine-syms` file for the global symbols of the archive
+/// index. `underscore` is set where C names are decorated with a leading
+/// `_` (Apple, 32-bit Windows); the decoration stays in front. Compiler
+/// generated globals such as `__real@3f800000`, `.refptr.x` or
+/// `__stack_chk_guard` are left alone, as are names that already carry the
+/// prefix.
+pub fn redefine_syms(symbols: &[String], underscore: bool) -> String {
+    let mut lines = String::new();
+    for symbol in symbols {
+        let (decoration, name) = match symbol.strip_prefix('_') {
+            Some(name) if underscore => ("_", name),
+            _ => ("", symbol.as_str()),
+        };
+        // Opus has a few `_celt_*` functions; `__` names belong to the
+        // compiler and runtime
+        let identifier = name
+            .trim_start_matches('_')
+            .starts_with(|c: char| c.is_ascii_alphabetic())
+            && !name.starts_with("__")
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !identifier || name.starts_with(PREFIX) {
+            continue;
+        }
+        lines.push_str(&format!(
+            "{}{} {}{}\n",
+            decoration,
+            name,
+            decoration,
+            prefixed(name)
+        ));
+    }
+    lines
+}
+
+/// objcopy builds to try, in order. GNU objcopy only handles the ELF and
+/// PE formats of its own host reliably, so Mach-O and MSVC archives need
+/// llvm-objcopy.
+pub fn objcopy_candidates(target_vendor: &str, target_env: &str) -> &'static [&'static str] {
+    if target_vendor == "apple" || target_env == "msvc" {
+        &["llvm-objcopy"]
+    } else {
+        &["objcopy", "llvm-objcopy"]
+    }
+}
+
+/// bindgen output with a `#[link_name]` on every function, for `include!`
+/// into the bindings module. Inner attributes (`#![allow(...)]`) can't be
+/// included and are dropped; the including module carries them instead.
+pub fn prefix_bindings(bindings: &str) -> String {
+    let mut out = String::with_capacity(bindings.len() * 11 / 10);
+    for line in bindings.lines() {
+        if line.starts_with("#![") {
+            continue;
+        }
+        if let Some(rest) = line.trim_start().strip_prefix("pub fn ") {
+            let name: String = rest
+                .chars()
+                .take_while(|c| c.is_alphanumeric() || *c == '_')
+                .collect();
+            let indent = &line[..line.len() - line.trim_start().len()];
+            out.push_str(&format!(
+                "{}#[link_name = \"{}\"]\n",
+                indent,
+                prefixed(&name)
+            ));
+        }
+        out.push_str(line);
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_prefixed() {
+        assert_eq!(prefixed("opus_encoder_create"), "opus_head_encoder_create");
+        assert_eq!(prefixed("opus_select_arch"), "opus_head_select_arch");
+        assert_eq!(prefixed("celt_fir_c"), "opus_head_celt_fir_c");
+        assert_eq!(
+            prefixed("silk_encode_frame_FLP"),
+            "opus_head_silk_encode_frame_FLP"
+        );
+    }
+
+    #[test]
+    fn test_redefine_syms() {
+        let symbols = names(&[
+            "opus_encode",
+            "celt_fir_c",
+            "_celt_lpc",
+            "__real@3f800000",
+            ".refptr.celt_fir_c",
+            "opus_head_encode",
+        ]);
+        assert_eq!(
+            redefine_syms(&symbols, false),
+            "opus_encode opus_head_encode\n\
+             celt_fir_c opus_head_celt_fir_c\n\
+             _celt_lpc opus_head__celt_lpc\n"
+        );
+
+        let apple = names(&[
+            "_opus_encode",
+            "_silk_A2NLSF",
+            "__celt_lpc",
+            "___stack_chk_guard",
+        ]);
+        assert_eq!(
+            redefine_syms(&apple, true),
+            "_opus_encode _opus_head_encode\n\
+             _silk_A2NLSF _opus_head_silk_A2NLSF\n\
+             __celt_lpc _opus_head__celt_lpc\n"
+        );
+    }
+
+    #[test]
+    fn test_objcopy_candidates() {
+        assert_eq!(
+            objcopy_candidates("unknown", "gnu"),
+            ["objcopy", "llvm-objcopy"]
+        );
+        assert_eq!(objcopy_candidates("apple", ""), ["llvm-objcopy"]);
+        assert_eq!(objcopy_candidates("pc", "msvc"), ["llvm-objcopy"]);
+    }
+
+    #[test]
+    fn test_prefix_bindings() {
+        let bindings = "/* automatically generated by rust-bindgen 0.72.1 */\n\
+            \n\
+            #![allow(rustdoc::broken_intra_doc_links)]\n\
+            \n\
+            pub const OPUS_OK: u32 = 0;\n\
+            unsafe extern \"C\" {\n\
+            \x20   #[doc = \" Gets the size\"]\n\
+            \x20   pub fn opus_encoder_get_size(channels: ::std::os::raw::c_int) -> ::std::os::raw::c_int;\n\
+            }\n";
+        assert_eq!(
+            prefix_bindings(bindings),
+            "/* automatically generated by rust-bindgen 0.72.1 */\n\
+            \n\
+            \n\
+            pub const OPUS_OK: u32 = 0;\n\
+            unsafe extern \"C\" {\n\
+            \x20   #[doc = \" Gets the size\"]\n\
+            \x20   #[link_name = \"opus_head_encoder_get_size\"]\n\
+            \x20   pub fn opus_encoder_get_size(channels: ::std::os::raw::c_int) -> ::std::os::raw::c_int;\n\
+            }\n"
+        );
+    }
+}
@@ -0,0 +1,303 @@
+//! Checks for the tools the Opus build needs, before CMake runs
+//!
+//! Without them the build fails deep inside the cmake crate or CMake's
+//! compiler detection with an OS error. The preflight names everything
+//! that is missing at once, with the package or variable that fixes it.
+//!
+//! Kept free of the cmake crate and cargo directives so it can be shared
+//! between build.rs and the tests in `tests/build_preflight.rs`.
+
+/// What the build is about to use
+#[derive(Debug, Default)]
+pub struct Toolchain<'a> {
+    pub host: &'a str,
+    pub target: &'a str,
+    pub target_os: &'a str,
+    pub target_env: &'a str,
+    /// CMake binary from `CMAKE` (or a target/host variant), as the cmake
+    /// crate reads it
+    pub cmake: Option<&'a str>,
+    /// C compiler set through `CC`, `TARGET_CC` or `CROSS_COMPILE`
+    pub c_compiler: Option<&'a str>,
+    /// A CMake toolchain file picks the compilers and sysroot itself
+    pub toolchain_file: bool,
+    /// `SDKROOT` is set, so Apple builds don't need xcrun
+    pub sdkroot: bool,
+    /// `ANDROID_NDK_HOME` or `ANDROID_NDK_ROOT` is set
+    pub android_ndk: bool,
+    /// The compiler is chosen and checked elsewhere (the `lto` feature
+    /// defaults to clang, musl targets look for musl-gcc)
+    pub compiler_checked_elsewhere: bool,
+}
+
+/// A tool that isn't there
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Missing {
+    /// The tool, e.g. "cmake"
+    pub tool: String,
+    /// How to get it
+    pub fix: String,
+}
+
+/// Apple embedded targets, whose SDK comes from xcrun
+const APPLE_EMBEDDED: [&str; 4] = ["ios", "tvos", "watchos", "visionos"];
+
+/// Everything `toolchain` needs that `find` (a PATH lookup outside tests)
+/// can't find
+pub fn missing(toolchain: &Toolchain, find: impl Fn(&str) -> bool) -> Vec<Missing> {
+    let mut missing = Vec::new();
+    let windows_host = toolchain.host.contains("windows");
+
+    // On Windows the cmake crate also finds the CMake that comes with
+    // Visual Studio, which isn't on PATH
+    match toolchain.cmake {
+        Some(cmake) if !find(cmake) => missing.push(Missing {
+            tool: format!("cmake ({})", cmake),
+            fix: "CMAKE points to a program that does not exist; fix or unset it".to_string(),
+        }),
+        None if !windows_host && !find("cmake") => missing.push(Missing {
+            tool: "cmake".to_string(),
+            fix: format!(
+                "install CMake 3.16 or newer (e.g. {}), or set CMAKE to its path",
+                match host_os(toolchain.host) {
+                    "illumos" | "solaris" => "`pkg install cmake` or `pkgin install cmake`",
+                    "haiku" => "`pkgman install cmake`",
+                    _ => "`apt install cmake`, `dnf install cmake` or `brew install cmake`",
+                }
+            ),
+        }),
+        _ => {}
+    }
+
+    if toolchain.toolchain_file {
+        return missing;
+    }
+
+    if let Some(compiler) = toolchain.c_compiler {
+        if !find(compiler) {
+            missing.push(Missing {
+                tool: format!("C compiler ({})", compiler),
+                fix: "CC, TARGET_CC or CROSS_COMPILE names a compiler that is not installed; install it or fix the variable".to_string(),
+            });
+        }
+    } else if toolchain.target == toolchain.host
+        && toolchain.target_env != "msvc"
+        && !toolchain.compiler_checked_elsewhere
+    {
+        // cc-rs, which picks the compiler for the cmake crate, defaults to
+        // gcc for MinGW and cc everywhere else
+        let default = if windows_host { "gcc" } else { "cc" };
+        if !find(default) {
+            missing.push(Missing {
+                tool: format!("C compiler ({})", default),
+                fix: if toolchain.target_os == "macos" {
+                    "install the Xcode command line tools (`xcode-select --install`), or set CC".to_string()
+                } else if windows_host {
+                    "install MinGW-w64 and put its bin directory on PATH, or set CC".to_string()
+                } else if matches!(toolchain.target_os, "illumos" | "solaris") {
+                    "install GCC (e.g. `pkg install gcc13` or `pkgin install gcc13`), or set CC".to_string()
+                } else if toolchain.target_os == "haiku" {
+                    "install GCC (`pkgman install gcc`), or set CC".to_string()
+                } else {
+                    "install a C compiler (e.g. `apt install build-essential`, `dnf install gcc` or `apk add build-base`), or set CC".to_string()
+                },
+            });
+        }
+    }
+
+    if APPLE_EMBEDDED.contains(&toolchain.target_os) && !toolchain.sdkroot && !find("xcrun") {
+        missing.push(Missing {
+            tool: "xcrun".to_string(),
+            fix: format!(
+                "{} builds need Xcode (`xcode-select --install` is not enough for device SDKs), or SDKROOT set to the SDK",
+                toolchain.target
+            ),
+        });
+    }
+
+    if toolchain.target_os == "android" && !toolchain.android_ndk {
+        missing.push(Missing {
+            tool: "Android NDK".to_string(),
+            fix: "set ANDROID_NDK_HOME (or ANDROID_NDK_ROOT) to the NDK directory, or CMAKE_TOOLCHAIN_FILE to a toolchain file".to_string(),
+        });
+    }
+
+    missing
+}
+
+/// The OS part of a host triple, e.g. `illumos` for `x86_64-unknown-illumos`
+fn host_os(host: &str) -> &str {
+    ["illumos", "solaris", "haiku"]
+        .into_iter()
+        .find(|os| host.ends_with(os))
+        .unwrap_or("")
+}
+
+/// One error naming every missing tool
+pub fn message(target: &str, missing: &[Missing]) -> String {
+    let mut message = format!("Missing build tools for {}:", target);
+    for tool in missing {
+        message.push_str(&format!("\n  - {}: {}", tool.tool, tool.fix));
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LINUX: &str = "x86_64-unknown-linux-gnu";
+
+    fn linux() -> Toolchain<'static> {
+        Toolchain {
+            host: LINUX,
+            target: LINUX,
+            target_os: "linux",
+            target_env: "gnu",
+            ..Toolchain::default()
+        }
+    }
+
+    fn tools(missing: &[Missing]) -> Vec<&str> {
+        missing.iter().map(|m| m.tool.as_str()).collect()
+    }
+
+    #[test]
+    fn test_all_present() {
+        assert!(missing(&linux(), |_| true).is_empty());
+    }
+
+    #[test]
+    fn test_native_linux() {
+        let found = missing(&linux(), |_| false);
+        assert_eq!(tools(&found), ["cmake", "C compiler (cc)"]);
+        assert!(found[0].fix.contains("apt install cmake"));
+        assert!(found[1].fix.contains("build-essential"));
+
+        // Only what is missing is reported
+        assert_eq!(
+            tools(&missing(&linux(), |tool| tool == "cmake")),
+            ["C compiler (cc)"]
+        );
+    }
+
+    #[test]
+    fn test_overrides() {
+        let toolchain = Toolchain {
+            cmake: Some("/opt/cmake/bin/cmake"),
+            c_compiler: Some("aarch64-linux-gnu-gcc"),
+            target: "aarch64-unknown-linux-gnu",
+            ..linux()
+        };
+        let found = missing(&toolchain, |tool| tool == "cmake" || tool == "cc");
+        assert_eq!(
+            tools(&found),
+            [
+                "cmake (/opt/cmake/bin/cmake)",
+                "C compiler (aarch64-linux-gnu-gcc)"
+            ]
+        );
+
+        // Cross builds without CC leave the compiler to cc-rs, and a
+        // toolchain file to CMake
+        let cross = Toolchain {
+            target: "aarch64-unknown-linux-gnu",
+            ..linux()
+        };
+        assert!(missing(&cross, |tool| tool == "cmake").is_empty());
+        let toolchain_file = Toolchain {
+            toolchain_file: true,
+            c_compiler: Some("missing-cc"),
+            ..linux()
+        };
+        assert!(missing(&toolchain_file, |tool| tool == "cmake").is_empty());
+    }
+
+    #[test]
+    fn test_platforms() {
+        let msvc = Toolchain {
+            host: "x86_64-pc-windows-msvc",
+            target: "x86_64-pc-windows-msvc",
+            target_os: "windows",
+            target_env: "msvc",
+            ..Toolchain::default()
+        };
+        assert!(missing(&msvc, |_| false).is_empty());
+
+        let ios = Toolchain {
+            host: "aarch64-apple-darwin",
+            target: "aarch64-apple-ios",
+            target_os: "ios",
+            ..Toolchain::default()
+        };
+        let found = missing(&ios, |tool| tool == "cmake");
+        assert_eq!(tools(&found), ["xcrun"]);
+        assert!(found[0].fix.contains("aarch64-apple-ios"));
+        let with_sdkroot = Toolchain {
+            sdkroot: true,
+            ..ios
+        };
+        assert!(missing(&with_sdkroot, |tool| tool == "cmake").is_empty());
+
+        let android = Toolchain {
+            target: "aarch64-linux-android",
+            target_os: "android",
+            ..linux()
+        };
+        let found = missing(&android, |_| true);
+        assert_eq!(tools(&found), ["Android NDK"]);
+        assert!(found[0].fix.contains("ANDROID_NDK_HOME"));
+        let with_ndk = Toolchain {
+            android_ndk: true,
+            ..android
+        };
+        assert!(missing(&with_ndk, |_| true).is_empty());
+    }
+
+    #[test]
+    fn test_other_unixes() {
+        for (triple, os, cmake, cc) in [
+            (
+                "x86_64-unknown-illumos",
+                "illumos",
+                "pkg install cmake",
+                "pkg install gcc13",
+            ),
+            (
+                "x86_64-pc-solaris",
+                "solaris",
+                "pkg install cmake",
+                "pkg install gcc13",
+            ),
+            (
+                "x86_64-unknown-haiku",
+                "haiku",
+                "pkgman install cmake",
+                "pkgman install gcc",
+            ),
+        ] {
+            let native = Toolchain {
+                host: triple,
+                target: triple,
+                target_os: os,
+                ..Toolchain::default()
+            };
+            let found = missing(&native, |_| false);
+            assert_eq!(tools(&found), ["cmake", "C compiler (cc)"]);
+            assert!(found[0].fix.contains(cmake), "{}", found[0].fix);
+            assert!(found[1].fix.contains(cc), "{}", found[1].fix);
+        }
+    }
+
+    #[test]
+    fn test_message() {
+        let found = missing(&linux(), |_| false);
+        assert_eq!(
+            message(LINUX, &found),
+            format!(
+                "Missing build tools for {}:\n  - cmake: {}\n  - C compiler (cc): {}",
+                LINUX, found[0].fix, found[1].fix
+            )
+        );
+    }
+}
@@ -0,0 +1,110 @@
+//! CMake build type selection
+//!
+//! Kept free of the cmake crate and cargo directives so it can be shared
+//! between build.rs and the tests in `tests/build_profile.rs`.
+
+/// Build types accepted by the `OPUS_SYS_CMAKE_PROFILE` override
+pub const CMAKE_PROFILES: [&str; 4] = ["Debug", "Release", "RelWithDebInfo", "MinSizeRel"];
+
+/// Override variables, in order of precedence. `OPUS_CMAKE_PROFILE` is the
+/// older name and still honored.
+pub const OVERRIDE_ENV: [&str; 2] = ["OPUS_SYS_CMAKE_PROFILE", "OPUS_CMAKE_PROFILE"];
+
+/// Pick the CMake build type for the Opus build.
+///
+/// An override wins (matched case-insensitively). Otherwise the Cargo
+/// `OPT_LEVEL` decides, falling back to `PROFILE` when it is unset:
+///
+/// - `0`: `Debug`, except on windows-msvc where a Debug build links the
+///   debug CRT (MSVCRTD) that conflicts with Rust's, so `RelWithDebInfo`
+/// - `s` / `z`: `MinSizeRel`
+/// - anything else: `Release`
+pub fn cmake_profile(
+    requested: Option<&str>,
+    opt_level: Option<&str>,
+    cargo_profile: Option<&str>,
+    msvc: bool,
+) -> Result<&'static str, String> {
+    if let Some(requested) = requested {
+        return CMAKE_PROFILES
+            .iter()
+            .copied()
+            .find(|p| p.eq_ignore_ascii_case(requested.trim()))
+            .ok_or_else(|| {
+                format!(
+                    "Invalid CMake profile '{}', expected one of: {}",
+                    requested,
+                    CMAKE_PROFILES.join(", ")
+                )
+            });
+    }
+
+    let opt_level = match opt_level {
+        Some(level) => level,
+        None if cargo_profile == Some("release") => "3",
+        None => "0",
+    };
+
+    Ok(match opt_level {
+        "0" if msvc => "RelWithDebInfo",
+        "0" => "Debug",
+        "s" | "z" => "MinSizeRel",
+        _ => "Release",
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn select(opt_level: &str, msvc: bool) -> &'static str {
+        cmake_profile(None, Some(opt_level), None, msvc).unwrap()
+    }
+
+    #[test]
+    fn test_opt_level() {
+        assert_eq!(select("0", false), "Debug");
+        assert_eq!(select("1", false), "Release");
+        assert_eq!(select("2", false), "Release");
+        assert_eq!(select("3", false), "Release");
+        assert_eq!(select("s", false), "MinSizeRel");
+        assert_eq!(select("z", false), "MinSizeRel");
+    }
+
+    #[test]
+    fn test_msvc_never_uses_debug_crt() {
+        assert_eq!(select("0", true), "RelWithDebInfo");
+        assert_eq!(select("3", true), "Release");
+        assert_eq!(select("z", true), "MinSizeRel");
+    }
+
+    #[test]
+    fn test_profile_fallback() {
+        assert_eq!(
+            cmake_profile(None, None, Some("release"), false),
+            Ok("Release")
+        );
+        assert_eq!(cmake_profile(None, None, Some("debug"), false), Ok("Debug"));
+        assert_eq!(cmake_profile(None, None, None, true), Ok("RelWithDebInfo"));
+    }
+
+    #[test]
+    fn test_override() {
+        assert_eq!(
+            cmake_profile(Some(" release "), Some("0"), None, false),
+            Ok("Release")
+        );
+        // An explicit Debug is honored even on MSVC
+        assert_eq!(
+            cmake_profile(Some("debug"), Some("3"), None, true),
+            Ok("Debug")
+        );
+        let err = cmake_profile(Some("Fast"), None, None, false).unwrap_err();
+        assert!(
+            err.contains("'Fast'") && err.contains("MinSizeRel"),
+            "{}",
+            err
+        );
+        assert_eq!(OVERRIDE_ENV[0], "OPUS_SYS_CMAKE_PROFILE");
+    }
+}
@@ -0,0 +1,219 @@
+//! Runtime CPU detection (RTCD) configuration
+//!
+//! Kept free of the cmake crate and cargo directives so it can be shared
+//! between build.rs and the tests in `tests/build_rtcd.rs`.
+
+/// CMake options and C macros that pin Opus to a fixed ISA level
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct PresumedIsa {
+    /// CMake cache entries, passed as `-D<name>=<value>`
+    pub cmake_defines: Vec<(&'static str, &'static str)>,
+    /// Preprocessor macros Opus's CMake does not define for the presume path
+    pub c_defines: Vec<&'static str>,
+}
+
+/// x86 ISA levels Opus can dispatch on, with the target features each requires
+const X86_LEVELS: [(&str, &str, &[&str]); 4] = [
+    ("OPUS_X86_MAY_HAVE_SSE", "OPUS_X86_PRESUME_SSE", &["sse"]),
+    ("OPUS_X86_MAY_HAVE_SSE2", "OPUS_X86_PRESUME_SSE2", &["sse2"]),
+    (
+        "OPUS_X86_MAY_HAVE_SSE4_1",
+        "OPUS_X86_PRESUME_SSE4_1",
+        &["sse4.1"],
+    ),
+    (
+        "OPUS_X86_MAY_HAVE_AVX2",
+        "OPUS_X86_PRESUME_AVX2",
+        &["avx", "avx2", "fma"],
+    ),
+];
+
+/// Compute the configuration that builds Opus without RTCD dispatch.
+///
+/// Every ISA level implied by `target_features` (the comma separated
+/// `CARGO_CFG_TARGET_FEATURE`) is presumed; all others are switched off
+/// entirely, so the build falls back to the baseline C code for them.
+pub fn presumed_isa(target_arch: &str, target_features: &[&str]) -> PresumedIsa {
+    let has = |feature: &str| target_features.contains(&feature);
+    let mut isa = PresumedIsa::default();
+
+    match target_arch {
+        "x86" | "x86_64" => {
+            for (may_have, presume, required) in X86_LEVELS {
+                // PRESUME is a dependent option of MAY_HAVE in Opus's CMake, so
+                // both stay on; with PRESUME set no runtime check is compiled in
+                let value = if required.iter().all(|f| has(f)) {
+                    "ON"
+                } else {
+                    "OFF"
+                };
+                isa.cmake_defines.push((may_have, value));
+                isa.cmake_defines.push((presume, value));
+            }
+        }
+        "arm" | "aarch64" => {
+            // MAY_HAVE_NEON is what turns on RTCD for ARM, so it must be off.
+            // The NEON headers still gate on OPUS_ARM_MAY_HAVE_NEON_INTR, which
+            // CMake only defines alongside MAY_HAVE_NEON, so define it here.
+            isa.cmake_defines.push(("OPUS_MAY_HAVE_NEON", "OFF"));
+            if has("neon") {
+                isa.cmake_defines.push(("OPUS_PRESUME_NEON", "ON"));
+                isa.c_defines
+                    .extend(["OPUS_ARM_MAY_HAVE_NEON_INTR", "OPUS_ARM_PRESUME_NEON_INTR"]);
+            } else {
+                isa.cmake_defines.push(("OPUS_PRESUME_NEON", "OFF"));
+            }
+        }
+        _ => {}
+    }
+
+    isa
+}
+
+/// ARM target features Opus has kernels for: the CMake option presuming
+/// it, and the macro MSVC doesn't define but the kernels check for
+const ARM_FEATURES: [(&str, &str, &str); 2] = [
+    ("neon", "OPUS_PRESUME_NEON", "__ARM_NEON"),
+    ("dotprod", "OPUS_PRESUME_DOTPROD", "__ARM_FEATURE_DOTPROD"),
+];
+
+/// ARM target features with no Opus kernels to enable
+pub const ARM_UNUSED_FEATURES: [&str; 2] = ["i8mm", "fp16"];
+
+/// Presume the ARM features in `target_features` while keeping RTCD for
+/// the rest.
+///
+/// The DOTPROD kernels are only built for DRED/OSCE, so `dotprod` is
+/// skipped without `dred` or `osce`. No target features means no defines at all,
+/// leaving Opus's own defaults.
+pub fn arm_features(target_features: &[&str], msvc: bool, dnn: bool) -> PresumedIsa {
+    let mut isa = PresumedIsa::default();
+    for (feature, presume, msvc_macro) in ARM_FEATURES {
+        if !target_features.contains(&feature) || (feature == "dotprod" && !dnn) {
+            continue;
+        }
+        isa.cmake_defines.push((presume, "ON"));
+        if msvc {
+            isa.c_defines.push(msvc_macro);
+        }
+    }
+    isa
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn define<'a>(isa: &'a PresumedIsa, name: &str) -> Option<&'a str> {
+        isa.cmake_defines
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, v)| *v)
+    }
+
+    #[test]
+    fn test_x86_64_presumes_avx2_and_sse41() {
+        let features = ["fxsr", "sse", "sse2", "sse4.1", "avx", "avx2", "fma"];
+        let isa = presumed_isa("x86_64", &features);
+
+        for (may_have, presume, _) in X86_LEVELS {
+            assert_eq!(define(&isa, may_have), Some("ON"), "{}", may_have);
+            assert_eq!(define(&isa, presume), Some("ON"), "{}", presume);
+        }
+        assert!(isa.c_defines.is_empty());
+    }
+
+    #[test]
+    fn test_x86_64_baseline_disables_higher_levels() {
+        let isa = presumed_isa("x86_64", &["fxsr", "sse", "sse2"]);
+
+        assert_eq!(define(&isa, "OPUS_X86_PRESUME_SSE2"), Some("ON"));
+        assert_eq!(define(&isa, "OPUS_X86_MAY_HAVE_SSE4_1"), Some("OFF"));
+        assert_eq!(define(&isa, "OPUS_X86_PRESUME_SSE4_1"), Some("OFF"));
+        assert_eq!(define(&isa, "OPUS_X86_MAY_HAVE_AVX2"), Some("OFF"));
+        assert_eq!(define(&isa, "OPUS_X86_PRESUME_AVX2"), Some("OFF"));
+    }
+
+    #[test]
+    fn test_x86_avx2_requires_fma() {
+        let features = ["sse", "sse2", "sse4.1", "avx", "avx2"];
+        let isa = presumed_isa("x86_64", &features);
+
+        assert_eq!(define(&isa, "OPUS_X86_PRESUME_SSE4_1"), Some("ON"));
+        assert_eq!(define(&isa, "OPUS_X86_MAY_HAVE_AVX2"), Some("OFF"));
+    }
+
+    #[test]
+    fn test_x86_no_features_falls_back_to_c() {
+        let isa = presumed_isa("x86", &[]);
+
+        assert_eq!(isa.cmake_defines.len(), X86_LEVELS.len() * 2);
+        assert!(isa.cmake_defines.iter().all(|(_, v)| *v == "OFF"));
+    }
+
+    #[test]
+    fn test_aarch64_presumes_neon() {
+        let isa = presumed_isa("aarch64", &["neon", "fp-armv8"]);
+
+        assert_eq!(define(&isa, "OPUS_MAY_HAVE_NEON"), Some("OFF"));
+        assert_eq!(define(&isa, "OPUS_PRESUME_NEON"), Some("ON"));
+        assert_eq!(
+            isa.c_defines,
+            ["OPUS_ARM_MAY_HAVE_NEON_INTR", "OPUS_ARM_PRESUME_NEON_INTR"]
+        );
+    }
+
+    #[test]
+    fn test_arm_without_neon_falls_back_to_c() {
+        let isa = presumed_isa("arm", &["v7"]);
+
+        assert_eq!(define(&isa, "OPUS_MAY_HAVE_NEON"), Some("OFF"));
+        assert_eq!(define(&isa, "OPUS_PRESUME_NEON"), Some("OFF"));
+        assert!(isa.c_defines.is_empty());
+    }
+
+    #[test]
+    fn test_arm_features_gcc() {
+        let isa = arm_features(
+            &["neon", "dotprod", "fp-armv8", "i8mm", "fp16"],
+            false,
+            true,
+        );
+
+        assert_eq!(
+            isa.cmake_defines,
+            [("OPUS_PRESUME_NEON", "ON"), ("OPUS_PRESUME_DOTPROD", "ON")]
+        );
+        // GCC and Clang define the feature macros themselves
+        assert!(isa.c_defines.is_empty());
+    }
+
+    #[test]
+    fn test_arm_features_msvc() {
+        let isa = arm_features(&["neon", "dotprod"], true, true);
+
+        assert_eq!(isa.c_defines, ["__ARM_NEON", "__ARM_FEATURE_DOTPROD"]);
+    }
+
+    #[test]
+    fn test_arm_dotprod_needs_dnn() {
+        let isa = arm_features(&["neon", "dotprod"], true, false);
+
+        assert_eq!(isa.cmake_defines, [("OPUS_PRESUME_NEON", "ON")]);
+        assert_eq!(isa.c_defines, ["__ARM_NEON"]);
+    }
+
+    #[test]
+    fn test_arm_no_features_leaves_defaults() {
+        assert_eq!(arm_features(&[], true, true), PresumedIsa::default());
+        assert_eq!(
+            arm_features(&ARM_UNUSED_FEATURES, false, true),
+            PresumedIsa::default()
+        );
+    }
+
+    #[test]
+    fn test_other_arch_is_untouched() {
+        assert_eq!(presumed_isa("riscv64", &["d"]), PresumedIsa::default());
+    }
+}
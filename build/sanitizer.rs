@@ -0,0 +1,201 @@
+//! Sanitizer instrumentation for the C build
+//!
+//! `-Z sanitizer=address` only instruments Rust code, so an overflow inside
+//! libopus goes unnoticed unless the C objects get the matching
+//! `-fsanitize` flags. Cargo reports rustc's sanitizers to build scripts as
+//! `CARGO_CFG_SANITIZE`; `OPUS_SYS_SANITIZER` overrides them.
+//!
+//! Kept free of the cmake crate and cargo directives so it can be shared
+//! between build.rs and the tests in `tests/build_sanitizer.rs`.
+
+/// Comma separated sanitizers for the C build, e.g. `address,undefined`,
+/// or `none`
+pub const SANITIZER_ENV: &str = "OPUS_SYS_SANITIZER";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sanitizer {
+    Address,
+    HwAddress,
+    Leak,
+    Memory,
+    Thread,
+    /// Not a rustc sanitizer; only requested through [`SANITIZER_ENV`]
+    Undefined,
+}
+
+impl Sanitizer {
+    /// Parse a `-Z sanitizer` / `-fsanitize` name
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "address" => Some(Self::Address),
+            "hwaddress" => Some(Self::HwAddress),
+            "leak" => Some(Self::Leak),
+            "memory" => Some(Self::Memory),
+            "thread" => Some(Self::Thread),
+            "undefined" => Some(Self::Undefined),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Address => "address",
+            Self::HwAddress => "hwaddress",
+            Self::Leak => "leak",
+            Self::Memory => "memory",
+            Self::Thread => "thread",
+            Self::Undefined => "undefined",
+        }
+    }
+
+    /// Whether the instrumented C code calls into a sanitizer runtime. Only
+    /// rustc's `-Z sanitizer` links one, so these need the same sanitizer
+    /// on the Rust side.
+    pub fn needs_runtime(self) -> bool {
+        self != Self::Undefined
+    }
+
+    /// Compiler flags, or an error if the compiler has no such sanitizer
+    pub fn cflags(self, msvc: bool) -> Result<&'static [&'static str], String> {
+        if msvc {
+            return match self {
+                Self::Address => Ok(&["/fsanitize=address"]),
+                _ => Err(format!("MSVC has no {} sanitizer", self.name())),
+            };
+        }
+        Ok(match self {
+            Self::Address => &["-fsanitize=address", "-fno-omit-frame-pointer"],
+            Self::HwAddress => &["-fsanitize=hwaddress", "-fno-omit-frame-pointer"],
+            Self::Leak => &["-fsanitize=leak"],
+            Self::Memory => &["-fsanitize=memory", "-fno-omit-frame-pointer"],
+            Self::Thread => &["-fsanitize=thread"],
+            // Traps rather than reporting, so no UBSan runtime is needed
+            Self::Undefined => &["-fsanitize=undefined", "-fsanitize-undefined-trap-on-error"],
+        })
+    }
+}
+
+/// Sanitizers for the C build. `requested` is OPUS_SYS_SANITIZER and wins
+/// when set; otherwise the build follows `cfg_sanitize`
+/// (`CARGO_CFG_SANITIZE`), skipping rustc sanitizers that have no C
+/// counterpart such as `cfi` or `safestack`.
+pub fn sanitizers(
+    requested: Option<&str>,
+    cfg_sanitize: Option<&str>,
+) -> Result<Vec<Sanitizer>, String> {
+    let mut sanitizers = Vec::new();
+    match requested.map(str::trim).filter(|s| !s.is_empty()) {
+        Some("none") => {}
+        Some(requested) => {
+            for name in requested.split(',').map(str::trim) {
+                let sanitizer = Sanitizer::parse(name).ok_or_else(|| {
+                    format!(
+                        "{}: unknown sanitizer '{}' (expected address, hwaddress, leak, memory, thread, undefined or none)",
+                        SANITIZER_ENV, name
+                    )
+                })?;
+                sanitizers.push(sanitizer);
+            }
+        }
+        None => sanitizers.extend(
+            cfg_sanitize
+                .unwrap_or_default()
+                .split(',')
+                .filter_map(|name| Sanitizer::parse(name.trim())),
+        ),
+    }
+    let mut unique = Vec::new();
+    for sanitizer in sanitizers {
+        if !unique.contains(&sanitizer) {
+            unique.push(sanitizer);
+        }
+    }
+    Ok(unique)
+}
+
+/// Sanitizers in `sanitizers` whose runtime rustc doesn't link, because
+/// they aren't in `cfg_sanitize`. The final link fails with undefined
+/// `__asan_*` (or similar) symbols for these.
+pub fn missing_runtimes(sanitizers: &[Sanitizer], cfg_sanitize: Option<&str>) -> Vec<Sanitizer> {
+    let rustc: Vec<&str> = cfg_sanitize
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .collect();
+    sanitizers
+        .iter()
+        .copied()
+        .filter(|s| s.needs_runtime() && !rustc.contains(&s.name()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_follows_rustc() {
+        assert_eq!(sanitizers(None, None), Ok(vec![]));
+        assert_eq!(
+            sanitizers(None, Some("address")),
+            Ok(vec![Sanitizer::Address])
+        );
+        assert_eq!(
+            sanitizers(Some(" "), Some("cfi,memory,safestack")),
+            Ok(vec![Sanitizer::Memory])
+        );
+    }
+
+    #[test]
+    fn test_override() {
+        assert_eq!(
+            sanitizers(Some("address, undefined,address"), None),
+            Ok(vec![Sanitizer::Address, Sanitizer::Undefined])
+        );
+        assert_eq!(sanitizers(Some("none"), Some("address")), Ok(vec![]));
+
+        let err = sanitizers(Some("address,cfi"), None).unwrap_err();
+        assert!(
+            err.contains(SANITIZER_ENV) && err.contains("'cfi'"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_missing_runtimes() {
+        let both = [Sanitizer::Address, Sanitizer::Undefined];
+        assert_eq!(missing_runtimes(&both, Some("address")), []);
+        assert_eq!(missing_runtimes(&both, None), [Sanitizer::Address]);
+        assert_eq!(
+            missing_runtimes(&[Sanitizer::Thread], Some("address,leak")),
+            [Sanitizer::Thread]
+        );
+    }
+
+    #[test]
+    fn test_cflags() {
+        assert_eq!(
+            Sanitizer::Address.cflags(false).unwrap()[0],
+            "-fsanitize=address"
+        );
+        assert_eq!(
+            Sanitizer::Address.cflags(true),
+            Ok(&["/fsanitize=address"][..])
+        );
+        assert!(Sanitizer::Undefined.cflags(true).is_err());
+        for name in [
+            "address",
+            "hwaddress",
+            "leak",
+            "memory",
+            "thread",
+            "undefined",
+        ] {
+            let sanitizer = Sanitizer::parse(name).unwrap();
+            assert_eq!(sanitizer.name(), name);
+            let flags = sanitizer.cflags(false).unwrap();
+            assert_eq!(flags[0], format!("-fsanitize={}", name));
+        }
+    }
+}
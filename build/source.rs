@@ -0,0 +1,111 @@
+//! Location of the Opus source tree
+//!
+//! Kept free of the cmake crate and cargo directives so it can be shared
+//! between build.rs and the tests in `tests/build_source.rs`.
+
+use std::path::{Path, PathBuf};
+
+/// Override for the Opus source tree, e.g. a checkout with local patches
+pub const SOURCE_DIR_ENV: &str = "OPUS_SOURCE_DIR";
+
+/// Files that every Opus source tree has, relative to its root
+pub const REQUIRED_FILES: [&str; 2] = ["CMakeLists.txt", "include/opus.h"];
+
+/// Where the Opus sources come from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    /// `vendored/opus` in this crate
+    Vendored(PathBuf),
+    /// A tree named by `OPUS_SOURCE_DIR`
+    External(PathBuf),
+}
+
+/// Pick the source tree. `requested` is OPUS_SOURCE_DIR: unset or blank
+/// keeps `vendored`, otherwise it must be an absolute path to a directory
+/// holding [`REQUIRED_FILES`]. `is_file` is [`Path::is_file`] outside tests.
+pub fn source_dir(
+    requested: Option<&str>,
+    vendored: &Path,
+    is_file: impl Fn(&Path) -> bool,
+) -> Result<Source, String> {
+    let Some(requested) = requested.map(str::trim).filter(|s| !s.is_empty()) else {
+        return Ok(Source::Vendored(vendored.to_path_buf()));
+    };
+
+    // Relative to what would be ambiguous: build scripts run in the
+    // package directory, not where cargo was invoked
+    let dir = PathBuf::from(requested);
+    if !dir.is_absolute() {
+        return Err(format!(
+            "{} must be an absolute path, got '{}'",
+            SOURCE_DIR_ENV, requested
+        ));
+    }
+
+    let missing: Vec<&str> = REQUIRED_FILES
+        .iter()
+        .copied()
+        .filter(|file| !is_file(&dir.join(file)))
+        .collect();
+    if !missing.is_empty() {
+        return Err(format!(
+            "{}={} does not look like an Opus source tree (missing {})",
+            SOURCE_DIR_ENV,
+            dir.display(),
+            missing.join(", ")
+        ));
+    }
+
+    Ok(Source::External(dir))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn root() -> PathBuf {
+        if cfg!(windows) {
+            PathBuf::from(r"C:\src\opus")
+        } else {
+            PathBuf::from("/src/opus")
+        }
+    }
+
+    #[test]
+    fn test_unset_uses_vendored() {
+        let vendored = Path::new("vendored/opus");
+        for requested in [None, Some(""), Some("  ")] {
+            assert_eq!(
+                source_dir(requested, vendored, |_| false),
+                Ok(Source::Vendored(vendored.to_path_buf()))
+            );
+        }
+    }
+
+    #[test]
+    fn test_external_tree() {
+        let root = root();
+        let source = source_dir(root.to_str(), Path::new("vendored/opus"), |path| {
+            REQUIRED_FILES.iter().any(|file| path == root.join(file))
+        })
+        .unwrap();
+        assert_eq!(source, Source::External(root));
+    }
+
+    #[test]
+    fn test_invalid_tree() {
+        let root = root();
+        let has_cmake = |path: &Path| path == root.join("CMakeLists.txt");
+        let err = source_dir(root.to_str(), Path::new("vendored/opus"), has_cmake).unwrap_err();
+        assert!(err.contains(SOURCE_DIR_ENV), "{}", err);
+        assert!(err.contains("include/opus.h"), "{}", err);
+        assert!(!err.contains("CMakeLists.txt"), "{}", err);
+
+        let err = source_dir(Some("../opus"), Path::new("vendored/opus"), |_| true).unwrap_err();
+        assert!(
+            err.contains(SOURCE_DIR_ENV) && err.contains("absolute"),
+            "{}",
+            err
+        );
+    }
+}
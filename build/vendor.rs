@@ -0,0 +1,563 @@
+//! Downloading and preparing the Opus sources
+//!
+//! Does what vendor_opus.py does without Python: downloads the pinned Opus
+//! commit, checks it against the SHA-256 in [`PINNED`], adds the DNN model
+//! files, applies `patches/` and strips the embedded weights. `curl` and
+//! `tar` do the transfer and unpacking; both ship with Windows 10 and later
+//! as well as macOS and Linux.
+//!
+//! Shared between `cargo vendor-opus` (xtask/), build.rs for the
+//! `auto-vendor` feature and the tests in `tests/build_vendor.rs`. Kept free
+//! of the cmake crate and cargo directives.
+
+use crate::{patch, version};
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+/// An Opus commit and the SHA-256 of its GitHub source tarball
+pub struct Pin {
+    pub commit: &'static str,
+    /// Commit date, for OPUS_VERSION
+    pub date: &'static str,
+    /// Latest Opus release the commit includes. GitHub tarballs carry no
+    /// tags, so this goes into `package_version` for CMake to report.
+    pub release: &'static str,
+    /// Lowercase hex, as printed by `cargo vendor-opus --print-sha256`
+    pub sha256: &'static str,
+}
+
+/// The vendored Opus version. Bumping Opus means changing this constant:
+/// set the new commit, date and release, then take the checksum from
+/// `cargo vendor-opus --print-sha256` on a trusted connection.
+pub const PINNED: Pin = Pin {
+    commit: "a3f0ec02b34281c38aefdaf4bbbd5787ad641d62",
+    date: "2026-01-21",
+    release: "1.6",
+    sha256: "",
+};
+
+/// `*_data.c` files whose embedded weights are stripped, as in
+/// strip_weights.py; weights are loaded with OPUS_SET_DNN_BLOB instead
+pub const STRIPPED_FILES: [&str; 8] = [
+    "pitchdnn_data.c",
+    "fargan_data.c",
+    "plc_data.c",
+    "dred_rdovae_enc_data.c",
+    "dred_rdovae_dec_data.c",
+    "lace_data.c",
+    "nolace_data.c",
+    "bbwenet_data.c",
+];
+
+const STRIPPED_COMMENT: &str =
+    "/* Weight data stripped by strip_weights.py for crate size reduction */";
+
+pub fn tarball_url(commit: &str) -> String {
+    format!("https://github.com/xiph/opus/archive/{}.tar.gz", commit)
+}
+
+/// The model tarball is named after its own SHA-256
+pub fn model_url(sha256: &str) -> String {
+    format!(
+        "https://media.xiph.org/opus/models/opus_data-{}.tar.gz",
+        sha256
+    )
+}
+
+/// The model checksum that `autogen.sh` passes to `dnn/download_model.sh`
+pub fn model_hash(autogen: &str) -> Option<&str> {
+    autogen.lines().find_map(|line| {
+        let rest = line.trim().strip_prefix("dnn/download_model.sh")?;
+        let hash = rest.trim().trim_matches(|c| c == '"' || c == '\'');
+        (hash.len() == 64 && hash.bytes().all(|b| b.is_ascii_hexdigit())).then_some(hash)
+    })
+}
+
+/// Lowercase hex SHA-256 of `data`
+pub fn sha256(data: &[u8]) -> String {
+    const K: [u32; 64] = [
+        0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4,
+        0xab1c5ed5, 0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe,
+        0x9bdc06a7, 0xc19bf174, 0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f,
+        0x4a7484aa, 0x5cb0a9dc, 0x76f988da, 0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7,
+        0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967, 0x27b70a85, 0x2e1b2138, 0x4d2c6dfc,
+        0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85, 0xa2bfe8a1, 0xa81a664b,
+        0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070, 0x19a4c116,
+        0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+        0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7,
+        0xc67178f2,
+    ];
+    let mut h: [u32; 8] = [
+        0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab,
+        0x5be0cd19,
+    ];
+
+    // Message, a 1 bit, zeros up to 56 mod 64 bytes, then the bit length
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+
+    for block in message.chunks_exact(64) {
+        let mut w = [0u32; 64];
+        for (i, word) in block.chunks_exact(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16]
+                .wrapping_add(s0)
+                .wrapping_add(w[i - 7])
+                .wrapping_add(s1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut hh] = h;
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let t1 = hh
+                .wrapping_add(s1)
+                .wrapping_add(ch)
+                .wrapping_add(K[i])
+                .wrapping_add(w[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(maj);
+            hh = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+        for (state, value) in h.iter_mut().zip([a, b, c, d, e, f, g, hh]) {
+            *state = state.wrapping_add(value);
+        }
+    }
+
+    h.iter().map(|word| format!("{:08x}", word)).collect()
+}
+
+/// Check a download against its expected SHA-256
+pub fn verify_sha256(what: &str, data: &[u8], expected: &str) -> Result<(), String> {
+    let actual = sha256(data);
+    if actual.eq_ignore_ascii_case(expected.trim()) {
+        Ok(())
+    } else {
+        Err(format!(
+            "SHA-256 mismatch for {}: expected {}, got {}",
+            what, expected, actual
+        ))
+    }
+}
+
+/// Empty the `#ifndef USE_WEIGHTS_FILE` blocks of a `*_data.c` file, like
+/// strip_weights.py. Returns `None` if there was nothing to strip.
+pub fn strip_weights(content: &str) -> Option<String> {
+    let mut lines = Vec::new();
+    // Nesting depth inside a weights block, 0 outside
+    let mut depth = 0;
+    let mut removed = false;
+
+    for line in content.split('\n') {
+        let trimmed = line.trim();
+        if depth == 0 {
+            lines.push(line);
+            if trimmed == "#ifndef USE_WEIGHTS_FILE" {
+                lines.push(STRIPPED_COMMENT);
+                depth = 1;
+            }
+            continue;
+        }
+
+        if trimmed.starts_with("#if") {
+            depth += 1;
+        } else if trimmed.starts_with("#endif") {
+            depth -= 1;
+            if depth == 0 {
+                lines.push(line);
+                continue;
+            }
+        }
+        removed |= trimmed != STRIPPED_COMMENT;
+    }
+
+    removed.then(|| lines.join("\n"))
+}
+
+/// Contents of `vendored/OPUS_VERSION`
+pub fn version_file(pin: &Pin) -> String {
+    format!(
+        "# Opus Vendor Information
+#
+# This file tracks the exact version of the vendored Opus source.
+
+source: https://github.com/xiph/opus
+commit: {}
+date: {}
+branch: main
+
+# To update, change vendor::PINNED in build/vendor.rs and run:
+# cargo vendor-opus
+",
+        pin.commit, pin.date
+    )
+}
+
+/// The commit recorded in an OPUS_VERSION file
+pub fn vendored_commit(version_file: &str) -> Option<&str> {
+    version_file
+        .lines()
+        .find_map(|line| line.strip_prefix("commit:"))
+        .map(str::trim)
+}
+
+/// Check that a vendored tree is the one `pin` names, from the contents of
+/// its `OPUS_VERSION` and, if present, its `package_version`.
+///
+/// A tree left over from an older checkout or edited by hand would
+/// otherwise build silently, with whatever features it happens to have.
+pub fn verify_vendored(
+    pin: &Pin,
+    version_file: Option<&str>,
+    package_version: Option<&str>,
+) -> Result<(), String> {
+    let update = "run `cargo vendor-opus --force` to restore the pinned sources, \
+                  or set OPUS_SOURCE_DIR to build a different tree";
+    match version_file
+        .and_then(vendored_commit)
+        .filter(|commit| !commit.is_empty())
+    {
+        Some(commit) if commit == pin.commit => {}
+        Some(commit) => {
+            return Err(format!(
+                "vendored/OPUS_VERSION is at Opus commit {}, but build/vendor.rs pins {}; \
+                 if the new commit is intended, update vendor::PINNED, otherwise {}",
+                commit, pin.commit, update
+            ))
+        }
+        None => {
+            return Err(format!(
+                "vendored/OPUS_VERSION is missing or names no commit; {}",
+                update
+            ))
+        }
+    }
+
+    if let Some(text) = package_version {
+        let expected = version::package_version_file(pin.release, pin.commit);
+        let found = version::from_package_version(text);
+        if found != version::from_package_version(&expected) {
+            return Err(format!(
+                "vendored/opus/{} says {}, expected {} for commit {}; {}",
+                version::PACKAGE_VERSION_FILE,
+                found.as_deref().unwrap_or("nothing"),
+                expected.trim(),
+                pin.commit,
+                update
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Fetch `url` with curl
+pub fn download(url: &str) -> Result<Vec<u8>, String> {
+    let output = Command::new("curl")
+        .args(["--fail", "--silent", "--show-error", "--location"])
+        .args(["--retry", "3", url])
+        .output()
+        .map_err(|e| format!("Failed to run curl (is it installed?): {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Downloading {} failed: {}",
+            url,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(output.stdout)
+}
+
+/// Unpack a `.tar.gz` into `dest`, dropping the top-level directory if
+/// `strip_top_dir`
+fn unpack(archive: &[u8], dest: &Path, strip_top_dir: bool) -> Result<(), String> {
+    let file = dest.with_extension("tar.gz");
+    fs::write(&file, archive).map_err(|e| format!("Failed to write {}: {}", file.display(), e))?;
+
+    let mut tar = Command::new("tar");
+    tar.arg("-xzf").arg(&file).arg("-C").arg(dest);
+    if strip_top_dir {
+        tar.arg("--strip-components=1");
+    }
+    let status = tar.status();
+    let _ = fs::remove_file(&file);
+    match status {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(format!("tar failed with {}", status)),
+        Err(e) => Err(format!("Failed to run tar (is it installed?): {}", e)),
+    }
+}
+
+/// Apply every `*.patch` in `patches_dir` to `opus_dir`, in file name order
+fn apply_patches(opus_dir: &Path, patches_dir: &Path) -> Result<(), String> {
+    let mut patch_files: Vec<_> = fs::read_dir(patches_dir)
+        .map_err(|e| format!("Failed to read {}: {}", patches_dir.display(), e))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "patch"))
+        .collect();
+    patch_files.sort();
+
+    for patch_file in patch_files {
+        let text = fs::read_to_string(&patch_file)
+            .map_err(|e| format!("Failed to read {}: {}", patch_file.display(), e))?;
+        let name = patch_file.display();
+        for file in patch::parse(&text).map_err(|e| format!("{}: {}", name, e))? {
+            let path = opus_dir.join(&file.path);
+            let original = fs::read_to_string(&path)
+                .map_err(|e| format!("{}: failed to read {}: {}", name, path.display(), e))?;
+            let patched = file
+                .apply(&original)
+                .map_err(|e| format!("{}: {}", name, e))?;
+            fs::write(&path, patched)
+                .map_err(|e| format!("{}: failed to write {}: {}", name, path.display(), e))?;
+        }
+    }
+    Ok(())
+}
+
+/// Download `pin` into `dest/opus` and write `dest/OPUS_VERSION`, plus a
+/// `package_version` in the tree so CMake knows the Opus version.
+///
+/// The tree is assembled in `dest/.opus-staging` and only replaces an
+/// existing `dest/opus` once every step succeeded. `fetch` is [`download`]
+/// outside tests.
+pub fn vendor(
+    pin: &Pin,
+    dest: &Path,
+    patches_dir: &Path,
+    mut fetch: impl FnMut(&str) -> Result<Vec<u8>, String>,
+) -> Result<(), String> {
+    if pin.sha256.is_empty() {
+        return Err(format!(
+            "No SHA-256 pinned for Opus {}; set vendor::PINNED.sha256 from `cargo vendor-opus --print-sha256`",
+            pin.commit
+        ));
+    }
+
+    let tarball = fetch(&tarball_url(pin.commit))?;
+    verify_sha256(&format!("Opus {}", pin.commit), &tarball, pin.sha256)?;
+
+    let staging = dest.join(".opus-staging");
+    let io_err = |path: &Path, e: std::io::Error| format!("{}: {}", path.display(), e);
+    if staging.exists() {
+        fs::remove_dir_all(&staging).map_err(|e| io_err(&staging, e))?;
+    }
+    fs::create_dir_all(&staging).map_err(|e| io_err(&staging, e))?;
+    unpack(&tarball, &staging, true)?;
+
+    // The model tarball provides dnn/*_data.{c,h}; autogen.sh pins it
+    let autogen_path = staging.join("autogen.sh");
+    let autogen = fs::read_to_string(&autogen_path).map_err(|e| io_err(&autogen_path, e))?;
+    let model_sha256 = model_hash(&autogen).ok_or("No model hash found in autogen.sh")?;
+    let model = fetch(&model_url(model_sha256))?;
+    verify_sha256("the Opus DNN model", &model, model_sha256)?;
+    unpack(&model, &staging, false)?;
+
+    apply_patches(&staging, patches_dir)?;
+
+    for name in STRIPPED_FILES {
+        let path = staging.join("dnn").join(name);
+        let Ok(content) = fs::read_to_string(&path) else {
+            continue;
+        };
+        if let Some(stripped) = strip_weights(&content) {
+            fs::write(&path, stripped).map_err(|e| io_err(&path, e))?;
+        }
+    }
+
+    let package_version = staging.join(version::PACKAGE_VERSION_FILE);
+    fs::write(
+        &package_version,
+        version::package_version_file(pin.release, pin.commit),
+    )
+    .map_err(|e| io_err(&package_version, e))?;
+
+    let opus_dir = dest.join("opus");
+    if opus_dir.exists() {
+        fs::remove_dir_all(&opus_dir).map_err(|e| io_err(&opus_dir, e))?;
+    }
+    fs::rename(&staging, &opus_dir).map_err(|e| io_err(&opus_dir, e))?;
+    let version_path = dest.join("OPUS_VERSION");
+    fs::write(&version_path, version_file(pin)).map_err(|e| io_err(&version_path, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A small `.tar.gz` standing in for a downloaded Opus tarball
+    const FIXTURE: &[u8] = include_bytes!("../tests/fixtures/opus-fixture.tar.gz");
+    const FIXTURE_SHA256: &str = "b6531a3ba7d28cd15809a5b417e267b19a54062dd906eea671e39fd0576a9fda";
+
+    #[test]
+    fn test_sha256() {
+        assert_eq!(
+            sha256(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            sha256(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        // Two blocks of padding
+        assert_eq!(
+            sha256(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+        );
+        assert_eq!(
+            sha256(&[b'a'; 1000]),
+            "41edece42d63e8d9bf515a9ba6932e1c20cbc9f5a5d134645adb5db1b9737ea3"
+        );
+        assert_eq!(sha256(FIXTURE), FIXTURE_SHA256);
+    }
+
+    #[test]
+    fn test_checksum_mismatch() {
+        assert_eq!(verify_sha256("fixture", FIXTURE, FIXTURE_SHA256), Ok(()));
+
+        let wrong = "0".repeat(64);
+        let err = verify_sha256("fixture", FIXTURE, &wrong).unwrap_err();
+        assert!(
+            err.contains(&wrong) && err.contains(FIXTURE_SHA256),
+            "{}",
+            err
+        );
+
+        // vendor() stops before touching the destination
+        let dest = std::env::temp_dir().join("opus-head-sys-vendor-mismatch");
+        let _ = fs::remove_dir_all(&dest);
+        let pin = Pin {
+            sha256: "1111111111111111111111111111111111111111111111111111111111111111",
+            ..PINNED
+        };
+        let mut urls = Vec::new();
+        let err = vendor(&pin, &dest, Path::new("patches"), |url| {
+            urls.push(url.to_string());
+            Ok(FIXTURE.to_vec())
+        })
+        .unwrap_err();
+        assert!(
+            err.contains("SHA-256 mismatch") && err.contains(pin.sha256),
+            "{}",
+            err
+        );
+        assert_eq!(urls, [tarball_url(PINNED.commit)]);
+        assert!(!dest.exists());
+    }
+
+    #[test]
+    fn test_unpinned() {
+        let pin = Pin {
+            sha256: "",
+            ..PINNED
+        };
+        let err = vendor(&pin, Path::new("unused"), Path::new("patches"), |_| {
+            panic!("nothing should be downloaded")
+        })
+        .unwrap_err();
+        assert!(err.contains(pin.commit), "{}", err);
+    }
+
+    #[test]
+    fn test_download_failure() {
+        assert!(download("file:///nonexistent/opus-head-sys/opus.tar.gz").is_err());
+    }
+
+    #[test]
+    fn test_model_hash() {
+        let hash = "a5177ec6fb7d15058e99e57029746100121f68e4890b1467d4094aa336b6013e";
+        let autogen = format!("set -e\n\ndnn/download_model.sh \"{}\"\n", hash);
+        assert_eq!(model_hash(&autogen), Some(hash));
+        assert_eq!(model_hash("dnn/download_model.sh \"abc\"\n"), None);
+        assert!(model_url(hash).ends_with(&format!("opus_data-{}.tar.gz", hash)));
+    }
+
+    #[test]
+    fn test_strip_weights() {
+        let content = "#include \"plc_data.h\"\n\
+            #ifndef USE_WEIGHTS_FILE\n\
+            #ifdef ENABLE_X\n\
+            static const float w[] = {1, 2};\n\
+            #endif\n\
+            #endif /* USE_WEIGHTS_FILE */\n\
+            int init_plc(void);\n";
+        assert_eq!(
+            strip_weights(content).unwrap(),
+            format!(
+                "#include \"plc_data.h\"\n#ifndef USE_WEIGHTS_FILE\n{}\n\
+                 #endif /* USE_WEIGHTS_FILE */\nint init_plc(void);\n",
+                STRIPPED_COMMENT
+            )
+        );
+
+        // Already stripped files are left alone
+        assert_eq!(strip_weights(&strip_weights(content).unwrap()), None);
+    }
+
+    #[test]
+    fn test_verify_vendored() {
+        let version = version_file(&PINNED);
+        let package_version = version::package_version_file(PINNED.release, PINNED.commit);
+        assert_eq!(
+            verify_vendored(&PINNED, Some(&version), Some(&package_version)),
+            Ok(())
+        );
+        // vendor_opus.py writes no package_version
+        assert_eq!(verify_vendored(&PINNED, Some(&version), None), Ok(()));
+
+        let old = Pin {
+            commit: "0123456789abcdef0123456789abcdef01234567",
+            ..PINNED
+        };
+        let err = verify_vendored(&PINNED, Some(&version_file(&old)), None).unwrap_err();
+        assert!(
+            err.contains(old.commit) && err.contains(PINNED.commit),
+            "{}",
+            err
+        );
+
+        let err = verify_vendored(&PINNED, None, None).unwrap_err();
+        assert!(err.contains("OPUS_VERSION is missing"), "{}", err);
+        let err = verify_vendored(&PINNED, Some("commit:\n"), None).unwrap_err();
+        assert!(err.contains("names no commit"), "{}", err);
+
+        // An OPUS_VERSION edited to match doesn't make an old tree pass
+        let err = verify_vendored(
+            &PINNED,
+            Some(&version),
+            Some(&version::package_version_file("1.5.2", old.commit)),
+        )
+        .unwrap_err();
+        assert!(err.contains("1.5.2-g0123456"), "{}", err);
+    }
+
+    #[test]
+    fn test_version_file_matches_vendored() {
+        assert_eq!(vendored_commit(&version_file(&PINNED)), Some(PINNED.commit));
+        // The pin is the source of truth for vendored/opus
+        assert_eq!(
+            vendored_commit(include_str!("../vendored/OPUS_VERSION")),
+            Some(PINNED.commit)
+        );
+        assert_eq!(
+            include_str!("../vendored/opus/package_version"),
+            version::package_version_file(PINNED.release, PINNED.commit)
+        );
+    }
+}
@@ -0,0 +1,121 @@
+//! The Opus package version, as Opus' CMake build determines it
+//!
+//! `cmake/OpusPackageVersion.cmake` takes the version from `git describe`
+//! in a git checkout, otherwise from the `package_version` file at the top
+//! of the tree, and falls back to `0`. The build script does the same so
+//! `OPUS_VENDORED_VERSION` matches what `opus_get_version_string()` reports.
+//!
+//! Kept free of the cmake crate and cargo directives so it can be shared
+//! between build.rs and the tests in `tests/build_version.rs`.
+
+/// Written by release tarballs and `cargo vendor-opus`
+pub const PACKAGE_VERSION_FILE: &str = "package_version";
+
+/// What CMake uses when neither git nor `package_version` has a version
+pub const UNKNOWN_VERSION: &str = "0";
+
+/// The version in a `package_version` file, e.g. `1.6` for
+/// `PACKAGE_VERSION="1.6"`. Like CMake, only the first assignment counts
+/// and `unknown` becomes `0`.
+pub fn from_package_version(text: &str) -> Option<String> {
+    let line = text
+        .lines()
+        .find(|line| line.contains("PACKAGE_VERSION="))?;
+    let version = line
+        .replacen("PACKAGE_VERSION=", "", 1)
+        .replace('"', "")
+        .replace("unknown", "0");
+    let version = version.trim();
+    (!version.is_empty()).then(|| version.to_string())
+}
+
+/// The version in `git describe --tags --match "v*"` output, e.g.
+/// `1.6-12-ga3f0ec0` for `v1.6-12-ga3f0ec0`
+pub fn from_git_describe(output: &str) -> Option<String> {
+    let version = output.trim().strip_prefix('v')?;
+    (!version.is_empty()).then(|| version.to_string())
+}
+
+/// The version compiled into a library, from the string
+/// `opus_get_version_string()` returns, e.g. `1.5.2` for `libopus 1.5.2`.
+/// For prebuilt libraries, whose sources aren't around.
+pub fn from_library(data: &[u8]) -> Option<String> {
+    const PREFIX: &[u8] = b"libopus ";
+    let start = data.windows(PREFIX.len()).position(|w| w == PREFIX)? + PREFIX.len();
+    let rest = &data[start..];
+    let end = rest.iter().position(|&b| b == 0)?;
+    let version = std::str::from_utf8(&rest[..end]).ok()?;
+    let printable = version.bytes().all(|b| b.is_ascii_graphic());
+    (!version.is_empty() && printable).then(|| version.to_string())
+}
+
+/// The `package_version` contents for a tree with no git metadata, e.g.
+/// `PACKAGE_VERSION="1.6-ga3f0ec0"` for a commit after the 1.6 release
+pub fn package_version_file(release: &str, commit: &str) -> String {
+    let short = commit.get(..7).unwrap_or(commit);
+    format!("PACKAGE_VERSION=\"{}-g{}\"\n", release, short)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_package_version() {
+        assert_eq!(
+            from_package_version("PACKAGE_VERSION=\"1.5.2\"\n").as_deref(),
+            Some("1.5.2")
+        );
+        assert_eq!(
+            from_package_version("# comment\nPACKAGE_VERSION=\"unknown\"\n").as_deref(),
+            Some(UNKNOWN_VERSION)
+        );
+        assert_eq!(
+            from_package_version("PACKAGE_VERSION=\"1.6\"\nPACKAGE_VERSION=\"2.0\"\n").as_deref(),
+            Some("1.6")
+        );
+        assert_eq!(from_package_version(""), None);
+        assert_eq!(from_package_version("PACKAGE_VERSION=\"\""), None);
+    }
+
+    #[test]
+    fn test_vendored_tree_has_version() {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("vendored/opus")
+            .join(PACKAGE_VERSION_FILE);
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert_ne!(
+            from_package_version(&text).as_deref(),
+            Some(UNKNOWN_VERSION)
+        );
+    }
+
+    #[test]
+    fn test_from_git_describe() {
+        assert_eq!(
+            from_git_describe("v1.6-12-ga3f0ec0\n").as_deref(),
+            Some("1.6-12-ga3f0ec0")
+        );
+        assert_eq!(from_git_describe("v1.5.2").as_deref(), Some("1.5.2"));
+        assert_eq!(from_git_describe(""), None);
+        assert_eq!(from_git_describe("1.5.2"), None);
+    }
+
+    #[test]
+    fn test_from_library() {
+        let data = b"\x7fELF\0\0opus_encode\0libopus 1.6-ga3f0ec0\0celt\0";
+        assert_eq!(from_library(data).as_deref(), Some("1.6-ga3f0ec0"));
+        assert_eq!(from_library(b"libopus 1.5.2\0").as_deref(), Some("1.5.2"));
+        assert_eq!(from_library(b"opus_encode\0"), None);
+        // Not the version string, e.g. a path or a truncated file
+        assert_eq!(from_library(b"libopus \0"), None);
+        assert_eq!(from_library(b"libopus 1.5"), None);
+    }
+
+    #[test]
+    fn test_package_version_file_round_trip() {
+        let text = package_version_file("1.6", "a3f0ec02b34281c38aefdaf4bbbd5787ad641d62");
+        assert_eq!(text, "PACKAGE_VERSION=\"1.6-ga3f0ec0\"\n");
+        assert_eq!(from_package_version(&text).as_deref(), Some("1.6-ga3f0ec0"));
+    }
+}
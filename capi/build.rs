@@ -0,0 +1,117 @@
+//! Generates the export table and the per-platform export lists for the
+//! public `opus_*` functions in opus-head-sys's bindings
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Marks a bindings block left out with the `no-float-api` feature
+const NO_FLOAT_API_CFG: &str = "#[cfg(not(feature = \"no-float-api\"))]";
+
+fn main() {
+    let manifest_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    let target_os = env::var("CARGO_CFG_TARGET_OS").unwrap();
+    let target_env = env::var("CARGO_CFG_TARGET_ENV").unwrap();
+    let no_float_api = env::var_os("CARGO_FEATURE_NO_FLOAT_API").is_some();
+
+    let mut sources = vec![manifest_dir.join("../src/bindings.rs")];
+    if env::var_os("CARGO_FEATURE_CUSTOM_MODES").is_some() {
+        sources.push(manifest_dir.join("../src/custom_bindings.rs"));
+    }
+    let mut functions = Vec::new();
+    for source in &sources {
+        println!("cargo:rerun-if-changed={}", source.display());
+        let bindings = fs::read_to_string(source)
+            .unwrap_or_else(|e| panic!("Failed to read {}: {}", source.display(), e));
+        functions.extend(public_functions(&bindings, no_float_api));
+    }
+
+    let table: String = functions
+        .iter()
+        .map(|f| format!("    Export(opus_head_sys::{} as *const ()),\n", f))
+        .collect();
+    write(
+        &out_dir.join("exports.rs"),
+        &format!(
+            "#[used]\npub static EXPORTS: [Export; {}] = [\n{}];\n",
+            functions.len(),
+            table
+        ),
+    );
+
+    // Also written next to the library, for hosts that build an import
+    // library from it
+    let def = format!("EXPORTS\n{}", lines(&functions, "    ", ""));
+    write(&out_dir.join("opus_head.def"), &def);
+    if let Some(profile_dir) = out_dir.ancestors().nth(3) {
+        write(&profile_dir.join("opus_head.def"), &def);
+    }
+
+    if target_os == "windows" && target_env == "msvc" {
+        // rustc passes its own .def; /EXPORT adds to it
+        for f in &functions {
+            println!("cargo:rustc-cdylib-link-arg=/EXPORT:{}", f);
+        }
+    } else if target_os == "windows" {
+        // GNU ld reads .def files given as inputs alongside rustc's
+        println!(
+            "cargo:rustc-cdylib-link-arg={}",
+            out_dir.join("opus_head.def").display()
+        );
+    } else if target_os == "macos" || target_os == "ios" {
+        for f in &functions {
+            println!("cargo:rustc-cdylib-link-arg=-Wl,-exported_symbol,_{}", f);
+        }
+    } else {
+        // GNU ld and lld merge this with rustc's version script
+        let map = format!("{{\n  global:\n{}}};\n", lines(&functions, "    ", ";"));
+        let map_path = out_dir.join("opus_head.map");
+        write(&map_path, &map);
+        println!(
+            "cargo:rustc-cdylib-link-arg=-Wl,--version-script={}",
+            map_path.display()
+        );
+    }
+}
+
+/// Names of the `pub fn`s in bindgen output, skipping the float API blocks
+/// when `no_float_api` is set
+fn public_functions(bindings: &str, no_float_api: bool) -> Vec<String> {
+    let mut functions = Vec::new();
+    let mut skip_block = false;
+    let mut in_float_block = false;
+    for line in bindings.lines() {
+        if line == NO_FLOAT_API_CFG {
+            skip_block = no_float_api;
+            continue;
+        }
+        if line.starts_with("unsafe extern \"C\" {") || line.starts_with("extern \"C\" {") {
+            in_float_block = skip_block;
+            skip_block = false;
+        } else if line == "}" {
+            in_float_block = false;
+        } else if let Some(rest) = line.trim_start().strip_prefix("pub fn ") {
+            let name: String = rest
+                .chars()
+                .take_while(|c| c.is_alphanumeric() || *c == '_')
+                .collect();
+            if !in_float_block && name.starts_with("opus_") {
+                functions.push(name);
+            }
+        }
+    }
+    functions
+}
+
+fn lines(functions: &[String], indent: &str, suffix: &str) -> String {
+    functions
+        .iter()
+        .map(|f| format!("{}{}{}\n", indent, f, suffix))
+        .collect()
+}
+
+fn write(path: &Path, contents: &str) {
+    fs::write(path, contents)
+        .unwrap_or_else(|e| panic!("Failed to write {}: {}", path.display(), e));
+}
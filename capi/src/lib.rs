@@ -0,0 +1,16 @@
+//! libopus as a shared library
+//!
+//! Rust cdylibs only export `#[no_mangle]` Rust items, so the C functions
+//! linked in from opus-head-sys would stay hidden. The build script lists
+//! the public `opus_*` functions from the bindings; the table below makes
+//! the linker pull every one of them out of the static library, and the
+//! build script's linker arguments put them in the dynamic symbol table.
+
+/// A function address, only kept so the linker sees a reference
+#[repr(transparent)]
+pub struct Export(*const ());
+
+// Never dereferenced
+unsafe impl Sync for Export {}
+
+include!(concat!(env!("OUT_DIR"), "/exports.rs"));
@@ -0,0 +1,93 @@
+//! Loads the built shared library the way a C host would and checks that
+//! the Opus C ABI is visible
+
+use libloading::{Library, Symbol};
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_int, c_uchar};
+use std::path::PathBuf;
+
+const OPUS_OK: c_int = 0;
+const OPUS_APPLICATION_AUDIO: c_int = 2049;
+
+type EncoderCreate = unsafe extern "C" fn(i32, c_int, c_int, *mut c_int) -> *mut u8;
+type Encode = unsafe extern "C" fn(*mut u8, *const i16, c_int, *mut c_uchar, i32) -> i32;
+type EncoderDestroy = unsafe extern "C" fn(*mut u8);
+type VersionString = unsafe extern "C" fn() -> *const c_char;
+
+/// The cdylib, in `deps` next to the test binary under `cargo test` or in
+/// the profile directory after `cargo build`
+fn library_path() -> PathBuf {
+    let name = format!(
+        "{}opus_head{}",
+        std::env::consts::DLL_PREFIX,
+        std::env::consts::DLL_SUFFIX
+    );
+    let exe = std::env::current_exe().unwrap();
+    let deps = exe.parent().unwrap();
+    [deps, deps.parent().unwrap()]
+        .iter()
+        .map(|dir| dir.join(&name))
+        .find(|path| path.is_file())
+        .unwrap_or_else(|| panic!("{} not found next to {}", name, exe.display()))
+}
+
+#[test]
+fn test_resolve_and_encode() {
+    let path = library_path();
+    unsafe {
+        let lib = Library::new(&path)
+            .unwrap_or_else(|e| panic!("Failed to load {}: {}", path.display(), e));
+
+        let version: Symbol<VersionString> = lib.get(b"opus_get_version_string").unwrap();
+        let version = CStr::from_ptr(version()).to_str().unwrap();
+        assert!(version.starts_with("libopus "), "{}", version);
+
+        let create: Symbol<EncoderCreate> = lib.get(b"opus_encoder_create").unwrap();
+        let encode: Symbol<Encode> = lib.get(b"opus_encode").unwrap();
+        let destroy: Symbol<EncoderDestroy> = lib.get(b"opus_encoder_destroy").unwrap();
+
+        let mut error: c_int = 0;
+        let encoder = create(48000, 2, OPUS_APPLICATION_AUDIO, &mut error);
+        assert_eq!(error, OPUS_OK, "Failed to create encoder");
+        assert!(!encoder.is_null());
+
+        let pcm = vec![0i16; 960 * 2];
+        let mut packet = [0u8; 1275];
+        let len = encode(encoder, pcm.as_ptr(), 960, packet.as_mut_ptr(), 1275);
+        assert!(len > 0, "Encoding failed: {}", len);
+        destroy(encoder);
+    }
+}
+
+#[test]
+fn test_every_public_function_exported() {
+    let path = library_path();
+    let lib = unsafe { Library::new(&path).unwrap() };
+    for name in [
+        "opus_decoder_create",
+        "opus_decoder_ctl",
+        "opus_packet_parse",
+        "opus_repacketizer_create",
+        "opus_multistream_encoder_create",
+        "opus_multistream_decoder_create",
+        "opus_projection_ambisonics_encoder_create",
+        "opus_dred_decoder_create",
+        "opus_strerror",
+    ] {
+        let symbol = unsafe { lib.get::<unsafe extern "C" fn()>(name.as_bytes()) };
+        assert!(symbol.is_ok(), "{} is not exported", name);
+    }
+}
+
+/// Internal symbols stay hidden, so the library doesn't clash with other
+/// code in the host
+#[cfg(target_os = "linux")]
+#[test]
+fn test_internals_hidden() {
+    let path = library_path();
+    let lib = unsafe { Library::new(&path).unwrap() };
+    for name in ["opus_encode_native", "opus_fft_c", "celt_encode_with_ec"] {
+        let symbol = unsafe { lib.get::<unsafe extern "C" fn()>(name.as_bytes()) };
+        assert!(symbol.is_err(), "{} is exported", name);
+    }
+}
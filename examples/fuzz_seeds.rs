@@ -0,0 +1,173 @@
+//! Write the seed corpus for the fuzz targets in `fuzz/`
+//!
+//! ```text
+//! cargo run --example fuzz_seeds
+//! ```
+//!
+//! Encodes a short tone with this crate's encoder and writes the packets to
+//! `fuzz/seeds/decode` (each prefixed with the selector byte the `decode`
+//! target reads) and `fuzz/seeds/packet_parse` (one packet per TOC frame
+//! count code, plus a multistream packet). The output is deterministic, so
+//! rerunning it after an Opus update only changes what the encoder changed.
+
+use opus_head_sys::packet::{max_packet_size, FrameDuration};
+use opus_head_sys::*;
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+
+/// Sample rates in the order of `SAMPLE_RATES` in `fuzz/fuzz_targets/decode.rs`
+const SAMPLE_RATES: [i32; 5] = [8000, 12000, 16000, 24000, 48000];
+/// Selector bits of the `decode` target
+const STEREO: u8 = 0x8;
+const FEC: u8 = 0x40;
+
+const MAX_PACKET: usize = max_packet_size(2, FrameDuration::Ms120);
+
+/// `frames` frames of `frame_ms` of a tone, encoded one packet each
+fn encode(
+    sample_rate: i32,
+    channels: usize,
+    frame_ms: usize,
+    frames: usize,
+    setup: impl Fn(*mut OpusEncoder),
+) -> Vec<Vec<u8>> {
+    let frame_size = sample_rate as usize * frame_ms / 1000;
+    unsafe {
+        let mut error = 0;
+        let encoder = opus_encoder_create(
+            sample_rate,
+            channels as i32,
+            OPUS_APPLICATION_AUDIO as i32,
+            &mut error,
+        );
+        assert_eq!(error, OPUS_OK as i32, "Failed to create encoder");
+        setup(encoder);
+
+        let packets = (0..frames)
+            .map(|frame| {
+                let pcm: Vec<i16> = (0..frame_size * channels)
+                    .map(|i| {
+                        let n = frame * frame_size + i / channels;
+                        let t = n as f32 / sample_rate as f32;
+                        ((t * 440.0 * std::f32::consts::TAU).sin() * 8000.0) as i16
+                    })
+                    .collect();
+                let mut packet = vec![0u8; MAX_PACKET];
+                let len = opus_encode(
+                    encoder,
+                    pcm.as_ptr(),
+                    frame_size as i32,
+                    packet.as_mut_ptr(),
+                    packet.len() as i32,
+                );
+                assert!(len > 0, "Encoding failed: {}", len);
+                packet.truncate(len as usize);
+                packet
+            })
+            .collect();
+
+        opus_encoder_destroy(encoder);
+        packets
+    }
+}
+
+/// Join single-frame packets with the repacketizer
+fn combine(packets: &[Vec<u8>]) -> Vec<u8> {
+    unsafe {
+        let rp = opus_repacketizer_create();
+        for packet in packets {
+            let ret = opus_repacketizer_cat(rp, packet.as_ptr(), packet.len() as i32);
+            assert_eq!(ret, OPUS_OK as i32, "Failed to combine packets");
+        }
+        let mut out = vec![0u8; MAX_PACKET];
+        let len = opus_repacketizer_out(rp, out.as_mut_ptr(), out.len() as i32);
+        assert!(len > 0, "Repacketizing failed: {}", len);
+        out.truncate(len as usize);
+        opus_repacketizer_destroy(rp);
+        out
+    }
+}
+
+/// One 20 ms packet of two uncoupled mono streams; the first one is
+/// self-delimited
+fn multistream() -> Vec<u8> {
+    unsafe {
+        let mapping = [0u8, 1];
+        let mut error = 0;
+        let encoder = opus_multistream_encoder_create(
+            48000,
+            2,
+            2,
+            0,
+            mapping.as_ptr(),
+            OPUS_APPLICATION_AUDIO as i32,
+            &mut error,
+        );
+        assert_eq!(error, OPUS_OK as i32, "Failed to create encoder");
+        let pcm: Vec<i16> = (0..960 * 2)
+            .map(|i| ((i as f32 * 0.05).sin() * 8000.0) as i16)
+            .collect();
+        let mut packet = vec![0u8; MAX_PACKET];
+        let len = opus_multistream_encode(
+            encoder,
+            pcm.as_ptr(),
+            960,
+            packet.as_mut_ptr(),
+            packet.len() as i32,
+        );
+        assert!(len > 0, "Encoding failed: {}", len);
+        packet.truncate(len as usize);
+        opus_multistream_encoder_destroy(encoder);
+        packet
+    }
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let seeds = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fuzz/seeds");
+
+    let decode = seeds.join("decode");
+    fs::create_dir_all(&decode)?;
+    for (index, &sample_rate) in SAMPLE_RATES.iter().enumerate() {
+        for channels in [1, 2] {
+            let selector = index as u8 | if channels == 2 { STEREO } else { 0 };
+            let packets = encode(sample_rate, channels, 20, 2, |_| {});
+            for (i, packet) in packets.iter().enumerate() {
+                let name = format!("{}-{}ch-{}", sample_rate, channels, i);
+                fs::write(decode.join(&name), [&[selector][..], packet].concat())?;
+            }
+        }
+    }
+    // Speech with in-band FEC, decoded as FEC
+    let fec = encode(16000, 1, 20, 3, |encoder| unsafe {
+        opus_encoder_ctl(encoder, OPUS_SET_INBAND_FEC_REQUEST as i32, 1);
+        opus_encoder_ctl(encoder, OPUS_SET_PACKET_LOSS_PERC_REQUEST as i32, 20);
+        opus_encoder_ctl(encoder, OPUS_SET_BITRATE_REQUEST as i32, 24000);
+        opus_encoder_ctl(
+            encoder,
+            OPUS_SET_SIGNAL_REQUEST as i32,
+            OPUS_SIGNAL_VOICE as i32,
+        );
+    });
+    fs::write(
+        decode.join("16000-1ch-fec"),
+        [&[2 | FEC][..], &fec[2]].concat(),
+    )?;
+    // Concealment
+    fs::write(decode.join("48000-2ch-plc"), [4 | STEREO])?;
+
+    let parse = seeds.join("packet_parse");
+    fs::create_dir_all(&parse)?;
+    let frames = encode(48000, 2, 20, 3, |_| {});
+    fs::write(parse.join("code0"), &frames[0])?;
+    let cbr = encode(48000, 1, 10, 2, |encoder| unsafe {
+        opus_encoder_ctl(encoder, OPUS_SET_VBR_REQUEST as i32, 0);
+    });
+    fs::write(parse.join("code1"), combine(&cbr))?;
+    fs::write(parse.join("code2"), combine(&frames[..2]))?;
+    fs::write(parse.join("code3"), combine(&frames))?;
+    fs::write(parse.join("multistream"), multistream())?;
+
+    println!("Wrote seeds to {}", seeds.display());
+    Ok(())
+}
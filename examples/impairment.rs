@@ -0,0 +1,155 @@
+//! Send an encoded stream over a simulated impaired network and report how
+//! the receiver recovered each frame
+//!
+//! ```text
+//! cargo run --release --features simulate --example impairment -- \
+//!     [--loss 10] [--burst 1] [--reorder 2] [--jitter 2] [--delay 3] [--seed 1] \
+//!     [--bitrate 24000] [--seconds 30] [--no-fec] [--dred opus_data-<hash>.bin]
+//! ```
+//!
+//! Encodes a generated voice-like signal in 20 ms frames at 16 kHz, with
+//! in-band FEC tuned for the loss rate unless `--no-fec` is given and with
+//! 100 ms of DRED if `--dred` points at the DNN weights. The packets go
+//! through a [`Network`] losing `--loss` percent in bursts of `--burst`,
+//! holding back `--reorder` percent by one frame and delaying each by up to
+//! `--jitter` frames. The [`Receiver`] plays every frame `--delay` frames
+//! after it was sent and counts how many were clean, recovered by FEC or
+//! DRED, or concealed.
+
+use opus_head_sys::simulate::{Impairment, Network, Receiver, Stats};
+use opus_head_sys::stream::StreamEncoder;
+use opus_head_sys::*;
+use std::error::Error;
+
+const SAMPLE_RATE: i32 = 16000;
+/// 20 ms
+const FRAME_SIZE: usize = 320;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut impairment = Impairment {
+        loss: 0.1,
+        burst: 1,
+        reorder: 0.02,
+        jitter: 2,
+        seed: 1,
+    };
+    let mut delay: u64 = 3;
+    let mut bitrate = 24000;
+    let mut seconds = 30;
+    let mut fec = true;
+    let mut weights = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--loss" => impairment.loss = value()?.parse::<f64>()? / 100.0,
+            "--burst" => impairment.burst = value()?.parse()?,
+            "--reorder" => impairment.reorder = value()?.parse::<f64>()? / 100.0,
+            "--jitter" => impairment.jitter = value()?.parse()?,
+            "--seed" => impairment.seed = value()?.parse()?,
+            "--delay" => delay = value()?.parse()?,
+            "--bitrate" => bitrate = value()?.parse()?,
+            "--seconds" => seconds = value()?.parse()?,
+            "--no-fec" => fec = false,
+            "--dred" => weights = Some(std::fs::read(value()?)?),
+            _ => return Err(format!("unknown argument {}", arg).into()),
+        }
+    }
+    let mut network = Network::new(impairment)
+        .ok_or("--loss and --reorder must be 0 to 100, --burst at least 1")?;
+
+    let mut encoder = StreamEncoder::new(SAMPLE_RATE, 1, OPUS_APPLICATION_VOIP as i32, FRAME_SIZE)
+        .map_err(|e| format!("opus_encoder_create failed: {}", e))?;
+    let raw = encoder.as_ptr();
+    unsafe {
+        opus_encoder_ctl(raw, OPUS_SET_BITRATE_REQUEST as i32, bitrate);
+        opus_encoder_ctl(raw, OPUS_SET_INBAND_FEC_REQUEST as i32, fec as i32);
+        // FEC and DRED only add redundancy for an expected loss rate
+        let loss_perc = (impairment.loss * impairment.burst as f64 * 100.0).round() as i32;
+        opus_encoder_ctl(
+            raw,
+            OPUS_SET_PACKET_LOSS_PERC_REQUEST as i32,
+            loss_perc.min(100),
+        );
+    }
+
+    let mut receiver =
+        Receiver::new(SAMPLE_RATE, 1).map_err(|e| format!("opus_decoder_create failed: {}", e))?;
+    if let Some(weights) = &weights {
+        unsafe {
+            let ret = opus_encoder_ctl(
+                raw,
+                OPUS_SET_DNN_BLOB_REQUEST as i32,
+                weights.as_ptr() as *const std::ffi::c_void,
+                weights.len() as i32,
+            );
+            if ret != OPUS_OK as i32 {
+                return Err(format!("loading the DNN weights failed: {}", ret).into());
+            }
+            opus_encoder_ctl(raw, OPUS_SET_DRED_DURATION_REQUEST as i32, 100);
+        }
+        receiver
+            .enable_dred(weights)
+            .map_err(|e| format!("enabling DRED failed: {}", e))?;
+    }
+
+    let packets = encoder
+        .push(&voice(seconds * SAMPLE_RATE as usize))
+        .map_err(|e| format!("opus_encode failed: {}", e))?;
+    let bytes: usize = packets.iter().map(Vec::len).sum();
+
+    let mut pcm = vec![0i16; FRAME_SIZE];
+    let mut lost = 0;
+    for slot in 0..packets.len() as u64 + delay {
+        if let Some(packet) = packets.get(slot as usize) {
+            lost += network.send(slot, packet).is_none() as usize;
+        }
+        for (sequence, packet) in network.deliver(slot) {
+            receiver.receive(sequence, &packet);
+        }
+        if slot >= delay {
+            receiver
+                .play(&mut pcm)
+                .map_err(|e| format!("decoding frame {} failed: {}", slot - delay, e))?;
+        }
+    }
+
+    println!(
+        "{} frames at {:.1} kbit/s, {} lost in the network",
+        packets.len(),
+        bytes as f64 * 8.0 / seconds as f64 / 1000.0,
+        lost
+    );
+    report(&receiver.stats());
+    Ok(())
+}
+
+fn report(stats: &Stats) {
+    let percent = |count: usize| count as f64 * 100.0 / stats.frames().max(1) as f64;
+    for (name, count) in [
+        ("clean", stats.clean),
+        ("FEC", stats.fec),
+        ("DRED", stats.dred),
+        ("concealed", stats.concealed),
+    ] {
+        println!("{:10} {:6} ({:5.1}%)", name, count, percent(count));
+    }
+    println!("{:10} {:6}", "late", stats.late);
+}
+
+/// A harmonic tone with a syllable-rate envelope, so the encoder stays in
+/// SILK mode and sees voiced frames
+fn voice(samples: usize) -> Vec<i16> {
+    use std::f64::consts::TAU;
+    (0..samples)
+        .map(|i| {
+            let t = i as f64 / SAMPLE_RATE as f64;
+            let tone: f64 = (1..=8)
+                .map(|h| (t * TAU * 140.0 * h as f64).sin() / h as f64)
+                .sum();
+            let envelope = (0.5 + 0.5 * (t * TAU * 4.0).sin()).powi(2);
+            (tone * envelope * 6000.0) as i16
+        })
+        .collect()
+}
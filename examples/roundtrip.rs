@@ -0,0 +1,241 @@
+//! Encode a 16-bit PCM WAV file with Opus and decode it back
+//!
+//! ```text
+//! cargo run --example roundtrip -- input.wav output.wav [bitrate]
+//! ```
+//!
+//! Without an input file a 2 second 440 Hz test tone is encoded instead, so
+//! `cargo run --example roundtrip` works out of the box and always produces
+//! the same output. The input must be 8, 12, 16, 24 or 48 kHz, mono or stereo.
+
+use opus_head_sys::packet::{max_packet_size, FrameDuration};
+use opus_head_sys::*;
+use std::error::Error;
+use std::fs;
+
+/// 20 ms frames, the usual choice for both voice and music
+const FRAME_MS: usize = 20;
+/// Largest packet libopus can produce for one frame
+const MAX_PACKET: usize = max_packet_size(2, FrameDuration::Ms20);
+
+struct Wav {
+    sample_rate: u32,
+    channels: u16,
+    /// Interleaved samples
+    samples: Vec<i16>,
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let output = args
+        .get(1)
+        .cloned()
+        .unwrap_or_else(|| "roundtrip.wav".to_string());
+    let bitrate: i32 = match args.get(2) {
+        Some(b) => b.parse()?,
+        None => 64000,
+    };
+
+    let input = match args.first() {
+        Some(path) => read_wav(&fs::read(path)?)?,
+        None => {
+            println!("No input given, encoding a 440 Hz test tone");
+            test_tone(48000, 2)
+        }
+    };
+
+    let channels = input.channels as usize;
+    let frame_size = input.sample_rate as usize * FRAME_MS / 1000;
+    let num_frames = input.samples.len().div_ceil(frame_size * channels);
+
+    // Encode every frame, zero-padding the last one
+    let mut packets: Vec<Vec<u8>> = Vec::with_capacity(num_frames);
+    unsafe {
+        let mut error = 0;
+        let encoder = opus_encoder_create(
+            input.sample_rate as i32,
+            channels as i32,
+            OPUS_APPLICATION_AUDIO as i32,
+            &mut error,
+        );
+        if error != OPUS_OK as i32 {
+            return Err(format!("opus_encoder_create failed: {}", error).into());
+        }
+        opus_encoder_ctl(encoder, OPUS_SET_BITRATE_REQUEST as i32, bitrate);
+
+        let mut frame = vec![0i16; frame_size * channels];
+        let mut packet = [0u8; MAX_PACKET];
+        for chunk in input.samples.chunks(frame_size * channels) {
+            frame.fill(0);
+            frame[..chunk.len()].copy_from_slice(chunk);
+
+            let len = opus_encode(
+                encoder,
+                frame.as_ptr(),
+                frame_size as i32,
+                packet.as_mut_ptr(),
+                packet.len() as i32,
+            );
+            if len < 0 {
+                opus_encoder_destroy(encoder);
+                return Err(format!("opus_encode failed: {}", len).into());
+            }
+            packets.push(packet[..len as usize].to_vec());
+        }
+
+        opus_encoder_destroy(encoder);
+    }
+
+    // Decode the packets back to PCM
+    let mut decoded = Vec::with_capacity(num_frames * frame_size * channels);
+    unsafe {
+        let mut error = 0;
+        let decoder = opus_decoder_create(input.sample_rate as i32, channels as i32, &mut error);
+        if error != OPUS_OK as i32 {
+            return Err(format!("opus_decoder_create failed: {}", error).into());
+        }
+
+        let mut frame = vec![0i16; frame_size * channels];
+        for packet in &packets {
+            let samples = opus_decode(
+                decoder,
+                packet.as_ptr(),
+                packet.len() as i32,
+                frame.as_mut_ptr(),
+                frame_size as i32,
+                0,
+            );
+            if samples < 0 {
+                opus_decoder_destroy(decoder);
+                return Err(format!("opus_decode failed: {}", samples).into());
+            }
+            decoded.extend_from_slice(&frame[..samples as usize * channels]);
+        }
+
+        opus_decoder_destroy(decoder);
+    }
+    decoded.truncate(input.samples.len());
+
+    let total_bytes: usize = packets.iter().map(Vec::len).sum();
+    let seconds = (num_frames * FRAME_MS) as f64 / 1000.0;
+    println!(
+        "Encoded {} frames ({:.2} s, {} Hz, {} ch) to {} bytes: {:.1} kbit/s (target {} kbit/s)",
+        num_frames,
+        seconds,
+        input.sample_rate,
+        channels,
+        total_bytes,
+        total_bytes as f64 * 8.0 / seconds / 1000.0,
+        bitrate / 1000
+    );
+
+    let result = Wav {
+        sample_rate: input.sample_rate,
+        channels: input.channels,
+        samples: decoded,
+    };
+    fs::write(&output, write_wav(&result))?;
+    println!("Wrote {}", output);
+
+    Ok(())
+}
+
+fn test_tone(sample_rate: u32, channels: u16) -> Wav {
+    let frames = sample_rate as usize * 2;
+    let samples = (0..frames)
+        .flat_map(|i| {
+            let t = i as f64 / sample_rate as f64;
+            let s = ((t * 440.0 * std::f64::consts::TAU).sin() * 8000.0) as i16;
+            std::iter::repeat_n(s, channels as usize)
+        })
+        .collect();
+    Wav {
+        sample_rate,
+        channels,
+        samples,
+    }
+}
+
+/// Minimal RIFF/WAVE reader: 16-bit integer PCM only, unknown chunks skipped
+fn read_wav(data: &[u8]) -> Result<Wav, Box<dyn Error>> {
+    if data.len() < 12 || &data[0..4] != b"RIFF" || &data[8..12] != b"WAVE" {
+        return Err("not a RIFF/WAVE file".into());
+    }
+
+    let u16_at = |at: usize| u16::from_le_bytes([data[at], data[at + 1]]);
+    let u32_at = |at: usize| u32::from_le_bytes(data[at..at + 4].try_into().unwrap());
+
+    let mut format = None;
+    let mut pos = 12;
+    while pos + 8 <= data.len() {
+        let id = &data[pos..pos + 4];
+        let size = u32_at(pos + 4) as usize;
+        let body = pos + 8;
+        let end = body.checked_add(size).filter(|&e| e <= data.len());
+        let end = end.ok_or("truncated WAV chunk")?;
+
+        match id {
+            b"fmt " if size >= 16 => {
+                let (tag, channels, rate, bits) = (
+                    u16_at(body),
+                    u16_at(body + 2),
+                    u32_at(body + 4),
+                    u16_at(body + 14),
+                );
+                // 1 = PCM, 0xFFFE = WAVE_FORMAT_EXTENSIBLE
+                if (tag != 1 && tag != 0xFFFE) || bits != 16 {
+                    return Err("only 16-bit PCM WAV is supported".into());
+                }
+                format = Some((rate, channels));
+            }
+            b"data" => {
+                let (sample_rate, channels) = format.ok_or("data chunk before fmt chunk")?;
+                if !matches!(sample_rate, 8000 | 12000 | 16000 | 24000 | 48000) {
+                    return Err(format!("unsupported sample rate {} Hz", sample_rate).into());
+                }
+                if !(1..=2).contains(&channels) {
+                    return Err(format!("unsupported channel count {}", channels).into());
+                }
+                let samples = data[body..end]
+                    .chunks_exact(2)
+                    .map(|b| i16::from_le_bytes([b[0], b[1]]))
+                    .collect();
+                return Ok(Wav {
+                    sample_rate,
+                    channels,
+                    samples,
+                });
+            }
+            _ => {}
+        }
+
+        // Chunks are padded to an even size
+        pos = end + (size & 1);
+    }
+
+    Err("no data chunk found".into())
+}
+
+fn write_wav(wav: &Wav) -> Vec<u8> {
+    let data_len = (wav.samples.len() * 2) as u32;
+    let block_align = wav.channels * 2;
+
+    let mut out = Vec::with_capacity(44 + data_len as usize);
+    out.extend_from_slice(b"RIFF");
+    out.extend_from_slice(&(36 + data_len).to_le_bytes());
+    out.extend_from_slice(b"WAVE");
+    out.extend_from_slice(b"fmt ");
+    out.extend_from_slice(&16u32.to_le_bytes());
+    out.extend_from_slice(&1u16.to_le_bytes());
+    out.extend_from_slice(&wav.channels.to_le_bytes());
+    out.extend_from_slice(&wav.sample_rate.to_le_bytes());
+    out.extend_from_slice(&(wav.sample_rate * block_align as u32).to_le_bytes());
+    out.extend_from_slice(&block_align.to_le_bytes());
+    out.extend_from_slice(&16u16.to_le_bytes());
+    out.extend_from_slice(b"data");
+    out.extend_from_slice(&data_len.to_le_bytes());
+    for sample in &wav.samples {
+        out.extend_from_slice(&sample.to_le_bytes());
+    }
+    out
+}
@@ -0,0 +1,63 @@
+//! Transcode a 16-bit PCM WAV file through Opus and report how it went
+//!
+//! ```text
+//! cargo run --release --example transcode -- input.wav output.wav [--bitrate 32000] [--packets packets.bit]
+//! ```
+//!
+//! Encodes 20 ms frames at the given bitrate (64 kb/s by default), decodes
+//! them back into `output.wav`, and prints the achieved bitrate and how many
+//! times faster than realtime encoding and decoding ran. `--packets` also
+//! writes the packets in `opus_demo`'s length-prefixed format. The input
+//! must be 8, 12, 16, 24 or 48 kHz, mono or stereo.
+
+#[path = "transcode/pipeline.rs"]
+mod pipeline;
+
+use std::error::Error;
+use std::fs;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut args = std::env::args().skip(1);
+    let mut paths = Vec::new();
+    let mut bitrate = 64000;
+    let mut packets_path = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--bitrate" => bitrate = args.next().ok_or("--bitrate needs a value")?.parse()?,
+            "--packets" => packets_path = Some(args.next().ok_or("--packets needs a path")?),
+            _ => paths.push(arg),
+        }
+    }
+    let [input, output] = &paths[..] else {
+        return Err(
+            "usage: transcode <input.wav> <output.wav> [--bitrate <bps>] [--packets <file>]".into(),
+        );
+    };
+
+    let wav = pipeline::read_wav(&fs::read(input)?)?;
+    let result = pipeline::transcode(&wav, bitrate)?;
+
+    let seconds = wav.seconds();
+    println!(
+        "{:.2} s ({} Hz, {} ch) in {} packets: {:.1} kbit/s (target {} kbit/s)",
+        seconds,
+        wav.sample_rate,
+        wav.channels,
+        result.packets.len(),
+        result.bitrate() / 1000.0,
+        bitrate / 1000
+    );
+    println!(
+        "Encoding {:.0}x realtime, decoding {:.0}x realtime",
+        seconds / result.encode_time.as_secs_f64(),
+        seconds / result.decode_time.as_secs_f64()
+    );
+
+    if let Some(path) = packets_path {
+        fs::write(&path, pipeline::write_packets(&result.packets))?;
+        println!("Wrote {}", path);
+    }
+    fs::write(output, pipeline::write_wav(&result.decoded))?;
+    println!("Wrote {}", output);
+    Ok(())
+}
@@ -0,0 +1,222 @@
+//! Core of the `transcode` example, also run by `tests/transcode.rs`
+//!
+//! Encodes 16-bit PCM in 20 ms frames with [`StreamEncoder`], decodes the
+//! packets back with [`Decoder`], and times both.
+
+use opus_head_sys::decoder::Decoder;
+use opus_head_sys::stream::StreamEncoder;
+use opus_head_sys::*;
+use std::time::{Duration, Instant};
+
+/// 20 ms frames, the usual choice for both voice and music
+pub const FRAME_MS: usize = 20;
+
+pub struct Wav {
+    pub sample_rate: u32,
+    pub channels: u16,
+    /// Interleaved samples
+    pub samples: Vec<i16>,
+}
+
+impl Wav {
+    /// Samples per channel
+    pub fn frames(&self) -> usize {
+        self.samples.len() / self.channels as usize
+    }
+
+    /// Length in seconds
+    pub fn seconds(&self) -> f64 {
+        self.frames() as f64 / self.sample_rate as f64
+    }
+}
+
+/// An encoded packet
+pub struct Packet {
+    pub data: Vec<u8>,
+    /// `OPUS_GET_FINAL_RANGE` after encoding it, which a decoder must match
+    pub final_range: u32,
+}
+
+pub struct Transcoded {
+    pub packets: Vec<Packet>,
+    /// Output of the decoder: the input delayed by the encoder's lookahead
+    /// and padded to whole frames
+    pub decoded: Wav,
+    pub encode_time: Duration,
+    pub decode_time: Duration,
+}
+
+impl Transcoded {
+    /// Bits per second of Opus payload, without any container
+    pub fn bitrate(&self) -> f64 {
+        let bytes: usize = self.packets.iter().map(|p| p.data.len()).sum();
+        bytes as f64 * 8.0 / (self.packets.len() * FRAME_MS) as f64 * 1000.0
+    }
+}
+
+/// Encode `input` at `bitrate` bits per second and decode it again
+pub fn transcode(input: &Wav, bitrate: i32) -> Result<Transcoded, String> {
+    let channels = input.channels as usize;
+    let frame_size = input.sample_rate as usize * FRAME_MS / 1000;
+    let mut encoder = StreamEncoder::new(
+        input.sample_rate as i32,
+        channels,
+        OPUS_APPLICATION_AUDIO as i32,
+        frame_size,
+    )
+    .map_err(|e| format!("opus_encoder_create failed: {}", e))?;
+    let raw = encoder.as_ptr();
+    let ret = unsafe { opus_encoder_ctl(raw, OPUS_SET_BITRATE_REQUEST as i32, bitrate) };
+    if ret != OPUS_OK as i32 {
+        return Err(format!("invalid bitrate {}: {}", bitrate, ret));
+    }
+
+    let start = Instant::now();
+    let mut packets = Vec::new();
+    let mut on_packet = |data: &[u8]| {
+        let mut final_range: u32 = 0;
+        unsafe {
+            opus_encoder_ctl(
+                raw,
+                OPUS_GET_FINAL_RANGE_REQUEST as i32,
+                &mut final_range as *mut u32,
+            )
+        };
+        packets.push(Packet {
+            data: data.to_vec(),
+            final_range,
+        });
+    };
+    encoder
+        .push_with(&input.samples, &mut on_packet)
+        .map_err(|e| format!("opus_encode failed: {}", e))?;
+    // The last partial frame, padded with silence
+    if let Some(data) = encoder
+        .finish()
+        .map_err(|e| format!("opus_encode failed: {}", e))?
+    {
+        on_packet(&data);
+    }
+    let encode_time = start.elapsed();
+
+    let start = Instant::now();
+    let mut samples = Vec::with_capacity(packets.len() * frame_size * channels);
+    let mut pcm = vec![0i16; frame_size * channels];
+    let mut decoder = Decoder::new(input.sample_rate as i32, channels)
+        .map_err(|e| format!("opus_decoder_create failed: {}", e))?;
+    for (i, packet) in packets.iter().enumerate() {
+        let decoded = decoder
+            .decode(Some(&packet.data), &mut pcm, false)
+            .map_err(|e| format!("opus_decode failed on packet {}: {}", i, e))?;
+        samples.extend_from_slice(&pcm[..decoded * channels]);
+    }
+    let decode_time = start.elapsed();
+
+    Ok(Transcoded {
+        packets,
+        decoded: Wav {
+            sample_rate: input.sample_rate,
+            channels: input.channels,
+            samples,
+        },
+        encode_time,
+        decode_time,
+    })
+}
+
+/// Packets in `opus_demo`'s bitstream format, so `opus_demo -d` can decode
+/// them: each is preceded by its length and final range, both 32-bit
+/// big-endian
+pub fn write_packets(packets: &[Packet]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for packet in packets {
+        out.extend_from_slice(&(packet.data.len() as u32).to_be_bytes());
+        out.extend_from_slice(&packet.final_range.to_be_bytes());
+        out.extend_from_slice(&packet.data);
+    }
+    out
+}
+
+/// Minimal RIFF/WAVE reader: 16-bit integer PCM only, unknown chunks skipped
+pub fn read_wav(data: &[u8]) -> Result<Wav, String> {
+    if data.len() < 12 || &data[0..4] != b"RIFF" || &data[8..12] != b"WAVE" {
+        return Err("not a RIFF/WAVE file".into());
+    }
+
+    let u16_at = |at: usize| u16::from_le_bytes([data[at], data[at + 1]]);
+    let u32_at = |at: usize| u32::from_le_bytes(data[at..at + 4].try_into().unwrap());
+
+    let mut format = None;
+    let mut pos = 12;
+    while pos + 8 <= data.len() {
+        let id = &data[pos..pos + 4];
+        let size = u32_at(pos + 4) as usize;
+        let body = pos + 8;
+        let end = body.checked_add(size).filter(|&e| e <= data.len());
+        let end = end.ok_or("truncated WAV chunk")?;
+
+        match id {
+            b"fmt " if size >= 16 => {
+                let (tag, channels, rate, bits) = (
+                    u16_at(body),
+                    u16_at(body + 2),
+                    u32_at(body + 4),
+                    u16_at(body + 14),
+                );
+                // 1 = PCM, 0xFFFE = WAVE_FORMAT_EXTENSIBLE
+                if (tag != 1 && tag != 0xFFFE) || bits != 16 {
+                    return Err("only 16-bit PCM WAV is supported".into());
+                }
+                format = Some((rate, channels));
+            }
+            b"data" => {
+                let (sample_rate, channels) = format.ok_or("data chunk before fmt chunk")?;
+                if !matches!(sample_rate, 8000 | 12000 | 16000 | 24000 | 48000) {
+                    return Err(format!("unsupported sample rate {} Hz", sample_rate));
+                }
+                if !(1..=2).contains(&channels) {
+                    return Err(format!("unsupported channel count {}", channels));
+                }
+                let samples = data[body..end]
+                    .chunks_exact(2)
+                    .map(|b| i16::from_le_bytes([b[0], b[1]]))
+                    .collect();
+                return Ok(Wav {
+                    sample_rate,
+                    channels,
+                    samples,
+                });
+            }
+            _ => {}
+        }
+
+        // Chunks are padded to an even size
+        pos = end + (size & 1);
+    }
+
+    Err("no data chunk found".into())
+}
+
+pub fn write_wav(wav: &Wav) -> Vec<u8> {
+    let data_len = (wav.samples.len() * 2) as u32;
+    let block_align = wav.channels * 2;
+
+    let mut out = Vec::with_capacity(44 + data_len as usize);
+    out.extend_from_slice(b"RIFF");
+    out.extend_from_slice(&(36 + data_len).to_le_bytes());
+    out.extend_from_slice(b"WAVE");
+    out.extend_from_slice(b"fmt ");
+    out.extend_from_slice(&16u32.to_le_bytes());
+    out.extend_from_slice(&1u16.to_le_bytes());
+    out.extend_from_slice(&wav.channels.to_le_bytes());
+    out.extend_from_slice(&wav.sample_rate.to_le_bytes());
+    out.extend_from_slice(&(wav.sample_rate * block_align as u32).to_le_bytes());
+    out.extend_from_slice(&block_align.to_le_bytes());
+    out.extend_from_slice(&16u16.to_le_bytes());
+    out.extend_from_slice(b"data");
+    out.extend_from_slice(&data_len.to_le_bytes());
+    for sample in &wav.samples {
+        out.extend_from_slice(&sample.to_le_bytes());
+    }
+    out
+}
@@ -0,0 +1,124 @@
+//! Feeds arbitrary bytes to `opus_decode` / `opus_decode_float` through
+//! long-lived decoders, as a stream of packets from a hostile peer.
+//!
+//! The first byte picks the decoder and call, the rest is the packet (an
+//! empty one runs packet loss concealment):
+//!
+//! - bits 0-2: sample rate, an index into [`SAMPLE_RATES`] (modulo)
+//! - bit 3: stereo
+//! - bit 4: float output
+//! - bit 5: a 20 ms output buffer instead of room for 120 ms
+//! - bit 6: decode FEC
+//!
+//! Decoder state carries over between inputs, so a crash may depend on
+//! the inputs before it.
+//!
+//! Run with:
+//! ```bash
+//! cargo +nightly fuzz run decode fuzz/seeds/decode
+//! ```
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use opus_head_sys::*;
+use std::sync::{Mutex, OnceLock};
+
+const SAMPLE_RATES: [i32; 5] = [8000, 12000, 16000, 24000, 48000];
+
+/// One decoder per sample rate and channel count, never destroyed
+struct Decoders([[*mut OpusDecoder; 2]; SAMPLE_RATES.len()]);
+
+// Only used behind the mutex
+unsafe impl Send for Decoders {}
+
+fn decoders() -> &'static Mutex<Decoders> {
+    static DECODERS: OnceLock<Mutex<Decoders>> = OnceLock::new();
+    DECODERS.get_or_init(|| {
+        Mutex::new(Decoders(SAMPLE_RATES.map(|sample_rate| {
+            [1, 2].map(|channels| unsafe {
+                let mut error: i32 = 0;
+                let decoder = opus_decoder_create(sample_rate, channels, &mut error);
+                assert_eq!(error, OPUS_OK as i32, "Failed to create decoder");
+                decoder
+            })
+        })))
+    })
+}
+
+fuzz_target!(|data: &[u8]| {
+    let Some((&selector, packet)) = data.split_first() else {
+        return;
+    };
+    let sample_rate = SAMPLE_RATES[(selector & 0x7) as usize % SAMPLE_RATES.len()];
+    let channels = if selector & 0x8 != 0 { 2 } else { 1 };
+    let float = selector & 0x10 != 0;
+    let max_ms = if selector & 0x20 != 0 { 20 } else { 120 };
+    let fec = i32::from(selector & 0x40 != 0);
+
+    let capacity = sample_rate * max_ms / 1000;
+    let len = packet.len() as i32;
+    let decoders = decoders().lock().unwrap();
+    let decoder = decoders.0[(selector & 0x7) as usize % SAMPLE_RATES.len()][channels - 1];
+
+    unsafe {
+        let mut pcm = vec![0i16; capacity as usize * channels];
+        let mut float_pcm = vec![0f32; capacity as usize * channels];
+        let mut decode = |frame_size: i32| {
+            if float {
+                opus_decode_float(
+                    decoder,
+                    packet.as_ptr(),
+                    len,
+                    float_pcm.as_mut_ptr(),
+                    frame_size,
+                    fec,
+                )
+            } else {
+                opus_decode(
+                    decoder,
+                    packet.as_ptr(),
+                    len,
+                    pcm.as_mut_ptr(),
+                    frame_size,
+                    fec,
+                )
+            }
+        };
+
+        // Rejected before the decoder state is touched
+        assert_eq!(decode(-capacity), OPUS_BAD_ARG, "Negative frame size accepted");
+        assert_eq!(decode(0), OPUS_BAD_ARG, "Zero frame size accepted");
+
+        let ret = decode(capacity);
+        if packet.is_empty() {
+            assert!(
+                (0..=capacity).contains(&ret),
+                "Concealment returned {} for {} samples",
+                ret,
+                capacity
+            );
+            return;
+        }
+
+        // Room for the longest packet: only a malformed packet fails
+        let allowed: &[i32] = if max_ms == 120 {
+            &[OPUS_INVALID_PACKET]
+        } else {
+            &[OPUS_INVALID_PACKET, OPUS_BUFFER_TOO_SMALL]
+        };
+        assert!(
+            (0..=capacity).contains(&ret) || allowed.contains(&ret),
+            "Unexpected decode return {} for {} byte packet into {} samples",
+            ret,
+            packet.len(),
+            capacity
+        );
+
+        // A decoded packet is as long as the decoder says it is
+        if ret >= 0 && fec == 0 {
+            let samples = opus_decoder_get_nb_samples(decoder, packet.as_ptr(), len);
+            assert_eq!(ret, samples, "Decoded length differs from nb_samples");
+        }
+    }
+});
@@ -0,0 +1,87 @@
+//! Feeds arbitrary bytes to `opus_packet_parse` and
+//! `opus_packet_get_nb_samples`, and to the crate's own packet parsers
+//! built on them, checking that every reported frame lies inside the input
+//! and the durations agree.
+//!
+//! Run with:
+//! ```bash
+//! cargo +nightly fuzz run packet_parse fuzz/seeds/packet_parse
+//! ```
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use opus_head_sys::*;
+
+/// Most frames in a packet: 120 ms of 2.5 ms frames
+const MAX_FRAMES: usize = 48;
+/// Longest packet at 48 kHz: 120 ms
+const MAX_SAMPLES: i32 = 5760;
+/// Largest compressed frame
+const MAX_FRAME_BYTES: i16 = 1275;
+
+fuzz_target!(|data: &[u8]| {
+    let len = data.len() as i32;
+    let range = data.as_ptr_range();
+
+    let mut toc = 0u8;
+    let mut frames = [std::ptr::null(); MAX_FRAMES];
+    let mut sizes = [0i16; MAX_FRAMES];
+    let mut payload_offset = 0;
+    let count = unsafe {
+        opus_packet_parse(
+            data.as_ptr(),
+            len,
+            &mut toc,
+            frames.as_mut_ptr(),
+            sizes.as_mut_ptr(),
+            &mut payload_offset,
+        )
+    };
+    let samples = if data.is_empty() {
+        OPUS_BAD_ARG
+    } else {
+        unsafe { opus_packet_get_nb_samples(data.as_ptr(), len, 48000) }
+    };
+    assert!(samples <= MAX_SAMPLES, "{} samples in one packet", samples);
+
+    if count < 0 {
+        assert!(packet::parse(data).is_err());
+    } else {
+        let count = count as usize;
+        assert!((1..=MAX_FRAMES).contains(&count), "{} frames", count);
+        assert_eq!(toc, data[0]);
+        assert!((1..=len).contains(&payload_offset));
+        for (&frame, &size) in frames.iter().zip(&sizes).take(count) {
+            assert!((0..=MAX_FRAME_BYTES).contains(&size), "Frame of {} bytes", size);
+            assert!(range.contains(&frame) || (size == 0 && frame == range.end));
+            assert!(frame as usize + size as usize <= range.end as usize);
+        }
+
+        // A packet that parses has a duration, from its frame count
+        let per_frame = unsafe { opus_packet_get_samples_per_frame(data.as_ptr(), 48000) };
+        assert_eq!(samples, count as i32 * per_frame);
+
+        let parsed = packet::parse(data).expect("packet::parse rejected a valid packet");
+        assert_eq!(parsed.frames().len(), count);
+        assert_eq!(parsed.payload_offset, payload_offset as usize);
+        for (frame, &size) in parsed.frames().iter().zip(&sizes) {
+            assert_eq!(frame.len(), size as usize);
+        }
+    }
+
+    // The same bytes as the self-delimited packet at the start of a
+    // multistream packet
+    if let Ok((consumed, parsed)) = packet::parse_self_delimited(data) {
+        assert!((1..=data.len()).contains(&consumed));
+        assert!(parsed.payload_offset <= consumed);
+        let packet = data[..consumed].as_ptr_range();
+        for frame in parsed.frames() {
+            let frame = frame.as_ptr_range();
+            assert!(packet.start <= frame.start && frame.end <= packet.end);
+        }
+        let split = repacketizer::split_self_delimited(data).expect("split disagrees with parse");
+        assert_eq!(split.0.len(), parsed.frames().len());
+        assert_eq!(split.1, consumed);
+    }
+});
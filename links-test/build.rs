@@ -0,0 +1,40 @@
+//! Reads the metadata opus-head-sys emits for `links = "opus"`, as a -sys
+//! crate compiling C code against Opus would, and fails if it is missing or
+//! points nowhere
+
+use std::env;
+use std::path::Path;
+
+fn main() {
+    let include = dep_var("DEP_OPUS_INCLUDE");
+    let lib = dep_var("DEP_OPUS_LIB");
+    let root = dep_var("DEP_OPUS_ROOT");
+    assert_eq!(dep_var("DEP_OPUS_STATIC"), "1");
+
+    assert!(
+        Path::new(&include).join("opus.h").is_file(),
+        "DEP_OPUS_INCLUDE has no opus.h: {}",
+        include
+    );
+    assert!(
+        ["libopus.a", "opus.lib"]
+            .iter()
+            .any(|name| Path::new(&lib).join(name).is_file()),
+        "DEP_OPUS_LIB has no static library: {}",
+        lib
+    );
+    assert!(
+        Path::new(&root).is_dir(),
+        "DEP_OPUS_ROOT is missing: {}",
+        root
+    );
+
+    // For the tests
+    println!("cargo:rustc-env=OPUS_INCLUDE_DIR={}", include);
+    println!("cargo:rustc-env=OPUS_LIB_DIR={}", lib);
+}
+
+fn dep_var(name: &str) -> String {
+    println!("cargo:rerun-if-env-changed={}", name);
+    env::var(name).unwrap_or_else(|_| panic!("{} is not set", name))
+}
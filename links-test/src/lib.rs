@@ -0,0 +1,8 @@
+//! Where opus-head-sys put the Opus headers and library, as its
+//! `DEP_OPUS_*` metadata told the build script
+
+/// `DEP_OPUS_INCLUDE`, the directory with `opus.h`
+pub const INCLUDE_DIR: &str = env!("OPUS_INCLUDE_DIR");
+
+/// `DEP_OPUS_LIB`, the directory with the static library
+pub const LIB_DIR: &str = env!("OPUS_LIB_DIR");
@@ -0,0 +1,26 @@
+//! The metadata matches the library the Rust crate links
+
+use opus_head_links_test::{INCLUDE_DIR, LIB_DIR};
+use std::ffi::CStr;
+use std::path::Path;
+
+#[test]
+fn test_headers_match_library() {
+    let header = std::fs::read_to_string(Path::new(INCLUDE_DIR).join("opus_defines.h")).unwrap();
+    let version = unsafe { CStr::from_ptr(opus_head_sys::opus_get_version_string()) };
+    println!("{} {} {:?}", INCLUDE_DIR, LIB_DIR, version);
+
+    // The constants in the headers are the ones the bindings were made from
+    let voip = header
+        .lines()
+        .find_map(|line| line.strip_prefix("#define OPUS_APPLICATION_VOIP"))
+        .unwrap();
+    assert_eq!(
+        voip.trim().parse::<u32>(),
+        Ok(opus_head_sys::OPUS_APPLICATION_VOIP)
+    );
+    assert!(version
+        .to_str()
+        .unwrap()
+        .ends_with(opus_head_sys::VENDORED_VERSION));
+}
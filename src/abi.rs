@@ -0,0 +1,79 @@
+//! Compile-time checks that the committed bindings fit the target
+//!
+//! `src/bindings.rs` is generated once and used on every platform. That is
+//! only sound because it spells every C type through `core::ffi`, which
+//! follows the target: `c_char` is unsigned on ARM Linux, and nothing uses
+//! `c_long` or `size_t`, whose widths differ between Windows and Unix. The
+//! assertions below pin down what the bindings still assume, so building
+//! for a target where it doesn't hold fails instead of miscompiling calls.
+//! A binding regenerated with a platform-dependent type breaks the type
+//! identities here too.
+
+use crate::bindings::*;
+use core::ffi::{c_char, c_int, c_short, c_uchar, c_uint, c_ushort};
+use core::mem::{align_of, size_of};
+
+// opus_types.h uses the <stdint.h> types wherever it can; the bindings
+// must match them exactly, not just in size
+const _: fn(opus_int32) -> i32 = |x| x;
+const _: fn(opus_uint32) -> u32 = |x| x;
+const _: fn(opus_int16) -> i16 = |x| x;
+const _: fn(opus_uint16) -> u16 = |x| x;
+
+// `int` carries error codes, sample counts and every ctl argument
+const _: () = assert!(size_of::<c_int>() == 4 && size_of::<c_uint>() == 4);
+const _: () = assert!(size_of::<c_short>() == 2 && size_of::<c_ushort>() == 2);
+const _: () = assert!(size_of::<c_uchar>() == 1 && size_of::<c_char>() == 1);
+
+// Packets are passed as `*const c_uchar`, which the helpers build from
+// `&[u8]`
+const _: fn(*const c_uchar) -> *const u8 = |p| p;
+
+// Sample buffers are passed as `*mut opus_int16` from `&mut [i16]`
+const _: () = assert!(align_of::<opus_int16>() == align_of::<i16>());
+
+// The state structs are opaque and only handled through pointers
+const _: () = assert!(size_of::<OpusEncoder>() == 0 && size_of::<OpusDecoder>() == 0);
+const _: () = assert!(size_of::<OpusMSEncoder>() == 0 && size_of::<OpusMSDecoder>() == 0);
+const _: () = assert!(size_of::<OpusRepacketizer>() == 0);
+const _: () = assert!(size_of::<*mut OpusEncoder>() == size_of::<usize>());
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CStr;
+
+    #[test]
+    fn test_c_char_strings() {
+        // Read through c_char with whatever signedness the target has
+        let version = unsafe { CStr::from_ptr(opus_get_version_string()) };
+        assert!(version.to_str().unwrap().starts_with("libopus "));
+        let error = unsafe { CStr::from_ptr(opus_strerror(OPUS_BAD_ARG)) };
+        assert_eq!(error.to_str().unwrap(), "invalid argument");
+    }
+
+    #[test]
+    fn test_int32_ctl_roundtrip() {
+        // A value past 16 bits survives the varargs `opus_int32` path both
+        // ways, so the ctl argument widths agree with the C side
+        unsafe {
+            let mut error: c_int = 0;
+            let encoder =
+                opus_encoder_create(48000, 2, OPUS_APPLICATION_AUDIO as c_int, &mut error);
+            assert_eq!(error, OPUS_OK as c_int, "Failed to create encoder");
+
+            let bitrate: opus_int32 = 300_000;
+            let ret = opus_encoder_ctl(encoder, OPUS_SET_BITRATE_REQUEST as c_int, bitrate);
+            assert_eq!(ret, OPUS_OK as c_int);
+            let mut value: opus_int32 = 0;
+            let ret = opus_encoder_ctl(
+                encoder,
+                OPUS_GET_BITRATE_REQUEST as c_int,
+                &mut value as *mut opus_int32,
+            );
+            assert_eq!(ret, OPUS_OK as c_int);
+            assert_eq!(value, bitrate);
+            opus_encoder_destroy(encoder);
+        }
+    }
+}
@@ -0,0 +1,211 @@
+//! Measuring how closely decoded audio follows the input
+//!
+//! Opus isn't waveform-exact, so a roundtrip test can't compare samples;
+//! [`segmental_snr`] tells "resembles the input" from "garbled" instead.
+//! The decoder output lags the input by the encoder's lookahead, which
+//! [`compensate_delay`] removes first. [`roundtrip_snr`] does all of it for
+//! a configured [`StreamEncoder`].
+//!
+//! ```
+//! use opus_head_sys::analysis::roundtrip_snr;
+//! use opus_head_sys::stream::StreamEncoder;
+//! use opus_head_sys::test_util::sine;
+//!
+//! let mut encoder = StreamEncoder::music(48000, 1).unwrap();
+//! let signal: Vec<i16> = sine(440.0, 48000, 48000, 0.5);
+//! let snr = roundtrip_snr(&mut encoder, &signal).unwrap();
+//! assert!(snr > 10.0, "{} dB", snr);
+//! ```
+
+use crate::bindings::*;
+use crate::decoder::Decoder;
+use crate::stream::StreamEncoder;
+
+/// The range a segment's SNR is clamped to, in dB, so one silent-ish or
+/// perfect segment doesn't dominate the mean
+const SEGMENT_SNR_RANGE: (f64, f64) = (-10.0, 35.0);
+
+/// Segments with an RMS below this (about -60 dBFS) are left out
+const SILENT_RMS: f64 = 32.0;
+
+/// Root mean square of `samples`, 0 for none
+pub fn rms(samples: &[i16]) -> f64 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    let energy: f64 = samples.iter().map(|&s| f64::from(s).powi(2)).sum();
+    (energy / samples.len() as f64).sqrt()
+}
+
+/// Largest magnitude in `samples`, 32768 for `i16::MIN`
+pub fn peak(samples: &[i16]) -> u16 {
+    samples.iter().map(|s| s.unsigned_abs()).max().unwrap_or(0)
+}
+
+/// Mean SNR in dB of `test` against `reference` over segments of `frame`
+/// samples, each clamped to -10 to 35 dB. Segments where the reference
+/// is silent are skipped, so pauses don't drag the mean down.
+///
+/// Only the whole segments both have in common are compared. For
+/// interleaved audio, `frame` counts the samples of every channel, e.g.
+/// 1920 for 20 ms of 48 kHz stereo. NaN if no segment has signal, which
+/// fails any threshold a test asserts.
+///
+/// # Panics
+///
+/// If `frame` is 0.
+pub fn segmental_snr(reference: &[i16], test: &[i16], frame: usize) -> f32 {
+    let mut sum = 0.0;
+    let mut segments = 0;
+    for (reference, test) in reference.chunks_exact(frame).zip(test.chunks_exact(frame)) {
+        if rms(reference) < SILENT_RMS {
+            continue;
+        }
+        let (signal, noise) =
+            reference
+                .iter()
+                .zip(test)
+                .fold((0.0, 0.0), |(signal, noise), (&r, &t)| {
+                    let error = f64::from(r) - f64::from(t);
+                    (signal + f64::from(r).powi(2), noise + error * error)
+                });
+        let snr = if noise == 0.0 {
+            SEGMENT_SNR_RANGE.1
+        } else {
+            10.0 * (signal / noise).log10()
+        };
+        sum += snr.clamp(SEGMENT_SNR_RANGE.0, SEGMENT_SNR_RANGE.1);
+        segments += 1;
+    }
+    if segments == 0 {
+        return f32::NAN;
+    }
+    (sum / f64::from(segments)) as f32
+}
+
+/// `OPUS_GET_LOOKAHEAD` of `encoder`: how many samples per channel the
+/// decoder output lags the input by
+pub fn lookahead(encoder: &StreamEncoder) -> Result<usize, i32> {
+    let mut lookahead: i32 = 0;
+    let ret = unsafe {
+        opus_encoder_ctl(
+            encoder.as_ptr(),
+            OPUS_GET_LOOKAHEAD_REQUEST as i32,
+            &mut lookahead as *mut i32,
+        )
+    };
+    if ret != OPUS_OK as i32 {
+        return Err(ret);
+    }
+    Ok(lookahead as usize)
+}
+
+/// Interleaved `decoded` output with the first `delay` samples per channel
+/// dropped, so it lines up with the input
+pub fn compensate_delay(decoded: &[i16], delay: usize, channels: usize) -> &[i16] {
+    decoded.get(delay * channels..).unwrap_or(&[])
+}
+
+/// Encode interleaved `signal` with `encoder`, decode it again and return
+/// the [`segmental_snr`] of the output, delay compensated, over segments of
+/// the encoder's frame size.
+///
+/// The encoder is flushed with its lookahead in silence, so the whole
+/// signal is compared. Returns the Opus error code if encoding or decoding
+/// fails.
+pub fn roundtrip_snr(encoder: &mut StreamEncoder, signal: &[i16]) -> Result<f32, i32> {
+    let channels = encoder.channels();
+    let mut sample_rate: i32 = 0;
+    let ret = unsafe {
+        opus_encoder_ctl(
+            encoder.as_ptr(),
+            OPUS_GET_SAMPLE_RATE_REQUEST as i32,
+            &mut sample_rate as *mut i32,
+        )
+    };
+    if ret != OPUS_OK as i32 {
+        return Err(ret);
+    }
+    let delay = lookahead(encoder)?;
+
+    let mut packets = encoder.push(signal)?;
+    packets.extend(encoder.push(&vec![0; delay * channels])?);
+    packets.extend(encoder.finish()?);
+
+    let mut decoder = Decoder::new(sample_rate, channels)?;
+    let frame = encoder.frame_size() * channels;
+    let mut decoded = Vec::with_capacity(packets.len() * frame);
+    let mut pcm = vec![0i16; frame];
+    for packet in &packets {
+        let samples = decoder.decode(Some(packet), &mut pcm, false)?;
+        decoded.extend_from_slice(&pcm[..samples * channels]);
+    }
+    let decoded = compensate_delay(&decoded, delay, channels);
+    Ok(segmental_snr(signal, decoded, frame))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{noise, sine};
+
+    #[test]
+    fn test_rms_and_peak() {
+        assert_eq!(rms(&[]), 0.0);
+        assert_eq!(rms(&[3, -4, 3, -4]), 12.5f64.sqrt());
+        assert_eq!(peak(&[]), 0);
+        assert_eq!(peak(&[100, -200, 150]), 200);
+        assert_eq!(peak(&[i16::MIN]), 32768);
+        // A full scale sine has an RMS of peak / sqrt(2)
+        let pcm: Vec<i16> = sine(1000.0, 48000, 48000, 1.0);
+        assert!((rms(&pcm) - 32767.0 / 2f64.sqrt()).abs() < 1.0);
+    }
+
+    #[test]
+    fn test_segmental_snr() {
+        let reference: Vec<i16> = noise(1, 4800);
+        // Identical, and at the clamp
+        assert_eq!(segmental_snr(&reference, &reference, 480), 35.0);
+        // An error at a tenth of the amplitude is 20 dB down
+        let test: Vec<i16> = reference.iter().map(|&s| s - s / 10).collect();
+        let snr = segmental_snr(&reference, &test, 480);
+        assert!((snr - 20.0).abs() < 0.1, "{} dB", snr);
+        // Unrelated noise is about -3 dB
+        let snr = segmental_snr(&reference, &noise::<i16>(2, 4800), 480);
+        assert!((snr + 3.0).abs() < 0.5, "{} dB", snr);
+        // Silent output has as much error as signal
+        assert_eq!(segmental_snr(&reference, &[0; 4800], 480), 0.0);
+    }
+
+    #[test]
+    fn test_segmental_snr_skips_silence() {
+        let mut reference: Vec<i16> = noise(1, 960);
+        reference.extend([0; 960]);
+        let mut test = reference.clone();
+        // Anything in the silent half is ignored
+        test[960..].copy_from_slice(&noise::<i16>(2, 960));
+        assert_eq!(segmental_snr(&reference, &test, 480), 35.0);
+        assert!(segmental_snr(&[0; 960], &test, 480).is_nan());
+        // Only whole common segments count
+        assert!(segmental_snr(&reference[..479], &test, 480).is_nan());
+    }
+
+    #[test]
+    fn test_compensate_delay() {
+        let decoded = [0, 0, 0, 0, 1, 2, 3, 4];
+        assert_eq!(compensate_delay(&decoded, 2, 2), [1, 2, 3, 4]);
+        assert_eq!(compensate_delay(&decoded, 4, 1), [1, 2, 3, 4]);
+        assert!(compensate_delay(&decoded, 5, 2).is_empty());
+    }
+
+    #[test]
+    fn test_roundtrip_snr() {
+        let signal: Vec<i16> = sine(440.0, 48000, 48000, 0.5);
+        let mut encoder = StreamEncoder::music(48000, 1).unwrap();
+        unsafe { opus_encoder_ctl(encoder.as_ptr(), OPUS_SET_BITRATE_REQUEST as i32, 64000) };
+        let snr = roundtrip_snr(&mut encoder, &signal).unwrap();
+        assert!(snr > 15.0, "{} dB", snr);
+        // 2.5 ms of CELT overlap and 4 ms of delay compensation
+        assert_eq!(lookahead(&encoder), Ok(312));
+    }
+}
@@ -0,0 +1,203 @@
+//! Encode a 16-bit PCM WAV file to Ogg Opus, decode Ogg Opus to WAV, or
+//! both in one go
+//!
+//! ```text
+//! cargo install opus-head-sys --features cli
+//! opus-roundtrip input.wav output.opus [bitrate]
+//! opus-roundtrip input.opus output.wav
+//! opus-roundtrip input.wav output.wav [bitrate]
+//! ```
+//!
+//! The input's format is detected from its contents, the output's from its
+//! extension: `.wav` writes a WAV file, anything else Ogg Opus. WAV input
+//! must be 8, 12, 16, 24 or 48 kHz, mono or stereo, as there is no
+//! resampler. Decoding writes the sample rate recorded in the Ogg Opus
+//! header if Opus supports it, otherwise 48 kHz.
+
+mod ogg;
+mod wav;
+
+use opus_head_sys::decoder::Decoder;
+use opus_head_sys::stream::StreamEncoder;
+use opus_head_sys::*;
+use std::ffi::CStr;
+use std::fs;
+use std::process::ExitCode;
+use std::time::Instant;
+
+/// 20 ms frames, the usual choice for both voice and music
+const FRAME_MS: usize = 20;
+const DEFAULT_BITRATE: i32 = 64000;
+
+const USAGE: &str =
+    "usage: opus-roundtrip <input.wav|input.opus> <output.opus|output.wav> [bitrate]";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("opus-roundtrip: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: &[String]) -> Result<(), String> {
+    let (input, output) = match args {
+        [input, output] | [input, output, _] => (input, output),
+        _ => return Err(USAGE.into()),
+    };
+    let bitrate = match args.get(2) {
+        Some(b) => b.parse().map_err(|_| format!("invalid bitrate '{}'", b))?,
+        None => DEFAULT_BITRATE,
+    };
+    let to_wav = output.to_ascii_lowercase().ends_with(".wav");
+
+    let data = fs::read(input).map_err(|e| format!("{}: {}", input, e))?;
+    let bytes = if data.starts_with(b"OggS") {
+        if !to_wav {
+            return Err("an Ogg Opus input can only be decoded to a .wav file".into());
+        }
+        wav::write(&decode(&ogg::demux(&data)?)?)
+    } else {
+        let opus = encode(&wav::read(&data)?, bitrate)?;
+        if to_wav {
+            wav::write(&decode(&ogg::demux(&opus)?)?)
+        } else {
+            opus
+        }
+    };
+
+    fs::write(output, bytes).map_err(|e| format!("{}: {}", output, e))?;
+    println!("Wrote {}", output);
+    Ok(())
+}
+
+/// Encode to an Ogg Opus file
+fn encode(wav: &wav::Wav, bitrate: i32) -> Result<Vec<u8>, String> {
+    let channels = wav.channels as usize;
+    let frame_size = wav.sample_rate as usize * FRAME_MS / 1000;
+    let mut encoder = StreamEncoder::new(
+        wav.sample_rate as i32,
+        channels,
+        OPUS_APPLICATION_AUDIO as i32,
+        frame_size,
+    )
+    .map_err(|e| format!("opus_encoder_create failed: {}", opus_error(e)))?;
+
+    let mut lookahead: i32 = 0;
+    unsafe {
+        let ret = opus_encoder_ctl(encoder.as_ptr(), OPUS_SET_BITRATE_REQUEST as i32, bitrate);
+        if ret != OPUS_OK as i32 {
+            return Err(format!("invalid bitrate {}: {}", bitrate, opus_error(ret)));
+        }
+        opus_encoder_ctl(
+            encoder.as_ptr(),
+            OPUS_GET_LOOKAHEAD_REQUEST as i32,
+            &mut lookahead as *mut i32,
+        );
+    }
+
+    let start = Instant::now();
+    let encode_error = |e| format!("opus_encode failed: {}", opus_error(e));
+    let mut packets = encoder.push(&wav.samples).map_err(encode_error)?;
+    // The decoder output lags by the lookahead, which the pre-skip drops, so
+    // the input's last samples only come out of the encoder after it
+    let flush = vec![0; lookahead as usize * channels];
+    packets.extend(encoder.push(&flush).map_err(encode_error)?);
+    packets.extend(encoder.finish().map_err(encode_error)?);
+    let elapsed = start.elapsed();
+
+    let scale = 48000 / wav.sample_rate;
+    let head = ogg::OpusHead {
+        channels: wav.channels as u8,
+        pre_skip: (lookahead as u32 * scale) as u16,
+        input_sample_rate: wav.sample_rate,
+        output_gain: 0,
+    };
+    let vendor = unsafe { CStr::from_ptr(opus_get_version_string()) };
+    let file = ogg::mux(
+        &head,
+        &vendor.to_string_lossy(),
+        &packets,
+        wav.frames() as u64 * scale as u64,
+    );
+
+    let seconds = wav.frames() as f64 / wav.sample_rate as f64;
+    println!(
+        "Encoded {:.2} s ({} Hz, {} ch) to {} packets, {} bytes: {:.1} kbit/s (target {} kbit/s), {:.0}x realtime",
+        seconds,
+        wav.sample_rate,
+        channels,
+        packets.len(),
+        file.len(),
+        file.len() as f64 * 8.0 / seconds.max(f64::MIN_POSITIVE) / 1000.0,
+        bitrate / 1000,
+        seconds / elapsed.as_secs_f64().max(f64::MIN_POSITIVE)
+    );
+    Ok(file)
+}
+
+/// Decode an Ogg Opus stream, dropping the pre-skip and the padding after
+/// the end its last granule position marks
+fn decode(stream: &ogg::OpusStream) -> Result<wav::Wav, String> {
+    let head = &stream.head;
+    let sample_rate = match head.input_sample_rate {
+        rate @ (8000 | 12000 | 16000 | 24000 | 48000) => rate,
+        _ => 48000,
+    };
+    let channels = head.channels as usize;
+    // 120 ms, the longest packet
+    let mut pcm = vec![0i16; sample_rate as usize * 120 / 1000 * channels];
+    let mut samples = Vec::new();
+
+    let start = Instant::now();
+    let mut decoder = Decoder::new(sample_rate as i32, channels)
+        .map_err(|e| format!("opus_decoder_create failed: {}", opus_error(e)))?;
+    unsafe {
+        opus_decoder_ctl(
+            decoder.as_ptr(),
+            OPUS_SET_GAIN_REQUEST as i32,
+            head.output_gain as i32,
+        )
+    };
+    for (i, packet) in stream.packets.iter().enumerate() {
+        // An empty packet marks a lost one
+        let packet = (!packet.is_empty()).then_some(&packet[..]);
+        let decoded = decoder
+            .decode(packet, &mut pcm, false)
+            .map_err(|e| format!("packet {}: {}", i, opus_error(e)))?;
+        samples.extend_from_slice(&pcm[..decoded * channels]);
+    }
+    let elapsed = start.elapsed();
+
+    let scale = (48000 / sample_rate) as u64;
+    let pre_skip = (head.pre_skip as u64 / scale) as usize * channels;
+    samples.drain(..pre_skip.min(samples.len()));
+    if let Some(end) = stream.end {
+        let frames = end.saturating_sub(head.pre_skip as u64) / scale;
+        samples.truncate(frames as usize * channels);
+    }
+
+    let wav = wav::Wav {
+        sample_rate,
+        channels: head.channels as u16,
+        samples,
+    };
+    let seconds = wav.frames() as f64 / sample_rate as f64;
+    println!(
+        "Decoded {} packets to {:.2} s ({} Hz, {} ch), {:.0}x realtime",
+        stream.packets.len(),
+        seconds,
+        sample_rate,
+        channels,
+        seconds / elapsed.as_secs_f64().max(f64::MIN_POSITIVE)
+    );
+    Ok(wav)
+}
+
+fn opus_error(code: i32) -> String {
+    let message = unsafe { CStr::from_ptr(opus_strerror(code)) };
+    format!("{} ({})", message.to_string_lossy(), code)
+}
@@ -0,0 +1,323 @@
+//! Minimal Ogg Opus (RFC 7845) muxing and demuxing
+//!
+//! Enough for a single logical stream with channel mapping family 0 (mono or
+//! stereo): the `OpusHead` and `OpusTags` headers, then the audio packets,
+//! grouped into pages of up to a second. Granule positions count 48 kHz
+//! samples including the pre-skip, and the last page's trims the padding
+//! after the end of the input.
+
+/// Serial number of the one logical stream; nothing is chained or
+/// multiplexed with it
+const SERIAL: u32 = 0x4f70_7573;
+
+/// Audio per page, in 48 kHz samples, like opusenc's default page delay
+const PAGE_SAMPLES: u64 = 48000;
+
+/// Page header flags
+const CONTINUED: u8 = 0x01;
+const BOS: u8 = 0x02;
+const EOS: u8 = 0x04;
+
+/// The identification header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpusHead {
+    pub channels: u8,
+    /// 48 kHz samples to drop from the start of the decoded output
+    pub pre_skip: u16,
+    /// Sample rate of the original input, for information only
+    pub input_sample_rate: u32,
+    /// Gain to apply to the decoded output, in Q7.8 dB
+    pub output_gain: i16,
+}
+
+impl OpusHead {
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(19);
+        out.extend_from_slice(b"OpusHead");
+        out.push(1);
+        out.push(self.channels);
+        out.extend_from_slice(&self.pre_skip.to_le_bytes());
+        out.extend_from_slice(&self.input_sample_rate.to_le_bytes());
+        out.extend_from_slice(&self.output_gain.to_le_bytes());
+        // Channel mapping family 0: mono or stereo, no mapping table
+        out.push(0);
+        out
+    }
+
+    fn parse(data: &[u8]) -> Result<Self, String> {
+        if data.len() < 19 || &data[..8] != b"OpusHead" {
+            return Err("not an Ogg Opus stream".into());
+        }
+        // Only the major version (upper 4 bits) breaks compatibility
+        if data[8] >> 4 != 0 {
+            return Err(format!("unsupported Ogg Opus version {}", data[8]));
+        }
+        let channels = data[9];
+        if data[18] != 0 || !(1..=2).contains(&channels) {
+            return Err(format!(
+                "only mono and stereo are supported, not {} channels with mapping family {}",
+                channels, data[18]
+            ));
+        }
+        Ok(Self {
+            channels,
+            pre_skip: u16::from_le_bytes([data[10], data[11]]),
+            input_sample_rate: u32::from_le_bytes(data[12..16].try_into().unwrap()),
+            output_gain: i16::from_le_bytes([data[16], data[17]]),
+        })
+    }
+}
+
+/// The comment header with a vendor string and no comments
+fn opus_tags(vendor: &str) -> Vec<u8> {
+    let mut out = Vec::with_capacity(16 + vendor.len());
+    out.extend_from_slice(b"OpusTags");
+    out.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+    out.extend_from_slice(vendor.as_bytes());
+    out.extend_from_slice(&0u32.to_le_bytes());
+    out
+}
+
+/// Mux `packets` into an Ogg Opus file. `samples` is the length of the
+/// input in 48 kHz samples, which the packets must cover after the
+/// pre-skip.
+pub fn mux(head: &OpusHead, vendor: &str, packets: &[Vec<u8>], samples: u64) -> Vec<u8> {
+    let mut writer = PageWriter::default();
+    writer.page(BOS, 0, &[&head.to_bytes()]);
+    writer.page(0, 0, &[&opus_tags(vendor)]);
+
+    let end = head.pre_skip as u64 + samples;
+    let mut granule = head.pre_skip as u64;
+    let mut page: Vec<&[u8]> = Vec::new();
+    let (mut page_start, mut segments) = (granule, 0);
+    for (i, packet) in packets.iter().enumerate() {
+        let packet_segments = packet.len() / 255 + 1;
+        if segments + packet_segments > 255 {
+            writer.page(0, granule, &page);
+            page.clear();
+            (page_start, segments) = (granule, 0);
+        }
+        page.push(packet);
+        segments += packet_segments;
+        granule += opus_head_sys::packet::samples(packet, 48000).unwrap_or(0) as u64;
+
+        if i + 1 == packets.len() {
+            writer.page(EOS, granule.min(end), &page);
+        } else if granule - page_start >= PAGE_SAMPLES {
+            writer.page(0, granule, &page);
+            page.clear();
+            (page_start, segments) = (granule, 0);
+        }
+    }
+    if packets.is_empty() {
+        writer.page(EOS, head.pre_skip as u64, &[]);
+    }
+    writer.out
+}
+
+#[derive(Default)]
+struct PageWriter {
+    sequence: u32,
+    out: Vec<u8>,
+}
+
+impl PageWriter {
+    /// Write whole `packets` as one page, at most 255 lacing values
+    fn page(&mut self, flags: u8, granule: u64, packets: &[&[u8]]) {
+        let start = self.out.len();
+        let mut lacing = Vec::new();
+        for packet in packets {
+            lacing.extend(std::iter::repeat_n(255, packet.len() / 255));
+            lacing.push((packet.len() % 255) as u8);
+        }
+
+        self.out.extend_from_slice(b"OggS");
+        self.out.push(0);
+        self.out.push(flags);
+        self.out.extend_from_slice(&granule.to_le_bytes());
+        self.out.extend_from_slice(&SERIAL.to_le_bytes());
+        self.out.extend_from_slice(&self.sequence.to_le_bytes());
+        self.out.extend_from_slice(&[0; 4]);
+        self.out.push(lacing.len() as u8);
+        self.out.extend_from_slice(&lacing);
+        for packet in packets {
+            self.out.extend_from_slice(packet);
+        }
+
+        let crc = crc32(&self.out[start..]);
+        self.out[start + 22..start + 26].copy_from_slice(&crc.to_le_bytes());
+        self.sequence += 1;
+    }
+}
+
+/// A demuxed Ogg Opus stream
+#[derive(Debug)]
+pub struct OpusStream {
+    pub head: OpusHead,
+    /// Audio packets, after the headers
+    pub packets: Vec<Vec<u8>>,
+    /// Granule position of the last page, if any page had one
+    pub end: Option<u64>,
+}
+
+/// Demux the first logical stream of an Ogg file, checking page CRCs
+pub fn demux(mut data: &[u8]) -> Result<OpusStream, String> {
+    let mut serial = None;
+    let mut packets = Vec::new();
+    let mut partial: Vec<u8> = Vec::new();
+    let mut end = None;
+
+    for index in 0.. {
+        if data.is_empty() {
+            break;
+        }
+        if data.len() < 27 || &data[..4] != b"OggS" || data[4] != 0 {
+            return Err("not an Ogg file or truncated page".into());
+        }
+        let header_len = 27 + data[26] as usize;
+        let lacing = data.get(27..header_len).ok_or("truncated page")?;
+        let page_len = header_len + lacing.iter().map(|&l| l as usize).sum::<usize>();
+        let page = data.get(..page_len).ok_or("truncated page")?;
+        data = &data[page_len..];
+
+        let mut check = page.to_vec();
+        check[22..26].fill(0);
+        if crc32(&check) != u32::from_le_bytes(page[22..26].try_into().unwrap()) {
+            return Err(format!("CRC mismatch in page {}", index));
+        }
+        let page_serial = u32::from_le_bytes(page[14..18].try_into().unwrap());
+        if *serial.get_or_insert(page_serial) != page_serial {
+            continue;
+        }
+        if page[5] & CONTINUED == 0 {
+            partial.clear();
+        }
+
+        let mut body = &page[header_len..];
+        for &len in lacing {
+            partial.extend_from_slice(&body[..len as usize]);
+            body = &body[len as usize..];
+            if len < 255 {
+                packets.push(std::mem::take(&mut partial));
+            }
+        }
+        let granule = u64::from_le_bytes(page[6..14].try_into().unwrap());
+        // -1: no packet ends on this page
+        if granule != u64::MAX {
+            end = Some(granule);
+        }
+        if page[5] & EOS != 0 {
+            break;
+        }
+    }
+
+    let mut packets = packets.into_iter();
+    let head = OpusHead::parse(&packets.next().ok_or("empty Ogg stream")?)?;
+    match packets.next() {
+        Some(tags) if tags.starts_with(b"OpusTags") => {}
+        _ => return Err("missing OpusTags header".into()),
+    }
+    Ok(OpusStream {
+        head,
+        packets: packets.collect(),
+        end,
+    })
+}
+
+/// Ogg's CRC-32: polynomial 0x04c11db7, no reflection, zero initial value
+/// and final XOR
+fn crc32(data: &[u8]) -> u32 {
+    const TABLE: [u32; 256] = {
+        let mut table = [0u32; 256];
+        let mut i = 0;
+        while i < 256 {
+            let mut r = (i as u32) << 24;
+            let mut bit = 0;
+            while bit < 8 {
+                r = if r & 0x8000_0000 != 0 {
+                    (r << 1) ^ 0x04c1_1db7
+                } else {
+                    r << 1
+                };
+                bit += 1;
+            }
+            table[i] = r;
+            i += 1;
+        }
+        table
+    };
+    data.iter().fold(0, |crc, &byte| {
+        (crc << 8) ^ TABLE[((crc >> 24) as u8 ^ byte) as usize]
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn head() -> OpusHead {
+        OpusHead {
+            channels: 2,
+            pre_skip: 312,
+            input_sample_rate: 44100,
+            output_gain: -256,
+        }
+    }
+
+    #[test]
+    fn test_crc32() {
+        // CRC-32 with these parameters, as catalogued for CRC-32/CKSUM
+        // without its final XOR
+        assert_eq!(crc32(b"123456789"), 0x89a1_897f);
+        assert_eq!(crc32(b""), 0);
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        // 20 ms CELT packets (TOC 0xfc) of sizes around the lacing boundary
+        let packets: Vec<Vec<u8>> = (0..200)
+            .map(|i| {
+                let mut packet = vec![0xfc];
+                packet.resize([1, 254, 255, 256, 510, 1275][i % 6], i as u8);
+                packet
+            })
+            .collect();
+        let samples = 200 * 960 - 700;
+        let file = mux(&head(), "test", &packets, samples);
+
+        let stream = demux(&file).unwrap();
+        assert_eq!(stream.head, head());
+        assert_eq!(stream.packets, packets);
+        assert_eq!(stream.end, Some(312 + samples));
+
+        // Four seconds of audio, plus the two header pages
+        let pages = file.windows(4).filter(|w| w == b"OggS").count();
+        assert!((6..10).contains(&pages), "{} pages", pages);
+    }
+
+    #[test]
+    fn test_rejects_corruption() {
+        let packets = vec![vec![0xfc, 1, 2, 3]; 10];
+        let mut file = mux(&head(), "test", &packets, 9000);
+        let last = file.len() - 1;
+        file[last] ^= 1;
+        assert!(demux(&file).unwrap_err().contains("CRC"));
+        assert!(demux(&file[..file.len() - 2]).is_err());
+        assert!(demux(b"RIFF").is_err());
+    }
+
+    #[test]
+    fn test_head() {
+        let bytes = head().to_bytes();
+        assert_eq!(bytes.len(), 19);
+        assert_eq!(OpusHead::parse(&bytes).unwrap(), head());
+
+        let mut surround = bytes.clone();
+        surround[9] = 6;
+        surround[18] = 1;
+        assert!(OpusHead::parse(&surround).is_err());
+        let mut version = bytes;
+        version[8] = 0x10;
+        assert!(OpusHead::parse(&version).is_err());
+    }
+}
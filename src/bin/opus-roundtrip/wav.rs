@@ -0,0 +1,139 @@
+//! 16-bit PCM WAV reading and writing
+
+#[derive(Debug)]
+pub struct Wav {
+    pub sample_rate: u32,
+    pub channels: u16,
+    /// Interleaved samples
+    pub samples: Vec<i16>,
+}
+
+impl Wav {
+    /// Samples per channel
+    pub fn frames(&self) -> usize {
+        self.samples.len() / self.channels.max(1) as usize
+    }
+}
+
+/// Minimal RIFF/WAVE reader: 16-bit integer PCM only, unknown chunks skipped
+pub fn read(data: &[u8]) -> Result<Wav, String> {
+    if data.len() < 12 || &data[0..4] != b"RIFF" || &data[8..12] != b"WAVE" {
+        return Err("not a RIFF/WAVE file".into());
+    }
+
+    let u16_at = |at: usize| u16::from_le_bytes([data[at], data[at + 1]]);
+    let u32_at = |at: usize| u32::from_le_bytes(data[at..at + 4].try_into().unwrap());
+
+    let mut format = None;
+    let mut pos = 12;
+    while pos + 8 <= data.len() {
+        let id = &data[pos..pos + 4];
+        let size = u32_at(pos + 4) as usize;
+        let body = pos + 8;
+        let end = body.checked_add(size).filter(|&e| e <= data.len());
+        let end = end.ok_or("truncated WAV chunk")?;
+
+        match id {
+            b"fmt " if size >= 16 => {
+                let (tag, channels, rate, bits) = (
+                    u16_at(body),
+                    u16_at(body + 2),
+                    u32_at(body + 4),
+                    u16_at(body + 14),
+                );
+                // 1 = PCM, 0xFFFE = WAVE_FORMAT_EXTENSIBLE
+                if (tag != 1 && tag != 0xFFFE) || bits != 16 {
+                    return Err("only 16-bit PCM WAV is supported".into());
+                }
+                format = Some((rate, channels));
+            }
+            b"data" => {
+                let (sample_rate, channels) = format.ok_or("data chunk before fmt chunk")?;
+                if !matches!(sample_rate, 8000 | 12000 | 16000 | 24000 | 48000) {
+                    return Err(format!(
+                        "unsupported sample rate {} Hz, Opus takes 8, 12, 16, 24 or 48 kHz",
+                        sample_rate
+                    ));
+                }
+                if !(1..=2).contains(&channels) {
+                    return Err(format!("unsupported channel count {}", channels));
+                }
+                let samples = data[body..end]
+                    .chunks_exact(2)
+                    .map(|b| i16::from_le_bytes([b[0], b[1]]))
+                    .collect();
+                return Ok(Wav {
+                    sample_rate,
+                    channels,
+                    samples,
+                });
+            }
+            _ => {}
+        }
+
+        // Chunks are padded to an even size
+        pos = end + (size & 1);
+    }
+
+    Err("no data chunk found".into())
+}
+
+pub fn write(wav: &Wav) -> Vec<u8> {
+    let data_len = (wav.samples.len() * 2) as u32;
+    let block_align = wav.channels * 2;
+
+    let mut out = Vec::with_capacity(44 + data_len as usize);
+    out.extend_from_slice(b"RIFF");
+    out.extend_from_slice(&(36 + data_len).to_le_bytes());
+    out.extend_from_slice(b"WAVE");
+    out.extend_from_slice(b"fmt ");
+    out.extend_from_slice(&16u32.to_le_bytes());
+    out.extend_from_slice(&1u16.to_le_bytes());
+    out.extend_from_slice(&wav.channels.to_le_bytes());
+    out.extend_from_slice(&wav.sample_rate.to_le_bytes());
+    out.extend_from_slice(&(wav.sample_rate * block_align as u32).to_le_bytes());
+    out.extend_from_slice(&block_align.to_le_bytes());
+    out.extend_from_slice(&16u16.to_le_bytes());
+    out.extend_from_slice(b"data");
+    out.extend_from_slice(&data_len.to_le_bytes());
+    for sample in &wav.samples {
+        out.extend_from_slice(&sample.to_le_bytes());
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let wav = Wav {
+            sample_rate: 16000,
+            channels: 2,
+            samples: vec![0, 1, -1, i16::MAX, i16::MIN, 1234],
+        };
+        let bytes = write(&wav);
+        assert_eq!(bytes.len(), 44 + 12);
+        let read = read(&bytes).unwrap();
+        assert_eq!(read.sample_rate, 16000);
+        assert_eq!(read.channels, 2);
+        assert_eq!(read.samples, wav.samples);
+        assert_eq!(read.frames(), 3);
+    }
+
+    #[test]
+    fn test_rejects_unsupported() {
+        let mut bytes = write(&Wav {
+            sample_rate: 44100,
+            channels: 1,
+            samples: vec![0; 10],
+        });
+        assert!(read(&bytes).unwrap_err().contains("44100"));
+        // 8-bit
+        bytes[24..28].copy_from_slice(&48000u32.to_le_bytes());
+        bytes[34] = 8;
+        assert!(read(&bytes).is_err());
+        assert!(read(b"OggS").is_err());
+    }
+}
@@ -0,0 +1,201 @@
+//! Loss-based adaptive bitrate control
+//!
+//! A small controller for senders that get packet loss feedback (e.g. RTCP
+//! receiver reports). Each report moves the bitrate with the same loss
+//! thresholds as WebRTC's loss-based estimator, and sets the packet loss
+//! percentage so the encoder spends more on in-band FEC as loss rises:
+//!
+//! | reported loss | bitrate              |
+//! |---------------|----------------------|
+//! | below 2%      | +8%                  |
+//! | 2% to 10%     | unchanged            |
+//! | above 10%     | `* (1 - loss / 2)`   |
+//!
+//! The packet loss percentage only has an effect with in-band FEC enabled
+//! (`OPUS_SET_INBAND_FEC`) and on the SILK or hybrid path.
+
+use crate::bindings::*;
+
+/// Loss below which the bitrate ramps up
+const INCREASE_BELOW: f32 = 0.02;
+/// Loss above which the bitrate backs off
+const DECREASE_ABOVE: f32 = 0.10;
+/// Multiplicative ramp-up per report
+const INCREASE_FACTOR: f32 = 1.08;
+
+/// Encoder settings chosen for one loss report
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BitrateUpdate {
+    /// Value for `OPUS_SET_BITRATE`, in bits per second
+    pub bitrate: i32,
+    /// Value for `OPUS_SET_PACKET_LOSS_PERC`, 0..=100
+    pub packet_loss_perc: i32,
+}
+
+/// Loss-based bitrate controller, see the [module docs](self)
+#[derive(Debug, Clone)]
+pub struct AdaptiveBitrate {
+    min: i32,
+    max: i32,
+    current: BitrateUpdate,
+}
+
+impl AdaptiveBitrate {
+    /// Start at `target` bits per second, staying within `min..=max`.
+    ///
+    /// Returns `None` unless `0 < min <= target <= max`.
+    pub fn new(target: i32, min: i32, max: i32) -> Option<Self> {
+        (0 < min && min <= target && target <= max).then_some(AdaptiveBitrate {
+            min,
+            max,
+            current: BitrateUpdate {
+                bitrate: target,
+                packet_loss_perc: 0,
+            },
+        })
+    }
+
+    /// The settings from the last report, or the initial target
+    pub fn current(&self) -> BitrateUpdate {
+        self.current
+    }
+
+    /// Feed a loss fraction (0.0..=1.0, as in an RTCP "fraction lost")
+    /// and get the new settings. Out-of-range and NaN values are clamped.
+    pub fn update(&mut self, loss: f32) -> BitrateUpdate {
+        let loss = if loss.is_nan() {
+            0.0
+        } else {
+            loss.clamp(0.0, 1.0)
+        };
+
+        let bitrate = self.current.bitrate as f32;
+        let bitrate = if loss < INCREASE_BELOW {
+            bitrate * INCREASE_FACTOR
+        } else if loss > DECREASE_ABOVE {
+            bitrate * (1.0 - loss / 2.0)
+        } else {
+            bitrate
+        };
+
+        self.current = BitrateUpdate {
+            bitrate: (bitrate.round() as i32).clamp(self.min, self.max),
+            packet_loss_perc: (loss * 100.0).round() as i32,
+        };
+        self.current
+    }
+
+    /// Set the current bitrate and packet loss percentage on `encoder`.
+    ///
+    /// Returns `OPUS_OK` or the first error reported by `opus_encoder_ctl`.
+    ///
+    /// # Safety
+    ///
+    /// `encoder` must be a valid encoder from `opus_encoder_create` or
+    /// `opus_encoder_init`.
+    pub unsafe fn apply(&self, encoder: *mut OpusEncoder) -> i32 {
+        let ret = opus_encoder_ctl(
+            encoder,
+            OPUS_SET_BITRATE_REQUEST as i32,
+            self.current.bitrate,
+        );
+        if ret != OPUS_OK as i32 {
+            return ret;
+        }
+        opus_encoder_ctl(
+            encoder,
+            OPUS_SET_PACKET_LOSS_PERC_REQUEST as i32,
+            self.current.packet_loss_perc,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_invalid_ranges() {
+        assert!(AdaptiveBitrate::new(32000, 0, 64000).is_none());
+        assert!(AdaptiveBitrate::new(8000, 16000, 64000).is_none());
+        assert!(AdaptiveBitrate::new(96000, 16000, 64000).is_none());
+        assert!(AdaptiveBitrate::new(16000, 16000, 16000).is_some());
+    }
+
+    #[test]
+    fn test_monotonic_in_loss() {
+        let start = AdaptiveBitrate::new(32000, 6000, 128000).unwrap();
+
+        let mut previous: Option<BitrateUpdate> = None;
+        for step in 0..=100 {
+            let loss = step as f32 / 100.0;
+            let update = start.clone().update(loss);
+
+            if let Some(previous) = previous {
+                assert!(
+                    update.bitrate <= previous.bitrate,
+                    "bitrate rose from {} to {} at {} loss",
+                    previous.bitrate,
+                    update.bitrate,
+                    loss
+                );
+                assert!(update.packet_loss_perc >= previous.packet_loss_perc);
+            }
+            previous = Some(update);
+        }
+    }
+
+    #[test]
+    fn test_converges_to_limits() {
+        let mut abr = AdaptiveBitrate::new(32000, 6000, 64000).unwrap();
+
+        for _ in 0..100 {
+            abr.update(0.0);
+        }
+        assert_eq!(abr.current().bitrate, 64000);
+        assert_eq!(abr.current().packet_loss_perc, 0);
+
+        for _ in 0..100 {
+            abr.update(0.5);
+        }
+        assert_eq!(abr.current().bitrate, 6000);
+        assert_eq!(abr.current().packet_loss_perc, 50);
+
+        // Moderate loss holds the bitrate
+        let held = abr.update(0.05);
+        assert_eq!(held.bitrate, 6000);
+        assert_eq!(abr.update(f32::NAN).packet_loss_perc, 0);
+        assert_eq!(abr.update(2.0).packet_loss_perc, 100);
+    }
+
+    #[test]
+    fn test_apply_sets_encoder_ctls() {
+        let mut abr = AdaptiveBitrate::new(32000, 6000, 64000).unwrap();
+        let update = abr.update(0.2);
+
+        unsafe {
+            let mut error: i32 = 0;
+            let encoder = opus_encoder_create(48000, 1, OPUS_APPLICATION_VOIP as i32, &mut error);
+            assert_eq!(error, OPUS_OK as i32, "Failed to create encoder");
+
+            assert_eq!(abr.apply(encoder), OPUS_OK as i32);
+
+            let mut value: i32 = -1;
+            opus_encoder_ctl(
+                encoder,
+                OPUS_GET_BITRATE_REQUEST as i32,
+                &mut value as *mut i32,
+            );
+            assert_eq!(value, update.bitrate);
+            opus_encoder_ctl(
+                encoder,
+                OPUS_GET_PACKET_LOSS_PERC_REQUEST as i32,
+                &mut value as *mut i32,
+            );
+            assert_eq!(value, 20);
+
+            opus_encoder_destroy(encoder);
+        }
+    }
+}
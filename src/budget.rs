@@ -0,0 +1,258 @@
+//! Per-packet byte budgets
+//!
+//! `opus_encode` never writes more than `max_data_bytes`, but it picks the
+//! mode and bandwidth from the configured bitrate and only then squeezes
+//! the frame into the buffer. When the budget is well below the bitrate,
+//! e.g. a single-MTU packet on a constrained link, that wastes bits on a
+//! bandwidth the budget can't carry. [`encode_capped`] lowers the bitrate
+//! to what the budget allows for that one packet instead.
+
+use crate::bindings::*;
+
+/// Lowest bitrate Opus accepts with `OPUS_SET_BITRATE`
+const MIN_BITRATE: i64 = 500;
+
+/// The bitrate that fills `max_bytes` per frame of `frame_size` samples
+/// at `sample_rate`
+pub fn budget_bitrate(max_bytes: usize, frame_size: usize, sample_rate: i32) -> i32 {
+    if frame_size == 0 {
+        return i32::MAX;
+    }
+    let bitrate = max_bytes as i64 * 8 * i64::from(sample_rate) / frame_size as i64;
+    bitrate.clamp(MIN_BITRATE, i64::from(i32::MAX)) as i32
+}
+
+/// Encode one frame with `opus_encode` into at most `max_bytes` bytes.
+///
+/// `pcm` holds interleaved samples for `channels` channels; its length sets
+/// the frame size. The packet is also limited to `out.len()`. Returns the
+/// packet length, or the Opus error code.
+///
+/// If the encoder's bitrate (`OPUS_GET_BITRATE`) is above what the budget
+/// allows, it is lowered with `OPUS_SET_BITRATE` for this packet and set
+/// back afterwards, so the global setting keeps applying to the packets
+/// encoded without a cap. Opus only reports the bitrate it resolves
+/// `OPUS_AUTO` and `OPUS_BITRATE_MAX` to, so after a lowered packet those
+/// come back as that explicit bitrate. Under the budget, the encoder is
+/// left untouched.
+///
+/// # Safety
+///
+/// `encoder` must be a valid encoder created for `channels` channels.
+pub unsafe fn encode_capped(
+    encoder: *mut OpusEncoder,
+    pcm: &[i16],
+    channels: usize,
+    max_bytes: usize,
+    out: &mut [u8],
+) -> Result<usize, i32> {
+    if channels == 0
+        || !pcm.len().is_multiple_of(channels)
+        || pcm.len() / channels > i32::MAX as usize
+    {
+        return Err(OPUS_BAD_ARG);
+    }
+    let frame_size = pcm.len() / channels;
+    let max_bytes = max_bytes.min(out.len()).min(i32::MAX as usize);
+
+    let mut bitrate: i32 = 0;
+    let ret = opus_encoder_ctl(
+        encoder,
+        OPUS_GET_BITRATE_REQUEST as i32,
+        &mut bitrate as *mut i32,
+    );
+    if ret != OPUS_OK as i32 {
+        return Err(ret);
+    }
+    let mut sample_rate: i32 = 0;
+    let ret = opus_encoder_ctl(
+        encoder,
+        OPUS_GET_SAMPLE_RATE_REQUEST as i32,
+        &mut sample_rate as *mut i32,
+    );
+    if ret != OPUS_OK as i32 {
+        return Err(ret);
+    }
+
+    let capped = budget_bitrate(max_bytes, frame_size, sample_rate);
+    let lowered = bitrate > capped;
+    if lowered {
+        let ret = opus_encoder_ctl(encoder, OPUS_SET_BITRATE_REQUEST as i32, capped);
+        if ret != OPUS_OK as i32 {
+            return Err(ret);
+        }
+    }
+
+    let len = opus_encode(
+        encoder,
+        pcm.as_ptr(),
+        frame_size as i32,
+        out.as_mut_ptr(),
+        max_bytes as i32,
+    );
+
+    if lowered {
+        let ret = opus_encoder_ctl(encoder, OPUS_SET_BITRATE_REQUEST as i32, bitrate);
+        if len >= 0 && ret != OPUS_OK as i32 {
+            return Err(ret);
+        }
+    }
+
+    if len < 0 {
+        Err(len)
+    } else {
+        Ok(len as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: i32 = 48000;
+    const FRAME_SIZE: usize = 960; // 20ms at 48kHz
+
+    /// A 440 Hz tone with some noise, so every frame needs bits
+    fn signal(frame: usize, channels: usize) -> Vec<i16> {
+        (0..FRAME_SIZE * channels)
+            .map(|i| {
+                let t = (frame * FRAME_SIZE + i / channels) as f32 / SAMPLE_RATE as f32;
+                let tone = (t * 440.0 * std::f32::consts::TAU).sin() * 12000.0;
+                let noise = ((i * 7919 + frame * 104729) % 2001) as f32 - 1000.0;
+                (tone + noise) as i16
+            })
+            .collect()
+    }
+
+    fn get_bitrate(encoder: *mut OpusEncoder) -> i32 {
+        let mut bitrate: i32 = 0;
+        let ret = unsafe {
+            opus_encoder_ctl(
+                encoder,
+                OPUS_GET_BITRATE_REQUEST as i32,
+                &mut bitrate as *mut i32,
+            )
+        };
+        assert_eq!(ret, OPUS_OK as i32);
+        bitrate
+    }
+
+    #[test]
+    fn test_budget_bitrate() {
+        // 100 bytes every 20 ms
+        assert_eq!(budget_bitrate(100, 960, 48000), 40000);
+        assert_eq!(budget_bitrate(100, 160, 8000), 40000);
+        assert_eq!(budget_bitrate(0, 960, 48000), 500);
+        assert_eq!(budget_bitrate(100, 0, 48000), i32::MAX);
+    }
+
+    #[test]
+    fn test_packets_fit_budget() {
+        const BUDGET: usize = 40;
+
+        unsafe {
+            let mut error: i32 = 0;
+            let encoder =
+                opus_encoder_create(SAMPLE_RATE, 2, OPUS_APPLICATION_AUDIO as i32, &mut error);
+            assert_eq!(error, OPUS_OK as i32, "Failed to create encoder");
+            let decoder = opus_decoder_create(SAMPLE_RATE, 2, &mut error);
+            assert_eq!(error, OPUS_OK as i32, "Failed to create decoder");
+
+            let ret = opus_encoder_ctl(encoder, OPUS_SET_BITRATE_REQUEST as i32, 128000);
+            assert_eq!(ret, OPUS_OK as i32);
+
+            let mut packet = [0u8; 1275];
+            let mut output = vec![0i16; FRAME_SIZE * 2];
+            for frame in 0..20 {
+                let len = encode_capped(encoder, &signal(frame, 2), 2, BUDGET, &mut packet)
+                    .expect("encode_capped failed");
+                assert!(len > 0 && len <= BUDGET, "frame {}: {} bytes", frame, len);
+                // The global setting applies again once the packet is done
+                assert_eq!(get_bitrate(encoder), 128000);
+
+                let decoded = opus_decode(
+                    decoder,
+                    packet.as_ptr(),
+                    len as i32,
+                    output.as_mut_ptr(),
+                    FRAME_SIZE as i32,
+                    0,
+                );
+                assert_eq!(decoded, FRAME_SIZE as i32);
+            }
+
+            // Uncapped packets use the full bitrate again
+            let len = opus_encode(
+                encoder,
+                signal(20, 2).as_ptr(),
+                FRAME_SIZE as i32,
+                packet.as_mut_ptr(),
+                packet.len() as i32,
+            );
+            assert!(len as usize > BUDGET * 2, "{} bytes", len);
+
+            opus_encoder_destroy(encoder);
+            opus_decoder_destroy(decoder);
+        }
+    }
+
+    #[test]
+    fn test_under_budget_and_short_buffer() {
+        unsafe {
+            let mut error: i32 = 0;
+            let encoder =
+                opus_encoder_create(SAMPLE_RATE, 1, OPUS_APPLICATION_VOIP as i32, &mut error);
+            assert_eq!(error, OPUS_OK as i32, "Failed to create encoder");
+            let ret = opus_encoder_ctl(encoder, OPUS_SET_BITRATE_REQUEST as i32, 24000);
+            assert_eq!(ret, OPUS_OK as i32);
+
+            // 24 kbps needs 60 bytes per 20 ms; a 1000 byte budget changes nothing
+            let mut packet = [0u8; 1275];
+            let len = encode_capped(encoder, &signal(0, 1), 1, 1000, &mut packet).unwrap();
+            assert!(len <= 1000);
+            assert_eq!(get_bitrate(encoder), 24000);
+
+            // The output buffer caps the packet too
+            let mut small = [0u8; 30];
+            let len = encode_capped(encoder, &signal(1, 1), 1, 1000, &mut small).unwrap();
+            assert!(len <= small.len(), "{} bytes", len);
+
+            opus_encoder_destroy(encoder);
+        }
+    }
+
+    #[test]
+    fn test_invalid_input() {
+        unsafe {
+            let mut error: i32 = 0;
+            let encoder =
+                opus_encoder_create(SAMPLE_RATE, 2, OPUS_APPLICATION_AUDIO as i32, &mut error);
+            assert_eq!(error, OPUS_OK as i32, "Failed to create encoder");
+
+            let mut packet = [0u8; 100];
+            let pcm = signal(0, 2);
+            assert_eq!(
+                encode_capped(encoder, &pcm, 0, 100, &mut packet),
+                Err(OPUS_BAD_ARG)
+            );
+            assert_eq!(
+                encode_capped(encoder, &pcm[1..], 2, 100, &mut packet),
+                Err(OPUS_BAD_ARG)
+            );
+            // Not a valid Opus frame size
+            assert_eq!(
+                encode_capped(encoder, &pcm[..200], 2, 100, &mut packet),
+                Err(OPUS_BAD_ARG)
+            );
+            // A failed encode still restores the bitrate
+            let before = get_bitrate(encoder);
+            assert_eq!(
+                encode_capped(encoder, &pcm[..200], 2, 10, &mut packet),
+                Err(OPUS_BAD_ARG)
+            );
+            assert_eq!(get_bitrate(encoder), before);
+
+            opus_encoder_destroy(encoder);
+        }
+    }
+}
@@ -0,0 +1,328 @@
+//! What the vendored Opus build was configured with
+//!
+//! Cargo features only request options: `dred` and `osce` are dropped on
+//! targets and configurations that can't support them, and SIMD levels may be presumed
+//! from `-C target-feature`. The build script reports what it actually
+//! configured as `opus_*` cfgs, so applications can decide at runtime (or
+//! with [`cfg!`]) whether to enable DRED, or log their codec setup.
+//!
+//! ```
+//! let info = opus_head_sys::build_info();
+//! println!("{}", info);
+//! if opus_head_sys::HAS_DNN {
+//!     // Load the DNN weights and enable DRED
+//! }
+//! ```
+//!
+//! The constants below carry the same answers for `const` contexts and
+//! for a bug report, e.g. `log::info!("{}", build_info::summary())`.
+//!
+//! A library linked from `OPUS_LIB_DIR` wasn't configured by the build
+//! script ([`PREBUILT`]): DRED, OSCE, RTCD and the version are read from the
+//! library itself, the other options are the Cargo features it is expected
+//! to match, and the CMake build type is unknown.
+
+use core::fmt;
+
+/// DRED or OSCE is compiled in (`OPUS_SET_DNN_BLOB` works); see
+/// [`DRED_ENABLED`] and [`OSCE_ENABLED`] for which
+pub const HAS_DNN: bool = cfg!(opus_dnn);
+
+/// Built with `OPUS_FLOAT_APPROX` and `OPUS_FAST_MATH`
+pub const HAS_FAST_MATH: bool = cfg!(opus_fast_math);
+
+/// The float API is available (not a fixed-point `no-float-api` build)
+pub const HAS_FLOAT_API: bool = !cfg!(opus_fixed_point);
+
+/// The Opus Custom API (`opus_custom_*`) is compiled in
+pub const HAS_CUSTOM_MODES: bool = cfg!(opus_custom_modes);
+
+/// Same as [`HAS_DNN`]
+#[deprecated(note = "use HAS_DNN")]
+pub const DNN_ENABLED: bool = HAS_DNN;
+
+/// Built with `OPUS_DRED=ON` (the `dred` feature): the DRED ctls and
+/// `opus_dred_*` functions work
+pub const DRED_ENABLED: bool = cfg!(opus_dred);
+
+/// Built with `OPUS_OSCE=ON` (the `osce` feature): decoder complexity 6 and
+/// up enhances SILK, see [`crate::osce`]
+pub const OSCE_ENABLED: bool = cfg!(opus_osce);
+
+/// Built with `OPUS_RUNTIME_WEIGHTS=ON`, which build.rs sets with either
+/// DNN feature: the weights aren't compiled in and must be passed with
+/// `OPUS_SET_DNN_BLOB` before DRED or OSCE do anything
+pub const RUNTIME_WEIGHTS: bool = HAS_DNN;
+
+/// Linked from `OPUS_LIB_DIR` instead of built from source, see the
+/// [module docs](self)
+pub const PREBUILT: bool = cfg!(opus_prebuilt);
+
+/// Built with `OPUS_FIXED_POINT` (`no-float-api`)
+pub const FIXED_POINT: bool = cfg!(opus_fixed_point);
+
+/// Same as [`HAS_FAST_MATH`]
+#[deprecated(note = "use HAS_FAST_MATH")]
+pub const FAST_MATH: bool = HAS_FAST_MATH;
+
+/// The Rust target triple Opus was built for, e.g.
+/// `aarch64-linux-android`; empty for targets without an Opus library
+/// (WASM)
+pub const TARGET_TRIPLE: &str = build_env(option_env!("OPUS_SYS_TARGET"));
+
+/// CMake build type, e.g. `Release`; empty if Opus was not built here
+/// (WASM, [`PREBUILT`])
+pub const CMAKE_PROFILE: &str = build_env(option_env!("OPUS_SYS_CMAKE_BUILD_TYPE"));
+
+/// See [`BuildInfo::presumed_isa`]
+pub const PRESUMED_ISA: &str = build_env(option_env!("OPUS_SYS_PRESUMED_ISA"));
+
+/// The Opus version compiled into the library, e.g. `1.6-ga3f0ec0`: the
+/// part of `opus_get_version_string()` after `libopus `. `0` if the source
+/// tree has no version, empty for targets without an Opus build (WASM) or
+/// a [`PREBUILT`] library without a version string.
+pub const VENDORED_VERSION: &str = build_env(option_env!("OPUS_VENDORED_VERSION"));
+
+/// Major number of [`VENDORED_VERSION`]
+pub const VENDORED_VERSION_MAJOR: u32 = version_component(VENDORED_VERSION, 0);
+
+/// Minor number of [`VENDORED_VERSION`], 0 if it has none
+pub const VENDORED_VERSION_MINOR: u32 = version_component(VENDORED_VERSION, 1);
+
+/// The leading number of the `index`th dot separated part of `version`
+const fn version_component(version: &str, index: usize) -> u32 {
+    let bytes = version.as_bytes();
+    let mut i = 0;
+    let mut part = 0;
+    while part < index {
+        while i < bytes.len() && bytes[i].is_ascii_digit() {
+            i += 1;
+        }
+        if i == bytes.len() || bytes[i] != b'.' {
+            return 0;
+        }
+        i += 1;
+        part += 1;
+    }
+    let mut value = 0;
+    while i < bytes.len() && bytes[i].is_ascii_digit() {
+        value = value * 10 + (bytes[i] - b'0') as u32;
+        i += 1;
+    }
+    value
+}
+
+/// The build configuration of the linked Opus library
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BuildInfo {
+    /// See [`HAS_DNN`]
+    pub dnn: bool,
+    /// See [`DRED_ENABLED`]
+    pub dred: bool,
+    /// See [`OSCE_ENABLED`]
+    pub osce: bool,
+    /// See [`HAS_FAST_MATH`]
+    pub fast_math: bool,
+    /// See [`HAS_FLOAT_API`]
+    pub float_api: bool,
+    /// See [`HAS_CUSTOM_MODES`]
+    pub custom_modes: bool,
+    /// SIMD intrinsics are compiled in (not a `no-intrinsics` build)
+    pub intrinsics: bool,
+    /// Some SIMD level is left to runtime CPU detection (RTCD); see
+    /// [`crate::cpu`] for the level picked on this machine
+    pub rtcd: bool,
+    /// Opus' internal assertions are compiled in (`OPUS_ASSERTIONS`), which
+    /// abort on a violated invariant; a debugging build
+    pub assertions: bool,
+    /// CMake options for the instruction sets presumed without runtime
+    /// detection, comma separated, e.g. `OPUS_X86_PRESUME_SSE4_1`. Empty
+    /// when nothing beyond the target baseline is presumed.
+    pub presumed_isa: &'static str,
+    /// See [`CMAKE_PROFILE`]
+    pub cmake_build_type: &'static str,
+    /// See [`TARGET_TRIPLE`]
+    pub target: &'static str,
+    /// See [`PREBUILT`]
+    pub prebuilt: bool,
+}
+
+impl BuildInfo {
+    /// Iterate over [`Self::presumed_isa`]
+    pub fn presumed(&self) -> impl Iterator<Item = &'static str> {
+        self.presumed_isa.split(',').filter(|s| !s.is_empty())
+    }
+}
+
+impl fmt::Display for BuildInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let on = |enabled: bool| if enabled { "on" } else { "off" };
+        write!(
+            f,
+            "dnn={} dred={} osce={} fast_math={} float_api={} custom_modes={} intrinsics={} rtcd={} assertions={} presumed=[{}] build_type={} target={}",
+            on(self.dnn),
+            on(self.dred),
+            on(self.osce),
+            on(self.fast_math),
+            on(self.float_api),
+            on(self.custom_modes),
+            on(self.intrinsics),
+            on(self.rtcd),
+            on(self.assertions),
+            self.presumed_isa,
+            if self.prebuilt {
+                "prebuilt"
+            } else {
+                self.cmake_build_type
+            },
+            self.target
+        )
+    }
+}
+
+/// Set by build.rs, which emits nothing for targets it doesn't build (WASM)
+const fn build_env(value: Option<&'static str>) -> &'static str {
+    match value {
+        Some(value) => value,
+        None => "",
+    }
+}
+
+const BUILD_INFO: BuildInfo = BuildInfo {
+    dnn: HAS_DNN,
+    dred: DRED_ENABLED,
+    osce: OSCE_ENABLED,
+    fast_math: HAS_FAST_MATH,
+    float_api: HAS_FLOAT_API,
+    custom_modes: HAS_CUSTOM_MODES,
+    intrinsics: !cfg!(opus_no_intrinsics),
+    rtcd: cfg!(opus_rtcd),
+    assertions: cfg!(opus_assertions),
+    presumed_isa: PRESUMED_ISA,
+    cmake_build_type: CMAKE_PROFILE,
+    target: TARGET_TRIPLE,
+    prebuilt: PREBUILT,
+};
+
+/// The build configuration of the linked Opus library
+pub fn build_info() -> BuildInfo {
+    BUILD_INFO
+}
+
+/// One line with the whole build configuration and the Opus version, for
+/// logs and bug reports
+#[cfg(feature = "std")]
+pub fn summary() -> String {
+    format!("opus {} {}", VENDORED_VERSION, BUILD_INFO)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bindings::*;
+
+    #[test]
+    fn test_matches_cargo_features() {
+        let info = build_info();
+        assert_eq!(info.fast_math, cfg!(feature = "fast-math"));
+        assert_eq!(info.float_api, !cfg!(feature = "no-float-api"));
+        assert_eq!(info.custom_modes, cfg!(feature = "custom-modes"));
+        assert_eq!(info.intrinsics, !cfg!(feature = "no-intrinsics"));
+        // Also on with OPUS_SYS_ASSERTIONS=1
+        assert!(info.assertions || !cfg!(feature = "assertions"));
+        // Requested features may be dropped, never added; a prebuilt
+        // library has what it was built with
+        assert!(!info.dred || cfg!(feature = "dred") || info.prebuilt);
+        assert!(!info.osce || cfg!(feature = "osce") || info.prebuilt);
+        assert_eq!(info.dnn, info.dred || info.osce);
+        assert_eq!(info.cmake_build_type.is_empty(), info.prebuilt);
+        println!("{}", info);
+    }
+
+    #[test]
+    fn test_consts_match_cargo_features() {
+        assert_eq!(HAS_FAST_MATH, cfg!(feature = "fast-math"));
+        assert_eq!(FIXED_POINT, cfg!(feature = "no-float-api"));
+        // Forced off for armv7 Android and fixed-point builds
+        let armv7_android = cfg!(target_os = "android") && cfg!(target_arch = "arm");
+        let dnn_possible = !(FIXED_POINT || armv7_android);
+        // Read from a prebuilt library, checked against it by
+        // test_dnn_ctl_matches_build_info
+        if !PREBUILT {
+            assert_eq!(DRED_ENABLED, cfg!(feature = "dred") && dnn_possible);
+            assert_eq!(OSCE_ENABLED, cfg!(feature = "osce") && dnn_possible);
+        }
+        assert_eq!(HAS_DNN, DRED_ENABLED || OSCE_ENABLED);
+        assert_eq!(RUNTIME_WEIGHTS, HAS_DNN);
+        assert_eq!(CMAKE_PROFILE, build_info().cmake_build_type);
+        assert_eq!(PRESUMED_ISA, build_info().presumed_isa);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_summary() {
+        let summary = summary();
+        assert!(summary.starts_with(&format!("opus {} dnn=", VENDORED_VERSION)));
+        assert!(summary.ends_with(&format!("target={}", TARGET_TRIPLE)));
+        // Which model is in, not just that one is
+        let on = |enabled: bool| if enabled { "on" } else { "off" };
+        let models = format!(" dred={} osce={} ", on(DRED_ENABLED), on(OSCE_ENABLED));
+        assert!(summary.contains(&models), "{}", summary);
+        assert!(TARGET_TRIPLE.contains(std::env::consts::ARCH));
+        println!("{}", summary);
+    }
+
+    #[test]
+    fn test_dnn_ctl_matches_build_info() {
+        unsafe {
+            let mut error: i32 = 0;
+            let encoder = opus_encoder_create(48000, 1, OPUS_APPLICATION_VOIP as i32, &mut error);
+            assert_eq!(error, OPUS_OK as i32, "Failed to create encoder");
+
+            // The DRED duration ctl only exists with DRED compiled in
+            let ret = opus_encoder_ctl(encoder, OPUS_SET_DRED_DURATION_REQUEST as i32, 10);
+            assert_eq!(ret == OPUS_OK as i32, DRED_ENABLED, "ctl returned {}", ret);
+
+            opus_encoder_destroy(encoder);
+        }
+    }
+
+    #[test]
+    fn test_runtime_version_matches_vendored() {
+        // A stale libopus.a in OUT_DIR would report another version
+        let runtime = unsafe { std::ffi::CStr::from_ptr(opus_get_version_string()) };
+        let runtime = runtime.to_string_lossy();
+        assert!(!VENDORED_VERSION.is_empty());
+        assert_eq!(runtime, format!("libopus {}", VENDORED_VERSION));
+    }
+
+    #[test]
+    fn test_version_component() {
+        assert_eq!(version_component("1.6-ga3f0ec0", 0), 1);
+        assert_eq!(version_component("1.6-ga3f0ec0", 1), 6);
+        assert_eq!(version_component("1.5.2", 1), 5);
+        assert_eq!(version_component("1.5.2", 2), 2);
+        assert_eq!(version_component("0", 0), 0);
+        assert_eq!(version_component("0", 1), 0);
+        assert_eq!(version_component("", 0), 0);
+        assert_eq!(version_component("1-rc1.2", 1), 0);
+    }
+
+    #[test]
+    fn test_presumed_isa_iter() {
+        let info = BuildInfo {
+            presumed_isa: "OPUS_X86_PRESUME_SSE4_1,OPUS_X86_PRESUME_AVX2",
+            ..build_info()
+        };
+        assert_eq!(
+            info.presumed().collect::<Vec<_>>(),
+            ["OPUS_X86_PRESUME_SSE4_1", "OPUS_X86_PRESUME_AVX2"]
+        );
+
+        let none = BuildInfo {
+            presumed_isa: "",
+            ..info
+        };
+        assert_eq!(none.presumed().count(), 0);
+    }
+}
@@ -0,0 +1,171 @@
+//! Constant bitrate with a fixed packet duration
+//!
+//! Telephony gateways and some RTP profiles need every packet to be the
+//! same size and the same duration (ptime). That takes several encoder
+//! ctls agreeing with each other: `OPUS_SET_VBR` off, `OPUS_SET_BITRATE`,
+//! and `OPUS_SET_EXPERT_FRAME_DURATION`, so that `opus_encode` rejects a
+//! frame of another duration instead of emitting a packet of another size.
+//! [`configure_cbr`] sets them in one call and reads them back.
+//!
+//! Leave DTX (`OPUS_SET_DTX`) off for true CBR: during silence the encoder
+//! then emits 1 or 2 byte packets instead of full-size ones, which is what
+//! DTX is for but breaks a fixed packet size.
+//!
+//! ```
+//! use opus_head_sys::cbr::configure_cbr;
+//! use opus_head_sys::packet::FrameDuration;
+//! use opus_head_sys::*;
+//!
+//! unsafe {
+//!     let mut error = 0;
+//!     let encoder = opus_encoder_create(8000, 1, OPUS_APPLICATION_VOIP as i32, &mut error);
+//!     // 20 ms packets of 80 bytes
+//!     configure_cbr(encoder, 32000, FrameDuration::Ms20).unwrap();
+//!     opus_encoder_destroy(encoder);
+//! }
+//! ```
+
+use crate::bindings::*;
+use crate::packet::{FrameDuration, MAX_FRAME_BYTES};
+
+/// Lowest bitrate `OPUS_SET_BITRATE` doesn't round up
+const MIN_BITRATE: i32 = 500;
+
+/// Configure `encoder` for constant bitrate: `OPUS_SET_VBR` 0,
+/// `OPUS_SET_VBR_CONSTRAINT` 1, `OPUS_SET_EXPERT_FRAME_DURATION` to
+/// `duration` and `OPUS_SET_BITRATE` to `bitrate` bits per second, then
+/// check with the getters that the encoder kept them.
+///
+/// The constraint does nothing while VBR is off, but keeps the bitrate
+/// bounded per packet should VBR be turned back on. Afterwards every
+/// `opus_encode` call must pass frames of `duration`.
+///
+/// Returns `OPUS_BAD_ARG` for a bitrate Opus would clamp (below 500 bit/s
+/// or above 750 kbit/s per channel) or one whose frames would exceed the
+/// 1275 bytes an Opus frame can hold, e.g. over 510 kbit/s at 20 ms, and
+/// the error of the first ctl that fails otherwise. The encoder may be
+/// left partly configured on error.
+///
+/// # Safety
+///
+/// `encoder` must be a valid encoder.
+pub unsafe fn configure_cbr(
+    encoder: *mut OpusEncoder,
+    bitrate: i32,
+    duration: FrameDuration,
+) -> Result<(), i32> {
+    if bitrate < MIN_BITRATE || frame_bytes(bitrate, duration) > MAX_FRAME_BYTES as u64 {
+        return Err(OPUS_BAD_ARG);
+    }
+    let settings = [
+        (OPUS_SET_VBR_REQUEST, OPUS_GET_VBR_REQUEST, 0),
+        (
+            OPUS_SET_VBR_CONSTRAINT_REQUEST,
+            OPUS_GET_VBR_CONSTRAINT_REQUEST,
+            1,
+        ),
+        (
+            OPUS_SET_EXPERT_FRAME_DURATION_REQUEST,
+            OPUS_GET_EXPERT_FRAME_DURATION_REQUEST,
+            duration.to_opus(),
+        ),
+        (OPUS_SET_BITRATE_REQUEST, OPUS_GET_BITRATE_REQUEST, bitrate),
+    ];
+    for (set, _, value) in settings {
+        let ret = opus_encoder_ctl(encoder, set as i32, value);
+        if ret != OPUS_OK as i32 {
+            return Err(ret);
+        }
+    }
+    for (_, get, value) in settings {
+        let mut actual: i32 = 0;
+        let ret = opus_encoder_ctl(encoder, get as i32, &mut actual as *mut i32);
+        if ret != OPUS_OK as i32 {
+            return Err(ret);
+        }
+        if actual != value {
+            return Err(OPUS_BAD_ARG);
+        }
+    }
+    Ok(())
+}
+
+/// Bytes per Opus frame at `bitrate` for packets of `duration`, which have
+/// one frame per 20 ms beyond 20 ms
+fn frame_bytes(bitrate: i32, duration: FrameDuration) -> u64 {
+    let frame_units = (duration.units() / duration.max_frames()) as u64;
+    // 2.5 ms is 1/400 s
+    bitrate as u64 * frame_units / 400 / 8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packet::max_packet_size;
+
+    const SAMPLE_RATE: i32 = 48000;
+
+    unsafe fn create() -> *mut OpusEncoder {
+        let mut error: i32 = 0;
+        let encoder = opus_encoder_create(SAMPLE_RATE, 1, OPUS_APPLICATION_VOIP as i32, &mut error);
+        assert_eq!(error, OPUS_OK as i32, "Failed to create encoder");
+        encoder
+    }
+
+    unsafe fn encode(encoder: *mut OpusEncoder, frame_size: usize, seed: u64) -> i32 {
+        let input = crate::test_util::noise::<i16>(seed, frame_size);
+        let mut packet = [0u8; max_packet_size(1, FrameDuration::Ms60)];
+        opus_encode(
+            encoder,
+            input.as_ptr(),
+            frame_size as i32,
+            packet.as_mut_ptr(),
+            packet.len() as i32,
+        )
+    }
+
+    #[test]
+    fn test_fixed_packet_size() {
+        for (bitrate, duration, bytes) in [
+            (32000, FrameDuration::Ms20, 80),
+            (64000, FrameDuration::Ms10, 80),
+            (24000, FrameDuration::Ms60, 180),
+        ] {
+            unsafe {
+                let encoder = create();
+                configure_cbr(encoder, bitrate, duration).unwrap();
+                let frame_size = duration.samples(SAMPLE_RATE as u32) as usize;
+                for seed in 0..10 {
+                    let len = encode(encoder, frame_size, seed);
+                    assert_eq!(len, bytes, "{} bit/s in {:?} packets", bitrate, duration);
+                }
+                // A frame of another duration is rejected
+                assert_eq!(encode(encoder, frame_size / 2, 0), OPUS_BAD_ARG);
+                opus_encoder_destroy(encoder);
+            }
+        }
+    }
+
+    #[test]
+    fn test_invalid_bitrate() {
+        unsafe {
+            let encoder = create();
+            for bitrate in [0, 499, OPUS_AUTO, OPUS_BITRATE_MAX, 512_000] {
+                assert_eq!(
+                    configure_cbr(encoder, bitrate, FrameDuration::Ms20),
+                    Err(OPUS_BAD_ARG),
+                    "{}",
+                    bitrate
+                );
+            }
+            // Over 750 kbit/s per channel, which Opus would clamp
+            assert_eq!(
+                configure_cbr(encoder, 800_000, FrameDuration::Ms5),
+                Err(OPUS_BAD_ARG)
+            );
+            configure_cbr(encoder, 510_000, FrameDuration::Ms20).unwrap();
+            configure_cbr(encoder, 500, FrameDuration::Ms120).unwrap();
+            opus_encoder_destroy(encoder);
+        }
+    }
+}
@@ -0,0 +1,187 @@
+//! Clipping detection for float decoding
+//!
+//! `opus_decode_float` does not clip: a hot encode decodes to samples past
+//! ±1.0, which are only clipped when converted to integer PCM or tamed with
+//! `opus_pcm_soft_clip`. [`decode_float_checked`] decodes and reports how
+//! many samples went over, so QA pipelines can flag over-driven streams
+//! before deciding whether to soft-clip.
+
+use crate::bindings::*;
+
+/// Samples outside ±1.0 in a decoded buffer
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ClipStats {
+    /// Number of samples (over all channels) with a magnitude above 1.0
+    pub clipped: usize,
+    /// Largest magnitude seen
+    pub peak: f32,
+}
+
+impl ClipStats {
+    /// Scan `pcm` for samples past full scale
+    pub fn scan(pcm: &[f32]) -> Self {
+        pcm.iter().fold(ClipStats::default(), |stats, sample| {
+            let magnitude = sample.abs();
+            ClipStats {
+                clipped: stats.clipped + usize::from(magnitude > 1.0),
+                peak: stats.peak.max(magnitude),
+            }
+        })
+    }
+
+    /// Whether any sample exceeded full scale
+    pub fn is_clipped(&self) -> bool {
+        self.clipped > 0
+    }
+}
+
+/// Result of [`decode_float_checked`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CheckedDecode {
+    /// Decoded samples per channel, as returned by `opus_decode_float`
+    pub samples: usize,
+    /// Clipping in the decoded samples, before any soft clipping
+    pub clip: ClipStats,
+}
+
+/// Decode with `opus_decode_float` and scan the output for clipping.
+///
+/// `pcm` holds interleaved output for `channels` channels; its length sets
+/// the maximum frame size. An empty `data` runs packet loss concealment.
+/// Returns the Opus error code on failure.
+///
+/// # Safety
+///
+/// `decoder` must be a valid decoder created for `channels` channels.
+pub unsafe fn decode_float_checked(
+    decoder: *mut OpusDecoder,
+    data: &[u8],
+    pcm: &mut [f32],
+    channels: usize,
+    decode_fec: bool,
+) -> Result<CheckedDecode, i32> {
+    if channels == 0 || data.len() > i32::MAX as usize {
+        return Err(OPUS_BAD_ARG);
+    }
+    let frame_size = (pcm.len() / channels).min(i32::MAX as usize);
+
+    let ret = opus_decode_float(
+        decoder,
+        if data.is_empty() {
+            core::ptr::null()
+        } else {
+            data.as_ptr()
+        },
+        data.len() as i32,
+        pcm.as_mut_ptr(),
+        frame_size as i32,
+        i32::from(decode_fec),
+    );
+    if ret < 0 {
+        return Err(ret);
+    }
+
+    let samples = ret as usize;
+    Ok(CheckedDecode {
+        samples,
+        clip: ClipStats::scan(&pcm[..samples * channels]),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scan() {
+        let stats = ClipStats::scan(&[0.0, 0.5, -1.0, 1.0, 1.25, -1.5]);
+        assert_eq!(stats.clipped, 2);
+        assert_eq!(stats.peak, 1.5);
+        assert!(stats.is_clipped());
+
+        assert!(!ClipStats::scan(&[0.25, -0.75]).is_clipped());
+        assert_eq!(ClipStats::scan(&[]), ClipStats::default());
+    }
+
+    /// Encode `amplitude` sines and decode them with clipping detection
+    fn roundtrip(amplitude: f32) -> ClipStats {
+        const SAMPLE_RATE: i32 = 48000;
+        const FRAME_SIZE: usize = 960;
+
+        unsafe {
+            let mut error: i32 = 0;
+            let encoder =
+                opus_encoder_create(SAMPLE_RATE, 1, OPUS_APPLICATION_AUDIO as i32, &mut error);
+            assert_eq!(error, OPUS_OK as i32, "Failed to create encoder");
+            let decoder = opus_decoder_create(SAMPLE_RATE, 1, &mut error);
+            assert_eq!(error, OPUS_OK as i32, "Failed to create decoder");
+
+            let mut total = ClipStats::default();
+            let mut packet = [0u8; 1275];
+            let mut pcm = [0f32; FRAME_SIZE];
+            for frame in 0..10 {
+                let input: Vec<f32> = (0..FRAME_SIZE)
+                    .map(|i| {
+                        let t = (frame * FRAME_SIZE + i) as f32 / SAMPLE_RATE as f32;
+                        (t * 440.0 * std::f32::consts::TAU).sin() * amplitude
+                    })
+                    .collect();
+                let len = opus_encode_float(
+                    encoder,
+                    input.as_ptr(),
+                    FRAME_SIZE as i32,
+                    packet.as_mut_ptr(),
+                    packet.len() as i32,
+                );
+                assert!(len > 0, "Encoding failed: {}", len);
+
+                let decoded =
+                    decode_float_checked(decoder, &packet[..len as usize], &mut pcm, 1, false)
+                        .expect("Decoding failed");
+                assert_eq!(decoded.samples, FRAME_SIZE);
+                total.clipped += decoded.clip.clipped;
+                total.peak = total.peak.max(decoded.clip.peak);
+            }
+
+            // Packet loss concealment goes through the same path
+            let plc = decode_float_checked(decoder, &[], &mut pcm, 1, false).unwrap();
+            assert_eq!(plc.samples, FRAME_SIZE);
+
+            opus_decoder_destroy(decoder);
+            opus_encoder_destroy(encoder);
+            total
+        }
+    }
+
+    #[test]
+    fn test_decode_float_checked() {
+        let quiet = roundtrip(0.25);
+        assert!(!quiet.is_clipped(), "Quiet input clipped: {:?}", quiet);
+
+        let hot = roundtrip(1.5);
+        assert!(hot.is_clipped(), "Hot input not reported: {:?}", hot);
+        assert!(hot.peak > 1.0);
+    }
+
+    #[test]
+    fn test_bad_arguments() {
+        unsafe {
+            let mut error: i32 = 0;
+            let decoder = opus_decoder_create(48000, 2, &mut error);
+            assert_eq!(error, OPUS_OK as i32, "Failed to create decoder");
+
+            let mut pcm = [0f32; 1920];
+            assert_eq!(
+                decode_float_checked(decoder, &[], &mut pcm, 0, false),
+                Err(OPUS_BAD_ARG)
+            );
+            // Too small for even a 2.5 ms frame
+            assert_eq!(
+                decode_float_checked(decoder, &[0xfc, 0xff, 0xfe], &mut pcm[..4], 2, false),
+                Err(OPUS_BUFFER_TOO_SMALL)
+            );
+
+            opus_decoder_destroy(decoder);
+        }
+    }
+}
@@ -0,0 +1,246 @@
+//! Encoders and decoders behind a trait, for generic pipelines
+//!
+//! Routing code that shouldn't care how many channels a stream has can
+//! take any [`OpusEncode`] and [`OpusDecode`], generically or as trait
+//! objects. Both work a frame at a time, like `opus_encode` and
+//! `opus_decode`; the concrete methods, e.g. [`StreamEncoder::push`] for
+//! input of any length, stay available. The multistream types implement
+//! them too, so a stereo and a 5.1 stream can share one pipeline.
+//!
+//! ```
+//! use opus_head_sys::codec::{OpusDecode, OpusEncode};
+//! use opus_head_sys::decoder::Decoder;
+//! use opus_head_sys::stream::StreamEncoder;
+//!
+//! /// One frame through any encoder and decoder
+//! fn roundtrip(
+//!     encoder: &mut dyn OpusEncode,
+//!     decoder: &mut dyn OpusDecode,
+//!     pcm: &[i16],
+//! ) -> Vec<i16> {
+//!     let mut packet = [0u8; 1500];
+//!     let len = encoder.encode(pcm, &mut packet).unwrap();
+//!     let mut out = vec![0; pcm.len()];
+//!     let samples = decoder.decode(Some(&packet[..len]), &mut out, false).unwrap();
+//!     out.truncate(samples * decoder.channels());
+//!     out
+//! }
+//!
+//! let mut encoder = StreamEncoder::voip(48000, 2).unwrap();
+//! let mut decoder = Decoder::new(48000, 2).unwrap();
+//! assert_eq!(roundtrip(&mut encoder, &mut decoder, &[0; 1920]).len(), 1920);
+//! ```
+
+use crate::decoder::Decoder;
+use crate::multistream::{MultistreamDecoder, MultistreamEncoder};
+use crate::stream::StreamEncoder;
+
+/// An encoder taking one frame of interleaved samples at a time
+pub trait OpusEncode {
+    /// Encode `pcm`, one frame for all channels, into `out` and return the
+    /// packet length, or the Opus error code
+    fn encode(&mut self, pcm: &[i16], out: &mut [u8]) -> Result<usize, i32>;
+
+    /// Channels interleaved in `pcm`
+    fn channels(&self) -> usize;
+}
+
+/// A decoder producing one frame of interleaved samples at a time
+pub trait OpusDecode {
+    /// Decode `packet` into `pcm`, or conceal a lost packet with `None`, and
+    /// return the samples per channel written, or the Opus error code
+    fn decode(&mut self, packet: Option<&[u8]>, pcm: &mut [i16], fec: bool) -> Result<usize, i32>;
+
+    /// Channels interleaved in `pcm`
+    fn channels(&self) -> usize;
+}
+
+/// Encodes `pcm` as one frame of any Opus frame size, bypassing the
+/// buffering of [`push`](StreamEncoder::push). `OPUS_BAD_ARG` if samples
+/// are pending from `push`, as they would otherwise come out after `pcm`.
+impl OpusEncode for StreamEncoder {
+    fn encode(&mut self, pcm: &[i16], out: &mut [u8]) -> Result<usize, i32> {
+        self.encode_frame(pcm, out)
+    }
+
+    fn channels(&self) -> usize {
+        StreamEncoder::channels(self)
+    }
+}
+
+/// [`Decoder::decode`]
+impl OpusDecode for Decoder {
+    fn decode(&mut self, packet: Option<&[u8]>, pcm: &mut [i16], fec: bool) -> Result<usize, i32> {
+        Decoder::decode(self, packet, pcm, fec)
+    }
+
+    fn channels(&self) -> usize {
+        Decoder::channels(self)
+    }
+}
+
+/// [`MultistreamEncoder::encode`]
+impl OpusEncode for MultistreamEncoder {
+    fn encode(&mut self, pcm: &[i16], out: &mut [u8]) -> Result<usize, i32> {
+        MultistreamEncoder::encode(self, pcm, out)
+    }
+
+    fn channels(&self) -> usize {
+        MultistreamEncoder::channels(self)
+    }
+}
+
+/// [`MultistreamDecoder::decode`], with only the Opus error code of a
+/// [`DecodeError`](crate::multistream::DecodeError)
+impl OpusDecode for MultistreamDecoder {
+    fn decode(&mut self, packet: Option<&[u8]>, pcm: &mut [i16], fec: bool) -> Result<usize, i32> {
+        MultistreamDecoder::decode(self, packet, pcm, fec).map_err(|error| error.code)
+    }
+
+    fn channels(&self) -> usize {
+        MultistreamDecoder::channels(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::segmental_snr;
+    use crate::bindings::*;
+    use crate::test_util::{interleave, sine};
+
+    const SAMPLE_RATE: i32 = 48000;
+    const FRAME_SIZE: usize = 960;
+
+    /// Encode and decode `pcm` a frame at a time, knowing only the traits
+    fn roundtrip<E: OpusEncode, D: OpusDecode>(
+        encoder: &mut E,
+        decoder: &mut D,
+        pcm: &[i16],
+    ) -> Vec<i16> {
+        assert_eq!(encoder.channels(), decoder.channels());
+        let frame = FRAME_SIZE * encoder.channels();
+        let mut packet = [0u8; 1500];
+        let mut out = vec![0i16; frame];
+        let mut decoded = Vec::new();
+        for pcm in pcm.chunks_exact(frame) {
+            let len = encoder.encode(pcm, &mut packet).unwrap();
+            let samples = decoder
+                .decode(Some(&packet[..len]), &mut out, false)
+                .unwrap();
+            assert_eq!(samples, FRAME_SIZE);
+            decoded.extend_from_slice(&out);
+        }
+        decoded
+    }
+
+    #[test]
+    fn test_mono_and_stereo_through_traits() {
+        let tone: Vec<i16> = sine(440.0, SAMPLE_RATE as u32, FRAME_SIZE * 25, 0.5);
+        for channels in [1, 2] {
+            let pcm = interleave(&vec![tone.clone(); channels]);
+            let mut encoder = StreamEncoder::music(SAMPLE_RATE, channels).unwrap();
+            let mut decoder = Decoder::new(SAMPLE_RATE, channels).unwrap();
+            let decoded = roundtrip(&mut encoder, &mut decoder, &pcm);
+            assert_eq!(decoded.len(), pcm.len());
+
+            // Skip the first frames, which the codec delay shifts
+            let delay = crate::analysis::lookahead(&encoder).unwrap() * channels;
+            let frame = FRAME_SIZE * channels;
+            let snr = segmental_snr(&pcm[frame..], &decoded[frame + delay..], frame);
+            assert!(snr > 10.0, "{} channels: {} dB", channels, snr);
+        }
+    }
+
+    #[test]
+    fn test_trait_objects() {
+        let mut pipeline: Vec<(Box<dyn OpusEncode>, Box<dyn OpusDecode>)> = vec![
+            (
+                Box::new(StreamEncoder::voip(SAMPLE_RATE, 1).unwrap()),
+                Box::new(Decoder::new(SAMPLE_RATE, 1).unwrap()),
+            ),
+            (
+                Box::new(StreamEncoder::voip(SAMPLE_RATE, 2).unwrap()),
+                Box::new(Decoder::new(SAMPLE_RATE, 2).unwrap()),
+            ),
+        ];
+        let mut packet = [0u8; 1500];
+        for (encoder, decoder) in &mut pipeline {
+            let mut pcm = vec![0i16; FRAME_SIZE * encoder.channels()];
+            let len = encoder.encode(&pcm, &mut packet).unwrap();
+            assert_eq!(
+                decoder.decode(Some(&packet[..len]), &mut pcm, false),
+                Ok(FRAME_SIZE)
+            );
+            // Concealment through the trait too
+            assert_eq!(decoder.decode(None, &mut pcm, false), Ok(FRAME_SIZE));
+        }
+    }
+
+    #[test]
+    fn test_stereo_and_surround_trait_objects() {
+        let application = OPUS_APPLICATION_AUDIO as i32;
+        let surround = MultistreamEncoder::surround(SAMPLE_RATE, 6, 1, application).unwrap();
+        let surround_decoder = MultistreamDecoder::new(
+            SAMPLE_RATE,
+            6,
+            surround.streams(),
+            surround.coupled_streams(),
+            surround.mapping(),
+        )
+        .unwrap();
+        let mut pipeline: Vec<(Box<dyn OpusEncode>, Box<dyn OpusDecode>)> = vec![
+            (
+                Box::new(StreamEncoder::music(SAMPLE_RATE, 2).unwrap()),
+                Box::new(Decoder::new(SAMPLE_RATE, 2).unwrap()),
+            ),
+            (Box::new(surround), Box::new(surround_decoder)),
+        ];
+
+        // Low enough for the LFE channel of 5.1 to carry it
+        let tone: Vec<i16> = sine(110.0, SAMPLE_RATE as u32, FRAME_SIZE * 25, 0.5);
+        let mut packet = [0u8; 4000];
+        for (encoder, decoder) in &mut pipeline {
+            let channels = encoder.channels();
+            assert_eq!(decoder.channels(), channels);
+            let pcm = interleave(&vec![tone.clone(); channels]);
+            let frame = FRAME_SIZE * channels;
+            let mut out = vec![0i16; frame];
+            let mut decoded = Vec::new();
+            for pcm in pcm.chunks_exact(frame) {
+                let len = encoder.encode(pcm, &mut packet).unwrap();
+                let samples = decoder
+                    .decode(Some(&packet[..len]), &mut out, false)
+                    .unwrap();
+                assert_eq!(samples, FRAME_SIZE);
+                decoded.extend_from_slice(&out);
+            }
+            // Both decode the tone on every channel, delayed by the lookahead
+            // of 312 samples at 48 kHz
+            let delay = 312 * channels;
+            let snr = segmental_snr(&pcm[frame..], &decoded[frame + delay..], frame);
+            assert!(snr > 10.0, "{} channels: {} dB", channels, snr);
+            assert_eq!(decoder.decode(None, &mut out, false), Ok(FRAME_SIZE));
+            assert_eq!(
+                decoder.decode(Some(&[]), &mut out, false),
+                Err(OPUS_BAD_ARG)
+            );
+        }
+    }
+
+    #[test]
+    fn test_encode_errors() {
+        let mut encoder = StreamEncoder::voip(SAMPLE_RATE, 2).unwrap();
+        let mut packet = [0u8; 1500];
+        // Not whole sample frames, or not an Opus frame size
+        assert_eq!(encoder.encode(&[0; 1919], &mut packet), Err(OPUS_BAD_ARG));
+        assert_eq!(encoder.encode(&[0; 1000], &mut packet), Err(OPUS_BAD_ARG));
+        // Samples buffered by push would be reordered
+        assert_eq!(encoder.push(&[0; 100]), Ok(vec![]));
+        assert_eq!(encoder.encode(&[0; 1920], &mut packet), Err(OPUS_BAD_ARG));
+        assert!(encoder.finish().unwrap().is_some());
+        assert!(encoder.encode(&[0; 1920], &mut packet).is_ok());
+        // Other frame sizes are fine, 10 ms here
+        assert!(encoder.encode(&[0; 960], &mut packet).is_ok());
+    }
+}
@@ -0,0 +1,150 @@
+//! The SIMD level Opus runs with on this machine
+//!
+//! With runtime CPU detection (RTCD), Opus checks the CPU once per encoder
+//! and decoder and dispatches to the best implementation it has, e.g. AVX2
+//! on one box and SSE4.1 on the next. Levels presumed at build time (see
+//! [`BuildInfo::presumed_isa`](crate::BuildInfo::presumed_isa)) are used
+//! unconditionally. [`detected_cpu_features`] reports which one applies,
+//! for "why is it slow on this machine" reports:
+//!
+//! ```
+//! let cpu = opus_head_sys::cpu::detected_cpu_features();
+//! println!("opus simd: {} (rtcd={})", cpu.level.unwrap_or("baseline"), cpu.runtime_detected);
+//! ```
+
+/// Names of the `arch` indices Opus dispatches on, see `celt/cpu_support.h`
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+const LEVELS: &[&str] = &["c", "sse", "sse2", "sse4.1", "avx2"];
+#[cfg(any(target_arch = "arm", target_arch = "aarch64"))]
+const LEVELS: &[&str] = &["armv4", "edsp", "media", "neon", "neon+dotprod"];
+#[cfg(not(any(
+    target_arch = "x86",
+    target_arch = "x86_64",
+    target_arch = "arm",
+    target_arch = "aarch64"
+)))]
+const LEVELS: &[&str] = &[];
+
+/// Presume options, highest first, and the level each one fixes
+const PRESUMED_LEVELS: [(&str, &str); 6] = [
+    ("OPUS_X86_PRESUME_AVX2", "avx2"),
+    ("OPUS_X86_PRESUME_SSE4_1", "sse4.1"),
+    ("OPUS_X86_PRESUME_SSE2", "sse2"),
+    ("OPUS_X86_PRESUME_SSE", "sse"),
+    ("OPUS_PRESUME_DOTPROD", "neon+dotprod"),
+    ("OPUS_PRESUME_NEON", "neon"),
+];
+
+#[cfg(opus_rtcd)]
+extern "C" {
+    /// Internal to libopus (`celt/cpu_support.h`), only defined with RTCD
+    #[cfg_attr(feature = "prefix-symbols", link_name = "opus_head_select_arch")]
+    fn opus_select_arch() -> core::ffi::c_int;
+}
+
+/// What [`detected_cpu_features`] found
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CpuFeatures {
+    /// The level was picked by Opus at runtime rather than fixed at build
+    /// time
+    pub runtime_detected: bool,
+    /// The `arch` index Opus dispatches on (`opus_select_arch()`); 0
+    /// without RTCD
+    pub arch: i32,
+    /// The SIMD level in use, e.g. `avx2`, `sse4.1` or `neon+dotprod`, and
+    /// `c` without intrinsics. `None` if only the target's baseline is
+    /// compiled in, which Opus doesn't name.
+    pub level: Option<&'static str>,
+}
+
+/// The SIMD level Opus uses on this machine, see the [module docs](self)
+pub fn detected_cpu_features() -> CpuFeatures {
+    #[cfg(opus_rtcd)]
+    {
+        let arch = unsafe { opus_select_arch() };
+        CpuFeatures {
+            runtime_detected: true,
+            arch,
+            level: usize::try_from(arch)
+                .ok()
+                .and_then(|i| LEVELS.get(i))
+                .copied(),
+        }
+    }
+    #[cfg(not(opus_rtcd))]
+    {
+        let info = crate::build_info::build_info();
+        CpuFeatures {
+            runtime_detected: false,
+            arch: 0,
+            level: if info.intrinsics {
+                presumed_level(info.presumed_isa)
+            } else {
+                Some("c")
+            },
+        }
+    }
+}
+
+/// The highest level among the comma separated presume options
+fn presumed_level(presumed_isa: &str) -> Option<&'static str> {
+    PRESUMED_LEVELS
+        .iter()
+        .find(|(option, _)| presumed_isa.split(',').any(|p| p == *option))
+        .map(|&(_, level)| level)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build_info::build_info;
+
+    #[test]
+    fn test_matches_build_info() {
+        let cpu = detected_cpu_features();
+        let info = build_info();
+        assert_eq!(cpu.runtime_detected, info.rtcd);
+        assert_eq!(cpu.runtime_detected, cfg!(opus_rtcd));
+        // Intrinsics off means no runtime detection either
+        assert!(info.intrinsics || !info.rtcd);
+        println!("{:?} {}", cpu, info);
+    }
+
+    #[cfg(all(opus_rtcd, any(target_arch = "x86", target_arch = "x86_64")))]
+    #[test]
+    fn test_x86_level_matches_cpuid() {
+        // Opus' AVX2 level also needs AVX and FMA
+        let expected = if is_x86_feature_detected!("avx2")
+            && is_x86_feature_detected!("avx")
+            && is_x86_feature_detected!("fma")
+        {
+            "avx2"
+        } else if is_x86_feature_detected!("sse4.1") {
+            "sse4.1"
+        } else if is_x86_feature_detected!("sse2") {
+            "sse2"
+        } else {
+            "sse"
+        };
+        let cpu = detected_cpu_features();
+        assert_eq!(cpu.level, Some(expected), "{:?}", cpu);
+        assert_eq!(LEVELS[cpu.arch as usize], expected);
+    }
+
+    #[test]
+    fn test_presumed_level() {
+        assert_eq!(
+            presumed_level("OPUS_X86_PRESUME_SSE4_1,OPUS_X86_PRESUME_AVX2"),
+            Some("avx2")
+        );
+        assert_eq!(
+            presumed_level("OPUS_X86_PRESUME_SSE,OPUS_X86_PRESUME_SSE2"),
+            Some("sse2")
+        );
+        assert_eq!(
+            presumed_level("OPUS_PRESUME_NEON,OPUS_PRESUME_DOTPROD"),
+            Some("neon+dotprod")
+        );
+        assert_eq!(presumed_level(""), None);
+    }
+}
@@ -0,0 +1,168 @@
+/* automatically generated by rust-bindgen 0.72.1 */
+
+#![allow(rustdoc::broken_intra_doc_links)]
+
+use super::bindings::*;
+
+#[doc = " Contains the state of an encoder. One encoder state is needed\n for each stream. It is initialized once at the beginning of the\n stream. Do *not* re-initialize the state for every frame.\n @brief Encoder state"]
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct OpusCustomEncoder {
+    _unused: [u8; 0],
+}
+#[doc = " State of the decoder. One decoder state is needed for each stream.\n It is initialized once at the beginning of the stream. Do *not*\n re-initialize the state for every frame.\n @brief Decoder state"]
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct OpusCustomDecoder {
+    _unused: [u8; 0],
+}
+#[doc = " The mode contains all the information necessary to create an\n encoder. Both the encoder and decoder need to be initialized\n with exactly the same mode, otherwise the output will be\n corrupted. The mode MUST NOT BE DESTROYED until the encoders and\n decoders that use it are destroyed as well.\n @brief Mode configuration"]
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct OpusCustomMode {
+    _unused: [u8; 0],
+}
+unsafe extern "C" {
+    #[doc = " Creates a new mode struct. This will be passed to an encoder or\n decoder. The mode MUST NOT BE DESTROYED until the encoders and\n decoders that use it are destroyed as well.\n @param [in] Fs <tt>int</tt>: Sampling rate (8000 to 96000 Hz)\n @param [in] frame_size <tt>int</tt>: Number of samples (per channel) to encode in each\n packet (64 - 1024, prime factorization must contain zero or more 2s, 3s, or 5s and no other primes)\n @param [out] error <tt>int*</tt>: Returned error code (if NULL, no error will be returned)\n @return A newly created mode"]
+    pub fn opus_custom_mode_create(
+        Fs: opus_int32,
+        frame_size: ::core::ffi::c_int,
+        error: *mut ::core::ffi::c_int,
+    ) -> *mut OpusCustomMode;
+}
+unsafe extern "C" {
+    #[doc = " Destroys a mode struct. Only call this after all encoders and\n decoders using this mode are destroyed as well.\n @param [in] mode <tt>OpusCustomMode*</tt>: Mode to be freed."]
+    pub fn opus_custom_mode_destroy(mode: *mut OpusCustomMode);
+}
+unsafe extern "C" {
+    #[doc = " Gets the size of an OpusCustomEncoder structure.\n @param [in] mode <tt>OpusCustomMode *</tt>: Mode configuration\n @param [in] channels <tt>int</tt>: Number of channels\n @returns size"]
+    pub fn opus_custom_encoder_get_size(
+        mode: *const OpusCustomMode,
+        channels: ::core::ffi::c_int,
+    ) -> ::core::ffi::c_int;
+}
+unsafe extern "C" {
+    #[doc = " Initializes a previously allocated encoder state\n The memory pointed to by st must be the size returned by opus_custom_encoder_get_size.\n This is intended for applications which use their own allocator instead of malloc.\n @see opus_custom_encoder_create(),opus_custom_encoder_get_size()\n To reset a previously initialized state use the OPUS_RESET_STATE CTL.\n @param [in] st <tt>OpusCustomEncoder*</tt>: Encoder state\n @param [in] mode <tt>OpusCustomMode *</tt>: Contains all the information about the characteristics of\n the stream (must be the same characteristics as used for the\n decoder)\n @param [in] channels <tt>int</tt>: Number of channels\n @return OPUS_OK Success or @ref opus_errorcodes"]
+    pub fn opus_custom_encoder_init(
+        st: *mut OpusCustomEncoder,
+        mode: *const OpusCustomMode,
+        channels: ::core::ffi::c_int,
+    ) -> ::core::ffi::c_int;
+}
+unsafe extern "C" {
+    #[doc = " Creates a new encoder state. Each stream needs its own encoder\n state (can't be shared across simultaneous streams).\n @param [in] mode <tt>OpusCustomMode*</tt>: Contains all the information about the characteristics of\n the stream (must be the same characteristics as used for the\n decoder)\n @param [in] channels <tt>int</tt>: Number of channels\n @param [out] error <tt>int*</tt>: Returns an error code\n @return Newly created encoder state."]
+    pub fn opus_custom_encoder_create(
+        mode: *const OpusCustomMode,
+        channels: ::core::ffi::c_int,
+        error: *mut ::core::ffi::c_int,
+    ) -> *mut OpusCustomEncoder;
+}
+unsafe extern "C" {
+    #[doc = " Destroys an encoder state.\n @param[in] st <tt>OpusCustomEncoder*</tt>: State to be freed."]
+    pub fn opus_custom_encoder_destroy(st: *mut OpusCustomEncoder);
+}
+#[cfg(not(feature = "no-float-api"))]
+unsafe extern "C" {
+    #[doc = " Encodes a frame of audio.\n @param [in] st <tt>OpusCustomEncoder*</tt>: Encoder state\n @param [in] pcm <tt>float*</tt>: PCM audio in float format, with a normal range of +/-1.0.\n Samples with a range beyond +/-1.0 are supported but will\n be clipped by decoders using the integer API and should\n only be used if it is known that the far end supports\n extended dynamic range. There must be exactly\n frame_size samples per channel.\n @param [in] frame_size <tt>int</tt>: Number of samples per frame of input signal\n @param [out] compressed <tt>char *</tt>: The compressed data is written here. This may not alias pcm and must be at least maxCompressedBytes long.\n @param [in] maxCompressedBytes <tt>int</tt>: Maximum number of bytes to use for compressing the frame\n (can change from one frame to another)\n @return Number of bytes written to \"compressed\".\n If negative, an error has occurred (see error codes). It is IMPORTANT that\n the length returned be somehow transmitted to the decoder. Otherwise, no\n decoding is possible."]
+    pub fn opus_custom_encode_float(
+        st: *mut OpusCustomEncoder,
+        pcm: *const f32,
+        frame_size: ::core::ffi::c_int,
+        compressed: *mut ::core::ffi::c_uchar,
+        maxCompressedBytes: ::core::ffi::c_int,
+    ) -> ::core::ffi::c_int;
+}
+unsafe extern "C" {
+    #[doc = " Encodes a frame of audio.\n @param [in] st <tt>OpusCustomEncoder*</tt>: Encoder state\n @param [in] pcm <tt>opus_int16*</tt>: PCM audio in signed 16-bit format (native endian).\n There must be exactly frame_size samples per channel.\n @param [in] frame_size <tt>int</tt>: Number of samples per frame of input signal\n @param [out] compressed <tt>char *</tt>: The compressed data is written here. This may not alias pcm and must be at least maxCompressedBytes long.\n @param [in] maxCompressedBytes <tt>int</tt>: Maximum number of bytes to use for compressing the frame\n (can change from one frame to another)\n @return Number of bytes written to \"compressed\".\n If negative, an error has occurred (see error codes). It is IMPORTANT that\n the length returned be somehow transmitted to the decoder. Otherwise, no\n decoding is possible."]
+    pub fn opus_custom_encode(
+        st: *mut OpusCustomEncoder,
+        pcm: *const opus_int16,
+        frame_size: ::core::ffi::c_int,
+        compressed: *mut ::core::ffi::c_uchar,
+        maxCompressedBytes: ::core::ffi::c_int,
+    ) -> ::core::ffi::c_int;
+}
+unsafe extern "C" {
+    #[doc = " Encodes a frame of audio.\n @param [in] st <tt>OpusCustomEncoder*</tt>: Encoder state\n @param [in] pcm <tt>opus_int32*</tt>: PCM audio in signed 32-bit format (native endian) representing (or slightly exceeding) 24-bit values.\n There must be exactly frame_size samples per channel.\n @param [in] frame_size <tt>int</tt>: Number of samples per frame of input signal\n @param [out] compressed <tt>char *</tt>: The compressed data is written here. This may not alias pcm and must be at least maxCompressedBytes long.\n @param [in] maxCompressedBytes <tt>int</tt>: Maximum number of bytes to use for compressing the frame\n (can change from one frame to another)\n @return Number of bytes written to \"compressed\".\n If negative, an error has occurred (see error codes). It is IMPORTANT that\n the length returned be somehow transmitted to the decoder. Otherwise, no\n decoding is possible."]
+    pub fn opus_custom_encode24(
+        st: *mut OpusCustomEncoder,
+        pcm: *const opus_int32,
+        frame_size: ::core::ffi::c_int,
+        compressed: *mut ::core::ffi::c_uchar,
+        maxCompressedBytes: ::core::ffi::c_int,
+    ) -> ::core::ffi::c_int;
+}
+unsafe extern "C" {
+    #[doc = " Perform a CTL function on an Opus custom encoder.\n\n Generally the request and subsequent arguments are generated\n by a convenience macro.\n @see opus_encoderctls"]
+    pub fn opus_custom_encoder_ctl(
+        st: *mut OpusCustomEncoder,
+        request: ::core::ffi::c_int,
+        ...
+    ) -> ::core::ffi::c_int;
+}
+unsafe extern "C" {
+    #[doc = " Gets the size of an OpusCustomDecoder structure.\n @param [in] mode <tt>OpusCustomMode *</tt>: Mode configuration\n @param [in] channels <tt>int</tt>: Number of channels\n @returns size"]
+    pub fn opus_custom_decoder_get_size(
+        mode: *const OpusCustomMode,
+        channels: ::core::ffi::c_int,
+    ) -> ::core::ffi::c_int;
+}
+unsafe extern "C" {
+    #[doc = " Initializes a previously allocated decoder state\n The memory pointed to by st must be the size returned by opus_custom_decoder_get_size.\n This is intended for applications which use their own allocator instead of malloc.\n @see opus_custom_decoder_create(),opus_custom_decoder_get_size()\n To reset a previously initialized state use the OPUS_RESET_STATE CTL.\n @param [in] st <tt>OpusCustomDecoder*</tt>: Decoder state\n @param [in] mode <tt>OpusCustomMode *</tt>: Contains all the information about the characteristics of\n the stream (must be the same characteristics as used for the\n encoder)\n @param [in] channels <tt>int</tt>: Number of channels\n @return OPUS_OK Success or @ref opus_errorcodes"]
+    pub fn opus_custom_decoder_init(
+        st: *mut OpusCustomDecoder,
+        mode: *const OpusCustomMode,
+        channels: ::core::ffi::c_int,
+    ) -> ::core::ffi::c_int;
+}
+unsafe extern "C" {
+    #[doc = " Creates a new decoder state. Each stream needs its own decoder state (can't\n be shared across simultaneous streams).\n @param [in] mode <tt>OpusCustomMode</tt>: Contains all the information about the characteristics of the\n stream (must be the same characteristics as used for the encoder)\n @param [in] channels <tt>int</tt>: Number of channels\n @param [out] error <tt>int*</tt>: Returns an error code\n @return Newly created decoder state."]
+    pub fn opus_custom_decoder_create(
+        mode: *const OpusCustomMode,
+        channels: ::core::ffi::c_int,
+        error: *mut ::core::ffi::c_int,
+    ) -> *mut OpusCustomDecoder;
+}
+unsafe extern "C" {
+    #[doc = " Destroys a decoder state.\n @param[in] st <tt>OpusCustomDecoder*</tt>: State to be freed."]
+    pub fn opus_custom_decoder_destroy(st: *mut OpusCustomDecoder);
+}
+#[cfg(not(feature = "no-float-api"))]
+unsafe extern "C" {
+    #[doc = " Decode an opus custom frame with floating point output\n @param [in] st <tt>OpusCustomDecoder*</tt>: Decoder state\n @param [in] data <tt>char*</tt>: Input payload. Use a NULL pointer to indicate packet loss\n @param [in] len <tt>int</tt>: Number of bytes in payload\n @param [out] pcm <tt>float*</tt>: Output signal (interleaved if 2 channels). length\n is frame_size*channels*sizeof(float)\n @param [in] frame_size Number of samples per channel of available space in *pcm.\n @returns Number of decoded samples or @ref opus_errorcodes"]
+    pub fn opus_custom_decode_float(
+        st: *mut OpusCustomDecoder,
+        data: *const ::core::ffi::c_uchar,
+        len: ::core::ffi::c_int,
+        pcm: *mut f32,
+        frame_size: ::core::ffi::c_int,
+    ) -> ::core::ffi::c_int;
+}
+unsafe extern "C" {
+    #[doc = " Decode an opus custom frame\n @param [in] st <tt>OpusCustomDecoder*</tt>: Decoder state\n @param [in] data <tt>char*</tt>: Input payload. Use a NULL pointer to indicate packet loss\n @param [in] len <tt>int</tt>: Number of bytes in payload\n @param [out] pcm <tt>opus_int16*</tt>: Output signal (interleaved if 2 channels). length\n is frame_size*channels*sizeof(opus_int16)\n @param [in] frame_size Number of samples per channel of available space in *pcm.\n @returns Number of decoded samples or @ref opus_errorcodes"]
+    pub fn opus_custom_decode(
+        st: *mut OpusCustomDecoder,
+        data: *const ::core::ffi::c_uchar,
+        len: ::core::ffi::c_int,
+        pcm: *mut opus_int16,
+        frame_size: ::core::ffi::c_int,
+    ) -> ::core::ffi::c_int;
+}
+unsafe extern "C" {
+    #[doc = " Decode an opus custom frame\n @param [in] st <tt>OpusCustomDecoder*</tt>: Decoder state\n @param [in] data <tt>char*</tt>: Input payload. Use a NULL pointer to indicate packet loss\n @param [in] len <tt>int</tt>: Number of bytes in payload\n @param [out] pcm <tt>opus_int32*</tt>: Output signal (interleaved if 2 channels) representing (or slightly exceeding) 24-bit values. length\n is frame_size*channels*sizeof(opus_int32)\n @param [in] frame_size Number of samples per channel of available space in *pcm.\n @returns Number of decoded samples or @ref opus_errorcodes"]
+    pub fn opus_custom_decode24(
+        st: *mut OpusCustomDecoder,
+        data: *const ::core::ffi::c_uchar,
+        len: ::core::ffi::c_int,
+        pcm: *mut opus_int32,
+        frame_size: ::core::ffi::c_int,
+    ) -> ::core::ffi::c_int;
+}
+unsafe extern "C" {
+    #[doc = " Perform a CTL function on an Opus custom decoder.\n\n Generally the request and subsequent arguments are generated\n by a convenience macro.\n @see opus_genericctls"]
+    pub fn opus_custom_decoder_ctl(
+        st: *mut OpusCustomDecoder,
+        request: ::core::ffi::c_int,
+        ...
+    ) -> ::core::ffi::c_int;
+}
@@ -0,0 +1,699 @@
+//! A decoder writing interleaved or planar output
+//!
+//! `opus_decode` writes interleaved samples (`L R L R ...`), while audio
+//! graphs usually keep one buffer per channel. [`Decoder`] owns an
+//! `OpusDecoder` and decodes either way: [`decode`](Decoder::decode) into
+//! an interleaved buffer, [`decode_planar`](Decoder::decode_planar) into
+//! one slice per channel, through an interleaved scratch buffer split with
+//! [`layout::deinterleave_into`](crate::layout::deinterleave_into). Both
+//! have `f32` variants.
+//!
+//! A lost packet is concealed for as long as the last packet decoded, as a
+//! playback loop wants, or for any duration with
+//! [`conceal`](Decoder::conceal).
+//!
+//! ```
+//! use opus_head_sys::decoder::Decoder;
+//!
+//! let mut decoder = Decoder::new(48000, 2).unwrap();
+//! let (mut left, mut right) = ([0i16; 960], [0i16; 960]);
+//! # let packet: Option<&[u8]> = None;
+//! // None conceals a lost packet
+//! let samples = decoder
+//!     .decode_planar(packet, &mut [&mut left, &mut right], false)
+//!     .unwrap();
+//! assert_eq!(samples, 960);
+//! ```
+
+use crate::bindings::*;
+use crate::dnn::{self, BlobError, DnnBlob};
+use crate::layout::deinterleave_into;
+use crate::plc;
+#[cfg(feature = "bytes")]
+use bytes::Bytes;
+
+/// An owned decoder, see the [module docs](self)
+///
+/// `Send` but not `Sync`, like [`StreamEncoder`](crate::stream::StreamEncoder):
+/// one decoder per stream can run on any thread, but a shared one needs a
+/// `Mutex`.
+///
+/// ```compile_fail
+/// fn assert_sync<T: Sync>() {}
+/// assert_sync::<opus_head_sys::decoder::Decoder>();
+/// ```
+#[derive(Debug)]
+pub struct Decoder {
+    decoder: *mut OpusDecoder,
+    sample_rate: i32,
+    channels: usize,
+    scratch: Vec<i16>,
+    #[cfg(not(feature = "no-float-api"))]
+    scratch_float: Vec<f32>,
+    /// The DNN weights loaded, which the decoder points into
+    dnn_blob: Option<DnnBlob>,
+    /// Samples per channel of the last packet decoded, what `None` conceals
+    last_frame_size: usize,
+}
+
+// The decoder state is a single allocation with no thread affinity
+unsafe impl Send for Decoder {}
+
+impl Decoder {
+    /// Create a decoder with `opus_decoder_create`, returning its error code
+    /// on failure
+    pub fn new(sample_rate: i32, channels: usize) -> Result<Self, i32> {
+        if !(1..=2).contains(&channels) {
+            return Err(OPUS_BAD_ARG);
+        }
+        let mut error: i32 = 0;
+        let decoder = unsafe { opus_decoder_create(sample_rate, channels as i32, &mut error) };
+        if error != OPUS_OK as i32 {
+            return Err(error);
+        }
+        if decoder.is_null() {
+            return Err(OPUS_ALLOC_FAIL);
+        }
+        Ok(Decoder {
+            decoder,
+            sample_rate,
+            channels,
+            scratch: Vec::new(),
+            #[cfg(not(feature = "no-float-api"))]
+            scratch_float: Vec::new(),
+            dnn_blob: None,
+            // 20 ms before the first packet
+            last_frame_size: usize::try_from(sample_rate).unwrap_or(0) / 50,
+        })
+    }
+
+    /// The underlying decoder, for `opus_decoder_ctl`. It stays owned by
+    /// `self`; don't destroy it.
+    pub fn as_ptr(&self) -> *mut OpusDecoder {
+        self.decoder
+    }
+
+    /// The sample rate given to [`new`](Self::new), which
+    /// `OPUS_GET_SAMPLE_RATE` reports as well
+    pub fn sample_rate(&self) -> i32 {
+        self.sample_rate
+    }
+
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    /// Samples per channel of the last packet decoded, which
+    /// [`decode`](Self::decode) conceals a lost packet for; 20 ms before
+    /// the first. Concealment and FEC don't change it.
+    pub fn last_frame_size(&self) -> usize {
+        self.last_frame_size
+    }
+
+    /// Samples per channel of the last packet decoded or concealed, from
+    /// [`plc::last_packet_duration`]. Unlike
+    /// [`last_frame_size`](Self::last_frame_size) this follows concealment
+    /// and FEC too, and is 0 before anything was decoded.
+    pub fn last_packet_duration(&self) -> Result<i32, i32> {
+        unsafe { plc::last_packet_duration(self.decoder) }
+    }
+
+    /// Disable phase inversion in intensity stereo with
+    /// `OPUS_SET_PHASE_INVERSION_DISABLED`, for output that is downmixed to
+    /// mono later, where inverted channels would cancel out.
+    pub fn set_phase_inversion_disabled(&mut self, disabled: bool) -> Result<(), i32> {
+        self.set(OPUS_SET_PHASE_INVERSION_DISABLED_REQUEST, disabled as i32)
+    }
+
+    /// Whether phase inversion is disabled,
+    /// `OPUS_GET_PHASE_INVERSION_DISABLED`
+    pub fn phase_inversion_disabled(&self) -> Result<bool, i32> {
+        self.get(OPUS_GET_PHASE_INVERSION_DISABLED_REQUEST)
+            .map(|value| value == 1)
+    }
+
+    /// `opus_decoder_ctl` with a setter `request`, returning its error code
+    fn set(&mut self, request: u32, value: i32) -> Result<(), i32> {
+        let ret = unsafe { opus_decoder_ctl(self.decoder, request as i32, value) };
+        if ret == OPUS_OK as i32 {
+            Ok(())
+        } else {
+            Err(ret)
+        }
+    }
+
+    /// `opus_decoder_ctl` with a getter `request`, returning its error code
+    fn get(&self, request: u32) -> Result<i32, i32> {
+        let mut value: i32 = 0;
+        let ret = unsafe { opus_decoder_ctl(self.decoder, request as i32, &mut value as *mut i32) };
+        if ret == OPUS_OK as i32 {
+            Ok(value)
+        } else {
+            Err(ret)
+        }
+    }
+
+    /// Load DNN weights for deep PLC and OSCE with `OPUS_SET_DNN_BLOB`,
+    /// keeping them for as long as the decoder uses them.
+    ///
+    /// On failure the decoder is left as it was, with the weights loaded
+    /// before or without DNN. See [`dnn`].
+    pub fn set_dnn_blob(&mut self, blob: &DnnBlob) -> Result<(), BlobError> {
+        unsafe { dnn::load_decoder(self.decoder, blob, self.dnn_blob.as_ref())? };
+        self.dnn_blob = Some(blob.clone());
+        Ok(())
+    }
+
+    /// Decode `packet` into interleaved `pcm`, or conceal a lost packet with
+    /// `None`, as `opus_decode` does.
+    ///
+    /// A packet may decode to as many samples per channel as `pcm` holds,
+    /// and `fec` produces exactly that many. `None` conceals for the
+    /// [`last_frame_size`](Self::last_frame_size), `OPUS_BUFFER_TOO_SMALL`
+    /// if `pcm` can't hold it; see [`conceal`](Self::conceal) for another
+    /// duration. An empty packet is `OPUS_BAD_ARG`, as libopus would take it
+    /// for a lost one. Returns the samples per channel written, or the Opus
+    /// error code.
+    pub fn decode(
+        &mut self,
+        packet: Option<&[u8]>,
+        pcm: &mut [i16],
+        fec: bool,
+    ) -> Result<usize, i32> {
+        let frame_size = self.frame_size(packet, pcm.len())?;
+        self.decode_frame(packet, pcm, frame_size, fec)
+    }
+
+    /// Conceal a lost packet for `samples` per channel into interleaved
+    /// `pcm`, e.g. to fill a gap of known duration, returning `samples` or
+    /// the Opus error code
+    pub fn conceal(&mut self, pcm: &mut [i16], samples: usize) -> Result<usize, i32> {
+        self.check_capacity(samples, pcm.len())?;
+        self.decode_frame(None, pcm, samples, false)
+    }
+
+    /// [`decode`](Self::decode) for a packet received as [`Bytes`], e.g.
+    /// from a `tokio` transport. `&Bytes` derefs to `&[u8]`, so this only
+    /// saves the conversion of the `Option`.
+    #[cfg(feature = "bytes")]
+    pub fn decode_bytes(
+        &mut self,
+        packet: Option<&Bytes>,
+        pcm: &mut [i16],
+        fec: bool,
+    ) -> Result<usize, i32> {
+        self.decode(packet.map(|packet| &packet[..]), pcm, fec)
+    }
+
+    /// [`decode`](Self::decode) with `opus_decode_float`
+    #[cfg(not(feature = "no-float-api"))]
+    pub fn decode_float(
+        &mut self,
+        packet: Option<&[u8]>,
+        pcm: &mut [f32],
+        fec: bool,
+    ) -> Result<usize, i32> {
+        let frame_size = self.frame_size(packet, pcm.len())?;
+        self.decode_frame_float(packet, pcm, frame_size, fec)
+    }
+
+    /// [`conceal`](Self::conceal) with `opus_decode_float`
+    #[cfg(not(feature = "no-float-api"))]
+    pub fn conceal_float(&mut self, pcm: &mut [f32], samples: usize) -> Result<usize, i32> {
+        self.check_capacity(samples, pcm.len())?;
+        self.decode_frame_float(None, pcm, samples, false)
+    }
+
+    /// Like [`decode`](Self::decode), writing one slice per channel.
+    ///
+    /// `out` must have one slice per channel, all as long as the frame
+    /// size. Unlike the interleaved output, a packet must fill them: one
+    /// decoding to a different number of samples is `OPUS_BAD_ARG`, as are
+    /// mismatched slices, and `out` is left untouched. Concealment fills
+    /// them too, whatever their length.
+    pub fn decode_planar(
+        &mut self,
+        packet: Option<&[u8]>,
+        out: &mut [&mut [i16]],
+        fec: bool,
+    ) -> Result<usize, i32> {
+        let frame_size = self.check_planar(packet, out, fec)?;
+        let mut scratch = core::mem::take(&mut self.scratch);
+        scratch.resize(frame_size * self.channels, 0);
+        let result = self
+            .decode_frame(packet, &mut scratch, frame_size, fec)
+            .and_then(|samples| split(&scratch, samples, frame_size, out));
+        self.scratch = scratch;
+        result
+    }
+
+    /// [`decode_planar`](Self::decode_planar) with `opus_decode_float`
+    #[cfg(not(feature = "no-float-api"))]
+    pub fn decode_planar_float(
+        &mut self,
+        packet: Option<&[u8]>,
+        out: &mut [&mut [f32]],
+        fec: bool,
+    ) -> Result<usize, i32> {
+        let frame_size = self.check_planar(packet, out, fec)?;
+        let mut scratch = core::mem::take(&mut self.scratch_float);
+        scratch.resize(frame_size * self.channels, 0.0);
+        let result = self
+            .decode_frame_float(packet, &mut scratch, frame_size, fec)
+            .and_then(|samples| split(&scratch, samples, frame_size, out));
+        self.scratch_float = scratch;
+        result
+    }
+
+    /// `opus_decode` of at most `frame_size` samples per channel, noting the
+    /// size of a packet decoded
+    fn decode_frame(
+        &mut self,
+        packet: Option<&[u8]>,
+        pcm: &mut [i16],
+        frame_size: usize,
+        fec: bool,
+    ) -> Result<usize, i32> {
+        let decoder = self.decoder;
+        let result = decode_with(
+            packet,
+            frame_size,
+            pcm,
+            |data, len, out, frame_size| unsafe {
+                opus_decode(decoder, data, len, out, frame_size, fec as i32)
+            },
+        );
+        self.record(packet, fec, result)
+    }
+
+    /// [`decode_frame`](Self::decode_frame) with `opus_decode_float`
+    #[cfg(not(feature = "no-float-api"))]
+    fn decode_frame_float(
+        &mut self,
+        packet: Option<&[u8]>,
+        pcm: &mut [f32],
+        frame_size: usize,
+        fec: bool,
+    ) -> Result<usize, i32> {
+        let decoder = self.decoder;
+        let result = decode_with(
+            packet,
+            frame_size,
+            pcm,
+            |data, len, out, frame_size| unsafe {
+                opus_decode_float(decoder, data, len, out, frame_size, fec as i32)
+            },
+        );
+        self.record(packet, fec, result)
+    }
+
+    /// Keep the size of a packet decoded for concealing the next one
+    fn record(
+        &mut self,
+        packet: Option<&[u8]>,
+        fec: bool,
+        result: Result<usize, i32>,
+    ) -> Result<usize, i32> {
+        if let (Some(_), false, Ok(samples)) = (packet, fec, result) {
+            self.last_frame_size = samples;
+        }
+        result
+    }
+
+    /// The frame size to decode into `len` interleaved samples: all of them
+    /// for a packet, the last frame size for concealment
+    fn frame_size(&self, packet: Option<&[u8]>, len: usize) -> Result<usize, i32> {
+        match packet {
+            Some(_) => Ok(len / self.channels),
+            None => {
+                self.check_capacity(self.last_frame_size, len)?;
+                Ok(self.last_frame_size)
+            }
+        }
+    }
+
+    /// `OPUS_BUFFER_TOO_SMALL` unless `len` interleaved samples hold
+    /// `samples` per channel
+    fn check_capacity(&self, samples: usize, len: usize) -> Result<(), i32> {
+        if samples > len / self.channels {
+            return Err(OPUS_BUFFER_TOO_SMALL);
+        }
+        Ok(())
+    }
+
+    /// The frame size of planar `out`, checked against the channel count
+    /// and the samples in `packet`
+    fn check_planar<T>(
+        &self,
+        packet: Option<&[u8]>,
+        out: &[&mut [T]],
+        fec: bool,
+    ) -> Result<usize, i32> {
+        if out.len() != self.channels {
+            return Err(OPUS_BAD_ARG);
+        }
+        let frame_size = out[0].len();
+        if frame_size == 0 || out.iter().any(|channel| channel.len() != frame_size) {
+            return Err(OPUS_BAD_ARG);
+        }
+        // FEC and concealment produce whatever frame size is asked for
+        if let (Some(packet), false) = (packet, fec) {
+            if crate::packet::samples(packet, self.sample_rate)? != frame_size {
+                return Err(OPUS_BAD_ARG);
+            }
+        }
+        Ok(frame_size)
+    }
+}
+
+/// Run `decode` for `packet` (data, length, output, frame size) into
+/// interleaved `pcm`, which holds `frame_size` samples per channel
+fn decode_with<T>(
+    packet: Option<&[u8]>,
+    frame_size: usize,
+    pcm: &mut [T],
+    decode: impl FnOnce(*const u8, i32, *mut T, i32) -> i32,
+) -> Result<usize, i32> {
+    let frame_size = frame_size.min(i32::MAX as usize);
+    let (data, len) = match packet {
+        // Concealed by libopus, for all of `pcm` rather than the last frame
+        // size
+        Some([]) => return Err(OPUS_BAD_ARG),
+        Some(packet) => (
+            packet.as_ptr(),
+            i32::try_from(packet.len()).map_err(|_| OPUS_BAD_ARG)?,
+        ),
+        None => (core::ptr::null(), 0),
+    };
+    let ret = decode(data, len, pcm.as_mut_ptr(), frame_size as i32);
+    if ret < 0 {
+        Err(ret)
+    } else {
+        Ok(ret as usize)
+    }
+}
+
+/// Deinterleave the decoded `scratch` into `out`
+fn split<T: Copy>(
+    scratch: &[T],
+    samples: usize,
+    frame_size: usize,
+    out: &mut [&mut [T]],
+) -> Result<usize, i32> {
+    // Checked up front, so only a decoder disagreeing with its packet
+    if samples != frame_size {
+        return Err(OPUS_INTERNAL_ERROR);
+    }
+    deinterleave_into(scratch, out).map_err(|_| OPUS_BAD_ARG)?;
+    Ok(samples)
+}
+
+impl Drop for Decoder {
+    fn drop(&mut self) {
+        unsafe { opus_decoder_destroy(self.decoder) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: i32 = 48000;
+    const FRAME_SIZE: usize = 960;
+
+    /// 20 ms stereo packets of a tone, a different one per channel
+    fn packets(count: usize) -> Vec<Vec<u8>> {
+        let mut encoder =
+            crate::stream::StreamEncoder::new(SAMPLE_RATE, 2, OPUS_APPLICATION_AUDIO as i32, 960)
+                .unwrap();
+        let input: Vec<i16> = (0..FRAME_SIZE * count * 2)
+            .map(|i| {
+                let t = (i / 2) as f32 / SAMPLE_RATE as f32;
+                let freq = if i % 2 == 0 { 440.0 } else { 660.0 };
+                ((t * freq * std::f32::consts::TAU).sin() * 8000.0) as i16
+            })
+            .collect();
+        encoder.push(&input).unwrap()
+    }
+
+    /// Stereo packets of `frame_size` samples per channel
+    fn packets_of(frame_size: usize, count: usize) -> Vec<Vec<u8>> {
+        let mut encoder = crate::stream::StreamEncoder::new(
+            SAMPLE_RATE,
+            2,
+            OPUS_APPLICATION_AUDIO as i32,
+            frame_size,
+        )
+        .unwrap();
+        encoder
+            .push(&vec![1000i16; frame_size * count * 2])
+            .unwrap()
+    }
+
+    #[test]
+    fn test_conceals_last_frame_size() {
+        let mut decoder = Decoder::new(SAMPLE_RATE, 2).unwrap();
+        // Room for 120 ms, so only the last packet sizes concealment
+        let mut pcm = vec![0i16; 5760 * 2];
+        assert_eq!(decoder.last_frame_size(), FRAME_SIZE);
+        assert_eq!(decoder.decode(None, &mut pcm, false), Ok(FRAME_SIZE));
+
+        let ms20 = packets_of(FRAME_SIZE, 2);
+        let ms10 = packets_of(FRAME_SIZE / 2, 2);
+        let ms60 = packets_of(FRAME_SIZE * 3, 1);
+        for (packet, size) in [
+            (&ms20[0], FRAME_SIZE),
+            (&ms10[0], FRAME_SIZE / 2),
+            (&ms10[1], FRAME_SIZE / 2),
+            (&ms60[0], FRAME_SIZE * 3),
+            (&ms20[1], FRAME_SIZE),
+        ] {
+            assert_eq!(decoder.decode(Some(packet), &mut pcm, false), Ok(size));
+            // Twice: concealment doesn't change the size
+            assert_eq!(decoder.decode(None, &mut pcm, false), Ok(size));
+            assert_eq!(decoder.decode(None, &mut pcm, false), Ok(size));
+            assert_eq!(decoder.last_frame_size(), size);
+        }
+
+        // Another duration, and FEC, on request
+        assert_eq!(
+            decoder.conceal(&mut pcm, FRAME_SIZE / 4),
+            Ok(FRAME_SIZE / 4)
+        );
+        assert_eq!(
+            decoder.decode(Some(&ms10[0]), &mut pcm[..FRAME_SIZE * 2], true),
+            Ok(FRAME_SIZE)
+        );
+        assert_eq!(decoder.last_frame_size(), FRAME_SIZE);
+
+        // The output must hold the concealment
+        assert_eq!(
+            decoder.decode(None, &mut pcm[..FRAME_SIZE], false),
+            Err(OPUS_BUFFER_TOO_SMALL)
+        );
+        assert_eq!(
+            decoder.conceal(&mut pcm[..FRAME_SIZE], FRAME_SIZE),
+            Err(OPUS_BUFFER_TOO_SMALL)
+        );
+    }
+
+    #[test]
+    fn test_last_packet_duration() {
+        let mut decoder = Decoder::new(SAMPLE_RATE, 2).unwrap();
+        let mut pcm = vec![0i16; 5760 * 2];
+        assert_eq!(decoder.last_packet_duration(), Ok(0));
+
+        let packet = &packets_of(FRAME_SIZE / 2, 1)[0];
+        assert_eq!(
+            decoder.decode(Some(packet), &mut pcm, false),
+            Ok(FRAME_SIZE / 2)
+        );
+        assert_eq!(decoder.last_packet_duration(), Ok(FRAME_SIZE as i32 / 2));
+        assert_eq!(decoder.decode(None, &mut pcm, false), Ok(FRAME_SIZE / 2));
+        assert_eq!(decoder.last_packet_duration(), Ok(FRAME_SIZE as i32 / 2));
+        // Concealment of another duration counts, unlike for last_frame_size
+        assert_eq!(decoder.conceal(&mut pcm, FRAME_SIZE), Ok(FRAME_SIZE));
+        assert_eq!(decoder.last_packet_duration(), Ok(FRAME_SIZE as i32));
+        assert_eq!(decoder.last_frame_size(), FRAME_SIZE / 2);
+    }
+
+    #[test]
+    fn test_phase_inversion_disabled() {
+        let mut decoder = Decoder::new(SAMPLE_RATE, 2).unwrap();
+        assert_eq!(decoder.phase_inversion_disabled(), Ok(false));
+        decoder.set_phase_inversion_disabled(true).unwrap();
+        assert_eq!(decoder.phase_inversion_disabled(), Ok(true));
+
+        let mut pcm = vec![0i16; FRAME_SIZE * 2];
+        for packet in packets(4) {
+            assert_eq!(
+                decoder.decode(Some(&packet), &mut pcm, false),
+                Ok(FRAME_SIZE)
+            );
+        }
+        decoder.set_phase_inversion_disabled(false).unwrap();
+        assert_eq!(decoder.phase_inversion_disabled(), Ok(false));
+    }
+
+    #[test]
+    fn test_sample_rate() {
+        for sample_rate in [8000, 12000, 16000, 24000, 48000] {
+            let decoder = Decoder::new(sample_rate, 1).unwrap();
+            assert_eq!(decoder.sample_rate(), sample_rate);
+            assert_eq!(decoder.get(OPUS_GET_SAMPLE_RATE_REQUEST), Ok(sample_rate));
+        }
+    }
+
+    #[test]
+    fn test_empty_packet() {
+        let mut decoder = Decoder::new(SAMPLE_RATE, 2).unwrap();
+        let mut pcm = vec![0i16; 5760 * 2];
+        let packet = &packets_of(FRAME_SIZE / 2, 1)[0];
+        assert_eq!(
+            decoder.decode(Some(packet), &mut pcm, false),
+            Ok(FRAME_SIZE / 2)
+        );
+        for fec in [false, true] {
+            assert_eq!(decoder.decode(Some(&[]), &mut pcm, fec), Err(OPUS_BAD_ARG));
+        }
+        let (mut left, mut right) = (vec![0i16; FRAME_SIZE], vec![0i16; FRAME_SIZE]);
+        assert_eq!(
+            decoder.decode_planar(Some(&[]), &mut [&mut left, &mut right], true),
+            Err(OPUS_BAD_ARG)
+        );
+        // The 120 ms `pcm` holds didn't become the frame size
+        assert_eq!(decoder.last_frame_size(), FRAME_SIZE / 2);
+        assert_eq!(decoder.decode(None, &mut pcm, false), Ok(FRAME_SIZE / 2));
+    }
+
+    #[cfg(not(feature = "no-float-api"))]
+    #[test]
+    fn test_conceals_last_frame_size_float() {
+        let mut decoder = Decoder::new(SAMPLE_RATE, 2).unwrap();
+        let mut pcm = vec![0.0f32; 5760 * 2];
+        let packet = &packets_of(FRAME_SIZE / 2, 1)[0];
+        assert_eq!(
+            decoder.decode_float(Some(packet), &mut pcm, false),
+            Ok(FRAME_SIZE / 2)
+        );
+        assert_eq!(
+            decoder.decode_float(None, &mut pcm, false),
+            Ok(FRAME_SIZE / 2)
+        );
+        assert_eq!(decoder.conceal_float(&mut pcm, FRAME_SIZE), Ok(FRAME_SIZE));
+    }
+
+    #[test]
+    fn test_planar_matches_interleaved() {
+        let packets = packets(10);
+        let mut interleaved = Decoder::new(SAMPLE_RATE, 2).unwrap();
+        let mut planar = Decoder::new(SAMPLE_RATE, 2).unwrap();
+        let mut pcm = vec![0i16; FRAME_SIZE * 2];
+        let (mut left, mut right) = (vec![0i16; FRAME_SIZE], vec![0i16; FRAME_SIZE]);
+
+        // The fourth packet is lost
+        for (i, packet) in packets.iter().enumerate() {
+            let packet = (i != 3).then_some(packet.as_slice());
+            assert_eq!(interleaved.decode(packet, &mut pcm, false), Ok(FRAME_SIZE));
+            assert_eq!(
+                planar.decode_planar(packet, &mut [&mut left, &mut right], false),
+                Ok(FRAME_SIZE)
+            );
+            for (frame, samples) in pcm.chunks_exact(2).enumerate() {
+                assert_eq!(samples, [left[frame], right[frame]], "Packet {}", i);
+            }
+        }
+        assert!(left.iter().any(|&s| s != 0));
+        assert_ne!(left, right);
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn test_decode_bytes() {
+        let packets = packets(3);
+        let mut from_slice = Decoder::new(SAMPLE_RATE, 2).unwrap();
+        let mut from_bytes = Decoder::new(SAMPLE_RATE, 2).unwrap();
+        let mut expected = vec![0i16; FRAME_SIZE * 2];
+        let mut pcm = vec![0i16; FRAME_SIZE * 2];
+        for packet in &packets {
+            let bytes = Bytes::copy_from_slice(packet);
+            from_slice
+                .decode(Some(packet), &mut expected, false)
+                .unwrap();
+            assert_eq!(
+                from_bytes.decode_bytes(Some(&bytes), &mut pcm, false),
+                Ok(FRAME_SIZE)
+            );
+            assert_eq!(pcm, expected);
+        }
+        assert_eq!(
+            from_bytes.decode_bytes(None, &mut pcm, false),
+            Ok(FRAME_SIZE)
+        );
+    }
+
+    #[cfg(not(feature = "no-float-api"))]
+    #[test]
+    fn test_planar_float() {
+        let packets = packets(5);
+        let mut interleaved = Decoder::new(SAMPLE_RATE, 2).unwrap();
+        let mut planar = Decoder::new(SAMPLE_RATE, 2).unwrap();
+        let mut pcm = vec![0.0f32; FRAME_SIZE * 2];
+        let (mut left, mut right) = (vec![0.0f32; FRAME_SIZE], vec![0.0f32; FRAME_SIZE]);
+        for packet in &packets {
+            interleaved
+                .decode_float(Some(packet), &mut pcm, false)
+                .unwrap();
+            planar
+                .decode_planar_float(Some(packet), &mut [&mut left, &mut right], false)
+                .unwrap();
+            let expected: Vec<f32> = left
+                .iter()
+                .zip(&right)
+                .flat_map(|(&l, &r)| [l, r])
+                .collect();
+            assert_eq!(pcm, expected);
+        }
+        assert!(left.iter().any(|&s| s != 0.0));
+    }
+
+    #[test]
+    fn test_planar_errors() {
+        let packets = packets(1);
+        let packet = Some(packets[0].as_slice());
+        let mut decoder = Decoder::new(SAMPLE_RATE, 2).unwrap();
+        let mut left = vec![1i16; FRAME_SIZE];
+        let mut right = vec![1i16; FRAME_SIZE];
+        let mut short = vec![1i16; FRAME_SIZE / 2];
+
+        // One slice per channel
+        assert_eq!(
+            decoder.decode_planar(packet, &mut [&mut left], false),
+            Err(OPUS_BAD_ARG)
+        );
+        assert_eq!(
+            decoder.decode_planar(packet, &mut [&mut left, &mut right, &mut short], false),
+            Err(OPUS_BAD_ARG)
+        );
+        // Of the same length
+        assert_eq!(
+            decoder.decode_planar(packet, &mut [&mut left, &mut short], false),
+            Err(OPUS_BAD_ARG)
+        );
+        // Matching the packet
+        let (mut half_left, mut half_right) = (vec![1i16; FRAME_SIZE / 2], short);
+        assert_eq!(
+            decoder.decode_planar(packet, &mut [&mut half_left, &mut half_right], false),
+            Err(OPUS_BAD_ARG)
+        );
+        assert!(left.iter().chain(&right).all(|&s| s == 1));
+
+        // Concealment takes the slice length
+        assert_eq!(
+            decoder.decode_planar(None, &mut [&mut half_left, &mut half_right], false),
+            Ok(FRAME_SIZE / 2)
+        );
+        assert_eq!(Decoder::new(SAMPLE_RATE, 3).unwrap_err(), OPUS_BAD_ARG);
+        assert_eq!(Decoder::new(44100, 1).unwrap_err(), OPUS_BAD_ARG);
+    }
+}
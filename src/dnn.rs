@@ -0,0 +1,499 @@
+//! Loading DNN weights from untrusted files
+//!
+//! DRED, OSCE and deep PLC run on weights loaded at runtime with
+//! `OPUS_SET_DNN_BLOB`: the `opus_data-*.bin` blob, a sequence of records
+//! that each have a 64 byte header (`DNNw`, version, type, size, block size
+//! and name) followed by an array. libopus trusts the blob completely:
+//!
+//! - A record that doesn't parse leaves it with a NULL array list, which it
+//!   then dereferences.
+//! - Weights that parse but lack an array fail the load halfway, leaving
+//!   NULL layers behind in a model loaded earlier, which stays in use.
+//! - The models point into the blob rather than copying it, so it must
+//!   outlive the encoder or decoder, and it's read in place as `int`s and
+//!   `float`s, so it must be 4 byte aligned.
+//!
+//! [`DnnBlob::new`] checks every record the way libopus' parser does and
+//! copies the blob into aligned, shared storage.
+//! [`StreamEncoder::set_dnn_blob`](crate::stream::StreamEncoder::set_dnn_blob)
+//! and [`Decoder::set_dnn_blob`](crate::decoder::Decoder::set_dnn_blob)
+//! load it into a scratch encoder or decoder first and into the real one
+//! only if that succeeds, then keep the blob alive while it's in use. A
+//! failed load leaves the encoder or decoder as it was, without DNN if it
+//! had none, and returns a [`BlobError`]. Should the real one fail anyway,
+//! the blob it had from `set_dnn_blob` before is loaded again.
+//!
+//! ```no_run
+//! use opus_head_sys::dnn::DnnBlob;
+//! use opus_head_sys::stream::StreamEncoder;
+//!
+//! let blob = DnnBlob::new(&std::fs::read("opus_data.bin").unwrap()).unwrap();
+//! let mut encoder = StreamEncoder::voip(16000, 1).unwrap();
+//! if let Err(e) = encoder.set_dnn_blob(&blob) {
+//!     // Still encodes, just without DRED
+//!     eprintln!("No DRED: {}", e);
+//! }
+//! ```
+
+use crate::bindings::*;
+use std::fmt;
+use std::sync::Arc;
+
+/// Size of a record header, `WEIGHT_BLOCK_SIZE` in `dnn/nnet.h`
+const HEADER_SIZE: usize = 64;
+
+/// `WEIGHT_BLOB_VERSION`
+const BLOB_VERSION: i32 = 0;
+
+/// `WEIGHT_TYPE_float` to `WEIGHT_TYPE_int8`
+const TYPES: core::ops::RangeInclusive<i32> = 0..=3;
+
+/// Why weights could not be loaded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlobError {
+    /// The blob is empty, or longer than the `int` length libopus takes
+    Length(usize),
+    /// The record at byte `offset` is malformed
+    Malformed { offset: usize, reason: &'static str },
+    /// libopus rejected the weights with this return value, typically
+    /// because arrays are missing or have the wrong size: they're for
+    /// another Opus version, or not Opus weights at all
+    Rejected(i32),
+    /// Neither DRED nor OSCE is compiled in, or the weights are built into
+    /// the library (`OPUS_UNIMPLEMENTED`)
+    Unsupported,
+}
+
+impl fmt::Display for BlobError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BlobError::Length(len) => write!(f, "invalid DNN weights length {}", len),
+            BlobError::Malformed { offset, reason } => {
+                write!(f, "malformed DNN weights at byte {}: {}", offset, reason)
+            }
+            BlobError::Rejected(ret) => write!(f, "opus rejected the DNN weights: {}", ret),
+            BlobError::Unsupported => write!(f, "opus is built without runtime DNN weights"),
+        }
+    }
+}
+
+impl std::error::Error for BlobError {}
+
+impl BlobError {
+    /// The nearest Opus error code, for APIs that return one
+    pub(crate) fn code(&self) -> i32 {
+        match self {
+            BlobError::Length(_) | BlobError::Malformed { .. } => OPUS_BAD_ARG,
+            BlobError::Rejected(ret) if *ret < 0 => *ret,
+            BlobError::Rejected(_) => OPUS_BAD_ARG,
+            BlobError::Unsupported => OPUS_UNIMPLEMENTED,
+        }
+    }
+}
+
+/// DNN weights checked for `OPUS_SET_DNN_BLOB`, see the
+/// [module docs](self). Cloning shares the weights.
+#[derive(Clone)]
+pub struct DnnBlob {
+    /// The blob in `u32`s, for the alignment libopus expects
+    words: Arc<[u32]>,
+    len: usize,
+}
+
+impl DnnBlob {
+    /// Check every record of `data` and copy it.
+    ///
+    /// This catches truncated and corrupt files, but not weights for
+    /// another Opus version; loading them fails with
+    /// [`BlobError::Rejected`].
+    pub fn new(data: &[u8]) -> Result<Self, BlobError> {
+        if data.is_empty() || data.len() > i32::MAX as usize {
+            return Err(BlobError::Length(data.len()));
+        }
+        let mut offset = 0;
+        while offset < data.len() {
+            offset += check_record(&data[offset..])
+                .map_err(|reason| BlobError::Malformed { offset, reason })?;
+        }
+
+        let mut words = vec![0u32; data.len().div_ceil(4)];
+        unsafe {
+            core::ptr::copy_nonoverlapping(data.as_ptr(), words.as_mut_ptr() as *mut u8, data.len())
+        };
+        Ok(DnnBlob {
+            words: words.into(),
+            len: data.len(),
+        })
+    }
+
+    /// The blob as passed to [`new`](Self::new)
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe { core::slice::from_raw_parts(self.words.as_ptr() as *const u8, self.len) }
+    }
+
+    /// Length in bytes
+    // Never empty: `new` rejects an empty blob
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    fn as_ptr(&self) -> *const core::ffi::c_void {
+        self.words.as_ptr() as *const core::ffi::c_void
+    }
+}
+
+impl fmt::Debug for DnnBlob {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DnnBlob").field("len", &self.len).finish()
+    }
+}
+
+/// `OPUS_SET_DNN_BLOB` with `blob` on `encoder`, after a scratch encoder
+/// took it: if libopus fails halfway, only the scratch one is left with a
+/// half loaded model. `previous` is the blob `encoder` has loaded, if any,
+/// which is put back should the real load still fail.
+///
+/// # Safety
+///
+/// `encoder` must be valid, and `blob` and `previous` must outlive it.
+pub(crate) unsafe fn load_encoder(
+    encoder: *mut OpusEncoder,
+    blob: &DnnBlob,
+    previous: Option<&DnnBlob>,
+) -> Result<(), BlobError> {
+    let set = |encoder, blob: &DnnBlob| {
+        opus_encoder_ctl(
+            encoder,
+            OPUS_SET_DNN_BLOB_REQUEST as i32,
+            blob.as_ptr(),
+            blob.len as i32,
+        )
+    };
+    let mut error: i32 = 0;
+    let scratch = opus_encoder_create(16000, 1, OPUS_APPLICATION_VOIP as i32, &mut error);
+    if error != OPUS_OK as i32 || scratch.is_null() {
+        return Err(BlobError::Rejected(error));
+    }
+    let result = load(encoder, scratch, blob, previous, set);
+    opus_encoder_destroy(scratch);
+    result
+}
+
+/// [`load_encoder`] for a decoder
+///
+/// # Safety
+///
+/// `decoder` must be valid, and `blob` and `previous` must outlive it.
+pub(crate) unsafe fn load_decoder(
+    decoder: *mut OpusDecoder,
+    blob: &DnnBlob,
+    previous: Option<&DnnBlob>,
+) -> Result<(), BlobError> {
+    let set = |decoder, blob: &DnnBlob| {
+        opus_decoder_ctl(
+            decoder,
+            OPUS_SET_DNN_BLOB_REQUEST as i32,
+            blob.as_ptr(),
+            blob.len as i32,
+        )
+    };
+    let mut error: i32 = 0;
+    let scratch = opus_decoder_create(16000, 1, &mut error);
+    if error != OPUS_OK as i32 || scratch.is_null() {
+        return Err(BlobError::Rejected(error));
+    }
+    let result = load(decoder, scratch, blob, previous, set);
+    opus_decoder_destroy(scratch);
+    result
+}
+
+/// `blob` into `scratch`, then into `state` with `set`.
+///
+/// The second load parses the same blob the same way, so it only fails if
+/// libopus does something the scratch instance didn't show, e.g. runs out of
+/// memory. `state` may then hold a half loaded model: `previous`, which it
+/// loaded before, is loaded again to replace it. Without `previous` the
+/// models never had their `loaded` flag set, so libopus doesn't use them;
+/// weights loaded some other way, e.g. with `opus_encoder_ctl` on the raw
+/// pointer, can't be restored.
+fn load<S: Copy>(
+    state: S,
+    scratch: S,
+    blob: &DnnBlob,
+    previous: Option<&DnnBlob>,
+    set: impl Fn(S, &DnnBlob) -> i32,
+) -> Result<(), BlobError> {
+    check(set(scratch, blob))?;
+    check(set(state, blob)).inspect_err(|_| {
+        if let Some(previous) = previous {
+            set(state, previous);
+        }
+    })
+}
+
+/// The length of the record `data` starts with, checked like
+/// `parse_record` in `dnn/parse_lpcnet_weights.c` does, plus the header
+/// fields it ignores
+fn check_record(data: &[u8]) -> Result<usize, &'static str> {
+    if data.len() < HEADER_SIZE {
+        return Err("truncated header");
+    }
+    let field = |i: usize| i32::from_ne_bytes(data[4 * i..4 * i + 4].try_into().unwrap());
+    let (version, kind, size, block_size) = (field(1), field(2), field(3), field(4));
+    if &data[..4] != b"DNNw" {
+        return Err("no DNNw magic");
+    }
+    if version != BLOB_VERSION {
+        return Err("unknown version");
+    }
+    if !TYPES.contains(&kind) {
+        return Err("unknown array type");
+    }
+    if data[HEADER_SIZE - 1] != 0 {
+        return Err("unterminated name");
+    }
+    // libopus fails the whole blob on an empty array
+    if size <= 0 || block_size < size {
+        return Err("invalid array size");
+    }
+    if block_size % 4 != 0 {
+        return Err("misaligned block");
+    }
+    if block_size as usize > data.len() - HEADER_SIZE {
+        return Err("array past the end");
+    }
+    Ok(HEADER_SIZE + block_size as usize)
+}
+
+/// The result of `OPUS_SET_DNN_BLOB`. The decoder returns 0 or -1 from
+/// its models rather than an Opus error code.
+fn check(ret: i32) -> Result<(), BlobError> {
+    match ret {
+        0 => Ok(()),
+        OPUS_UNIMPLEMENTED => Err(BlobError::Unsupported),
+        ret => Err(BlobError::Rejected(ret)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoder::Decoder;
+    use crate::stream::StreamEncoder;
+    use crate::test_util::speech_like;
+
+    /// A record as `write_lpcnet_weights.c` writes it
+    fn record(name: &str, kind: i32, data: &[u8]) -> Vec<u8> {
+        let block_size = data.len().div_ceil(HEADER_SIZE) * HEADER_SIZE;
+        let mut record = b"DNNw".to_vec();
+        for field in [BLOB_VERSION, kind, data.len() as i32, block_size as i32] {
+            record.extend(field.to_ne_bytes());
+        }
+        let mut name = name.as_bytes().to_vec();
+        name.resize(HEADER_SIZE - record.len(), 0);
+        record.extend(name);
+        record.extend(data);
+        record.resize(HEADER_SIZE + block_size, 0);
+        record
+    }
+
+    /// Well-formed records with arrays no model has
+    fn foreign_weights() -> Vec<u8> {
+        let mut blob = record("not_an_opus_layer_bias", 0, &[0x3f; 40]);
+        blob.extend(record("not_an_opus_layer_weights", 3, &[0x7f; 300]));
+        blob
+    }
+
+    #[test]
+    fn test_well_formed() {
+        let blob = foreign_weights();
+        let checked = DnnBlob::new(&blob).unwrap();
+        assert_eq!(checked.as_bytes(), &blob[..]);
+        assert_eq!(checked.len(), 64 + 64 + 64 + 320);
+        assert_eq!(checked.as_ptr() as usize % 4, 0);
+    }
+
+    #[test]
+    fn test_malformed() {
+        let good = foreign_weights();
+        let second = 128;
+        let malformed =
+            |offset: usize, reason: &'static str| BlobError::Malformed { offset, reason };
+        let patched = |at: usize, bytes: &[u8]| {
+            let mut blob = good.clone();
+            blob[at..at + bytes.len()].copy_from_slice(bytes);
+            DnnBlob::new(&blob).unwrap_err()
+        };
+
+        assert_eq!(DnnBlob::new(&[]).unwrap_err(), BlobError::Length(0));
+        assert_eq!(
+            DnnBlob::new(&good[..good.len() - 1]).unwrap_err(),
+            malformed(second, "array past the end")
+        );
+        assert_eq!(
+            DnnBlob::new(&good[..second + 10]).unwrap_err(),
+            malformed(second, "truncated header")
+        );
+        assert_eq!(patched(second, b"DNNx"), malformed(second, "no DNNw magic"));
+        assert_eq!(patched(4, &[1, 0, 0, 0]), malformed(0, "unknown version"));
+        assert_eq!(
+            patched(8, &[4, 0, 0, 0]),
+            malformed(0, "unknown array type")
+        );
+        assert_eq!(patched(63, b"x"), malformed(0, "unterminated name"));
+        assert_eq!(patched(12, &[0; 4]), malformed(0, "invalid array size"));
+        assert_eq!(
+            patched(12, &65i32.to_ne_bytes()),
+            malformed(0, "invalid array size")
+        );
+        assert_eq!(
+            patched(16, &(-64i32).to_ne_bytes()),
+            malformed(0, "invalid array size")
+        );
+        assert_eq!(
+            patched(16, &62i32.to_ne_bytes()),
+            malformed(0, "misaligned block")
+        );
+        assert_eq!(
+            patched(16, &0x7fff_fff0i32.to_ne_bytes()),
+            malformed(0, "array past the end")
+        );
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    enum Instance {
+        Scratch,
+        Real,
+    }
+
+    #[test]
+    fn test_failed_real_load_restores_previous() {
+        use Instance::*;
+        let previous = DnnBlob::new(&record("previous_bias", 0, &[0x3f; 40])).unwrap();
+        let blob = DnnBlob::new(&foreign_weights()).unwrap();
+        // The real instance takes only the previous blob, as if it ran out
+        // of memory halfway through any other
+        let calls = core::cell::RefCell::new(Vec::new());
+        let set = |instance, blob: &DnnBlob| {
+            calls.borrow_mut().push((instance, blob.len()));
+            match instance {
+                Real if blob.len() != previous.len() => -1,
+                _ => 0,
+            }
+        };
+
+        assert_eq!(
+            load(Real, Scratch, &blob, Some(&previous), set),
+            Err(BlobError::Rejected(-1))
+        );
+        assert_eq!(
+            calls.take(),
+            [
+                (Scratch, blob.len()),
+                (Real, blob.len()),
+                (Real, previous.len())
+            ]
+        );
+
+        // Nothing to restore without DNN before
+        assert_eq!(
+            load(Real, Scratch, &blob, None, set),
+            Err(BlobError::Rejected(-1))
+        );
+        assert_eq!(calls.take(), [(Scratch, blob.len()), (Real, blob.len())]);
+
+        // Success, or failure on the scratch instance, needs no restore
+        assert_eq!(load(Real, Scratch, &previous, Some(&blob), set), Ok(()));
+        assert_eq!(
+            calls.take(),
+            [(Scratch, previous.len()), (Real, previous.len())]
+        );
+        let reject = |instance, blob: &DnnBlob| {
+            calls.borrow_mut().push((instance, blob.len()));
+            OPUS_BAD_ARG
+        };
+        assert_eq!(
+            load(Real, Scratch, &blob, Some(&previous), reject),
+            Err(BlobError::Rejected(OPUS_BAD_ARG))
+        );
+        assert_eq!(calls.take(), [(Scratch, blob.len())]);
+    }
+
+    /// 20 ms packets of speech
+    fn encode(encoder: &mut StreamEncoder) -> Vec<Vec<u8>> {
+        encoder.push(&speech_like(1, 16000, 320 * 25)).unwrap()
+    }
+
+    #[test]
+    fn test_corrupted_blob_leaves_encoder_usable() {
+        // The magic followed by garbage, which crashes opus_encoder_ctl
+        let mut garbage = b"DNNw".to_vec();
+        garbage.extend([0x5a; 252]);
+        assert_eq!(
+            DnnBlob::new(&garbage).unwrap_err(),
+            BlobError::Malformed {
+                offset: 0,
+                reason: "unknown version"
+            }
+        );
+
+        let blob = DnnBlob::new(&foreign_weights()).unwrap();
+        let mut encoder = StreamEncoder::voip(16000, 1).unwrap();
+        let expected = if !crate::HAS_DNN {
+            Err(BlobError::Unsupported)
+        } else if crate::build_info::DRED_ENABLED {
+            Err(BlobError::Rejected(OPUS_BAD_ARG))
+        } else {
+            // Only the DRED encoder takes weights
+            Ok(())
+        };
+        assert_eq!(encoder.set_dnn_blob(&blob), expected);
+        // Encodes exactly as if nothing had been loaded
+        let packets = encode(&mut encoder);
+        assert_eq!(packets, encode(&mut StreamEncoder::voip(16000, 1).unwrap()));
+        assert!(packets.iter().all(|p| p.len() > 10));
+    }
+
+    #[test]
+    fn test_rejected_blob_leaves_decoder_usable() {
+        let blob = DnnBlob::new(&foreign_weights()).unwrap();
+        let mut decoder = Decoder::new(16000, 1).unwrap();
+        let result = decoder.set_dnn_blob(&blob);
+        if crate::HAS_DNN {
+            assert!(matches!(result, Err(BlobError::Rejected(_))));
+        } else {
+            assert_eq!(result, Err(BlobError::Unsupported));
+        }
+        let mut pcm = [0i16; 320];
+        for packet in encode(&mut StreamEncoder::voip(16000, 1).unwrap()) {
+            assert_eq!(decoder.decode(Some(&packet), &mut pcm, false), Ok(320));
+        }
+        // Concealment, which uses deep PLC once loaded
+        assert_eq!(decoder.decode(None, &mut pcm, false), Ok(320));
+    }
+
+    /// The real weights, which need generate_weights.py
+    #[test]
+    #[cfg(all(any(feature = "dred", feature = "osce"), not(feature = "no-float-api")))]
+    fn test_load_weights() {
+        let Some(weights) = crate::test_support::load_dnn_weights() else {
+            println!("No DNN weights in target/model, skipping");
+            return;
+        };
+        let blob = DnnBlob::new(&weights).unwrap();
+        let mut encoder = StreamEncoder::voip(16000, 1).unwrap();
+        assert_eq!(encoder.set_dnn_blob(&blob), Ok(()));
+        let mut decoder = Decoder::new(16000, 1).unwrap();
+        assert_eq!(decoder.set_dnn_blob(&blob), Ok(()));
+        // Foreign weights afterwards keep the real ones in place
+        let foreign = DnnBlob::new(&foreign_weights()).unwrap();
+        assert!(decoder.set_dnn_blob(&foreign).is_err());
+        drop(blob);
+        let mut pcm = [0i16; 320];
+        for packet in encode(&mut encoder) {
+            assert_eq!(decoder.decode(Some(&packet), &mut pcm, false), Ok(320));
+        }
+        assert_eq!(decoder.decode(None, &mut pcm, false), Ok(320));
+    }
+}
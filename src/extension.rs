@@ -0,0 +1,239 @@
+//! Packet extensions, carried in the padding of code 3 packets
+//! (draft-ietf-mlmi-opus-extension), which is where libopus puts DRED
+//!
+//! A port of the iterator in libopus' `src/extensions.c`, including frame
+//! separators and the "repeat these extensions" shorthand, so a packet can
+//! be searched for an extension without a decoder.
+
+use crate::bindings::OPUS_INVALID_PACKET;
+
+/// An extension in a packet's padding
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Extension<'a> {
+    /// 3 to 127; 0 to 2 are padding, frame separators and repeats
+    pub id: u8,
+    /// The frame of the packet it belongs to
+    pub frame: usize,
+    pub data: &'a [u8],
+}
+
+/// The number of length bytes after the ID byte and the payload of the
+/// extension with `id_byte` whose payload starts `data`, or `None` if it's
+/// truncated. A long extension without a length (L = 0) runs up to
+/// `trailing` bytes before the end of `data`.
+fn payload(data: &[u8], id_byte: u8, trailing: usize) -> Option<(usize, &[u8])> {
+    let id = id_byte >> 1;
+    let l = usize::from(id_byte & 1);
+    if (id == 0 && l == 1) || id == 2 {
+        return Some((0, &data[..0]));
+    }
+    if (1..32).contains(&id) {
+        // Short extensions have L bytes of payload
+        return data.get(..l).map(|payload| (0, payload));
+    }
+    if l == 0 {
+        let end = data.len().checked_sub(trailing)?;
+        return Some((0, &data[..end]));
+    }
+    let mut header = 0;
+    let mut len = 0;
+    loop {
+        let lacing = *data.get(header)?;
+        header += 1;
+        len += usize::from(lacing);
+        if lacing != 255 {
+            break;
+        }
+    }
+    data.get(header..header + len)
+        .map(|payload| (header, payload))
+}
+
+/// The first extension in `padding`, in bitstream order, for which
+/// `matches` returns true. `frames` is the packet's frame count.
+///
+/// Returns `OPUS_INVALID_PACKET` where libopus' iterator would, for a
+/// truncated extension or a frame separator past the last frame.
+pub(crate) fn find<'a>(
+    padding: &'a [u8],
+    frames: usize,
+    mut matches: impl FnMut(&Extension<'a>) -> bool,
+) -> Result<Option<Extension<'a>>, i32> {
+    let mut pos = 0;
+    let mut frame = 0;
+    // Where the extensions a repeat copies start, the end of the last long
+    // one among them, and the short payload bytes after it
+    let mut repeat_start = 0;
+    let mut last_long = None;
+    let mut trailing_short = 0;
+
+    while pos < padding.len() {
+        let start = pos;
+        let id_byte = padding[pos];
+        let (header, data) = payload(&padding[pos + 1..], id_byte, 0).ok_or(OPUS_INVALID_PACKET)?;
+        pos += 1 + header + data.len();
+        match id_byte >> 1 {
+            // Padding
+            0 => {}
+            // Frame separator, advancing by 1 or by its payload
+            1 => {
+                let increment = if id_byte & 1 == 0 {
+                    1
+                } else {
+                    usize::from(data[0])
+                };
+                if increment == 0 {
+                    continue;
+                }
+                frame += increment;
+                if frame >= frames {
+                    return Err(OPUS_INVALID_PACKET);
+                }
+                repeat_start = pos;
+                last_long = None;
+                trailing_short = 0;
+            }
+            // Repeat the extensions since `repeat_start` for every later
+            // frame: their payloads follow, without ID bytes
+            2 => {
+                let repeat_l = id_byte & 1 == 1;
+                for repeat_frame in frame + 1..frames {
+                    let mut src = repeat_start;
+                    while src < start {
+                        let src_id_byte = padding[src];
+                        let (header, data) = payload(&padding[src + 1..start], src_id_byte, 0)
+                            .ok_or(OPUS_INVALID_PACKET)?;
+                        src += 1 + header + data.len();
+                        // Padding and frame separators aren't repeated
+                        if src_id_byte <= 3 {
+                            continue;
+                        }
+                        // With L = 0, the last long extension of the last
+                        // frame takes the rest of the padding
+                        let mut id_byte = src_id_byte;
+                        if !repeat_l && repeat_frame + 1 >= frames && Some(src) == last_long {
+                            id_byte &= !1;
+                        }
+                        let (header, data) = payload(&padding[pos..], id_byte, trailing_short)
+                            .ok_or(OPUS_INVALID_PACKET)?;
+                        pos += header + data.len();
+                        let extension = Extension {
+                            id: id_byte >> 1,
+                            frame: repeat_frame,
+                            data,
+                        };
+                        if matches(&extension) {
+                            return Ok(Some(extension));
+                        }
+                    }
+                }
+                repeat_start = pos;
+                last_long = None;
+                if !repeat_l {
+                    frame += 1;
+                    if frame >= frames {
+                        // The rest is padding
+                        break;
+                    }
+                }
+            }
+            id => {
+                if id >= 32 {
+                    last_long = Some(pos);
+                    trailing_short = 0;
+                } else {
+                    trailing_short += usize::from(id_byte & 1);
+                }
+                let extension = Extension { id, frame, data };
+                if matches(&extension) {
+                    return Ok(Some(extension));
+                }
+            }
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every extension in `padding`
+    fn all(padding: &[u8], frames: usize) -> Result<Vec<(u8, usize, Vec<u8>)>, i32> {
+        let mut found = Vec::new();
+        find(padding, frames, |extension| {
+            found.push((extension.id, extension.frame, extension.data.to_vec()));
+            false
+        })?;
+        Ok(found)
+    }
+
+    #[test]
+    fn test_short_and_long() {
+        // Short ID 5 with one byte, short ID 6 without, long ID 40 with a
+        // length, then long ID 41 running to the end
+        let padding = [10 | 1, 0xaa, 12, 80 | 1, 2, 1, 2, 82, 3, 4, 5];
+        assert_eq!(
+            all(&padding, 1),
+            Ok(vec![
+                (5, 0, vec![0xaa]),
+                (6, 0, vec![]),
+                (40, 0, vec![1, 2]),
+                (41, 0, vec![3, 4, 5]),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_long_length_lacing() {
+        let mut padding = vec![80 | 1, 255, 1];
+        padding.extend(std::iter::repeat_n(7, 256));
+        padding.push(12);
+        let found = all(&padding, 1).unwrap();
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].2.len(), 256);
+        assert_eq!(found[1], (6, 0, vec![]));
+    }
+
+    #[test]
+    fn test_frame_separators() {
+        // ID 5 in frame 0, separator, ID 6 in frame 1, separator by 2,
+        // ID 7 in frame 3
+        let padding = [10 | 1, 1, 2, 12, 3, 2, 14];
+        assert_eq!(
+            all(&padding, 4),
+            Ok(vec![(5, 0, vec![1]), (6, 1, vec![]), (7, 3, vec![])])
+        );
+        // A separator past the last frame
+        assert_eq!(all(&padding, 3), Err(OPUS_INVALID_PACKET));
+        // An increment of 0 does nothing
+        assert_eq!(all(&[3, 0, 12], 1), Ok(vec![(6, 0, vec![])]));
+    }
+
+    #[test]
+    fn test_repeat() {
+        // ID 5 with one byte and long ID 40, repeated for frames 1 and 2
+        // with L = 0: the last repeated long extension runs to the end
+        let padding = [10 | 1, 1, 80 | 1, 1, 9, 4, 2, 1, 8, 3, 7, 7];
+        assert_eq!(
+            all(&padding, 3),
+            Ok(vec![
+                (5, 0, vec![1]),
+                (40, 0, vec![9]),
+                (5, 1, vec![2]),
+                (40, 1, vec![8]),
+                (5, 2, vec![3]),
+                (40, 2, vec![7, 7]),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_padding_and_truncation() {
+        // ID 0 with L = 0 is padding to the end
+        assert_eq!(all(&[0, 10 | 1, 1], 1), Ok(vec![]));
+        assert_eq!(all(&[1, 1, 12], 1), Ok(vec![(6, 0, vec![])]));
+        assert_eq!(all(&[10 | 1], 1), Err(OPUS_INVALID_PACKET));
+        assert_eq!(all(&[80 | 1, 4, 1], 1), Err(OPUS_INVALID_PACKET));
+    }
+}
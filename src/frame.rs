@@ -0,0 +1,166 @@
+//! Fixed-size frames checked at compile time
+//!
+//! [`Frame`] holds exactly one Opus frame of interleaved `i16` samples. The
+//! sample rate, duration and buffer length are type parameters, and an
+//! invalid combination is a compile error, so a real-time loop built on
+//! `Frame<48000, 20, 960>` can never hand `opus_encode` a wrong frame size.
+//!
+//! The buffer length `N` has to be spelled out because stable Rust cannot
+//! compute an array length from other const parameters; it is checked to be
+//! one frame of mono or stereo audio instead:
+//!
+//! ```
+//! use opus_head_sys::frame::Frame;
+//!
+//! let mono = Frame::<48000, 20, 960>::zeroed();
+//! let stereo = Frame::<16000, 10, 320>::zeroed();
+//! assert_eq!((mono.channels(), stereo.channels()), (1, 2));
+//! ```
+//!
+//! ```compile_fail
+//! // 15 ms is not an Opus frame duration
+//! let frame = opus_head_sys::frame::Frame::<48000, 15, 720>::zeroed();
+//! ```
+//!
+//! ```compile_fail
+//! // 20 ms at 48 kHz is 960 samples per channel, not 1000
+//! let frame = opus_head_sys::frame::Frame::<48000, 20, 1000>::zeroed();
+//! ```
+//!
+//! Durations are whole milliseconds, so 2.5 ms frames are not expressible;
+//! use the slice-based API for those and for variable frame sizes.
+
+use crate::bindings::*;
+
+/// One frame of interleaved samples, see the [module docs](self)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame<const SR: u32, const MS: u32, const N: usize>([i16; N]);
+
+impl<const SR: u32, const MS: u32, const N: usize> Frame<SR, MS, N> {
+    /// Samples per channel, checking the rate and duration
+    pub const SAMPLES_PER_CHANNEL: usize = {
+        assert!(
+            matches!(SR, 8000 | 12000 | 16000 | 24000 | 48000),
+            "sample rate must be 8000, 12000, 16000, 24000 or 48000"
+        );
+        assert!(
+            matches!(MS, 5 | 10 | 20 | 40 | 60 | 80 | 100 | 120),
+            "frame duration must be 5, 10, 20, 40, 60, 80, 100 or 120 ms"
+        );
+        (SR / 1000 * MS) as usize
+    };
+
+    /// Channel count implied by `N`, checking it is one mono or stereo frame
+    pub const CHANNELS: usize = {
+        let per_channel = Self::SAMPLES_PER_CHANNEL;
+        assert!(
+            N == per_channel || N == 2 * per_channel,
+            "N must be one frame of mono or stereo samples"
+        );
+        N / per_channel
+    };
+
+    /// Wrap interleaved samples
+    pub fn new(samples: [i16; N]) -> Self {
+        let _ = Self::CHANNELS;
+        Frame(samples)
+    }
+
+    /// A frame of silence
+    pub fn zeroed() -> Self {
+        Self::new([0; N])
+    }
+
+    pub fn channels(&self) -> usize {
+        Self::CHANNELS
+    }
+
+    pub fn samples_per_channel(&self) -> usize {
+        Self::SAMPLES_PER_CHANNEL
+    }
+
+    pub fn samples(&self) -> &[i16; N] {
+        &self.0
+    }
+
+    pub fn samples_mut(&mut self) -> &mut [i16; N] {
+        &mut self.0
+    }
+
+    /// Encode this frame with `opus_encode`, returning the packet length or
+    /// an Opus error code. The frame size needs no runtime check.
+    ///
+    /// # Safety
+    ///
+    /// `encoder` must be a valid encoder created for `SR` Hz and
+    /// [`Self::CHANNELS`] channels.
+    pub unsafe fn encode(&self, encoder: *mut OpusEncoder, packet: &mut [u8]) -> i32 {
+        opus_encode(
+            encoder,
+            self.0.as_ptr(),
+            Self::SAMPLES_PER_CHANNEL as i32,
+            packet.as_mut_ptr(),
+            packet.len().min(i32::MAX as usize) as i32,
+        )
+    }
+}
+
+impl<const SR: u32, const MS: u32, const N: usize> Default for Frame<SR, MS, N> {
+    fn default() -> Self {
+        Self::zeroed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_sizes() {
+        assert_eq!(Frame::<48000, 20, 960>::SAMPLES_PER_CHANNEL, 960);
+        assert_eq!(Frame::<48000, 20, 1920>::CHANNELS, 2);
+        assert_eq!(Frame::<8000, 60, 480>::SAMPLES_PER_CHANNEL, 480);
+        assert_eq!(Frame::<12000, 5, 60>::CHANNELS, 1);
+        assert_eq!(Frame::<24000, 120, 5760>::CHANNELS, 2);
+    }
+
+    #[test]
+    fn test_encode_decode_frame() {
+        let mut frame = Frame::<48000, 20, 1920>::zeroed();
+        for (i, sample) in frame.samples_mut().iter_mut().enumerate() {
+            *sample = ((i as f32 * 0.05).sin() * 8000.0) as i16;
+        }
+
+        unsafe {
+            let mut error: i32 = 0;
+            let encoder = opus_encoder_create(
+                48000,
+                frame.channels() as i32,
+                OPUS_APPLICATION_AUDIO as i32,
+                &mut error,
+            );
+            assert_eq!(error, OPUS_OK as i32, "Failed to create encoder");
+
+            let mut packet = [0u8; 1275];
+            let len = frame.encode(encoder, &mut packet);
+            assert!(len > 0, "Encoding failed: {}", len);
+
+            let decoder = opus_decoder_create(48000, frame.channels() as i32, &mut error);
+            assert_eq!(error, OPUS_OK as i32, "Failed to create decoder");
+
+            let mut decoded = Frame::<48000, 20, 1920>::zeroed();
+            let samples = opus_decode(
+                decoder,
+                packet.as_ptr(),
+                len,
+                decoded.samples_mut().as_mut_ptr(),
+                decoded.samples_per_channel() as i32,
+                0,
+            );
+            assert_eq!(samples as usize, decoded.samples_per_channel());
+
+            opus_decoder_destroy(decoder);
+            opus_encoder_destroy(encoder);
+        }
+    }
+}
@@ -0,0 +1,232 @@
+//! Conversion between planar and interleaved sample buffers
+//!
+//! The Opus encode and decode functions take interleaved samples
+//! (`L R L R ...`), while many DSP frameworks keep one buffer per channel.
+//! These helpers convert between the two with the lengths checked up
+//! front. They are generic over the sample type, so the same functions
+//! serve `opus_encode` (`i16`) and `opus_encode_float` (`f32`).
+
+use core::fmt;
+
+/// Why a buffer could not be converted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LayoutError {
+    /// The channel count is zero
+    NoChannels,
+    /// The planar channels do not all have the same length
+    ChannelLengthMismatch,
+    /// A buffer has the wrong number of samples for the channel layout
+    LengthMismatch { expected: usize, actual: usize },
+}
+
+impl fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LayoutError::NoChannels => write!(f, "channel count is zero"),
+            LayoutError::ChannelLengthMismatch => {
+                write!(f, "planar channels have different lengths")
+            }
+            LayoutError::LengthMismatch { expected, actual } => {
+                write!(f, "expected {} samples, got {}", expected, actual)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for LayoutError {}
+
+/// Interleave one buffer per channel into `out`.
+///
+/// Every channel must have the same length, and `out` must hold exactly
+/// that many samples per channel.
+pub fn interleave<T: Copy>(planar: &[&[T]], out: &mut [T]) -> Result<(), LayoutError> {
+    let channels = planar.len();
+    let first = planar.first().ok_or(LayoutError::NoChannels)?;
+    let frames = first.len();
+    if planar.iter().any(|channel| channel.len() != frames) {
+        return Err(LayoutError::ChannelLengthMismatch);
+    }
+    if out.len() != frames * channels {
+        return Err(LayoutError::LengthMismatch {
+            expected: frames * channels,
+            actual: out.len(),
+        });
+    }
+
+    for (frame, samples) in out.chunks_exact_mut(channels).enumerate() {
+        for (sample, channel) in samples.iter_mut().zip(planar) {
+            *sample = channel[frame];
+        }
+    }
+    Ok(())
+}
+
+/// Split interleaved samples into one buffer per channel.
+///
+/// `out` must have one entry per channel; each is cleared and refilled, so
+/// the buffers can be reused across frames without reallocating.
+#[cfg(feature = "std")]
+pub fn deinterleave<T: Copy>(
+    interleaved: &[T],
+    channels: usize,
+    out: &mut [Vec<T>],
+) -> Result<(), LayoutError> {
+    if channels == 0 {
+        return Err(LayoutError::NoChannels);
+    }
+    if out.len() != channels {
+        return Err(LayoutError::LengthMismatch {
+            expected: channels,
+            actual: out.len(),
+        });
+    }
+    if !interleaved.len().is_multiple_of(channels) {
+        return Err(LayoutError::LengthMismatch {
+            expected: interleaved.len().next_multiple_of(channels),
+            actual: interleaved.len(),
+        });
+    }
+
+    for (index, channel) in out.iter_mut().enumerate() {
+        channel.clear();
+        channel.extend(interleaved.iter().skip(index).step_by(channels));
+    }
+    Ok(())
+}
+
+/// Split interleaved samples into `out`, one slice per channel.
+///
+/// Unlike [`deinterleave`], this fills existing slices and needs no `std`:
+/// every slice must have the same length, and `interleaved` must hold
+/// exactly that many samples per channel.
+pub fn deinterleave_into<T: Copy>(
+    interleaved: &[T],
+    out: &mut [&mut [T]],
+) -> Result<(), LayoutError> {
+    let channels = out.len();
+    let frames = out.first().ok_or(LayoutError::NoChannels)?.len();
+    if out.iter().any(|channel| channel.len() != frames) {
+        return Err(LayoutError::ChannelLengthMismatch);
+    }
+    if interleaved.len() != frames * channels {
+        return Err(LayoutError::LengthMismatch {
+            expected: frames * channels,
+            actual: interleaved.len(),
+        });
+    }
+
+    for (frame, samples) in interleaved.chunks_exact(channels).enumerate() {
+        for (channel, &sample) in out.iter_mut().zip(samples) {
+            channel[frame] = sample;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_interleave_roundtrip() {
+        let left = [1i16, 2, 3, 4];
+        let right = [-1i16, -2, -3, -4];
+
+        let mut interleaved = [0i16; 8];
+        interleave(&[&left, &right], &mut interleaved).unwrap();
+        assert_eq!(interleaved, [1, -1, 2, -2, 3, -3, 4, -4]);
+
+        let mut planar = vec![vec![99i16; 16], Vec::new()];
+        deinterleave(&interleaved, 2, &mut planar).unwrap();
+        assert_eq!(planar[0], left);
+        assert_eq!(planar[1], right);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_float_samples() {
+        let channels: Vec<Vec<f32>> = (0..3)
+            .map(|c| (0..5).map(|i| (c * 10 + i) as f32 * 0.1).collect())
+            .collect();
+        let planar: Vec<&[f32]> = channels.iter().map(Vec::as_slice).collect();
+
+        let mut interleaved = vec![0.0f32; 15];
+        interleave(&planar, &mut interleaved).unwrap();
+        assert_eq!(&interleaved[..3], &[0.0, 1.0, 2.0]);
+
+        let mut out = vec![Vec::new(); 3];
+        deinterleave(&interleaved, 3, &mut out).unwrap();
+        assert_eq!(out, channels);
+    }
+
+    #[test]
+    fn test_interleave_errors() {
+        let mut out = [0i16; 4];
+        assert_eq!(
+            interleave::<i16>(&[], &mut out),
+            Err(LayoutError::NoChannels)
+        );
+        assert_eq!(
+            interleave(&[&[1i16, 2][..], &[3][..]], &mut out),
+            Err(LayoutError::ChannelLengthMismatch)
+        );
+        assert_eq!(
+            interleave(&[&[1i16, 2][..], &[3, 4][..]], &mut out[..3]),
+            Err(LayoutError::LengthMismatch {
+                expected: 4,
+                actual: 3
+            })
+        );
+    }
+
+    #[test]
+    fn test_deinterleave_into() {
+        let interleaved = [1i16, -1, 2, -2, 3, -3];
+        let (mut left, mut right) = ([0i16; 3], [0i16; 3]);
+        deinterleave_into(&interleaved, &mut [&mut left, &mut right]).unwrap();
+        assert_eq!(left, [1, 2, 3]);
+        assert_eq!(right, [-1, -2, -3]);
+
+        assert_eq!(
+            deinterleave_into::<i16>(&interleaved, &mut []),
+            Err(LayoutError::NoChannels)
+        );
+        assert_eq!(
+            deinterleave_into(&interleaved, &mut [&mut left, &mut right[..2]]),
+            Err(LayoutError::ChannelLengthMismatch)
+        );
+        assert_eq!(
+            deinterleave_into(&interleaved[..4], &mut [&mut left, &mut right]),
+            Err(LayoutError::LengthMismatch {
+                expected: 6,
+                actual: 4
+            })
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_deinterleave_errors() {
+        let mut out = vec![Vec::new(); 2];
+        assert_eq!(
+            deinterleave(&[1i16, 2], 0, &mut out),
+            Err(LayoutError::NoChannels)
+        );
+        assert_eq!(
+            deinterleave(&[1i16, 2, 3], 2, &mut out),
+            Err(LayoutError::LengthMismatch {
+                expected: 4,
+                actual: 3
+            })
+        );
+        assert_eq!(
+            deinterleave(&[1i16, 2, 3], 3, &mut out),
+            Err(LayoutError::LengthMismatch {
+                expected: 3,
+                actual: 2
+            })
+        );
+    }
+}
@@ -7,23 +7,115 @@
 //! This crate provides raw FFI bindings. All functions that call into the C
 //! library are unsafe. Users should consider using a safe wrapper crate.
 //!
+//! ## `no_std`
+//!
+//! Without the default `std` feature the crate is `#![no_std]`: the
+//! bindings, the constants and the helpers that don't allocate remain,
+//! while [`stream`], [`decoder`], [`codec`], [`dnn`], [`repacketizer`], [`multistream`],
+//! [`projection`], [`loss`] and [`bitrate`] need `std`. For bare-metal targets the build
+//! script doesn't build Opus; point `OPUS_LIB_DIR` at a `libopus.a` built
+//! for the target.
+//!
 //! ## License
 //!
 //! The Opus codec is licensed under the BSD 3-Clause License.
 //! See the NOTICE file for full copyright information.
 
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 #![allow(non_upper_case_globals)]
 #![allow(non_camel_case_types)]
 #![allow(non_snake_case)]
 #![allow(dead_code)]
-#![allow(clippy::all)]
 
+// Generated by bindgen, so clippy only checks the hand-written modules
+#[cfg(not(any(feature = "prefix-symbols", feature = "generate-bindings")))]
+#[allow(clippy::all)]
 mod bindings;
+// With the symbols renamed, build.rs writes the bindings with matching
+// link names; with generate-bindings, it runs bindgen on the installed
+// headers
+#[cfg(any(feature = "prefix-symbols", feature = "generate-bindings"))]
+#[allow(clippy::all, rustdoc::broken_intra_doc_links)]
+mod bindings {
+    include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
+}
 pub use bindings::*;
 
+mod abi;
+pub mod build_info;
+pub use build_info::{
+    build_info, BuildInfo, HAS_CUSTOM_MODES, HAS_DNN, HAS_FAST_MATH, HAS_FLOAT_API,
+    VENDORED_VERSION, VENDORED_VERSION_MAJOR, VENDORED_VERSION_MINOR,
+};
+
+#[cfg(any(feature = "test-util", test))]
+pub mod analysis;
+#[cfg(feature = "std")]
+pub mod bitrate;
+pub mod budget;
+pub mod cbr;
+#[cfg(not(feature = "no-float-api"))]
+pub mod clip;
+#[cfg(feature = "std")]
+pub mod codec;
+pub mod cpu;
+#[cfg(feature = "std")]
+pub mod decoder;
+#[cfg(feature = "std")]
+pub mod dnn;
+#[cfg(feature = "dred")]
+mod extension;
+pub mod frame;
+pub mod layout;
+#[cfg(feature = "std")]
+pub mod loss;
+#[cfg(feature = "std")]
+pub mod multistream;
+pub mod osce;
+pub mod packet;
+pub mod plc;
+pub mod preset;
+#[cfg(feature = "std")]
+pub mod projection;
+#[cfg(feature = "std")]
+pub mod repacketizer;
+pub mod signal;
+#[cfg(feature = "simulate")]
+pub mod simulate;
+#[cfg(feature = "std")]
+pub mod stream;
+#[cfg(any(feature = "test-util", test))]
+pub mod test_util;
+
+#[cfg(all(
+    feature = "custom-modes",
+    not(any(feature = "prefix-symbols", feature = "generate-bindings"))
+))]
+#[allow(clippy::all)]
+mod custom_bindings;
+#[cfg(all(
+    feature = "custom-modes",
+    any(feature = "prefix-symbols", feature = "generate-bindings")
+))]
+#[allow(clippy::all, rustdoc::broken_intra_doc_links)]
+mod custom_bindings {
+    include!(concat!(env!("OUT_DIR"), "/custom_bindings.rs"));
+}
+#[cfg(feature = "custom-modes")]
+pub use custom_bindings::*;
+
+#[cfg(test)]
+#[path = "../tests/util/mod.rs"]
+mod test_support;
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::packet::{max_packet_size, FrameDuration};
+    use crate::test_util::noise;
+
+    /// Room for a 20 ms packet, the longest these tests encode
+    const MAX_PACKET: usize = max_packet_size(2, FrameDuration::Ms20);
 
     #[test]
     fn test_opus_version() {
@@ -109,6 +201,43 @@ mod tests {
         }
     }
 
+    /// The 16 KB page size link flags reach the CMake cache exactly when
+    /// enabled. Run with `OPUS_16KB_PAGE_SIZE=1` to check them on the host.
+    /// Skipped when CMake didn't run, e.g. with `OPUS_LIB_DIR`.
+    #[test]
+    fn test_16kb_page_size_flags_in_cmake_cache() {
+        let cache_path = concat!(env!("OUT_DIR"), "/build/CMakeCache.txt");
+        let Ok(cache) = std::fs::read_to_string(cache_path) else {
+            println!("No CMakeCache.txt in OUT_DIR, skipping");
+            return;
+        };
+
+        let expected = match option_env!("OPUS_16KB_PAGE_SIZE").map(str::trim) {
+            Some("0") => false,
+            Some(s) if !s.is_empty() => true,
+            _ => cfg!(all(
+                target_os = "android",
+                any(target_arch = "aarch64", target_arch = "x86_64")
+            )),
+        };
+
+        let linker_flags = cache
+            .lines()
+            .find(|l| l.starts_with("CMAKE_SHARED_LINKER_FLAGS"))
+            .unwrap_or_default();
+        println!("{}", linker_flags);
+        assert_eq!(
+            linker_flags.contains("-Wl,-z,max-page-size=16384"),
+            expected,
+            "Unexpected linker flags: {:?}",
+            linker_flags
+        );
+        assert_eq!(
+            linker_flags.contains("-Wl,-z,common-page-size=16384"),
+            expected
+        );
+    }
+
     /// Test loading DNN weights from target/model and setting them via OPUS_SET_DNN_BLOB.
     ///
     /// This test requires the weights to be generated first:
@@ -119,7 +248,7 @@ mod tests {
     /// Note: As of now, the actual weight loading may crash on some configurations.
     /// This test verifies that the weight file can be read and has the correct format.
     #[test]
-    #[cfg(feature = "dnn")]
+    #[cfg(all(any(feature = "dred", feature = "osce"), not(feature = "no-float-api")))]
     fn test_dnn_blob_loading() {
         use std::path::PathBuf;
 
@@ -265,52 +394,6 @@ mod tests {
         println!("DNN blob loading test passed!");
     }
 
-    /// Helper function to create deterministic random audio data (noise)
-    /// Uses a seed for reproducibility across tests
-    fn generate_noise_with_seed(samples: usize, seed: u64) -> Vec<i16> {
-        use std::collections::hash_map::DefaultHasher;
-        use std::hash::{Hash, Hasher};
-
-        let mut data = Vec::with_capacity(samples);
-        let mut hasher = DefaultHasher::new();
-        seed.hash(&mut hasher);
-
-        for i in 0..samples {
-            i.hash(&mut hasher);
-            let hash = hasher.finish();
-            // Scale to i16 range with reduced amplitude to avoid clipping
-            let sample = ((hash as i32 % 32768) - 16384) as i16;
-            data.push(sample);
-        }
-        data
-    }
-
-    /// Helper function to create random audio data (noise) - default seed
-    fn generate_noise(samples: usize) -> Vec<i16> {
-        generate_noise_with_seed(samples, 12345)
-    }
-
-    /// Helper function to load DNN weights if available
-    fn load_dnn_weights() -> Option<Vec<u8>> {
-        use std::path::PathBuf;
-
-        let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
-        let model_dir = manifest_dir.join("target").join("model");
-
-        let weights_path = std::fs::read_dir(&model_dir)
-            .ok()?
-            .filter_map(|e| e.ok())
-            .map(|e| e.path())
-            .find(|p| {
-                p.file_name()
-                    .and_then(|n| n.to_str())
-                    .map(|n| n.starts_with("opus_data-") && n.ends_with(".bin"))
-                    .unwrap_or(false)
-            })?;
-
-        std::fs::read(&weights_path).ok()
-    }
-
     /// Test basic encode/decode roundtrip WITHOUT DNN model
     /// Also tries to enable DRED (should fail without DNN weights loaded)
     #[test]
@@ -362,8 +445,8 @@ mod tests {
             assert!(!decoder.is_null());
 
             // Generate noise input
-            let input = generate_noise(FRAME_SIZE);
-            let mut encoded = vec![0u8; 4000]; // Max packet size
+            let input = noise::<i16>(1, FRAME_SIZE);
+            let mut encoded = vec![0u8; MAX_PACKET];
             let mut decoded = vec![0i16; FRAME_SIZE];
 
             // Encode
@@ -412,7 +495,7 @@ mod tests {
 
     /// Test basic encode/decode roundtrip WITH DNN model loaded and DRED enabled
     #[test]
-    #[cfg(feature = "dnn")]
+    #[cfg(all(feature = "dred", not(feature = "no-float-api")))]
     fn test_encode_decode_with_dnn() {
         const SAMPLE_RATE: i32 = 48000;
         const CHANNELS: i32 = 1;
@@ -420,7 +503,7 @@ mod tests {
         const BITRATE: i32 = 64000;
 
         // Load DNN weights
-        let weights_data = match load_dnn_weights() {
+        let weights_data = match crate::test_support::load_dnn_weights() {
             Some(data) => data,
             None => {
                 panic!(
@@ -504,8 +587,8 @@ mod tests {
             }
 
             // Generate noise input
-            let input = generate_noise(FRAME_SIZE);
-            let mut encoded = vec![0u8; 4000]; // Max packet size
+            let input = noise::<i16>(1, FRAME_SIZE);
+            let mut encoded = vec![0u8; MAX_PACKET];
             let mut decoded = vec![0i16; FRAME_SIZE];
 
             // Encode (with DRED enabled, packet may be larger)
@@ -603,8 +686,8 @@ mod tests {
 
             for frame_num in 0..NUM_FRAMES {
                 // Generate mono noise input
-                let input = generate_noise(FRAME_SIZE);
-                let mut encoded = vec![0u8; 4000];
+                let input = noise::<i16>(1, FRAME_SIZE);
+                let mut encoded = vec![0u8; MAX_PACKET];
                 let mut decoded = vec![0i16; FRAME_SIZE];
 
                 // Encode
@@ -651,103 +734,198 @@ mod tests {
         println!("Multi-frame encode/decode test WITHOUT DNN passed!");
     }
 
-    /// Test multiple frames encode/decode WITH DNN
+    /// Test float encode/decode roundtrip (not available with `no-float-api`)
     #[test]
-    #[cfg(feature = "dnn")]
-    fn test_multi_frame_encode_decode_with_dnn() {
+    #[cfg(not(feature = "no-float-api"))]
+    fn test_encode_decode_float() {
         const SAMPLE_RATE: i32 = 48000;
-        const CHANNELS: i32 = 1; // Mono - DRED works better with mono voice
+        const CHANNELS: i32 = 1;
         const FRAME_SIZE: usize = 960; // 20ms at 48kHz
-        const NUM_FRAMES: usize = 10;
-        const BITRATE: i32 = 32000; // 32kbit/s - typical for voice, forces SILK mode
-
-        // Load DNN weights
-        let weights_data = match load_dnn_weights() {
-            Some(data) => data,
-            None => {
-                panic!(
-                    "DNN weights not found. Run 'python generate_weights.py' to generate weights."
-                );
-            }
-        };
 
         unsafe {
             let mut error: i32 = 0;
 
-            // Create encoder (VOIP mode for voice)
             let encoder = opus_encoder_create(
                 SAMPLE_RATE,
                 CHANNELS,
-                OPUS_APPLICATION_VOIP as i32,
+                OPUS_APPLICATION_AUDIO as i32,
                 &mut error,
             );
             assert_eq!(error, OPUS_OK as i32, "Failed to create encoder");
 
-            // Set bitrate
-            opus_encoder_ctl(encoder, OPUS_SET_BITRATE_REQUEST as i32, BITRATE);
+            let decoder = opus_decoder_create(SAMPLE_RATE, CHANNELS, &mut error);
+            assert_eq!(error, OPUS_OK as i32, "Failed to create decoder");
 
-            // Set expected packet loss (needed for DRED to add redundancy)
-            let packet_loss_perc: i32 = 25; // 25% expected packet loss
-            let ret = opus_encoder_ctl(
+            let input: Vec<f32> = noise(1, FRAME_SIZE);
+            let mut encoded = vec![0u8; MAX_PACKET];
+            let mut decoded = vec![0f32; FRAME_SIZE];
+
+            let encoded_len = opus_encode_float(
                 encoder,
-                OPUS_SET_PACKET_LOSS_PERC_REQUEST as i32,
-                packet_loss_perc,
+                input.as_ptr(),
+                FRAME_SIZE as i32,
+                encoded.as_mut_ptr(),
+                encoded.len() as i32,
+            );
+            assert!(
+                encoded_len > 0,
+                "Float encoding failed with error {}",
+                encoded_len
             );
-            assert_eq!(ret, OPUS_OK as i32, "Failed to set packet loss");
-            println!("Packet loss set to {}%", packet_loss_perc);
 
-            // Load DNN weights into encoder
-            let ret = opus_encoder_ctl(
-                encoder,
-                OPUS_SET_DNN_BLOB_REQUEST as i32,
-                weights_data.as_ptr() as *const std::ffi::c_void,
-                weights_data.len() as i32,
+            let decoded_len = opus_decode_float(
+                decoder,
+                encoded.as_ptr(),
+                encoded_len,
+                decoded.as_mut_ptr(),
+                FRAME_SIZE as i32,
+                0,
             );
             assert_eq!(
-                ret, OPUS_OK as i32,
-                "Failed to load DNN weights into encoder"
+                decoded_len, FRAME_SIZE as i32,
+                "Float decoding failed or wrong frame size"
             );
 
-            // Enable DRED with 100ms of redundancy
-            let dred_duration_ms: i32 = 100;
-            let ret = opus_encoder_ctl(
-                encoder,
-                OPUS_SET_DRED_DURATION_REQUEST as i32,
-                dred_duration_ms,
-            );
-            assert_eq!(ret, OPUS_OK as i32, "Failed to enable DRED");
-            println!("DRED enabled with {}ms redundancy", dred_duration_ms);
+            opus_encoder_destroy(encoder);
+            opus_decoder_destroy(decoder);
+        }
+    }
 
-            // Create decoder
-            let decoder = opus_decoder_create(SAMPLE_RATE, CHANNELS, &mut error);
-            assert_eq!(error, OPUS_OK as i32, "Failed to create decoder");
+    /// Test 5.1 surround float encode/decode through the multistream API
+    #[test]
+    #[cfg(not(feature = "no-float-api"))]
+    fn test_multistream_encode_decode_float_surround() {
+        const SAMPLE_RATE: i32 = 48000;
+        const CHANNELS: usize = 6; // 5.1 in Vorbis channel order
+        const FRAME_SIZE: usize = 960; // 20ms at 48kHz
+        const NUM_FRAMES: usize = 10;
 
-            // Load DNN weights into decoder (may not be supported for all decoder types)
-            let ret = opus_decoder_ctl(
-                decoder,
-                OPUS_SET_DNN_BLOB_REQUEST as i32,
-                weights_data.as_ptr() as *const std::ffi::c_void,
-                weights_data.len() as i32,
+        unsafe {
+            let mut error: i32 = 0;
+            let mut streams: i32 = 0;
+            let mut coupled_streams: i32 = 0;
+            let mut mapping = [0u8; CHANNELS];
+
+            // Mapping family 1: Vorbis channel layouts
+            let encoder = opus_multistream_surround_encoder_create(
+                SAMPLE_RATE,
+                CHANNELS as i32,
+                1,
+                &mut streams,
+                &mut coupled_streams,
+                mapping.as_mut_ptr(),
+                OPUS_APPLICATION_AUDIO as i32,
+                &mut error,
             );
-            if ret == OPUS_OK as i32 {
-                println!("DNN weights loaded into decoder");
-            } else {
-                println!(
-                    "Note: Decoder DNN blob returned {} (OSCE may use different API)",
-                    ret
+            assert_eq!(error, OPUS_OK as i32, "Failed to create surround encoder");
+            // FL+FR and RL+RR coupled, C and LFE mono
+            assert_eq!((streams, coupled_streams), (4, 2));
+
+            let decoder = opus_multistream_decoder_create(
+                SAMPLE_RATE,
+                CHANNELS as i32,
+                streams,
+                coupled_streams,
+                mapping.as_ptr(),
+                &mut error,
+            );
+            assert_eq!(
+                error, OPUS_OK as i32,
+                "Failed to create multistream decoder"
+            );
+
+            // A different tone per channel, interleaved. The LFE channel
+            // (last in Vorbis order) is low-passed, so it gets a low tone.
+            let input: Vec<f32> = (0..FRAME_SIZE * NUM_FRAMES)
+                .flat_map(|i| {
+                    (0..CHANNELS).map(move |ch| {
+                        let freq = if ch == CHANNELS - 1 {
+                            60.0
+                        } else {
+                            220.0 * (ch + 1) as f32
+                        };
+                        let t = i as f32 / SAMPLE_RATE as f32;
+                        (t * freq * std::f32::consts::TAU).sin() * 0.25
+                    })
+                })
+                .collect();
+
+            let mut encoded = vec![0u8; max_packet_size(CHANNELS, FrameDuration::Ms20)];
+            let mut decoded = vec![0f32; FRAME_SIZE * CHANNELS];
+            let mut energy = [0f32; CHANNELS];
+
+            for (frame_num, frame) in input.chunks_exact(FRAME_SIZE * CHANNELS).enumerate() {
+                let encoded_len = opus_multistream_encode_float(
+                    encoder,
+                    frame.as_ptr(),
+                    FRAME_SIZE as i32,
+                    encoded.as_mut_ptr(),
+                    encoded.len() as i32,
+                );
+                assert!(
+                    encoded_len > 0,
+                    "Frame {} float encoding failed with error {}",
+                    frame_num,
+                    encoded_len
+                );
+
+                let decoded_len = opus_multistream_decode_float(
+                    decoder,
+                    encoded.as_ptr(),
+                    encoded_len,
+                    decoded.as_mut_ptr(),
+                    FRAME_SIZE as i32,
+                    0,
+                );
+                assert_eq!(
+                    decoded_len, FRAME_SIZE as i32,
+                    "Frame {} float decoding failed or wrong frame size",
+                    frame_num
                 );
+
+                for (i, sample) in decoded.iter().enumerate() {
+                    energy[i % CHANNELS] += sample * sample;
+                }
             }
 
-            let mut total_encoded_bytes = 0;
-            let mut frame_sizes = Vec::new();
+            // Every channel, LFE included, carries signal through the round trip
+            for (ch, e) in energy.iter().enumerate() {
+                println!("Channel {} energy: {:.2}", ch, e);
+                assert!(*e > 1.0, "Channel {} decoded to silence", ch);
+            }
 
-            for frame_num in 0..NUM_FRAMES {
-                // Generate mono noise input with frame-specific seed for reproducibility
-                let input = generate_noise_with_seed(FRAME_SIZE, frame_num as u64);
-                let mut encoded = vec![0u8; 4000];
-                let mut decoded = vec![0i16; FRAME_SIZE];
+            opus_multistream_encoder_destroy(encoder);
+            opus_multistream_decoder_destroy(decoder);
+        }
+    }
 
-                // Encode
+    /// Encode a fixed input and return the per-frame encoder final range values
+    fn encode_final_ranges(num_frames: usize) -> Vec<u32> {
+        const SAMPLE_RATE: i32 = 48000;
+        const CHANNELS: i32 = 1;
+        const FRAME_SIZE: usize = 960; // 20ms at 48kHz
+
+        let mut ranges = Vec::with_capacity(num_frames);
+
+        unsafe {
+            let mut error: i32 = 0;
+
+            let encoder = opus_encoder_create(
+                SAMPLE_RATE,
+                CHANNELS,
+                OPUS_APPLICATION_AUDIO as i32,
+                &mut error,
+            );
+            assert_eq!(error, OPUS_OK as i32, "Failed to create encoder");
+
+            let decoder = opus_decoder_create(SAMPLE_RATE, CHANNELS, &mut error);
+            assert_eq!(error, OPUS_OK as i32, "Failed to create decoder");
+
+            let mut encoded = vec![0u8; MAX_PACKET];
+            let mut decoded = vec![0i16; FRAME_SIZE];
+
+            for frame_num in 0..num_frames {
+                let input = noise::<i16>(frame_num as u64, FRAME_SIZE);
                 let encoded_len = opus_encode(
                     encoder,
                     input.as_ptr(),
@@ -756,10 +934,7 @@ mod tests {
                     encoded.len() as i32,
                 );
                 assert!(encoded_len > 0, "Frame {} encoding failed", frame_num);
-                total_encoded_bytes += encoded_len;
-                frame_sizes.push(encoded_len);
 
-                // Decode
                 let decoded_len = opus_decode(
                     decoder,
                     encoded.as_ptr(),
@@ -773,21 +948,853 @@ mod tests {
                     "Frame {} decoding failed",
                     frame_num
                 );
+
+                let mut range: u32 = 0;
+                let ret = opus_encoder_ctl(
+                    encoder,
+                    OPUS_GET_FINAL_RANGE_REQUEST as i32,
+                    &mut range as *mut u32,
+                );
+                assert_eq!(ret, OPUS_OK as i32, "OPUS_GET_FINAL_RANGE failed");
+                ranges.push(range);
             }
 
-            let duration_ms = NUM_FRAMES * 20;
-            let bitrate_actual = (total_encoded_bytes * 8 * 1000) / duration_ms as i32;
-            println!("\n=== WITH DNN MODEL + DRED ===");
-            println!("Frame sizes: {:?}", frame_sizes);
-            println!(
-                "Encoded {} frames ({} ms) to {} bytes, actual bitrate: {} bps",
-                NUM_FRAMES, duration_ms, total_encoded_bytes, bitrate_actual
+            opus_encoder_destroy(encoder);
+            opus_decoder_destroy(decoder);
+        }
+
+        ranges
+    }
+
+    /// Test that encoding the same input twice is bit-identical (final range matches)
+    #[test]
+    fn test_encode_deterministic_final_range() {
+        let first = encode_final_ranges(10);
+        let second = encode_final_ranges(10);
+        println!("Final ranges: {:08x?}", first);
+        assert_eq!(first, second, "Encoder output differs between runs");
+    }
+
+    /// Test that OPUS_GET_IN_DTX reports DTX once the encoder sees sustained silence
+    #[test]
+    fn test_in_dtx() {
+        const SAMPLE_RATE: i32 = 48000;
+        const CHANNELS: i32 = 1;
+        const FRAME_SIZE: usize = 960; // 20ms at 48kHz
+        const NUM_FRAMES: usize = 50; // 1s of silence
+
+        unsafe {
+            let mut error: i32 = 0;
+
+            let encoder = opus_encoder_create(
+                SAMPLE_RATE,
+                CHANNELS,
+                OPUS_APPLICATION_VOIP as i32,
+                &mut error,
+            );
+            assert_eq!(error, OPUS_OK as i32, "Failed to create encoder");
+
+            let ret = opus_encoder_ctl(encoder, OPUS_SET_DTX_REQUEST as i32, 1);
+            assert_eq!(ret, OPUS_OK as i32, "Failed to enable DTX");
+
+            let mut in_dtx: i32 = -1;
+            let ret = opus_encoder_ctl(
+                encoder,
+                OPUS_GET_IN_DTX_REQUEST as i32,
+                &mut in_dtx as *mut i32,
+            );
+            assert_eq!(ret, OPUS_OK as i32, "OPUS_GET_IN_DTX failed");
+            assert_eq!(in_dtx, 0, "Fresh encoder should not be in DTX");
+
+            let silence = vec![0i16; FRAME_SIZE];
+            let mut encoded = vec![0u8; MAX_PACKET];
+
+            for frame_num in 0..NUM_FRAMES {
+                let encoded_len = opus_encode(
+                    encoder,
+                    silence.as_ptr(),
+                    FRAME_SIZE as i32,
+                    encoded.as_mut_ptr(),
+                    encoded.len() as i32,
+                );
+                assert!(encoded_len > 0, "Frame {} encoding failed", frame_num);
+            }
+
+            let ret = opus_encoder_ctl(
+                encoder,
+                OPUS_GET_IN_DTX_REQUEST as i32,
+                &mut in_dtx as *mut i32,
             );
+            assert_eq!(ret, OPUS_OK as i32, "OPUS_GET_IN_DTX failed");
+            assert_eq!(in_dtx, 1, "Encoder should be in DTX after 1s of silence");
 
             opus_encoder_destroy(encoder);
-            opus_decoder_destroy(decoder);
         }
+    }
 
-        println!("Multi-frame encode/decode test WITH DNN + DRED passed!");
+    #[test]
+    fn test_vbr_constraint_and_max_bandwidth() {
+        const SAMPLE_RATE: i32 = 48000;
+        const CHANNELS: i32 = 1;
+        const FRAME_SIZE: usize = 960; // 20ms at 48kHz
+        const NUM_FRAMES: usize = 10;
+
+        unsafe {
+            let mut error: i32 = 0;
+
+            let encoder = opus_encoder_create(
+                SAMPLE_RATE,
+                CHANNELS,
+                OPUS_APPLICATION_AUDIO as i32,
+                &mut error,
+            );
+            assert_eq!(error, OPUS_OK as i32, "Failed to create encoder");
+
+            let mut value: i32 = -1;
+
+            // Constrained VBR: boolean, anything else is rejected
+            for enabled in [1, 0] {
+                let ret =
+                    opus_encoder_ctl(encoder, OPUS_SET_VBR_CONSTRAINT_REQUEST as i32, enabled);
+                assert_eq!(ret, OPUS_OK as i32, "OPUS_SET_VBR_CONSTRAINT failed");
+
+                let ret = opus_encoder_ctl(
+                    encoder,
+                    OPUS_GET_VBR_CONSTRAINT_REQUEST as i32,
+                    &mut value as *mut i32,
+                );
+                assert_eq!(ret, OPUS_OK as i32, "OPUS_GET_VBR_CONSTRAINT failed");
+                assert_eq!(value, enabled);
+            }
+            let ret = opus_encoder_ctl(encoder, OPUS_SET_VBR_CONSTRAINT_REQUEST as i32, 2);
+            assert_eq!(ret, OPUS_BAD_ARG, "VBR constraint must be 0 or 1");
+
+            // Max bandwidth only accepts concrete bandwidths, not OPUS_AUTO
+            let ret = opus_encoder_ctl(encoder, OPUS_SET_MAX_BANDWIDTH_REQUEST as i32, OPUS_AUTO);
+            assert_eq!(ret, OPUS_BAD_ARG, "OPUS_AUTO is not a valid max bandwidth");
+
+            let ret = opus_encoder_ctl(
+                encoder,
+                OPUS_SET_MAX_BANDWIDTH_REQUEST as i32,
+                OPUS_BANDWIDTH_WIDEBAND as i32,
+            );
+            assert_eq!(ret, OPUS_OK as i32, "OPUS_SET_MAX_BANDWIDTH failed");
+
+            let ret = opus_encoder_ctl(
+                encoder,
+                OPUS_GET_MAX_BANDWIDTH_REQUEST as i32,
+                &mut value as *mut i32,
+            );
+            assert_eq!(ret, OPUS_OK as i32, "OPUS_GET_MAX_BANDWIDTH failed");
+            assert_eq!(value, OPUS_BANDWIDTH_WIDEBAND as i32);
+
+            // Even at a generous bitrate the packets must respect the cap
+            opus_encoder_ctl(encoder, OPUS_SET_BITRATE_REQUEST as i32, 64000);
+
+            let mut encoded = vec![0u8; MAX_PACKET];
+            for frame_num in 0..NUM_FRAMES {
+                let input = noise::<i16>(frame_num as u64, FRAME_SIZE);
+                let encoded_len = opus_encode(
+                    encoder,
+                    input.as_ptr(),
+                    FRAME_SIZE as i32,
+                    encoded.as_mut_ptr(),
+                    encoded.len() as i32,
+                );
+                assert!(encoded_len > 0, "Frame {} encoding failed", frame_num);
+
+                let bandwidth = opus_packet_get_bandwidth(encoded.as_ptr());
+                assert!(
+                    bandwidth <= OPUS_BANDWIDTH_WIDEBAND as i32,
+                    "Frame {} has bandwidth {} above the wideband cap",
+                    frame_num,
+                    bandwidth
+                );
+            }
+
+            opus_encoder_destroy(encoder);
+        }
+    }
+
+    #[test]
+    fn test_force_channels() {
+        const SAMPLE_RATE: i32 = 48000;
+        const CHANNELS: i32 = 2;
+        const FRAME_SIZE: usize = 960; // 20ms at 48kHz
+        const NUM_FRAMES: usize = 10;
+
+        unsafe {
+            let mut error: i32 = 0;
+
+            let encoder = opus_encoder_create(
+                SAMPLE_RATE,
+                CHANNELS,
+                OPUS_APPLICATION_VOIP as i32,
+                &mut error,
+            );
+            assert_eq!(error, OPUS_OK as i32, "Failed to create encoder");
+
+            let mut value: i32 = -1;
+
+            // OPUS_AUTO, 1 and 2 are the only accepted values
+            for channels in [OPUS_AUTO, 2, 1] {
+                let ret =
+                    opus_encoder_ctl(encoder, OPUS_SET_FORCE_CHANNELS_REQUEST as i32, channels);
+                assert_eq!(ret, OPUS_OK as i32, "OPUS_SET_FORCE_CHANNELS failed");
+
+                let ret = opus_encoder_ctl(
+                    encoder,
+                    OPUS_GET_FORCE_CHANNELS_REQUEST as i32,
+                    &mut value as *mut i32,
+                );
+                assert_eq!(ret, OPUS_OK as i32, "OPUS_GET_FORCE_CHANNELS failed");
+                assert_eq!(value, channels);
+            }
+            for invalid in [0, 3] {
+                let ret =
+                    opus_encoder_ctl(encoder, OPUS_SET_FORCE_CHANNELS_REQUEST as i32, invalid);
+                assert_eq!(
+                    ret, OPUS_BAD_ARG,
+                    "{} forced channels must be rejected",
+                    invalid
+                );
+            }
+
+            // Uncorrelated noise in each channel would normally be coded as
+            // stereo; forced mono must still produce mono packets
+            let mut encoded = vec![0u8; MAX_PACKET];
+            for frame_num in 0..NUM_FRAMES {
+                let input = noise::<i16>(frame_num as u64, FRAME_SIZE * CHANNELS as usize);
+                let encoded_len = opus_encode(
+                    encoder,
+                    input.as_ptr(),
+                    FRAME_SIZE as i32,
+                    encoded.as_mut_ptr(),
+                    encoded.len() as i32,
+                );
+                assert!(encoded_len > 0, "Frame {} encoding failed", frame_num);
+
+                let channels = opus_packet_get_nb_channels(encoded.as_ptr());
+                assert_eq!(channels, 1, "Frame {} is not mono", frame_num);
+            }
+
+            opus_encoder_destroy(encoder);
+        }
+    }
+
+    /// CELT intensity stereo may invert the phase of one channel, which
+    /// cancels out when the decoded stereo is later downmixed to mono.
+    /// OPUS_SET_PHASE_INVERSION_DISABLED turns that off on either side.
+    #[test]
+    fn test_phase_inversion_disabled() {
+        const SAMPLE_RATE: i32 = 48000;
+        const CHANNELS: i32 = 2;
+        const FRAME_SIZE: usize = 960; // 20ms at 48kHz
+        const NUM_FRAMES: usize = 10;
+
+        unsafe {
+            let mut error: i32 = 0;
+
+            let encoder = opus_encoder_create(
+                SAMPLE_RATE,
+                CHANNELS,
+                OPUS_APPLICATION_AUDIO as i32,
+                &mut error,
+            );
+            assert_eq!(error, OPUS_OK as i32, "Failed to create encoder");
+
+            let decoder = opus_decoder_create(SAMPLE_RATE, CHANNELS, &mut error);
+            assert_eq!(error, OPUS_OK as i32, "Failed to create decoder");
+
+            let mut value: i32 = -1;
+
+            // Allowed by default
+            let ret = opus_encoder_ctl(
+                encoder,
+                OPUS_GET_PHASE_INVERSION_DISABLED_REQUEST as i32,
+                &mut value as *mut i32,
+            );
+            assert_eq!(
+                ret, OPUS_OK as i32,
+                "OPUS_GET_PHASE_INVERSION_DISABLED failed"
+            );
+            assert_eq!(value, 0);
+
+            for disabled in [1, 0, 1] {
+                let ret = opus_encoder_ctl(
+                    encoder,
+                    OPUS_SET_PHASE_INVERSION_DISABLED_REQUEST as i32,
+                    disabled,
+                );
+                assert_eq!(
+                    ret, OPUS_OK as i32,
+                    "OPUS_SET_PHASE_INVERSION_DISABLED failed"
+                );
+
+                let ret = opus_encoder_ctl(
+                    encoder,
+                    OPUS_GET_PHASE_INVERSION_DISABLED_REQUEST as i32,
+                    &mut value as *mut i32,
+                );
+                assert_eq!(
+                    ret, OPUS_OK as i32,
+                    "OPUS_GET_PHASE_INVERSION_DISABLED failed"
+                );
+                assert_eq!(value, disabled);
+
+                let ret = opus_decoder_ctl(
+                    decoder,
+                    OPUS_SET_PHASE_INVERSION_DISABLED_REQUEST as i32,
+                    disabled,
+                );
+                assert_eq!(
+                    ret, OPUS_OK as i32,
+                    "Decoder OPUS_SET_PHASE_INVERSION_DISABLED failed"
+                );
+
+                let ret = opus_decoder_ctl(
+                    decoder,
+                    OPUS_GET_PHASE_INVERSION_DISABLED_REQUEST as i32,
+                    &mut value as *mut i32,
+                );
+                assert_eq!(
+                    ret, OPUS_OK as i32,
+                    "Decoder OPUS_GET_PHASE_INVERSION_DISABLED failed"
+                );
+                assert_eq!(value, disabled);
+            }
+            for invalid in [-1, 2] {
+                let ret = opus_encoder_ctl(
+                    encoder,
+                    OPUS_SET_PHASE_INVERSION_DISABLED_REQUEST as i32,
+                    invalid,
+                );
+                assert_eq!(ret, OPUS_BAD_ARG, "{} must be rejected", invalid);
+                let ret = opus_decoder_ctl(
+                    decoder,
+                    OPUS_SET_PHASE_INVERSION_DISABLED_REQUEST as i32,
+                    invalid,
+                );
+                assert_eq!(
+                    ret, OPUS_BAD_ARG,
+                    "{} must be rejected by the decoder",
+                    invalid
+                );
+            }
+
+            // Low bitrate stereo, where intensity stereo is in use
+            let ret = opus_encoder_ctl(encoder, OPUS_SET_BITRATE_REQUEST as i32, 24000);
+            assert_eq!(ret, OPUS_OK as i32, "OPUS_SET_BITRATE failed");
+
+            let mut encoded = vec![0u8; MAX_PACKET];
+            let mut decoded = vec![0i16; FRAME_SIZE * CHANNELS as usize];
+            for frame_num in 0..NUM_FRAMES {
+                let input = noise::<i16>(frame_num as u64, FRAME_SIZE * CHANNELS as usize);
+                let encoded_len = opus_encode(
+                    encoder,
+                    input.as_ptr(),
+                    FRAME_SIZE as i32,
+                    encoded.as_mut_ptr(),
+                    encoded.len() as i32,
+                );
+                assert!(encoded_len > 0, "Frame {} encoding failed", frame_num);
+
+                let decoded_len = opus_decode(
+                    decoder,
+                    encoded.as_ptr(),
+                    encoded_len,
+                    decoded.as_mut_ptr(),
+                    FRAME_SIZE as i32,
+                    0,
+                );
+                assert_eq!(
+                    decoded_len, FRAME_SIZE as i32,
+                    "Frame {} decoding failed",
+                    frame_num
+                );
+            }
+
+            opus_decoder_destroy(decoder);
+            opus_encoder_destroy(encoder);
+        }
+    }
+    #[test]
+    fn test_get_sample_rate() {
+        unsafe {
+            let mut error: i32 = 0;
+
+            for sample_rate in [8000, 12000, 16000, 24000, 48000] {
+                let encoder =
+                    opus_encoder_create(sample_rate, 1, OPUS_APPLICATION_VOIP as i32, &mut error);
+                assert_eq!(error, OPUS_OK as i32, "Failed to create encoder");
+                let decoder = opus_decoder_create(sample_rate, 1, &mut error);
+                assert_eq!(error, OPUS_OK as i32, "Failed to create decoder");
+
+                let mut value: i32 = 0;
+                let ret = opus_encoder_ctl(
+                    encoder,
+                    OPUS_GET_SAMPLE_RATE_REQUEST as i32,
+                    &mut value as *mut i32,
+                );
+                assert_eq!(ret, OPUS_OK as i32, "OPUS_GET_SAMPLE_RATE failed");
+                assert_eq!(value, sample_rate);
+
+                let mut decoder_rate: i32 = 0;
+                let ret = opus_decoder_ctl(
+                    decoder,
+                    OPUS_GET_SAMPLE_RATE_REQUEST as i32,
+                    &mut decoder_rate as *mut i32,
+                );
+                assert_eq!(ret, OPUS_OK as i32, "OPUS_GET_SAMPLE_RATE failed");
+                assert_eq!(decoder_rate, sample_rate);
+
+                // Frame sizes follow from the queried rate alone
+                let frame_size = (decoder_rate / 50) as usize; // 20ms
+                let input = noise::<i16>(1, frame_size);
+                let mut encoded = vec![0u8; MAX_PACKET];
+                let encoded_len = opus_encode(
+                    encoder,
+                    input.as_ptr(),
+                    frame_size as i32,
+                    encoded.as_mut_ptr(),
+                    encoded.len() as i32,
+                );
+                assert!(encoded_len > 0, "Encoding failed at {} Hz", sample_rate);
+
+                let mut decoded = vec![0i16; frame_size];
+                let decoded_samples = opus_decode(
+                    decoder,
+                    encoded.as_ptr(),
+                    encoded_len,
+                    decoded.as_mut_ptr(),
+                    frame_size as i32,
+                    0,
+                );
+                assert_eq!(decoded_samples, frame_size as i32);
+
+                opus_decoder_destroy(decoder);
+                opus_encoder_destroy(encoder);
+            }
+        }
+    }
+
+    /// Packet loss percentage and prediction only affect the SILK path: the
+    /// loss estimate sets how much LBRR (in-band FEC) and how conservative
+    /// the long-term prediction gains are, and disabling prediction makes
+    /// every frame independently decodable at some bitrate cost.
+    #[test]
+    fn test_silk_controls() {
+        const SAMPLE_RATE: i32 = 16000;
+        const CHANNELS: i32 = 1;
+        const FRAME_SIZE: usize = 320; // 20ms at 16kHz
+        const NUM_FRAMES: usize = 10;
+
+        unsafe {
+            let mut error: i32 = 0;
+
+            let encoder = opus_encoder_create(
+                SAMPLE_RATE,
+                CHANNELS,
+                OPUS_APPLICATION_VOIP as i32,
+                &mut error,
+            );
+            assert_eq!(error, OPUS_OK as i32, "Failed to create encoder");
+
+            let mut value: i32 = -1;
+
+            // Packet loss percentage: 0..=100
+            for perc in [0, 25, 100] {
+                let ret = opus_encoder_ctl(encoder, OPUS_SET_PACKET_LOSS_PERC_REQUEST as i32, perc);
+                assert_eq!(ret, OPUS_OK as i32, "OPUS_SET_PACKET_LOSS_PERC failed");
+
+                let ret = opus_encoder_ctl(
+                    encoder,
+                    OPUS_GET_PACKET_LOSS_PERC_REQUEST as i32,
+                    &mut value as *mut i32,
+                );
+                assert_eq!(ret, OPUS_OK as i32, "OPUS_GET_PACKET_LOSS_PERC failed");
+                assert_eq!(value, perc);
+            }
+            for invalid in [-1, 101] {
+                let ret =
+                    opus_encoder_ctl(encoder, OPUS_SET_PACKET_LOSS_PERC_REQUEST as i32, invalid);
+                assert_eq!(
+                    ret, OPUS_BAD_ARG,
+                    "{}% packet loss must be rejected",
+                    invalid
+                );
+            }
+
+            // Prediction disabled: boolean
+            for disabled in [1, 0] {
+                let ret = opus_encoder_ctl(
+                    encoder,
+                    OPUS_SET_PREDICTION_DISABLED_REQUEST as i32,
+                    disabled,
+                );
+                assert_eq!(ret, OPUS_OK as i32, "OPUS_SET_PREDICTION_DISABLED failed");
+
+                let ret = opus_encoder_ctl(
+                    encoder,
+                    OPUS_GET_PREDICTION_DISABLED_REQUEST as i32,
+                    &mut value as *mut i32,
+                );
+                assert_eq!(ret, OPUS_OK as i32, "OPUS_GET_PREDICTION_DISABLED failed");
+                assert_eq!(value, disabled);
+            }
+            let ret = opus_encoder_ctl(encoder, OPUS_SET_PREDICTION_DISABLED_REQUEST as i32, 2);
+            assert_eq!(ret, OPUS_BAD_ARG, "Prediction disabled must be 0 or 1");
+
+            // Low-bitrate wideband VoIP stays on SILK with both knobs set
+            opus_encoder_ctl(encoder, OPUS_SET_BITRATE_REQUEST as i32, 12000);
+            opus_encoder_ctl(encoder, OPUS_SET_PACKET_LOSS_PERC_REQUEST as i32, 20);
+            opus_encoder_ctl(encoder, OPUS_SET_PREDICTION_DISABLED_REQUEST as i32, 1);
+
+            let mut encoded = vec![0u8; MAX_PACKET];
+            for frame_num in 0..NUM_FRAMES {
+                let input = noise::<i16>(frame_num as u64, FRAME_SIZE);
+                let encoded_len = opus_encode(
+                    encoder,
+                    input.as_ptr(),
+                    FRAME_SIZE as i32,
+                    encoded.as_mut_ptr(),
+                    encoded.len() as i32,
+                );
+                assert!(encoded_len > 0, "Frame {} encoding failed", frame_num);
+
+                let toc = packet::parse_toc(encoded[0]);
+                assert_eq!(
+                    toc.config.mode(),
+                    packet::Mode::SilkOnly,
+                    "Frame {} is not SILK-only",
+                    frame_num
+                );
+            }
+
+            opus_encoder_destroy(encoder);
+        }
+    }
+
+    /// Test Opus Custom encode/decode with a non-standard frame size
+    #[test]
+    #[cfg(feature = "custom-modes")]
+    fn test_custom_mode_encode_decode() {
+        const SAMPLE_RATE: i32 = 48000;
+        const CHANNELS: i32 = 1;
+        const FRAME_SIZE: usize = 256; // Not a regular Opus frame size
+
+        unsafe {
+            let mut error: i32 = 0;
+
+            let mode = opus_custom_mode_create(SAMPLE_RATE, FRAME_SIZE as i32, &mut error);
+            assert_eq!(error, OPUS_OK as i32, "Failed to create custom mode");
+            assert!(!mode.is_null());
+
+            let encoder = opus_custom_encoder_create(mode, CHANNELS, &mut error);
+            assert_eq!(error, OPUS_OK as i32, "Failed to create custom encoder");
+
+            let decoder = opus_custom_decoder_create(mode, CHANNELS, &mut error);
+            assert_eq!(error, OPUS_OK as i32, "Failed to create custom decoder");
+
+            let input = noise::<i16>(1, FRAME_SIZE);
+            let mut encoded = vec![0u8; 1275];
+            let mut decoded = vec![0i16; FRAME_SIZE];
+
+            let encoded_len = opus_custom_encode(
+                encoder,
+                input.as_ptr(),
+                FRAME_SIZE as i32,
+                encoded.as_mut_ptr(),
+                encoded.len() as i32,
+            );
+            assert!(
+                encoded_len > 0,
+                "Custom encoding failed with error {}",
+                encoded_len
+            );
+
+            let decoded_len = opus_custom_decode(
+                decoder,
+                encoded.as_ptr(),
+                encoded_len,
+                decoded.as_mut_ptr(),
+                FRAME_SIZE as i32,
+            );
+            assert_eq!(
+                decoded_len, FRAME_SIZE as i32,
+                "Custom decoding failed or wrong frame size"
+            );
+
+            opus_custom_encoder_destroy(encoder);
+            opus_custom_decoder_destroy(decoder);
+            opus_custom_mode_destroy(mode);
+        }
+    }
+
+    /// Test multiple frames encode/decode WITH DNN
+    #[test]
+    #[cfg(all(feature = "dred", not(feature = "no-float-api")))]
+    fn test_multi_frame_encode_decode_with_dnn() {
+        const SAMPLE_RATE: i32 = 48000;
+        const CHANNELS: i32 = 1; // Mono - DRED works better with mono voice
+        const FRAME_SIZE: usize = 960; // 20ms at 48kHz
+        const NUM_FRAMES: usize = 10;
+        const BITRATE: i32 = 32000; // 32kbit/s - typical for voice, forces SILK mode
+
+        // Load DNN weights
+        let weights_data = match crate::test_support::load_dnn_weights() {
+            Some(data) => data,
+            None => {
+                panic!(
+                    "DNN weights not found. Run 'python generate_weights.py' to generate weights."
+                );
+            }
+        };
+
+        unsafe {
+            let mut error: i32 = 0;
+
+            // Create encoder (VOIP mode for voice)
+            let encoder = opus_encoder_create(
+                SAMPLE_RATE,
+                CHANNELS,
+                OPUS_APPLICATION_VOIP as i32,
+                &mut error,
+            );
+            assert_eq!(error, OPUS_OK as i32, "Failed to create encoder");
+
+            // Set bitrate
+            opus_encoder_ctl(encoder, OPUS_SET_BITRATE_REQUEST as i32, BITRATE);
+
+            // Set expected packet loss (needed for DRED to add redundancy)
+            let packet_loss_perc: i32 = 25; // 25% expected packet loss
+            let ret = opus_encoder_ctl(
+                encoder,
+                OPUS_SET_PACKET_LOSS_PERC_REQUEST as i32,
+                packet_loss_perc,
+            );
+            assert_eq!(ret, OPUS_OK as i32, "Failed to set packet loss");
+            println!("Packet loss set to {}%", packet_loss_perc);
+
+            // Load DNN weights into encoder
+            let ret = opus_encoder_ctl(
+                encoder,
+                OPUS_SET_DNN_BLOB_REQUEST as i32,
+                weights_data.as_ptr() as *const std::ffi::c_void,
+                weights_data.len() as i32,
+            );
+            assert_eq!(
+                ret, OPUS_OK as i32,
+                "Failed to load DNN weights into encoder"
+            );
+
+            // Enable DRED with 100ms of redundancy
+            let dred_duration_ms: i32 = 100;
+            let ret = opus_encoder_ctl(
+                encoder,
+                OPUS_SET_DRED_DURATION_REQUEST as i32,
+                dred_duration_ms,
+            );
+            assert_eq!(ret, OPUS_OK as i32, "Failed to enable DRED");
+            println!("DRED enabled with {}ms redundancy", dred_duration_ms);
+
+            // Create decoder
+            let decoder = opus_decoder_create(SAMPLE_RATE, CHANNELS, &mut error);
+            assert_eq!(error, OPUS_OK as i32, "Failed to create decoder");
+
+            // Load DNN weights into decoder (may not be supported for all decoder types)
+            let ret = opus_decoder_ctl(
+                decoder,
+                OPUS_SET_DNN_BLOB_REQUEST as i32,
+                weights_data.as_ptr() as *const std::ffi::c_void,
+                weights_data.len() as i32,
+            );
+            if ret == OPUS_OK as i32 {
+                println!("DNN weights loaded into decoder");
+            } else {
+                println!(
+                    "Note: Decoder DNN blob returned {} (OSCE may use different API)",
+                    ret
+                );
+            }
+
+            let mut total_encoded_bytes = 0;
+            let mut frame_sizes = Vec::new();
+            let mut encode_time = std::time::Duration::ZERO;
+
+            for frame_num in 0..NUM_FRAMES {
+                // Generate mono noise input with frame-specific seed for reproducibility
+                let input = noise::<i16>(frame_num as u64, FRAME_SIZE);
+                let mut encoded = vec![0u8; MAX_PACKET];
+                let mut decoded = vec![0i16; FRAME_SIZE];
+
+                // Encode (timed: DRED inference dominates, so this shows which
+                // DNN kernels are in use, e.g. with presume-dotprod)
+                let start = std::time::Instant::now();
+                let encoded_len = opus_encode(
+                    encoder,
+                    input.as_ptr(),
+                    FRAME_SIZE as i32,
+                    encoded.as_mut_ptr(),
+                    encoded.len() as i32,
+                );
+                encode_time += start.elapsed();
+                assert!(encoded_len > 0, "Frame {} encoding failed", frame_num);
+                total_encoded_bytes += encoded_len;
+                frame_sizes.push(encoded_len);
+
+                // Decode
+                let decoded_len = opus_decode(
+                    decoder,
+                    encoded.as_ptr(),
+                    encoded_len,
+                    decoded.as_mut_ptr(),
+                    FRAME_SIZE as i32,
+                    0,
+                );
+                assert_eq!(
+                    decoded_len, FRAME_SIZE as i32,
+                    "Frame {} decoding failed",
+                    frame_num
+                );
+            }
+
+            let duration_ms = NUM_FRAMES * 20;
+            let bitrate_actual = (total_encoded_bytes * 8 * 1000) / duration_ms as i32;
+            println!("\n=== WITH DNN MODEL + DRED ===");
+            println!("Frame sizes: {:?}", frame_sizes);
+            println!(
+                "Encoded {} frames ({} ms) to {} bytes, actual bitrate: {} bps",
+                NUM_FRAMES, duration_ms, total_encoded_bytes, bitrate_actual
+            );
+            println!(
+                "Encode time per frame: {:?}",
+                encode_time / NUM_FRAMES as u32
+            );
+
+            opus_encoder_destroy(encoder);
+            opus_decoder_destroy(decoder);
+        }
+
+        println!("Multi-frame encode/decode test WITH DNN + DRED passed!");
+    }
+
+    /// Invalid arguments are checked up front and never reach an assertion
+    unsafe fn check_invalid_args() {
+        let mut error: i32 = 0;
+        let encoder = opus_encoder_create(48000, 1, OPUS_APPLICATION_AUDIO as i32, &mut error);
+        assert_eq!(error, OPUS_OK as i32, "Failed to create encoder");
+        let decoder = opus_decoder_create(48000, 1, &mut error);
+        assert_eq!(error, OPUS_OK as i32, "Failed to create decoder");
+
+        let pcm = vec![0i16; 960];
+        let mut packet = vec![0u8; 1275];
+        let len = opus_encode(encoder, pcm.as_ptr(), 960, packet.as_mut_ptr(), 1275);
+        assert!(len > 0, "Encoding failed: {}", len);
+        assert_eq!(
+            opus_encode(encoder, pcm.as_ptr(), 0, packet.as_mut_ptr(), 1275),
+            OPUS_BAD_ARG
+        );
+
+        let mut output = vec![0i16; 960];
+        assert_eq!(
+            opus_decode(decoder, packet.as_ptr(), len, output.as_mut_ptr(), 0, 0),
+            OPUS_BAD_ARG
+        );
+        assert_eq!(
+            opus_decode(decoder, packet.as_ptr(), -1, output.as_mut_ptr(), 960, 0),
+            OPUS_BAD_ARG
+        );
+        // Still usable afterwards
+        assert_eq!(
+            opus_decode(decoder, packet.as_ptr(), len, output.as_mut_ptr(), 960, 0),
+            960
+        );
+
+        opus_encoder_destroy(encoder);
+        opus_decoder_destroy(decoder);
+    }
+
+    #[test]
+    fn test_invalid_args_return_errors() {
+        unsafe { check_invalid_args() };
+    }
+
+    /// The same with Opus' assertions compiled in, plus garbage packets
+    /// through the decoder, which must come back as errors or audio rather
+    /// than abort on an internal check:
+    ///
+    /// `cargo test --features assertions --lib -- --ignored test_assertions_build`
+    #[test]
+    #[ignore = "needs the assertions feature"]
+    fn test_assertions_build() {
+        assert!(
+            build_info().assertions,
+            "build with --features assertions or OPUS_SYS_ASSERTIONS=1"
+        );
+        unsafe {
+            check_invalid_args();
+
+            let mut error: i32 = 0;
+            let decoder = opus_decoder_create(48000, 2, &mut error);
+            assert_eq!(error, OPUS_OK as i32, "Failed to create decoder");
+            let mut output = vec![0i16; 5760 * 2];
+            let mut state = 0x9e37_79b9_7f4a_7c15u64;
+            for _ in 0..2000 {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                let len = 1 + (state % 400) as usize;
+                let packet: Vec<u8> = (0..len).map(|i| (state >> (i % 8 * 8)) as u8).collect();
+                let ret = opus_decode(
+                    decoder,
+                    packet.as_ptr(),
+                    len as i32,
+                    output.as_mut_ptr(),
+                    5760,
+                    0,
+                );
+                assert!(
+                    ret >= 0 || ret == OPUS_INVALID_PACKET,
+                    "opus_decode: {}",
+                    ret
+                );
+            }
+            opus_decoder_destroy(decoder);
+        }
+    }
+
+    /// Decodes a 20 ms frame into a buffer that only holds 10 ms, which the C
+    /// code can't check. Only built when the C code has AddressSanitizer and
+    /// ignored by default because the report aborts the test binary. Run it
+    /// alone to confirm the instrumentation reaches libopus; it must fail
+    /// with a heap-buffer-overflow in opus_decode:
+    ///
+    /// `RUSTFLAGS=-Zsanitizer=address cargo +nightly test --target x86_64-unknown-linux-gnu --lib -- --ignored test_asan_reports_c_overflow`
+    #[cfg(opus_sanitize_address)]
+    #[test]
+    #[ignore = "aborts under AddressSanitizer by design"]
+    fn test_asan_reports_c_overflow() {
+        const FRAME_SIZE: usize = 960; // 20ms at 48kHz
+
+        unsafe {
+            let mut error: i32 = 0;
+            let decoder = opus_decoder_create(48000, 1, &mut error);
+            assert_eq!(error, OPUS_OK as i32, "Failed to create decoder");
+
+            let mut output = vec![0i16; FRAME_SIZE / 2];
+            // Packet loss concealment still writes frame_size samples
+            opus_decode(
+                decoder,
+                std::ptr::null(),
+                0,
+                output.as_mut_ptr(),
+                FRAME_SIZE as i32,
+                0,
+            );
+
+            opus_decoder_destroy(decoder);
+        }
+        panic!("AddressSanitizer did not report the overflow in opus_decode");
     }
 }
@@ -0,0 +1,219 @@
+//! Deterministic packet loss for FEC and DRED tests
+//!
+//! [`LossSimulator`] drops packets from a stream with a seeded pseudo-random
+//! generator, so recovery tests see the same loss pattern on every run:
+//!
+//! ```
+//! use opus_head_sys::loss::LossSimulator;
+//!
+//! let packets: Vec<Vec<u8>> = (0..50).map(|i| vec![i as u8]).collect();
+//! let mut loss = LossSimulator::new(0.1, 2, 42).unwrap();
+//! for packet in loss.simulate(packets.iter().map(Vec::as_slice)) {
+//!     match packet {
+//!         Some(_data) => { /* opus_decode(decoder, data, ...) */ }
+//!         None => { /* conceal, or decode the next packet with decode_fec = 1 */ }
+//!     }
+//! }
+//! ```
+//!
+//! Losses come in bursts: each packet starts a loss event with the given
+//! probability, and an event drops `burst` packets in a row. With `burst`
+//! of 1 every packet is lost independently.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+/// Seeded packet loss generator, see the [module docs](self)
+#[derive(Debug, Clone)]
+pub struct LossSimulator {
+    probability: f64,
+    burst: usize,
+    seed: u64,
+    /// Packets decided so far
+    index: u64,
+    /// Packets still to drop in the current burst
+    remaining: usize,
+}
+
+impl LossSimulator {
+    /// Start a loss event with `probability` per packet, each dropping
+    /// `burst` consecutive packets. The same `seed` gives the same pattern.
+    ///
+    /// Returns `None` unless `probability` is within `0.0..=1.0` and
+    /// `burst` is at least 1.
+    pub fn new(probability: f64, burst: usize, seed: u64) -> Option<Self> {
+        ((0.0..=1.0).contains(&probability) && burst > 0).then_some(LossSimulator {
+            probability,
+            burst,
+            seed,
+            index: 0,
+            remaining: 0,
+        })
+    }
+
+    /// Decide whether the next packet is lost
+    pub fn next_lost(&mut self) -> bool {
+        let index = self.index;
+        self.index += 1;
+
+        if self.remaining > 0 {
+            self.remaining -= 1;
+            return true;
+        }
+        if self.uniform(index) < self.probability {
+            self.remaining = self.burst - 1;
+            return true;
+        }
+        false
+    }
+
+    /// Pass `packets` through, yielding `None` for each lost one
+    pub fn simulate<'a, I>(&'a mut self, packets: I) -> impl Iterator<Item = Option<&'a [u8]>> + 'a
+    where
+        I: IntoIterator<Item = &'a [u8]>,
+        I::IntoIter: 'a,
+    {
+        packets
+            .into_iter()
+            .map(move |packet| (!self.next_lost()).then_some(packet))
+    }
+
+    /// Uniform value in `0.0..1.0` for packet `index`
+    fn uniform(&self, index: u64) -> f64 {
+        let mut hasher = DefaultHasher::new();
+        self.seed.hash(&mut hasher);
+        index.hash(&mut hasher);
+        // The top 53 bits fill an f64 mantissa exactly
+        (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bindings::*;
+
+    fn pattern(probability: f64, burst: usize, seed: u64, packets: usize) -> Vec<bool> {
+        let mut loss = LossSimulator::new(probability, burst, seed).unwrap();
+        (0..packets).map(|_| loss.next_lost()).collect()
+    }
+
+    #[test]
+    fn test_new() {
+        assert!(LossSimulator::new(0.0, 1, 0).is_some());
+        assert!(LossSimulator::new(1.0, 5, 0).is_some());
+        assert!(LossSimulator::new(-0.1, 1, 0).is_none());
+        assert!(LossSimulator::new(1.5, 1, 0).is_none());
+        assert!(LossSimulator::new(f64::NAN, 1, 0).is_none());
+        assert!(LossSimulator::new(0.5, 0, 0).is_none());
+    }
+
+    #[test]
+    fn test_seeded_pattern() {
+        assert_eq!(pattern(0.2, 1, 7, 500), pattern(0.2, 1, 7, 500));
+        assert_ne!(pattern(0.2, 1, 7, 500), pattern(0.2, 1, 8, 500));
+
+        assert!(pattern(0.0, 3, 1, 200).iter().all(|&lost| !lost));
+        assert!(pattern(1.0, 3, 1, 200).iter().all(|&lost| lost));
+
+        let lost = pattern(0.2, 1, 7, 10_000).iter().filter(|&&l| l).count();
+        assert!((1700..2300).contains(&lost), "lost {} of 10000", lost);
+    }
+
+    #[test]
+    fn test_bursts() {
+        const BURST: usize = 4;
+        let lost = pattern(0.05, BURST, 3, 2000);
+
+        // Every run of losses is at least one burst long, unless cut off
+        // by the end of the stream
+        let mut runs = Vec::new();
+        let mut run = 0;
+        for &l in &lost {
+            if l {
+                run += 1;
+            } else if run > 0 {
+                runs.push(run);
+                run = 0;
+            }
+        }
+        assert!(!runs.is_empty());
+        assert!(runs.iter().all(|&run| run >= BURST), "{:?}", runs);
+    }
+
+    #[test]
+    fn test_fec_recovery() {
+        const SAMPLE_RATE: i32 = 16000;
+        const FRAME_SIZE: usize = 320;
+        const FRAMES: usize = 100;
+
+        unsafe {
+            let mut error: i32 = 0;
+            let encoder =
+                opus_encoder_create(SAMPLE_RATE, 1, OPUS_APPLICATION_VOIP as i32, &mut error);
+            assert_eq!(error, OPUS_OK as i32, "Failed to create encoder");
+            let decoder = opus_decoder_create(SAMPLE_RATE, 1, &mut error);
+            assert_eq!(error, OPUS_OK as i32, "Failed to create decoder");
+            opus_encoder_ctl(encoder, OPUS_SET_INBAND_FEC_REQUEST as i32, 1);
+            opus_encoder_ctl(encoder, OPUS_SET_PACKET_LOSS_PERC_REQUEST as i32, 20);
+
+            let packets: Vec<Vec<u8>> = (0..FRAMES)
+                .map(|frame| {
+                    let input: Vec<i16> = (0..FRAME_SIZE)
+                        .map(|i| {
+                            let t = (frame * FRAME_SIZE + i) as f32 / SAMPLE_RATE as f32;
+                            ((t * 300.0 * std::f32::consts::TAU).sin() * 8000.0) as i16
+                        })
+                        .collect();
+                    let mut packet = vec![0u8; 1275];
+                    let len = opus_encode(
+                        encoder,
+                        input.as_ptr(),
+                        FRAME_SIZE as i32,
+                        packet.as_mut_ptr(),
+                        packet.len() as i32,
+                    );
+                    assert!(len > 0, "Encoding failed: {}", len);
+                    packet.truncate(len as usize);
+                    packet
+                })
+                .collect();
+
+            let mut loss = LossSimulator::new(0.2, 1, 99).unwrap();
+            let received: Vec<Option<&[u8]>> =
+                loss.simulate(packets.iter().map(Vec::as_slice)).collect();
+            assert!(received.iter().any(Option::is_none));
+
+            // A lost packet is rebuilt from the FEC data in the next one
+            let mut recovered = 0;
+            let mut pcm = [0i16; FRAME_SIZE];
+            for (i, packet) in received.iter().enumerate() {
+                let (data, fec) = match (packet, received.get(i + 1)) {
+                    (Some(data), _) => (*data, 0),
+                    (None, Some(Some(next))) => {
+                        recovered += 1;
+                        (*next, 1)
+                    }
+                    (None, _) => (&[][..], 0),
+                };
+                let ret = opus_decode(
+                    decoder,
+                    if data.is_empty() {
+                        std::ptr::null()
+                    } else {
+                        data.as_ptr()
+                    },
+                    data.len() as i32,
+                    pcm.as_mut_ptr(),
+                    FRAME_SIZE as i32,
+                    fec,
+                );
+                assert_eq!(ret, FRAME_SIZE as i32, "Decoding packet {} failed", i);
+            }
+            assert!(recovered > 0);
+
+            opus_decoder_destroy(decoder);
+            opus_encoder_destroy(encoder);
+        }
+    }
+}
@@ -0,0 +1,579 @@
+//! Multistream decoding that names the malformed stream
+//!
+//! A multistream packet holds one packet per stream, all but the last in
+//! the self-delimiting framing of RFC 6716, Appendix B. When any of them
+//! is malformed, `opus_multistream_decode` fails the whole packet with
+//! `OPUS_INVALID_PACKET` and no hint which. [`decode_checked`] decodes the
+//! same way and, on that error, walks the stream boundaries with
+//! `opus_packet_parse` to report the first stream that doesn't parse, e.g.
+//! the LFE stream of a 5.1 packet mangled by a muxer.
+//!
+//! [`MultistreamEncoder`] and [`MultistreamDecoder`] own the C state for
+//! the standard surround layouts and decode through `decode_checked`.
+//!
+//! ```
+//! use opus_head_sys::OPUS_APPLICATION_AUDIO;
+//! use opus_head_sys::multistream::{MultistreamDecoder, MultistreamEncoder};
+//!
+//! // 5.1 in Vorbis channel order
+//! let mut encoder =
+//!     MultistreamEncoder::surround(48000, 6, 1, OPUS_APPLICATION_AUDIO as i32).unwrap();
+//! let mut decoder = MultistreamDecoder::new(
+//!     48000,
+//!     6,
+//!     encoder.streams(),
+//!     encoder.coupled_streams(),
+//!     encoder.mapping(),
+//! )
+//! .unwrap();
+//! let mut packet = [0u8; 1500];
+//! let len = encoder.encode(&[0; 960 * 6], &mut packet).unwrap();
+//! let mut pcm = [0i16; 960 * 6];
+//! assert_eq!(decoder.decode(Some(&packet[..len]), &mut pcm, false), Ok(960));
+//! ```
+
+use crate::bindings::*;
+use crate::repacketizer::to_standard_framing;
+use std::fmt;
+
+/// Most streams a multistream decoder can have
+const MAX_STREAMS: i32 = 255;
+
+/// A failed [`decode_checked`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeError {
+    /// The Opus error code from `opus_multistream_decode`
+    pub code: i32,
+    /// Index of the first malformed stream, for `OPUS_INVALID_PACKET`. `None`
+    /// for other errors, or if every stream parses on its own (e.g. the
+    /// streams disagree on their duration).
+    pub stream: Option<usize>,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.stream {
+            Some(stream) => write!(f, "opus error {} in stream {}", self.code, stream),
+            None => write!(f, "opus error {}", self.code),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// Decode with `opus_multistream_decode`, reporting which stream was
+/// malformed if the packet is rejected.
+///
+/// `pcm` holds interleaved output for `channels` channels; its length sets
+/// the maximum frame size. An empty `data` runs packet loss concealment.
+/// Returns the decoded samples per channel.
+///
+/// # Safety
+///
+/// `decoder` must be a valid multistream decoder created for `channels`
+/// output channels.
+pub unsafe fn decode_checked(
+    decoder: *mut OpusMSDecoder,
+    data: &[u8],
+    pcm: &mut [i16],
+    channels: usize,
+    decode_fec: bool,
+) -> Result<usize, DecodeError> {
+    if channels == 0 || data.len() > i32::MAX as usize {
+        return Err(DecodeError {
+            code: OPUS_BAD_ARG,
+            stream: None,
+        });
+    }
+    let frame_size = (pcm.len() / channels).min(i32::MAX as usize);
+
+    let ret = opus_multistream_decode(
+        decoder,
+        if data.is_empty() {
+            std::ptr::null()
+        } else {
+            data.as_ptr()
+        },
+        data.len() as i32,
+        pcm.as_mut_ptr(),
+        frame_size as i32,
+        i32::from(decode_fec),
+    );
+    if ret >= 0 {
+        return Ok(ret as usize);
+    }
+
+    let stream = if ret == OPUS_INVALID_PACKET {
+        invalid_stream(data, stream_count(decoder))
+    } else {
+        None
+    };
+    Err(DecodeError { code: ret, stream })
+}
+
+/// An owned multistream encoder, see the [module docs](self)
+///
+/// `Send` but not `Sync`, like [`StreamEncoder`](crate::stream::StreamEncoder).
+#[derive(Debug)]
+pub struct MultistreamEncoder {
+    encoder: *mut OpusMSEncoder,
+    streams: usize,
+    coupled_streams: usize,
+    /// Stream channel of each input channel
+    mapping: Vec<u8>,
+}
+
+// The encoder state is a single allocation with no thread affinity
+unsafe impl Send for MultistreamEncoder {}
+
+impl MultistreamEncoder {
+    /// Create an encoder for a standard layout with
+    /// `opus_multistream_surround_encoder_create`, which picks the streams
+    /// and mapping: `mapping_family` 0 for mono and stereo, 1 for the Vorbis
+    /// channel orders of up to 8 channels. Returns the Opus error code on
+    /// failure.
+    pub fn surround(
+        sample_rate: i32,
+        channels: usize,
+        mapping_family: i32,
+        application: i32,
+    ) -> Result<Self, i32> {
+        if !(1..=MAX_STREAMS as usize).contains(&channels) {
+            return Err(OPUS_BAD_ARG);
+        }
+        let mut streams = 0;
+        let mut coupled_streams = 0;
+        let mut mapping = vec![0u8; channels];
+        let mut error = 0;
+        let encoder = unsafe {
+            opus_multistream_surround_encoder_create(
+                sample_rate,
+                channels as i32,
+                mapping_family,
+                &mut streams,
+                &mut coupled_streams,
+                mapping.as_mut_ptr(),
+                application,
+                &mut error,
+            )
+        };
+        if error != OPUS_OK as i32 {
+            return Err(error);
+        }
+        if encoder.is_null() {
+            return Err(OPUS_ALLOC_FAIL);
+        }
+        Ok(MultistreamEncoder {
+            encoder,
+            streams: streams as usize,
+            coupled_streams: coupled_streams as usize,
+            mapping,
+        })
+    }
+
+    /// The underlying encoder, for `opus_multistream_encoder_ctl`. It stays
+    /// owned by `self`; don't destroy it.
+    pub fn as_ptr(&self) -> *mut OpusMSEncoder {
+        self.encoder
+    }
+
+    pub fn channels(&self) -> usize {
+        self.mapping.len()
+    }
+
+    pub fn streams(&self) -> usize {
+        self.streams
+    }
+
+    pub fn coupled_streams(&self) -> usize {
+        self.coupled_streams
+    }
+
+    /// The mapping chosen, which the decoder needs too
+    pub fn mapping(&self) -> &[u8] {
+        &self.mapping
+    }
+
+    /// Encode `pcm`, one frame of interleaved samples for all channels,
+    /// into `out` with `opus_multistream_encode`. Returns the packet length,
+    /// or `OPUS_BAD_ARG` if `pcm` isn't whole sample frames, or the error
+    /// from `opus_multistream_encode`.
+    pub fn encode(&mut self, pcm: &[i16], out: &mut [u8]) -> Result<usize, i32> {
+        let channels = self.channels();
+        if !pcm.len().is_multiple_of(channels) {
+            return Err(OPUS_BAD_ARG);
+        }
+        let frame_size = i32::try_from(pcm.len() / channels).map_err(|_| OPUS_BAD_ARG)?;
+        let ret = unsafe {
+            opus_multistream_encode(
+                self.encoder,
+                pcm.as_ptr(),
+                frame_size,
+                out.as_mut_ptr(),
+                out.len().min(i32::MAX as usize) as i32,
+            )
+        };
+        if ret < 0 {
+            Err(ret)
+        } else {
+            Ok(ret as usize)
+        }
+    }
+}
+
+impl Drop for MultistreamEncoder {
+    fn drop(&mut self) {
+        unsafe { opus_multistream_encoder_destroy(self.encoder) };
+    }
+}
+
+/// An owned multistream decoder, see the [module docs](self)
+///
+/// Conceals a lost packet like [`Decoder`](crate::decoder::Decoder), for as
+/// long as the last packet decoded. `Send` but not `Sync`.
+#[derive(Debug)]
+pub struct MultistreamDecoder {
+    decoder: *mut OpusMSDecoder,
+    channels: usize,
+    /// Samples per channel of the last packet decoded, what `None` conceals
+    last_frame_size: usize,
+}
+
+// The decoder state is a single allocation with no thread affinity
+unsafe impl Send for MultistreamDecoder {}
+
+impl MultistreamDecoder {
+    /// Create a decoder with `opus_multistream_decoder_create` for the
+    /// layout of a [`MultistreamEncoder`] or an Ogg Opus header. `mapping`
+    /// holds one entry per output channel. Returns the Opus error code on
+    /// failure.
+    pub fn new(
+        sample_rate: i32,
+        channels: usize,
+        streams: usize,
+        coupled_streams: usize,
+        mapping: &[u8],
+    ) -> Result<Self, i32> {
+        if channels == 0 || mapping.len() != channels || channels > MAX_STREAMS as usize {
+            return Err(OPUS_BAD_ARG);
+        }
+        let streams = i32::try_from(streams).map_err(|_| OPUS_BAD_ARG)?;
+        let coupled_streams = i32::try_from(coupled_streams).map_err(|_| OPUS_BAD_ARG)?;
+        let mut error = 0;
+        let decoder = unsafe {
+            opus_multistream_decoder_create(
+                sample_rate,
+                channels as i32,
+                streams,
+                coupled_streams,
+                mapping.as_ptr(),
+                &mut error,
+            )
+        };
+        if error != OPUS_OK as i32 {
+            return Err(error);
+        }
+        if decoder.is_null() {
+            return Err(OPUS_ALLOC_FAIL);
+        }
+        Ok(MultistreamDecoder {
+            decoder,
+            channels,
+            // 20 ms before the first packet
+            last_frame_size: usize::try_from(sample_rate).unwrap_or(0) / 50,
+        })
+    }
+
+    /// The underlying decoder, for `opus_multistream_decoder_ctl`. It stays
+    /// owned by `self`; don't destroy it.
+    pub fn as_ptr(&self) -> *mut OpusMSDecoder {
+        self.decoder
+    }
+
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    /// Decode `packet` into interleaved `pcm` with [`decode_checked`], or
+    /// conceal a lost packet with `None`, as
+    /// [`Decoder::decode`](crate::decoder::Decoder::decode) does: `None`
+    /// conceals for the last packet's duration, `OPUS_BUFFER_TOO_SMALL` if
+    /// `pcm` can't hold it, and an empty packet is `OPUS_BAD_ARG`. Returns
+    /// the samples per channel written.
+    pub fn decode(
+        &mut self,
+        packet: Option<&[u8]>,
+        pcm: &mut [i16],
+        fec: bool,
+    ) -> Result<usize, DecodeError> {
+        let error = |code| DecodeError { code, stream: None };
+        let (data, pcm) = match packet {
+            Some([]) => return Err(error(OPUS_BAD_ARG)),
+            Some(packet) => (packet, pcm),
+            None => {
+                let len = self.last_frame_size * self.channels;
+                let pcm = pcm.get_mut(..len).ok_or(error(OPUS_BUFFER_TOO_SMALL))?;
+                (&[][..], pcm)
+            }
+        };
+        let samples = unsafe { decode_checked(self.decoder, data, pcm, self.channels, fec)? };
+        if packet.is_some() && !fec {
+            self.last_frame_size = samples;
+        }
+        Ok(samples)
+    }
+}
+
+impl Drop for MultistreamDecoder {
+    fn drop(&mut self) {
+        unsafe { opus_multistream_decoder_destroy(self.decoder) };
+    }
+}
+
+/// The first stream of the multistream packet `data` that doesn't parse,
+/// or whose duration differs from the first stream's. `None` if all
+/// `streams` streams are fine.
+pub fn invalid_stream(data: &[u8], streams: usize) -> Option<usize> {
+    let mut offset = 0;
+    let mut duration = None;
+    for stream in 0..streams {
+        let rest = &data[offset..];
+        let packet = if stream + 1 < streams {
+            let Ok((packet, consumed)) = to_standard_framing(rest) else {
+                return Some(stream);
+            };
+            offset += consumed;
+            packet
+        } else {
+            rest.to_vec()
+        };
+
+        let Some(samples) = parse(&packet) else {
+            return Some(stream);
+        };
+        if *duration.get_or_insert(samples) != samples {
+            return Some(stream);
+        }
+    }
+    None
+}
+
+/// Samples at 48 kHz in a packet with standard framing, `None` if
+/// `opus_packet_parse` rejects it
+fn parse(packet: &[u8]) -> Option<i32> {
+    let len = i32::try_from(packet.len()).ok()?;
+    let mut toc = 0u8;
+    let mut frames = [std::ptr::null(); 48];
+    let mut sizes = [0i16; 48];
+    let mut payload_offset = 0;
+    let ret = unsafe {
+        opus_packet_parse(
+            packet.as_ptr(),
+            len,
+            &mut toc,
+            frames.as_mut_ptr(),
+            sizes.as_mut_ptr(),
+            &mut payload_offset,
+        )
+    };
+    if ret < 0 {
+        return None;
+    }
+    let samples = unsafe { opus_packet_get_nb_samples(packet.as_ptr(), len, 48000) };
+    (samples > 0).then_some(samples)
+}
+
+/// Number of streams of `decoder`, found by asking for each stream's
+/// decoder state until that fails
+unsafe fn stream_count(decoder: *mut OpusMSDecoder) -> usize {
+    let mut state: *mut OpusDecoder = std::ptr::null_mut();
+    (0..MAX_STREAMS)
+        .take_while(|&stream| {
+            opus_multistream_decoder_ctl(
+                decoder,
+                OPUS_MULTISTREAM_GET_DECODER_STATE_REQUEST as i32,
+                stream,
+                &mut state as *mut *mut OpusDecoder,
+            ) == OPUS_OK as i32
+        })
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packet::{max_packet_size, FrameDuration};
+
+    const SAMPLE_RATE: i32 = 48000;
+    const FRAME_SIZE: usize = 960;
+    const CHANNELS: usize = 3;
+
+    /// One 20 ms packet of three uncoupled streams carrying tones
+    fn encode_packet() -> Vec<u8> {
+        unsafe {
+            let mapping = [0u8, 1, 2];
+            let mut error: i32 = 0;
+            let encoder = opus_multistream_encoder_create(
+                SAMPLE_RATE,
+                CHANNELS as i32,
+                3,
+                0,
+                mapping.as_ptr(),
+                OPUS_APPLICATION_AUDIO as i32,
+                &mut error,
+            );
+            assert_eq!(error, OPUS_OK as i32, "Failed to create encoder");
+
+            let input: Vec<i16> = (0..FRAME_SIZE * CHANNELS)
+                .map(|i| {
+                    let t = (i / CHANNELS) as f32 / SAMPLE_RATE as f32;
+                    let freq = 220.0 * (1 + i % CHANNELS) as f32;
+                    ((t * freq * std::f32::consts::TAU).sin() * 8000.0) as i16
+                })
+                .collect();
+            let mut packet = vec![0u8; max_packet_size(CHANNELS, FrameDuration::Ms20)];
+            let len = opus_multistream_encode(
+                encoder,
+                input.as_ptr(),
+                FRAME_SIZE as i32,
+                packet.as_mut_ptr(),
+                packet.len() as i32,
+            );
+            assert!(len > 0, "Encoding failed: {}", len);
+            packet.truncate(len as usize);
+            opus_multistream_encoder_destroy(encoder);
+            packet
+        }
+    }
+
+    /// Decode `packet` with a fresh decoder matching [`encode_packet`]
+    fn decode(packet: &[u8]) -> Result<usize, DecodeError> {
+        unsafe {
+            let mapping = [0u8, 1, 2];
+            let mut error: i32 = 0;
+            let decoder = opus_multistream_decoder_create(
+                SAMPLE_RATE,
+                CHANNELS as i32,
+                3,
+                0,
+                mapping.as_ptr(),
+                &mut error,
+            );
+            assert_eq!(error, OPUS_OK as i32, "Failed to create decoder");
+            assert_eq!(stream_count(decoder), 3);
+
+            let mut pcm = vec![0i16; FRAME_SIZE * CHANNELS];
+            let result = decode_checked(decoder, packet, &mut pcm, CHANNELS, false);
+            opus_multistream_decoder_destroy(decoder);
+            result
+        }
+    }
+
+    #[test]
+    fn test_valid_packet() {
+        let packet = encode_packet();
+        assert_eq!(decode(&packet), Ok(FRAME_SIZE));
+        assert_eq!(invalid_stream(&packet, 3), None);
+        // Concealment
+        assert_eq!(decode(&[]), Ok(FRAME_SIZE));
+    }
+
+    #[test]
+    fn test_malformed_stream() {
+        let packet = encode_packet();
+        let (_, first) = to_standard_framing(&packet).unwrap();
+        let (_, second) = to_standard_framing(&packet[first..]).unwrap();
+        let last = first + second;
+
+        // The last stream: code 3 with a frame count of 0
+        let mut bad = packet[..last].to_vec();
+        bad.extend([0x03, 0x00]);
+        let error = decode(&bad).unwrap_err();
+        assert_eq!(
+            error,
+            DecodeError {
+                code: OPUS_INVALID_PACKET,
+                stream: Some(2)
+            }
+        );
+        assert_eq!(error.to_string(), "opus error -4 in stream 2");
+
+        // The middle stream's length field runs past the end
+        let error = decode(&packet[..first + 3]).unwrap_err();
+        assert_eq!(error.stream, Some(1));
+
+        // The middle stream has a different duration: a 10 ms CELT frame
+        let mut bad = packet[..first].to_vec();
+        bad.extend([0xf0, 0x00]);
+        bad.extend(&packet[last..]);
+        let error = decode(&bad).unwrap_err();
+        assert_eq!(error.code, OPUS_INVALID_PACKET);
+        assert_eq!(error.stream, Some(1));
+    }
+
+    #[test]
+    fn test_surround_roundtrip() {
+        let mut encoder =
+            MultistreamEncoder::surround(SAMPLE_RATE, 6, 1, OPUS_APPLICATION_AUDIO as i32).unwrap();
+        // 5.1: 4 streams, front and rear pairs coupled
+        assert_eq!((encoder.streams(), encoder.coupled_streams()), (4, 2));
+        assert_eq!(encoder.mapping().len(), 6);
+        let mut decoder = MultistreamDecoder::new(
+            SAMPLE_RATE,
+            6,
+            encoder.streams(),
+            encoder.coupled_streams(),
+            encoder.mapping(),
+        )
+        .unwrap();
+
+        let mut packet = vec![0u8; max_packet_size(6, FrameDuration::Ms20)];
+        let len = encoder.encode(&[0; FRAME_SIZE * 6], &mut packet).unwrap();
+        let mut pcm = vec![0i16; FRAME_SIZE * 2 * 6];
+        assert_eq!(
+            decoder.decode(Some(&packet[..len]), &mut pcm, false),
+            Ok(FRAME_SIZE)
+        );
+        // Concealment for the last packet's 20 ms, not all of `pcm`
+        assert_eq!(decoder.decode(None, &mut pcm, false), Ok(FRAME_SIZE));
+        assert_eq!(
+            decoder
+                .decode(None, &mut pcm[..100], false)
+                .unwrap_err()
+                .code,
+            OPUS_BUFFER_TOO_SMALL
+        );
+        assert_eq!(
+            decoder.decode(Some(&[]), &mut pcm, false).unwrap_err().code,
+            OPUS_BAD_ARG
+        );
+        assert_eq!(
+            encoder.encode(&[0; FRAME_SIZE * 6 + 1], &mut packet),
+            Err(OPUS_BAD_ARG)
+        );
+    }
+
+    #[test]
+    fn test_invalid_layout() {
+        let application = OPUS_APPLICATION_AUDIO as i32;
+        assert_eq!(
+            MultistreamEncoder::surround(SAMPLE_RATE, 0, 1, application).unwrap_err(),
+            OPUS_BAD_ARG
+        );
+        // Family 0 is mono or stereo only
+        assert!(MultistreamEncoder::surround(SAMPLE_RATE, 6, 0, application).is_err());
+        // One mapping entry per channel
+        assert_eq!(
+            MultistreamDecoder::new(SAMPLE_RATE, 3, 3, 0, &[0, 1]).unwrap_err(),
+            OPUS_BAD_ARG
+        );
+    }
+
+    #[test]
+    fn test_invalid_stream() {
+        assert_eq!(invalid_stream(&[], 2), Some(0));
+        // A self-delimited empty 20 ms CELT frame, then nothing
+        assert_eq!(invalid_stream(&[0xf8, 0x00], 2), Some(1));
+        assert_eq!(invalid_stream(&[0xf8, 0x00, 0xf8], 2), None);
+    }
+}
@@ -0,0 +1,181 @@
+//! Decoder complexity, which selects the speech enhancement (OSCE) and
+//! deep PLC models
+//!
+//! `OPUS_SET_COMPLEXITY` on a decoder picks how much of the `osce` feature's
+//! work runs on SILK and hybrid packets:
+//!
+//! | Complexity | Decoder side processing |
+//! |---|---|
+//! | 0–3 | None (the default is 0) |
+//! | 4 | Bandwidth extension, if enabled with `OPUS_SET_OSCE_BWE` |
+//! | 5 | Deep PLC for lost packets |
+//! | 6 | LACE enhancement |
+//! | 7–10 | NoLACE enhancement, the most expensive |
+//!
+//! The models only run once their weights are loaded with
+//! `OPUS_SET_DNN_BLOB`. Without the `osce` feature only deep PLC is left,
+//! which `dred` builds as well, and without either the setting changes
+//! nothing. CELT-only packets aren't enhanced, but their concealment uses
+//! deep PLC too.
+
+use crate::bindings::*;
+
+/// Set the decoder complexity (0 to 10) with `OPUS_SET_COMPLEXITY`.
+/// Returns the Opus error code on failure, `OPUS_BAD_ARG` out of range.
+///
+/// # Safety
+///
+/// `decoder` must be a valid decoder.
+pub unsafe fn set_complexity(decoder: *mut OpusDecoder, complexity: i32) -> Result<(), i32> {
+    let ret = opus_decoder_ctl(decoder, OPUS_SET_COMPLEXITY_REQUEST as i32, complexity);
+    if ret == OPUS_OK as i32 {
+        Ok(())
+    } else {
+        Err(ret)
+    }
+}
+
+/// The decoder complexity from `OPUS_GET_COMPLEXITY`. Returns the Opus
+/// error code on failure.
+///
+/// # Safety
+///
+/// `decoder` must be a valid decoder.
+pub unsafe fn complexity(decoder: *mut OpusDecoder) -> Result<i32, i32> {
+    let mut value: i32 = 0;
+    let ret = opus_decoder_ctl(
+        decoder,
+        OPUS_GET_COMPLEXITY_REQUEST as i32,
+        &mut value as *mut i32,
+    );
+    if ret != OPUS_OK as i32 {
+        return Err(ret);
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_complexity_roundtrip() {
+        unsafe {
+            let mut error: i32 = 0;
+            let decoder = opus_decoder_create(48000, 1, &mut error);
+            assert_eq!(error, OPUS_OK as i32, "Failed to create decoder");
+
+            assert_eq!(complexity(decoder), Ok(0));
+            for value in [0, 5, 10] {
+                assert_eq!(set_complexity(decoder, value), Ok(()));
+                assert_eq!(complexity(decoder), Ok(value));
+            }
+            assert_eq!(set_complexity(decoder, 11), Err(OPUS_BAD_ARG));
+            assert_eq!(set_complexity(decoder, -1), Err(OPUS_BAD_ARG));
+            assert_eq!(complexity(decoder), Ok(10));
+
+            opus_decoder_destroy(decoder);
+        }
+    }
+
+    /// Decodes the same SILK stream at complexity 0 and 10 and prints the
+    /// time each took. With weights in `target/model` the second one runs
+    /// NoLACE and its output differs; without them both decode plain SILK.
+    #[test]
+    #[cfg(all(feature = "osce", not(feature = "no-float-api")))]
+    fn test_complexity_cost() {
+        use std::time::{Duration, Instant};
+
+        const SAMPLE_RATE: i32 = 48000;
+        const FRAME_SIZE: usize = 960;
+        const FRAMES: usize = 100;
+
+        let weights = crate::test_support::load_dnn_weights();
+        if weights.is_none() {
+            println!("No DNN weights in target/model, comparing without OSCE");
+        }
+
+        unsafe {
+            let mut error: i32 = 0;
+            let encoder =
+                opus_encoder_create(SAMPLE_RATE, 1, OPUS_APPLICATION_VOIP as i32, &mut error);
+            assert_eq!(error, OPUS_OK as i32, "Failed to create encoder");
+            // Wideband SILK, which OSCE enhances
+            opus_encoder_ctl(encoder, OPUS_SET_BITRATE_REQUEST as i32, 16000);
+            opus_encoder_ctl(
+                encoder,
+                OPUS_SET_MAX_BANDWIDTH_REQUEST as i32,
+                OPUS_BANDWIDTH_WIDEBAND as i32,
+            );
+            let packets: Vec<Vec<u8>> = (0..FRAMES)
+                .map(|frame| {
+                    let input: Vec<i16> = (0..FRAME_SIZE)
+                        .map(|i| {
+                            let t = (frame * FRAME_SIZE + i) as f32 / SAMPLE_RATE as f32;
+                            let pitch = (t * 180.0 * std::f32::consts::TAU).sin();
+                            let formant = (t * 700.0 * std::f32::consts::TAU).sin();
+                            ((pitch + 0.5 * formant) * 6000.0) as i16
+                        })
+                        .collect();
+                    let mut packet = vec![0u8; 1275];
+                    let len = opus_encode(
+                        encoder,
+                        input.as_ptr(),
+                        FRAME_SIZE as i32,
+                        packet.as_mut_ptr(),
+                        packet.len() as i32,
+                    );
+                    assert!(len > 0, "Encoding failed: {}", len);
+                    packet.truncate(len as usize);
+                    packet
+                })
+                .collect();
+            opus_encoder_destroy(encoder);
+
+            let decode_all = |level: i32| -> (Vec<i16>, Duration) {
+                let mut error: i32 = 0;
+                let decoder = opus_decoder_create(SAMPLE_RATE, 1, &mut error);
+                assert_eq!(error, OPUS_OK as i32, "Failed to create decoder");
+                if let Some(weights) = &weights {
+                    let ret = opus_decoder_ctl(
+                        decoder,
+                        OPUS_SET_DNN_BLOB_REQUEST as i32,
+                        weights.as_ptr(),
+                        weights.len() as i32,
+                    );
+                    assert_eq!(ret, OPUS_OK as i32, "Failed to load DNN weights");
+                }
+                set_complexity(decoder, level).unwrap();
+
+                let mut output = vec![0i16; FRAME_SIZE * FRAMES];
+                let start = Instant::now();
+                for (packet, out) in packets.iter().zip(output.chunks_exact_mut(FRAME_SIZE)) {
+                    let ret = opus_decode(
+                        decoder,
+                        packet.as_ptr(),
+                        packet.len() as i32,
+                        out.as_mut_ptr(),
+                        FRAME_SIZE as i32,
+                        0,
+                    );
+                    assert_eq!(ret, FRAME_SIZE as i32);
+                }
+                let elapsed = start.elapsed();
+                opus_decoder_destroy(decoder);
+                (output, elapsed)
+            };
+
+            let (plain, plain_time) = decode_all(0);
+            let (enhanced, enhanced_time) = decode_all(10);
+            println!(
+                "{} ms of audio: complexity 0 took {:?}, complexity 10 took {:?}",
+                FRAMES * 20,
+                plain_time,
+                enhanced_time
+            );
+            if weights.is_some() {
+                assert_ne!(plain, enhanced, "complexity 10 didn't enhance");
+            }
+        }
+    }
+}
@@ -0,0 +1,958 @@
+//! Helpers for the Opus packet TOC (table-of-contents) byte
+//!
+//! The first byte of every Opus packet (RFC 6716, section 3.1) carries a
+//! 5-bit configuration number (mode, bandwidth and frame size), a stereo
+//! flag and a 2-bit frame count code:
+//!
+//! ```text
+//!  0 1 2 3 4 5 6 7
+//! +-+-+-+-+-+-+-+-+
+//! | config  |s| c |
+//! +-+-+-+-+-+-+-+-+
+//! ```
+//!
+//! These are pure Rust helpers for building and inspecting that byte, e.g.
+//! when synthesizing or rewriting packets. Use `opus_packet_parse` to split
+//! a full packet into its frames.
+//!
+//! [`samples`], [`duration_ms`] and [`rtp_timestamp_increment`] give the
+//! duration of a whole packet, from the TOC byte and the frame count byte of
+//! code 3 packets, e.g. to advance RTP timestamps when packetizing for
+//! WebRTC or SIP. [`is_dtx_packet`] tells the packets an encoder with
+//! `OPUS_SET_DTX` emits during silence from ones carrying audio, and
+//! `has_dred` (with the `dred` feature) the packets carrying DRED.
+//!
+//! [`parse`] and [`parse_self_delimited`] split a packet into its frames
+//! with `opus_packet_parse`. The self-delimiting framing (RFC 6716,
+//! Appendix B) is what every stream but the last of a multistream packet
+//! uses: it adds the length of the last frame after the other lengths (for
+//! code 0 and 1 packets, right after the TOC byte; for code 3 CBR, the size
+//! of every frame), so the packet can be followed by more data.
+//! `opus_packet_parse` only takes the standard framing, where the last
+//! frame runs to the end of the data; libopus has no public encoder for the
+//! self-delimited one.
+
+use crate::bindings::*;
+
+/// Coding mode of a packet
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Mode {
+    /// SILK-only (linear prediction), configs 0..=11
+    SilkOnly,
+    /// SILK for the low band plus CELT for the high band, configs 12..=15
+    Hybrid,
+    /// CELT-only (MDCT), configs 16..=31
+    CeltOnly,
+}
+
+/// Audio bandwidth of a packet
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Bandwidth {
+    /// 4 kHz passband
+    Narrowband,
+    /// 6 kHz passband
+    Mediumband,
+    /// 8 kHz passband
+    Wideband,
+    /// 12 kHz passband
+    SuperWideband,
+    /// 20 kHz passband
+    Fullband,
+}
+
+impl Bandwidth {
+    /// The matching `OPUS_BANDWIDTH_*` constant, as returned by
+    /// `opus_packet_get_bandwidth`
+    pub fn to_opus(self) -> i32 {
+        (match self {
+            Bandwidth::Narrowband => OPUS_BANDWIDTH_NARROWBAND,
+            Bandwidth::Mediumband => OPUS_BANDWIDTH_MEDIUMBAND,
+            Bandwidth::Wideband => OPUS_BANDWIDTH_WIDEBAND,
+            Bandwidth::SuperWideband => OPUS_BANDWIDTH_SUPERWIDEBAND,
+            Bandwidth::Fullband => OPUS_BANDWIDTH_FULLBAND,
+        }) as i32
+    }
+
+    /// The bandwidth for an `OPUS_BANDWIDTH_*` constant, `None` for anything
+    /// else, including `OPUS_AUTO`
+    pub fn from_opus(value: i32) -> Option<Self> {
+        [
+            Bandwidth::Narrowband,
+            Bandwidth::Mediumband,
+            Bandwidth::Wideband,
+            Bandwidth::SuperWideband,
+            Bandwidth::Fullband,
+        ]
+        .into_iter()
+        .find(|bandwidth| bandwidth.to_opus() == value)
+    }
+}
+
+/// Duration of each frame in a packet
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FrameSize {
+    Ms2_5,
+    Ms5,
+    Ms10,
+    Ms20,
+    Ms40,
+    Ms60,
+}
+
+impl FrameSize {
+    /// Samples per channel in one frame at the given sample rate
+    pub fn samples(self, sample_rate: u32) -> u32 {
+        let per_2_5ms = sample_rate / 400;
+        per_2_5ms
+            * match self {
+                FrameSize::Ms2_5 => 1,
+                FrameSize::Ms5 => 2,
+                FrameSize::Ms10 => 4,
+                FrameSize::Ms20 => 8,
+                FrameSize::Ms40 => 16,
+                FrameSize::Ms60 => 24,
+            }
+    }
+}
+
+/// Duration of the audio in one packet, as chosen with
+/// `OPUS_SET_EXPERT_FRAME_DURATION` or by the frame size passed to
+/// `opus_encode`. Up to 20 ms it is one frame; longer packets hold one
+/// frame per 20 ms at most (fewer for 40 and 60 ms SILK frames).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FrameDuration {
+    Ms2_5,
+    Ms5,
+    Ms10,
+    Ms20,
+    Ms40,
+    Ms60,
+    Ms80,
+    Ms100,
+    Ms120,
+}
+
+impl FrameDuration {
+    /// Duration in units of 2.5 ms
+    pub(crate) const fn units(self) -> usize {
+        match self {
+            FrameDuration::Ms2_5 => 1,
+            FrameDuration::Ms5 => 2,
+            FrameDuration::Ms10 => 4,
+            FrameDuration::Ms20 => 8,
+            FrameDuration::Ms40 => 16,
+            FrameDuration::Ms60 => 24,
+            FrameDuration::Ms80 => 32,
+            FrameDuration::Ms100 => 40,
+            FrameDuration::Ms120 => 48,
+        }
+    }
+
+    /// The matching `OPUS_FRAMESIZE_*` constant for
+    /// `OPUS_SET_EXPERT_FRAME_DURATION`
+    pub fn to_opus(self) -> i32 {
+        (match self {
+            FrameDuration::Ms2_5 => OPUS_FRAMESIZE_2_5_MS,
+            FrameDuration::Ms5 => OPUS_FRAMESIZE_5_MS,
+            FrameDuration::Ms10 => OPUS_FRAMESIZE_10_MS,
+            FrameDuration::Ms20 => OPUS_FRAMESIZE_20_MS,
+            FrameDuration::Ms40 => OPUS_FRAMESIZE_40_MS,
+            FrameDuration::Ms60 => OPUS_FRAMESIZE_60_MS,
+            FrameDuration::Ms80 => OPUS_FRAMESIZE_80_MS,
+            FrameDuration::Ms100 => OPUS_FRAMESIZE_100_MS,
+            FrameDuration::Ms120 => OPUS_FRAMESIZE_120_MS,
+        }) as i32
+    }
+
+    /// Samples per channel at the given sample rate
+    pub fn samples(self, sample_rate: u32) -> u32 {
+        sample_rate / 400 * self.units() as u32
+    }
+
+    /// The duration of `samples` samples per channel at `sample_rate`, or
+    /// `None` if that is not an Opus frame size
+    pub fn from_samples(samples: usize, sample_rate: u32) -> Option<Self> {
+        let unit = sample_rate as usize / 400;
+        if unit == 0 || !samples.is_multiple_of(unit) {
+            return None;
+        }
+        Some(match samples / unit {
+            1 => FrameDuration::Ms2_5,
+            2 => FrameDuration::Ms5,
+            4 => FrameDuration::Ms10,
+            8 => FrameDuration::Ms20,
+            16 => FrameDuration::Ms40,
+            24 => FrameDuration::Ms60,
+            32 => FrameDuration::Ms80,
+            40 => FrameDuration::Ms100,
+            48 => FrameDuration::Ms120,
+            _ => return None,
+        })
+    }
+
+    /// Most frames the encoder codes a packet of this duration in
+    pub const fn max_frames(self) -> usize {
+        if self.units() <= 8 {
+            1
+        } else {
+            self.units() / 8
+        }
+    }
+}
+
+/// Largest Opus frame in bytes (RFC 6716, section 3.2.1), reached at
+/// 510 kbit/s for 20 ms
+pub const MAX_FRAME_BYTES: usize = 1275;
+
+/// Largest packet an encoder produces for `frame_duration` of audio, to
+/// size the output buffer of `opus_encode` exactly instead of guessing.
+///
+/// One or two channels are one Opus stream: a TOC byte and a frame, or for
+/// several frames a code 3 packet with the frame count byte and a 2-byte
+/// length for every frame but the last. More channels are taken as a
+/// multistream packet with a stream per channel, which bounds any mapping,
+/// each but the last carrying up to 2 more bytes of self-delimiting
+/// length. 0 channels give 0.
+///
+/// ```
+/// use opus_head_sys::packet::{max_packet_size, FrameDuration};
+///
+/// assert_eq!(max_packet_size(2, FrameDuration::Ms20), 1276);
+/// // Six 20 ms frames, more than the 4000 bytes often used
+/// assert_eq!(max_packet_size(1, FrameDuration::Ms120), 7662);
+/// ```
+pub const fn max_packet_size(channels: usize, frame_duration: FrameDuration) -> usize {
+    let frames = frame_duration.max_frames();
+    let stream = if frames == 1 {
+        1 + MAX_FRAME_BYTES
+    } else {
+        2 + 2 * (frames - 1) + frames * MAX_FRAME_BYTES
+    };
+    match channels {
+        0 => 0,
+        1 | 2 => stream,
+        streams => streams * (stream + 2) - 2,
+    }
+}
+
+/// Frame count code (`c`) of the TOC byte
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FrameCount {
+    /// c=0: one frame
+    One,
+    /// c=1: two frames of equal compressed size
+    TwoEqual,
+    /// c=2: two frames of different compressed size
+    TwoDifferent,
+    /// c=3: an arbitrary number of frames, given in the following byte
+    Arbitrary,
+}
+
+impl FrameCount {
+    /// The 2-bit `c` field value
+    pub fn code(self) -> u8 {
+        match self {
+            FrameCount::One => 0,
+            FrameCount::TwoEqual => 1,
+            FrameCount::TwoDifferent => 2,
+            FrameCount::Arbitrary => 3,
+        }
+    }
+
+    fn from_code(code: u8) -> Self {
+        match code & 0x3 {
+            0 => FrameCount::One,
+            1 => FrameCount::TwoEqual,
+            2 => FrameCount::TwoDifferent,
+            _ => FrameCount::Arbitrary,
+        }
+    }
+}
+
+/// A valid TOC configuration number (0..=31)
+///
+/// Not every mode/bandwidth/frame size combination exists, so construction
+/// goes through [`Config::new`] or [`Config::from_index`]. With the `serde`
+/// feature it is stored as its index, which is checked when deserializing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "u8", into = "u8")
+)]
+pub struct Config(u8);
+
+#[cfg(feature = "std")]
+impl TryFrom<u8> for Config {
+    type Error = String;
+
+    fn try_from(index: u8) -> Result<Self, Self::Error> {
+        Config::from_index(index).ok_or_else(|| format!("invalid Opus config {}", index))
+    }
+}
+
+impl From<Config> for u8 {
+    fn from(config: Config) -> u8 {
+        config.index()
+    }
+}
+
+const SILK_FRAME_SIZES: [FrameSize; 4] = [
+    FrameSize::Ms10,
+    FrameSize::Ms20,
+    FrameSize::Ms40,
+    FrameSize::Ms60,
+];
+const HYBRID_FRAME_SIZES: [FrameSize; 2] = [FrameSize::Ms10, FrameSize::Ms20];
+const CELT_FRAME_SIZES: [FrameSize; 4] = [
+    FrameSize::Ms2_5,
+    FrameSize::Ms5,
+    FrameSize::Ms10,
+    FrameSize::Ms20,
+];
+
+impl Config {
+    /// Look up the configuration number for a combination, or `None` if
+    /// Opus has no such configuration (e.g. CELT at 60 ms, SILK at fullband)
+    pub fn new(mode: Mode, bandwidth: Bandwidth, frame_size: FrameSize) -> Option<Self> {
+        use Bandwidth::*;
+
+        let (base, sizes): (u8, &[FrameSize]) = match (mode, bandwidth) {
+            (Mode::SilkOnly, Narrowband) => (0, &SILK_FRAME_SIZES),
+            (Mode::SilkOnly, Mediumband) => (4, &SILK_FRAME_SIZES),
+            (Mode::SilkOnly, Wideband) => (8, &SILK_FRAME_SIZES),
+            (Mode::Hybrid, SuperWideband) => (12, &HYBRID_FRAME_SIZES),
+            (Mode::Hybrid, Fullband) => (14, &HYBRID_FRAME_SIZES),
+            // CELT has no mediumband configs
+            (Mode::CeltOnly, Narrowband) => (16, &CELT_FRAME_SIZES),
+            (Mode::CeltOnly, Wideband) => (20, &CELT_FRAME_SIZES),
+            (Mode::CeltOnly, SuperWideband) => (24, &CELT_FRAME_SIZES),
+            (Mode::CeltOnly, Fullband) => (28, &CELT_FRAME_SIZES),
+            _ => return None,
+        };
+
+        let offset = sizes.iter().position(|&s| s == frame_size)?;
+        Some(Config(base + offset as u8))
+    }
+
+    /// Wrap a raw configuration number, or `None` if it is above 31
+    pub fn from_index(index: u8) -> Option<Self> {
+        (index < 32).then_some(Config(index))
+    }
+
+    /// The raw configuration number (0..=31)
+    pub fn index(self) -> u8 {
+        self.0
+    }
+
+    pub fn mode(self) -> Mode {
+        match self.0 {
+            0..=11 => Mode::SilkOnly,
+            12..=15 => Mode::Hybrid,
+            _ => Mode::CeltOnly,
+        }
+    }
+
+    pub fn bandwidth(self) -> Bandwidth {
+        use Bandwidth::*;
+
+        match self.0 {
+            0..=3 => Narrowband,
+            4..=7 => Mediumband,
+            8..=11 => Wideband,
+            12..=13 => SuperWideband,
+            14..=15 => Fullband,
+            16..=19 => Narrowband,
+            20..=23 => Wideband,
+            24..=27 => SuperWideband,
+            _ => Fullband,
+        }
+    }
+
+    pub fn frame_size(self) -> FrameSize {
+        match self.mode() {
+            Mode::SilkOnly => SILK_FRAME_SIZES[(self.0 & 0x3) as usize],
+            Mode::Hybrid => HYBRID_FRAME_SIZES[(self.0 & 0x1) as usize],
+            Mode::CeltOnly => CELT_FRAME_SIZES[(self.0 & 0x3) as usize],
+        }
+    }
+}
+
+/// Decoded contents of a TOC byte
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TocInfo {
+    pub config: Config,
+    pub stereo: bool,
+    pub frame_count: FrameCount,
+}
+
+/// Build a TOC byte
+pub fn toc(config: Config, stereo: bool, frame_count: FrameCount) -> u8 {
+    (config.index() << 3) | ((stereo as u8) << 2) | frame_count.code()
+}
+
+/// Decode a TOC byte; every byte value is a valid TOC
+pub fn parse_toc(byte: u8) -> TocInfo {
+    TocInfo {
+        config: Config(byte >> 3),
+        stereo: byte & 0x4 != 0,
+        frame_count: FrameCount::from_code(byte),
+    }
+}
+
+/// RTP clock rate of Opus, whatever the sample rate (RFC 7587, section 4.1)
+pub const RTP_CLOCK_RATE: i32 = 48000;
+
+/// Samples per channel in `data` at `sample_rate`, from
+/// `opus_packet_get_nb_samples`. Returns `OPUS_BAD_ARG` for an empty packet
+/// and `OPUS_INVALID_PACKET` if the count is missing or over 120 ms.
+pub fn samples(data: &[u8], sample_rate: i32) -> Result<usize, i32> {
+    let len = i32::try_from(data.len()).map_err(|_| OPUS_BAD_ARG)?;
+    if len == 0 || sample_rate <= 0 {
+        return Err(OPUS_BAD_ARG);
+    }
+    let samples = unsafe { opus_packet_get_nb_samples(data.as_ptr(), len, sample_rate) };
+    if samples < 0 {
+        Err(samples)
+    } else {
+        Ok(samples as usize)
+    }
+}
+
+/// Duration of `data` in milliseconds, see [`samples`]. The sample rate only
+/// matters for rates that don't divide into 2.5 ms, such as 44.1 kHz.
+pub fn duration_ms(data: &[u8], sample_rate: i32) -> Result<f64, i32> {
+    Ok(samples(data, sample_rate)? as f64 * 1000.0 / sample_rate as f64)
+}
+
+/// How far the RTP timestamp advances for `data`: its samples at the
+/// 48 kHz [`RTP_CLOCK_RATE`], also when encoding at a lower rate
+pub fn rtp_timestamp_increment(data: &[u8]) -> Result<u32, i32> {
+    samples(data, RTP_CLOCK_RATE).map(|samples| samples as u32)
+}
+
+/// Whether `data` is a DTX packet, the 1 or 2 byte packet an encoder with
+/// `OPUS_SET_DTX` emits in place of a frame with no voice activity. Only the
+/// TOC byte is coded, so the decoder fills the frame with comfort noise.
+///
+/// An RTP sender may drop these and set the marker bit on the first packet
+/// after them, which starts the talkspurt (RFC 3551, section 4.1). Like
+/// WebRTC, this only looks at the length, so it also holds for a DTX packet
+/// in a code 1 or 2 packet's framing. An empty slice isn't a packet.
+pub fn is_dtx_packet(data: &[u8]) -> bool {
+    matches!(data.len(), 1 | 2)
+}
+
+/// Extension ID libopus codes DRED with (`DRED_EXTENSION_ID` in
+/// `dnn/dred_config.h`)
+#[cfg(feature = "dred")]
+const DRED_EXTENSION_ID: u8 = 126;
+
+/// Until the DRED format is final, libopus prefixes the payload with `D`
+/// and this version, and ignores DRED of any other version
+/// (`DRED_EXPERIMENTAL_VERSION`)
+#[cfg(feature = "dred")]
+const DRED_EXPERIMENTAL_VERSION: u8 = 12;
+
+/// Whether `data` carries DRED redundancy this build's decoder can use,
+/// e.g. for a jitter buffer to decide whether a packet that just arrived
+/// can recover the frames lost before it.
+///
+/// This looks for the DRED extension in the packet's padding the way
+/// `opus_dred_parse` does, without decoding it: that would need a DRED
+/// decoder with the DNN weights loaded, and the parse is the expensive
+/// part. A packet for which this returns true can still yield no audio,
+/// e.g. when the loss is further back than the redundancy reaches. False
+/// for a malformed packet.
+#[cfg(feature = "dred")]
+pub fn has_dred(data: &[u8]) -> bool {
+    let Ok(frames) = parse(data) else {
+        return false;
+    };
+    // The padding follows the last frame
+    let end = frames.payload_offset + frames.frames().iter().map(|f| f.len()).sum::<usize>();
+    let Some(padding) = data.get(end..) else {
+        return false;
+    };
+    let dred = crate::extension::find(padding, frames.count, |extension| {
+        extension.id == DRED_EXTENSION_ID
+            && extension.data.len() > 2
+            && extension.data[0] == b'D'
+            && extension.data[1] == DRED_EXPERIMENTAL_VERSION
+    });
+    matches!(dred, Ok(Some(_)))
+}
+
+/// Most frames in a packet: 120 ms of 2.5 ms frames
+const MAX_FRAMES: usize = 48;
+
+/// The frames of a packet, borrowed from the data it was parsed from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Frames<'a> {
+    pub toc: TocInfo,
+    /// Where the first frame starts, after the TOC byte, frame count,
+    /// padding length and frame lengths
+    pub payload_offset: usize,
+    frames: [&'a [u8]; MAX_FRAMES],
+    count: usize,
+}
+
+impl<'a> Frames<'a> {
+    /// The compressed frames, without padding
+    pub fn frames(&self) -> &[&'a [u8]] {
+        &self.frames[..self.count]
+    }
+}
+
+/// Parse a packet with standard framing, which takes up all of `data`.
+/// Returns the Opus error code for a malformed packet, e.g.
+/// `OPUS_INVALID_PACKET`.
+pub fn parse(data: &[u8]) -> Result<Frames<'_>, i32> {
+    parse_shifted(data, data, 0)
+}
+
+/// Parse the self-delimited packet at the start of `data`.
+///
+/// Returns the number of bytes it takes up, where the data following it
+/// (the next stream of a multistream packet) starts, and its frames. The
+/// payload offset counts the self-delimiting length field.
+#[cfg(feature = "std")]
+pub fn parse_self_delimited(data: &[u8]) -> Result<(usize, Frames<'_>), i32> {
+    let (packet, consumed) = crate::repacketizer::to_standard_framing(data)?;
+    // Only the length field was dropped, and the frames all follow it
+    let frames = parse_shifted(&packet, data, consumed - packet.len())?;
+    Ok((consumed, frames))
+}
+
+/// Parse `packet`, a copy of `data` with `shift` bytes dropped before the
+/// payload, and borrow the frames from `data`
+fn parse_shifted<'a>(packet: &[u8], data: &'a [u8], shift: usize) -> Result<Frames<'a>, i32> {
+    let len = i32::try_from(packet.len()).map_err(|_| OPUS_BAD_ARG)?;
+    if len == 0 {
+        return Err(OPUS_BAD_ARG);
+    }
+    let mut toc = 0u8;
+    let mut starts = [core::ptr::null(); MAX_FRAMES];
+    let mut sizes = [0i16; MAX_FRAMES];
+    let mut payload_offset = 0;
+    let count = unsafe {
+        opus_packet_parse(
+            packet.as_ptr(),
+            len,
+            &mut toc,
+            starts.as_mut_ptr(),
+            sizes.as_mut_ptr(),
+            &mut payload_offset,
+        )
+    };
+    if count < 0 {
+        return Err(count);
+    }
+
+    let mut frames: [&[u8]; MAX_FRAMES] = [&[]; MAX_FRAMES];
+    for (frame, (&start, &size)) in frames
+        .iter_mut()
+        .zip(starts.iter().zip(&sizes))
+        .take(count as usize)
+    {
+        let offset = start as usize - packet.as_ptr() as usize + shift;
+        *frame = &data[offset..offset + size as usize];
+    }
+    Ok(Frames {
+        toc: parse_toc(toc),
+        payload_offset: payload_offset as usize + shift,
+        frames,
+        count: count as usize,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME_COUNTS: [FrameCount; 4] = [
+        FrameCount::One,
+        FrameCount::TwoEqual,
+        FrameCount::TwoDifferent,
+        FrameCount::Arbitrary,
+    ];
+
+    #[test]
+    fn test_toc_known_values() {
+        // SILK NB 10 ms mono, one frame
+        let silk = Config::new(Mode::SilkOnly, Bandwidth::Narrowband, FrameSize::Ms10).unwrap();
+        assert_eq!(toc(silk, false, FrameCount::One), 0x00);
+
+        // Hybrid FB 20 ms stereo, two equal frames
+        let hybrid = Config::new(Mode::Hybrid, Bandwidth::Fullband, FrameSize::Ms20).unwrap();
+        assert_eq!(hybrid.index(), 15);
+        assert_eq!(toc(hybrid, true, FrameCount::TwoEqual), (15 << 3) | 0x4 | 1);
+
+        // CELT FB 20 ms mono, arbitrary frame count
+        let celt = Config::new(Mode::CeltOnly, Bandwidth::Fullband, FrameSize::Ms20).unwrap();
+        assert_eq!(celt.index(), 31);
+        assert_eq!(toc(celt, false, FrameCount::Arbitrary), 0xFB);
+    }
+
+    #[test]
+    fn test_invalid_configs() {
+        assert!(Config::new(Mode::SilkOnly, Bandwidth::Fullband, FrameSize::Ms20).is_none());
+        assert!(Config::new(Mode::Hybrid, Bandwidth::Wideband, FrameSize::Ms20).is_none());
+        assert!(Config::new(Mode::Hybrid, Bandwidth::Fullband, FrameSize::Ms40).is_none());
+        assert!(Config::new(Mode::CeltOnly, Bandwidth::Mediumband, FrameSize::Ms10).is_none());
+        assert!(Config::new(Mode::CeltOnly, Bandwidth::Fullband, FrameSize::Ms60).is_none());
+        assert!(Config::new(Mode::SilkOnly, Bandwidth::Wideband, FrameSize::Ms5).is_none());
+        assert!(Config::from_index(32).is_none());
+        assert_eq!(Bandwidth::from_opus(OPUS_AUTO), None);
+        assert_eq!(
+            Bandwidth::from_opus(OPUS_BANDWIDTH_FULLBAND as i32 + 1),
+            None
+        );
+    }
+
+    #[test]
+    fn test_all_tocs_roundtrip() {
+        for byte in 0..=u8::MAX {
+            let info = parse_toc(byte);
+            assert_eq!(toc(info.config, info.stereo, info.frame_count), byte);
+
+            let config = info.config;
+            let rebuilt = Config::new(config.mode(), config.bandwidth(), config.frame_size());
+            assert_eq!(rebuilt, Some(config), "config {}", config.index());
+        }
+    }
+
+    #[test]
+    fn test_modes_and_frame_counts_match_libopus() {
+        for mode in [Mode::SilkOnly, Mode::Hybrid, Mode::CeltOnly] {
+            let configs: Vec<Config> = (0..32)
+                .filter_map(Config::from_index)
+                .filter(|c| c.mode() == mode)
+                .collect();
+            assert!(!configs.is_empty());
+
+            for config in configs {
+                for stereo in [false, true] {
+                    for frame_count in FRAME_COUNTS {
+                        let byte = toc(config, stereo, frame_count);
+                        // c=3 carries the frame count in the second byte
+                        let packet = [byte, 3];
+
+                        let info = parse_toc(byte);
+                        assert_eq!(info.config, config);
+                        assert_eq!(info.stereo, stereo);
+                        assert_eq!(info.frame_count, frame_count);
+
+                        unsafe {
+                            let bandwidth = opus_packet_get_bandwidth(packet.as_ptr());
+                            assert_eq!(bandwidth, config.bandwidth().to_opus());
+                            assert_eq!(Bandwidth::from_opus(bandwidth), Some(config.bandwidth()));
+
+                            let channels = opus_packet_get_nb_channels(packet.as_ptr());
+                            assert_eq!(channels, if stereo { 2 } else { 1 });
+
+                            let samples = opus_packet_get_samples_per_frame(packet.as_ptr(), 48000);
+                            assert_eq!(samples as u32, config.frame_size().samples(48000));
+
+                            let frames = opus_packet_get_nb_frames(packet.as_ptr(), 2);
+                            let expected = match frame_count {
+                                FrameCount::One => 1,
+                                FrameCount::TwoEqual | FrameCount::TwoDifferent => 2,
+                                FrameCount::Arbitrary => 3,
+                            };
+                            assert_eq!(frames, expected);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_packet_duration() {
+        let celt_20 = Config::new(Mode::CeltOnly, Bandwidth::Fullband, FrameSize::Ms20).unwrap();
+        let one = [toc(celt_20, false, FrameCount::One), 0xff];
+        assert_eq!(samples(&one, 48000), Ok(960));
+        assert_eq!(samples(&one, 16000), Ok(320));
+        assert_eq!(duration_ms(&one, 48000), Ok(20.0));
+        assert_eq!(rtp_timestamp_increment(&one), Ok(960));
+
+        // Two 10 ms hybrid frames, timestamps still at 48 kHz
+        let hybrid_10 = Config::new(Mode::Hybrid, Bandwidth::Fullband, FrameSize::Ms10).unwrap();
+        let two = [toc(hybrid_10, true, FrameCount::TwoEqual), 0xff];
+        assert_eq!(duration_ms(&two, 24000), Ok(20.0));
+        assert_eq!(rtp_timestamp_increment(&two), Ok(960));
+
+        // Three 20 ms frames
+        let three = [toc(celt_20, false, FrameCount::Arbitrary), 3];
+        assert_eq!(duration_ms(&three, 48000), Ok(60.0));
+        assert_eq!(samples(&three, 44100), Ok(2646));
+        assert_eq!(duration_ms(&three, 44100), Ok(60.0));
+        assert_eq!(rtp_timestamp_increment(&three), Ok(2880));
+
+        // Six 2.5 ms frames
+        let celt_2_5 = Config::new(Mode::CeltOnly, Bandwidth::Wideband, FrameSize::Ms2_5).unwrap();
+        let six = [toc(celt_2_5, false, FrameCount::Arbitrary), 6];
+        assert_eq!(duration_ms(&six, 48000), Ok(15.0));
+    }
+
+    #[test]
+    fn test_packet_duration_invalid() {
+        let celt_20 = Config::new(Mode::CeltOnly, Bandwidth::Fullband, FrameSize::Ms20).unwrap();
+        let code_3 = toc(celt_20, false, FrameCount::Arbitrary);
+        assert_eq!(samples(&[], 48000), Err(OPUS_BAD_ARG));
+        assert_eq!(samples(&[code_3, 1], 0), Err(OPUS_BAD_ARG));
+        // No frame count byte
+        assert_eq!(samples(&[code_3], 48000), Err(OPUS_INVALID_PACKET));
+        // Seven 20 ms frames are over 120 ms
+        assert_eq!(duration_ms(&[code_3, 7], 48000), Err(OPUS_INVALID_PACKET));
+        assert_eq!(rtp_timestamp_increment(&[code_3, 6]), Ok(5760));
+    }
+
+    #[test]
+    fn test_is_dtx_packet() {
+        let silk_20 = Config::new(Mode::SilkOnly, Bandwidth::Wideband, FrameSize::Ms20).unwrap();
+        assert!(is_dtx_packet(&[toc(silk_20, false, FrameCount::One)]));
+        // Code 2 with an empty first frame
+        assert!(is_dtx_packet(&[
+            toc(silk_20, false, FrameCount::TwoDifferent),
+            0
+        ]));
+        assert!(!is_dtx_packet(&[]));
+
+        unsafe {
+            let mut error: i32 = 0;
+            let encoder = opus_encoder_create(16000, 1, OPUS_APPLICATION_VOIP as i32, &mut error);
+            assert_eq!(error, OPUS_OK as i32, "Failed to create encoder");
+            let input = crate::test_util::noise::<i16>(1, 320);
+            let mut packet = [0u8; max_packet_size(1, FrameDuration::Ms20)];
+            let len = opus_encode(
+                encoder,
+                input.as_ptr(),
+                320,
+                packet.as_mut_ptr(),
+                packet.len() as i32,
+            );
+            opus_encoder_destroy(encoder);
+            assert!(len > 2, "Encoding failed: {}", len);
+            assert!(!is_dtx_packet(&packet[..len as usize]));
+        }
+    }
+
+    /// A code 3 packet with one 10 byte CELT frame and `padding`
+    #[cfg(feature = "dred")]
+    fn padded(padding: &[u8]) -> Vec<u8> {
+        let celt_20 = Config::new(Mode::CeltOnly, Bandwidth::Fullband, FrameSize::Ms20).unwrap();
+        let mut packet = vec![toc(celt_20, false, FrameCount::Arbitrary), 0x40 | 1];
+        packet.push(padding.len() as u8);
+        packet.extend([0x5a; 10]);
+        packet.extend_from_slice(padding);
+        packet
+    }
+
+    #[test]
+    #[cfg(feature = "dred")]
+    fn test_has_dred() {
+        // Long extension 126 with a length: `D`, the version, a payload
+        assert!(has_dred(&padded(&[253, 4, b'D', 12, 0x55, 0x66])));
+        // Without a length, after a short extension
+        assert!(has_dred(&padded(&[11, 0, 252, b'D', 12, 0x55])));
+
+        assert!(!has_dred(&padded(&[])));
+        // Another DRED version, another extension, no payload
+        assert!(!has_dred(&padded(&[253, 4, b'D', 11, 0x55, 0x66])));
+        assert!(!has_dred(&padded(&[251, 4, b'D', 12, 0x55, 0x66])));
+        assert!(!has_dred(&padded(&[253, 2, b'D', 12])));
+        // Truncated, and no packet at all
+        assert!(!has_dred(&padded(&[253, 9, b'D', 12, 0x55, 0x66])));
+        assert!(!has_dred(&[]));
+        // A packet without padding
+        let silk_20 = Config::new(Mode::SilkOnly, Bandwidth::Wideband, FrameSize::Ms20).unwrap();
+        assert!(!has_dred(&[toc(silk_20, false, FrameCount::One), 1, 2, 3]));
+    }
+
+    /// DRED from the encoder, which needs the weights in `target/model`
+    #[test]
+    #[cfg(all(feature = "dred", not(feature = "no-float-api")))]
+    fn test_has_dred_encoded() {
+        const FRAME_SIZE: usize = 320;
+        let Some(weights) = crate::test_support::load_dnn_weights() else {
+            println!("No DNN weights in target/model, skipping");
+            return;
+        };
+        let input = crate::test_util::speech_like::<i16>(1, 16000, FRAME_SIZE * 20);
+        for duration in [0, 100] {
+            unsafe {
+                let mut error: i32 = 0;
+                let encoder =
+                    opus_encoder_create(16000, 1, OPUS_APPLICATION_VOIP as i32, &mut error);
+                assert_eq!(error, OPUS_OK as i32, "Failed to create encoder");
+                let ret = opus_encoder_ctl(
+                    encoder,
+                    OPUS_SET_DNN_BLOB_REQUEST as i32,
+                    weights.as_ptr() as *const core::ffi::c_void,
+                    weights.len() as i32,
+                );
+                assert_eq!(ret, OPUS_OK as i32, "Failed to load DNN weights");
+                opus_encoder_ctl(encoder, OPUS_SET_BITRATE_REQUEST as i32, 32000);
+                opus_encoder_ctl(encoder, OPUS_SET_PACKET_LOSS_PERC_REQUEST as i32, 20);
+                opus_encoder_ctl(encoder, OPUS_SET_DRED_DURATION_REQUEST as i32, duration);
+
+                let mut with_dred = 0;
+                let mut packet = [0u8; max_packet_size(1, FrameDuration::Ms20)];
+                for frame in input.chunks_exact(FRAME_SIZE) {
+                    let len = opus_encode(
+                        encoder,
+                        frame.as_ptr(),
+                        FRAME_SIZE as i32,
+                        packet.as_mut_ptr(),
+                        packet.len() as i32,
+                    );
+                    assert!(len > 0, "Encoding failed: {}", len);
+                    with_dred += usize::from(has_dred(&packet[..len as usize]));
+                }
+                opus_encoder_destroy(encoder);
+                if duration == 0 {
+                    assert_eq!(with_dred, 0);
+                } else {
+                    // Every packet once the encoder has warmed up
+                    assert!(with_dred >= 15, "{} packets with DRED", with_dred);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_frame_duration() {
+        for (samples, duration) in [
+            (120, FrameDuration::Ms2_5),
+            (960, FrameDuration::Ms20),
+            (5760, FrameDuration::Ms120),
+        ] {
+            assert_eq!(FrameDuration::from_samples(samples, 48000), Some(duration));
+            assert_eq!(duration.samples(48000), samples as u32);
+        }
+        assert_eq!(
+            FrameDuration::from_samples(160, 8000),
+            Some(FrameDuration::Ms20)
+        );
+        assert_eq!(FrameDuration::from_samples(1440, 48000), None);
+        assert_eq!(FrameDuration::from_samples(100, 48000), None);
+        assert_eq!(FrameDuration::from_samples(960, 0), None);
+        assert_eq!(FrameDuration::Ms60.to_opus(), OPUS_FRAMESIZE_60_MS as i32);
+        assert_eq!(FrameDuration::Ms10.max_frames(), 1);
+        assert_eq!(FrameDuration::Ms100.max_frames(), 5);
+    }
+
+    #[test]
+    fn test_max_packet_size() {
+        assert_eq!(max_packet_size(1, FrameDuration::Ms2_5), 1276);
+        assert_eq!(max_packet_size(2, FrameDuration::Ms40), 2 + 2 + 2 * 1275);
+        assert_eq!(max_packet_size(0, FrameDuration::Ms20), 0);
+        // Three self-delimited streams
+        assert_eq!(max_packet_size(3, FrameDuration::Ms20), 3 * 1276 + 2 * 2);
+
+        // At the maximum bitrate noise fills every frame. CBR would pad
+        // multi-frame packets to the whole buffer, so stay with VBR.
+        for duration in [
+            FrameDuration::Ms20,
+            FrameDuration::Ms60,
+            FrameDuration::Ms120,
+        ] {
+            let frame_size = duration.samples(48000) as usize;
+            let max = max_packet_size(2, duration);
+            unsafe {
+                let mut error: i32 = 0;
+                let encoder =
+                    opus_encoder_create(48000, 2, OPUS_APPLICATION_AUDIO as i32, &mut error);
+                assert_eq!(error, OPUS_OK as i32, "Failed to create encoder");
+                opus_encoder_ctl(encoder, OPUS_SET_BITRATE_REQUEST as i32, OPUS_BITRATE_MAX);
+
+                let input = crate::test_util::noise::<i16>(1, frame_size * 2);
+                let mut packet = vec![0u8; max * 2];
+                let len = opus_encode(
+                    encoder,
+                    input.as_ptr(),
+                    frame_size as i32,
+                    packet.as_mut_ptr(),
+                    packet.len() as i32,
+                );
+                opus_encoder_destroy(encoder);
+                assert!(len > 0, "Encoding failed: {}", len);
+                assert!(len as usize <= max, "{:?}: {} > {}", duration, len, max);
+                if duration == FrameDuration::Ms120 {
+                    assert!(len > 4000, "{}", len);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        // Code 2: a 1-byte frame, then a 3-byte one
+        let frames = parse(&[0x0a, 1, 9, 7, 7, 7]).unwrap();
+        assert_eq!(frames.toc.frame_count, FrameCount::TwoDifferent);
+        assert_eq!(frames.payload_offset, 2);
+        assert_eq!(frames.frames(), [&[9][..], &[7, 7, 7]]);
+
+        // Code 3 CBR with a padding byte
+        let frames = parse(&[0x0b, 0x42, 1, 5, 5, 6, 6, 0]).unwrap();
+        assert_eq!(frames.frames(), [&[5, 5][..], &[6, 6]]);
+
+        assert_eq!(parse(&[]), Err(OPUS_BAD_ARG));
+        assert_eq!(parse(&[0x03, 0x00]), Err(OPUS_INVALID_PACKET));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_parse_self_delimited() {
+        let rest = [0xaa, 0xbb];
+
+        // Code 0: the length field sits between the TOC and the frame
+        let data = [0x08, 3, 1, 2, 3, 0xaa, 0xbb];
+        let (consumed, frames) = parse_self_delimited(&data).unwrap();
+        assert_eq!(&data[consumed..], rest);
+        assert_eq!(frames.payload_offset, 2);
+        assert_eq!(frames.frames(), [&[1, 2, 3][..]]);
+        // The standard framing reads the field as the frame
+        assert_eq!(parse(&data[..consumed]).unwrap().frames().len(), 1);
+        assert_eq!(parse(&data[..consumed]).unwrap().frames()[0][0], 3);
+
+        // Code 3 VBR: frame lengths, then the last frame's
+        let data = [0x0b, 0x83, 1, 2, 3, 4, 5, 5, 6, 6, 6, 0xaa, 0xbb];
+        let (consumed, frames) = parse_self_delimited(&data).unwrap();
+        assert_eq!(&data[consumed..], rest);
+        assert_eq!(frames.payload_offset, 5);
+        assert_eq!(frames.frames(), [&[4][..], &[5, 5], &[6, 6, 6]]);
+
+        assert!(parse_self_delimited(&[0x08, 10, 1]).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_roundtrip() {
+        let info = parse_toc(toc(
+            Config::new(Mode::Hybrid, Bandwidth::Fullband, FrameSize::Ms20).unwrap(),
+            true,
+            FrameCount::TwoEqual,
+        ));
+        let json = serde_json::to_string(&info).unwrap();
+        assert_eq!(
+            json,
+            r#"{"config":15,"stereo":true,"frame_count":"TwoEqual"}"#
+        );
+        assert_eq!(serde_json::from_str::<TocInfo>(&json).unwrap(), info);
+
+        assert_eq!(
+            serde_json::from_str::<Bandwidth>(r#""SuperWideband""#).unwrap(),
+            Bandwidth::SuperWideband
+        );
+        assert!(serde_json::from_str::<Config>("32").is_err());
+    }
+}
@@ -0,0 +1,112 @@
+//! Duration of the last packet (`OPUS_GET_LAST_PACKET_DURATION`)
+//!
+//! The decoder tracks how many samples per channel the last packet it
+//! decoded or concealed produced, e.g. for jitter buffer accounting without
+//! parsing the packet again. To conceal a lost packet for as long as the
+//! last one, decode `None` with `decoder::Decoder`, which keeps the size
+//! of the last packet decoded.
+
+use crate::bindings::*;
+
+/// `OPUS_GET_LAST_PACKET_DURATION`: samples per channel, at the decoder's
+/// sample rate, of the last packet decoded or concealed.
+///
+/// For jitter buffer accounting without parsing the packet again. 0 before
+/// anything was decoded and after `OPUS_RESET_STATE`.
+///
+/// # Safety
+///
+/// `decoder` must be a valid decoder.
+pub unsafe fn last_packet_duration(decoder: *mut OpusDecoder) -> Result<i32, i32> {
+    let mut duration: i32 = 0;
+    let ret = opus_decoder_ctl(
+        decoder,
+        OPUS_GET_LAST_PACKET_DURATION_REQUEST as i32,
+        &mut duration as *mut i32,
+    );
+    if ret != OPUS_OK as i32 {
+        return Err(ret);
+    }
+    Ok(duration)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: i32 = 48000;
+
+    /// Mono packets of the given durations in samples
+    fn encode(frame_sizes: &[usize]) -> Vec<Vec<u8>> {
+        unsafe {
+            let mut error: i32 = 0;
+            let encoder =
+                opus_encoder_create(SAMPLE_RATE, 1, OPUS_APPLICATION_AUDIO as i32, &mut error);
+            assert_eq!(error, OPUS_OK as i32, "Failed to create encoder");
+
+            let packets = frame_sizes
+                .iter()
+                .map(|&frame_size| {
+                    let input: Vec<i16> = (0..frame_size)
+                        .map(|i| {
+                            let t = i as f32 / SAMPLE_RATE as f32;
+                            ((t * 440.0 * std::f32::consts::TAU).sin() * 8000.0) as i16
+                        })
+                        .collect();
+                    let mut packet = vec![0u8; 1275];
+                    let len = opus_encode(
+                        encoder,
+                        input.as_ptr(),
+                        frame_size as i32,
+                        packet.as_mut_ptr(),
+                        packet.len() as i32,
+                    );
+                    assert!(len > 0, "Encoding failed: {}", len);
+                    packet.truncate(len as usize);
+                    packet
+                })
+                .collect();
+
+            opus_encoder_destroy(encoder);
+            packets
+        }
+    }
+
+    /// `opus_decode` of `packet`, or concealment of `frame_size` samples
+    unsafe fn decode(decoder: *mut OpusDecoder, packet: Option<&[u8]>, frame_size: usize) -> i32 {
+        let mut pcm = vec![0i16; 5760];
+        let (data, len) = match packet {
+            Some(packet) => (packet.as_ptr(), packet.len() as i32),
+            None => (core::ptr::null(), 0),
+        };
+        opus_decode(decoder, data, len, pcm.as_mut_ptr(), frame_size as i32, 0)
+    }
+
+    #[test]
+    fn test_last_packet_duration() {
+        let packets = encode(&[960, 480, 2880]);
+        unsafe {
+            let mut error: i32 = 0;
+            let decoder = opus_decoder_create(SAMPLE_RATE, 1, &mut error);
+            assert_eq!(error, OPUS_OK as i32, "Failed to create decoder");
+            assert_eq!(last_packet_duration(decoder), Ok(0));
+
+            // Whatever was decoded last, concealment included
+            for packet in &packets {
+                let samples = decode(decoder, Some(packet), 5760);
+                assert_eq!(last_packet_duration(decoder), Ok(samples));
+            }
+            assert_eq!(last_packet_duration(decoder), Ok(2880));
+            assert_eq!(decode(decoder, None, 240), 240);
+            assert_eq!(last_packet_duration(decoder), Ok(240));
+
+            assert_eq!(
+                opus_decoder_ctl(decoder, OPUS_RESET_STATE as i32),
+                OPUS_OK as i32
+            );
+            assert_eq!(last_packet_duration(decoder), Ok(0));
+
+            opus_decoder_destroy(decoder);
+        }
+    }
+}
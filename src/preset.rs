@@ -0,0 +1,247 @@
+//! Encoder settings for common use cases
+//!
+//! Of the dozen encoder ctls, a handful decide whether an encoder suits a
+//! voice call, music or a latency-critical link. A [`Preset`] bundles
+//! them; [`Preset::create`] makes an encoder with them and
+//! [`Preset::apply`] sets them on an existing one, to be tweaked with
+//! `opus_encoder_ctl` afterwards:
+//!
+//! | Setting | [`Voip`](Preset::Voip) | [`Music`](Preset::Music) | [`LowLatency`](Preset::LowLatency) |
+//! |---|---|---|---|
+//! | Application | `OPUS_APPLICATION_VOIP` | `OPUS_APPLICATION_AUDIO` | `OPUS_APPLICATION_RESTRICTED_LOWDELAY` |
+//! | `OPUS_SET_SIGNAL` | `OPUS_SIGNAL_VOICE` | `OPUS_SIGNAL_MUSIC` | `OPUS_AUTO` |
+//! | `OPUS_SET_COMPLEXITY` | 9 | 10 | 5 |
+//! | `OPUS_SET_INBAND_FEC` | 1 | 0 | 0 |
+//! | `OPUS_SET_PACKET_LOSS_PERC` | 10 | 0 | 0 |
+//! | `OPUS_SET_DTX` | 1 | 0 | 0 |
+//! | `OPUS_SET_PREDICTION_DISABLED` | 0 | 0 | 1 |
+//! | Frame duration ([`frame_ms`](Preset::frame_ms)) | 20 ms | 20 ms | 10 ms |
+//!
+//! Everything else, notably the bitrate, keeps the encoder default. The
+//! low-latency preset is CELT only, which has no in-band FEC; disabling
+//! inter-frame prediction instead makes every packet decodable on its own,
+//! so a loss costs one frame, at a higher bitrate for the same quality.
+//!
+//! ```
+//! use opus_head_sys::preset::Preset;
+//! use opus_head_sys::*;
+//!
+//! unsafe {
+//!     let encoder = Preset::Voip.create(48000, 1).unwrap();
+//!     // Start from the preset, then adjust
+//!     opus_encoder_ctl(encoder, OPUS_SET_BITRATE_REQUEST as i32, 24000);
+//!     opus_encoder_destroy(encoder);
+//! }
+//! ```
+
+use crate::bindings::*;
+use crate::signal::{set_signal, Signal};
+
+/// A bundle of encoder settings, see the [module docs](self)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Preset {
+    /// Voice calls: SILK-friendly speech coding with FEC and DTX
+    Voip,
+    /// Music streaming: full quality, no bits spent on redundancy
+    Music,
+    /// Interactive audio over a low-latency path: CELT only, 10 ms frames
+    LowLatency,
+}
+
+impl Preset {
+    /// Application passed to `opus_encoder_create`
+    pub fn application(self) -> i32 {
+        match self {
+            Preset::Voip => OPUS_APPLICATION_VOIP as i32,
+            Preset::Music => OPUS_APPLICATION_AUDIO as i32,
+            Preset::LowLatency => OPUS_APPLICATION_RESTRICTED_LOWDELAY as i32,
+        }
+    }
+
+    pub fn signal(self) -> Signal {
+        match self {
+            Preset::Voip => Signal::Voice,
+            Preset::Music => Signal::Music,
+            Preset::LowLatency => Signal::Auto,
+        }
+    }
+
+    pub fn complexity(self) -> i32 {
+        match self {
+            Preset::Voip => 9,
+            Preset::Music => 10,
+            Preset::LowLatency => 5,
+        }
+    }
+
+    /// `OPUS_SET_INBAND_FEC`, with [`packet_loss_perc`](Self::packet_loss_perc)
+    /// as the loss it prepares for
+    pub fn inband_fec(self) -> bool {
+        self == Preset::Voip
+    }
+
+    /// `OPUS_SET_PACKET_LOSS_PERC`, which sizes the FEC data
+    pub fn packet_loss_perc(self) -> i32 {
+        match self {
+            Preset::Voip => 10,
+            Preset::Music | Preset::LowLatency => 0,
+        }
+    }
+
+    pub fn dtx(self) -> bool {
+        self == Preset::Voip
+    }
+
+    /// `OPUS_SET_PREDICTION_DISABLED`
+    pub fn prediction_disabled(self) -> bool {
+        self == Preset::LowLatency
+    }
+
+    /// Suggested frame duration in milliseconds
+    pub fn frame_ms(self) -> usize {
+        match self {
+            Preset::Voip | Preset::Music => 20,
+            Preset::LowLatency => 10,
+        }
+    }
+
+    /// Create an encoder with `opus_encoder_create` and apply the preset.
+    /// Returns the Opus error code on failure; the caller destroys the
+    /// encoder with `opus_encoder_destroy`.
+    pub fn create(self, sample_rate: i32, channels: usize) -> Result<*mut OpusEncoder, i32> {
+        let mut error: i32 = 0;
+        let encoder = unsafe {
+            opus_encoder_create(sample_rate, channels as i32, self.application(), &mut error)
+        };
+        if error != OPUS_OK as i32 {
+            return Err(error);
+        }
+        if encoder.is_null() {
+            return Err(OPUS_ALLOC_FAIL);
+        }
+        if let Err(e) = unsafe { self.apply(encoder) } {
+            unsafe { opus_encoder_destroy(encoder) };
+            return Err(e);
+        }
+        Ok(encoder)
+    }
+
+    /// Set the preset's ctls on `encoder`, including the application, which
+    /// only succeeds before the first `opus_encode` call. Returns the Opus
+    /// error code of the first ctl that fails.
+    ///
+    /// # Safety
+    ///
+    /// `encoder` must be a valid encoder.
+    pub unsafe fn apply(self, encoder: *mut OpusEncoder) -> Result<(), i32> {
+        let settings = [
+            (OPUS_SET_APPLICATION_REQUEST, self.application()),
+            (OPUS_SET_COMPLEXITY_REQUEST, self.complexity()),
+            (OPUS_SET_INBAND_FEC_REQUEST, self.inband_fec() as i32),
+            (OPUS_SET_PACKET_LOSS_PERC_REQUEST, self.packet_loss_perc()),
+            (OPUS_SET_DTX_REQUEST, self.dtx() as i32),
+            (
+                OPUS_SET_PREDICTION_DISABLED_REQUEST,
+                self.prediction_disabled() as i32,
+            ),
+        ];
+        for (request, value) in settings {
+            let ret = opus_encoder_ctl(encoder, request as i32, value);
+            if ret != OPUS_OK as i32 {
+                return Err(ret);
+            }
+        }
+        set_signal(encoder, self.signal())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PRESETS: [Preset; 3] = [Preset::Voip, Preset::Music, Preset::LowLatency];
+
+    unsafe fn get(encoder: *mut OpusEncoder, request: u32) -> i32 {
+        let mut value: i32 = 0;
+        let ret = opus_encoder_ctl(encoder, request as i32, &mut value as *mut i32);
+        assert_eq!(ret, OPUS_OK as i32);
+        value
+    }
+
+    #[test]
+    fn test_create() {
+        for preset in PRESETS {
+            unsafe {
+                let encoder = preset.create(48000, 2).unwrap();
+                assert_eq!(
+                    get(encoder, OPUS_GET_APPLICATION_REQUEST),
+                    preset.application()
+                );
+                assert_eq!(
+                    get(encoder, OPUS_GET_SIGNAL_REQUEST),
+                    i32::from(preset.signal())
+                );
+                assert_eq!(
+                    get(encoder, OPUS_GET_COMPLEXITY_REQUEST),
+                    preset.complexity()
+                );
+                assert_eq!(
+                    get(encoder, OPUS_GET_INBAND_FEC_REQUEST),
+                    preset.inband_fec() as i32
+                );
+                assert_eq!(
+                    get(encoder, OPUS_GET_PACKET_LOSS_PERC_REQUEST),
+                    preset.packet_loss_perc()
+                );
+                assert_eq!(get(encoder, OPUS_GET_DTX_REQUEST), preset.dtx() as i32);
+                assert_eq!(
+                    get(encoder, OPUS_GET_PREDICTION_DISABLED_REQUEST),
+                    preset.prediction_disabled() as i32
+                );
+                opus_encoder_destroy(encoder);
+            }
+        }
+        assert_eq!(Preset::Voip.create(44100, 1), Err(OPUS_BAD_ARG));
+    }
+
+    #[test]
+    fn test_apply_and_encode() {
+        const SAMPLE_RATE: i32 = 48000;
+        for preset in PRESETS {
+            let frame_size = SAMPLE_RATE as usize * preset.frame_ms() / 1000;
+            unsafe {
+                let mut error: i32 = 0;
+                let encoder =
+                    opus_encoder_create(SAMPLE_RATE, 1, OPUS_APPLICATION_AUDIO as i32, &mut error);
+                assert_eq!(error, OPUS_OK as i32, "Failed to create encoder");
+                preset.apply(encoder).unwrap();
+                assert_eq!(
+                    get(encoder, OPUS_GET_APPLICATION_REQUEST),
+                    preset.application()
+                );
+
+                let input = crate::test_util::noise::<i16>(1, frame_size);
+                let mut packet = [0u8; 1275];
+                let len = opus_encode(
+                    encoder,
+                    input.as_ptr(),
+                    frame_size as i32,
+                    packet.as_mut_ptr(),
+                    packet.len() as i32,
+                );
+                assert!(len > 0, "{:?}: encoding failed: {}", preset, len);
+                if preset == Preset::LowLatency {
+                    // CELT only
+                    assert!(packet[0] & 0x80 != 0);
+                }
+
+                // The application is fixed once encoding started
+                if preset != Preset::Voip {
+                    assert_eq!(Preset::Voip.apply(encoder), Err(OPUS_BAD_ARG));
+                }
+                opus_encoder_destroy(encoder);
+            }
+        }
+    }
+}
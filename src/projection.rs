@@ -0,0 +1,324 @@
+//! Ambisonics decoding straight to a playback layout
+//!
+//! A projection stream (mapping family 3, RFC 8486) carries ambisonics
+//! mixed into Opus streams. The decoder needs the encoder's demixing
+//! matrix, read with `OPUS_PROJECTION_GET_DEMIXING_MATRIX` and sent to the
+//! receiver out of band (it is the channel mapping table of an Ogg Opus
+//! header). [`demixing_matrix`] fetches it; `opus_projection_decoder_create`
+//! with it outputs the ambisonic channels in ACN order with SN3D
+//! normalization, followed by the two non-diegetic channels if the stream
+//! has them.
+//!
+//! That matrix only undoes the encoder's mixing; libopus doesn't render to
+//! speakers or headphones. [`decode_to_layout`] adds a virtual microphone
+//! stereo render for listeners without a spatial renderer. Binaural output
+//! needs HRTFs and belongs in a renderer on top of [`Layout::Ambisonics`].
+
+use crate::bindings::*;
+
+/// Output channel layout for [`decode_to_layout`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    /// The decoder's channels unchanged: ambisonics (ACN/SN3D), then any
+    /// non-diegetic stereo pair
+    Ambisonics,
+    /// Two cardioids at ±90° from the first-order components, with the
+    /// non-diegetic channels added to their side
+    Stereo,
+}
+
+impl Layout {
+    /// Output channels for a decoder with `channels` channels
+    pub fn channels(self, channels: usize) -> usize {
+        match self {
+            Layout::Ambisonics => channels,
+            Layout::Stereo => 2,
+        }
+    }
+}
+
+/// The demixing matrix of `encoder`, to create the matching decoder with.
+///
+/// # Safety
+///
+/// `encoder` must be a valid projection encoder.
+pub unsafe fn demixing_matrix(encoder: *mut OpusProjectionEncoder) -> Result<Vec<u8>, i32> {
+    let mut size: i32 = 0;
+    let ret = opus_projection_encoder_ctl(
+        encoder,
+        OPUS_PROJECTION_GET_DEMIXING_MATRIX_SIZE_REQUEST as i32,
+        &mut size as *mut i32,
+    );
+    if ret != OPUS_OK as i32 {
+        return Err(ret);
+    }
+
+    let mut matrix = vec![0u8; size as usize];
+    let ret = opus_projection_encoder_ctl(
+        encoder,
+        OPUS_PROJECTION_GET_DEMIXING_MATRIX_REQUEST as i32,
+        matrix.as_mut_ptr(),
+        size,
+    );
+    if ret != OPUS_OK as i32 {
+        return Err(ret);
+    }
+    Ok(matrix)
+}
+
+/// Decode `data` with `opus_projection_decode` and render it to `layout`.
+///
+/// `channels` is the decoder's channel count. `pcm` holds interleaved
+/// output in `layout`; its length sets the maximum frame size. An empty
+/// `data` runs packet loss concealment. Returns the samples per channel
+/// written, or `OPUS_BAD_ARG` for [`Layout::Stereo`] with fewer than the 4
+/// first-order channels.
+///
+/// # Safety
+///
+/// `decoder` must be a valid projection decoder created for `channels`
+/// output channels.
+pub unsafe fn decode_to_layout(
+    decoder: *mut OpusProjectionDecoder,
+    data: &[u8],
+    channels: usize,
+    layout: Layout,
+    pcm: &mut [i16],
+) -> Result<usize, i32> {
+    decode_with(data, channels, layout, pcm, |data, len, out, frame_size| {
+        opus_projection_decode(decoder, data, len, out, frame_size, 0)
+    })
+}
+
+/// [`decode_to_layout`] with `opus_projection_decode_float`.
+///
+/// # Safety
+///
+/// `decoder` must be a valid projection decoder created for `channels`
+/// output channels.
+#[cfg(not(feature = "no-float-api"))]
+pub unsafe fn decode_to_layout_float(
+    decoder: *mut OpusProjectionDecoder,
+    data: &[u8],
+    channels: usize,
+    layout: Layout,
+    pcm: &mut [f32],
+) -> Result<usize, i32> {
+    decode_with(data, channels, layout, pcm, |data, len, out, frame_size| {
+        opus_projection_decode_float(decoder, data, len, out, frame_size, 0)
+    })
+}
+
+/// Samples [`render`] can mix
+trait Sample: Copy + Default {
+    fn to_f32(self) -> f32;
+    fn from_f32(value: f32) -> Self;
+}
+
+impl Sample for i16 {
+    fn to_f32(self) -> f32 {
+        f32::from(self)
+    }
+
+    fn from_f32(value: f32) -> Self {
+        // `as` saturates
+        value.round() as i16
+    }
+}
+
+impl Sample for f32 {
+    fn to_f32(self) -> f32 {
+        self
+    }
+
+    fn from_f32(value: f32) -> Self {
+        value
+    }
+}
+
+/// Run `decode(data, len, out, frame_size)` into `pcm` directly or through
+/// a buffer with all decoder channels
+fn decode_with<T: Sample>(
+    data: &[u8],
+    channels: usize,
+    layout: Layout,
+    pcm: &mut [T],
+    decode: impl FnOnce(*const u8, i32, *mut T, i32) -> i32,
+) -> Result<usize, i32> {
+    if channels == 0 || data.len() > i32::MAX as usize {
+        return Err(OPUS_BAD_ARG);
+    }
+    if layout == Layout::Stereo && channels < 4 {
+        return Err(OPUS_BAD_ARG);
+    }
+    let frame_size = (pcm.len() / layout.channels(channels)).min(i32::MAX as usize);
+    let data_ptr = if data.is_empty() {
+        core::ptr::null()
+    } else {
+        data.as_ptr()
+    };
+
+    if layout == Layout::Ambisonics {
+        let ret = decode(
+            data_ptr,
+            data.len() as i32,
+            pcm.as_mut_ptr(),
+            frame_size as i32,
+        );
+        return if ret < 0 { Err(ret) } else { Ok(ret as usize) };
+    }
+
+    let mut decoded = vec![T::default(); frame_size * channels];
+    let ret = decode(
+        data_ptr,
+        data.len() as i32,
+        decoded.as_mut_ptr(),
+        frame_size as i32,
+    );
+    if ret < 0 {
+        return Err(ret);
+    }
+    let samples = ret as usize;
+    render(&decoded[..samples * channels], channels, pcm);
+    Ok(samples)
+}
+
+/// Mix interleaved ambisonic frames down to interleaved stereo in `out`
+fn render<T: Sample>(decoded: &[T], channels: usize, out: &mut [T]) {
+    // (order + 1)^2 ambisonic channels, plus 2 non-diegetic ones or not
+    let non_diegetic = !is_square(channels);
+    for (frame, out) in decoded.chunks_exact(channels).zip(out.chunks_exact_mut(2)) {
+        // ACN order: W, Y, Z, X. Y points left.
+        let w = frame[0].to_f32();
+        let y = frame[1].to_f32();
+        let (mut left, mut right) = (0.5 * (w + y), 0.5 * (w - y));
+        if non_diegetic {
+            left += frame[channels - 2].to_f32();
+            right += frame[channels - 1].to_f32();
+        }
+        out[0] = T::from_f32(left);
+        out[1] = T::from_f32(right);
+    }
+}
+
+fn is_square(n: usize) -> bool {
+    (0..=n).take_while(|i| i * i <= n).any(|i| i * i == n)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packet::{max_packet_size, FrameDuration};
+
+    const SAMPLE_RATE: i32 = 48000;
+    const FRAME_SIZE: usize = 960;
+    const CHANNELS: usize = 4;
+
+    /// First-order ambisonics of a tone from the left, as packets and the
+    /// demixing matrix
+    fn encode_left(frames: usize) -> (Vec<Vec<u8>>, Vec<u8>, i32, i32) {
+        unsafe {
+            let mut streams = 0;
+            let mut coupled = 0;
+            let mut error = 0;
+            let encoder = opus_projection_ambisonics_encoder_create(
+                SAMPLE_RATE,
+                CHANNELS as i32,
+                3,
+                &mut streams,
+                &mut coupled,
+                OPUS_APPLICATION_AUDIO as i32,
+                &mut error,
+            );
+            assert_eq!(error, OPUS_OK as i32, "Failed to create encoder");
+            let matrix = demixing_matrix(encoder).unwrap();
+            assert!(!matrix.is_empty());
+
+            let packets = (0..frames)
+                .map(|frame| {
+                    let input: Vec<i16> = (0..FRAME_SIZE * CHANNELS)
+                        .map(|i| {
+                            let n = frame * FRAME_SIZE + i / CHANNELS;
+                            let t = n as f32 / SAMPLE_RATE as f32;
+                            let s = (t * 440.0 * std::f32::consts::TAU).sin() * 8000.0;
+                            // W and Y only: the source sits at +90°
+                            match i % CHANNELS {
+                                0 | 1 => s as i16,
+                                _ => 0,
+                            }
+                        })
+                        .collect();
+                    let mut packet = vec![0u8; max_packet_size(CHANNELS, FrameDuration::Ms20)];
+                    let len = opus_projection_encode(
+                        encoder,
+                        input.as_ptr(),
+                        FRAME_SIZE as i32,
+                        packet.as_mut_ptr(),
+                        packet.len() as i32,
+                    );
+                    assert!(len > 0, "Encoding failed: {}", len);
+                    packet.truncate(len as usize);
+                    packet
+                })
+                .collect();
+
+            opus_projection_encoder_destroy(encoder);
+            (packets, matrix, streams, coupled)
+        }
+    }
+
+    #[test]
+    fn test_stereo_from_left() {
+        let (packets, matrix, streams, coupled) = encode_left(10);
+        unsafe {
+            let mut error = 0;
+            let decoder = opus_projection_decoder_create(
+                SAMPLE_RATE,
+                CHANNELS as i32,
+                streams,
+                coupled,
+                matrix.as_ptr() as *mut u8,
+                matrix.len() as i32,
+                &mut error,
+            );
+            assert_eq!(error, OPUS_OK as i32, "Failed to create decoder");
+
+            let mut pcm = vec![0i16; FRAME_SIZE * 2];
+            let (mut left, mut right) = (0f64, 0f64);
+            for packet in &packets {
+                let samples = decode_to_layout(decoder, packet, CHANNELS, Layout::Stereo, &mut pcm);
+                assert_eq!(samples, Ok(FRAME_SIZE));
+                for frame in pcm.chunks_exact(2) {
+                    left += f64::from(frame[0]).powi(2);
+                    right += f64::from(frame[1]).powi(2);
+                }
+            }
+            assert!(left > 10.0 * right, "left {} right {}", left, right);
+
+            // Concealment, and all channels unchanged
+            let mut pcm = vec![0i16; FRAME_SIZE * CHANNELS];
+            assert_eq!(
+                decode_to_layout(decoder, &[], CHANNELS, Layout::Ambisonics, &mut pcm),
+                Ok(FRAME_SIZE)
+            );
+
+            opus_projection_decoder_destroy(decoder);
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        // W, Y, Z, X, then the non-diegetic pair
+        let frame = [100i16, 60, 7, 9, 1000, -1000];
+        let mut out = [0i16; 2];
+        render(&frame, 6, &mut out);
+        assert_eq!(out, [1080, -980]);
+        render(&frame[..4], 4, &mut out);
+        assert_eq!(out, [80, 20]);
+
+        assert!(is_square(1) && is_square(4) && is_square(9) && is_square(16));
+        assert!(!is_square(6) && !is_square(11));
+        assert_eq!(Layout::Stereo.channels(11), 2);
+        assert_eq!(Layout::Ambisonics.channels(11), 11);
+    }
+}
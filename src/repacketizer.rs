@@ -0,0 +1,345 @@
+//! Splitting multi-frame packets into single-frame packets
+//!
+//! A packet may carry up to 120 ms in several frames (TOC codes 1 to 3).
+//! [`split`] unpacks it with `opus_repacketizer_out_range`, one packet per
+//! frame, e.g. for an SFU that forwards 20 ms packets to one receiver and
+//! recombines them into 60 ms packets for another.
+//!
+//! Each stream but the last in a multistream packet uses the
+//! self-delimiting framing of RFC 6716, Appendix B, which the repacketizer
+//! API does not accept. [`split_self_delimited`] converts such a stream
+//! first and reports where the next one starts.
+
+use crate::bindings::*;
+use crate::packet::{max_packet_size, FrameDuration};
+
+/// Largest single-frame packet: a TOC byte plus a 1275-byte frame
+const MAX_FRAME_PACKET: usize = max_packet_size(1, FrameDuration::Ms20);
+
+/// Owns an `OpusRepacketizer`, destroyed on drop
+struct Repacketizer(*mut OpusRepacketizer);
+
+impl Drop for Repacketizer {
+    fn drop(&mut self) {
+        unsafe { opus_repacketizer_destroy(self.0) };
+    }
+}
+
+/// Split a packet with standard framing into one packet per frame.
+///
+/// Each result is a code 0 packet with the original TOC configuration;
+/// padding is dropped. Returns the Opus error code for a malformed packet,
+/// e.g. `OPUS_INVALID_PACKET`.
+pub fn split(packet: &[u8]) -> Result<Vec<Vec<u8>>, i32> {
+    if packet.is_empty() || packet.len() > i32::MAX as usize {
+        return Err(OPUS_INVALID_PACKET);
+    }
+
+    unsafe {
+        let rp = opus_repacketizer_create();
+        if rp.is_null() {
+            return Err(OPUS_ALLOC_FAIL);
+        }
+        let rp = Repacketizer(rp);
+
+        // `packet` outlives the repacketizer, as opus_repacketizer_cat requires
+        let ret = opus_repacketizer_cat(rp.0, packet.as_ptr(), packet.len() as i32);
+        if ret != OPUS_OK as i32 {
+            return Err(ret);
+        }
+
+        let frames = opus_repacketizer_get_nb_frames(rp.0);
+        (0..frames)
+            .map(|i| {
+                let mut frame = vec![0u8; MAX_FRAME_PACKET];
+                let len = opus_repacketizer_out_range(
+                    rp.0,
+                    i,
+                    i + 1,
+                    frame.as_mut_ptr(),
+                    frame.len() as i32,
+                );
+                if len < 0 {
+                    return Err(len);
+                }
+                frame.truncate(len as usize);
+                Ok(frame)
+            })
+            .collect()
+    }
+}
+
+/// Split the self-delimited packet at the start of `data`, as [`split`].
+///
+/// Returns the frames, as standard single-frame packets, and the number of
+/// bytes the self-delimited packet took up in `data`; the next stream of a
+/// multistream packet starts there.
+pub fn split_self_delimited(data: &[u8]) -> Result<(Vec<Vec<u8>>, usize), i32> {
+    let (packet, consumed) = to_standard_framing(data)?;
+    Ok((split(&packet)?, consumed))
+}
+
+/// Frame length field at the start of `data`: the length and its size in
+/// bytes (RFC 6716, section 3.2.1)
+fn frame_length(data: &[u8]) -> Result<(usize, usize), i32> {
+    match data {
+        [first, ..] if *first < 252 => Ok((*first as usize, 1)),
+        [first, second, ..] => Ok((*second as usize * 4 + *first as usize, 2)),
+        _ => Err(OPUS_INVALID_PACKET),
+    }
+}
+
+/// Rewrite the self-delimited packet at the start of `data` with standard
+/// framing by dropping its extra length field. Also returns the length of
+/// the self-delimited packet.
+pub(crate) fn to_standard_framing(data: &[u8]) -> Result<(Vec<u8>, usize), i32> {
+    let Some(&toc) = data.first() else {
+        return Err(OPUS_INVALID_PACKET);
+    };
+
+    // Header up to the self-delimiting length field, and the combined size
+    // of the frames before the last one
+    let (header, leading) = match toc & 0x3 {
+        // One frame, or two of the same size: the field gives that size
+        0 | 1 => (1, 0),
+        // Two frames: the first length is coded as usual
+        2 => {
+            let (first, bytes) = frame_length(&data[1..])?;
+            (1 + bytes, first)
+        }
+        // Frame count byte, padding length, then VBR lengths for all frames
+        // but the last
+        _ => {
+            let &count_byte = data.get(1).ok_or(OPUS_INVALID_PACKET)?;
+            let mut pos = 2;
+            let mut padding = 0;
+            if count_byte & 0x40 != 0 {
+                loop {
+                    let &byte = data.get(pos).ok_or(OPUS_INVALID_PACKET)?;
+                    pos += 1;
+                    padding += if byte == 255 { 254 } else { byte as usize };
+                    if byte != 255 {
+                        break;
+                    }
+                }
+            }
+            let frames = (count_byte & 0x3f) as usize;
+            if frames == 0 {
+                return Err(OPUS_INVALID_PACKET);
+            }
+            let mut leading = 0;
+            if count_byte & 0x80 != 0 {
+                for _ in 1..frames {
+                    let (len, bytes) = frame_length(data.get(pos..).unwrap_or_default())?;
+                    pos += bytes;
+                    leading += len;
+                }
+            }
+            let (last, bytes) = frame_length(data.get(pos..).unwrap_or_default())?;
+            let body = if count_byte & 0x80 != 0 {
+                leading + last
+            } else {
+                // CBR: the field is the size of every frame
+                frames * last
+            };
+            let total = pos + bytes + body + padding;
+            return standard_packet(data, pos, bytes, total);
+        }
+    };
+
+    let (last, bytes) = frame_length(data.get(header..).unwrap_or_default())?;
+    let frames = if toc & 0x3 == 1 {
+        2 * last
+    } else {
+        leading + last
+    };
+    standard_packet(data, header, bytes, header + bytes + frames)
+}
+
+/// `data[..total]` without the `bytes`-long length field at `at`
+fn standard_packet(
+    data: &[u8],
+    at: usize,
+    bytes: usize,
+    total: usize,
+) -> Result<(Vec<u8>, usize), i32> {
+    if total > data.len() {
+        return Err(OPUS_INVALID_PACKET);
+    }
+    let mut packet = Vec::with_capacity(total - bytes);
+    packet.extend_from_slice(&data[..at]);
+    packet.extend_from_slice(&data[at + bytes..total]);
+    Ok((packet, total))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: i32 = 48000;
+    const FRAME_SIZE: usize = 960;
+
+    /// Encode `count` 20 ms mono frames as separate packets
+    fn encode_frames(count: usize) -> Vec<Vec<u8>> {
+        unsafe {
+            let mut error: i32 = 0;
+            let encoder =
+                opus_encoder_create(SAMPLE_RATE, 1, OPUS_APPLICATION_AUDIO as i32, &mut error);
+            assert_eq!(error, OPUS_OK as i32, "Failed to create encoder");
+
+            let packets = (0..count)
+                .map(|frame| {
+                    let input: Vec<i16> = (0..FRAME_SIZE)
+                        .map(|i| {
+                            let t = (frame * FRAME_SIZE + i) as f32 / SAMPLE_RATE as f32;
+                            ((t * 440.0 * std::f32::consts::TAU).sin() * 8000.0) as i16
+                        })
+                        .collect();
+                    let mut packet = vec![0u8; MAX_FRAME_PACKET];
+                    let len = opus_encode(
+                        encoder,
+                        input.as_ptr(),
+                        FRAME_SIZE as i32,
+                        packet.as_mut_ptr(),
+                        packet.len() as i32,
+                    );
+                    assert!(len > 0, "Encoding failed: {}", len);
+                    packet.truncate(len as usize);
+                    packet
+                })
+                .collect();
+
+            opus_encoder_destroy(encoder);
+            packets
+        }
+    }
+
+    #[test]
+    fn test_split_roundtrip() {
+        let packets = encode_frames(3);
+
+        // Combine the three 20 ms packets into one 60 ms packet
+        let combined = unsafe {
+            let rp = Repacketizer(opus_repacketizer_create());
+            for packet in &packets {
+                let ret = opus_repacketizer_cat(rp.0, packet.as_ptr(), packet.len() as i32);
+                assert_eq!(ret, OPUS_OK as i32);
+            }
+            let mut out = vec![0u8; max_packet_size(1, FrameDuration::Ms60)];
+            let len = opus_repacketizer_out(rp.0, out.as_mut_ptr(), out.len() as i32);
+            assert!(len > 0, "Repacketizing failed: {}", len);
+            out.truncate(len as usize);
+            out
+        };
+        assert_ne!(combined[0] & 0x3, 0, "Expected a multi-frame packet");
+
+        // The encoder's own packets are single-frame code 0 packets
+        assert_eq!(split(&combined).unwrap(), packets);
+        assert_eq!(split(&packets[0]).unwrap(), &packets[..1]);
+    }
+
+    #[test]
+    fn test_split_invalid() {
+        assert_eq!(split(&[]), Err(OPUS_INVALID_PACKET));
+        // Code 3 with a frame count of 0
+        assert_eq!(split(&[0x03, 0x00]), Err(OPUS_INVALID_PACKET));
+        assert!(split_self_delimited(&[0x00]).is_err());
+        // Length field past the end of the data
+        assert!(split_self_delimited(&[0x00, 10, 1, 2]).is_err());
+    }
+
+    #[test]
+    fn test_to_standard_framing() {
+        let rest = [0xaa, 0xbb];
+        let with_rest = |packet: &[u8]| [packet, &rest].concat();
+
+        // Code 0: TOC, length, frame
+        let data = with_rest(&[0x08, 3, 1, 2, 3]);
+        assert_eq!(to_standard_framing(&data), Ok((vec![0x08, 1, 2, 3], 5)));
+
+        // Code 1: one length for both frames
+        let data = with_rest(&[0x09, 2, 1, 2, 3, 4]);
+        assert_eq!(to_standard_framing(&data), Ok((vec![0x09, 1, 2, 3, 4], 6)));
+
+        // Code 2: the second frame's length follows the first's
+        let data = with_rest(&[0x0a, 1, 2, 9, 7, 7]);
+        assert_eq!(to_standard_framing(&data), Ok((vec![0x0a, 1, 9, 7, 7], 6)));
+
+        // Code 3 CBR with padding: count byte, padding length, frame length
+        let data = with_rest(&[0x0b, 0x42, 1, 2, 5, 5, 6, 6, 0]);
+        assert_eq!(
+            to_standard_framing(&data),
+            Ok((vec![0x0b, 0x42, 1, 5, 5, 6, 6, 0], 9))
+        );
+
+        // Code 3 VBR: lengths of all but the last frame, then the last
+        let data = with_rest(&[0x0b, 0x83, 1, 2, 3, 4, 5, 5, 6, 6, 6]);
+        assert_eq!(
+            to_standard_framing(&data),
+            Ok((vec![0x0b, 0x83, 1, 2, 4, 5, 5, 6, 6, 6], 11))
+        );
+
+        // Two-byte length: 252 + 4 * 1 = 256
+        let mut data = vec![0x08, 252, 1];
+        data.extend(std::iter::repeat_n(7, 256));
+        let (packet, consumed) = to_standard_framing(&data).unwrap();
+        assert_eq!((packet.len(), consumed), (257, 259));
+    }
+
+    #[test]
+    fn test_split_multistream() {
+        unsafe {
+            let mut error: i32 = 0;
+            // Two uncoupled mono streams: the first one is self-delimited
+            let mapping = [0u8, 1];
+            let encoder = opus_multistream_encoder_create(
+                SAMPLE_RATE,
+                2,
+                2,
+                0,
+                mapping.as_ptr(),
+                OPUS_APPLICATION_AUDIO as i32,
+                &mut error,
+            );
+            assert_eq!(error, OPUS_OK as i32, "Failed to create encoder");
+            let decoder = opus_decoder_create(SAMPLE_RATE, 1, &mut error);
+            assert_eq!(error, OPUS_OK as i32, "Failed to create decoder");
+
+            let input: Vec<i16> = (0..FRAME_SIZE * 2)
+                .map(|i| ((i as f32 * 0.05).sin() * 8000.0) as i16)
+                .collect();
+            let mut packet = [0u8; max_packet_size(2, FrameDuration::Ms20)];
+            let len = opus_multistream_encode(
+                encoder,
+                input.as_ptr(),
+                FRAME_SIZE as i32,
+                packet.as_mut_ptr(),
+                packet.len() as i32,
+            );
+            assert!(len > 0, "Encoding failed: {}", len);
+            let packet = &packet[..len as usize];
+
+            let (first, consumed) = split_self_delimited(packet).unwrap();
+            assert!(consumed < packet.len());
+            let second = split(&packet[consumed..]).unwrap();
+
+            // Both streams decode as ordinary single-stream packets
+            let mut pcm = [0i16; FRAME_SIZE];
+            for frame in first.iter().chain(&second) {
+                let ret = opus_decode(
+                    decoder,
+                    frame.as_ptr(),
+                    frame.len() as i32,
+                    pcm.as_mut_ptr(),
+                    FRAME_SIZE as i32,
+                    0,
+                );
+                assert_eq!(ret, FRAME_SIZE as i32);
+            }
+            assert_eq!((first.len(), second.len()), (1, 1));
+
+            opus_decoder_destroy(decoder);
+            opus_multistream_encoder_destroy(encoder);
+        }
+    }
+}
@@ -0,0 +1,117 @@
+//! Signal type hint (`OPUS_SET_SIGNAL`)
+//!
+//! Telling the encoder whether the input is speech or music biases its
+//! mode and bandwidth decisions: `Voice` favours SILK at lower bitrates,
+//! `Music` favours CELT. Conferencing apps typically set `Voice`, music
+//! streaming `Music`; the default `Auto` lets the encoder's own signal
+//! analysis decide.
+
+use crate::bindings::*;
+
+/// Value for `OPUS_SET_SIGNAL`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Signal {
+    /// `OPUS_AUTO`, the encoder default
+    #[default]
+    Auto,
+    /// `OPUS_SIGNAL_VOICE`
+    Voice,
+    /// `OPUS_SIGNAL_MUSIC`
+    Music,
+}
+
+impl From<Signal> for i32 {
+    fn from(signal: Signal) -> i32 {
+        match signal {
+            Signal::Auto => OPUS_AUTO,
+            Signal::Voice => OPUS_SIGNAL_VOICE as i32,
+            Signal::Music => OPUS_SIGNAL_MUSIC as i32,
+        }
+    }
+}
+
+impl TryFrom<i32> for Signal {
+    /// The value, which is not an `OPUS_SET_SIGNAL` setting
+    type Error = i32;
+
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        match value {
+            OPUS_AUTO => Ok(Signal::Auto),
+            v if v == OPUS_SIGNAL_VOICE as i32 => Ok(Signal::Voice),
+            v if v == OPUS_SIGNAL_MUSIC as i32 => Ok(Signal::Music),
+            v => Err(v),
+        }
+    }
+}
+
+/// Set the signal type with `OPUS_SET_SIGNAL`. Returns the Opus error code
+/// on failure.
+///
+/// # Safety
+///
+/// `encoder` must be a valid encoder.
+pub unsafe fn set_signal(encoder: *mut OpusEncoder, signal: Signal) -> Result<(), i32> {
+    let ret = opus_encoder_ctl(encoder, OPUS_SET_SIGNAL_REQUEST as i32, i32::from(signal));
+    if ret == OPUS_OK as i32 {
+        Ok(())
+    } else {
+        Err(ret)
+    }
+}
+
+/// The signal type from `OPUS_GET_SIGNAL`. Returns the Opus error code on
+/// failure.
+///
+/// # Safety
+///
+/// `encoder` must be a valid encoder.
+pub unsafe fn signal(encoder: *mut OpusEncoder) -> Result<Signal, i32> {
+    let mut value: i32 = 0;
+    let ret = opus_encoder_ctl(
+        encoder,
+        OPUS_GET_SIGNAL_REQUEST as i32,
+        &mut value as *mut i32,
+    );
+    if ret != OPUS_OK as i32 {
+        return Err(ret);
+    }
+    Signal::try_from(value).map_err(|_| OPUS_INTERNAL_ERROR)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conversions() {
+        for signal in [Signal::Auto, Signal::Voice, Signal::Music] {
+            assert_eq!(Signal::try_from(i32::from(signal)), Ok(signal));
+        }
+        assert_eq!(i32::from(Signal::Voice), 3001);
+        assert_eq!(Signal::try_from(3000), Err(3000));
+        assert_eq!(Signal::default(), Signal::Auto);
+    }
+
+    #[test]
+    fn test_set_and_get() {
+        unsafe {
+            let mut error: i32 = 0;
+            let encoder = opus_encoder_create(48000, 1, OPUS_APPLICATION_VOIP as i32, &mut error);
+            assert_eq!(error, OPUS_OK as i32, "Failed to create encoder");
+
+            assert_eq!(signal(encoder), Ok(Signal::Auto));
+            for expected in [Signal::Voice, Signal::Music, Signal::Auto] {
+                set_signal(encoder, expected).unwrap();
+                assert_eq!(signal(encoder), Ok(expected));
+            }
+
+            // Rejected values leave the setting alone
+            let ret = opus_encoder_ctl(encoder, OPUS_SET_SIGNAL_REQUEST as i32, 1234);
+            assert_eq!(ret, OPUS_BAD_ARG);
+            assert_eq!(signal(encoder), Ok(Signal::Auto));
+
+            opus_encoder_destroy(encoder);
+        }
+    }
+}
@@ -0,0 +1,621 @@
+//! Network impairment simulation for evaluating FEC and DRED settings
+//!
+//! [`Network`] carries packets from a sender to a receiver one slot (one
+//! frame duration) apart, losing, delaying and reordering them with a
+//! seeded pseudo-random generator so each run sees the same impairments.
+//! [`Receiver`] is a fixed-delay jitter buffer in front of a decoder: it
+//! plays one frame per slot and picks the recovery path for each, the
+//! packet itself, in-band FEC from the next packet, DRED from a later one,
+//! or concealment, counting how often each was taken.
+//!
+//! ```
+//! use opus_head_sys::simulate::{Impairment, Network, Receiver};
+//! use opus_head_sys::stream::StreamEncoder;
+//! use opus_head_sys::OPUS_APPLICATION_VOIP;
+//!
+//! // 20 ms mono frames at 16 kHz
+//! let mut encoder = StreamEncoder::new(16000, 1, OPUS_APPLICATION_VOIP as i32, 320).unwrap();
+//! let packets = encoder.push(&vec![0i16; 320 * 50]).unwrap();
+//!
+//! let mut network = Network::new(Impairment {
+//!     loss: 0.05,
+//!     burst: 2,
+//!     reorder: 0.01,
+//!     jitter: 1,
+//!     seed: 42,
+//! })
+//! .unwrap();
+//! let mut receiver = Receiver::new(16000, 1).unwrap();
+//! let mut pcm = vec![0i16; 320];
+//! // Play each frame 3 slots after it was sent
+//! let delay = 3;
+//! for slot in 0..(packets.len() + delay) as u64 {
+//!     if let Some(packet) = packets.get(slot as usize) {
+//!         network.send(slot, packet);
+//!     }
+//!     for (sequence, packet) in network.deliver(slot) {
+//!         receiver.receive(sequence, &packet);
+//!     }
+//!     if slot >= delay as u64 {
+//!         let (_samples, _recovery) = receiver.play(&mut pcm).unwrap();
+//!     }
+//! }
+//! assert_eq!(receiver.stats().frames(), packets.len());
+//! ```
+//!
+//! FEC needs an encoder with `OPUS_SET_INBAND_FEC` and a nonzero
+//! `OPUS_SET_PACKET_LOSS_PERC`; DRED needs DNN weights on both sides, see
+//! [`Receiver::enable_dred`].
+
+use crate::bindings::*;
+use crate::decoder::Decoder;
+use crate::dnn::DnnBlob;
+use crate::loss::LossSimulator;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::hash::{Hash, Hasher};
+
+/// Impairments applied by a [`Network`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Impairment {
+    /// Probability that a packet starts a loss event, see
+    /// [`LossSimulator`]
+    pub loss: f64,
+    /// Packets dropped in a row per loss event, at least 1
+    pub burst: usize,
+    /// Probability that a packet is held back by one slot, so it arrives
+    /// after the packet sent next
+    pub reorder: f64,
+    /// Maximum extra delay in slots, drawn uniformly from `0..=jitter` for
+    /// each packet
+    pub jitter: u32,
+    /// The same seed gives the same impairments
+    pub seed: u64,
+}
+
+impl Impairment {
+    /// A network that delivers every packet in its own slot
+    pub fn none() -> Self {
+        Impairment {
+            loss: 0.0,
+            burst: 1,
+            reorder: 0.0,
+            jitter: 0,
+            seed: 0,
+        }
+    }
+}
+
+/// A packet in flight
+#[derive(Debug, Clone)]
+struct InFlight {
+    /// Slot it arrives in
+    arrival: u64,
+    /// Held back by reordering, so it goes after the packets arriving in
+    /// the same slot
+    held: bool,
+    sequence: u64,
+    data: Vec<u8>,
+}
+
+/// Seeded lossy, jittery, reordering link, see the [module docs](self)
+#[derive(Debug, Clone)]
+pub struct Network {
+    impairment: Impairment,
+    loss: LossSimulator,
+    in_flight: Vec<InFlight>,
+}
+
+impl Network {
+    /// Returns `None` unless `loss` and `reorder` are within `0.0..=1.0`
+    /// and `burst` is at least 1.
+    pub fn new(impairment: Impairment) -> Option<Self> {
+        if !(0.0..=1.0).contains(&impairment.reorder) {
+            return None;
+        }
+        let loss = LossSimulator::new(impairment.loss, impairment.burst, impairment.seed)?;
+        Some(Network {
+            impairment,
+            loss,
+            in_flight: Vec::new(),
+        })
+    }
+
+    /// Send packet `sequence` in slot `sequence`. Packets have to be sent in
+    /// order, as the loss pattern follows the order of the calls.
+    ///
+    /// Returns the slot the packet arrives in, or `None` if it is lost.
+    pub fn send(&mut self, sequence: u64, packet: &[u8]) -> Option<u64> {
+        if self.loss.next_lost() {
+            return None;
+        }
+        let Impairment {
+            reorder,
+            jitter,
+            seed,
+            ..
+        } = self.impairment;
+        let delay = ((uniform(seed, Stream::Jitter, sequence) * (jitter as f64 + 1.0)) as u64)
+            .min(jitter as u64);
+        let held = uniform(seed, Stream::Reorder, sequence) < reorder;
+        let arrival = sequence + delay + held as u64;
+        self.in_flight.push(InFlight {
+            arrival,
+            held,
+            sequence,
+            data: packet.to_vec(),
+        });
+        Some(arrival)
+    }
+
+    /// Packets arriving by `slot` as `(sequence, packet)`, in the order
+    /// they arrive
+    pub fn deliver(&mut self, slot: u64) -> Vec<(u64, Vec<u8>)> {
+        let (mut arrived, in_flight): (Vec<_>, Vec<_>) = self
+            .in_flight
+            .drain(..)
+            .partition(|packet| packet.arrival <= slot);
+        self.in_flight = in_flight;
+        arrived.sort_by_key(|packet| (packet.arrival, packet.held, packet.sequence));
+        arrived
+            .into_iter()
+            .map(|packet| (packet.sequence, packet.data))
+            .collect()
+    }
+
+    /// Packets sent but not delivered yet
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.len()
+    }
+}
+
+/// Independent random streams drawn from the same seed
+#[derive(Hash)]
+enum Stream {
+    Jitter,
+    Reorder,
+}
+
+/// Uniform value in `0.0..1.0` for packet `index` of `stream`
+fn uniform(seed: u64, stream: Stream, index: u64) -> f64 {
+    let mut hasher = DefaultHasher::new();
+    seed.hash(&mut hasher);
+    stream.hash(&mut hasher);
+    index.hash(&mut hasher);
+    // The top 53 bits fill an f64 mantissa exactly
+    (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// How [`Receiver::play`] produced a frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Recovery {
+    /// Decoded from its own packet
+    Clean,
+    /// Rebuilt from the in-band FEC (LBRR) data of the next packet
+    Fec,
+    /// Rebuilt from the DRED data of a later packet
+    Dred,
+    /// Concealed by the decoder's PLC
+    Concealed,
+}
+
+/// Frames played by a [`Receiver`], by [`Recovery`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub clean: usize,
+    pub fec: usize,
+    pub dred: usize,
+    pub concealed: usize,
+    /// Packets that arrived after their frame was played, and were dropped
+    pub late: usize,
+}
+
+impl Stats {
+    /// Frames played
+    pub fn frames(&self) -> usize {
+        self.clean + self.fec + self.dred + self.concealed
+    }
+
+    /// Frames rebuilt from redundancy, FEC or DRED
+    pub fn recovered(&self) -> usize {
+        self.fec + self.dred
+    }
+}
+
+/// Jitter buffer and decoder, see the [module docs](self)
+///
+/// `Send` but not `Sync`, like [`Decoder`].
+#[derive(Debug)]
+pub struct Receiver {
+    decoder: Decoder,
+    /// Set by [`enable_dred`](Self::enable_dred)
+    dred: Option<(*mut OpusDREDDecoder, *mut OpusDRED)>,
+    /// The DNN weights the DRED decoder points into
+    weights: Option<DnnBlob>,
+    /// Packets received but not played yet, by sequence number
+    buffer: BTreeMap<u64, Vec<u8>>,
+    /// Sequence number of the next frame to play
+    next: u64,
+    stats: Stats,
+}
+
+// The decoder states are single allocations with no thread affinity
+unsafe impl Send for Receiver {}
+
+impl Receiver {
+    /// Create the decoder with [`Decoder::new`], returning its error on
+    /// failure
+    pub fn new(sample_rate: i32, channels: usize) -> Result<Self, i32> {
+        Ok(Receiver {
+            decoder: Decoder::new(sample_rate, channels)?,
+            dred: None,
+            weights: None,
+            buffer: BTreeMap::new(),
+            next: 0,
+            stats: Stats::default(),
+        })
+    }
+
+    /// Recover lost frames from DRED, loading the DNN `weights` (the
+    /// `opus_data-*.bin` blob) into the decoder and a DRED decoder.
+    ///
+    /// The weights are checked and copied as by [`DnnBlob::new`]. Returns
+    /// the Opus error code, e.g. `OPUS_BAD_ARG` for malformed weights or
+    /// `OPUS_UNIMPLEMENTED` without the `dred` feature; the decoder is
+    /// left as it was then.
+    pub fn enable_dred(&mut self, weights: &[u8]) -> Result<(), i32> {
+        if self.dred.is_some() {
+            return Ok(());
+        }
+        let blob = DnnBlob::new(weights).map_err(|e| e.code())?;
+        unsafe {
+            let mut error: i32 = 0;
+            let dred_decoder = opus_dred_decoder_create(&mut error);
+            if error != OPUS_OK as i32 {
+                return Err(error);
+            }
+            let dred = opus_dred_alloc(&mut error);
+            if error != OPUS_OK as i32 {
+                opus_dred_decoder_destroy(dred_decoder);
+                return Err(error);
+            }
+            let mut ret = opus_dred_decoder_ctl(
+                dred_decoder,
+                OPUS_SET_DNN_BLOB_REQUEST as i32,
+                blob.as_bytes().as_ptr() as *const core::ffi::c_void,
+                blob.len() as i32,
+            );
+            if ret == OPUS_OK as i32 {
+                ret = self
+                    .decoder
+                    .set_dnn_blob(&blob)
+                    .map_or_else(|e| e.code(), |()| 0);
+            }
+            if ret != OPUS_OK as i32 {
+                opus_dred_free(dred);
+                opus_dred_decoder_destroy(dred_decoder);
+                return Err(ret);
+            }
+            self.dred = Some((dred_decoder, dred));
+        }
+        self.weights = Some(blob);
+        Ok(())
+    }
+
+    /// The underlying decoder, e.g. for `opus_decoder_ctl`. Still owned by
+    /// the receiver.
+    pub fn as_ptr(&self) -> *mut OpusDecoder {
+        self.decoder.as_ptr()
+    }
+
+    /// Buffer packet `sequence`, or drop it if its frame was played already
+    pub fn receive(&mut self, sequence: u64, packet: &[u8]) {
+        if sequence < self.next {
+            self.stats.late += 1;
+            return;
+        }
+        self.buffer.insert(sequence, packet.to_vec());
+    }
+
+    /// Play the next frame into `pcm` (interleaved), recovering it if its
+    /// packet is missing: from FEC in the next packet if that carries any,
+    /// then from DRED in the earliest later packet that reaches back far
+    /// enough, and by concealment otherwise.
+    ///
+    /// Returns the samples per channel written and how, or the Opus error
+    /// code, e.g. `OPUS_BUFFER_TOO_SMALL` if `pcm` can't hold the frame.
+    pub fn play(&mut self, pcm: &mut [i16]) -> Result<(usize, Recovery), i32> {
+        let sequence = self.next;
+        self.next += 1;
+
+        let decoded = unsafe { self.decode(sequence, pcm) };
+        // A frame that failed to decode is still played, as a gap
+        self.buffer.remove(&sequence);
+        let (samples, recovery) = decoded?;
+        match recovery {
+            Recovery::Clean => self.stats.clean += 1,
+            Recovery::Fec => self.stats.fec += 1,
+            Recovery::Dred => self.stats.dred += 1,
+            Recovery::Concealed => self.stats.concealed += 1,
+        }
+        Ok((samples, recovery))
+    }
+
+    /// Frames played so far, and packets dropped as late
+    pub fn stats(&self) -> Stats {
+        self.stats
+    }
+
+    unsafe fn decode(&mut self, sequence: u64, pcm: &mut [i16]) -> Result<(usize, Recovery), i32> {
+        if let Some(packet) = self.buffer.get(&sequence) {
+            let samples = self.decoder.decode(Some(packet), pcm, false)?;
+            return Ok((samples, Recovery::Clean));
+        }
+
+        // A missing frame lasts as long as the last one
+        let samples = self.decoder.last_frame_size();
+        let len = samples * self.decoder.channels();
+        if len > pcm.len() {
+            return Err(OPUS_BUFFER_TOO_SMALL);
+        }
+
+        if let Some(next) = self.buffer.get(&(sequence + 1)) {
+            if opus_packet_has_lbrr(next.as_ptr(), next.len() as i32) == 1 {
+                if let Ok(samples) = self.decoder.decode(Some(next), &mut pcm[..len], true) {
+                    return Ok((samples, Recovery::Fec));
+                }
+            }
+        }
+
+        if let Some((dred_decoder, dred)) = self.dred {
+            if let Some((&later, packet)) = self.buffer.range(sequence + 1..).next() {
+                // How far before the start of `later` this frame begins
+                let offset = (later - sequence) as usize * samples;
+                let mut dred_end: i32 = 0;
+                let available = opus_dred_parse(
+                    dred_decoder,
+                    dred,
+                    packet.as_ptr(),
+                    packet.len() as i32,
+                    offset as i32,
+                    self.decoder.sample_rate(),
+                    &mut dred_end,
+                    0,
+                );
+                if available >= offset as i32 {
+                    let ret = opus_decoder_dred_decode(
+                        self.decoder.as_ptr(),
+                        dred,
+                        offset as i32,
+                        pcm.as_mut_ptr(),
+                        samples as i32,
+                    );
+                    if ret >= 0 {
+                        return Ok((ret as usize, Recovery::Dred));
+                    }
+                }
+            }
+        }
+
+        let samples = self.decoder.conceal(pcm, samples)?;
+        Ok((samples, Recovery::Concealed))
+    }
+}
+
+impl Drop for Receiver {
+    fn drop(&mut self) {
+        unsafe {
+            if let Some((dred_decoder, dred)) = self.dred {
+                opus_dred_free(dred);
+                opus_dred_decoder_destroy(dred_decoder);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Arrival slot of each of `packets` packets, `None` if lost
+    fn arrivals(impairment: Impairment, packets: u64) -> Vec<Option<u64>> {
+        let mut network = Network::new(impairment).unwrap();
+        (0..packets)
+            .map(|sequence| network.send(sequence, &[sequence as u8]))
+            .collect()
+    }
+
+    fn impairment(loss: f64, burst: usize, reorder: f64, jitter: u32, seed: u64) -> Impairment {
+        Impairment {
+            loss,
+            burst,
+            reorder,
+            jitter,
+            seed,
+        }
+    }
+
+    #[test]
+    fn test_new() {
+        assert!(Network::new(Impairment::none()).is_some());
+        assert!(Network::new(impairment(0.1, 2, 0.1, 3, 0)).is_some());
+        assert!(Network::new(impairment(1.5, 1, 0.0, 0, 0)).is_none());
+        assert!(Network::new(impairment(0.1, 0, 0.0, 0, 0)).is_none());
+        assert!(Network::new(impairment(0.1, 1, -0.1, 0, 0)).is_none());
+        assert!(Network::new(impairment(0.1, 1, f64::NAN, 0, 0)).is_none());
+    }
+
+    #[test]
+    fn test_no_impairment() {
+        let mut network = Network::new(Impairment::none()).unwrap();
+        for slot in 0..100 {
+            assert_eq!(network.send(slot, &[slot as u8]), Some(slot));
+            assert_eq!(network.deliver(slot), vec![(slot, vec![slot as u8])]);
+        }
+        assert_eq!(network.in_flight(), 0);
+    }
+
+    #[test]
+    fn test_seeded() {
+        let a = impairment(0.1, 2, 0.1, 3, 7);
+        assert_eq!(arrivals(a, 1000), arrivals(a, 1000));
+        let b = Impairment { seed: 8, ..a };
+        assert_ne!(arrivals(a, 1000), arrivals(b, 1000));
+    }
+
+    #[test]
+    fn test_loss() {
+        let arrived = arrivals(impairment(0.1, 1, 0.0, 0, 11), 10_000);
+        let lost = arrived.iter().filter(|a| a.is_none()).count();
+        assert!((800..1200).contains(&lost), "lost {} of 10000", lost);
+
+        // Bursts of 3 from 3% of the packets lose about 9% of them
+        let arrived = arrivals(impairment(0.03, 3, 0.0, 0, 11), 10_000);
+        let lost = arrived.iter().filter(|a| a.is_none()).count();
+        assert!((700..1100).contains(&lost), "lost {} of 10000", lost);
+    }
+
+    #[test]
+    fn test_reorder() {
+        let mut network = Network::new(impairment(0.0, 1, 0.2, 0, 5)).unwrap();
+        let mut order = Vec::new();
+        for slot in 0..10_000 {
+            network.send(slot, &[]);
+            order.extend(network.deliver(slot).into_iter().map(|(s, _)| s));
+        }
+        order.extend(network.deliver(u64::MAX).into_iter().map(|(s, _)| s));
+        assert_eq!(order.len(), 10_000);
+
+        // A held packet swaps places with the next one unless that is held
+        // too, which happens for 20% * 80% of the pairs
+        let swapped = order.windows(2).filter(|w| w[0] > w[1]).count();
+        assert!((1400..1800).contains(&swapped), "swapped {}", swapped);
+        let mut sorted = order.clone();
+        sorted.sort();
+        assert_eq!(sorted, (0..10_000).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_jitter() {
+        const JITTER: u32 = 3;
+        let arrived = arrivals(impairment(0.0, 1, 0.0, JITTER, 3), 10_000);
+        let mut histogram = [0usize; JITTER as usize + 1];
+        for (sequence, arrival) in arrived.iter().enumerate() {
+            let delay = arrival.unwrap() - sequence as u64;
+            histogram[delay as usize] += 1;
+        }
+        // Uniform over 0..=JITTER
+        for (delay, &count) in histogram.iter().enumerate() {
+            assert!((2300..2700).contains(&count), "{} slots: {}", delay, count);
+        }
+    }
+
+    #[test]
+    fn test_deliver_once() {
+        let mut network = Network::new(impairment(0.0, 1, 0.3, 4, 9)).unwrap();
+        for sequence in 0..50 {
+            network.send(sequence, &[]);
+        }
+        let mut delivered = Vec::new();
+        for slot in 0..60 {
+            for (sequence, _) in network.deliver(slot) {
+                // Jitter plus one slot held back at most
+                assert!((sequence..=sequence + 5).contains(&slot));
+                delivered.push(sequence);
+            }
+        }
+        assert_eq!(network.in_flight(), 0);
+        delivered.sort();
+        assert_eq!(delivered, (0..50).collect::<Vec<_>>());
+    }
+
+    /// 20 ms frames of a tone at 16 kHz, with in-band FEC
+    fn fec_packets(frames: usize) -> Vec<Vec<u8>> {
+        const FRAME_SIZE: usize = 320;
+        let mut encoder =
+            crate::stream::StreamEncoder::new(16000, 1, OPUS_APPLICATION_VOIP as i32, FRAME_SIZE)
+                .unwrap();
+        unsafe {
+            opus_encoder_ctl(encoder.as_ptr(), OPUS_SET_INBAND_FEC_REQUEST as i32, 1);
+            opus_encoder_ctl(
+                encoder.as_ptr(),
+                OPUS_SET_PACKET_LOSS_PERC_REQUEST as i32,
+                20,
+            );
+        }
+        let input: Vec<i16> = (0..frames * FRAME_SIZE)
+            .map(|i| {
+                let t = i as f32 / 16000.0;
+                ((t * 300.0 * std::f32::consts::TAU).sin() * 8000.0) as i16
+            })
+            .collect();
+        encoder.push(&input).unwrap()
+    }
+
+    fn run(packets: &[Vec<u8>], impairment: Impairment, delay: u64) -> Stats {
+        let mut network = Network::new(impairment).unwrap();
+        let mut receiver = Receiver::new(16000, 1).unwrap();
+        let mut pcm = [0i16; 320];
+        for slot in 0..packets.len() as u64 + delay {
+            if let Some(packet) = packets.get(slot as usize) {
+                network.send(slot, packet);
+            }
+            for (sequence, packet) in network.deliver(slot) {
+                receiver.receive(sequence, &packet);
+            }
+            if slot >= delay {
+                let (samples, _) = receiver.play(&mut pcm).unwrap();
+                assert_eq!(samples, 320);
+            }
+        }
+        receiver.stats()
+    }
+
+    #[test]
+    fn test_receiver_clean() {
+        let packets = fec_packets(50);
+        let stats = run(&packets, Impairment::none(), 0);
+        assert_eq!(
+            stats,
+            Stats {
+                clean: 50,
+                ..Stats::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_receiver_recovery() {
+        let packets = fec_packets(200);
+        let stats = run(&packets, impairment(0.2, 1, 0.05, 1, 99), 3);
+        assert_eq!(stats.frames(), 200);
+        assert_eq!(stats.late, 0);
+        assert!(stats.fec > 0, "{:?}", stats);
+        assert!(stats.concealed > 0, "{:?}", stats);
+        assert_eq!(stats.dred, 0);
+        assert!(stats.clean > 120 && stats.clean < 200, "{:?}", stats);
+    }
+
+    #[test]
+    fn test_receiver_late() {
+        let packets = fec_packets(100);
+        // Up to 4 slots of jitter against a 1 slot playout delay
+        let stats = run(&packets, impairment(0.0, 1, 0.0, 4, 1), 1);
+        assert!(stats.late > 0, "{:?}", stats);
+        assert_eq!(stats.frames(), 100);
+    }
+
+    #[test]
+    fn test_receiver_malformed_weights() {
+        let mut receiver = Receiver::new(16000, 1).unwrap();
+        let mut weights = b"DNNw".to_vec();
+        weights.extend([0x5a; 252]);
+        assert_eq!(receiver.enable_dred(&weights), Err(OPUS_BAD_ARG));
+        assert!(receiver.dred.is_none());
+        let mut pcm = [0i16; 320];
+        for (sequence, packet) in fec_packets(5).iter().enumerate() {
+            receiver.receive(sequence as u64, packet);
+            assert_eq!(receiver.play(&mut pcm), Ok((320, Recovery::Clean)));
+        }
+    }
+}
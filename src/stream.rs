@@ -0,0 +1,1126 @@
+//! Encoding input of arbitrary length
+//!
+//! Audio from a capture callback rarely arrives in whole Opus frames, e.g.
+//! 441 or 480 samples at a time. [`FrameChunker`] buffers whatever it is
+//! given and hands out each frame as soon as it is complete, for any sample
+//! type and channel count, e.g. to feed a multistream encoder or
+//! `opus_encode_float`. [`StreamEncoder`] does the same for an
+//! `OpusEncoder` and encodes each frame, padding the remainder with silence
+//! on [`finish`](StreamEncoder::finish).
+//!
+//! ```
+//! use opus_head_sys::stream::StreamEncoder;
+//! use opus_head_sys::OPUS_APPLICATION_VOIP;
+//!
+//! // 20 ms mono frames at 48 kHz
+//! let mut encoder = StreamEncoder::new(48000, 1, OPUS_APPLICATION_VOIP as i32, 960).unwrap();
+//! let mut packets = Vec::new();
+//! for chunk in [441, 441, 441] {
+//!     // Whatever the capture callback delivers
+//!     packets.extend(encoder.push(&vec![0i16; chunk]).unwrap());
+//! }
+//! assert_eq!(packets.len(), 1);
+//! packets.extend(encoder.finish().unwrap());
+//! assert_eq!(packets.len(), 2);
+//! ```
+
+use crate::bindings::*;
+use crate::cbr::configure_cbr;
+use crate::dnn::{self, BlobError, DnnBlob};
+use crate::packet::{max_packet_size, Bandwidth, FrameDuration};
+use crate::preset::Preset;
+#[cfg(feature = "bytes")]
+use bytes::{Bytes, BytesMut};
+
+/// Packets `encode_to_bytes` makes room for at once
+#[cfg(feature = "bytes")]
+const BYTES_PACKETS: usize = 16;
+
+/// An encoder fed with arbitrary-length input, see the [module docs](self)
+///
+/// `Send` but not `Sync`: each encoder is independent, so one per stream
+/// can run on any thread, but the C state has no locking of its own. Share
+/// one between threads behind a `Mutex`.
+///
+/// ```compile_fail
+/// fn assert_sync<T: Sync>() {}
+/// assert_sync::<opus_head_sys::stream::StreamEncoder>();
+/// ```
+#[derive(Debug)]
+pub struct StreamEncoder {
+    encoder: *mut OpusEncoder,
+    duration: FrameDuration,
+    /// Samples not yet encoded
+    chunker: FrameChunker<i16>,
+    packet: Vec<u8>,
+    /// Room for the next few packets of `encode_to_bytes`, split off one
+    /// at a time
+    #[cfg(feature = "bytes")]
+    bytes: BytesMut,
+    /// The DNN weights loaded, which the encoder points into
+    dnn_blob: Option<DnnBlob>,
+}
+
+// The encoder state is a single allocation with no thread affinity
+unsafe impl Send for StreamEncoder {}
+
+impl StreamEncoder {
+    /// Create an encoder with `opus_encoder_create` that emits one packet
+    /// per `frame_size` samples per channel.
+    ///
+    /// Returns `OPUS_BAD_ARG` if `frame_size` is not an Opus frame size
+    /// (2.5 to 120 ms) at `sample_rate`, or the error from
+    /// `opus_encoder_create`.
+    pub fn new(
+        sample_rate: i32,
+        channels: usize,
+        application: i32,
+        frame_size: usize,
+    ) -> Result<Self, i32> {
+        let duration = check_args(sample_rate, channels, frame_size)?;
+
+        let mut error: i32 = 0;
+        let encoder =
+            unsafe { opus_encoder_create(sample_rate, channels as i32, application, &mut error) };
+        if error != OPUS_OK as i32 {
+            return Err(error);
+        }
+        if encoder.is_null() {
+            return Err(OPUS_ALLOC_FAIL);
+        }
+
+        Ok(Self::with_encoder(encoder, channels, frame_size, duration))
+    }
+
+    /// Create an encoder with `preset`'s settings, emitting one packet per
+    /// [`Preset::frame_ms`]. Returns `OPUS_BAD_ARG` for an unsupported
+    /// sample rate or channel count, or the error from creating the
+    /// encoder.
+    pub fn with_preset(preset: Preset, sample_rate: i32, channels: usize) -> Result<Self, i32> {
+        let frame_size = usize::try_from(sample_rate).unwrap_or(0) * preset.frame_ms() / 1000;
+        let duration = check_args(sample_rate, channels, frame_size)?;
+        let encoder = preset.create(sample_rate, channels)?;
+        Ok(Self::with_encoder(encoder, channels, frame_size, duration))
+    }
+
+    /// [`Preset::Voip`]: 20 ms frames for voice calls
+    pub fn voip(sample_rate: i32, channels: usize) -> Result<Self, i32> {
+        Self::with_preset(Preset::Voip, sample_rate, channels)
+    }
+
+    /// [`Preset::Music`]: 20 ms frames for music
+    pub fn music(sample_rate: i32, channels: usize) -> Result<Self, i32> {
+        Self::with_preset(Preset::Music, sample_rate, channels)
+    }
+
+    /// [`Preset::LowLatency`]: 10 ms CELT frames
+    pub fn low_latency(sample_rate: i32, channels: usize) -> Result<Self, i32> {
+        Self::with_preset(Preset::LowLatency, sample_rate, channels)
+    }
+
+    /// Take ownership of an existing encoder, e.g. one created by other C
+    /// code, and emit one packet per `frame_size` samples per channel. The
+    /// encoder is destroyed on drop unless given back with
+    /// [`into_raw`](Self::into_raw).
+    ///
+    /// Returns `OPUS_BAD_ARG` if `frame_size` is not an Opus frame size at
+    /// the encoder's sample rate or `channels` is not 1 or 2; `encoder` is
+    /// still owned by the caller then.
+    ///
+    /// # Safety
+    ///
+    /// `encoder` must be a valid encoder from `opus_encoder_create` (or
+    /// `opus_encoder_init` on memory allocated like it, as
+    /// `opus_encoder_destroy` frees it) created for `channels` channels,
+    /// and nothing else may use or destroy it afterwards.
+    pub unsafe fn from_raw(
+        encoder: *mut OpusEncoder,
+        channels: usize,
+        frame_size: usize,
+    ) -> Result<Self, i32> {
+        let mut sample_rate: i32 = 0;
+        let ret = opus_encoder_ctl(
+            encoder,
+            OPUS_GET_SAMPLE_RATE_REQUEST as i32,
+            &mut sample_rate as *mut i32,
+        );
+        if ret != OPUS_OK as i32 {
+            return Err(ret);
+        }
+        let duration = check_args(sample_rate, channels, frame_size)?;
+        Ok(Self::with_encoder(encoder, channels, frame_size, duration))
+    }
+
+    /// Give up ownership of the encoder without destroying it; the caller
+    /// must destroy it with `opus_encoder_destroy`. Buffered samples are
+    /// dropped, call [`finish`](Self::finish) first to keep them. DNN
+    /// weights loaded with [`set_dnn_blob`](Self::set_dnn_blob) are leaked,
+    /// as the encoder keeps using them.
+    pub fn into_raw(self) -> *mut OpusEncoder {
+        let mut this = core::mem::ManuallyDrop::new(self);
+        drop(core::mem::take(&mut this.chunker.buffer));
+        drop(core::mem::take(&mut this.packet));
+        #[cfg(feature = "bytes")]
+        drop(core::mem::take(&mut this.bytes));
+        core::mem::forget(this.dnn_blob.take());
+        this.encoder
+    }
+
+    fn with_encoder(
+        encoder: *mut OpusEncoder,
+        channels: usize,
+        frame_size: usize,
+        duration: FrameDuration,
+    ) -> Self {
+        StreamEncoder {
+            encoder,
+            duration,
+            chunker: FrameChunker::with_frame_size(frame_size, channels),
+            // Never too small, so the bitrate alone decides the packet size
+            packet: vec![0; max_packet_size(channels, duration)],
+            #[cfg(feature = "bytes")]
+            bytes: BytesMut::new(),
+            dnn_blob: None,
+        }
+    }
+
+    /// The underlying encoder, for `opus_encoder_ctl`. It stays owned by
+    /// `self`; don't destroy it or call `opus_encode` on it directly.
+    pub fn as_ptr(&self) -> *mut OpusEncoder {
+        self.encoder
+    }
+
+    /// The sample rate from `OPUS_GET_SAMPLE_RATE`, e.g. for an encoder
+    /// taken with [`from_raw`](Self::from_raw)
+    pub fn sample_rate(&self) -> Result<i32, i32> {
+        self.get(OPUS_GET_SAMPLE_RATE_REQUEST)
+    }
+
+    pub fn channels(&self) -> usize {
+        self.chunker.channels()
+    }
+
+    /// Samples per channel in each packet
+    pub fn frame_size(&self) -> usize {
+        self.chunker.frame_size()
+    }
+
+    /// Switch to constant bitrate at `bitrate` bits per second, with the
+    /// expert frame duration fixed to [`frame_size`](Self::frame_size), see
+    /// [`configure_cbr`]. Every packet then has the same size, except
+    /// with DTX enabled.
+    pub fn configure_cbr(&mut self, bitrate: i32) -> Result<(), i32> {
+        unsafe { configure_cbr(self.encoder, bitrate, self.duration) }
+    }
+
+    /// Whether the last frame was encoded as DTX, `OPUS_GET_IN_DTX`: the
+    /// encoder found it silent and sent a packet of at most 2 bytes, or
+    /// comfort noise, that a packetizer can drop. Needs `OPUS_SET_DTX`, as
+    /// [`voip`](Self::voip) sets.
+    pub fn in_dtx(&self) -> Result<bool, i32> {
+        self.get(OPUS_GET_IN_DTX_REQUEST).map(|value| value == 1)
+    }
+
+    /// Constrain VBR with `OPUS_SET_VBR_CONSTRAINT`, on by default: packets
+    /// then vary in size no more than a transport with a hard per-packet
+    /// budget allows. Has no effect with CBR.
+    pub fn set_vbr_constraint(&mut self, constrained: bool) -> Result<(), i32> {
+        self.set(OPUS_SET_VBR_CONSTRAINT_REQUEST, constrained as i32)
+    }
+
+    /// Whether VBR is constrained, `OPUS_GET_VBR_CONSTRAINT`
+    pub fn vbr_constraint(&self) -> Result<bool, i32> {
+        self.get(OPUS_GET_VBR_CONSTRAINT_REQUEST)
+            .map(|value| value == 1)
+    }
+
+    /// Cap the coded bandwidth with `OPUS_SET_MAX_BANDWIDTH`, e.g. to spend
+    /// a very low bitrate on a narrower band. `None` lifts the cap: the ctl
+    /// doesn't take `OPUS_AUTO`, so that sets fullband, the default.
+    pub fn set_max_bandwidth(&mut self, bandwidth: Option<Bandwidth>) -> Result<(), i32> {
+        let bandwidth = bandwidth.unwrap_or(Bandwidth::Fullband);
+        self.set(OPUS_SET_MAX_BANDWIDTH_REQUEST, bandwidth.to_opus())
+    }
+
+    /// The bandwidth cap, `OPUS_GET_MAX_BANDWIDTH`
+    pub fn max_bandwidth(&self) -> Result<Bandwidth, i32> {
+        let value = self.get(OPUS_GET_MAX_BANDWIDTH_REQUEST)?;
+        Bandwidth::from_opus(value).ok_or(OPUS_INTERNAL_ERROR)
+    }
+
+    /// Force mono (`Some(1)`) or stereo (`Some(2)`) coding with
+    /// `OPUS_SET_FORCE_CHANNELS`, e.g. mono for a stereo stream whose
+    /// source is effectively mono, to save bits. `None` (`OPUS_AUTO`), the
+    /// default, lets the encoder decide. Returns `OPUS_BAD_ARG` for any
+    /// other count, or for stereo on a mono encoder.
+    pub fn set_force_channels(&mut self, channels: Option<u8>) -> Result<(), i32> {
+        let value = match channels {
+            None => OPUS_AUTO,
+            Some(channels @ (1 | 2)) => channels as i32,
+            Some(_) => return Err(OPUS_BAD_ARG),
+        };
+        self.set(OPUS_SET_FORCE_CHANNELS_REQUEST, value)
+    }
+
+    /// The forced channel count, `OPUS_GET_FORCE_CHANNELS`: `None` unless
+    /// [`set_force_channels`](Self::set_force_channels) forced one
+    pub fn force_channels(&self) -> Result<Option<u8>, i32> {
+        match self.get(OPUS_GET_FORCE_CHANNELS_REQUEST)? {
+            OPUS_AUTO => Ok(None),
+            channels @ (1 | 2) => Ok(Some(channels as u8)),
+            _ => Err(OPUS_INTERNAL_ERROR),
+        }
+    }
+
+    /// Disable phase inversion with `OPUS_SET_PHASE_INVERSION_DISABLED`.
+    /// Intensity stereo may invert the phase of one channel, which cancels
+    /// out when the decoded stereo is downmixed to mono; disabling it costs
+    /// a little stereo quality.
+    pub fn set_phase_inversion_disabled(&mut self, disabled: bool) -> Result<(), i32> {
+        self.set(OPUS_SET_PHASE_INVERSION_DISABLED_REQUEST, disabled as i32)
+    }
+
+    /// Whether phase inversion is disabled,
+    /// `OPUS_GET_PHASE_INVERSION_DISABLED`
+    pub fn phase_inversion_disabled(&self) -> Result<bool, i32> {
+        self.get(OPUS_GET_PHASE_INVERSION_DISABLED_REQUEST)
+            .map(|value| value == 1)
+    }
+
+    /// The ctls that steer SILK, the speech path, see [`Silk`]
+    pub fn silk(&mut self) -> Silk<'_> {
+        Silk { encoder: self }
+    }
+
+    /// `opus_encoder_ctl` with a setter `request`, returning its error code
+    fn set(&mut self, request: u32, value: i32) -> Result<(), i32> {
+        let ret = unsafe { opus_encoder_ctl(self.encoder, request as i32, value) };
+        if ret == OPUS_OK as i32 {
+            Ok(())
+        } else {
+            Err(ret)
+        }
+    }
+
+    /// `opus_encoder_ctl` with a getter `request`, returning its error code
+    fn get(&self, request: u32) -> Result<i32, i32> {
+        let mut value: i32 = 0;
+        let ret = unsafe { opus_encoder_ctl(self.encoder, request as i32, &mut value as *mut i32) };
+        if ret == OPUS_OK as i32 {
+            Ok(value)
+        } else {
+            Err(ret)
+        }
+    }
+
+    /// Load DNN weights for DRED with `OPUS_SET_DNN_BLOB`, keeping them for
+    /// as long as the encoder uses them.
+    ///
+    /// On failure, e.g. for weights from another Opus version, the encoder
+    /// is left as it was: it keeps encoding, with the weights loaded before
+    /// or without DRED. See [`dnn`].
+    pub fn set_dnn_blob(&mut self, blob: &DnnBlob) -> Result<(), BlobError> {
+        unsafe { dnn::load_encoder(self.encoder, blob, self.dnn_blob.as_ref())? };
+        self.dnn_blob = Some(blob.clone());
+        Ok(())
+    }
+
+    /// Samples per channel buffered but not yet encoded
+    pub fn pending(&self) -> usize {
+        self.chunker.pending()
+    }
+
+    /// Buffer interleaved `samples` and call `on_packet` with each packet
+    /// encoded from a completed frame.
+    ///
+    /// `samples` need not hold whole frames or even whole sample frames
+    /// (one sample per channel). If encoding fails, the error is returned
+    /// and everything still buffered is discarded.
+    pub fn push_with(
+        &mut self,
+        samples: &[i16],
+        mut on_packet: impl FnMut(&[u8]),
+    ) -> Result<(), i32> {
+        let (encoder, packet) = (self.encoder, &mut self.packet);
+        let frame_size = self.chunker.frame_size();
+        self.chunker.try_push(samples, |frame| {
+            let len = encode(encoder, frame, frame_size, packet)?;
+            on_packet(&packet[..len]);
+            Ok(())
+        })
+    }
+
+    /// Like [`push_with`](Self::push_with), returning the packets
+    pub fn push(&mut self, samples: &[i16]) -> Result<Vec<Vec<u8>>, i32> {
+        let mut packets = Vec::new();
+        self.push_with(samples, |packet| packets.push(packet.to_vec()))?;
+        Ok(packets)
+    }
+
+    /// Encode `pcm` as one frame of any Opus frame size into a packet
+    /// buffer that can go to a transport without a copy, bypassing the
+    /// buffering of [`push`](Self::push). Packets are split off a buffer
+    /// with room for several, which is reused once they are all dropped.
+    ///
+    /// Returns `OPUS_BAD_ARG` if `pcm` isn't whole sample frames or samples
+    /// are pending from `push`, which would otherwise come out after `pcm`,
+    /// or the error from `opus_encode`.
+    #[cfg(feature = "bytes")]
+    pub fn encode_to_bytes(&mut self, pcm: &[i16]) -> Result<Bytes, i32> {
+        let max = self.packet.len();
+        let mut bytes = core::mem::take(&mut self.bytes);
+        if bytes.capacity() < max {
+            bytes.reserve(max * BYTES_PACKETS);
+        }
+        bytes.resize(max, 0);
+        let result = self.encode_frame(pcm, &mut bytes);
+        bytes.truncate(*result.as_ref().unwrap_or(&0));
+        let packet = bytes.split().freeze();
+        self.bytes = bytes;
+        result.map(|_| packet)
+    }
+
+    /// Encode `pcm` as one frame into `out`, for `encode_to_bytes` and
+    /// [`OpusEncode`](crate::codec::OpusEncode)
+    pub(crate) fn encode_frame(&mut self, pcm: &[i16], out: &mut [u8]) -> Result<usize, i32> {
+        let channels = self.channels();
+        if self.pending() > 0 || !pcm.len().is_multiple_of(channels) {
+            return Err(OPUS_BAD_ARG);
+        }
+        encode(self.encoder, pcm, pcm.len() / channels, out)
+    }
+
+    /// Encode the buffered remainder, padded with silence to a full frame.
+    /// Returns `None` if nothing was buffered.
+    ///
+    /// A partial sample frame (fewer samples than channels) is padded too.
+    /// The encoder can keep being used afterwards.
+    pub fn finish(&mut self) -> Result<Option<Vec<u8>>, i32> {
+        let (encoder, packet) = (self.encoder, &mut self.packet);
+        let frame_size = self.chunker.frame_size();
+        let mut result = Ok(None);
+        self.chunker.flush(PadMode::Zero, |frame| {
+            result =
+                encode(encoder, frame, frame_size, packet).map(|len| Some(packet[..len].to_vec()));
+        });
+        result
+    }
+}
+
+/// SILK's ctls on a [`StreamEncoder`], from [`StreamEncoder::silk`]
+///
+/// SILK codes speech with linear prediction, in SILK-only and hybrid
+/// packets. These settings shape its decisions; CELT, which codes music and
+/// the high band of hybrid packets, takes them as hints at most.
+///
+/// ```
+/// use opus_head_sys::stream::StreamEncoder;
+///
+/// let mut encoder = StreamEncoder::voip(16000, 1).unwrap();
+/// // A lossy uplink: spend bits on redundancy
+/// encoder.silk().set_packet_loss_perc(20).unwrap();
+/// assert_eq!(encoder.silk().packet_loss_perc(), Ok(20));
+/// ```
+#[derive(Debug)]
+pub struct Silk<'a> {
+    encoder: &'a mut StreamEncoder,
+}
+
+impl Silk<'_> {
+    /// The packet loss to expect, in percent, with
+    /// `OPUS_SET_PACKET_LOSS_PERC`. SILK then scales down its long-term
+    /// prediction, so a lost packet degrades fewer of the frames after it,
+    /// and with in-band FEC (`OPUS_SET_INBAND_FEC`) adds LBRR frames, a low
+    /// bitrate copy of the previous frame, once the loss is high enough for
+    /// the bitrate. CELT leans less on prediction too. Returns
+    /// `OPUS_BAD_ARG` above 100.
+    pub fn set_packet_loss_perc(&mut self, percent: u8) -> Result<(), i32> {
+        if percent > 100 {
+            return Err(OPUS_BAD_ARG);
+        }
+        self.encoder
+            .set(OPUS_SET_PACKET_LOSS_PERC_REQUEST, percent as i32)
+    }
+
+    /// The expected packet loss in percent, `OPUS_GET_PACKET_LOSS_PERC`
+    pub fn packet_loss_perc(&self) -> Result<u8, i32> {
+        let percent = self.encoder.get(OPUS_GET_PACKET_LOSS_PERC_REQUEST)?;
+        u8::try_from(percent).map_err(|_| OPUS_INTERNAL_ERROR)
+    }
+
+    /// Stop predicting from earlier frames with
+    /// `OPUS_SET_PREDICTION_DISABLED`: SILK codes each frame as the first
+    /// after a reset, and CELT drops its inter-frame prediction, so every
+    /// packet decodes on its own. Costs quality at a given bitrate; for
+    /// transports that lose packets in bursts.
+    pub fn set_prediction_disabled(&mut self, disabled: bool) -> Result<(), i32> {
+        self.encoder
+            .set(OPUS_SET_PREDICTION_DISABLED_REQUEST, disabled as i32)
+    }
+
+    /// Whether prediction is disabled, `OPUS_GET_PREDICTION_DISABLED`
+    pub fn prediction_disabled(&self) -> Result<bool, i32> {
+        self.encoder
+            .get(OPUS_GET_PREDICTION_DISABLED_REQUEST)
+            .map(|value| value == 1)
+    }
+}
+
+/// The duration of `frame_size`, or `OPUS_BAD_ARG` unless it is an Opus
+/// frame size (2.5 to 120 ms) at `sample_rate` and there are 1 or 2
+/// `channels`
+fn check_args(sample_rate: i32, channels: usize, frame_size: usize) -> Result<FrameDuration, i32> {
+    let sample_rate = u32::try_from(sample_rate).map_err(|_| OPUS_BAD_ARG)?;
+    let duration = FrameDuration::from_samples(frame_size, sample_rate).ok_or(OPUS_BAD_ARG)?;
+    if !(1..=2).contains(&channels) {
+        return Err(OPUS_BAD_ARG);
+    }
+    Ok(duration)
+}
+
+/// Encode one frame of `frame_size` samples per channel into `packet`
+fn encode(
+    encoder: *mut OpusEncoder,
+    frame: &[i16],
+    frame_size: usize,
+    packet: &mut [u8],
+) -> Result<usize, i32> {
+    let len = unsafe {
+        opus_encode(
+            encoder,
+            frame.as_ptr(),
+            frame_size as i32,
+            packet.as_mut_ptr(),
+            packet.len() as i32,
+        )
+    };
+    if len < 0 {
+        Err(len)
+    } else {
+        Ok(len as usize)
+    }
+}
+
+impl Drop for StreamEncoder {
+    fn drop(&mut self) {
+        unsafe { opus_encoder_destroy(self.encoder) };
+    }
+}
+
+/// What [`FrameChunker::flush`] does with a partial last frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PadMode {
+    /// Pad it with zeros (silence) to a whole frame
+    Zero,
+    /// Drop it
+    Drop,
+}
+
+/// Splits interleaved samples of any length into frames, see the
+/// [module docs](self)
+///
+/// It holds at most one frame: whole frames in a push are handed out
+/// straight from the input, and only the remainder is copied, so the buffer
+/// never grows after [`new`](Self::new).
+///
+/// ```
+/// use opus_head_sys::packet::FrameDuration;
+/// use opus_head_sys::stream::{FrameChunker, PadMode};
+///
+/// // 20 ms stereo frames at 48 kHz, from 441 sample capture callbacks
+/// let mut chunker = FrameChunker::new(48000, 2, FrameDuration::Ms20).unwrap();
+/// let mut frames = 0;
+/// for _ in 0..10 {
+///     chunker.push(&[0i16; 441 * 2], |frame| {
+///         assert_eq!(frame.len(), 960 * 2);
+///         frames += 1;
+///     });
+/// }
+/// assert_eq!((frames, chunker.pending()), (4, 570));
+/// chunker.flush(PadMode::Zero, |_| frames += 1);
+/// assert_eq!(frames, 5);
+/// ```
+#[derive(Debug, Clone)]
+pub struct FrameChunker<T = i16> {
+    channels: usize,
+    frame_size: usize,
+    /// Interleaved samples of an incomplete frame
+    buffer: Vec<T>,
+}
+
+impl<T: Copy + Default> FrameChunker<T> {
+    /// Hand out frames of `duration` with `channels` channels at
+    /// `sample_rate`. Returns `OPUS_BAD_ARG` for no channels or a sample
+    /// rate below 400 Hz.
+    pub fn new(sample_rate: u32, channels: usize, duration: FrameDuration) -> Result<Self, i32> {
+        let frame_size = duration.samples(sample_rate) as usize;
+        if channels == 0 || frame_size == 0 {
+            return Err(OPUS_BAD_ARG);
+        }
+        Ok(Self::with_frame_size(frame_size, channels))
+    }
+
+    fn with_frame_size(frame_size: usize, channels: usize) -> Self {
+        FrameChunker {
+            channels,
+            frame_size,
+            buffer: Vec::with_capacity(frame_size * channels),
+        }
+    }
+
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    /// Samples per channel in each frame
+    pub fn frame_size(&self) -> usize {
+        self.frame_size
+    }
+
+    /// Samples per channel buffered, less than a frame
+    pub fn pending(&self) -> usize {
+        self.buffer.len() / self.channels
+    }
+
+    /// Buffer interleaved `samples` and call `on_frame` with each frame
+    /// completed, `frame_size() * channels()` samples.
+    ///
+    /// `samples` need not hold whole frames or even whole sample frames
+    /// (one sample per channel); the channels stay in order across pushes.
+    pub fn push(&mut self, samples: &[T], mut on_frame: impl FnMut(&[T])) {
+        let result = self.try_push(samples, |frame| {
+            on_frame(frame);
+            Ok::<(), core::convert::Infallible>(())
+        });
+        match result {
+            Ok(()) => {}
+            Err(never) => match never {},
+        }
+    }
+
+    /// [`push`](Self::push) with an `on_frame` that can fail. The first
+    /// error is returned, and the rest of `samples` is discarded along with
+    /// everything buffered.
+    pub fn try_push<E>(
+        &mut self,
+        mut samples: &[T],
+        mut on_frame: impl FnMut(&[T]) -> Result<(), E>,
+    ) -> Result<(), E> {
+        let frame_len = self.frame_size * self.channels;
+        if !self.buffer.is_empty() {
+            let take = (frame_len - self.buffer.len()).min(samples.len());
+            self.buffer.extend_from_slice(&samples[..take]);
+            samples = &samples[take..];
+            if self.buffer.len() < frame_len {
+                return Ok(());
+            }
+            let result = on_frame(&self.buffer);
+            self.buffer.clear();
+            result?;
+        }
+        let mut frames = samples.chunks_exact(frame_len);
+        for frame in &mut frames {
+            on_frame(frame)?;
+        }
+        self.buffer.extend_from_slice(frames.remainder());
+        Ok(())
+    }
+
+    /// End the stream: call `on_frame` with what is buffered padded with
+    /// zeros to a whole frame, or drop it, as `pad` says. Does nothing if
+    /// nothing is buffered; the chunker is empty afterwards either way.
+    pub fn flush(&mut self, pad: PadMode, on_frame: impl FnOnce(&[T])) {
+        if pad == PadMode::Zero && !self.buffer.is_empty() {
+            self.buffer
+                .resize(self.frame_size * self.channels, T::default());
+            on_frame(&self.buffer);
+        }
+        self.buffer.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: i32 = 48000;
+    const FRAME_SIZE: usize = 960; // 20ms at 48kHz
+
+    /// A 440 Hz stereo tone
+    fn signal(samples_per_channel: usize) -> Vec<i16> {
+        (0..samples_per_channel * 2)
+            .map(|i| {
+                let t = (i / 2) as f32 / SAMPLE_RATE as f32;
+                ((t * 440.0 * std::f32::consts::TAU).sin() * 10000.0) as i16
+            })
+            .collect()
+    }
+
+    fn decode(packets: &[Vec<u8>]) -> usize {
+        unsafe {
+            let mut error: i32 = 0;
+            let decoder = opus_decoder_create(SAMPLE_RATE, 2, &mut error);
+            assert_eq!(error, OPUS_OK as i32, "Failed to create decoder");
+            let mut output = vec![0i16; FRAME_SIZE * 2];
+            let mut total = 0;
+            for packet in packets {
+                let decoded = opus_decode(
+                    decoder,
+                    packet.as_ptr(),
+                    packet.len() as i32,
+                    output.as_mut_ptr(),
+                    FRAME_SIZE as i32,
+                    0,
+                );
+                assert_eq!(decoded, FRAME_SIZE as i32);
+                total += decoded as usize;
+            }
+            opus_decoder_destroy(decoder);
+            total
+        }
+    }
+
+    #[test]
+    fn test_arbitrary_chunks() {
+        let mut encoder =
+            StreamEncoder::new(SAMPLE_RATE, 2, OPUS_APPLICATION_AUDIO as i32, FRAME_SIZE).unwrap();
+        let input = signal(FRAME_SIZE * 5 + 100);
+
+        // Odd chunk sizes, including ones that split a stereo sample frame
+        let mut packets = Vec::new();
+        let mut offset = 0;
+        for chunk in [1, 333, 2047, 7, 1920, 4000].iter().cycle() {
+            let end = (offset + chunk).min(input.len());
+            packets.extend(encoder.push(&input[offset..end]).unwrap());
+            offset = end;
+            if offset == input.len() {
+                break;
+            }
+        }
+        assert_eq!(packets.len(), 5);
+        assert_eq!(encoder.pending(), 100);
+
+        packets.extend(encoder.finish().unwrap());
+        assert_eq!(packets.len(), 6);
+        assert_eq!(encoder.pending(), 0);
+        assert_eq!(encoder.finish(), Ok(None));
+        assert_eq!(decode(&packets), FRAME_SIZE * 6);
+    }
+
+    #[test]
+    fn test_matches_whole_frames() {
+        // Chunking doesn't change the packets
+        let input = signal(FRAME_SIZE * 3);
+        let mut whole =
+            StreamEncoder::new(SAMPLE_RATE, 2, OPUS_APPLICATION_AUDIO as i32, FRAME_SIZE).unwrap();
+        let mut chunked =
+            StreamEncoder::new(SAMPLE_RATE, 2, OPUS_APPLICATION_AUDIO as i32, FRAME_SIZE).unwrap();
+
+        let expected = whole.push(&input).unwrap();
+        let mut packets = Vec::new();
+        for chunk in input.chunks(441) {
+            chunked
+                .push_with(chunk, |packet| packets.push(packet.to_vec()))
+                .unwrap();
+        }
+        assert_eq!(packets, expected);
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn test_encode_to_bytes() {
+        let input = signal(FRAME_SIZE * 3);
+        let mut pushed =
+            StreamEncoder::new(SAMPLE_RATE, 2, OPUS_APPLICATION_AUDIO as i32, FRAME_SIZE).unwrap();
+        let mut framed =
+            StreamEncoder::new(SAMPLE_RATE, 2, OPUS_APPLICATION_AUDIO as i32, FRAME_SIZE).unwrap();
+
+        let expected = pushed.push(&input).unwrap();
+        let packets: Vec<Bytes> = input
+            .chunks(FRAME_SIZE * 2)
+            .map(|frame| framed.encode_to_bytes(frame).unwrap())
+            .collect();
+        assert_eq!(packets, expected);
+        // Split off one buffer rather than allocated one by one
+        assert_eq!(
+            packets[1].as_ptr(),
+            packets[0].as_ptr().wrapping_add(packets[0].len())
+        );
+
+        // Half a sample frame, or samples pending from push
+        assert_eq!(framed.encode_to_bytes(&input[..1919]), Err(OPUS_BAD_ARG));
+        pushed.push(&input[..10]).unwrap();
+        assert_eq!(
+            pushed.encode_to_bytes(&input[..FRAME_SIZE * 2]),
+            Err(OPUS_BAD_ARG)
+        );
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        let app = OPUS_APPLICATION_AUDIO as i32;
+        for frame_size in [0, 100, 1000, 5760 + 120, 6720] {
+            assert_eq!(
+                StreamEncoder::new(SAMPLE_RATE, 2, app, frame_size).err(),
+                Some(OPUS_BAD_ARG),
+                "{}",
+                frame_size
+            );
+        }
+        assert!(StreamEncoder::new(SAMPLE_RATE, 2, app, 120).is_ok());
+        assert!(StreamEncoder::new(SAMPLE_RATE, 2, app, 5760).is_ok());
+        assert!(StreamEncoder::new(8000, 1, app, 160).is_ok());
+        assert_eq!(
+            StreamEncoder::new(SAMPLE_RATE, 3, app, FRAME_SIZE).err(),
+            Some(OPUS_BAD_ARG)
+        );
+        assert_eq!(
+            StreamEncoder::new(44100, 2, app, 882).err(),
+            Some(OPUS_BAD_ARG)
+        );
+    }
+
+    #[test]
+    fn test_raw_roundtrip() {
+        unsafe {
+            let mut error: i32 = 0;
+            let raw =
+                opus_encoder_create(SAMPLE_RATE, 2, OPUS_APPLICATION_AUDIO as i32, &mut error);
+            assert_eq!(error, OPUS_OK as i32, "Failed to create encoder");
+
+            // A frame size the encoder's sample rate doesn't allow leaves
+            // it with the caller
+            assert_eq!(
+                StreamEncoder::from_raw(raw, 2, 100).err(),
+                Some(OPUS_BAD_ARG)
+            );
+
+            let mut encoder = StreamEncoder::from_raw(raw, 2, FRAME_SIZE).unwrap();
+            assert_eq!(encoder.as_ptr(), raw);
+            let packets = encoder.push(&signal(FRAME_SIZE + 10)).unwrap();
+            assert_eq!(decode(&packets), FRAME_SIZE);
+
+            // Still usable, and destroyed once, by the caller
+            assert_eq!(encoder.into_raw(), raw);
+            let mut packet = [0u8; max_packet_size(2, FrameDuration::Ms20)];
+            let frame = signal(FRAME_SIZE);
+            let len = opus_encode(
+                raw,
+                frame.as_ptr(),
+                FRAME_SIZE as i32,
+                packet.as_mut_ptr(),
+                packet.len() as i32,
+            );
+            assert!(len > 0, "Encoding failed: {}", len);
+            opus_encoder_destroy(raw);
+        }
+    }
+
+    #[test]
+    fn test_presets() {
+        let mut voip = StreamEncoder::voip(16000, 1).unwrap();
+        assert_eq!(voip.frame_size(), 320);
+        let mut dtx: i32 = 0;
+        unsafe {
+            opus_encoder_ctl(
+                voip.as_ptr(),
+                OPUS_GET_DTX_REQUEST as i32,
+                &mut dtx as *mut i32,
+            )
+        };
+        assert_eq!(dtx, 1);
+        assert_eq!(voip.push(&[0; 640]).unwrap().len(), 2);
+
+        assert_eq!(
+            StreamEncoder::music(SAMPLE_RATE, 2).unwrap().frame_size(),
+            960
+        );
+        let mut low_latency = StreamEncoder::low_latency(SAMPLE_RATE, 2).unwrap();
+        assert_eq!(low_latency.frame_size(), 480);
+        let packets = low_latency.push(&signal(480)).unwrap();
+        // CELT only
+        assert!(packets[0][0] & 0x80 != 0);
+
+        assert_eq!(StreamEncoder::voip(44100, 1).err(), Some(OPUS_BAD_ARG));
+        assert_eq!(StreamEncoder::voip(48000, 3).err(), Some(OPUS_BAD_ARG));
+    }
+
+    #[test]
+    fn test_configure_cbr() {
+        let mut encoder =
+            StreamEncoder::new(SAMPLE_RATE, 2, OPUS_APPLICATION_AUDIO as i32, FRAME_SIZE).unwrap();
+        encoder.configure_cbr(64000).unwrap();
+        let mut packets = encoder.push(&signal(FRAME_SIZE * 5 + 100)).unwrap();
+        assert_eq!(packets.len(), 5);
+        // 64 kbit/s in 20 ms packets
+        assert!(packets.iter().all(|packet| packet.len() == 160));
+        // The padded remainder too
+        packets.extend(encoder.finish().unwrap());
+        assert_eq!(packets[5].len(), 160);
+        assert_eq!(encoder.configure_cbr(0), Err(OPUS_BAD_ARG));
+    }
+
+    #[test]
+    fn test_in_dtx() {
+        let mut encoder = StreamEncoder::voip(SAMPLE_RATE, 1).unwrap();
+        let tone = crate::test_util::sine(440.0, SAMPLE_RATE as u32, FRAME_SIZE * 5, 0.5);
+        let packets = encoder.push(&tone).unwrap();
+        assert!(packets.iter().all(|packet| packet.len() > 2));
+        assert_eq!(encoder.in_dtx(), Ok(false));
+
+        // DTX starts after 200 ms of silence
+        let packets = encoder.push(&vec![0; FRAME_SIZE * 20]).unwrap();
+        assert_eq!(encoder.in_dtx(), Ok(true));
+        assert!(packets.last().unwrap().len() <= 2);
+
+        // Not without DTX
+        let mut encoder =
+            StreamEncoder::new(SAMPLE_RATE, 1, OPUS_APPLICATION_VOIP as i32, FRAME_SIZE).unwrap();
+        encoder.push(&vec![0; FRAME_SIZE * 20]).unwrap();
+        assert_eq!(encoder.in_dtx(), Ok(false));
+    }
+
+    #[test]
+    fn test_vbr_constraint() {
+        let mut encoder = StreamEncoder::music(SAMPLE_RATE, 2).unwrap();
+        assert_eq!(encoder.vbr_constraint(), Ok(true));
+        encoder.set_vbr_constraint(false).unwrap();
+        assert_eq!(encoder.vbr_constraint(), Ok(false));
+        encoder.set_vbr_constraint(true).unwrap();
+        assert_eq!(encoder.vbr_constraint(), Ok(true));
+    }
+
+    #[test]
+    fn test_max_bandwidth() {
+        use crate::packet::parse_toc;
+
+        let mut encoder = StreamEncoder::music(SAMPLE_RATE, 2).unwrap();
+        assert_eq!(encoder.max_bandwidth(), Ok(Bandwidth::Fullband));
+        for bandwidth in [
+            Bandwidth::Narrowband,
+            Bandwidth::Mediumband,
+            Bandwidth::Wideband,
+            Bandwidth::SuperWideband,
+            Bandwidth::Fullband,
+        ] {
+            encoder.set_max_bandwidth(Some(bandwidth)).unwrap();
+            assert_eq!(encoder.max_bandwidth(), Ok(bandwidth));
+        }
+
+        // Every packet stays within the cap
+        encoder
+            .set_max_bandwidth(Some(Bandwidth::Wideband))
+            .unwrap();
+        for packet in encoder.push(&signal(FRAME_SIZE * 10)).unwrap() {
+            let bandwidth = parse_toc(packet[0]).config.bandwidth();
+            assert!(bandwidth.to_opus() <= Bandwidth::Wideband.to_opus());
+        }
+        encoder.set_max_bandwidth(None).unwrap();
+        assert_eq!(encoder.max_bandwidth(), Ok(Bandwidth::Fullband));
+    }
+
+    #[test]
+    fn test_force_channels() {
+        use crate::packet::parse_toc;
+
+        let mut encoder = StreamEncoder::music(SAMPLE_RATE, 2).unwrap();
+        assert_eq!(encoder.force_channels(), Ok(None));
+        encoder.set_force_channels(Some(1)).unwrap();
+        assert_eq!(encoder.force_channels(), Ok(Some(1)));
+        // Mono packets from stereo input
+        for packet in encoder.push(&signal(FRAME_SIZE * 3)).unwrap() {
+            assert!(!parse_toc(packet[0]).stereo);
+        }
+        encoder.set_force_channels(Some(2)).unwrap();
+        assert_eq!(encoder.force_channels(), Ok(Some(2)));
+        encoder.set_force_channels(None).unwrap();
+        assert_eq!(encoder.force_channels(), Ok(None));
+
+        // Rejected before reaching libopus, leaving the setting alone
+        for channels in [0, 3, 255] {
+            assert_eq!(
+                encoder.set_force_channels(Some(channels)),
+                Err(OPUS_BAD_ARG)
+            );
+        }
+        assert_eq!(encoder.force_channels(), Ok(None));
+        let mut mono = StreamEncoder::voip(SAMPLE_RATE, 1).unwrap();
+        assert_eq!(mono.set_force_channels(Some(2)), Err(OPUS_BAD_ARG));
+        assert_eq!(mono.set_force_channels(Some(1)), Ok(()));
+    }
+
+    #[test]
+    fn test_silk() {
+        let mut encoder = StreamEncoder::voip(16000, 1).unwrap();
+        assert_eq!(
+            encoder.silk().packet_loss_perc(),
+            Ok(Preset::Voip.packet_loss_perc() as u8)
+        );
+        let mut silk = encoder.silk();
+        for percent in [0, 35, 100] {
+            silk.set_packet_loss_perc(percent).unwrap();
+            assert_eq!(silk.packet_loss_perc(), Ok(percent));
+        }
+        assert_eq!(silk.set_packet_loss_perc(101), Err(OPUS_BAD_ARG));
+        assert_eq!(silk.packet_loss_perc(), Ok(100));
+
+        assert_eq!(silk.prediction_disabled(), Ok(false));
+        silk.set_prediction_disabled(true).unwrap();
+        assert_eq!(silk.prediction_disabled(), Ok(true));
+
+        // Changes what SILK codes, with the same loss expected
+        let speech = crate::test_util::speech_like(1, 16000, 320 * 25);
+        let independent: usize = encoder.push(&speech).unwrap().iter().map(Vec::len).sum();
+        let mut predicted = StreamEncoder::voip(16000, 1).unwrap();
+        predicted.silk().set_packet_loss_perc(100).unwrap();
+        let predicted: usize = predicted.push(&speech).unwrap().iter().map(Vec::len).sum();
+        assert_ne!(independent, predicted);
+    }
+
+    #[test]
+    fn test_phase_inversion_disabled() {
+        let mut encoder = StreamEncoder::music(SAMPLE_RATE, 2).unwrap();
+        assert_eq!(encoder.phase_inversion_disabled(), Ok(false));
+        encoder.set_phase_inversion_disabled(true).unwrap();
+        assert_eq!(encoder.phase_inversion_disabled(), Ok(true));
+        assert_eq!(encoder.push(&signal(FRAME_SIZE)).unwrap().len(), 1);
+        encoder.set_phase_inversion_disabled(false).unwrap();
+        assert_eq!(encoder.phase_inversion_disabled(), Ok(false));
+    }
+
+    #[test]
+    fn test_sample_rate() {
+        for sample_rate in [8000, 12000, 16000, 24000, 48000] {
+            let encoder = StreamEncoder::voip(sample_rate, 1).unwrap();
+            assert_eq!(encoder.sample_rate(), Ok(sample_rate));
+        }
+    }
+
+    /// 48 kHz stereo, a different tone per channel
+    fn stereo(samples_per_channel: usize) -> Vec<i16> {
+        use crate::test_util::{interleave, sine};
+        interleave(&[
+            sine::<i16>(440.0, 48000, samples_per_channel, 0.3),
+            sine::<i16>(660.0, 48000, samples_per_channel, 0.3),
+        ])
+    }
+
+    #[test]
+    fn test_chunker_441_sample_chunks() {
+        let mut chunker = FrameChunker::new(48000, 2, FrameDuration::Ms20).unwrap();
+        let input = stereo(441 * 250);
+        let mut error: i32 = 0;
+        let encoder = unsafe {
+            opus_encoder_create(SAMPLE_RATE, 2, OPUS_APPLICATION_AUDIO as i32, &mut error)
+        };
+        assert_eq!(error, OPUS_OK as i32);
+
+        let mut chunked = Vec::new();
+        let mut duration = 0;
+        let mut packet = [0u8; max_packet_size(2, FrameDuration::Ms20)];
+        let mut on_frame = |frame: &[i16]| {
+            chunked.extend_from_slice(frame);
+            let len = encode(encoder, frame, FRAME_SIZE, &mut packet).unwrap();
+            duration += crate::packet::samples(&packet[..len], SAMPLE_RATE).unwrap();
+        };
+        for chunk in input.chunks(441 * 2) {
+            chunker.push(chunk, &mut on_frame);
+        }
+        let tail = chunker.pending();
+        assert_eq!(tail, 441 * 250 % FRAME_SIZE);
+        chunker.flush(PadMode::Zero, &mut on_frame);
+        unsafe { opus_encoder_destroy(encoder) };
+
+        // The frames are the input in order, channels still interleaved,
+        // and the last one padded
+        let whole = input.len() - tail * 2;
+        assert_eq!(chunked[..whole], input[..whole]);
+        assert_eq!(chunked[whole..whole + tail * 2], input[whole..]);
+        assert_eq!(chunked.len(), whole + FRAME_SIZE * 2);
+
+        let samples = input.len() / 2;
+        assert!(duration >= samples && duration < samples + FRAME_SIZE);
+        assert_eq!(duration, 441 * 250 - tail + FRAME_SIZE);
+    }
+
+    #[test]
+    fn test_chunker_flush() {
+        let input = stereo(FRAME_SIZE + 100);
+        for (pad, expected) in [(PadMode::Zero, Some(200)), (PadMode::Drop, None)] {
+            let mut chunker = FrameChunker::new(48000, 2, FrameDuration::Ms20).unwrap();
+            // Ending on half a sample frame
+            chunker.push(&input[..input.len() - 1], |_| {});
+            assert_eq!(chunker.pending(), 99);
+            let mut last = None;
+            chunker.flush(pad, |frame| last = Some(frame.to_vec()));
+            if let Some(frame) = &last {
+                assert_eq!(frame.len(), FRAME_SIZE * 2);
+                assert_eq!(frame[..199], input[FRAME_SIZE * 2..input.len() - 1]);
+                assert!(frame[199..].iter().all(|&s| s == 0));
+            }
+            assert_eq!(last.map(|_| 200), expected);
+            assert_eq!(chunker.pending(), 0);
+            // Nothing left to flush
+            chunker.flush(PadMode::Zero, |_| panic!("Flushed twice"));
+        }
+    }
+
+    #[test]
+    fn test_chunker_no_reallocation() {
+        let mut chunker = FrameChunker::new(48000, 2, FrameDuration::Ms10).unwrap();
+        let buffer = chunker.buffer.as_ptr();
+        let capacity = chunker.buffer.capacity();
+        let input = vec![0.25f32; 48000 * 2];
+        let mut frames = 0;
+        for size in [441, 480, 1, 7919, 960, 3] {
+            for chunk in input.chunks(size * 2) {
+                chunker.push(chunk, |frame| {
+                    assert_eq!(frame.len(), 960);
+                    frames += 1;
+                });
+                assert_eq!(chunker.buffer.as_ptr(), buffer);
+                assert_eq!(chunker.buffer.capacity(), capacity);
+            }
+        }
+        assert_eq!(frames, 600);
+    }
+
+    #[test]
+    fn test_chunker_try_push() {
+        let mut chunker = FrameChunker::new(16000, 1, FrameDuration::Ms20).unwrap();
+        let input = [1i16; 1000];
+        chunker.push(&input[..100], |_| {});
+        let mut frames = 0;
+        let result = chunker.try_push(&input, |_| {
+            frames += 1;
+            if frames == 2 {
+                Err(OPUS_INTERNAL_ERROR)
+            } else {
+                Ok(())
+            }
+        });
+        assert_eq!(result, Err(OPUS_INTERNAL_ERROR));
+        // The rest is discarded
+        assert_eq!(chunker.pending(), 0);
+        assert_eq!(
+            chunker.try_push(&input[..10], |_| Ok::<(), i32>(())),
+            Ok(())
+        );
+        assert_eq!(chunker.pending(), 10);
+    }
+
+    #[test]
+    fn test_chunker_invalid() {
+        assert_eq!(
+            FrameChunker::<i16>::new(48000, 0, FrameDuration::Ms20).err(),
+            Some(OPUS_BAD_ARG)
+        );
+        assert_eq!(
+            FrameChunker::<i16>::new(100, 1, FrameDuration::Ms20).err(),
+            Some(OPUS_BAD_ARG)
+        );
+        // Any channel count, e.g. for a multistream encoder
+        let chunker = FrameChunker::<i16>::new(48000, 6, FrameDuration::Ms2_5).unwrap();
+        assert_eq!((chunker.channels(), chunker.frame_size()), (6, 120));
+    }
+}
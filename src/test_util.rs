@@ -0,0 +1,273 @@
+//! Deterministic test signals
+//!
+//! Generators for the inputs an Opus test typically needs: seeded white
+//! noise, sines, linear sweeps and speech-like noise bursts, plus helpers
+//! to interleave channels. Each returns `i16` or `f32` samples, whichever
+//! the caller asks for, both from the same signal in `[-1, 1]` full scale.
+//!
+//! The output is the same on every platform and Rust version, so it can
+//! feed golden tests: the noise comes from SplitMix64 rather than a
+//! `Hasher`, and the sines from a fixed polynomial rather than the
+//! platform's `libm`. Everything else is plain IEEE arithmetic done in a
+//! fixed order.
+//!
+//! ```
+//! use opus_head_sys::test_util::{interleave, noise, sine};
+//!
+//! // 20 ms of a 440 Hz tone on the left and noise on the right, at 48 kHz
+//! let left: Vec<i16> = sine(440.0, 48000, 960, 0.5);
+//! let right: Vec<i16> = noise(1, 960);
+//! let stereo = interleave(&[left, right]);
+//! assert_eq!(stereo.len(), 1920);
+//!
+//! // The same tone for opus_encode_float
+//! let float: Vec<f32> = sine(440.0, 48000, 960, 0.5);
+//! # assert_eq!(float.len(), 960);
+//! ```
+
+use crate::layout;
+
+/// A sample type the generators produce
+pub trait Sample: Copy {
+    /// Convert from full scale, where 1.0 is the largest magnitude
+    fn from_f64(value: f64) -> Self;
+}
+
+impl Sample for i16 {
+    fn from_f64(value: f64) -> Self {
+        // `as` saturates, so 1.0 gives 32767
+        (value * 32768.0).round() as i16
+    }
+}
+
+impl Sample for f32 {
+    fn from_f64(value: f64) -> Self {
+        value as f32
+    }
+}
+
+/// SplitMix64, which is small, fast and fully specified
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `[-1, 1)`, on a 16-bit grid so `i16` output is exact
+    fn next_unit(&mut self) -> f64 {
+        f64::from((self.next() >> 48) as u16 as i16) / 32768.0
+    }
+}
+
+/// Phase of a full turn, as a 32-bit fraction
+const TURN: f64 = 4_294_967_296.0;
+
+/// Phase advance per sample for `freq` Hz
+fn phase_increment(freq: f64, sample_rate: u32) -> u64 {
+    (freq / f64::from(sample_rate) * TURN).round() as u64
+}
+
+/// `sin(2π · phase / 2³²)`. A Taylor polynomial on the first quarter turn,
+/// accurate to about 1e-9, evaluated the same way everywhere, unlike
+/// `f64::sin`.
+fn sin_turn(phase: u32) -> f64 {
+    const QUARTER: u32 = 1 << 30;
+    const HALF: u32 = 1 << 31;
+    let negative = phase >= HALF;
+    let mut phase = phase % HALF;
+    if phase > QUARTER {
+        phase = HALF - phase;
+    }
+    let x = f64::from(phase) * (core::f64::consts::PI / f64::from(HALF));
+    let x2 = x * x;
+    let mut sum = 1.0 / 6_227_020_800.0;
+    for divisor in [-39_916_800.0, 362_880.0, -5_040.0, 120.0, -6.0] {
+        sum = 1.0 / divisor + x2 * sum;
+    }
+    let sin = x * (1.0 + x2 * sum);
+    if negative {
+        -sin
+    } else {
+        sin
+    }
+}
+
+/// White noise, uniform between -0.5 and 0.5 full scale (±16384 as `i16`),
+/// the same for the same `seed`
+pub fn noise<T: Sample>(seed: u64, samples: usize) -> Vec<T> {
+    let mut rng = SplitMix64(seed);
+    (0..samples)
+        .map(|_| T::from_f64(rng.next_unit() * 0.5))
+        .collect()
+}
+
+/// A sine of `freq` Hz with peak `amplitude` (full scale is 1.0), starting
+/// at phase 0
+pub fn sine<T: Sample>(freq: f64, sample_rate: u32, samples: usize, amplitude: f64) -> Vec<T> {
+    let increment = phase_increment(freq, sample_rate);
+    (0..samples as u64)
+        .map(|i| T::from_f64(amplitude * sin_turn(increment.wrapping_mul(i) as u32)))
+        .collect()
+}
+
+/// A sine whose frequency rises (or falls) linearly from `f0` Hz at the
+/// first sample to `f1` Hz at the last, with peak `amplitude`
+pub fn sweep<T: Sample>(
+    f0: f64,
+    f1: f64,
+    sample_rate: u32,
+    samples: usize,
+    amplitude: f64,
+) -> Vec<T> {
+    let start = i128::from(phase_increment(f0, sample_rate));
+    let end = i128::from(phase_increment(f1, sample_rate));
+    let steps = samples.saturating_sub(1).max(1) as i128;
+    let mut phase: u32 = 0;
+    (0..samples as i128)
+        .map(|i| {
+            let sample = T::from_f64(amplitude * sin_turn(phase));
+            let increment = start + (end - start) * i / steps;
+            phase = phase.wrapping_add(increment as u32);
+            sample
+        })
+        .collect()
+}
+
+/// Noise with a speech-like spectral tilt in bursts at a syllable rate of
+/// 4 Hz, which the encoder treats as active speech throughout: the
+/// envelope dips to 15% between bursts but never to silence. Peaks stay
+/// below full scale.
+pub fn speech_like<T: Sample>(seed: u64, sample_rate: u32, samples: usize) -> Vec<T> {
+    let mut rng = SplitMix64(seed);
+    let increment = phase_increment(4.0, sample_rate);
+    let mut lowpass = 0.0;
+    (0..samples as u64)
+        .map(|i| {
+            lowpass = 0.9 * lowpass + 0.1 * rng.next_unit();
+            let burst = sin_turn(increment.wrapping_mul(i) as u32).max(0.0);
+            T::from_f64(lowpass * (0.15 + 0.85 * burst) * 1.25)
+        })
+        .collect()
+}
+
+/// Interleave equally long channels into one buffer, e.g. `[left, right]`
+/// into stereo for `opus_encode`
+///
+/// # Panics
+///
+/// If `channels` is empty or the channels differ in length.
+pub fn interleave<T: Copy, C: AsRef<[T]>>(channels: &[C]) -> Vec<T> {
+    let planar: Vec<&[T]> = channels.iter().map(AsRef::as_ref).collect();
+    // The right length when the channels are; overwritten in place
+    let mut out = planar.concat();
+    layout::interleave(&planar, &mut out).expect("Channels must be non-empty and equally long");
+    out
+}
+
+/// Channel `index` of `interleaved` samples with `channels` channels
+pub fn channel<T: Copy>(interleaved: &[T], channels: usize, index: usize) -> Vec<T> {
+    interleaved
+        .iter()
+        .skip(index)
+        .step_by(channels)
+        .copied()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sin_turn_accuracy() {
+        for i in 0..10_000u32 {
+            let phase = i.wrapping_mul(429_497);
+            let expected = (f64::from(phase) / TURN * core::f64::consts::TAU).sin();
+            assert!((sin_turn(phase) - expected).abs() < 1e-9, "phase {}", phase);
+        }
+        assert_eq!(sin_turn(0), 0.0);
+        assert_eq!(sin_turn(1 << 31), 0.0);
+        assert!((sin_turn(1 << 30) - 1.0).abs() < 1e-9);
+        assert_eq!(sin_turn(3 << 30), -sin_turn(1 << 30));
+    }
+
+    // The samples below pin the output, which must never change with the
+    // platform or the Rust version: downstream golden tests depend on it
+
+    #[test]
+    fn test_noise_pinned() {
+        let pcm: Vec<i16> = noise(1, 6);
+        assert_eq!(pcm, [-14203, -8331, -951, 14561, 14558, -7770]);
+        let pcm: Vec<f32> = noise(42, 4);
+        let bits: Vec<u32> = pcm.iter().map(|s| s.to_bits()).collect();
+        assert_eq!(bits, [0xbe84_5200, 0x3e23_bc00, 0x3e8e_a400, 0x3eb0_3800]);
+        assert_ne!(noise::<i16>(1, 6), noise::<i16>(2, 6));
+    }
+
+    #[test]
+    fn test_sine_pinned() {
+        let pcm: Vec<i16> = sine(1000.0, 48000, 8, 0.5);
+        assert_eq!(pcm, [0, 2139, 4240, 6270, 8192, 9974, 11585, 12998]);
+        let pcm: Vec<f32> = sine(440.0, 48000, 100, 0.5);
+        assert_eq!(
+            [pcm[1].to_bits(), pcm[37].to_bits(), pcm[99].to_bits()],
+            [0x3ceb_c842, 0x3ed8_dcfb, 0xbe8c_8cc1]
+        );
+        // A quarter of the sample rate hits the peaks exactly
+        let pcm: Vec<i16> = sine(12000.0, 48000, 8, 1.0);
+        assert_eq!(pcm, [0, 32767, 0, -32768, 0, 32767, 0, -32768]);
+    }
+
+    #[test]
+    fn test_sweep_pinned() {
+        let pcm: Vec<i16> = sweep(100.0, 8000.0, 48000, 48000, 0.5);
+        assert_eq!(
+            [pcm[0], pcm[1], pcm[1000], pcm[24000], pcm[47999]],
+            [0, 214, -15703, 2112, -14189]
+        );
+        let pcm: Vec<f32> = sweep(100.0, 8000.0, 48000, 48000, 0.5);
+        assert_eq!(
+            [pcm[1000].to_bits(), pcm[47999].to_bits()],
+            [0xbef5_5a21, 0xbedd_b412]
+        );
+    }
+
+    #[test]
+    fn test_speech_like_pinned() {
+        let pcm: Vec<i16> = speech_like(7, 16000, 16000);
+        assert_eq!(
+            [pcm[0], pcm[1], pcm[100], pcm[2000], pcm[6000]],
+            [479, 456, -1034, 236, -784]
+        );
+        let pcm: Vec<f32> = speech_like(7, 16000, 16000);
+        assert_eq!(
+            [pcm[100].to_bits(), pcm[6000].to_bits()],
+            [0xbd01_33d1, 0xbcc3_f64a]
+        );
+        // Active in every 20 ms frame, between bursts too
+        for frame in speech_like::<i16>(1, 16000, 16000).chunks(320) {
+            assert!(frame.iter().any(|&s| s.abs() > 100));
+        }
+    }
+
+    #[test]
+    fn test_interleave() {
+        let stereo = interleave(&[[1, 2, 3], [4, 5, 6]]);
+        assert_eq!(stereo, [1, 4, 2, 5, 3, 6]);
+        assert_eq!(channel(&stereo, 2, 0), [1, 2, 3]);
+        assert_eq!(channel(&stereo, 2, 1), [4, 5, 6]);
+        let mono = interleave(&[vec![1.0f32, 2.0]]);
+        assert_eq!(mono, [1.0, 2.0]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_interleave_mismatched_lengths() {
+        interleave(&[&[1, 2][..], &[3][..]]);
+    }
+}
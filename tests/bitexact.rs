@@ -0,0 +1,173 @@
+//! Bit-exactness regression test on the range coder's final state
+//!
+//! Encodes a fixed input with a fixed configuration and compares the
+//! encoder's `OPUS_GET_FINAL_RANGE` after each frame with [`GOLDEN`], so a
+//! bump of the vendored Opus or a build change (SIMD presumption, compiler
+//! flags) that alters the bitstream fails here instead of going unnoticed.
+//! Every packet is also decoded, and the decoder's final range must equal
+//! the encoder's, which catches decoder drift on any build.
+//!
+//! The golden list is for the fixed-point build, whose integer arithmetic
+//! gives the same bitstream on every target and SIMD level. Float builds
+//! differ with the instructions the compiler and the runtime CPU detection
+//! pick, so they only get the encoder/decoder comparison:
+//!
+//! `cargo test --no-default-features --features std,no-float-api --test bitexact`
+//!
+//! When a change to the bitstream is intended, e.g. after updating
+//! `vendored/opus`, regenerate the list and paste it over [`GOLDEN`]:
+//!
+//! `UPDATE_GOLDEN=1 cargo test --no-default-features --features std,no-float-api --test bitexact -- --nocapture`
+
+use opus_head_sys::packet::{max_packet_size, FrameDuration};
+use opus_head_sys::*;
+
+const SAMPLE_RATE: i32 = 48000;
+const CHANNELS: usize = 2;
+/// 20 ms
+const FRAME_SIZE: usize = 960;
+/// Frames coded at each bitrate: SILK, hybrid and CELT territory
+const SEGMENTS: [(i32, usize); 3] = [(12000, 10), (24000, 10), (64000, 10)];
+
+/// Encoder final range after each frame of the fixed-point build
+#[rustfmt::skip]
+const GOLDEN: [u32; 30] = [
+    0x102a8500, 0x0311a400, 0x0898a400, 0x057a7c00, 0x0353a200, 0x08af9900,
+    0x087ce300, 0x03242b00, 0x01e75c00, 0x07618300, 0x0b20fb00, 0x00cedd54,
+    0x1b64b100, 0x0116cf00, 0x29c06f00, 0x05ebdc00, 0x7aaa6500, 0x34324200,
+    0x02312300, 0x03ba6f00, 0x0aef6900, 0x016b3900, 0x19a90300, 0x04f68c00,
+    0x23a83400, 0x14e6d800, 0x02e3a400, 0x03691800, 0x2588f100, 0x14c03800,
+];
+
+/// Seeded noise mixed with a tone per channel, from the generators in
+/// `test_util`, which produce the same samples on every platform
+fn input(frames: usize) -> Vec<i16> {
+    let samples = frames * FRAME_SIZE;
+    let channels: Vec<Vec<i16>> = [(440.0, 1), (330.0, 2)]
+        .into_iter()
+        .map(|(freq, seed)| {
+            let tone: Vec<i16> = test_util::sine(freq, SAMPLE_RATE as u32, samples, 0.25);
+            let noise: Vec<i16> = test_util::noise(seed, samples);
+            // Noise 18 dB below full scale
+            tone.iter().zip(noise).map(|(&t, n)| t + n / 8).collect()
+        })
+        .collect();
+    test_util::interleave(&channels)
+}
+
+unsafe fn encoder_final_range(encoder: *mut OpusEncoder) -> u32 {
+    let mut range: u32 = 0;
+    let ret = opus_encoder_ctl(
+        encoder,
+        OPUS_GET_FINAL_RANGE_REQUEST as i32,
+        &mut range as *mut u32,
+    );
+    assert_eq!(ret, OPUS_OK as i32);
+    range
+}
+
+unsafe fn decoder_final_range(decoder: *mut OpusDecoder) -> u32 {
+    let mut range: u32 = 0;
+    let ret = opus_decoder_ctl(
+        decoder,
+        OPUS_GET_FINAL_RANGE_REQUEST as i32,
+        &mut range as *mut u32,
+    );
+    assert_eq!(ret, OPUS_OK as i32);
+    range
+}
+
+/// Encode the fixed input, decoding each packet, and return the encoder's
+/// final range per frame
+fn final_ranges() -> Vec<u32> {
+    let frames: usize = SEGMENTS.iter().map(|&(_, frames)| frames).sum();
+    let input = input(frames);
+    let mut frames = input.chunks_exact(FRAME_SIZE * CHANNELS);
+    let mut ranges = Vec::new();
+    unsafe {
+        let mut error: i32 = 0;
+        let encoder = opus_encoder_create(
+            SAMPLE_RATE,
+            CHANNELS as i32,
+            OPUS_APPLICATION_AUDIO as i32,
+            &mut error,
+        );
+        assert_eq!(error, OPUS_OK as i32, "Failed to create encoder");
+        opus_encoder_ctl(encoder, OPUS_SET_COMPLEXITY_REQUEST as i32, 10);
+        let decoder = opus_decoder_create(SAMPLE_RATE, CHANNELS as i32, &mut error);
+        assert_eq!(error, OPUS_OK as i32, "Failed to create decoder");
+
+        let mut packet = vec![0u8; max_packet_size(CHANNELS, FrameDuration::Ms20)];
+        let mut pcm = vec![0i16; FRAME_SIZE * CHANNELS];
+        for (bitrate, count) in SEGMENTS {
+            opus_encoder_ctl(encoder, OPUS_SET_BITRATE_REQUEST as i32, bitrate);
+            for frame in frames.by_ref().take(count) {
+                let len = opus_encode(
+                    encoder,
+                    frame.as_ptr(),
+                    FRAME_SIZE as i32,
+                    packet.as_mut_ptr(),
+                    packet.len() as i32,
+                );
+                assert!(len > 0, "Encoding frame {} failed: {}", ranges.len(), len);
+                let range = encoder_final_range(encoder);
+
+                let ret = opus_decode(
+                    decoder,
+                    packet.as_ptr(),
+                    len,
+                    pcm.as_mut_ptr(),
+                    FRAME_SIZE as i32,
+                    0,
+                );
+                assert_eq!(
+                    ret,
+                    FRAME_SIZE as i32,
+                    "Decoding frame {} failed",
+                    ranges.len()
+                );
+                assert_eq!(
+                    decoder_final_range(decoder),
+                    range,
+                    "Decoder final range differs from the encoder's at frame {} ({} bit/s)",
+                    ranges.len(),
+                    bitrate
+                );
+                ranges.push(range);
+            }
+        }
+        opus_decoder_destroy(decoder);
+        opus_encoder_destroy(encoder);
+    }
+    ranges
+}
+
+#[test]
+fn test_final_range_golden() {
+    let ranges = final_ranges();
+    assert_eq!(ranges.len(), GOLDEN.len());
+
+    if std::env::var_os("UPDATE_GOLDEN").is_some_and(|v| v == "1") {
+        println!("const GOLDEN: [u32; {}] = [", ranges.len());
+        for row in ranges.chunks(6) {
+            let row: Vec<String> = row.iter().map(|r| format!("{:#010x},", r)).collect();
+            println!("    {}", row.join(" "));
+        }
+        println!("];");
+        return;
+    }
+    if !build_info::FIXED_POINT {
+        eprintln!("Float build: skipping the golden final ranges, see the module docs");
+        return;
+    }
+
+    let mismatches: Vec<usize> = (0..GOLDEN.len())
+        .filter(|&i| ranges[i] != GOLDEN[i])
+        .collect();
+    assert!(
+        mismatches.is_empty(),
+        "The bitstream changed at frames {:?}. If intended, regenerate GOLDEN \
+         with UPDATE_GOLDEN=1 (see the module docs)",
+        mismatches
+    );
+}
@@ -0,0 +1,4 @@
+//! Unit tests for the Android NDK logic in the build script
+
+#[path = "../build/android.rs"]
+mod android;
@@ -0,0 +1,4 @@
+//! Unit tests for the Apple platform logic in the build script
+
+#[path = "../build/apple.rs"]
+mod apple;
@@ -0,0 +1,4 @@
+//! Unit tests for reading archive symbol indexes in the build script
+
+#[path = "../build/archive.rs"]
+mod archive;
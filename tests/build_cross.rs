@@ -0,0 +1,4 @@
+//! Unit tests for the cross-compilation logic in the build script
+
+#[path = "../build/cross.rs"]
+mod cross;
@@ -0,0 +1,4 @@
+//! Unit tests for the MSVC runtime selection in the build script
+
+#[path = "../build/crt.rs"]
+mod crt;
@@ -0,0 +1,4 @@
+//! Unit tests for the environment variable tracking in the build script
+
+#[path = "../build/env_vars.rs"]
+mod env_vars;
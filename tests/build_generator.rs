@@ -0,0 +1,4 @@
+//! Unit tests for the CMake generator selection in the build script
+
+#[path = "../build/generator.rs"]
+mod generator;
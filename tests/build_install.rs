@@ -0,0 +1,4 @@
+//! Unit tests for locating the installed library in the build script
+
+#[path = "../build/install.rs"]
+mod install;
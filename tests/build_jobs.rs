@@ -0,0 +1,4 @@
+//! Unit tests for the build parallelism logic in the build script
+
+#[path = "../build/jobs.rs"]
+mod jobs;
@@ -0,0 +1,4 @@
+//! Unit tests for the cross-language LTO checks in the build script
+
+#[path = "../build/lto.rs"]
+mod lto;
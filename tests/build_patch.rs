@@ -0,0 +1,4 @@
+//! Unit tests for the source patching in the build script
+
+#[path = "../build/patch.rs"]
+mod patch;
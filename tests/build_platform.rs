@@ -0,0 +1,4 @@
+//! Unit tests for the per-OS settings in the build script
+
+#[path = "../build/platform.rs"]
+mod platform;
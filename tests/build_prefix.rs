@@ -0,0 +1,4 @@
+//! Unit tests for the symbol prefixing in the build script
+
+#[path = "../build/prefix.rs"]
+mod prefix;
@@ -0,0 +1,4 @@
+//! Unit tests for the build tool checks in the build script
+
+#[path = "../build/preflight.rs"]
+mod preflight;
@@ -0,0 +1,4 @@
+//! Unit tests for the CMake build type selection in the build script
+
+#[path = "../build/profile.rs"]
+mod profile;
@@ -0,0 +1,4 @@
+//! Unit tests for the RTCD logic in the build script
+
+#[path = "../build/rtcd.rs"]
+mod rtcd;
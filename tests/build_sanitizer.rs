@@ -0,0 +1,4 @@
+//! Unit tests for the sanitizer selection in the build script
+
+#[path = "../build/sanitizer.rs"]
+mod sanitizer;
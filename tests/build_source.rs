@@ -0,0 +1,4 @@
+//! Unit tests for the Opus source tree selection in the build script
+
+#[path = "../build/source.rs"]
+mod source;
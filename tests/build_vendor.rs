@@ -0,0 +1,8 @@
+//! Unit tests for the Opus download and checksum logic in the build script
+
+#[path = "../build/patch.rs"]
+mod patch;
+#[path = "../build/vendor.rs"]
+mod vendor;
+#[path = "../build/version.rs"]
+mod version;
@@ -0,0 +1,4 @@
+//! Unit tests for the Opus version detection in the build script
+
+#[path = "../build/version.rs"]
+mod version;
@@ -0,0 +1,105 @@
+//! Runs the opus-roundtrip binary on generated WAV files
+
+#![cfg(feature = "cli")]
+
+use std::path::PathBuf;
+use std::process::Command;
+
+/// A tone as a 16-bit WAV file
+fn tone_wav(sample_rate: u32, channels: u16, frames: usize) -> Vec<u8> {
+    let data_len = (frames * channels as usize * 2) as u32;
+    let mut out = Vec::new();
+    out.extend_from_slice(b"RIFF");
+    out.extend_from_slice(&(36 + data_len).to_le_bytes());
+    out.extend_from_slice(b"WAVEfmt ");
+    out.extend_from_slice(&16u32.to_le_bytes());
+    out.extend_from_slice(&1u16.to_le_bytes());
+    out.extend_from_slice(&channels.to_le_bytes());
+    out.extend_from_slice(&sample_rate.to_le_bytes());
+    out.extend_from_slice(&(sample_rate * channels as u32 * 2).to_le_bytes());
+    out.extend_from_slice(&(channels * 2).to_le_bytes());
+    out.extend_from_slice(&16u16.to_le_bytes());
+    out.extend_from_slice(b"data");
+    out.extend_from_slice(&data_len.to_le_bytes());
+    for i in 0..frames {
+        let t = i as f64 / sample_rate as f64;
+        let sample = ((t * 440.0 * std::f64::consts::TAU).sin() * 8000.0) as i16;
+        for _ in 0..channels {
+            out.extend_from_slice(&sample.to_le_bytes());
+        }
+    }
+    out
+}
+
+/// Sample rate, channels and samples per channel of a WAV file this test or
+/// the binary wrote
+fn wav_info(data: &[u8]) -> (u32, u16, usize) {
+    let channels = u16::from_le_bytes([data[22], data[23]]);
+    let sample_rate = u32::from_le_bytes(data[24..28].try_into().unwrap());
+    let data_len = u32::from_le_bytes(data[40..44].try_into().unwrap()) as usize;
+    (sample_rate, channels, data_len / 2 / channels as usize)
+}
+
+fn scratch(name: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("cli");
+    std::fs::create_dir_all(&dir).unwrap();
+    dir.join(name)
+}
+
+fn roundtrip(args: &[&PathBuf]) {
+    let output = Command::new(env!("CARGO_BIN_EXE_opus-roundtrip"))
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "opus-roundtrip {:?} failed: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[test]
+fn test_encode_then_decode() {
+    for (sample_rate, channels) in [(48000, 2), (16000, 1), (8000, 2)] {
+        // Not a whole number of 20 ms frames
+        let frames = sample_rate as usize + 123;
+        let name = format!("tone-{}-{}", sample_rate, channels);
+        let input = scratch(&format!("{}.wav", name));
+        let opus = scratch(&format!("{}.opus", name));
+        let output = scratch(&format!("{}-decoded.wav", name));
+        std::fs::write(&input, tone_wav(sample_rate, channels, frames)).unwrap();
+
+        roundtrip(&[&input, &opus]);
+        assert!(std::fs::read(&opus).unwrap().starts_with(b"OggS"));
+        roundtrip(&[&opus, &output]);
+
+        // Pre-skip and end trimming give back exactly the input's length
+        let decoded = std::fs::read(&output).unwrap();
+        assert_eq!(wav_info(&decoded), (sample_rate, channels, frames));
+    }
+}
+
+#[test]
+fn test_wav_to_wav() {
+    let input = scratch("direct.wav");
+    let output = scratch("direct-decoded.wav");
+    std::fs::write(&input, tone_wav(24000, 1, 24000)).unwrap();
+
+    roundtrip(&[&input, &output]);
+    let decoded = std::fs::read(&output).unwrap();
+    assert_eq!(wav_info(&decoded), (24000, 1, 24000));
+}
+
+#[test]
+fn test_rejects_bad_input() {
+    let input = scratch("cd.wav");
+    std::fs::write(&input, tone_wav(44100, 2, 4410)).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_opus-roundtrip"))
+        .arg(&input)
+        .arg(scratch("cd.opus"))
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("44100"));
+}
@@ -0,0 +1,190 @@
+//! RFC 6716 decoder conformance against the official test vectors
+//!
+//! Decodes every `testvectorNN.bit` at each sample rate, mono and stereo,
+//! and compares the output with the reference `testvectorNN.dec` or
+//! `testvectorNNm.dec` using the `opus_compare` metric, as
+//! `vendored/opus/tests/run_vectors.sh` does with `opus_demo`. Ignored by
+//! default since the vectors aren't bundled; unpack
+//! https://opus-codec.org/docs/opus_testvectors-rfc8251.tar.gz and run
+//!
+//! `OPUS_TESTVECTORS_DIR=/path/to/opus_newvectors cargo test --test conformance -- --ignored`
+//!
+//! with the features under test, e.g. `--no-default-features --features std,no-float-api`
+//! for a fixed-point build.
+
+#[path = "conformance/compare.rs"]
+mod compare;
+
+use opus_head_sys::decoder::Decoder;
+use opus_head_sys::*;
+use std::path::Path;
+
+/// Rates `run_vectors.sh` is run at
+const RATES: [u32; 5] = [48000, 24000, 16000, 12000, 8000];
+
+/// Vectors in the set, `testvector01` to `testvector12`
+const VECTORS: usize = 12;
+
+/// A packet of an `opus_demo` bitstream
+struct Packet<'a> {
+    /// `None` for a lost packet
+    data: Option<&'a [u8]>,
+    /// Encoder's final range, 0 if unknown
+    final_range: u32,
+}
+
+/// Split an `opus_demo` bitstream: each packet is preceded by its length
+/// and the encoder's final range, both 32-bit big-endian
+fn parse_bitstream(mut bytes: &[u8]) -> Result<Vec<Packet<'_>>, String> {
+    let mut packets = Vec::new();
+    while bytes.len() >= 8 {
+        let len = u32::from_be_bytes(bytes[..4].try_into().unwrap()) as usize;
+        let final_range = u32::from_be_bytes(bytes[4..8].try_into().unwrap());
+        bytes = &bytes[8..];
+        if len > bytes.len() {
+            return Err(format!(
+                "Packet {} needs {} bytes, {} left",
+                packets.len(),
+                len,
+                bytes.len()
+            ));
+        }
+        let (data, rest) = bytes.split_at(len);
+        packets.push(Packet {
+            data: (len > 0).then_some(data),
+            final_range,
+        });
+        bytes = rest;
+    }
+    Ok(packets)
+}
+
+/// Decode like `opus_demo -d <rate> <channels> -ignore_extensions`,
+/// checking the decoder's final range against the encoder's
+fn decode(packets: &[Packet], rate: u32, channels: usize) -> Result<Vec<i16>, String> {
+    let mut output = Vec::new();
+    // 120 ms, the longest packet
+    let mut pcm = vec![0i16; rate as usize * 120 / 1000 * channels];
+
+    let mut decoder =
+        Decoder::new(rate as i32, channels).map_err(|e| format!("opus_decoder_create: {}", e))?;
+    let raw = decoder.as_ptr();
+    unsafe { opus_decoder_ctl(raw, OPUS_SET_IGNORE_EXTENSIONS_REQUEST as i32, 1i32) };
+
+    let mut lost_prev = true;
+    for (i, packet) in packets.iter().enumerate() {
+        let samples = decoder
+            .decode(packet.data, &mut pcm, false)
+            .map_err(|e| format!("Packet {} failed to decode: {}", i, e))?;
+        output.extend_from_slice(&pcm[..samples * channels]);
+
+        let lost = packet.data.is_none();
+        if packet.final_range != 0 && !lost && !lost_prev {
+            let mut range: u32 = 0;
+            unsafe {
+                opus_decoder_ctl(
+                    raw,
+                    OPUS_GET_FINAL_RANGE_REQUEST as i32,
+                    &mut range as *mut u32,
+                )
+            };
+            if range != packet.final_range {
+                return Err(format!(
+                    "Range coder state mismatch in packet {}: 0x{:08x} vs 0x{:08x}",
+                    i, packet.final_range, range
+                ));
+            }
+        }
+        lost_prev = lost;
+    }
+    Ok(output)
+}
+
+/// Decode one vector and return the better quality of the two references
+fn check_vector(dir: &Path, name: &str, rate: u32, channels: usize) -> Result<f32, String> {
+    let bitstream = std::fs::read(dir.join(format!("{}.bit", name)))
+        .map_err(|e| format!("{}.bit: {}", name, e))?;
+    let decoded = decode(&parse_bitstream(&bitstream)?, rate, channels)?;
+
+    let mut best: Option<compare::Comparison> = None;
+    for reference in [format!("{}.dec", name), format!("{}m.dec", name)] {
+        let Ok(bytes) = std::fs::read(dir.join(&reference)) else {
+            continue;
+        };
+        let comparison = compare::compare(&compare::read_pcm16(&bytes), &decoded, channels, rate)
+            .map_err(|e| format!("{}: {}", reference, e))?;
+        if best.is_none_or(|best| comparison.quality > best.quality) {
+            best = Some(comparison);
+        }
+    }
+
+    match best {
+        None => Err(format!("No {}.dec or {}m.dec", name, name)),
+        Some(best) if best.passes() => Ok(best.quality),
+        Some(best) => Err(format!(
+            "Quality {:.1} % fails (internal weighted error is {:.6})",
+            best.quality, best.error
+        )),
+    }
+}
+
+#[test]
+#[ignore = "needs OPUS_TESTVECTORS_DIR"]
+fn test_vectors() {
+    let dir = std::env::var_os("OPUS_TESTVECTORS_DIR")
+        .expect("set OPUS_TESTVECTORS_DIR to the unpacked opus-testvectors");
+    let dir = Path::new(&dir);
+    println!("{}", build_info::summary());
+
+    let mut failures = Vec::new();
+    let mut runs = 0;
+    for channels in [1, 2] {
+        for rate in RATES {
+            let mut total = 0.0;
+            for vector in 1..=VECTORS {
+                let name = format!("testvector{:02}", vector);
+                runs += 1;
+                match check_vector(dir, &name, rate, channels) {
+                    Ok(quality) => {
+                        println!("{} {} Hz {} ch: {:.1} %", name, rate, channels, quality);
+                        total += quality;
+                    }
+                    Err(e) => {
+                        println!("{} {} Hz {} ch: {}", name, rate, channels, e);
+                        failures.push(format!("{} {} Hz {} ch: {}", name, rate, channels, e));
+                    }
+                }
+            }
+            println!(
+                "Average quality at {} Hz, {} ch: {:.1} %",
+                rate,
+                channels,
+                total / VECTORS as f32
+            );
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "{} of {} decodes fail:\n{}",
+        failures.len(),
+        runs,
+        failures.join("\n")
+    );
+}
+
+#[test]
+fn test_parse_bitstream() {
+    let bytes = [
+        0, 0, 0, 2, 0x12, 0x34, 0x56, 0x78, 0xfc, 0xff, // one packet
+        0, 0, 0, 0, 0, 0, 0, 0, // lost
+        0, 0, 0, // trailing bytes are ignored, as by opus_demo
+    ];
+    let packets = parse_bitstream(&bytes).unwrap();
+    assert_eq!(packets.len(), 2);
+    assert_eq!(packets[0].data, Some(&[0xfc, 0xff][..]));
+    assert_eq!(packets[0].final_range, 0x1234_5678);
+    assert_eq!(packets[1].data, None);
+
+    assert!(parse_bitstream(&[0, 0, 0, 9, 0, 0, 0, 0, 0xfc]).is_err());
+}
@@ -0,0 +1,506 @@
+//! Port of the reference `opus_compare` (`vendored/opus/src/opus_compare.c`)
+//!
+//! Compares decoder output with a test vector's reference decode using the
+//! pseudo-NMR metric the RFC 6716 conformance test is defined by: per-band
+//! spectral energies of the error, weighted by a simple masking model of the
+//! reference. The arithmetic follows the C code step by step, in the same
+//! float and double precision, so the quality matches its output.
+
+/// `OPUS_PI` of the C code, 3.14159265F, rounds to the same float
+const PI: f32 = std::f32::consts::PI;
+
+/// Bands on which the pseudo-NMR is computed (Bark-derived CELT bands)
+const BANDS: [usize; 22] = [
+    0, 2, 4, 6, 8, 10, 12, 14, 16, 20, 24, 28, 32, 40, 48, 56, 68, 80, 96, 120, 156, 200,
+];
+const NBANDS: usize = 21;
+const NFREQS: usize = 240;
+
+const TEST_WIN_SIZE: usize = 480;
+const TEST_WIN_STEP: usize = 120;
+
+/// Outcome of a comparison
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Comparison {
+    /// Internal weighted error
+    pub error: f64,
+    /// Opus quality metric in percent; negative fails
+    pub quality: f32,
+}
+
+impl Comparison {
+    /// Whether the output meets the conformance bar
+    pub fn passes(&self) -> bool {
+        self.quality >= 0.0
+    }
+}
+
+/// Compare decoder output with a reference decode.
+///
+/// `reference` is a `.dec` file's samples, always interleaved stereo at
+/// 48 kHz. `decoded` is interleaved with `channels` (1 or 2) at `rate`;
+/// mono compares against the downmixed reference, like `opus_compare`
+/// without `-s`, and lower rates like `-r`.
+pub fn compare(
+    reference: &[i16],
+    decoded: &[i16],
+    channels: usize,
+    rate: u32,
+) -> Result<Comparison, String> {
+    if channels != 1 && channels != 2 {
+        return Err(format!("Channel count must be 1 or 2, got {}", channels));
+    }
+    let (ybands, downsample) = match rate {
+        48000 => (NBANDS, 1),
+        24000 => (19, 2),
+        16000 => (17, 3),
+        12000 => (15, 4),
+        8000 => (13, 6),
+        _ => {
+            return Err(format!(
+                "Sampling rate must be 8000, 12000, 16000, 24000, or 48000, got {}",
+                rate
+            ))
+        }
+    };
+    let yfreqs = NFREQS / downsample;
+
+    let xlength = reference.len() / 2;
+    let mut x: Vec<f32> = reference[..xlength * 2].iter().map(|&s| s as f32).collect();
+    if channels == 1 {
+        for xi in 0..xlength {
+            x[xi] = (0.5 * (x[2 * xi] + x[2 * xi + 1]) as f64) as f32;
+        }
+    }
+    let ylength = decoded.len() / channels;
+    let y: Vec<f32> = decoded[..ylength * channels]
+        .iter()
+        .map(|&s| s as f32)
+        .collect();
+    if xlength != ylength * downsample {
+        return Err(format!(
+            "Sample counts do not match ({}!={})",
+            xlength,
+            ylength * downsample
+        ));
+    }
+    if xlength < TEST_WIN_SIZE {
+        return Err(format!(
+            "Insufficient sample data ({}<{})",
+            xlength, TEST_WIN_SIZE
+        ));
+    }
+
+    let nframes = (xlength - TEST_WIN_SIZE + TEST_WIN_STEP) / TEST_WIN_STEP;
+    let mut xb = vec![0f32; nframes * NBANDS * channels];
+    let mut xs = vec![0f32; nframes * NFREQS * channels];
+    let mut ys = vec![0f32; nframes * yfreqs * channels];
+    // Per-band spectral energy of the original signal and the error
+    band_energy(
+        Some(&mut xb),
+        &mut xs,
+        NBANDS,
+        &x,
+        channels,
+        nframes,
+        TEST_WIN_SIZE,
+        TEST_WIN_STEP,
+        1,
+    );
+    band_energy(
+        None,
+        &mut ys,
+        ybands,
+        &y,
+        channels,
+        nframes,
+        TEST_WIN_SIZE / downsample,
+        TEST_WIN_STEP / downsample,
+        downsample,
+    );
+
+    let xb_at = |xi: usize, bi: usize, ci: usize| (xi * NBANDS + bi) * channels + ci;
+    for xi in 0..nframes {
+        // Frequency masking (low to high): 10 dB/Bark slope
+        for bi in 1..NBANDS {
+            for ci in 0..channels {
+                xb[xb_at(xi, bi, ci)] += 0.1 * xb[xb_at(xi, bi - 1, ci)];
+            }
+        }
+        // Frequency masking (high to low): 15 dB/Bark slope
+        for bi in (0..NBANDS - 1).rev() {
+            for ci in 0..channels {
+                xb[xb_at(xi, bi, ci)] += 0.03 * xb[xb_at(xi, bi + 1, ci)];
+            }
+        }
+        if xi > 0 {
+            // Temporal masking: -3 dB/2.5ms slope
+            for bi in 0..NBANDS {
+                for ci in 0..channels {
+                    xb[xb_at(xi, bi, ci)] += 0.5 * xb[xb_at(xi - 1, bi, ci)];
+                }
+            }
+        }
+        // Allowing some cross-talk
+        if channels == 2 {
+            for bi in 0..NBANDS {
+                let l = xb[xb_at(xi, bi, 0)];
+                let r = xb[xb_at(xi, bi, 1)];
+                xb[xb_at(xi, bi, 0)] += 0.01 * r;
+                xb[xb_at(xi, bi, 1)] += 0.01 * l;
+            }
+        }
+        // Apply masking
+        for bi in 0..ybands {
+            for xj in BANDS[bi]..BANDS[bi + 1] {
+                for ci in 0..channels {
+                    let mask = 0.1 * xb[xb_at(xi, bi, ci)];
+                    xs[(xi * NFREQS + xj) * channels + ci] += mask;
+                    ys[(xi * yfreqs + xj) * channels + ci] += mask;
+                }
+            }
+        }
+    }
+
+    // Average of consecutive frames to make the comparison slightly less
+    // sensitive
+    for bi in 0..ybands {
+        for xj in BANDS[bi]..BANDS[bi + 1] {
+            for ci in 0..channels {
+                let mut xtmp = xs[xj * channels + ci];
+                let mut ytmp = ys[xj * channels + ci];
+                for xi in 1..nframes {
+                    let xi_at = (xi * NFREQS + xj) * channels + ci;
+                    let yi_at = (xi * yfreqs + xj) * channels + ci;
+                    let xtmp2 = xs[xi_at];
+                    let ytmp2 = ys[yi_at];
+                    xs[xi_at] += xtmp;
+                    ys[yi_at] += ytmp;
+                    xtmp = xtmp2;
+                    ytmp = ytmp2;
+                }
+            }
+        }
+    }
+
+    // At a lower rate, the last 300 Hz are left out to allow for different
+    // transition bands, except at 12 kHz, where the last band already skips
+    // 400 Hz
+    let max_compare = match rate {
+        48000 => BANDS[NBANDS],
+        12000 => BANDS[ybands],
+        _ => BANDS[ybands] - 3,
+    };
+    let mut err = 0f64;
+    for xi in 0..nframes {
+        let mut ef = 0f64;
+        for bi in 0..ybands {
+            let mut eb = 0f64;
+            for xj in BANDS[bi]..BANDS[bi + 1].min(max_compare) {
+                for ci in 0..channels {
+                    let re = ys[(xi * yfreqs + xj) * channels + ci]
+                        / xs[(xi * NFREQS + xj) * channels + ci];
+                    let mut im = (re as f64 - (re as f64).ln() - 1.0) as f32;
+                    // Less sensitive around the SILK/CELT cross-over, to
+                    // allow for mode freedom in the filters
+                    if (79..=81).contains(&xj) {
+                        im *= 0.1;
+                    }
+                    if xj == 80 {
+                        im *= 0.1;
+                    }
+                    eb += im as f64;
+                }
+            }
+            eb /= ((BANDS[bi + 1] - BANDS[bi]) * channels) as f64;
+            ef += eb * eb;
+        }
+        // A fixed normalization accepts slightly lower quality for lower
+        // sampling rates
+        ef /= NBANDS as f64;
+        ef *= ef;
+        err += ef * ef;
+    }
+    let error = (err / nframes as f64).powf(1.0 / 16.0);
+    let quality = (100.0 * (1.0 - 0.5 * (1.0 + error).ln() / 1.13f64.ln())) as f32;
+    Ok(Comparison { error, quality })
+}
+
+/// Windowed DFT power of each frame in `ps`, and with `out` each band's
+/// mean power
+#[allow(clippy::too_many_arguments)]
+fn band_energy(
+    mut out: Option<&mut [f32]>,
+    ps: &mut [f32],
+    nbands: usize,
+    input: &[f32],
+    channels: usize,
+    nframes: usize,
+    window_sz: usize,
+    step: usize,
+    downsample: usize,
+) {
+    let cosf = |v: f32| (v as f64).cos() as f32;
+    let sinf = |v: f32| (v as f64).sin() as f32;
+    let window: Vec<f32> = (0..window_sz)
+        .map(|xj| 0.5 - 0.5 * cosf((2.0 * PI / (window_sz - 1) as f32) * xj as f32))
+        .collect();
+    let c: Vec<f32> = (0..window_sz)
+        .map(|xj| cosf((2.0 * PI / window_sz as f32) * xj as f32))
+        .collect();
+    let s: Vec<f32> = (0..window_sz)
+        .map(|xj| sinf((2.0 * PI / window_sz as f32) * xj as f32))
+        .collect();
+    let ps_sz = window_sz / 2;
+    let mut x = vec![0f32; channels * window_sz];
+
+    for xi in 0..nframes {
+        for ci in 0..channels {
+            for xk in 0..window_sz {
+                x[ci * window_sz + xk] = window[xk] * input[(xi * step + xk) * channels + ci];
+            }
+        }
+        let mut xj = 0;
+        for bi in 0..nbands {
+            let mut p = [0f32; 2];
+            while xj < BANDS[bi + 1] {
+                for ci in 0..channels {
+                    let mut re = 0f32;
+                    let mut im = 0f32;
+                    let mut ti = 0;
+                    for xk in 0..window_sz {
+                        re += c[ti] * x[ci * window_sz + xk];
+                        im -= s[ti] * x[ci * window_sz + xk];
+                        ti += xj;
+                        if ti >= window_sz {
+                            ti -= window_sz;
+                        }
+                    }
+                    re *= downsample as f32;
+                    im *= downsample as f32;
+                    let power = re * re + im * im + 100000.0;
+                    ps[(xi * ps_sz + xj) * channels + ci] = power;
+                    p[ci] += power;
+                }
+                xj += 1;
+            }
+            if let Some(out) = out.as_deref_mut() {
+                let width = (BANDS[bi + 1] - BANDS[bi]) as f32;
+                for ci in 0..channels {
+                    out[(xi * nbands + bi) * channels + ci] = p[ci] / width;
+                }
+            }
+        }
+    }
+}
+
+/// Samples of a `.dec` file or decoder output: 16-bit little-endian PCM
+pub fn read_pcm16(bytes: &[u8]) -> Vec<i16> {
+    bytes
+        .chunks_exact(2)
+        .map(|pair| i16::from_le_bytes([pair[0], pair[1]]))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAMES: usize = 9600; // 200ms at 48kHz
+
+    /// Stereo 48 kHz reference: a tone per channel over a little noise
+    fn reference(noise_amplitude: f64) -> Vec<i16> {
+        let mut noise = Noise(0x2545_f491_4f6c_dd1d);
+        (0..FRAMES)
+            .flat_map(|t| {
+                let t = t as f64 / 48000.0;
+                let left = 8000.0 * (2.0 * std::f64::consts::PI * 440.0 * t).sin();
+                let right = 6000.0 * (2.0 * std::f64::consts::PI * 1000.0 * t).sin();
+                [
+                    (left + noise.next(noise_amplitude)) as i16,
+                    (right + noise.next(noise_amplitude)) as i16,
+                ]
+            })
+            .collect()
+    }
+
+    /// Deterministic white noise
+    struct Noise(u64);
+
+    impl Noise {
+        fn next(&mut self, amplitude: f64) -> f64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            ((self.0 >> 11) as f64 / (1u64 << 53) as f64 * 2.0 - 1.0) * amplitude
+        }
+    }
+
+    fn add_noise(samples: &[i16], amplitude: f64) -> Vec<i16> {
+        let mut noise = Noise(0x9e37_79b9_7f4a_7c15);
+        samples
+            .iter()
+            .map(|&s| (s as f64 + noise.next(amplitude)) as i16)
+            .collect()
+    }
+
+    /// Stereo at `rate`, keeping every nth frame
+    fn decimate(stereo: &[i16], rate: u32) -> Vec<i16> {
+        stereo
+            .chunks_exact(2)
+            .step_by(48000 / rate as usize)
+            .flatten()
+            .copied()
+            .collect()
+    }
+
+    /// Mono at `rate`: the downmix, keeping every nth sample
+    fn downmix(stereo: &[i16], rate: u32) -> Vec<i16> {
+        stereo
+            .chunks_exact(2)
+            .step_by(48000 / rate as usize)
+            .map(|pair| ((pair[0] as i32 + pair[1] as i32) / 2) as i16)
+            .collect()
+    }
+
+    #[test]
+    fn test_identical() {
+        let reference = reference(30.0);
+        let comparison = compare(&reference, &reference, 2, 48000).unwrap();
+        assert_eq!(comparison.error, 0.0);
+        assert_eq!(comparison.quality, 100.0);
+        assert!(comparison.passes());
+    }
+
+    /// Errors and qualities printed by the C `opus_compare` for the same
+    /// files, built from `vendored/opus/src/opus_compare.c`
+    #[test]
+    fn test_matches_opus_compare() {
+        let noisy = reference(30.0);
+        let tones = reference(0.0);
+        let cases = [
+            // Good: faint noise, or only the rate conversion
+            (
+                "stereo",
+                &noisy,
+                add_noise(&noisy, 10.0),
+                2,
+                48000,
+                0.069277,
+                72.6,
+            ),
+            (
+                "mono",
+                &noisy,
+                add_noise(&downmix(&noisy, 48000), 10.0),
+                1,
+                48000,
+                0.073822,
+                70.9,
+            ),
+            (
+                "stereo 24k",
+                &tones,
+                decimate(&tones, 24000),
+                2,
+                24000,
+                0.002102,
+                99.1,
+            ),
+            (
+                "mono 16k",
+                &tones,
+                downmix(&tones, 16000),
+                1,
+                16000,
+                0.005749,
+                97.7,
+            ),
+            (
+                "mono 8k",
+                &tones,
+                add_noise(&downmix(&tones, 8000), 5.0),
+                1,
+                8000,
+                0.055658,
+                77.8,
+            ),
+            // Bad: loud noise, silence, aliasing
+            (
+                "stereo loud noise",
+                &noisy,
+                add_noise(&noisy, 4000.0),
+                2,
+                48000,
+                61.789751,
+                -1593.6,
+            ),
+            (
+                "stereo silence",
+                &noisy,
+                vec![0; FRAMES * 2],
+                2,
+                48000,
+                0.427045,
+                -45.5,
+            ),
+            (
+                "mono 16k aliased",
+                &noisy,
+                downmix(&noisy, 16000),
+                1,
+                16000,
+                0.300677,
+                -7.5,
+            ),
+            (
+                "mono 8k loud noise",
+                &noisy,
+                add_noise(&downmix(&noisy, 8000), 4000.0),
+                1,
+                8000,
+                130.495834,
+                -1896.0,
+            ),
+        ];
+
+        for (name, reference, decoded, channels, rate, error, quality) in cases {
+            let comparison = compare(reference, &decoded, channels, rate).unwrap();
+            assert!(
+                (comparison.error - error).abs() < 5e-6 * error.max(1.0),
+                "{}: error {} != {}",
+                name,
+                comparison.error,
+                error
+            );
+            assert!(
+                (comparison.quality - quality).abs() < 0.05,
+                "{}: quality {} != {}",
+                name,
+                comparison.quality,
+                quality
+            );
+            assert_eq!(comparison.passes(), quality >= 0.0, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_invalid_input() {
+        let reference = reference(30.0);
+        assert!(compare(&reference, &reference, 2, 44100).is_err());
+        assert!(compare(&reference, &reference, 3, 48000).is_err());
+        // Sample counts must match after the rate conversion
+        assert!(compare(&reference, &reference[..reference.len() - 2], 2, 48000).is_err());
+        assert!(compare(&reference, &downmix(&reference, 48000), 1, 24000).is_err());
+        // Less than one window
+        assert!(compare(&reference[..800], &reference[..800], 2, 48000).is_err());
+    }
+
+    #[test]
+    fn test_read_pcm16() {
+        assert_eq!(
+            read_pcm16(&[0x01, 0x00, 0xff, 0xff, 0x00, 0x80, 0x7f]),
+            [1, -1, -32768]
+        );
+    }
+}
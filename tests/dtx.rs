@@ -0,0 +1,153 @@
+//! Discontinuous transmission: what the encoder emits during silence and
+//! how the decoder bridges the gap
+
+use opus_head_sys::analysis::rms;
+use opus_head_sys::packet::{is_dtx_packet, max_packet_size, FrameDuration};
+use opus_head_sys::*;
+
+const SAMPLE_RATE: i32 = 16000;
+/// 20 ms
+const FRAME_SIZE: usize = 320;
+/// 400 ms
+const SILENT_FRAMES: usize = 20;
+const SPEECH_FRAMES: usize = 20;
+/// Frames of silence coded normally before DTX starts (200 ms)
+const HANGOVER: usize = 10;
+
+/// Encode each frame, with whether the encoder reported DTX after it
+unsafe fn encode(encoder: *mut OpusEncoder, input: &[i16]) -> Vec<(Vec<u8>, bool)> {
+    input
+        .chunks_exact(FRAME_SIZE)
+        .map(|frame| {
+            let mut packet = vec![0u8; max_packet_size(1, FrameDuration::Ms20)];
+            let len = opus_encode(
+                encoder,
+                frame.as_ptr(),
+                FRAME_SIZE as i32,
+                packet.as_mut_ptr(),
+                packet.len() as i32,
+            );
+            assert!(len > 0, "Encoding failed: {}", len);
+            packet.truncate(len as usize);
+
+            let mut in_dtx: i32 = -1;
+            let ret = opus_encoder_ctl(
+                encoder,
+                OPUS_GET_IN_DTX_REQUEST as i32,
+                &mut in_dtx as *mut i32,
+            );
+            assert_eq!(ret, OPUS_OK as i32);
+            (packet, in_dtx == 1)
+        })
+        .collect()
+}
+
+/// Silence, then speech, through an encoder with DTX
+fn dtx_packets() -> Vec<(Vec<u8>, bool)> {
+    let mut input = vec![0i16; SILENT_FRAMES * FRAME_SIZE];
+    input.extend(test_util::speech_like::<i16>(
+        1,
+        SAMPLE_RATE as u32,
+        SPEECH_FRAMES * FRAME_SIZE,
+    ));
+    unsafe {
+        let mut error: i32 = 0;
+        let encoder = opus_encoder_create(SAMPLE_RATE, 1, OPUS_APPLICATION_VOIP as i32, &mut error);
+        assert_eq!(error, OPUS_OK as i32, "Failed to create encoder");
+        opus_encoder_ctl(encoder, OPUS_SET_BITRATE_REQUEST as i32, 24000);
+        opus_encoder_ctl(encoder, OPUS_SET_DTX_REQUEST as i32, 1);
+        let packets = encode(encoder, &input);
+        opus_encoder_destroy(encoder);
+        packets
+    }
+}
+
+#[test]
+fn test_dtx_during_silence() {
+    let packets = dtx_packets();
+    let (silence, speech) = packets.split_at(SILENT_FRAMES);
+
+    // The hangover is coded normally, then every frame is DTX until the
+    // refresh after 400 ms of DTX, which 400 ms of silence doesn't reach
+    let first_dtx = silence
+        .iter()
+        .position(|(packet, _)| is_dtx_packet(packet))
+        .expect("No DTX packet in 400 ms of silence");
+    assert!(
+        first_dtx <= HANGOVER + 1,
+        "DTX started after {} frames",
+        first_dtx
+    );
+    for (i, (packet, in_dtx)) in silence.iter().enumerate().skip(first_dtx) {
+        assert!(
+            packet.len() <= 2,
+            "Silent frame {} is {} bytes",
+            i,
+            packet.len()
+        );
+        assert!(in_dtx, "OPUS_GET_IN_DTX is 0 at silent frame {}", i);
+    }
+
+    // Back to normal packets as soon as there is a signal
+    for (i, (packet, in_dtx)) in speech.iter().enumerate() {
+        assert!(!is_dtx_packet(packet), "Speech frame {} is a DTX packet", i);
+        assert!(!in_dtx, "OPUS_GET_IN_DTX is 1 at speech frame {}", i);
+    }
+}
+
+#[test]
+fn test_decoder_bridges_dtx_gap() {
+    let packets = dtx_packets();
+    // Feed nothing for the DTX'd frames, as a receiver does when the sender
+    // drops them, or conceal each with a null packet
+    for conceal in [false, true] {
+        unsafe {
+            let mut error: i32 = 0;
+            let decoder = opus_decoder_create(SAMPLE_RATE, 1, &mut error);
+            assert_eq!(error, OPUS_OK as i32, "Failed to create decoder");
+            let mut pcm = [0i16; FRAME_SIZE];
+            let mut speech = Vec::new();
+            for (i, (packet, _)) in packets.iter().enumerate() {
+                let ret = if !is_dtx_packet(packet) {
+                    opus_decode(
+                        decoder,
+                        packet.as_ptr(),
+                        packet.len() as i32,
+                        pcm.as_mut_ptr(),
+                        FRAME_SIZE as i32,
+                        0,
+                    )
+                } else if conceal {
+                    opus_decode(
+                        decoder,
+                        std::ptr::null(),
+                        0,
+                        pcm.as_mut_ptr(),
+                        FRAME_SIZE as i32,
+                        0,
+                    )
+                } else {
+                    continue;
+                };
+                assert_eq!(
+                    ret, FRAME_SIZE as i32,
+                    "Decoding frame {} failed (conceal: {})",
+                    i, conceal
+                );
+                if i >= SILENT_FRAMES {
+                    speech.push(rms(&pcm));
+                }
+            }
+            opus_decoder_destroy(decoder);
+
+            assert_eq!(speech.len(), SPEECH_FRAMES);
+            // Decoding resumes with audio, not silence
+            assert!(
+                speech.iter().skip(2).all(|&e| e > 100.0),
+                "conceal: {}, {:?}",
+                conceal,
+                speech
+            );
+        }
+    }
+}
@@ -0,0 +1,55 @@
+//! Compares the bindings generated at build time with the committed
+//! `src/bindings.rs`, so a vendored Opus update that adds constants or
+//! functions without regenerating the file fails here
+
+#![cfg(feature = "generate-bindings")]
+
+use std::collections::BTreeSet;
+
+const GENERATED: &str = include_str!(concat!(env!("OUT_DIR"), "/bindings.rs"));
+const COMMITTED: &str = include_str!("../src/bindings.rs");
+
+/// `pub const` lines, as written by bindgen
+fn constants(bindings: &str) -> BTreeSet<&str> {
+    bindings
+        .lines()
+        .filter(|line| line.starts_with("pub const "))
+        .collect()
+}
+
+/// Names of the `pub fn`s
+fn functions(bindings: &str) -> BTreeSet<&str> {
+    bindings
+        .lines()
+        .filter_map(|line| line.trim_start().strip_prefix("pub fn "))
+        .filter_map(|rest| rest.split('(').next())
+        .collect()
+}
+
+fn stale(generated: &BTreeSet<&str>, committed: &BTreeSet<&str>) -> String {
+    let added: Vec<_> = generated.difference(committed).collect();
+    let removed: Vec<_> = committed.difference(generated).collect();
+    format!(
+        "src/bindings.rs is stale, regenerate it with `python vendor_opus.py --bindings-only`\n\
+         missing: {:#?}\nnot in the headers: {:#?}",
+        added, removed
+    )
+}
+
+#[test]
+fn test_constants_match() {
+    let generated = constants(GENERATED);
+    let committed = constants(COMMITTED);
+    assert!(!generated.is_empty());
+    assert!(generated == committed, "{}", stale(&generated, &committed));
+}
+
+// The committed file keeps the float functions behind a cfg instead
+#[cfg(not(feature = "no-float-api"))]
+#[test]
+fn test_functions_match() {
+    let generated = functions(GENERATED);
+    let committed = functions(COMMITTED);
+    assert!(generated.contains("opus_encoder_create"));
+    assert!(generated == committed, "{}", stale(&generated, &committed));
+}
@@ -0,0 +1,89 @@
+//! Links the renamed library next to another definition of the plain Opus
+//! symbols, as when a second libopus is in the process
+
+#![cfg(feature = "prefix-symbols")]
+
+use opus_head_sys::*;
+use std::os::raw::c_int;
+
+/// Stands in for the other libopus' encoder
+#[no_mangle]
+extern "C" fn opus_encoder_create(
+    _fs: opus_int32,
+    _channels: c_int,
+    _application: c_int,
+    error: *mut c_int,
+) -> *mut OpusEncoder {
+    unsafe { *error = -1234 };
+    std::ptr::null_mut()
+}
+
+/// Would be called by the multistream encoder if its internal references
+/// were not renamed too
+#[no_mangle]
+extern "C" fn opus_encoder_init(
+    _st: *mut OpusEncoder,
+    _fs: opus_int32,
+    _channels: c_int,
+    _application: c_int,
+) -> c_int {
+    std::process::abort()
+}
+
+extern "C" {
+    #[link_name = "opus_encoder_create"]
+    fn plain_opus_encoder_create(
+        fs: opus_int32,
+        channels: c_int,
+        application: c_int,
+        error: *mut c_int,
+    ) -> *mut OpusEncoder;
+}
+
+#[test]
+fn test_both_definitions_coexist() {
+    unsafe {
+        // The bindings reach the renamed library
+        let mut error: c_int = 0;
+        let encoder = opus_head_sys::opus_encoder_create(
+            48000,
+            2,
+            OPUS_APPLICATION_AUDIO as c_int,
+            &mut error,
+        );
+        assert_eq!(error, OPUS_OK as c_int, "Failed to create encoder");
+        assert!(!encoder.is_null());
+
+        let pcm = vec![0i16; 960 * 2];
+        let mut packet = [0u8; 1275];
+        let len = opus_encode(encoder, pcm.as_ptr(), 960, packet.as_mut_ptr(), 1275);
+        assert!(len > 0, "Encoding failed: {}", len);
+        opus_encoder_destroy(encoder);
+
+        // The plain name is the stub's
+        let mut error: c_int = 0;
+        let stub = plain_opus_encoder_create(48000, 2, OPUS_APPLICATION_AUDIO as c_int, &mut error);
+        assert!(stub.is_null());
+        assert_eq!(error, -1234);
+    }
+}
+
+#[test]
+fn test_internal_calls_stay_inside() {
+    unsafe {
+        // opus_multistream_encoder_create calls opus_encoder_init internally
+        let mapping = [0u8, 1];
+        let mut error: c_int = 0;
+        let encoder = opus_multistream_encoder_create(
+            48000,
+            2,
+            1,
+            1,
+            mapping.as_ptr(),
+            OPUS_APPLICATION_AUDIO as c_int,
+            &mut error,
+        );
+        assert_eq!(error, OPUS_OK as c_int, "Failed to create encoder");
+        opus_multistream_encoder_destroy(encoder);
+    }
+}
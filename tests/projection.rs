@@ -0,0 +1,139 @@
+//! Ambisonics through the projection API (mapping family 3), with the
+//! demixing matrix handed from the encoder to the decoder as a receiver
+//! would get it out of band
+
+use opus_head_sys::packet::{max_packet_size, FrameDuration};
+use opus_head_sys::projection::demixing_matrix;
+use opus_head_sys::*;
+
+const SAMPLE_RATE: i32 = 48000;
+/// 20 ms
+const FRAME_SIZE: usize = 960;
+const FRAMES: usize = 25;
+
+/// Packets of an omnidirectional tone, only in W (ACN 0), with the stream
+/// counts and the demixing matrix
+fn encode_omni(channels: usize) -> (Vec<Vec<u8>>, i32, i32, Vec<u8>) {
+    unsafe {
+        let mut streams = 0;
+        let mut coupled = 0;
+        let mut error = 0;
+        let encoder = opus_projection_ambisonics_encoder_create(
+            SAMPLE_RATE,
+            channels as i32,
+            3,
+            &mut streams,
+            &mut coupled,
+            OPUS_APPLICATION_AUDIO as i32,
+            &mut error,
+        );
+        assert_eq!(
+            error, OPUS_OK as i32,
+            "Failed to create a {} channel encoder",
+            channels
+        );
+        let matrix = demixing_matrix(encoder).unwrap();
+
+        let samples = FRAME_SIZE * FRAMES;
+        let mut planar = vec![vec![0i16; samples]; channels];
+        planar[0] = test_util::sine(440.0, SAMPLE_RATE as u32, samples, 0.25);
+        let input = test_util::interleave(&planar);
+
+        let mut packet = vec![0u8; max_packet_size(channels, FrameDuration::Ms20)];
+        let packets = input
+            .chunks_exact(FRAME_SIZE * channels)
+            .map(|frame| {
+                let len = opus_projection_encode(
+                    encoder,
+                    frame.as_ptr(),
+                    FRAME_SIZE as i32,
+                    packet.as_mut_ptr(),
+                    packet.len() as i32,
+                );
+                assert!(len > 0, "Encoding failed: {}", len);
+                packet[..len as usize].to_vec()
+            })
+            .collect();
+        opus_projection_encoder_destroy(encoder);
+        (packets, streams, coupled, matrix)
+    }
+}
+
+/// Energy of each decoded channel
+fn roundtrip(channels: usize) -> Vec<f64> {
+    let (packets, streams, coupled, matrix) = encode_omni(channels);
+    let mut energy = vec![0.0; channels];
+    unsafe {
+        let mut error = 0;
+        let decoder = opus_projection_decoder_create(
+            SAMPLE_RATE,
+            channels as i32,
+            streams,
+            coupled,
+            matrix.as_ptr() as *mut u8,
+            matrix.len() as i32,
+            &mut error,
+        );
+        assert_eq!(error, OPUS_OK as i32, "Failed to create decoder");
+
+        let mut pcm = vec![0i16; FRAME_SIZE * channels];
+        for packet in &packets {
+            let samples = opus_projection_decode(
+                decoder,
+                packet.as_ptr(),
+                packet.len() as i32,
+                pcm.as_mut_ptr(),
+                FRAME_SIZE as i32,
+                0,
+            );
+            assert_eq!(samples, FRAME_SIZE as i32, "Decoding failed");
+            for frame in pcm.chunks_exact(channels) {
+                for (sum, &sample) in energy.iter_mut().zip(frame) {
+                    *sum += f64::from(sample).powi(2);
+                }
+            }
+        }
+        opus_projection_decoder_destroy(decoder);
+    }
+    energy
+}
+
+#[test]
+fn test_omnidirectional_source() {
+    // First and second order, each also with the non-diegetic stereo pair
+    for channels in [4, 6, 9, 11] {
+        let energy = roundtrip(channels);
+        assert!(energy[0] > 0.0, "{} channels: W is silent", channels);
+        for (channel, &e) in energy.iter().enumerate().skip(1) {
+            assert!(
+                energy[0] > 10.0 * e,
+                "{} channels: channel {} has {:.0}, W {:.0}",
+                channels,
+                channel,
+                e,
+                energy[0]
+            );
+        }
+    }
+}
+
+#[test]
+fn test_truncated_matrix() {
+    let (_, streams, coupled, matrix) = encode_omni(4);
+    for len in [0, 1, matrix.len() / 2, matrix.len() - 1] {
+        unsafe {
+            let mut error = OPUS_OK as i32;
+            let decoder = opus_projection_decoder_create(
+                SAMPLE_RATE,
+                4,
+                streams,
+                coupled,
+                matrix.as_ptr() as *mut u8,
+                len as i32,
+                &mut error,
+            );
+            assert_eq!(error, OPUS_BAD_ARG, "{} of {} bytes", len, matrix.len());
+            assert!(decoder.is_null());
+        }
+    }
+}
@@ -0,0 +1,54 @@
+//! Roundtrip quality, guarding against a badly misconfigured encoder or
+//! decoder passing tests that only check lengths
+
+use opus_head_sys::analysis::roundtrip_snr;
+use opus_head_sys::stream::StreamEncoder;
+use opus_head_sys::test_util::{interleave, noise, sine, sweep};
+use opus_head_sys::*;
+
+const SAMPLE_RATE: u32 = 48000;
+/// 2 s
+const SAMPLES: usize = 96000;
+
+/// A chord on the left, a rising sweep over quiet noise on the right
+fn music() -> Vec<i16> {
+    let mix = |parts: &[Vec<i16>]| -> Vec<i16> {
+        (0..SAMPLES)
+            .map(|i| parts.iter().map(|part| part[i]).sum())
+            .collect()
+    };
+    let left = mix(&[
+        sine(220.0, SAMPLE_RATE, SAMPLES, 0.3),
+        sine(277.2, SAMPLE_RATE, SAMPLES, 0.2),
+        sine(329.6, SAMPLE_RATE, SAMPLES, 0.2),
+        sine(880.0, SAMPLE_RATE, SAMPLES, 0.1),
+    ]);
+    let right = mix(&[
+        sweep(200.0, 4000.0, SAMPLE_RATE, SAMPLES, 0.4),
+        noise(1, SAMPLES).iter().map(|s: &i16| s / 16).collect(),
+    ]);
+    interleave(&[left, right])
+}
+
+/// The SNR of `music` through a stereo music encoder at `bitrate`
+fn snr_at(bitrate: i32) -> f32 {
+    let mut encoder = StreamEncoder::music(SAMPLE_RATE as i32, 2).unwrap();
+    let ret =
+        unsafe { opus_encoder_ctl(encoder.as_ptr(), OPUS_SET_BITRATE_REQUEST as i32, bitrate) };
+    assert_eq!(ret, OPUS_OK as i32);
+    roundtrip_snr(&mut encoder, &music()).unwrap()
+}
+
+#[test]
+fn test_64k_stereo_music_resembles_input() {
+    let snr = snr_at(64000);
+    println!("64 kb/s: {:.1} dB", snr);
+    assert!(snr > 10.0, "{:.1} dB at 64 kb/s", snr);
+}
+
+#[test]
+fn test_6k_stereo_music_does_not() {
+    let snr = snr_at(6000);
+    println!("6 kb/s: {:.1} dB", snr);
+    assert!(snr < 5.0, "{:.1} dB at 6 kb/s", snr);
+}
@@ -0,0 +1,244 @@
+//! Packet loss concealment and in-band FEC recovery, with the default
+//! features and no DNN weights
+
+use opus_head_sys::analysis::rms;
+use opus_head_sys::*;
+
+/// A harmonic tone with a syllable-rate envelope, voiced enough for SILK
+fn voice(sample_rate: i32, channels: usize, frames: usize) -> Vec<i16> {
+    use std::f64::consts::TAU;
+    (0..frames)
+        .flat_map(|i| {
+            let t = i as f64 / sample_rate as f64;
+            let tone: f64 = (1..=6)
+                .map(|h| (t * TAU * 150.0 * h as f64).sin() / h as f64)
+                .sum();
+            let envelope = 0.6 + 0.4 * (t * TAU * 3.0).sin();
+            let sample = (tone * envelope * 6000.0) as i16;
+            std::iter::repeat_n(sample, channels)
+        })
+        .collect()
+}
+
+/// Encode `input` in frames of `frame_size` samples per channel
+unsafe fn encode(
+    encoder: *mut OpusEncoder,
+    input: &[i16],
+    channels: usize,
+    frame_size: usize,
+) -> Vec<Vec<u8>> {
+    input
+        .chunks_exact(frame_size * channels)
+        .map(|frame| {
+            let mut packet =
+                vec![0u8; packet::max_packet_size(channels, packet::FrameDuration::Ms20)];
+            let len = opus_encode(
+                encoder,
+                frame.as_ptr(),
+                frame_size as i32,
+                packet.as_mut_ptr(),
+                packet.len() as i32,
+            );
+            assert!(len > 0, "Encoding failed: {}", len);
+            packet.truncate(len as usize);
+            packet
+        })
+        .collect()
+}
+
+unsafe fn create_encoder(sample_rate: i32, channels: usize, application: u32) -> *mut OpusEncoder {
+    let mut error: i32 = 0;
+    let encoder = opus_encoder_create(sample_rate, channels as i32, application as i32, &mut error);
+    assert_eq!(error, OPUS_OK as i32, "Failed to create encoder");
+    encoder
+}
+
+unsafe fn create_decoder(sample_rate: i32, channels: usize) -> *mut OpusDecoder {
+    let mut error: i32 = 0;
+    let decoder = opus_decoder_create(sample_rate, channels as i32, &mut error);
+    assert_eq!(error, OPUS_OK as i32, "Failed to create decoder");
+    decoder
+}
+
+#[test]
+fn test_plc_every_fifth_packet() {
+    const SAMPLE_RATE: i32 = 48000;
+    const CHANNELS: usize = 2;
+    const FRAME_SIZE: usize = 960;
+    const FRAMES: usize = 100;
+
+    let input = voice(SAMPLE_RATE, CHANNELS, FRAME_SIZE * FRAMES);
+    let peak = input
+        .chunks_exact(FRAME_SIZE * CHANNELS)
+        .map(rms)
+        .fold(0.0, f64::max);
+
+    unsafe {
+        let encoder = create_encoder(SAMPLE_RATE, CHANNELS, OPUS_APPLICATION_VOIP);
+        let packets = encode(encoder, &input, CHANNELS, FRAME_SIZE);
+        opus_encoder_destroy(encoder);
+
+        let decoder = create_decoder(SAMPLE_RATE, CHANNELS);
+        let mut pcm = vec![0i16; FRAME_SIZE * CHANNELS];
+        let mut concealed = Vec::new();
+        for (i, packet) in packets.iter().enumerate() {
+            let lost = i % 5 == 4;
+            let ret = if lost {
+                // No data: conceal one frame
+                opus_decode(
+                    decoder,
+                    std::ptr::null(),
+                    0,
+                    pcm.as_mut_ptr(),
+                    FRAME_SIZE as i32,
+                    0,
+                )
+            } else {
+                opus_decode(
+                    decoder,
+                    packet.as_ptr(),
+                    packet.len() as i32,
+                    pcm.as_mut_ptr(),
+                    FRAME_SIZE as i32,
+                    0,
+                )
+            };
+            assert_eq!(ret, FRAME_SIZE as i32, "Frame {} came out short", i);
+            if lost {
+                concealed.push(rms(&pcm));
+            }
+        }
+        opus_decoder_destroy(decoder);
+
+        assert_eq!(concealed.len(), FRAMES / 5);
+        for (i, energy) in concealed.iter().enumerate() {
+            // Concealment fades the last frame out, it doesn't add energy
+            assert!(
+                *energy <= peak * 1.5,
+                "Concealed frame {} has RMS {:.0}, input peaks at {:.0}",
+                i,
+                energy,
+                peak
+            );
+        }
+        // A single loss in voiced speech is extrapolated, not muted
+        assert!(
+            concealed.iter().any(|&e| e > peak * 0.05),
+            "{:?}",
+            concealed
+        );
+    }
+}
+
+#[test]
+fn test_fec_recovers_dropped_packet() {
+    const SAMPLE_RATE: i32 = 16000;
+    const FRAME_SIZE: usize = 320;
+    const FRAMES: usize = 50;
+
+    unsafe {
+        let encoder = create_encoder(SAMPLE_RATE, 1, OPUS_APPLICATION_VOIP);
+        opus_encoder_ctl(encoder, OPUS_SET_BITRATE_REQUEST as i32, 24000);
+        opus_encoder_ctl(encoder, OPUS_SET_INBAND_FEC_REQUEST as i32, 1);
+        opus_encoder_ctl(encoder, OPUS_SET_PACKET_LOSS_PERC_REQUEST as i32, 20);
+        let packets = encode(
+            encoder,
+            &voice(SAMPLE_RATE, 1, FRAME_SIZE * FRAMES),
+            1,
+            FRAME_SIZE,
+        );
+        opus_encoder_destroy(encoder);
+
+        // Drop a packet whose successor carries its LBRR copy
+        let lost = (10..FRAMES - 1)
+            .find(|&i| {
+                let next = &packets[i + 1];
+                opus_packet_has_lbrr(next.as_ptr(), next.len() as i32) == 1
+            })
+            .expect("No packet carries FEC data");
+
+        let decoder = create_decoder(SAMPLE_RATE, 1);
+        let mut pcm = [0i16; FRAME_SIZE];
+        for (i, packet) in packets.iter().enumerate() {
+            if i == lost {
+                let next = &packets[i + 1];
+                let ret = opus_decode(
+                    decoder,
+                    next.as_ptr(),
+                    next.len() as i32,
+                    pcm.as_mut_ptr(),
+                    FRAME_SIZE as i32,
+                    1,
+                );
+                assert_eq!(ret, FRAME_SIZE as i32, "FEC decoding failed");
+                assert!(rms(&pcm) > 0.0, "The recovered frame is silent");
+                continue;
+            }
+            let ret = opus_decode(
+                decoder,
+                packet.as_ptr(),
+                packet.len() as i32,
+                pcm.as_mut_ptr(),
+                FRAME_SIZE as i32,
+                0,
+            );
+            assert_eq!(ret, FRAME_SIZE as i32, "Decoding packet {} failed", i);
+        }
+        opus_decoder_destroy(decoder);
+    }
+}
+
+#[test]
+fn test_fec_without_lbrr_falls_back_to_plc() {
+    const SAMPLE_RATE: i32 = 48000;
+    const CHANNELS: usize = 2;
+    const FRAME_SIZE: usize = 960;
+    const FRAMES: usize = 20;
+    const LOST: usize = 10;
+
+    let input = voice(SAMPLE_RATE, CHANNELS, FRAME_SIZE * FRAMES);
+    let peak = input
+        .chunks_exact(FRAME_SIZE * CHANNELS)
+        .map(rms)
+        .fold(0.0, f64::max);
+
+    unsafe {
+        // CELT has no LBRR. Music at a high bitrate is usually coded with
+        // it, but FEC can pull the mode decision towards SILK, so force it.
+        let encoder = create_encoder(SAMPLE_RATE, CHANNELS, OPUS_APPLICATION_RESTRICTED_LOWDELAY);
+        opus_encoder_ctl(encoder, OPUS_SET_BITRATE_REQUEST as i32, 128000);
+        opus_encoder_ctl(
+            encoder,
+            OPUS_SET_SIGNAL_REQUEST as i32,
+            OPUS_SIGNAL_MUSIC as i32,
+        );
+        opus_encoder_ctl(encoder, OPUS_SET_INBAND_FEC_REQUEST as i32, 1);
+        opus_encoder_ctl(encoder, OPUS_SET_PACKET_LOSS_PERC_REQUEST as i32, 20);
+        let packets = encode(encoder, &input, CHANNELS, FRAME_SIZE);
+        opus_encoder_destroy(encoder);
+
+        let next = &packets[LOST + 1];
+        assert_eq!(opus_packet_has_lbrr(next.as_ptr(), next.len() as i32), 0);
+
+        let decoder = create_decoder(SAMPLE_RATE, CHANNELS);
+        let mut pcm = vec![0i16; FRAME_SIZE * CHANNELS];
+        for (i, packet) in packets.iter().enumerate() {
+            let fec = (i == LOST) as i32;
+            let data = if i == LOST { next } else { packet };
+            let ret = opus_decode(
+                decoder,
+                data.as_ptr(),
+                data.len() as i32,
+                pcm.as_mut_ptr(),
+                FRAME_SIZE as i32,
+                fec,
+            );
+            // Concealed instead, at full length and without an error
+            assert_eq!(ret, FRAME_SIZE as i32, "Decoding frame {} failed", i);
+            if i == LOST {
+                assert!(rms(&pcm) <= peak * 1.5);
+            }
+        }
+        opus_decoder_destroy(decoder);
+    }
+}
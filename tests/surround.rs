@@ -0,0 +1,120 @@
+//! 5.1 surround through the multistream API, checking every channel comes
+//! out where it went in
+
+mod util;
+
+use opus_head_sys::packet::{max_packet_size, FrameDuration};
+use opus_head_sys::*;
+use util::goertzel;
+
+const SAMPLE_RATE: i32 = 48000;
+const CHANNELS: usize = 6;
+/// 20 ms
+const FRAME_SIZE: usize = 960;
+const FRAMES: usize = 50;
+
+/// A tone per channel in Vorbis order: front left, center, front right,
+/// rear left, rear right and LFE, which only carries low frequencies
+const TONES: [f64; CHANNELS] = [440.0, 620.0, 820.0, 1030.0, 1270.0, 70.0];
+
+fn input() -> Vec<i16> {
+    let channels: Vec<Vec<i16>> = TONES
+        .iter()
+        .map(|&tone| test_util::sine(tone, SAMPLE_RATE as u32, FRAME_SIZE * FRAMES, 0.25))
+        .collect();
+    test_util::interleave(&channels)
+}
+
+#[test]
+fn test_surround_channels_roundtrip() {
+    let input = input();
+    let mut output = Vec::with_capacity(input.len());
+
+    unsafe {
+        let mut streams: i32 = 0;
+        let mut coupled_streams: i32 = 0;
+        let mut mapping = [0u8; CHANNELS];
+        let mut error: i32 = 0;
+        let encoder = opus_multistream_surround_encoder_create(
+            SAMPLE_RATE,
+            CHANNELS as i32,
+            1,
+            &mut streams,
+            &mut coupled_streams,
+            mapping.as_mut_ptr(),
+            OPUS_APPLICATION_AUDIO as i32,
+            &mut error,
+        );
+        assert_eq!(error, OPUS_OK as i32, "Failed to create surround encoder");
+        // Family 1 codes 5.1 as L/R and RL/RR pairs, C and LFE alone
+        assert_eq!((streams, coupled_streams), (4, 2));
+        opus_multistream_encoder_ctl(encoder, OPUS_SET_BITRATE_REQUEST as i32, 256000);
+
+        let decoder = opus_multistream_decoder_create(
+            SAMPLE_RATE,
+            CHANNELS as i32,
+            streams,
+            coupled_streams,
+            mapping.as_ptr(),
+            &mut error,
+        );
+        assert_eq!(
+            error, OPUS_OK as i32,
+            "Failed to create multistream decoder"
+        );
+
+        let mut packet = vec![0u8; max_packet_size(CHANNELS, FrameDuration::Ms20)];
+        let mut pcm = vec![0i16; FRAME_SIZE * CHANNELS];
+        for frame in input.chunks_exact(FRAME_SIZE * CHANNELS) {
+            let len = opus_multistream_encode(
+                encoder,
+                frame.as_ptr(),
+                FRAME_SIZE as i32,
+                packet.as_mut_ptr(),
+                packet.len() as i32,
+            );
+            assert!(len > 0, "Encoding failed: {}", len);
+            let ret = opus_multistream_decode(
+                decoder,
+                packet.as_ptr(),
+                len,
+                pcm.as_mut_ptr(),
+                FRAME_SIZE as i32,
+                0,
+            );
+            assert_eq!(ret, FRAME_SIZE as i32, "Decoding failed");
+            output.extend_from_slice(&pcm);
+        }
+        opus_multistream_decoder_destroy(decoder);
+        opus_multistream_encoder_destroy(encoder);
+    }
+
+    // Skip the first 200 ms, while the codec delay and start-up settle
+    let start = FRAME_SIZE * 10 * CHANNELS;
+    for (channel, &tone) in TONES.iter().enumerate() {
+        let samples = test_util::channel(&output[start..], CHANNELS, channel);
+        let own = goertzel(&samples, SAMPLE_RATE as u32, tone);
+        assert!(
+            own > 0.005,
+            "Channel {} lost its {} Hz tone: {}",
+            channel,
+            tone,
+            own
+        );
+        for (other, &other_tone) in TONES.iter().enumerate() {
+            if other == channel {
+                continue;
+            }
+            let leaked = goertzel(&samples, SAMPLE_RATE as u32, other_tone);
+            assert!(
+                leaked * 100.0 < own,
+                "Channel {} carries channel {}'s {} Hz tone ({} vs {})",
+                channel,
+                other,
+                other_tone,
+                leaked,
+                own
+            );
+        }
+    }
+}
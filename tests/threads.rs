@@ -0,0 +1,113 @@
+//! Independent encoders and decoders running concurrently, one pair per
+//! thread as a server handling many streams would
+
+use opus_head_sys::decoder::Decoder;
+use opus_head_sys::stream::StreamEncoder;
+use opus_head_sys::*;
+use std::thread;
+
+const SAMPLE_RATE: i32 = 48000;
+const CHANNELS: usize = 2;
+/// 20 ms
+const FRAME_SIZE: usize = 960;
+const FRAMES: usize = 200;
+const THREADS: usize = 8;
+
+fn assert_send<T: Send>() {}
+
+/// A chirp on the left and noise on the right, the same for every thread
+fn input() -> Vec<i16> {
+    let samples = FRAME_SIZE * FRAMES;
+    test_util::interleave(&[
+        test_util::sweep(200.0, 4000.0, SAMPLE_RATE as u32, samples, 0.2),
+        test_util::noise(1, samples),
+    ])
+}
+
+fn create() -> (StreamEncoder, Decoder) {
+    let encoder = StreamEncoder::new(
+        SAMPLE_RATE,
+        CHANNELS,
+        OPUS_APPLICATION_AUDIO as i32,
+        FRAME_SIZE,
+    )
+    .unwrap();
+    unsafe {
+        opus_encoder_ctl(encoder.as_ptr(), OPUS_SET_BITRATE_REQUEST as i32, 64000);
+    }
+    (encoder, Decoder::new(SAMPLE_RATE, CHANNELS).unwrap())
+}
+
+unsafe fn final_range_of_encoder(encoder: *mut OpusEncoder) -> u32 {
+    let mut range: u32 = 0;
+    let ret = opus_encoder_ctl(
+        encoder,
+        OPUS_GET_FINAL_RANGE_REQUEST as i32,
+        &mut range as *mut u32,
+    );
+    assert_eq!(ret, OPUS_OK as i32);
+    range
+}
+
+unsafe fn final_range_of_decoder(decoder: *mut OpusDecoder) -> u32 {
+    let mut range: u32 = 0;
+    let ret = opus_decoder_ctl(
+        decoder,
+        OPUS_GET_FINAL_RANGE_REQUEST as i32,
+        &mut range as *mut u32,
+    );
+    assert_eq!(ret, OPUS_OK as i32);
+    range
+}
+
+/// Encode and decode `input`, returning the final range of every packet
+fn run(mut encoder: StreamEncoder, mut decoder: Decoder, input: &[i16]) -> Vec<u32> {
+    let raw = encoder.as_ptr();
+    let mut pcm = vec![0i16; FRAME_SIZE * CHANNELS];
+    let mut ranges = Vec::with_capacity(FRAMES);
+    encoder
+        .push_with(input, |packet| {
+            let range = unsafe { final_range_of_encoder(raw) };
+            let samples = decoder.decode(Some(packet), &mut pcm, false).unwrap();
+            assert_eq!(samples, FRAME_SIZE);
+            assert_eq!(
+                unsafe { final_range_of_decoder(decoder.as_ptr()) },
+                range,
+                "Decoder disagrees with the encoder at packet {}",
+                ranges.len()
+            );
+            ranges.push(range);
+        })
+        .unwrap();
+    ranges
+}
+
+#[test]
+fn test_concurrent_streams() {
+    assert_send::<StreamEncoder>();
+    assert_send::<Decoder>();
+
+    let input = input();
+    let (encoder, decoder) = create();
+    let expected = run(encoder, decoder, &input);
+    assert_eq!(expected.len(), FRAMES);
+
+    let input = std::sync::Arc::new(input);
+    let handles: Vec<_> = (0..THREADS)
+        .map(|_| {
+            // Created here, used on the thread
+            let (encoder, decoder) = create();
+            let input = input.clone();
+            thread::spawn(move || run(encoder, decoder, &input))
+        })
+        .collect();
+
+    for (i, handle) in handles.into_iter().enumerate() {
+        let ranges = handle.join().expect("Thread panicked");
+        assert_eq!(
+            ranges, expected,
+            "Thread {} produced a different bitstream",
+            i
+        );
+    }
+}
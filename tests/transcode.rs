@@ -0,0 +1,71 @@
+//! Runs the core of the `transcode` example on a generated tone
+
+#![cfg(feature = "std")]
+
+#[allow(dead_code)]
+#[path = "../examples/transcode/pipeline.rs"]
+mod pipeline;
+
+use pipeline::{Wav, FRAME_MS};
+
+/// A 440 Hz tone, written out and read back as a WAV file
+fn sine_wav(sample_rate: u32, channels: u16, frames: usize) -> Wav {
+    let samples = (0..frames)
+        .flat_map(|i| {
+            let t = i as f64 / sample_rate as f64;
+            let s = ((t * 440.0 * std::f64::consts::TAU).sin() * 8000.0) as i16;
+            std::iter::repeat_n(s, channels as usize)
+        })
+        .collect();
+    let wav = Wav {
+        sample_rate,
+        channels,
+        samples,
+    };
+    pipeline::read_wav(&pipeline::write_wav(&wav)).unwrap()
+}
+
+#[test]
+fn test_duration_matches_input() {
+    for sample_rate in [8000, 12000, 16000, 24000, 48000] {
+        for channels in [1, 2] {
+            // Not a whole number of frames
+            let input = sine_wav(sample_rate, channels, sample_rate as usize * 3 / 2 + 7);
+            let result = pipeline::transcode(&input, 32000).unwrap();
+
+            let frame_size = sample_rate as usize * FRAME_MS / 1000;
+            let decoded = &result.decoded;
+            assert_eq!(decoded.sample_rate, sample_rate);
+            assert_eq!(decoded.channels, channels);
+            assert!(
+                decoded.frames() >= input.frames()
+                    && decoded.frames() - input.frames() < frame_size,
+                "{} Hz, {} ch: decoded {} samples per channel from {}",
+                sample_rate,
+                channels,
+                decoded.frames(),
+                input.frames()
+            );
+            assert_eq!(result.packets.len(), input.frames().div_ceil(frame_size));
+        }
+    }
+}
+
+#[test]
+fn test_packet_file() {
+    let input = sine_wav(48000, 2, 48000);
+    let result = pipeline::transcode(&input, 64000).unwrap();
+    assert!(result.bitrate() > 32000.0 && result.bitrate() < 96000.0);
+
+    let file = pipeline::write_packets(&result.packets);
+    let mut rest = &file[..];
+    for packet in &result.packets {
+        let len = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+        let final_range = u32::from_be_bytes(rest[4..8].try_into().unwrap());
+        assert_eq!(&rest[8..8 + len], &packet.data[..]);
+        assert_eq!(final_range, packet.final_range);
+        assert_ne!(final_range, 0);
+        rest = &rest[8 + len..];
+    }
+    assert!(rest.is_empty());
+}
@@ -0,0 +1,44 @@
+//! Helpers shared by the unit tests in `src/`, the integration tests and
+//! the benchmarks in `benches/`, which include this file with `#[path]`
+
+// Not every includer uses every helper
+#![allow(dead_code)]
+
+/// Helper function to load DNN weights if available
+pub fn load_dnn_weights() -> Option<Vec<u8>> {
+    use std::path::PathBuf;
+
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let model_dir = manifest_dir.join("target").join("model");
+
+    let weights_path = std::fs::read_dir(&model_dir)
+        .ok()?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .find(|p| {
+            p.file_name()
+                .and_then(|n| n.to_str())
+                .map(|n| n.starts_with("opus_data-") && n.ends_with(".bin"))
+                .unwrap_or(false)
+        })?;
+
+    std::fs::read(&weights_path).ok()
+}
+
+/// Power of `freq` in `samples` with the Goertzel algorithm, normalized
+/// so a full-scale sine at `freq` gives about 0.25 whatever the length.
+///
+/// Cheaper than an FFT when only a few frequencies matter, e.g. to check
+/// which tone ended up in which decoded channel. `freq` needn't fall on a
+/// DFT bin.
+pub fn goertzel(samples: &[i16], sample_rate: u32, freq: f64) -> f64 {
+    let coeff = 2.0 * (std::f64::consts::TAU * freq / sample_rate as f64).cos();
+    let (mut s1, mut s2) = (0.0, 0.0);
+    for &sample in samples {
+        let s0 = sample as f64 / 32768.0 + coeff * s1 - s2;
+        s2 = s1;
+        s1 = s0;
+    }
+    let power = s1 * s1 + s2 * s2 - coeff * s1 * s2;
+    power / (samples.len() as f64).powi(2)
+}
@@ -180,49 +180,46 @@ def download_opus_model(opus_dir, model_hash):
             print(f"  Warning: {download_script} not found")
 
 
-def patch_cmake_for_runtime_weights(opus_dir):
+def apply_patches(opus_dir):
     """
-    Apply the OPUS_RUNTIME_WEIGHTS patch to Opus CMakeLists.txt.
+    Apply every patch in patches/ to the Opus source tree, in file name order.
     
-    This patch adds a CMake option that:
-    1. Defines USE_WEIGHTS_FILE at compile time
-    2. Filters out the large *_data.c files from the build (~83MB)
-    
-    The patch file is located at patches/0001-Add-OPUS_RUNTIME_WEIGHTS-cmake-option.patch
-    and can be submitted upstream to the Opus project.
+    The patches add the OPUS_RUNTIME_WEIGHTS and OPUS_PRESUME_DOTPROD CMake
+    options and carry fixes that can be submitted upstream to the Opus project.
+    Patches that are already applied are skipped.
     """
-    cmake_file = opus_dir / "CMakeLists.txt"
-    if not cmake_file.exists():
-        print("  Warning: CMakeLists.txt not found")
-        return False
-    
-    content = cmake_file.read_text()
-    
-    # Check if already patched
-    if "OPUS_RUNTIME_WEIGHTS" in content:
-        print("  CMakeLists.txt already patched")
-        return True
-    
-    # Find the patch file
     project_dir = Path(__file__).parent.resolve()
-    patch_file = project_dir / "patches" / "0001-Add-OPUS_RUNTIME_WEIGHTS-cmake-option.patch"
+    patch_files = sorted((project_dir / "patches").glob("*.patch"))
     
-    if not patch_file.exists():
-        print(f"  Error: Patch file not found at {patch_file}")
+    if not patch_files:
+        print("  Warning: no patch files found")
         return False
     
-    # Apply the patch using git apply
-    result = run_command(
-        ["git", "apply", str(patch_file)],
-        cwd=opus_dir,
-        check=False
-    )
-    if result.returncode == 0:
-        print("  Applied OPUS_RUNTIME_WEIGHTS patch")
-        return True
-    else:
-        print(f"  Error: git apply failed: {result.stderr}")
-        return False
+    ok = True
+    for patch_file in patch_files:
+        # Check if already patched
+        already_applied = run_command(
+            ["git", "apply", "--check", "--reverse", str(patch_file)],
+            cwd=opus_dir,
+            check=False
+        )
+        if already_applied.returncode == 0:
+            print(f"  {patch_file.name} already applied")
+            continue
+        
+        # Apply the patch using git apply
+        result = run_command(
+            ["git", "apply", str(patch_file)],
+            cwd=opus_dir,
+            check=False
+        )
+        if result.returncode == 0:
+            print(f"  Applied {patch_file.name}")
+        else:
+            print(f"  Error: git apply {patch_file.name} failed: {result.stderr}")
+            ok = False
+    
+    return ok
 
 
 def create_weight_stub_files(opus_dir):
@@ -330,6 +327,9 @@ def generate_bindings(opus_dir, output_dir):
             # Add module-level attribute to suppress broken doc link warnings
             # (Doxygen @param [in]/[out] syntax is misinterpreted as Rust doc links)
             "--raw-line", "#![allow(rustdoc::broken_intra_doc_links)]",
+            # core::ffi types, so the bindings build without std
+            "--use-core",
+            "--ctypes-prefix", "::core::ffi",
             "--",
             f"-I{include_dir}",
         ]
@@ -438,9 +438,9 @@ def main():
     except Exception as e:
         print(f"  Warning: model download failed (may be OK): {e}")
     
-    # Step 5: Patch CMake for OPUS_RUNTIME_WEIGHTS option
-    print("\n[5/7] Patching CMake for runtime weights support...")
-    patch_cmake_for_runtime_weights(opus_dir)
+    # Step 5: Apply patches (OPUS_RUNTIME_WEIGHTS, OPUS_PRESUME_DOTPROD, fixes)
+    print("\n[5/7] Applying patches...")
+    apply_patches(opus_dir)
     
     # Step 6: Strip weight data from source files
     print("\n[6/7] Stripping weight data from source files...")
@@ -478,12 +478,17 @@ Updated files:
   - {vendored_dir / "opus"} (source code)
   - {src_dir / "bindings.rs"}
 
+The build rejects a vendored tree that isn't vendor::PINNED. If this is a
+new commit, set PINNED in build/vendor.rs to:
+  commit: {commit_info["sha"]}
+  date: {commit_info["date"]}
+
 To build and test:
   cargo build
   cargo test
 
 To regenerate bindings manually:
-  bindgen vendored/opus/include/opus_all.h -o src/bindings.rs -- -I vendored/opus/include
+  bindgen vendored/opus/include/opus_all.h -o src/bindings.rs --use-core --ctypes-prefix ::core::ffi -- -I vendored/opus/include
 """)
 
 
@@ -84,6 +84,7 @@ elseif(OPUS_CPU_ARM AND NOT OPUS_DISABLE_INTRINSICS)
     option(OPUS_USE_NEON "Option to enable NEON" ON)
     option(OPUS_MAY_HAVE_NEON "Does runtime check for neon support" ON)
     option(OPUS_PRESUME_NEON "Assume target CPU has NEON support" OFF)
+    option(OPUS_PRESUME_DOTPROD "Assume target CPU has Aarch64 DOTPROD support" OFF)
     if(OPUS_CPU_ARM_MS OR CMAKE_SYSTEM_PROCESSOR MATCHES "aarch64")
       set(OPUS_PRESUME_NEON ON)
     elseif(CMAKE_SYSTEM_NAME MATCHES "iOS")
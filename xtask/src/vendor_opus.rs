@@ -0,0 +1,85 @@
+//! `cargo vendor-opus`: download the pinned Opus sources into vendored/
+//!
+//! Usage: `cargo vendor-opus [--force] [--print-sha256]`
+//!
+//! * `--force` re-downloads even if vendored/OPUS_VERSION already has the
+//!   pinned commit
+//! * `--print-sha256` only downloads the pinned commit's tarball and prints
+//!   its checksum, for updating `vendor::PINNED` when bumping Opus
+//!
+//! The bindings in src/bindings.rs are not regenerated; run
+//! `python vendor_opus.py --bindings-only` or bindgen afterwards if the
+//! public headers changed.
+
+#[allow(dead_code)]
+#[path = "../../build/patch.rs"]
+mod patch;
+#[path = "../../build/vendor.rs"]
+mod vendor;
+#[allow(dead_code)]
+#[path = "../../build/version.rs"]
+mod version;
+
+use std::path::Path;
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let flag = |name: &str| args.iter().any(|arg| arg == name);
+    if let Some(unknown) = args
+        .iter()
+        .find(|arg| !["--force", "--print-sha256"].contains(&arg.as_str()))
+    {
+        eprintln!("Unknown argument {}", unknown);
+        eprintln!("Usage: cargo vendor-opus [--force] [--print-sha256]");
+        return ExitCode::FAILURE;
+    }
+
+    let result = if flag("--print-sha256") {
+        print_sha256()
+    } else {
+        vendor_opus(flag("--force"))
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn fetch(url: &str) -> Result<Vec<u8>, String> {
+    eprintln!("Downloading {}", url);
+    vendor::download(url)
+}
+
+fn print_sha256() -> Result<(), String> {
+    let tarball = fetch(&vendor::tarball_url(vendor::PINNED.commit))?;
+    println!("{}", vendor::sha256(&tarball));
+    Ok(())
+}
+
+fn vendor_opus(force: bool) -> Result<(), String> {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .ok_or("xtask has no parent directory")?;
+    let vendored = root.join("vendored");
+    let pin = &vendor::PINNED;
+
+    let current = std::fs::read_to_string(vendored.join("OPUS_VERSION")).unwrap_or_default();
+    if !force
+        && vendor::vendored_commit(&current) == Some(pin.commit)
+        && vendored.join("opus").is_dir()
+    {
+        eprintln!(
+            "vendored/opus is already at {}, use --force to download it again",
+            pin.commit
+        );
+        return Ok(());
+    }
+
+    vendor::vendor(pin, &vendored, &root.join("patches"), fetch)?;
+    eprintln!("Vendored Opus {} ({})", pin.commit, pin.date);
+    Ok(())
+}